@@ -1,10 +1,16 @@
 #![cfg_attr(not(feature = "std"), no_std)]
+#![allow(non_camel_case_types)]
+#![allow(clippy::missing_safety_doc)]
 //! Clean-room, header-free ABI surfaces for VST3 hosting.
 //! Ph1: FUnknown/IPluginFactory
 //! Ph2: ClassInfo
 //! Ph3: IPluginBase/IComponent/IAudioProcessor + 32f processing
 //! Ph4: 64f processing, BusInfo (read-only)
 //! Ph5: setBusArrangements + ProcessData param/event pointers
+//! Ph6: IEditController/IBStream/IComponentHandler, component state, well-known IIDs
+//! Ph8: IEventList/IParameterChanges/IParamValueQueue, Event, IMidiMapping
+//! Ph9: IPlugView/IPlugFrame, ViewRect, platform type strings
+//! Ph10: ProcessContext, appended to ProcessData as in the SDK
 
 use core::ffi::c_void;
 use core::ptr::NonNull;
@@ -34,6 +40,29 @@ impl Tuid {
     pub const fn new(bytes: [u8; 16]) -> Self {
         Self(bytes)
     }
+
+    /// Build from the four 32-bit words used by the SDK's `INLINE_UID` (non-COM byte order).
+    pub const fn from_u32s(l1: u32, l2: u32, l3: u32, l4: u32) -> Self {
+        let (a, b, c, d) = (
+            l1.to_be_bytes(),
+            l2.to_be_bytes(),
+            l3.to_be_bytes(),
+            l4.to_be_bytes(),
+        );
+        Self([
+            a[0], a[1], a[2], a[3], b[0], b[1], b[2], b[3], c[0], c[1], c[2], c[3], d[0], d[1],
+            d[2], d[3],
+        ])
+    }
+}
+
+impl core::fmt::Debug for Tuid {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for b in &self.0 {
+            write!(f, "{:02X}", b)?;
+        }
+        Ok(())
+    }
 }
 
 #[macro_export]
@@ -41,6 +70,71 @@ macro_rules! tuid {
     ($($b:expr),* $(,)?) => { $crate::Tuid::new([ $($b as u8),* ]) };
 }
 
+// ===== Well-known interface IDs (Ph6) =========================================
+pub mod iids {
+    use super::Tuid;
+
+    pub const FUNKNOWN: Tuid = Tuid::from_u32s(0x00000000, 0x00000000, 0xC0000000, 0x00000046);
+    pub const IPLUGIN_BASE: Tuid = Tuid::from_u32s(0x22888DDB, 0x156E45AE, 0x8358B348, 0x08190625);
+    pub const IPLUGIN_FACTORY: Tuid =
+        Tuid::from_u32s(0x7A4D811C, 0x52114A1F, 0xAED9D2EE, 0x0B43BF9F);
+    pub const IPLUGIN_FACTORY2: Tuid =
+        Tuid::from_u32s(0x0007B650, 0xF24B4C0B, 0xA464EDB9, 0xF00B2ABB);
+    pub const ICOMPONENT: Tuid = Tuid::from_u32s(0xE831FF31, 0xF2D54301, 0x928EBBEE, 0x25697802);
+    pub const IAUDIO_PROCESSOR: Tuid =
+        Tuid::from_u32s(0x42043F99, 0xB7DA453C, 0xA569E79D, 0x9AAEC33D);
+    pub const IEDIT_CONTROLLER: Tuid =
+        Tuid::from_u32s(0xDCD7BBE3, 0x7742448D, 0xA874AACC, 0x979C759E);
+    pub const IBSTREAM: Tuid = Tuid::from_u32s(0xC3BF6EA2, 0x30994752, 0x9B6BF990, 0x1EE33E9B);
+    pub const ICOMPONENT_HANDLER: Tuid =
+        Tuid::from_u32s(0x93A0BEA3, 0x0BD045DB, 0x8E890B0C, 0xC1E46AC6);
+    pub const IEVENT_LIST: Tuid = Tuid::from_u32s(0x3A2C4214, 0x346349FE, 0xB2C4F397, 0xB9695A44);
+    pub const IPARAM_VALUE_QUEUE: Tuid =
+        Tuid::from_u32s(0x01263A18, 0xED074F6F, 0x98C9D356, 0x4686F9BA);
+    pub const IPARAMETER_CHANGES: Tuid =
+        Tuid::from_u32s(0xA4779663, 0x0BB64A56, 0xB44384A8, 0x466FEB9D);
+    pub const IMIDI_MAPPING: Tuid = Tuid::from_u32s(0xDF0FF9F7, 0x49B74669, 0xB63AB732, 0x7ADBF5E5);
+    pub const IPLUG_VIEW: Tuid = Tuid::from_u32s(0x5BC32507, 0xD06049EA, 0xA6151B52, 0x2B755B29);
+    pub const IPLUG_FRAME: Tuid = Tuid::from_u32s(0x367FAF01, 0xAFA94693, 0x8D4DA2A0, 0xED0882A3);
+    pub const IUNIT_INFO: Tuid = Tuid::from_u32s(0x3D4BD6B5, 0x913A4FD2, 0xA886E768, 0xA5EB92C1);
+    pub const INOTE_EXPRESSION_CONTROLLER: Tuid =
+        Tuid::from_u32s(0xB7F8F859, 0x41234872, 0x91169581, 0x4F3721A3);
+
+    /// Every IID above under its SDK interface name.
+    pub const REGISTRY: &[(&str, Tuid)] = &[
+        ("FUnknown", FUNKNOWN),
+        ("IPluginBase", IPLUGIN_BASE),
+        ("IPluginFactory", IPLUGIN_FACTORY),
+        ("IPluginFactory2", IPLUGIN_FACTORY2),
+        ("IComponent", ICOMPONENT),
+        ("IAudioProcessor", IAUDIO_PROCESSOR),
+        ("IEditController", IEDIT_CONTROLLER),
+        ("IBStream", IBSTREAM),
+        ("IComponentHandler", ICOMPONENT_HANDLER),
+        ("IEventList", IEVENT_LIST),
+        ("IParamValueQueue", IPARAM_VALUE_QUEUE),
+        ("IParameterChanges", IPARAMETER_CHANGES),
+        ("IMidiMapping", IMIDI_MAPPING),
+        ("IPlugView", IPLUG_VIEW),
+        ("IPlugFrame", IPLUG_FRAME),
+        ("IUnitInfo", IUNIT_INFO),
+        ("INoteExpressionController", INOTE_EXPRESSION_CONTROLLER),
+    ];
+
+    /// Look an interface up by name, ignoring ASCII case.
+    pub fn by_name(name: &str) -> Option<Tuid> {
+        REGISTRY
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|&(_, iid)| iid)
+    }
+
+    /// Name of a registered IID.
+    pub fn name_of(iid: &Tuid) -> Option<&'static str> {
+        REGISTRY.iter().find(|(_, i)| i == iid).map(|&(n, _)| n)
+    }
+}
+
 // ===== FUnknown ===============================================================
 #[repr(C)]
 pub struct FUnknownVTable {
@@ -66,9 +160,11 @@ impl FUnknown {
     pub unsafe fn add_ref(&mut self) -> u32 {
         ((*self.vtbl).add_ref)(self)
     }
+    /// Takes the pointer, not `&mut self`: the last release frees the object, which
+    /// must not happen while a reference to it is live.
     #[inline]
-    pub unsafe fn release(&mut self) -> u32 {
-        ((*self.vtbl).release)(self)
+    pub unsafe fn release(this: *mut Self) -> u32 {
+        ((*(*this).vtbl).release)(this)
     }
 }
 
@@ -79,6 +175,15 @@ pub mod classinfo_consts {
     pub const K_VENDOR_SIZE: usize = 64;
     pub const K_VERSION_SIZE: usize = 64;
     pub const K_SUBCATS_SIZE: usize = 128;
+
+    /// `PClassInfo::cardinality` of a class with no limit on live instances
+    /// (kManyInstances). Any other value limits them.
+    pub const K_MANY_INSTANCES: i32 = 0x7FFF_FFFF;
+
+    /// `PClassInfo::category` of audio processor components (kVstAudioEffectClass).
+    pub const K_VST_AUDIO_EFFECT_CLASS: &str = "Audio Module Class";
+    /// `PClassInfo::category` of separate edit controllers (kVstComponentControllerClass).
+    pub const K_VST_COMPONENT_CONTROLLER_CLASS: &str = "Component Controller Class";
 }
 
 #[repr(C)]
@@ -89,6 +194,17 @@ pub struct PClassInfo {
     pub name: [i8; classinfo_consts::K_NAME_SIZE],
 }
 
+bitflags::bitflags! {
+    /// `PClassInfo2::class_flags` (ComponentFlags).
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct ClassFlags: u32 {
+        /// Processor and controller may run on different machines/processes.
+        const DISTRIBUTABLE = 1 << 0;
+        /// Usable in a host's simple (non-expert) mode.
+        const SIMPLE_MODE_SUPPORTED = 1 << 1;
+    }
+}
+
 #[repr(C)]
 pub struct PClassInfo2 {
     pub cid: [i8; 16],
@@ -102,6 +218,36 @@ pub struct PClassInfo2 {
     pub sdk_version: [i8; classinfo_consts::K_VERSION_SIZE],
 }
 
+// ===== Factory info ===========================================================
+pub mod factory_info_consts {
+    pub const K_NAME_SIZE: usize = 64;
+    pub const K_URL_SIZE: usize = 256;
+    pub const K_EMAIL_SIZE: usize = 128;
+}
+
+bitflags::bitflags! {
+    /// `PFactoryInfo::flags` (FactoryFlags).
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct FactoryFlags: i32 {
+        /// Classes may be discarded (re-read) by the host at any time.
+        const CLASSES_DISCARDABLE = 1 << 0;
+        /// Class names are license-checked at instantiation.
+        const LICENSE_CHECK = 1 << 1;
+        /// Components must not be unloaded until the process exits.
+        const COMPONENT_NON_DISCARDABLE = 1 << 3;
+        /// Strings are UTF-16 (only meaningful for IPluginFactory3).
+        const UNICODE = 1 << 4;
+    }
+}
+
+#[repr(C)]
+pub struct PFactoryInfo {
+    pub vendor: [i8; factory_info_consts::K_NAME_SIZE],
+    pub url: [i8; factory_info_consts::K_URL_SIZE],
+    pub email: [i8; factory_info_consts::K_EMAIL_SIZE],
+    pub flags: int32,
+}
+
 // ===== IPluginFactory =========================================================
 #[repr(C)]
 pub struct IPluginFactoryVTable {
@@ -116,7 +262,7 @@ pub struct IPluginFactoryVTable {
 
     // v1
     pub get_factory_info:
-        unsafe extern "C" fn(this_: *mut IPluginFactory, info: *mut c_void) -> tresult, // reserved
+        unsafe extern "C" fn(this_: *mut IPluginFactory, info: *mut PFactoryInfo) -> tresult,
     pub count_classes: unsafe extern "C" fn(this_: *mut IPluginFactory) -> int32,
     pub get_class_info: unsafe extern "C" fn(
         this_: *mut IPluginFactory,
@@ -138,6 +284,10 @@ pub struct IPluginFactory {
     pub vtbl: *const IPluginFactoryVTable,
 }
 impl IPluginFactory {
+    #[inline]
+    pub unsafe fn get_factory_info(&mut self, out: *mut PFactoryInfo) -> tresult {
+        ((*self.vtbl).get_factory_info)(self, out)
+    }
     #[inline]
     pub unsafe fn count_classes(&mut self) -> int32 {
         ((*self.vtbl).count_classes)(self)
@@ -157,6 +307,30 @@ impl IPluginFactory {
     }
 }
 
+// ===== IPluginFactory2 (Ph7): extended class info ============================
+#[repr(C)]
+pub struct IPluginFactory2VTable {
+    /// IPluginFactory methods, unchanged.
+    pub base: IPluginFactoryVTable,
+    pub get_class_info2: unsafe extern "C" fn(
+        this_: *mut IPluginFactory2,
+        index: int32,
+        info: *mut PClassInfo2,
+    ) -> tresult,
+}
+
+/// Obtained from an `IPluginFactory` via `queryInterface(IPLUGIN_FACTORY2)`.
+#[repr(C)]
+pub struct IPluginFactory2 {
+    pub vtbl: *const IPluginFactory2VTable,
+}
+impl IPluginFactory2 {
+    #[inline]
+    pub unsafe fn get_class_info2(&mut self, index: int32, out: *mut PClassInfo2) -> tresult {
+        ((*self.vtbl).get_class_info2)(self, index, out)
+    }
+}
+
 pub type GetPluginFactoryProc = unsafe extern "C" fn() -> *mut IPluginFactory;
 
 #[derive(Copy, Clone)]
@@ -165,8 +339,11 @@ impl FactoryHandle {
     pub unsafe fn new(ptr: *mut IPluginFactory) -> Option<Self> {
         NonNull::new(ptr).map(Self)
     }
-    pub fn as_mut(&self) -> &mut IPluginFactory {
-        unsafe { self.0.as_ptr().as_mut().unwrap() }
+    /// The factory pointer. Calls go through it rather than through a `&mut` that
+    /// every copy of the handle could hand out at the same time.
+    #[inline]
+    pub fn as_ptr(&self) -> *mut IPluginFactory {
+        self.0.as_ptr()
     }
 }
 unsafe impl Send for FactoryHandle {}
@@ -179,12 +356,116 @@ pub mod process_consts {
 
     pub const PROCESS_MODE_REALTIME: i32 = 0;
     pub const PROCESS_MODE_PREFETCH: i32 = 1;
+    pub const PROCESS_MODE_OFFLINE: i32 = 2;
+
+    /// `ProcessSetup::flags`: the host keeps processing past the end of its input so
+    /// the plugin's tail can ring out.
+    pub const SETUP_FLAG_TAIL: i32 = 1 << 0;
+
+    /// `getTailSamples` results.
+    pub const NO_TAIL: u32 = 0;
+    pub const INFINITE_TAIL: u32 = u32::MAX;
+}
+
+/// `Vst::SpeakerArrangement` bits (`kSpeakerL` …) and the common arrangements built
+/// from them (`kStereo` …). A bus's channels are its speakers in bit order.
+pub mod speaker_arr {
+    pub type SpeakerArrangement = u64;
+
+    pub const SPEAKER_L: u64 = 1 << 0;
+    pub const SPEAKER_R: u64 = 1 << 1;
+    pub const SPEAKER_C: u64 = 1 << 2;
+    pub const SPEAKER_LFE: u64 = 1 << 3;
+    pub const SPEAKER_LS: u64 = 1 << 4;
+    pub const SPEAKER_RS: u64 = 1 << 5;
+    pub const SPEAKER_LC: u64 = 1 << 6;
+    pub const SPEAKER_RC: u64 = 1 << 7;
+    pub const SPEAKER_S: u64 = 1 << 8;
+    pub const SPEAKER_SL: u64 = 1 << 9;
+    pub const SPEAKER_SR: u64 = 1 << 10;
+    pub const SPEAKER_TC: u64 = 1 << 11;
+    pub const SPEAKER_TFL: u64 = 1 << 12;
+    pub const SPEAKER_TFC: u64 = 1 << 13;
+    pub const SPEAKER_TFR: u64 = 1 << 14;
+    pub const SPEAKER_TRL: u64 = 1 << 15;
+    pub const SPEAKER_TRC: u64 = 1 << 16;
+    pub const SPEAKER_TRR: u64 = 1 << 17;
+    pub const SPEAKER_LFE2: u64 = 1 << 18;
+    pub const SPEAKER_M: u64 = 1 << 19;
+    /// Ambisonic channels 0–3; 4–15 are `SPEAKER_ACN4 << (n - 4)`.
+    pub const SPEAKER_ACN0: u64 = 1 << 20;
+    pub const SPEAKER_ACN1: u64 = 1 << 21;
+    pub const SPEAKER_ACN2: u64 = 1 << 22;
+    pub const SPEAKER_ACN3: u64 = 1 << 23;
+    pub const SPEAKER_ACN4: u64 = 1 << 38;
+    pub const SPEAKER_ACN5: u64 = 1 << 39;
+    pub const SPEAKER_ACN6: u64 = 1 << 40;
+    pub const SPEAKER_ACN7: u64 = 1 << 41;
+    pub const SPEAKER_ACN8: u64 = 1 << 42;
+    pub const SPEAKER_ACN9: u64 = 1 << 43;
+    pub const SPEAKER_ACN10: u64 = 1 << 44;
+    pub const SPEAKER_ACN11: u64 = 1 << 45;
+    pub const SPEAKER_ACN12: u64 = 1 << 46;
+    pub const SPEAKER_ACN13: u64 = 1 << 47;
+    pub const SPEAKER_ACN14: u64 = 1 << 48;
+    pub const SPEAKER_ACN15: u64 = 1 << 49;
+
+    pub const EMPTY: u64 = 0;
+    pub const MONO: u64 = SPEAKER_M;
+    pub const STEREO: u64 = SPEAKER_L | SPEAKER_R;
+    /// `k30Cine`: L R C.
+    pub const CINE_30: u64 = STEREO | SPEAKER_C;
+    /// `k40Music`: L R Ls Rs.
+    pub const QUAD: u64 = STEREO | SPEAKER_LS | SPEAKER_RS;
+    pub const SURROUND_50: u64 = QUAD | SPEAKER_C;
+    pub const SURROUND_51: u64 = SURROUND_50 | SPEAKER_LFE;
+    /// `k71Music`: 5.1 plus side speakers.
+    pub const SURROUND_71: u64 = SURROUND_51 | SPEAKER_SL | SPEAKER_SR;
+    /// `k71Cine`: 5.1 plus left and right of center.
+    pub const SURROUND_71_CINE: u64 = SURROUND_51 | SPEAKER_LC | SPEAKER_RC;
+    /// `k71_4`: 7.1 with four height speakers.
+    pub const SURROUND_71_4: u64 =
+        SURROUND_71 | SPEAKER_TFL | SPEAKER_TFR | SPEAKER_TRL | SPEAKER_TRR;
+    /// `kAmbi1stOrderACN`: ACN channel order, SN3D normalization, like every
+    /// ambisonic layout below.
+    pub const AMBI_1ST_ORDER_ACN: u64 = SPEAKER_ACN0 | SPEAKER_ACN1 | SPEAKER_ACN2 | SPEAKER_ACN3;
+    /// `kAmbi2cdOrderACN`: ACN0 to ACN8.
+    pub const AMBI_2ND_ORDER_ACN: u64 = AMBI_1ST_ORDER_ACN | (0x1f * SPEAKER_ACN4);
+    /// `kAmbi3rdOrderACN`: ACN0 to ACN15.
+    pub const AMBI_3RD_ORDER_ACN: u64 = AMBI_1ST_ORDER_ACN | (0xfff * SPEAKER_ACN4);
+
+    /// Channels in `arr`; an ambisonic order `n` has `(n + 1)²`.
+    pub const fn channel_count(arr: SpeakerArrangement) -> usize {
+        arr.count_ones() as usize
+    }
+
+    /// The ambisonic layout of `order` (1 to 3).
+    pub const fn ambisonic_arrangement(order: u32) -> Option<SpeakerArrangement> {
+        match order {
+            1 => Some(AMBI_1ST_ORDER_ACN),
+            2 => Some(AMBI_2ND_ORDER_ACN),
+            3 => Some(AMBI_3RD_ORDER_ACN),
+            _ => None,
+        }
+    }
+
+    /// The order of a complete ambisonic layout; `None` for anything else, including
+    /// a partial set of ACN channels.
+    pub const fn ambisonic_order(arr: SpeakerArrangement) -> Option<u32> {
+        match arr {
+            AMBI_1ST_ORDER_ACN => Some(1),
+            AMBI_2ND_ORDER_ACN => Some(2),
+            AMBI_3RD_ORDER_ACN => Some(3),
+            _ => None,
+        }
+    }
 }
 
 pub type Sample32 = f32;
 pub type Sample64 = f64;
 
 #[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct ProcessSetup {
     pub process_mode: int32,
     pub sample_rate: f64,
@@ -213,6 +494,7 @@ pub struct ProcessData32 {
     pub output_parameter_changes: *mut c_void, // IParameterChanges*
     pub input_events: *mut c_void,            // IEventList*
     pub output_events: *mut c_void,           // IEventList*
+    pub process_context: *mut ProcessContext, // may be null
 }
 
 // 64-bit audio buffers
@@ -234,12 +516,82 @@ pub struct ProcessData64 {
     pub output_parameter_changes: *mut c_void,
     pub input_events: *mut c_void,
     pub output_events: *mut c_void,
+    pub process_context: *mut ProcessContext,
+}
+
+// --- Process context (Ph10) ----------------------------------------------------
+/// `ProcessContext::state` bits: transport state, then which fields are valid.
+pub mod process_context_consts {
+    pub const K_PLAYING: u32 = 1 << 1;
+    pub const K_CYCLE_ACTIVE: u32 = 1 << 2;
+    pub const K_RECORDING: u32 = 1 << 3;
+
+    pub const K_SYSTEM_TIME_VALID: u32 = 1 << 8;
+    pub const K_PROJECT_TIME_MUSIC_VALID: u32 = 1 << 9;
+    pub const K_TEMPO_VALID: u32 = 1 << 10;
+    pub const K_BAR_POSITION_VALID: u32 = 1 << 11;
+    pub const K_CYCLE_VALID: u32 = 1 << 12;
+    pub const K_TIME_SIG_VALID: u32 = 1 << 13;
+    pub const K_SMPTE_VALID: u32 = 1 << 14;
+    pub const K_CLOCK_VALID: u32 = 1 << 15;
+    pub const K_CONT_TIME_VALID: u32 = 1 << 17;
+    pub const K_CHORD_VALID: u32 = 1 << 18;
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Chord {
+    pub key_note: u8,
+    pub root_note: u8,
+    pub chord_mask: int16,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameRate {
+    pub frames_per_second: uint32,
+    pub flags: uint32,
+}
+
+/// Transport and time for one block. Musical positions are in quarter notes;
+/// `system_time` is in nanoseconds.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ProcessContext {
+    pub state: uint32,
+    pub sample_rate: f64,
+    pub project_time_samples: int64,
+    pub system_time: int64,
+    pub continuous_time_samples: int64,
+    pub project_time_music: f64,
+    pub bar_position_music: f64,
+    pub cycle_start_music: f64,
+    pub cycle_end_music: f64,
+    pub tempo: f64,
+    pub time_sig_numerator: int32,
+    pub time_sig_denominator: int32,
+    pub chord: Chord,
+    pub smpte_offset_subframes: int32,
+    pub frame_rate: FrameRate,
+    pub samples_to_next_clock: int32,
 }
 
 // --- Bus info (read-only subset) ---------------------------------------------
+pub const MEDIA_TYPE_AUDIO: int32 = 0;
+pub const MEDIA_TYPE_EVENT: int32 = 1;
+
 pub const BUS_DIR_INPUT: int32 = 0;
 pub const BUS_DIR_OUTPUT: int32 = 1;
 
+/// `BusInfo::bus_type`.
+pub const BUS_TYPE_MAIN: int32 = 0;
+pub const BUS_TYPE_AUX: int32 = 1;
+
+/// `BusInfo::flags`: the host should activate the bus without being asked.
+pub const BUS_FLAG_DEFAULT_ACTIVE: uint32 = 1 << 0;
+/// `BusInfo::flags`: the bus carries control voltage rather than audio.
+pub const BUS_FLAG_IS_CONTROL_VOLTAGE: uint32 = 1 << 1;
+
 #[repr(C)]
 pub struct BusInfo {
     pub media_type: int32, // 0=audio, others later
@@ -309,6 +661,21 @@ pub struct IComponentVTable {
         index: int32,
         info: *mut BusInfo,
     ) -> tresult,
+
+    // Ph6: activation + state
+    pub activate_bus: unsafe extern "C" fn(
+        this_: *mut IComponent,
+        media_type: int32,
+        direction: int32,
+        index: int32,
+        state: u8,
+    ) -> tresult,
+    pub set_active: unsafe extern "C" fn(this_: *mut IComponent, state: u8) -> tresult,
+    pub set_state: unsafe extern "C" fn(this_: *mut IComponent, state: *mut IBStream) -> tresult,
+    pub get_state: unsafe extern "C" fn(this_: *mut IComponent, state: *mut IBStream) -> tresult,
+
+    // Must come before initialize; see `io_mode_consts`.
+    pub set_io_mode: unsafe extern "C" fn(this_: *mut IComponent, mode: int32) -> tresult,
 }
 #[repr(C)]
 pub struct IComponent {
@@ -341,9 +708,35 @@ impl IComponent {
     ) -> tresult {
         ((*self.vtbl).get_bus_info)(self, media_type, direction, index, info)
     }
+    #[inline]
+    pub unsafe fn activate_bus(
+        &mut self,
+        media_type: int32,
+        direction: int32,
+        index: int32,
+        state: bool,
+    ) -> tresult {
+        ((*self.vtbl).activate_bus)(self, media_type, direction, index, state as u8)
+    }
+    #[inline]
+    pub unsafe fn set_active(&mut self, state: bool) -> tresult {
+        ((*self.vtbl).set_active)(self, state as u8)
+    }
+    #[inline]
+    pub unsafe fn set_state(&mut self, state: *mut IBStream) -> tresult {
+        ((*self.vtbl).set_state)(self, state)
+    }
+    #[inline]
+    pub unsafe fn get_state(&mut self, state: *mut IBStream) -> tresult {
+        ((*self.vtbl).get_state)(self, state)
+    }
+    #[inline]
+    pub unsafe fn set_io_mode(&mut self, mode: int32) -> tresult {
+        ((*self.vtbl).set_io_mode)(self, mode)
+    }
 }
 
-// --- IAudioProcessor (subset + 64f + setBusArrangements) ---------------------
+// --- IAudioProcessor (subset + 64f + set/getBusArrangement) -------------------
 #[repr(C)]
 pub struct IAudioProcessorVTable {
     pub query_interface: unsafe extern "C" fn(
@@ -378,6 +771,22 @@ pub struct IAudioProcessorVTable {
         unsafe extern "C" fn(this_: *mut IAudioProcessor, data: *mut ProcessData32) -> tresult,
     pub process_64f:
         unsafe extern "C" fn(this_: *mut IAudioProcessor, data: *mut ProcessData64) -> tresult,
+
+    // Latency and tail
+    pub get_latency_samples: unsafe extern "C" fn(this_: *mut IAudioProcessor) -> uint32,
+    pub get_tail_samples: unsafe extern "C" fn(this_: *mut IAudioProcessor) -> uint32,
+
+    // canProcessSampleSize: kResultTrue for a supported `process_consts::SYMBOLIC_SAMPLE_*`
+    pub can_process_sample_size:
+        unsafe extern "C" fn(this_: *mut IAudioProcessor, symbolic_sample_size: int32) -> tresult,
+
+    // getBusArrangement, appended so the slots above keep their offsets
+    pub get_bus_arrangement: unsafe extern "C" fn(
+        this_: *mut IAudioProcessor,
+        dir: int32,
+        index: int32,
+        arr: *mut u64,
+    ) -> tresult,
 }
 #[repr(C)]
 pub struct IAudioProcessor {
@@ -418,4 +827,886 @@ impl IAudioProcessor {
     pub unsafe fn process_64f(&mut self, d: &mut ProcessData64) -> tresult {
         ((*self.vtbl).process_64f)(self, d as *mut _)
     }
+    #[inline]
+    pub unsafe fn get_latency_samples(&mut self) -> uint32 {
+        ((*self.vtbl).get_latency_samples)(self)
+    }
+    #[inline]
+    pub unsafe fn get_tail_samples(&mut self) -> uint32 {
+        ((*self.vtbl).get_tail_samples)(self)
+    }
+    #[inline]
+    pub unsafe fn can_process_sample_size(&mut self, symbolic_sample_size: int32) -> tresult {
+        ((*self.vtbl).can_process_sample_size)(self, symbolic_sample_size)
+    }
+    #[inline]
+    pub unsafe fn get_bus_arrangement(
+        &mut self,
+        dir: int32,
+        index: int32,
+        arr: &mut u64,
+    ) -> tresult {
+        ((*self.vtbl).get_bus_arrangement)(self, dir, index, arr as *mut _)
+    }
+}
+
+// ===== Phase 6: streams, edit controller, component handler ===================
+pub type tchar = u16;
+pub type String128 = [tchar; 128];
+pub type ParamID = uint32;
+pub type ParamValue = f64;
+
+pub mod stream_consts {
+    pub const SEEK_SET: i32 = 0;
+    pub const SEEK_CUR: i32 = 1;
+    pub const SEEK_END: i32 = 2;
+}
+
+// --- IBStream -----------------------------------------------------------------
+#[repr(C)]
+pub struct IBStreamVTable {
+    pub query_interface: unsafe extern "C" fn(
+        this_: *mut FUnknown,
+        iid: *const Fuid,
+        obj: *mut *mut c_void,
+    ) -> tresult,
+    pub add_ref: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+    pub release: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+
+    pub read: unsafe extern "C" fn(
+        this_: *mut IBStream,
+        buffer: *mut c_void,
+        num_bytes: int32,
+        num_bytes_read: *mut int32,
+    ) -> tresult,
+    pub write: unsafe extern "C" fn(
+        this_: *mut IBStream,
+        buffer: *const c_void,
+        num_bytes: int32,
+        num_bytes_written: *mut int32,
+    ) -> tresult,
+    pub seek: unsafe extern "C" fn(
+        this_: *mut IBStream,
+        pos: int64,
+        mode: int32,
+        result: *mut int64,
+    ) -> tresult,
+    pub tell: unsafe extern "C" fn(this_: *mut IBStream, pos: *mut int64) -> tresult,
+}
+#[repr(C)]
+pub struct IBStream {
+    pub vtbl: *const IBStreamVTable,
+}
+impl IBStream {
+    #[inline]
+    pub unsafe fn read(&mut self, buf: *mut c_void, n: int32, read: *mut int32) -> tresult {
+        ((*self.vtbl).read)(self, buf, n, read)
+    }
+    #[inline]
+    pub unsafe fn write(&mut self, buf: *const c_void, n: int32, written: *mut int32) -> tresult {
+        ((*self.vtbl).write)(self, buf, n, written)
+    }
+    #[inline]
+    pub unsafe fn seek(&mut self, pos: int64, mode: int32, result: *mut int64) -> tresult {
+        ((*self.vtbl).seek)(self, pos, mode, result)
+    }
+    #[inline]
+    pub unsafe fn tell(&mut self, pos: *mut int64) -> tresult {
+        ((*self.vtbl).tell)(self, pos)
+    }
+}
+
+// --- IComponentHandler --------------------------------------------------------
+#[repr(C)]
+pub struct IComponentHandlerVTable {
+    pub query_interface: unsafe extern "C" fn(
+        this_: *mut FUnknown,
+        iid: *const Fuid,
+        obj: *mut *mut c_void,
+    ) -> tresult,
+    pub add_ref: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+    pub release: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+
+    pub begin_edit: unsafe extern "C" fn(this_: *mut IComponentHandler, id: ParamID) -> tresult,
+    pub perform_edit: unsafe extern "C" fn(
+        this_: *mut IComponentHandler,
+        id: ParamID,
+        value_normalized: ParamValue,
+    ) -> tresult,
+    pub end_edit: unsafe extern "C" fn(this_: *mut IComponentHandler, id: ParamID) -> tresult,
+    pub restart_component:
+        unsafe extern "C" fn(this_: *mut IComponentHandler, flags: int32) -> tresult,
+}
+#[repr(C)]
+pub struct IComponentHandler {
+    pub vtbl: *const IComponentHandlerVTable,
+}
+impl IComponentHandler {
+    #[inline]
+    pub unsafe fn begin_edit(&mut self, id: ParamID) -> tresult {
+        ((*self.vtbl).begin_edit)(self, id)
+    }
+    #[inline]
+    pub unsafe fn perform_edit(&mut self, id: ParamID, value: ParamValue) -> tresult {
+        ((*self.vtbl).perform_edit)(self, id, value)
+    }
+    #[inline]
+    pub unsafe fn end_edit(&mut self, id: ParamID) -> tresult {
+        ((*self.vtbl).end_edit)(self, id)
+    }
+    #[inline]
+    pub unsafe fn restart_component(&mut self, flags: int32) -> tresult {
+        ((*self.vtbl).restart_component)(self, flags)
+    }
+}
+
+/// `Vst::IoModes` for `IComponent::setIoMode`.
+pub mod io_mode_consts {
+    pub const K_SIMPLE: i32 = 0;
+    pub const K_ADVANCED: i32 = 1;
+    /// The host renders offline; the plugin may trade speed for quality.
+    pub const K_OFFLINE_PROCESSING: i32 = 2;
+}
+
+/// `RestartFlags` for `IComponentHandler::restartComponent`.
+pub mod restart_consts {
+    pub const K_RELOAD_COMPONENT: i32 = 1 << 0;
+    pub const K_IO_CHANGED: i32 = 1 << 1;
+    pub const K_PARAM_VALUES_CHANGED: i32 = 1 << 2;
+    pub const K_LATENCY_CHANGED: i32 = 1 << 3;
+    pub const K_PARAM_TITLES_CHANGED: i32 = 1 << 4;
+    pub const K_MIDI_CC_ASSIGNMENT_CHANGED: i32 = 1 << 5;
+    pub const K_NOTE_EXPRESSION_CHANGED: i32 = 1 << 6;
+    pub const K_IO_TITLES_CHANGED: i32 = 1 << 7;
+}
+
+// --- IEditController ----------------------------------------------------------
+pub mod param_consts {
+    pub const K_CAN_AUTOMATE: i32 = 1 << 0;
+    pub const K_IS_READ_ONLY: i32 = 1 << 1;
+    pub const K_IS_WRAP_AROUND: i32 = 1 << 2;
+    pub const K_IS_LIST: i32 = 1 << 3;
+    pub const K_IS_HIDDEN: i32 = 1 << 4;
+    pub const K_IS_PROGRAM_CHANGE: i32 = 1 << 15;
+    pub const K_IS_BYPASS: i32 = 1 << 16;
+}
+
+bitflags::bitflags! {
+    /// `ParameterInfo::flags`; the same bits as [`param_consts`].
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct ParameterFlags: i32 {
+        const CAN_AUTOMATE = param_consts::K_CAN_AUTOMATE;
+        const IS_READ_ONLY = param_consts::K_IS_READ_ONLY;
+        const IS_WRAP_AROUND = param_consts::K_IS_WRAP_AROUND;
+        const IS_LIST = param_consts::K_IS_LIST;
+        const IS_HIDDEN = param_consts::K_IS_HIDDEN;
+        const IS_PROGRAM_CHANGE = param_consts::K_IS_PROGRAM_CHANGE;
+        const IS_BYPASS = param_consts::K_IS_BYPASS;
+    }
+}
+
+#[repr(C)]
+pub struct ParameterInfo {
+    pub id: ParamID,
+    pub title: String128,
+    pub short_title: String128,
+    pub units: String128,
+    pub step_count: int32,
+    pub default_normalized_value: ParamValue,
+    pub unit_id: int32,
+    pub flags: int32,
+}
+
+#[repr(C)]
+pub struct IEditControllerVTable {
+    pub query_interface: unsafe extern "C" fn(
+        this_: *mut FUnknown,
+        iid: *const Fuid,
+        obj: *mut *mut c_void,
+    ) -> tresult,
+    pub add_ref: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+    pub release: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+
+    // IPluginBase
+    pub initialize:
+        unsafe extern "C" fn(this_: *mut IEditController, context: *mut FUnknown) -> tresult,
+    pub terminate: unsafe extern "C" fn(this_: *mut IEditController) -> tresult,
+
+    pub set_component_state:
+        unsafe extern "C" fn(this_: *mut IEditController, state: *mut IBStream) -> tresult,
+    pub set_state:
+        unsafe extern "C" fn(this_: *mut IEditController, state: *mut IBStream) -> tresult,
+    pub get_state:
+        unsafe extern "C" fn(this_: *mut IEditController, state: *mut IBStream) -> tresult,
+    pub get_parameter_count: unsafe extern "C" fn(this_: *mut IEditController) -> int32,
+    pub get_parameter_info: unsafe extern "C" fn(
+        this_: *mut IEditController,
+        param_index: int32,
+        info: *mut ParameterInfo,
+    ) -> tresult,
+    pub get_param_string_by_value: unsafe extern "C" fn(
+        this_: *mut IEditController,
+        id: ParamID,
+        value_normalized: ParamValue,
+        string: *mut String128,
+    ) -> tresult,
+    pub get_param_value_by_string: unsafe extern "C" fn(
+        this_: *mut IEditController,
+        id: ParamID,
+        string: *const tchar,
+        value_normalized: *mut ParamValue,
+    ) -> tresult,
+    pub normalized_param_to_plain: unsafe extern "C" fn(
+        this_: *mut IEditController,
+        id: ParamID,
+        value_normalized: ParamValue,
+    ) -> ParamValue,
+    pub plain_param_to_normalized: unsafe extern "C" fn(
+        this_: *mut IEditController,
+        id: ParamID,
+        plain_value: ParamValue,
+    ) -> ParamValue,
+    pub get_param_normalized:
+        unsafe extern "C" fn(this_: *mut IEditController, id: ParamID) -> ParamValue,
+    pub set_param_normalized: unsafe extern "C" fn(
+        this_: *mut IEditController,
+        id: ParamID,
+        value: ParamValue,
+    ) -> tresult,
+    pub set_component_handler: unsafe extern "C" fn(
+        this_: *mut IEditController,
+        handler: *mut IComponentHandler,
+    ) -> tresult,
+    pub create_view:
+        unsafe extern "C" fn(this_: *mut IEditController, name: *const i8) -> *mut c_void, // IPlugView*
+}
+#[repr(C)]
+pub struct IEditController {
+    pub vtbl: *const IEditControllerVTable,
+}
+impl IEditController {
+    #[inline]
+    pub unsafe fn initialize(&mut self, ctx: *mut FUnknown) -> tresult {
+        ((*self.vtbl).initialize)(self, ctx)
+    }
+    #[inline]
+    pub unsafe fn terminate(&mut self) -> tresult {
+        ((*self.vtbl).terminate)(self)
+    }
+    #[inline]
+    pub unsafe fn set_component_state(&mut self, state: *mut IBStream) -> tresult {
+        ((*self.vtbl).set_component_state)(self, state)
+    }
+    #[inline]
+    pub unsafe fn set_state(&mut self, state: *mut IBStream) -> tresult {
+        ((*self.vtbl).set_state)(self, state)
+    }
+    #[inline]
+    pub unsafe fn get_state(&mut self, state: *mut IBStream) -> tresult {
+        ((*self.vtbl).get_state)(self, state)
+    }
+    #[inline]
+    pub unsafe fn get_parameter_count(&mut self) -> int32 {
+        ((*self.vtbl).get_parameter_count)(self)
+    }
+    #[inline]
+    pub unsafe fn get_parameter_info(&mut self, index: int32, info: *mut ParameterInfo) -> tresult {
+        ((*self.vtbl).get_parameter_info)(self, index, info)
+    }
+    #[inline]
+    pub unsafe fn get_param_string_by_value(
+        &mut self,
+        id: ParamID,
+        value: ParamValue,
+        string: *mut String128,
+    ) -> tresult {
+        ((*self.vtbl).get_param_string_by_value)(self, id, value, string)
+    }
+    #[inline]
+    pub unsafe fn get_param_value_by_string(
+        &mut self,
+        id: ParamID,
+        string: *const tchar,
+        value: *mut ParamValue,
+    ) -> tresult {
+        ((*self.vtbl).get_param_value_by_string)(self, id, string, value)
+    }
+    #[inline]
+    pub unsafe fn normalized_param_to_plain(
+        &mut self,
+        id: ParamID,
+        value: ParamValue,
+    ) -> ParamValue {
+        ((*self.vtbl).normalized_param_to_plain)(self, id, value)
+    }
+    #[inline]
+    pub unsafe fn plain_param_to_normalized(
+        &mut self,
+        id: ParamID,
+        plain: ParamValue,
+    ) -> ParamValue {
+        ((*self.vtbl).plain_param_to_normalized)(self, id, plain)
+    }
+    #[inline]
+    pub unsafe fn get_param_normalized(&mut self, id: ParamID) -> ParamValue {
+        ((*self.vtbl).get_param_normalized)(self, id)
+    }
+    #[inline]
+    pub unsafe fn set_param_normalized(&mut self, id: ParamID, value: ParamValue) -> tresult {
+        ((*self.vtbl).set_param_normalized)(self, id, value)
+    }
+    #[inline]
+    pub unsafe fn set_component_handler(&mut self, handler: *mut IComponentHandler) -> tresult {
+        ((*self.vtbl).set_component_handler)(self, handler)
+    }
+    #[inline]
+    pub unsafe fn create_view(&mut self, name: *const i8) -> *mut c_void {
+        ((*self.vtbl).create_view)(self, name)
+    }
+}
+
+// ===== Phase 8: events, parameter changes, MIDI mapping =======================
+pub mod event_consts {
+    pub const NOTE_ON: u16 = 0;
+    pub const NOTE_OFF: u16 = 1;
+    pub const DATA: u16 = 2;
+    pub const POLY_PRESSURE: u16 = 3;
+    pub const LEGACY_MIDI_CC_OUT: u16 = 65535;
+
+    /// `Event::flags`: the event is played live (not from a sequencer track).
+    pub const IS_LIVE: u16 = 1 << 0;
+
+    /// `ControllerNumbers` past the 0..=127 CC range, for IMidiMapping.
+    pub const AFTER_TOUCH: i16 = 128;
+    pub const PITCH_BEND: i16 = 129;
+    /// `kCtrlProgramChange`: only in `LegacyMidiCcOutEvent::control_number`.
+    pub const PROGRAM_CHANGE: i16 = 130;
+
+    /// `DataEvent::type_` of a system exclusive message, `F0` to `F7` included.
+    pub const DATA_MIDI_SYSEX: u32 = 0;
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct NoteOnEvent {
+    pub channel: int16,
+    pub pitch: int16,
+    pub tuning: f32,
+    pub velocity: f32,
+    pub length: int32,
+    pub note_id: int32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct NoteOffEvent {
+    pub channel: int16,
+    pub pitch: int16,
+    pub velocity: f32,
+    pub note_id: int32,
+    pub tuning: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PolyPressureEvent {
+    pub channel: int16,
+    pub pitch: int16,
+    pub pressure: f32,
+    pub note_id: int32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LegacyMidiCcOutEvent {
+    pub control_number: u8,
+    pub channel: i8,
+    pub value: i8,
+    pub value2: i8,
+}
+
+/// `bytes` belong to whoever added the event and stay valid only for the `process`
+/// call it belongs to.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DataEvent {
+    pub size: u32,
+    /// `event_consts::DATA_MIDI_SYSEX`.
+    pub type_: u32,
+    pub bytes: *const u8,
+}
+
+// Only a pointer value: reading through it is unsafe and bound to the `process` call
+// anyway. This keeps `Event` `Send` and `Sync`.
+unsafe impl Send for DataEvent {}
+unsafe impl Sync for DataEvent {}
+
+/// Payload of an [`Event`], selected by `Event::type_`.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union EventData {
+    pub note_on: NoteOnEvent,
+    pub note_off: NoteOffEvent,
+    pub poly_pressure: PolyPressureEvent,
+    pub midi_cc_out: LegacyMidiCcOutEvent,
+    pub data: DataEvent,
+    /// Size and alignment of the largest SDK event (text and data events carry a pointer).
+    pub reserved: [u64; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Event {
+    pub bus_index: int32,
+    pub sample_offset: int32,
+    pub ppq_position: f64,
+    pub flags: u16,
+    pub type_: u16,
+    pub data: EventData,
+}
+
+impl Default for Event {
+    fn default() -> Self {
+        Self {
+            bus_index: 0,
+            sample_offset: 0,
+            ppq_position: 0.0,
+            flags: 0,
+            type_: event_consts::NOTE_ON,
+            data: EventData { reserved: [0; 3] },
+        }
+    }
+}
+
+impl Event {
+    /// The same event for event bus `bus_index`; the constructors target bus 0.
+    pub fn on_bus(mut self, bus_index: int32) -> Self {
+        self.bus_index = bus_index;
+        self
+    }
+
+    pub fn note_on(sample_offset: int32, channel: int16, pitch: int16, velocity: f32) -> Self {
+        Self {
+            sample_offset,
+            type_: event_consts::NOTE_ON,
+            data: EventData {
+                note_on: NoteOnEvent {
+                    channel,
+                    pitch,
+                    velocity,
+                    note_id: -1,
+                    ..NoteOnEvent::default()
+                },
+            },
+            ..Self::default()
+        }
+    }
+
+    pub fn note_off(sample_offset: int32, channel: int16, pitch: int16, velocity: f32) -> Self {
+        Self {
+            sample_offset,
+            type_: event_consts::NOTE_OFF,
+            data: EventData {
+                note_off: NoteOffEvent {
+                    channel,
+                    pitch,
+                    velocity,
+                    note_id: -1,
+                    tuning: 0.0,
+                },
+            },
+            ..Self::default()
+        }
+    }
+
+    pub fn poly_pressure(
+        sample_offset: int32,
+        channel: int16,
+        pitch: int16,
+        pressure: f32,
+    ) -> Self {
+        Self {
+            sample_offset,
+            type_: event_consts::POLY_PRESSURE,
+            data: EventData {
+                poly_pressure: PolyPressureEvent {
+                    channel,
+                    pitch,
+                    pressure,
+                    note_id: -1,
+                },
+            },
+            ..Self::default()
+        }
+    }
+
+    /// `control` is a CC number or one of the `event_consts` controller numbers; pitch
+    /// bend carries its LSB in `value` and MSB in `value2`.
+    pub fn midi_cc_out(
+        sample_offset: int32,
+        channel: i8,
+        control: u8,
+        value: i8,
+        value2: i8,
+    ) -> Self {
+        Self {
+            sample_offset,
+            type_: event_consts::LEGACY_MIDI_CC_OUT,
+            data: EventData {
+                midi_cc_out: LegacyMidiCcOutEvent {
+                    control_number: control,
+                    channel,
+                    value,
+                    value2,
+                },
+            },
+            ..Self::default()
+        }
+    }
+}
+
+impl core::fmt::Debug for Event {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut d = f.debug_struct("Event");
+        d.field("bus_index", &self.bus_index)
+            .field("sample_offset", &self.sample_offset)
+            .field("flags", &self.flags);
+        // The active union member is the one named by `type_`.
+        unsafe {
+            match self.type_ {
+                event_consts::NOTE_ON => d.field("note_on", &self.data.note_on),
+                event_consts::NOTE_OFF => d.field("note_off", &self.data.note_off),
+                event_consts::POLY_PRESSURE => d.field("poly_pressure", &self.data.poly_pressure),
+                event_consts::LEGACY_MIDI_CC_OUT => d.field("midi_cc_out", &self.data.midi_cc_out),
+                event_consts::DATA => d.field("data", &self.data.data),
+                other => d.field("type", &other),
+            };
+        }
+        d.finish()
+    }
+}
+
+// --- IEventList ---------------------------------------------------------------
+#[repr(C)]
+pub struct IEventListVTable {
+    pub query_interface: unsafe extern "C" fn(
+        this_: *mut FUnknown,
+        iid: *const Fuid,
+        obj: *mut *mut c_void,
+    ) -> tresult,
+    pub add_ref: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+    pub release: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+
+    pub get_event_count: unsafe extern "C" fn(this_: *mut IEventList) -> int32,
+    pub get_event:
+        unsafe extern "C" fn(this_: *mut IEventList, index: int32, e: *mut Event) -> tresult,
+    pub add_event: unsafe extern "C" fn(this_: *mut IEventList, e: *mut Event) -> tresult,
+}
+#[repr(C)]
+pub struct IEventList {
+    pub vtbl: *const IEventListVTable,
+}
+impl IEventList {
+    #[inline]
+    pub unsafe fn get_event_count(&mut self) -> int32 {
+        ((*self.vtbl).get_event_count)(self)
+    }
+    #[inline]
+    pub unsafe fn get_event(&mut self, index: int32, e: *mut Event) -> tresult {
+        ((*self.vtbl).get_event)(self, index, e)
+    }
+    #[inline]
+    pub unsafe fn add_event(&mut self, e: *mut Event) -> tresult {
+        ((*self.vtbl).add_event)(self, e)
+    }
+}
+
+// --- IParamValueQueue ---------------------------------------------------------
+#[repr(C)]
+pub struct IParamValueQueueVTable {
+    pub query_interface: unsafe extern "C" fn(
+        this_: *mut FUnknown,
+        iid: *const Fuid,
+        obj: *mut *mut c_void,
+    ) -> tresult,
+    pub add_ref: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+    pub release: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+
+    pub get_parameter_id: unsafe extern "C" fn(this_: *mut IParamValueQueue) -> ParamID,
+    pub get_point_count: unsafe extern "C" fn(this_: *mut IParamValueQueue) -> int32,
+    pub get_point: unsafe extern "C" fn(
+        this_: *mut IParamValueQueue,
+        index: int32,
+        sample_offset: *mut int32,
+        value: *mut ParamValue,
+    ) -> tresult,
+    pub add_point: unsafe extern "C" fn(
+        this_: *mut IParamValueQueue,
+        sample_offset: int32,
+        value: ParamValue,
+        index: *mut int32,
+    ) -> tresult,
+}
+#[repr(C)]
+pub struct IParamValueQueue {
+    pub vtbl: *const IParamValueQueueVTable,
+}
+impl IParamValueQueue {
+    #[inline]
+    pub unsafe fn get_parameter_id(&mut self) -> ParamID {
+        ((*self.vtbl).get_parameter_id)(self)
+    }
+    #[inline]
+    pub unsafe fn get_point_count(&mut self) -> int32 {
+        ((*self.vtbl).get_point_count)(self)
+    }
+    #[inline]
+    pub unsafe fn get_point(
+        &mut self,
+        index: int32,
+        sample_offset: *mut int32,
+        value: *mut ParamValue,
+    ) -> tresult {
+        ((*self.vtbl).get_point)(self, index, sample_offset, value)
+    }
+    #[inline]
+    pub unsafe fn add_point(
+        &mut self,
+        sample_offset: int32,
+        value: ParamValue,
+        index: *mut int32,
+    ) -> tresult {
+        ((*self.vtbl).add_point)(self, sample_offset, value, index)
+    }
+}
+
+// --- IParameterChanges --------------------------------------------------------
+#[repr(C)]
+pub struct IParameterChangesVTable {
+    pub query_interface: unsafe extern "C" fn(
+        this_: *mut FUnknown,
+        iid: *const Fuid,
+        obj: *mut *mut c_void,
+    ) -> tresult,
+    pub add_ref: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+    pub release: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+
+    pub get_parameter_count: unsafe extern "C" fn(this_: *mut IParameterChanges) -> int32,
+    pub get_parameter_data:
+        unsafe extern "C" fn(this_: *mut IParameterChanges, index: int32) -> *mut IParamValueQueue,
+    pub add_parameter_data: unsafe extern "C" fn(
+        this_: *mut IParameterChanges,
+        id: *const ParamID,
+        index: *mut int32,
+    ) -> *mut IParamValueQueue,
+}
+#[repr(C)]
+pub struct IParameterChanges {
+    pub vtbl: *const IParameterChangesVTable,
+}
+impl IParameterChanges {
+    #[inline]
+    pub unsafe fn get_parameter_count(&mut self) -> int32 {
+        ((*self.vtbl).get_parameter_count)(self)
+    }
+    #[inline]
+    pub unsafe fn get_parameter_data(&mut self, index: int32) -> *mut IParamValueQueue {
+        ((*self.vtbl).get_parameter_data)(self, index)
+    }
+    #[inline]
+    pub unsafe fn add_parameter_data(
+        &mut self,
+        id: *const ParamID,
+        index: *mut int32,
+    ) -> *mut IParamValueQueue {
+        ((*self.vtbl).add_parameter_data)(self, id, index)
+    }
+}
+
+// --- IMidiMapping (edit controller extension) ---------------------------------
+#[repr(C)]
+pub struct IMidiMappingVTable {
+    pub query_interface: unsafe extern "C" fn(
+        this_: *mut FUnknown,
+        iid: *const Fuid,
+        obj: *mut *mut c_void,
+    ) -> tresult,
+    pub add_ref: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+    pub release: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+
+    pub get_midi_controller_assignment: unsafe extern "C" fn(
+        this_: *mut IMidiMapping,
+        bus_index: int32,
+        channel: int16,
+        midi_controller_number: int16,
+        id: *mut ParamID,
+    ) -> tresult,
+}
+#[repr(C)]
+pub struct IMidiMapping {
+    pub vtbl: *const IMidiMappingVTable,
+}
+impl IMidiMapping {
+    #[inline]
+    pub unsafe fn get_midi_controller_assignment(
+        &mut self,
+        bus_index: int32,
+        channel: int16,
+        midi_controller_number: int16,
+        id: *mut ParamID,
+    ) -> tresult {
+        ((*self.vtbl).get_midi_controller_assignment)(
+            self,
+            bus_index,
+            channel,
+            midi_controller_number,
+            id,
+        )
+    }
+}
+
+// ===== Phase 9: plug-in editor views ==========================================
+
+/// `IEditController::createView` name of the main editor.
+pub const VIEW_TYPE_EDITOR: &[u8] = b"editor\0";
+
+/// NUL-terminated platform type strings for `IPlugView::attached`.
+pub mod platform_types {
+    /// Parent is an `HWND`.
+    pub const HWND: &[u8] = b"HWND\0";
+    /// Parent is an `NSView*`.
+    pub const NSVIEW: &[u8] = b"NSView\0";
+    /// Parent is an X11 window id.
+    pub const X11_EMBED_WINDOW_ID: &[u8] = b"X11EmbedWindowID\0";
+}
+
+/// View size in pixels.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ViewRect {
+    pub left: int32,
+    pub top: int32,
+    pub right: int32,
+    pub bottom: int32,
+}
+
+impl ViewRect {
+    pub fn width(&self) -> int32 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> int32 {
+        self.bottom - self.top
+    }
+}
+
+// --- IPlugView ----------------------------------------------------------------
+#[repr(C)]
+pub struct IPlugViewVTable {
+    pub query_interface: unsafe extern "C" fn(
+        this_: *mut FUnknown,
+        iid: *const Fuid,
+        obj: *mut *mut c_void,
+    ) -> tresult,
+    pub add_ref: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+    pub release: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+
+    pub is_platform_type_supported:
+        unsafe extern "C" fn(this_: *mut IPlugView, type_: *const i8) -> tresult,
+    pub attached: unsafe extern "C" fn(
+        this_: *mut IPlugView,
+        parent: *mut c_void,
+        type_: *const i8,
+    ) -> tresult,
+    pub removed: unsafe extern "C" fn(this_: *mut IPlugView) -> tresult,
+    pub on_wheel: unsafe extern "C" fn(this_: *mut IPlugView, distance: f32) -> tresult,
+    pub on_key_down: unsafe extern "C" fn(
+        this_: *mut IPlugView,
+        key: int16,
+        key_code: int16,
+        modifiers: int16,
+    ) -> tresult,
+    pub on_key_up: unsafe extern "C" fn(
+        this_: *mut IPlugView,
+        key: int16,
+        key_code: int16,
+        modifiers: int16,
+    ) -> tresult,
+    pub get_size: unsafe extern "C" fn(this_: *mut IPlugView, size: *mut ViewRect) -> tresult,
+    pub on_size: unsafe extern "C" fn(this_: *mut IPlugView, new_size: *mut ViewRect) -> tresult,
+    pub on_focus: unsafe extern "C" fn(this_: *mut IPlugView, state: u8) -> tresult,
+    pub set_frame: unsafe extern "C" fn(this_: *mut IPlugView, frame: *mut IPlugFrame) -> tresult,
+    pub can_resize: unsafe extern "C" fn(this_: *mut IPlugView) -> tresult,
+    pub check_size_constraint:
+        unsafe extern "C" fn(this_: *mut IPlugView, rect: *mut ViewRect) -> tresult,
+}
+#[repr(C)]
+pub struct IPlugView {
+    pub vtbl: *const IPlugViewVTable,
+}
+impl IPlugView {
+    #[inline]
+    pub unsafe fn is_platform_type_supported(&mut self, type_: *const i8) -> tresult {
+        ((*self.vtbl).is_platform_type_supported)(self, type_)
+    }
+    #[inline]
+    pub unsafe fn attached(&mut self, parent: *mut c_void, type_: *const i8) -> tresult {
+        ((*self.vtbl).attached)(self, parent, type_)
+    }
+    #[inline]
+    pub unsafe fn removed(&mut self) -> tresult {
+        ((*self.vtbl).removed)(self)
+    }
+    #[inline]
+    pub unsafe fn get_size(&mut self, size: *mut ViewRect) -> tresult {
+        ((*self.vtbl).get_size)(self, size)
+    }
+    #[inline]
+    pub unsafe fn on_size(&mut self, new_size: *mut ViewRect) -> tresult {
+        ((*self.vtbl).on_size)(self, new_size)
+    }
+    #[inline]
+    pub unsafe fn on_focus(&mut self, state: bool) -> tresult {
+        ((*self.vtbl).on_focus)(self, state as u8)
+    }
+    #[inline]
+    pub unsafe fn set_frame(&mut self, frame: *mut IPlugFrame) -> tresult {
+        ((*self.vtbl).set_frame)(self, frame)
+    }
+    #[inline]
+    pub unsafe fn can_resize(&mut self) -> tresult {
+        ((*self.vtbl).can_resize)(self)
+    }
+    #[inline]
+    pub unsafe fn check_size_constraint(&mut self, rect: *mut ViewRect) -> tresult {
+        ((*self.vtbl).check_size_constraint)(self, rect)
+    }
+}
+
+// --- IPlugFrame (host side) ---------------------------------------------------
+#[repr(C)]
+pub struct IPlugFrameVTable {
+    pub query_interface: unsafe extern "C" fn(
+        this_: *mut FUnknown,
+        iid: *const Fuid,
+        obj: *mut *mut c_void,
+    ) -> tresult,
+    pub add_ref: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+    pub release: unsafe extern "C" fn(this_: *mut FUnknown) -> u32,
+
+    pub resize_view: unsafe extern "C" fn(
+        this_: *mut IPlugFrame,
+        view: *mut IPlugView,
+        new_size: *mut ViewRect,
+    ) -> tresult,
+}
+#[repr(C)]
+pub struct IPlugFrame {
+    pub vtbl: *const IPlugFrameVTable,
+}
+impl IPlugFrame {
+    #[inline]
+    pub unsafe fn resize_view(&mut self, view: *mut IPlugView, new_size: *mut ViewRect) -> tresult {
+        ((*self.vtbl).resize_view)(self, view, new_size)
+    }
 }
@@ -0,0 +1,97 @@
+//! The `dsp` kernels against the plain loops they replace, which the compiler is free
+//! to vectorize for the baseline target: widening and narrowing a block, applying a
+//! gain and mixing one block into another.
+//!
+//! `cargo bench -p openvst3-host --bench dsp`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use openvst3_host::dsp::{self, Isa};
+
+const BLOCKS: u32 = 1_000_000;
+
+fn time(mut block: impl FnMut()) -> Duration {
+    for _ in 0..BLOCKS / 10 {
+        block();
+    }
+    let started = Instant::now();
+    for _ in 0..BLOCKS {
+        block();
+    }
+    started.elapsed() / BLOCKS
+}
+
+fn main() {
+    println!("kernels: {}", Isa::detect());
+    println!(
+        "{:>10} {:>6} {:>12} {:>12}",
+        "kernel", "frames", "loop", "dsp"
+    );
+    for frames in [64, 256, 1024] {
+        let narrow: Vec<f32> = (0..frames).map(|i| (i as f32 * 0.01).sin()).collect();
+        let wide: Vec<f64> = narrow.iter().map(|&s| s as f64).collect();
+        let mut narrow_out = vec![0.0f32; frames];
+        let mut wide_out = vec![0.0f64; frames];
+
+        let rows: [(&str, Duration, Duration); 4] = [
+            (
+                "f32->f64",
+                time(|| {
+                    for (d, &s) in wide_out.iter_mut().zip(black_box(&narrow)) {
+                        *d = s as f64;
+                    }
+                    black_box(&mut wide_out);
+                }),
+                time(|| {
+                    dsp::convert_f32_to_f64(black_box(&narrow), &mut wide_out);
+                    black_box(&mut wide_out);
+                }),
+            ),
+            (
+                "f64->f32",
+                time(|| {
+                    for (d, &s) in narrow_out.iter_mut().zip(black_box(&wide)) {
+                        *d = s.clamp(-f32::MAX as f64, f32::MAX as f64) as f32;
+                    }
+                    black_box(&mut narrow_out);
+                }),
+                time(|| {
+                    dsp::convert_f64_to_f32(black_box(&wide), &mut narrow_out);
+                    black_box(&mut narrow_out);
+                }),
+            ),
+            (
+                "gain",
+                time(|| {
+                    let gain = black_box(0.5);
+                    for s in narrow_out.iter_mut() {
+                        *s *= gain;
+                    }
+                    black_box(&mut narrow_out);
+                }),
+                time(|| {
+                    dsp::apply_gain(&mut narrow_out, black_box(0.5));
+                    black_box(&mut narrow_out);
+                }),
+            ),
+            (
+                "mix_add",
+                time(|| {
+                    let gain = black_box(0.5);
+                    for (b, &a) in narrow_out.iter_mut().zip(black_box(&narrow)) {
+                        *b += a * gain;
+                    }
+                    black_box(&mut narrow_out);
+                }),
+                time(|| {
+                    dsp::mix_add(black_box(&narrow), &mut narrow_out, black_box(0.5));
+                    black_box(&mut narrow_out);
+                }),
+            ),
+        ];
+        for (kernel, plain, kernels) in rows {
+            println!("{kernel:>10} {frames:>6} {plain:>12?} {kernels:>12?}");
+        }
+    }
+}
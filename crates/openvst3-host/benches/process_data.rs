@@ -0,0 +1,146 @@
+//! Per-block `ProcessData` setup: rebuilt from the channel buffers every block (as
+//! the renderer and the realtime CLI used to) against a [`BoundProcessData`] that only
+//! fills in the block. The plugin call itself is not included.
+//!
+//! Then the input side of an effect fed by a muted mono microphone: deinterleaving
+//! every block into each plugin channel, against an [`InputGate`] that stops copying
+//! once the input has stayed silent.
+//!
+//! Last, moving a block between plugins in series: four 32-bit slots, where the plan
+//! has no conversions and every hand-off is a copy, against 32- and 64-bit slots
+//! alternating, where every hand-off converts.
+//!
+//! `cargo bench -p openvst3-host --bench process_data`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use openvst3_abi::{AudioBusBuffers32, ProcessData32};
+use openvst3_host::{
+    pass_block, BoundProcessData, EventList, InputGate, ParameterChanges, PrecisionPlan,
+    SlotPrecision, SymbolicSampleSize,
+};
+
+const BLOCKS: u32 = 1_000_000;
+
+fn time(mut block: impl FnMut()) -> Duration {
+    for _ in 0..BLOCKS / 10 {
+        block();
+    }
+    let started = Instant::now();
+    for _ in 0..BLOCKS {
+        block();
+    }
+    started.elapsed() / BLOCKS
+}
+
+fn main() {
+    let mut events = EventList::with_capacity(64);
+    let mut changes = ParameterChanges::with_capacity(8, 64);
+    println!(
+        "{:>8} {:>6} {:>12} {:>12}",
+        "channels", "frames", "rebuilt", "bound"
+    );
+    for (channels, frames) in [(2, 32), (2, 64), (16, 32), (16, 64), (64, 32)] {
+        let mut inputs = vec![vec![0.0f32; frames]; channels];
+        let mut outputs = vec![vec![0.0f32; frames]; channels];
+        let rebuilt = time(|| {
+            let mut in_ptrs: Vec<*mut f32> = inputs.iter_mut().map(|b| b.as_mut_ptr()).collect();
+            let mut out_ptrs: Vec<*mut f32> = outputs.iter_mut().map(|b| b.as_mut_ptr()).collect();
+            let mut in_bus = AudioBusBuffers32 {
+                num_channels: channels as i32,
+                silence_flags: 0,
+                channel_buffers: in_ptrs.as_mut_ptr(),
+            };
+            let mut out_bus = AudioBusBuffers32 {
+                num_channels: channels as i32,
+                silence_flags: 0,
+                channel_buffers: out_ptrs.as_mut_ptr(),
+            };
+            let mut data = ProcessData32 {
+                num_inputs: 1,
+                num_outputs: 1,
+                inputs: &mut in_bus,
+                outputs: &mut out_bus,
+                num_samples: frames as i32,
+                input_parameter_changes: changes.as_iparameter_changes().cast(),
+                output_parameter_changes: core::ptr::null_mut(),
+                input_events: events.as_ievent_list().cast(),
+                output_events: core::ptr::null_mut(),
+                process_context: core::ptr::null_mut(),
+            };
+            black_box(&mut data);
+        });
+
+        let mut bound = BoundProcessData::<f32>::new(channels, channels, frames);
+        let bound_time = time(|| {
+            let data = bound
+                .prepare(frames, Some((&mut events, &mut changes)))
+                .unwrap();
+            black_box(data);
+        });
+        println!("{channels:>8} {frames:>6} {rebuilt:>12?} {bound_time:>12?}");
+    }
+
+    println!();
+    println!(
+        "{:>8} {:>6} {:>12} {:>12}",
+        "channels", "frames", "copied", "gated"
+    );
+    for (channels, frames) in [(2, 64), (2, 256), (16, 256)] {
+        let mic = vec![0.0f32; frames];
+        let mut copied = BoundProcessData::<f32>::new(channels, channels, frames);
+        let mut gated = BoundProcessData::<f32>::new(channels, channels, frames);
+        gated.set_input_gate(Some(InputGate::new()));
+        let read = |bound: &mut BoundProcessData<f32>| {
+            let mic = black_box(&mic);
+            let peak = mic.iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+            bound.write_input(peak as f64, |planar| {
+                for channel in planar.iter_mut() {
+                    channel[..frames].copy_from_slice(mic);
+                }
+            });
+            black_box(bound.prepare(frames, None).unwrap());
+        };
+        let copied_time = time(|| read(&mut copied));
+        let gated_time = time(|| read(&mut gated));
+        println!("{channels:>8} {frames:>6} {copied_time:>12?} {gated_time:>12?}");
+    }
+
+    println!();
+    println!(
+        "{:>8} {:>6} {:>12} {:>12} {:>12} {:>12}",
+        "channels", "frames", "f32 convs", "f32 chain", "mixed convs", "mixed chain"
+    );
+    let f32_plan =
+        PrecisionPlan::resolve([(SlotPrecision::Fixed(SymbolicSampleSize::Sample32), true); 4]);
+    let mixed_plan = PrecisionPlan::resolve([
+        (SlotPrecision::Auto, true),
+        (SlotPrecision::Auto, false),
+        (SlotPrecision::Auto, true),
+        (SlotPrecision::Auto, false),
+    ]);
+    for (channels, frames) in [(2, 64), (2, 256), (16, 256)] {
+        let mut wide = BoundProcessData::<f64>::new(channels, channels, frames);
+        let mut narrow = BoundProcessData::<f32>::new(channels, channels, frames);
+        let mut narrow_b = BoundProcessData::<f32>::new(channels, channels, frames);
+        let f32_time = time(|| {
+            // Three hand-offs between four slots.
+            pass_block(&narrow, &mut narrow_b, frames);
+            pass_block(&narrow_b, &mut narrow, frames);
+            pass_block(&narrow, &mut narrow_b, frames);
+            black_box(&mut narrow_b);
+        });
+        let mixed_time = time(|| {
+            pass_block(&wide, &mut narrow, frames);
+            pass_block(&narrow, &mut wide, frames);
+            pass_block(&wide, &mut narrow, frames);
+            black_box(&mut narrow);
+        });
+        println!(
+            "{channels:>8} {frames:>6} {:>12} {f32_time:>12?} {:>12} {mixed_time:>12?}",
+            f32_plan.conversions(),
+            mixed_plan.conversions()
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! Cheap checks on plugin output, for validation and for watching a live stream.
+
+use crate::limiter::Sample;
+
+/// What [`analyze_block`] found in a block of samples.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BlockAnalysis {
+    /// NaN and infinite samples.
+    pub non_finite: usize,
+    /// Index of the first of them.
+    pub first_non_finite: Option<usize>,
+    /// Largest absolute finite sample.
+    pub peak: f64,
+}
+
+impl BlockAnalysis {
+    pub fn is_finite(&self) -> bool {
+        self.non_finite == 0
+    }
+}
+
+/// Scan a block (planar or interleaved) in one pass, without allocating.
+pub fn analyze_block<T: Sample>(samples: &[T]) -> BlockAnalysis {
+    let mut found = BlockAnalysis::default();
+    for (i, s) in samples.iter().enumerate() {
+        let x = s.to_f64();
+        if x.is_finite() {
+            found.peak = found.peak.max(x.abs());
+        } else {
+            found.non_finite += 1;
+            found.first_non_finite.get_or_insert(i);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analyze_block_counts_non_finite_samples_and_finite_peak() {
+        let clean = analyze_block(&[0.25f32, -0.75, 0.5]);
+        assert!(clean.is_finite());
+        assert_eq!(clean.peak, 0.75);
+
+        let broken = analyze_block(&[0.1f64, f64::NAN, -2.0, f64::NEG_INFINITY]);
+        assert_eq!(broken.non_finite, 2);
+        assert_eq!(broken.first_non_finite, Some(1));
+        assert_eq!(broken.peak, 2.0);
+        assert_eq!(analyze_block::<f32>(&[]), BlockAnalysis::default());
+    }
+}
@@ -0,0 +1,396 @@
+//! Speaker arrangements by name, for command lines and log output.
+//!
+//! [`parse_arrangement`] takes a layout name (`stereo`, `5.1`, `7.1.4`, `ambi2` …),
+//! a bare channel count (`6ch`, laid out as [`arrangement_for_channels`]) or raw hex
+//! with a `0x` prefix. [`format_arrangement`] goes the other way, falling back to a
+//! channel count and then to hex. [`format_speakers`] lists a layout's speakers.
+//!
+//! When a plugin refuses a `setBusArrangements`, all it says is `kResultFalse`.
+//! [`explain_arrangement_mismatch`] asks it what it has instead, bus by bus, and which
+//! layout nearest the requested one it would take.
+//!
+//! The ambisonic layouts are ACN channel order with SN3D normalization, the only ones
+//! VST 3 defines. They share channel counts with speaker layouts (16 for both `ambi3`
+//! and a 16-speaker bus), so they are told apart by arrangement, never by count.
+
+use std::fmt;
+
+use openvst3_abi::speaker_arr::*;
+use openvst3_abi::{BUS_DIR_INPUT, BUS_DIR_OUTPUT};
+
+use crate::render::arrangement_for_channels;
+use crate::{HostError, HostLimits, ProcessorHandle};
+
+/// Named layouts: the name [`format_arrangement`] prints, then other accepted names.
+pub const NAMED_ARRANGEMENTS: &[(&str, &[&str], SpeakerArrangement)] = &[
+    ("mono", &["1.0"], MONO),
+    ("stereo", &["2.0"], STEREO),
+    ("3.0", &["lcr"], CINE_30),
+    ("quad", &["4.0"], QUAD),
+    ("5.0", &[], SURROUND_50),
+    ("5.1", &[], SURROUND_51),
+    ("7.1", &[], SURROUND_71),
+    ("7.1-cine", &[], SURROUND_71_CINE),
+    ("7.1.4", &[], SURROUND_71_4),
+    ("ambi1", &["foa"], AMBI_1ST_ORDER_ACN),
+    ("ambi2", &[], AMBI_2ND_ORDER_ACN),
+    ("ambi3", &[], AMBI_3RD_ORDER_ACN),
+];
+
+/// Parse a layout name, `<n>ch` or `0x<hex>`; names are case-insensitive.
+pub fn parse_arrangement(s: &str) -> Result<SpeakerArrangement, HostError> {
+    let name = s.trim().to_ascii_lowercase();
+    if let Some(&(_, _, arr)) = NAMED_ARRANGEMENTS
+        .iter()
+        .find(|(canonical, aliases, _)| *canonical == name || aliases.contains(&name.as_str()))
+    {
+        return Ok(arr);
+    }
+    if let Some(hex) = name.strip_prefix("0x") {
+        return u64::from_str_radix(hex, 16)
+            .map_err(|_| HostError::InvalidArrangement(format!("`{s}` is not hex")));
+    }
+    if let Some(count) = name.strip_suffix("ch") {
+        return match count.parse::<usize>() {
+            Ok(n) if n <= 64 => Ok(arrangement_for_channels(n)),
+            _ => Err(HostError::InvalidArrangement(format!(
+                "`{s}`: expected 0ch to 64ch"
+            ))),
+        };
+    }
+    let names: Vec<&str> = NAMED_ARRANGEMENTS.iter().map(|(n, _, _)| *n).collect();
+    Err(HostError::InvalidArrangement(format!(
+        "`{s}`: expected {}, <n>ch or 0x<hex>",
+        names.join(", ")
+    )))
+}
+
+/// The layout's name, else `<n>ch` for a plain layout, else hex.
+pub fn format_arrangement(arr: SpeakerArrangement) -> String {
+    if let Some((name, _, _)) = NAMED_ARRANGEMENTS.iter().find(|(_, _, a)| *a == arr) {
+        return (*name).to_string();
+    }
+    let count = channel_count(arr);
+    if arr == arrangement_for_channels(count) {
+        format!("{count}ch")
+    } else {
+        format!("{arr:#x}")
+    }
+}
+
+/// Names of the speaker bits below the ambisonic ones, in bit order.
+const SPEAKER_NAMES: [&str; 20] = [
+    "L", "R", "C", "LFE", "Ls", "Rs", "Lc", "Rc", "S", "Sl", "Sr", "Tc", "Tfl", "Tfc", "Tfr",
+    "Trl", "Trc", "Trr", "LFE2", "M",
+];
+
+/// The speakers of `arr` in bit order, e.g. `L R C LFE Ls Rs` for 5.1. Ambisonic
+/// channels are `ACN<n>`, bits without a speaker `bit<n>`.
+pub fn format_speakers(arr: SpeakerArrangement) -> String {
+    let names: Vec<String> = (0..64)
+        .filter(|bit| arr & (1u64 << bit) != 0)
+        .map(|bit| match bit {
+            0..=19 => SPEAKER_NAMES[bit as usize].to_string(),
+            20..=23 => format!("ACN{}", bit - 20),
+            // SPEAKER_ACN4 is bit 38.
+            38.. => format!("ACN{}", bit - 34),
+            _ => format!("bit{bit}"),
+        })
+        .collect();
+    names.join(" ")
+}
+
+/// `5.1 (L R C LFE Ls Rs)`, or `ambi3 (3rd-order ambisonics, ACN/SN3D)`.
+fn describe(arr: SpeakerArrangement) -> String {
+    let detail = match ambisonic_order(arr) {
+        Some(order) => format!(
+            "{}-order ambisonics, ACN/SN3D",
+            ["1st", "2nd", "3rd"][order as usize - 1]
+        ),
+        None => format_speakers(arr),
+    };
+    format!("{} ({detail})", format_arrangement(arr))
+}
+
+/// One audio bus of an [`ArrangementReport`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BusArrangement {
+    /// `BUS_DIR_INPUT` or `BUS_DIR_OUTPUT`.
+    pub direction: i32,
+    pub index: usize,
+    /// What the host asked for; `None` for a bus left out of the request.
+    pub requested: Option<SpeakerArrangement>,
+    /// What `getBusArrangement` reads back; `None` when it fails, as it does for a
+    /// bus the plugin does not have.
+    pub plugin: Option<SpeakerArrangement>,
+    /// For a mismatched bus, the layout nearest the requested channel count that
+    /// the plugin accepts there.
+    pub suggested: Option<SpeakerArrangement>,
+}
+
+impl BusArrangement {
+    /// Whether the plugin has a different layout than the one asked for.
+    pub fn is_mismatch(&self) -> bool {
+        self.requested.is_some() && self.requested != self.plugin
+    }
+}
+
+impl fmt::Display for BusArrangement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = if self.direction == BUS_DIR_INPUT {
+            "input"
+        } else {
+            "output"
+        };
+        write!(f, "{direction} bus {}: ", self.index)?;
+        match (self.requested, self.plugin) {
+            (Some(requested), Some(plugin)) if requested == plugin => {
+                return write!(f, "{}", describe(plugin));
+            }
+            (Some(requested), _) => write!(f, "requested {}, ", describe(requested))?,
+            (None, _) => write!(f, "not requested, ")?,
+        }
+        match self.plugin {
+            Some(plugin) => write!(f, "plugin has {}", describe(plugin))?,
+            None => write!(f, "plugin reports no arrangement")?,
+        }
+        match self.suggested {
+            Some(suggested) => write!(f, "; nearest it accepts: {}", describe(suggested)),
+            None if self.is_mismatch() && self.plugin.is_some() => {
+                write!(f, "; it accepts no other layout here")
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// What [`explain_arrangement_mismatch`] found: one line per bus when printed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArrangementReport {
+    pub inputs: Vec<BusArrangement>,
+    pub outputs: Vec<BusArrangement>,
+}
+
+impl ArrangementReport {
+    /// The buses whose layout is not the requested one, inputs first.
+    pub fn mismatches(&self) -> impl Iterator<Item = &BusArrangement> {
+        self.inputs
+            .iter()
+            .chain(&self.outputs)
+            .filter(|bus| bus.is_mismatch())
+    }
+}
+
+impl fmt::Display for ArrangementReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, bus) in self.inputs.iter().chain(&self.outputs).enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{bus}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Explain why `processor` refused `requested_in` and `requested_out`: read back
+/// every audio bus's arrangement with `getBusArrangement`, and for each bus that
+/// differs, try the requested layout and then the named ones nearest its channel
+/// count with `setBusArrangements`, the other buses kept at the plugin's layouts.
+/// The plugin's own layouts are set again at the end.
+///
+/// Call it on an inactive processor, like `setBusArrangements` itself.
+pub fn explain_arrangement_mismatch(
+    processor: &ProcessorHandle,
+    requested_in: &[SpeakerArrangement],
+    requested_out: &[SpeakerArrangement],
+) -> ArrangementReport {
+    let mut report = ArrangementReport {
+        inputs: read_back(processor, BUS_DIR_INPUT, requested_in),
+        outputs: read_back(processor, BUS_DIR_OUTPUT, requested_out),
+    };
+    if !processor.supports_set_bus_arrangements() {
+        return report;
+    }
+    // Only buses the plugin has take part in the probes.
+    let layouts = |buses: &[BusArrangement]| -> Vec<SpeakerArrangement> {
+        buses.iter().map_while(|bus| bus.plugin).collect()
+    };
+    let current = [layouts(&report.inputs), layouts(&report.outputs)];
+    let mut probed = false;
+    for side in 0..2 {
+        let buses = if side == 0 {
+            &mut report.inputs
+        } else {
+            &mut report.outputs
+        };
+        for bus in buses.iter_mut() {
+            let (Some(requested), true) = (bus.requested, bus.index < current[side].len()) else {
+                continue;
+            };
+            if !bus.is_mismatch() {
+                continue;
+            }
+            probed = true;
+            bus.suggested = nearest_layouts(requested, bus.plugin)
+                .into_iter()
+                .find(|&candidate| {
+                    let mut probe = current.clone();
+                    probe[side][bus.index] = candidate;
+                    processor.set_bus_arrangements(&probe[0], &probe[1]).is_ok()
+                });
+        }
+    }
+    if probed {
+        let _ = processor.set_bus_arrangements(&current[0], &current[1]);
+    }
+    report
+}
+
+/// The buses in `direction` up to the last one that is requested or answers
+/// `getBusArrangement`.
+fn read_back(
+    processor: &ProcessorHandle,
+    direction: i32,
+    requested: &[SpeakerArrangement],
+) -> Vec<BusArrangement> {
+    let limit = HostLimits::default().max_buses.max(requested.len());
+    let mut buses = Vec::new();
+    for index in 0..limit {
+        let plugin = processor.bus_arrangement(direction, index as i32).ok();
+        let wanted = requested.get(index).copied();
+        if plugin.is_none() && wanted.is_none() {
+            break;
+        }
+        buses.push(BusArrangement {
+            direction,
+            index,
+            requested: wanted,
+            plugin,
+            suggested: None,
+        });
+    }
+    buses
+}
+
+/// `requested`, the plugin's layout and the named ones, nearest `requested`'s
+/// channel count first; of two as near, one that is ambisonic if and only if
+/// `requested` is, then the one with fewer channels.
+fn nearest_layouts(
+    requested: SpeakerArrangement,
+    plugin: Option<SpeakerArrangement>,
+) -> Vec<SpeakerArrangement> {
+    let mut layouts = vec![requested];
+    let named = NAMED_ARRANGEMENTS.iter().map(|&(_, _, arr)| arr);
+    for arr in plugin.into_iter().chain(named) {
+        if !layouts.contains(&arr) {
+            layouts.push(arr);
+        }
+    }
+    let wanted = channel_count(requested);
+    let ambisonic = ambisonic_order(requested).is_some();
+    layouts.sort_by_key(|&arr| {
+        let count = channel_count(arr);
+        let other_family = ambisonic_order(arr).is_some() != ambisonic;
+        (count.abs_diff(wanted), other_family, count)
+    });
+    layouts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrangement_names_parse_to_speaker_constants() {
+        use openvst3_abi::speaker_arr::*;
+        let table: &[(&str, u64)] = &[
+            ("mono", MONO),
+            ("1.0", MONO),
+            ("stereo", STEREO),
+            ("2.0", STEREO),
+            ("3.0", CINE_30),
+            ("lcr", CINE_30),
+            ("quad", QUAD),
+            ("4.0", QUAD),
+            ("5.0", SURROUND_50),
+            ("5.1", SURROUND_51),
+            ("7.1", SURROUND_71),
+            ("7.1-cine", SURROUND_71_CINE),
+            ("7.1.4", SURROUND_71_4),
+            ("ambi1", AMBI_1ST_ORDER_ACN),
+            ("foa", AMBI_1ST_ORDER_ACN),
+            ("ambi2", AMBI_2ND_ORDER_ACN),
+            ("ambi3", AMBI_3RD_ORDER_ACN),
+            ("Stereo", STEREO),
+            ("1ch", MONO),
+            ("2ch", STEREO),
+            ("6ch", SURROUND_51),
+            ("0ch", EMPTY),
+            ("0x3", STEREO),
+            ("0X80000", MONO),
+        ];
+        for &(name, arr) in table {
+            assert_eq!(parse_arrangement(name).unwrap(), arr, "{name}");
+        }
+        let named = crate::arrangement::NAMED_ARRANGEMENTS;
+        let accepted: Vec<&str> = named
+            .iter()
+            .flat_map(|(name, aliases, _)| core::iter::once(name).chain(aliases.iter()))
+            .copied()
+            .collect();
+        assert!(accepted.iter().all(|n| table.iter().any(|(t, _)| t == n)));
+        for &(name, _, arr) in named {
+            assert_eq!(format_arrangement(arr), name);
+        }
+        assert_eq!(channel_count(AMBI_3RD_ORDER_ACN), 16);
+        assert_eq!(format_arrangement(0x7f), "7ch");
+        assert_eq!(format_arrangement(SPEAKER_L | SPEAKER_C), "0x5");
+        for bad in ["", "5.2", "65ch", "0xzz"] {
+            assert!(matches!(
+                parse_arrangement(bad),
+                Err(HostError::InvalidArrangement(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn speakers_are_named_in_bit_order() {
+        use openvst3_abi::speaker_arr::*;
+        assert_eq!(format_speakers(SURROUND_51), "L R C LFE Ls Rs");
+        assert_eq!(format_speakers(MONO), "M");
+        assert_eq!(
+            format_speakers(AMBI_2ND_ORDER_ACN).rsplit(' ').next(),
+            Some("ACN8")
+        );
+        assert_eq!(format_speakers(1 << 30), "bit30");
+    }
+
+    #[test]
+    fn ambisonic_layouts_are_told_apart_from_speakers_by_arrangement() {
+        use crate::render::{arrangement_for_channels, arrangement_for_channels_like};
+        use openvst3_abi::speaker_arr::*;
+
+        for order in 1..=3 {
+            let arr = ambisonic_arrangement(order).unwrap();
+            assert_eq!(ambisonic_order(arr), Some(order));
+            assert_eq!(channel_count(arr), ((order + 1) * (order + 1)) as usize);
+        }
+        assert_eq!(ambisonic_order(SPEAKER_ACN0 | SPEAKER_ACN1), None);
+        assert_eq!(ambisonic_order(arrangement_for_channels(16)), None);
+        assert_eq!(AMBI_3RD_ORDER_ACN & SPEAKER_ACN15, SPEAKER_ACN15);
+
+        let ambi = AMBI_3RD_ORDER_ACN;
+        assert_eq!(arrangement_for_channels_like(16, ambi), ambi);
+        assert_eq!(arrangement_for_channels_like(9, ambi), AMBI_2ND_ORDER_ACN);
+        assert_eq!(arrangement_for_channels_like(6, ambi), SURROUND_51);
+        assert_eq!(
+            arrangement_for_channels_like(16, arrangement_for_channels(16)),
+            arrangement_for_channels(16)
+        );
+        assert_eq!(
+            arrangement_for_channels_like(4, STEREO),
+            arrangement_for_channels(4)
+        );
+    }
+}
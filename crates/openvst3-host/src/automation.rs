@@ -0,0 +1,314 @@
+//! Recording parameter automation from a controller's edits.
+//!
+//! An edit controller reports what the user does with its parameters through the
+//! host's `IComponentHandler`: `beginEdit` when a knob is grabbed, `performEdit` for
+//! every value on the way, `endEdit` when it is let go. An [`AutomationRecorder`]
+//! attached to a [`ComponentHandler`] with
+//! [`ComponentHandler::track_automation`] keeps those calls as [`Gesture`]s, placed
+//! in the sample time of the audio stream.
+//!
+//! The edits arrive on the UI thread, stamped with the recorder's [`Clock`]; the
+//! audio thread relates that clock to sample time by handing the recorder each
+//! block's `ProcessContext` with [`sync`](AutomationRecorder::sync), from a
+//! [`SampleClock`](crate::SampleClock) or [`Transport`](crate::Transport) running on
+//! the same clock. The placement is then as steady as the context's `systemTime`.
+//!
+//! [`automation`](AutomationRecorder::automation) turns the recording into the
+//! breakpoints [`RenderEvents::automation`](crate::RenderEvents::automation) takes, so
+//! an offline render replays the performance; with the `serde` feature,
+//! [`lanes`](AutomationRecorder::lanes) gives the session-file form. The value holds
+//! between gestures instead of ramping from one to the next.
+//!
+//! [`ComponentHandler`]: crate::ComponentHandler
+//! [`ComponentHandler::track_automation`]: crate::ComponentHandler::track_automation
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use openvst3_abi::{process_context_consts as ctx, ParamID, ParamValue, ProcessContext};
+
+use crate::timing::{Clock, MonotonicClock};
+#[cfg(feature = "serde")]
+use crate::{AutomationLane, AutomationPoint};
+
+/// No block has been synced yet.
+const UNSYNCED: u64 = u64::MAX;
+
+/// One `beginEdit` .. `endEdit` of a parameter, in samples from the start of the
+/// stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gesture {
+    pub param: ParamID,
+    /// Where `beginEdit` came, or the edit for one that came without it.
+    pub start: usize,
+    /// Where `endEdit` came; `None` while the parameter is still held.
+    pub end: Option<usize>,
+    /// Every `performEdit` value, in order.
+    pub points: Vec<(usize, ParamValue)>,
+}
+
+/// See the module docs.
+pub struct AutomationRecorder {
+    clock: Box<dyn Clock>,
+    sample_rate: f64,
+    /// `f64` bits: the clock's time at sample 0, or `UNSYNCED`.
+    origin: AtomicU64,
+    gestures: Mutex<Vec<Gesture>>,
+}
+
+impl fmt::Debug for AutomationRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AutomationRecorder")
+            .field("sample_rate", &self.sample_rate)
+            .field("gestures", &self.lock().len())
+            .finish()
+    }
+}
+
+impl AutomationRecorder {
+    /// A recorder for a stream at `sample_rate`, on the [`MonotonicClock`].
+    pub fn new(sample_rate: f64) -> Self {
+        Self {
+            clock: Box::new(MonotonicClock::new()),
+            sample_rate,
+            origin: AtomicU64::new(UNSYNCED),
+            gestures: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Stamp edits with `clock`, the one the stream's contexts take `systemTime` from.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Relate the clock to sample time from the `context` of the block about to be
+    /// processed; contexts without both the system and the continuous time are
+    /// ignored. Realtime-safe. Edits before the first sync land on sample 0.
+    pub fn sync(&self, context: &ProcessContext) {
+        let valid = ctx::K_SYSTEM_TIME_VALID | ctx::K_CONT_TIME_VALID;
+        if context.state & valid != valid || self.sample_rate <= 0.0 {
+            return;
+        }
+        let origin = context.system_time as f64
+            - context.continuous_time_samples as f64 * 1e9 / self.sample_rate;
+        self.origin.store(origin.to_bits(), Ordering::Release);
+    }
+
+    /// Nothing panics while holding the lock, so a poisoned one is still consistent.
+    fn lock(&self) -> MutexGuard<'_, Vec<Gesture>> {
+        self.gestures.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The sample the clock is at now.
+    fn now(&self) -> usize {
+        let origin = self.origin.load(Ordering::Acquire);
+        if origin == UNSYNCED {
+            return 0;
+        }
+        let ns = self.clock.now_ns() as f64 - f64::from_bits(origin);
+        (ns * self.sample_rate / 1e9).round().max(0.0) as usize
+    }
+
+    /// Take in `beginEdit(id)`. A parameter already held stays in its gesture.
+    pub fn begin_edit(&self, id: ParamID) {
+        let at = self.now();
+        let mut gestures = self.lock();
+        if open(&mut gestures, id).is_none() {
+            gestures.push(Gesture {
+                param: id,
+                start: at,
+                end: None,
+                points: Vec::new(),
+            });
+        }
+    }
+
+    /// Take in `performEdit(id, value)`. An edit outside a gesture makes one of its
+    /// own.
+    pub fn perform_edit(&self, id: ParamID, value: ParamValue) {
+        let at = self.now();
+        let mut gestures = self.lock();
+        match open(&mut gestures, id) {
+            Some(gesture) => gesture.points.push((at, value)),
+            None => gestures.push(Gesture {
+                param: id,
+                start: at,
+                end: Some(at),
+                points: vec![(at, value)],
+            }),
+        }
+    }
+
+    /// Take in `endEdit(id)`; one without a `beginEdit` is ignored.
+    pub fn end_edit(&self, id: ParamID) {
+        let at = self.now();
+        if let Some(gesture) = open(&mut self.lock(), id) {
+            gesture.end = Some(at);
+        }
+    }
+
+    /// The gestures so far, in the order they began.
+    pub fn gestures(&self) -> Vec<Gesture> {
+        self.lock().clone()
+    }
+
+    /// Forget everything recorded.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// The recording as breakpoints per parameter, sorted by id and frame, for
+    /// [`RenderEvents::automation`](crate::RenderEvents::automation). Each gesture
+    /// after a parameter's first starts with a point holding the value the last one
+    /// left, so the value only moves while the parameter is held. Edits on the same
+    /// frame keep the last value.
+    pub fn automation(&self) -> Vec<(ParamID, Vec<(usize, ParamValue)>)> {
+        let mut curves: BTreeMap<ParamID, Vec<(usize, ParamValue)>> = BTreeMap::new();
+        for gesture in self.lock().iter() {
+            if gesture.points.is_empty() {
+                continue;
+            }
+            let curve = curves.entry(gesture.param).or_default();
+            if let Some(&(_, held)) = curve.last() {
+                push_point(curve, gesture.start, held);
+            }
+            for &(frame, value) in &gesture.points {
+                push_point(curve, frame, value);
+            }
+        }
+        curves.into_iter().collect()
+    }
+
+    /// [`automation`](Self::automation) as session-file lanes, in seconds.
+    #[cfg(feature = "serde")]
+    pub fn lanes(&self) -> Vec<AutomationLane> {
+        self.automation()
+            .into_iter()
+            .map(|(param, points)| AutomationLane {
+                param,
+                points: points
+                    .into_iter()
+                    .map(|(frame, value)| AutomationPoint {
+                        time: frame as f64 / self.sample_rate,
+                        value,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// The gesture holding `id`, if any.
+fn open(gestures: &mut [Gesture], id: ParamID) -> Option<&mut Gesture> {
+    gestures
+        .iter_mut()
+        .rev()
+        .find(|g| g.param == id && g.end.is_none())
+}
+
+/// Append a breakpoint, never before the last one.
+fn push_point(curve: &mut Vec<(usize, ParamValue)>, frame: usize, value: ParamValue) {
+    match curve.last_mut() {
+        Some(last) if frame <= last.0 => last.1 = value,
+        _ => curve.push((frame, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timing::tests::FakeClock;
+    use crate::{ComponentHandler, SampleClock};
+    use openvst3_abi::K_RESULT_OK;
+
+    #[test]
+    fn automation_recorder_keeps_gestures_in_sample_time() {
+        use std::sync::Arc;
+        let clock = FakeClock::default();
+        let mut samples = SampleClock::with_clock(48_000.0, clock.clone());
+        let recorder =
+            Arc::new(AutomationRecorder::new(48_000.0).with_clock(samples.clock().clone()));
+        let handler = ComponentHandler::new(|_| {});
+        handler.track_automation(&recorder);
+        let raw = handler.as_icomponent_handler();
+        let ms = |n: u64| clock.advance(n * 1_000_000);
+
+        // Before the first block there is no sample time yet.
+        unsafe { (*raw).perform_edit(9, 0.5) };
+        assert_eq!(recorder.gestures()[0].start, 0);
+        recorder.clear();
+
+        // 1 ms per 48 samples; the first block starts at 1 ms.
+        ms(1);
+        recorder.sync(&samples.next_block(480));
+        unsafe {
+            ms(5);
+            (*raw).begin_edit(3);
+            ms(1);
+            (*raw).perform_edit(3, 0.25);
+            ms(1);
+            (*raw).perform_edit(3, 0.5);
+            ms(1);
+            (*raw).end_edit(3);
+            ms(2);
+            recorder.sync(&samples.next_block(480));
+            ms(1);
+            // An edit without a gesture, and an endEdit without a beginEdit.
+            (*raw).perform_edit(7, 1.0);
+            (*raw).end_edit(7);
+            ms(1);
+            (*raw).begin_edit(3);
+            ms(1);
+            (*raw).perform_edit(3, 0.75);
+            (*raw).perform_edit(3, 0.8);
+        }
+        assert_eq!(
+            recorder.gestures(),
+            [
+                Gesture {
+                    param: 3,
+                    start: 240,
+                    end: Some(384),
+                    points: vec![(288, 0.25), (336, 0.5)],
+                },
+                Gesture {
+                    param: 7,
+                    start: 528,
+                    end: Some(528),
+                    points: vec![(528, 1.0)],
+                },
+                Gesture {
+                    param: 3,
+                    start: 576,
+                    end: None,
+                    points: vec![(624, 0.75), (624, 0.8)],
+                },
+            ]
+        );
+        // The value holds from the end of one gesture to the start of the next.
+        assert_eq!(
+            recorder.automation(),
+            [
+                (3, vec![(288, 0.25), (336, 0.5), (576, 0.5), (624, 0.8)]),
+                (7, vec![(528, 1.0)]),
+            ]
+        );
+        #[cfg(feature = "serde")]
+        {
+            let lanes = recorder.lanes();
+            assert_eq!(lanes[0].param, 3);
+            assert_eq!(lanes[0].points[2].time, 0.012);
+        }
+
+        // A dropped recorder is skipped.
+        drop(recorder);
+        unsafe { assert_eq!((*raw).perform_edit(3, 0.0), K_RESULT_OK) };
+    }
+}
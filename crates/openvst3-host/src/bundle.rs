@@ -0,0 +1,376 @@
+//! Turn a built plugin library into a `.vst3` bundle.
+//!
+//! [`package`] lays out `<name>.vst3/Contents/<arch>/<name><ext>` the way
+//! [`BundlePath::resolve`](crate::BundlePath::resolve) and other hosts look for it,
+//! and optionally writes `Contents/Resources/moduleinfo.json` and the macOS
+//! `Contents/Info.plist`.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use openvst3_abi::FactoryFlags;
+
+use crate::{fmt_cid_hex, ClassInfo, FactoryInfo, HostError, Module};
+
+/// The `Contents` subfolder holding this platform's binary.
+pub fn platform_arch_dir() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "MacOS"
+    } else if cfg!(target_os = "windows") {
+        if cfg!(target_arch = "x86_64") {
+            "x86_64-win"
+        } else {
+            "x86-win"
+        }
+    } else if cfg!(target_arch = "x86_64") {
+        "x86_64-linux"
+    } else if cfg!(target_arch = "aarch64") {
+        "aarch64-linux"
+    } else {
+        "unknown-linux"
+    }
+}
+
+/// The binary's file name inside the `arch` folder: macOS bundles use no extension.
+fn binary_name(name: &str, arch: &str) -> String {
+    if arch == "MacOS" {
+        name.to_string()
+    } else if arch.ends_with("-win") {
+        format!("{name}.dll")
+    } else {
+        format!("{name}.so")
+    }
+}
+
+/// The SDK's `moduleinfo.json`: what a host may show without loading the binary.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModuleInfo {
+    pub name: String,
+    pub version: String,
+    pub factory: FactoryInfo,
+    pub classes: Vec<ClassInfo>,
+}
+
+impl ModuleInfo {
+    /// Describe `module`'s factory. Classes whose info cannot be read are left out.
+    pub fn from_module(module: &Module, name: &str, version: &str) -> Result<Self, HostError> {
+        Ok(Self {
+            name: name.into(),
+            version: version.into(),
+            factory: module.factory_info()?,
+            classes: module.classes().filter_map(Result::ok).cloned().collect(),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        let flags = self.factory.flags();
+        let mut out = String::new();
+        out.push_str("{\n");
+        let _ = writeln!(out, "  \"Name\": {},", json_str(&self.name));
+        let _ = writeln!(out, "  \"Version\": {},", json_str(&self.version));
+        out.push_str("  \"Factory Info\": {\n");
+        let _ = writeln!(out, "    \"Vendor\": {},", json_str(&self.factory.vendor));
+        let _ = writeln!(out, "    \"URL\": {},", json_str(&self.factory.url));
+        let _ = writeln!(out, "    \"E-Mail\": {},", json_str(&self.factory.email));
+        out.push_str("    \"Flags\": {\n");
+        let _ = writeln!(
+            out,
+            "      \"Unicode\": {},",
+            flags.contains(FactoryFlags::UNICODE)
+        );
+        let _ = writeln!(
+            out,
+            "      \"Classes Discardable\": {},",
+            flags.contains(FactoryFlags::CLASSES_DISCARDABLE)
+        );
+        let _ = writeln!(
+            out,
+            "      \"Component Non Discardable\": {}",
+            flags.contains(FactoryFlags::COMPONENT_NON_DISCARDABLE)
+        );
+        out.push_str("    }\n  },\n");
+        out.push_str("  \"Compatibility\": [],\n");
+        out.push_str("  \"Classes\": [");
+        for (i, class) in self.classes.iter().enumerate() {
+            out.push_str(if i == 0 { "\n" } else { ",\n" });
+            let subs: Vec<String> = class.sub_category_tags().map(json_str).collect();
+            out.push_str("    {\n");
+            let _ = writeln!(out, "      \"CID\": \"{}\",", fmt_cid_hex(&class.cid));
+            let _ = writeln!(out, "      \"Category\": {},", json_str(&class.category));
+            let _ = writeln!(out, "      \"Name\": {},", json_str(&class.name));
+            let _ = writeln!(out, "      \"Vendor\": {},", json_str(&class.vendor));
+            let _ = writeln!(out, "      \"Version\": {},", json_str(&class.version));
+            let _ = writeln!(
+                out,
+                "      \"SDKVersion\": {},",
+                json_str(&class.sdk_version)
+            );
+            let _ = writeln!(out, "      \"Sub Categories\": [{}],", subs.join(", "));
+            let _ = writeln!(out, "      \"Class Flags\": {},", class.class_flags);
+            let _ = writeln!(
+                out,
+                "      \"Cardinality\": {},",
+                class.cardinality.as_raw()
+            );
+            out.push_str("      \"Snapshots\": []\n    }");
+        }
+        out.push_str(if self.classes.is_empty() {
+            "]\n}\n"
+        } else {
+            "\n  ]\n}\n"
+        });
+        out
+    }
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// `Info.plist` entries of a macOS bundle.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlistFields {
+    /// `CFBundleIdentifier`, e.g. `com.example.gain`.
+    pub identifier: String,
+    /// `CFBundleShortVersionString` and `CFBundleVersion`.
+    pub version: String,
+    /// `NSHumanReadableCopyright`; omitted when empty.
+    pub copyright: String,
+}
+
+impl PlistFields {
+    fn to_xml(&self, executable: &str) -> String {
+        let mut entries = vec![
+            ("CFBundleDevelopmentRegion", "English"),
+            ("CFBundleExecutable", executable),
+            ("CFBundleIdentifier", self.identifier.as_str()),
+            ("CFBundleInfoDictionaryVersion", "6.0"),
+            ("CFBundleName", executable),
+            ("CFBundlePackageType", "BNDL"),
+            ("CFBundleShortVersionString", self.version.as_str()),
+            ("CFBundleSignature", "????"),
+            ("CFBundleVersion", self.version.as_str()),
+        ];
+        if !self.copyright.is_empty() {
+            entries.push(("NSHumanReadableCopyright", self.copyright.as_str()));
+        }
+        let mut out = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+             \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+             <plist version=\"1.0\">\n<dict>\n",
+        );
+        for (key, value) in entries {
+            let _ = writeln!(
+                out,
+                "\t<key>{key}</key>\n\t<string>{}</string>",
+                xml_escape(value)
+            );
+        }
+        out.push_str("</dict>\n</plist>\n");
+        out
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// How [`package`] builds a bundle.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PackageOptions {
+    /// Bundle and binary name, without `.vst3`.
+    pub name: String,
+    /// `Contents` subfolder to use instead of [`platform_arch_dir`], to package for
+    /// another platform; the binary's extension follows it.
+    pub arch_override: Option<String>,
+    pub module_info: Option<ModuleInfo>,
+    /// Also writes `Contents/PkgInfo`.
+    pub macos_plist: Option<PlistFields>,
+    /// Link to the binary instead of copying it, so rebuilds show up in the bundle.
+    /// Only on Unix; elsewhere the binary is copied.
+    pub symlink: bool,
+}
+
+impl PackageOptions {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            ..Self::default()
+        }
+    }
+}
+
+fn io_error(path: &Path) -> impl FnOnce(std::io::Error) -> HostError + '_ {
+    move |e| HostError::Io(format!("{}: {e}", path.display()))
+}
+
+/// Build `out_dir/<name>.vst3` around `binary` and return its path. An existing
+/// bundle of that name is updated in place: files this writes are replaced, others
+/// are kept.
+pub fn package(
+    binary: impl AsRef<Path>,
+    out_dir: impl AsRef<Path>,
+    options: &PackageOptions,
+) -> Result<PathBuf, HostError> {
+    let binary = binary.as_ref();
+    let name = options.name.as_str();
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return Err(HostError::InvalidBundle(format!(
+            "bad bundle name `{name}`"
+        )));
+    }
+    if !binary.is_file() {
+        return Err(HostError::Io(format!("{}: not a file", binary.display())));
+    }
+    let bundle = out_dir.as_ref().join(format!("{name}.vst3"));
+    let contents = bundle.join("Contents");
+    let arch = options
+        .arch_override
+        .as_deref()
+        .unwrap_or(platform_arch_dir());
+    let bin_dir = contents.join(arch);
+    std::fs::create_dir_all(&bin_dir).map_err(io_error(&bin_dir))?;
+
+    let target = bin_dir.join(binary_name(name, arch));
+    if target.symlink_metadata().is_ok() {
+        std::fs::remove_file(&target).map_err(io_error(&target))?;
+    }
+    install_binary(binary, &target, options.symlink)?;
+
+    if let Some(info) = &options.module_info {
+        let resources = contents.join("Resources");
+        std::fs::create_dir_all(&resources).map_err(io_error(&resources))?;
+        let path = resources.join("moduleinfo.json");
+        std::fs::write(&path, info.to_json()).map_err(io_error(&path))?;
+    }
+    if let Some(plist) = &options.macos_plist {
+        let path = contents.join("Info.plist");
+        std::fs::write(&path, plist.to_xml(name)).map_err(io_error(&path))?;
+        let path = contents.join("PkgInfo");
+        std::fs::write(&path, "BNDL????").map_err(io_error(&path))?;
+    }
+    Ok(bundle)
+}
+
+#[cfg(unix)]
+fn install_binary(binary: &Path, target: &Path, symlink: bool) -> Result<(), HostError> {
+    if symlink {
+        let source = binary.canonicalize().map_err(io_error(binary))?;
+        return std::os::unix::fs::symlink(source, target).map_err(io_error(target));
+    }
+    std::fs::copy(binary, target)
+        .map(drop)
+        .map_err(io_error(target))
+}
+
+#[cfg(not(unix))]
+fn install_binary(binary: &Path, target: &Path, _symlink: bool) -> Result<(), HostError> {
+    std::fs::copy(binary, target)
+        .map(drop)
+        .map_err(io_error(target))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cardinality, Encoding};
+
+    fn class_info(name: &str) -> ClassInfo {
+        ClassInfo {
+            index: 0,
+            cid: *b"0123456789abcdef",
+            cardinality: Cardinality::ManyInstances,
+            category: "Audio Module Class".into(),
+            name: name.into(),
+            name_encoding: Encoding::Ascii,
+            class_flags: 1,
+            sub_categories: "Fx|Delay".into(),
+            vendor: "Ex".into(),
+            version: "1.0.0".into(),
+            sdk_version: "VST 3.7".into(),
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "file system")]
+    fn foreign_arch_layout_and_metadata() {
+        let dir = std::env::temp_dir().join(format!("openvst3-package-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let binary = dir.join("built.bin");
+        std::fs::write(&binary, b"binary").unwrap();
+
+        let options = PackageOptions {
+            arch_override: Some("x86_64-win".into()),
+            module_info: Some(ModuleInfo {
+                name: "Echo".into(),
+                version: "1.2".into(),
+                factory: FactoryInfo {
+                    vendor: "Ex".into(),
+                    url: String::new(),
+                    email: String::new(),
+                    flags: FactoryFlags::UNICODE.bits(),
+                },
+                classes: vec![class_info("Echo \"Tape\"")],
+            }),
+            macos_plist: Some(PlistFields {
+                identifier: "com.example.echo".into(),
+                version: "1.2".into(),
+                copyright: "A & B".into(),
+            }),
+            ..PackageOptions::new("Echo")
+        };
+        let bundle = package(&binary, &dir, &options).unwrap();
+        assert_eq!(bundle, dir.join("Echo.vst3"));
+        let contents = bundle.join("Contents");
+        assert_eq!(
+            std::fs::read(contents.join("x86_64-win/Echo.dll")).unwrap(),
+            b"binary"
+        );
+        let json = std::fs::read_to_string(contents.join("Resources/moduleinfo.json")).unwrap();
+        assert!(json.contains(r#""Name": "Echo \"Tape\"""#), "{json}");
+        assert!(
+            json.contains(r#""Sub Categories": ["Fx", "Delay"]"#),
+            "{json}"
+        );
+        assert!(json.contains(r#""Unicode": true"#), "{json}");
+        assert!(json.contains(&format!(r#""CID": "{}""#, fmt_cid_hex(b"0123456789abcdef"))));
+        let plist = std::fs::read_to_string(contents.join("Info.plist")).unwrap();
+        assert!(
+            plist.contains("<string>com.example.echo</string>"),
+            "{plist}"
+        );
+        assert!(plist.contains("<string>A &amp; B</string>"), "{plist}");
+
+        // Packaging again replaces the binary.
+        std::fs::write(&binary, b"rebuilt").unwrap();
+        package(&binary, &dir, &options).unwrap();
+        assert_eq!(
+            std::fs::read(contents.join("x86_64-win/Echo.dll")).unwrap(),
+            b"rebuilt"
+        );
+        assert!(matches!(
+            package(&binary, &dir, &PackageOptions::new("a/b")),
+            Err(HostError::InvalidBundle(_))
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
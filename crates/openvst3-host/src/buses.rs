@@ -0,0 +1,240 @@
+//! The audio and event buses a component declares, and activating them.
+//!
+//! Instruments take their notes through an event bus; some plugins ignore events on
+//! a bus the host never activated, so [`PluginInstance::activate`] first activates
+//! every bus flagged `kDefaultActive`, audio and event alike.
+//!
+//! Channel counts come straight from the plugin. [`ComponentHandle::checked_bus_layout`]
+//! holds audio buses to [`HostLimits`] before anything is allocated for them.
+//!
+//! [`PluginInstance::activate`]: crate::PluginInstance::activate
+
+use openvst3_abi::{
+    tresult, BusInfo, BUS_DIR_INPUT, BUS_DIR_OUTPUT, BUS_FLAG_DEFAULT_ACTIVE, K_RESULT_OK,
+    MEDIA_TYPE_AUDIO, MEDIA_TYPE_EVENT,
+};
+
+use std::fmt;
+
+use crate::plugin::{check, optional};
+use crate::{ComponentHandle, HostError, HostLimits, Limit, LimitOrigin};
+
+/// Which bus: media type, direction and index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BusId {
+    pub media_type: i32,
+    pub direction: i32,
+    pub index: i32,
+}
+
+impl fmt::Display for BusId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let media = match self.media_type {
+            MEDIA_TYPE_AUDIO => "audio",
+            MEDIA_TYPE_EVENT => "event",
+            _ => "unknown media",
+        };
+        let direction = match self.direction {
+            BUS_DIR_INPUT => "input",
+            BUS_DIR_OUTPUT => "output",
+            _ => "unknown direction",
+        };
+        write!(f, "{media} {direction} bus {}", self.index)
+    }
+}
+
+/// What [`ComponentHandle::checked_bus_layout`] does with an audio bus outside the
+/// [`HostLimits`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BusCheck {
+    /// Fail with [`HostError::SuspiciousBusInfo`].
+    #[default]
+    Strict,
+    /// Clamp the channel count into range and keep the reported one in
+    /// [`BusDesc::clamped_from`].
+    Lenient,
+}
+
+/// One bus as `getBusInfo` describes it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BusDesc {
+    pub index: i32,
+    pub name: String,
+    /// Audio channels, or MIDI channels for an event bus.
+    pub channel_count: i32,
+    /// `BUS_TYPE_MAIN` or `BUS_TYPE_AUX`.
+    pub bus_type: i32,
+    pub default_active: bool,
+    /// The channel count the plugin reported, when a lenient
+    /// [`checked_bus_layout`](ComponentHandle::checked_bus_layout) had to clamp it.
+    pub clamped_from: Option<i32>,
+}
+
+impl BusDesc {
+    fn from_info(index: i32, info: &BusInfo) -> Self {
+        let name: Vec<u8> = info
+            .name
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        Self {
+            index,
+            name: String::from_utf8_lossy(&name).into_owned(),
+            channel_count: info.channel_count,
+            bus_type: info.bus_type,
+            default_active: info.flags & BUS_FLAG_DEFAULT_ACTIVE != 0,
+            clamped_from: None,
+        }
+    }
+}
+
+/// Every bus of a component, by media type and direction.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BusLayout {
+    pub audio_inputs: Vec<BusDesc>,
+    pub audio_outputs: Vec<BusDesc>,
+    pub event_inputs: Vec<BusDesc>,
+    pub event_outputs: Vec<BusDesc>,
+}
+
+impl BusLayout {
+    /// The buses of one media type and direction; none for unknown ones.
+    pub fn buses(&self, media_type: i32, direction: i32) -> &[BusDesc] {
+        match (media_type, direction) {
+            (MEDIA_TYPE_AUDIO, BUS_DIR_INPUT) => &self.audio_inputs,
+            (MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT) => &self.audio_outputs,
+            (MEDIA_TYPE_EVENT, BUS_DIR_INPUT) => &self.event_inputs,
+            (MEDIA_TYPE_EVENT, BUS_DIR_OUTPUT) => &self.event_outputs,
+            _ => &[],
+        }
+    }
+
+    /// `(media type, direction, bus)` for every bus, audio first, inputs before
+    /// outputs.
+    pub fn iter(&self) -> impl Iterator<Item = (i32, i32, &BusDesc)> {
+        [
+            (MEDIA_TYPE_AUDIO, BUS_DIR_INPUT),
+            (MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT),
+            (MEDIA_TYPE_EVENT, BUS_DIR_INPUT),
+            (MEDIA_TYPE_EVENT, BUS_DIR_OUTPUT),
+        ]
+        .into_iter()
+        .flat_map(move |(media, dir)| self.buses(media, dir).iter().map(move |b| (media, dir, b)))
+    }
+
+    /// The buses a lenient check clamped; none after a strict one.
+    pub fn clamped(&self) -> impl Iterator<Item = BusId> + '_ {
+        self.iter()
+            .filter(|(_, _, b)| b.clamped_from.is_some())
+            .map(|(media_type, direction, b)| BusId {
+                media_type,
+                direction,
+                index: b.index,
+            })
+    }
+}
+
+impl ComponentHandle {
+    pub fn activate_bus(
+        &self,
+        media_type: i32,
+        direction: i32,
+        index: i32,
+        state: bool,
+    ) -> Result<(), HostError> {
+        check(self.activate_bus_raw(media_type, direction, index, state))
+    }
+
+    /// [`activate_bus`](Self::activate_bus) with the plugin's answer as is.
+    pub(crate) fn activate_bus_raw(
+        &self,
+        media_type: i32,
+        direction: i32,
+        index: i32,
+        state: bool,
+    ) -> tresult {
+        traced!(
+            "IComponent",
+            "activateBus",
+            unsafe { (*self.as_ptr()).activate_bus(media_type, direction, index, state) },
+            media_type = media_type,
+            direction = direction,
+            bus_index = index,
+            state = state
+        )
+    }
+
+    /// Read every audio and event bus; the first unreadable one fails the layout.
+    pub fn bus_layout(&self) -> Result<BusLayout, HostError> {
+        self.read_layout(&HostLimits::unlimited())
+    }
+
+    /// [`bus_layout`](Self::bus_layout), refusing more than `limits.max_buses` buses
+    /// of a kind before reading any.
+    fn read_layout(&self, limits: &HostLimits) -> Result<BusLayout, HostError> {
+        let read = |media_type, direction| {
+            let count = self.bus_count(media_type, direction).max(0) as usize;
+            limits.check(Limit::Buses, count, LimitOrigin::Plugin)?;
+            (0..count as i32)
+                .map(|i| {
+                    self.bus_info(media_type, direction, i)
+                        .map(|info| BusDesc::from_info(i, &info))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(BusLayout {
+            audio_inputs: read(MEDIA_TYPE_AUDIO, BUS_DIR_INPUT)?,
+            audio_outputs: read(MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT)?,
+            event_inputs: read(MEDIA_TYPE_EVENT, BUS_DIR_INPUT)?,
+            event_outputs: read(MEDIA_TYPE_EVENT, BUS_DIR_OUTPUT)?,
+        })
+    }
+
+    /// [`bus_layout`](Self::bus_layout) with the bus counts and every audio bus's
+    /// channel count held to `limits`; too many buses fail in either mode. Event
+    /// buses' channels are not checked: their count is MIDI channels, and nothing is
+    /// allocated per channel.
+    pub fn checked_bus_layout(
+        &self,
+        limits: &HostLimits,
+        mode: BusCheck,
+    ) -> Result<BusLayout, HostError> {
+        let mut layout = self.read_layout(limits)?;
+        for (direction, buses) in [
+            (BUS_DIR_INPUT, &mut layout.audio_inputs),
+            (BUS_DIR_OUTPUT, &mut layout.audio_outputs),
+        ] {
+            for bus in buses {
+                let id = BusId {
+                    media_type: MEDIA_TYPE_AUDIO,
+                    direction,
+                    index: bus.index,
+                };
+                if let Err(e) = limits.check_channels(id, bus.channel_count) {
+                    if mode == BusCheck::Strict {
+                        return Err(e);
+                    }
+                    bus.clamped_from = Some(bus.channel_count);
+                    bus.channel_count = limits.clamp_channels(bus.channel_count);
+                }
+            }
+        }
+        Ok(layout)
+    }
+
+    /// Activate every bus flagged `kDefaultActive`. A plugin may decline with
+    /// `kNotImplemented` or `kResultFalse`; returns how many buses it accepted.
+    pub fn activate_default_buses(&self) -> Result<usize, HostError> {
+        let layout = self.bus_layout()?;
+        let mut activated = 0;
+        for (media_type, direction, bus) in layout.iter().filter(|(_, _, b)| b.default_active) {
+            let tr = self.activate_bus_raw(media_type, direction, bus.index, true);
+            optional("IComponent", "activateBus", tr)?;
+            if tr == K_RESULT_OK {
+                activated += 1;
+            }
+        }
+        Ok(activated)
+    }
+}
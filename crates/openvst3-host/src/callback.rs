@@ -0,0 +1,56 @@
+//! Panic containment for the host objects plugins call into.
+//!
+//! Every vtable function of a host-implemented object ([`EventList`],
+//! [`ParameterChanges`], [`MemoryStream`], [`PlugFrame`], [`ComponentHandler`]) runs
+//! its body through `contain`. A panic must not unwind into the plugin, so it is
+//! caught there: its message is kept for [`take_last_callback_panic`] on the thread
+//! that made the call, the object is marked poisoned and the plugin gets an error
+//! result (`kInternalError` where the method returns a `tresult`). A poisoned object
+//! fails every later call the same way without running it.
+//!
+//! [`EventList`]: crate::EventList
+//! [`ParameterChanges`]: crate::ParameterChanges
+//! [`MemoryStream`]: crate::MemoryStream
+//! [`PlugFrame`]: crate::PlugFrame
+//! [`ComponentHandler`]: crate::ComponentHandler
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+thread_local! {
+    static LAST_PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// The message of the latest panic caught in a host object called on this thread,
+/// clearing it.
+pub fn take_last_callback_panic() -> Option<String> {
+    LAST_PANIC.with(|last| last.borrow_mut().take())
+}
+
+fn message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "panic with a non-string payload".to_string()
+    }
+}
+
+/// Run `body` unless `poisoned` is set; a panic sets it and yields `failed`.
+pub(crate) fn contain<R>(poisoned: &AtomicBool, failed: R, body: impl FnOnce() -> R) -> R {
+    if poisoned.load(Ordering::Acquire) {
+        return failed;
+    }
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(result) => result,
+        Err(payload) => {
+            poisoned.store(true, Ordering::Release);
+            let msg = message(payload.as_ref());
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(msg));
+            failed
+        }
+    }
+}
@@ -0,0 +1,256 @@
+//! Mapping planar audio between two channel layouts.
+//!
+//! A [`ChannelMap`] is a gain matrix from source to destination channels. Built from
+//! two speaker arrangements it routes each source speaker to the same speaker when
+//! the destination has it and folds it down otherwise:
+//!
+//! - mono is duplicated to left and right (or goes to the center, if there is one);
+//! - left and right sum into mono at -3 dB each;
+//! - center goes to left and right at -3 dB (ITU-R BS.775 downmix);
+//! - surrounds go to their side's front at -3 dB, or into mono at -6 dB;
+//! - LFE, height and ambisonic channels with no match are dropped.
+//!
+//! If that leaves the destination silent (e.g. ambisonics to stereo), channel `n`
+//! simply feeds channel `n`. [`ChannelMap::from_matrix`] takes explicit gains instead.
+
+use openvst3_abi::speaker_arr::*;
+
+use crate::limiter::Sample;
+use crate::render::arrangement_for_channels;
+use crate::HostError;
+
+/// -3 dB.
+const HALF_POWER: f64 = core::f64::consts::FRAC_1_SQRT_2;
+
+/// A source channel and its gain.
+type Tap = (usize, f64);
+
+/// See the module docs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelMap {
+    src_channels: usize,
+    /// Per destination channel, the source channels feeding it and their gains.
+    rows: Box<[Box<[Tap]>]>,
+    identity: bool,
+}
+
+/// Channel index of `speaker` in `arr`, if present.
+fn index_of(arr: SpeakerArrangement, speaker: u64) -> Option<usize> {
+    (arr & speaker != 0).then(|| (arr & (speaker - 1)).count_ones() as usize)
+}
+
+/// Where a source speaker missing from the destination goes: `(speaker, gain)`
+/// candidates, of which the first group fully present in the destination is used.
+fn fold_down(speaker: u64) -> &'static [&'static [(u64, f64)]] {
+    const L: &[(u64, f64)] = &[(SPEAKER_L, HALF_POWER)];
+    const R: &[(u64, f64)] = &[(SPEAKER_R, HALF_POWER)];
+    const LS: &[(u64, f64)] = &[(SPEAKER_LS, 1.0)];
+    const RS: &[(u64, f64)] = &[(SPEAKER_RS, 1.0)];
+    const M_HALF_POWER: &[(u64, f64)] = &[(SPEAKER_M, HALF_POWER)];
+    const M_HALF: &[(u64, f64)] = &[(SPEAKER_M, 0.5)];
+    match speaker {
+        SPEAKER_M => &[&[(SPEAKER_C, 1.0)], &[(SPEAKER_L, 1.0), (SPEAKER_R, 1.0)]],
+        SPEAKER_L | SPEAKER_R => &[M_HALF_POWER],
+        SPEAKER_C => &[
+            &[(SPEAKER_L, HALF_POWER), (SPEAKER_R, HALF_POWER)],
+            &[(SPEAKER_M, 1.0)],
+        ],
+        SPEAKER_LS | SPEAKER_LC => &[L, M_HALF],
+        SPEAKER_RS | SPEAKER_RC => &[R, M_HALF],
+        SPEAKER_SL => &[LS, L, M_HALF],
+        SPEAKER_SR => &[RS, R, M_HALF],
+        _ => &[],
+    }
+}
+
+impl ChannelMap {
+    /// The default mapping from the `src` to the `dst` arrangement.
+    pub fn from_arrangements(src: SpeakerArrangement, dst: SpeakerArrangement) -> Self {
+        let (src_channels, dst_channels) = (channel_count(src), channel_count(dst));
+        if src == dst {
+            return Self::identity(src_channels);
+        }
+        let mut rows = vec![Vec::new(); dst_channels];
+        for (s, speaker) in (0..64)
+            .map(|bit| 1u64 << bit)
+            .filter(|speaker| src & speaker != 0)
+            .enumerate()
+        {
+            if let Some(d) = index_of(dst, speaker) {
+                rows[d].push((s, 1.0));
+                continue;
+            }
+            let group = fold_down(speaker)
+                .iter()
+                .find(|group| group.iter().all(|&(to, _)| dst & to != 0));
+            for &(to, gain) in group.into_iter().flat_map(|g| g.iter()) {
+                rows[index_of(dst, to).expect("group is present")].push((s, gain));
+            }
+        }
+        if rows.iter().all(Vec::is_empty) {
+            for (d, row) in rows.iter_mut().enumerate().take(src_channels) {
+                row.push((d, 1.0));
+            }
+        }
+        Self::from_rows(src_channels, rows)
+    }
+
+    /// The default mapping between plain buses of `src` and `dst` channels, taken as
+    /// [`arrangement_for_channels`] layouts (so 6 channels are 5.1).
+    pub fn from_counts(src: usize, dst: usize) -> Self {
+        Self::from_arrangements(arrangement_for_channels(src), arrangement_for_channels(dst))
+    }
+
+    /// Explicit gains: one row per destination channel, each with a gain per source
+    /// channel.
+    pub fn from_matrix(matrix: &[impl AsRef<[f64]>]) -> Result<Self, HostError> {
+        let src_channels = matrix.first().map_or(0, |row| row.as_ref().len());
+        let mut rows = Vec::with_capacity(matrix.len());
+        for (d, row) in matrix.iter().enumerate() {
+            let row = row.as_ref();
+            if row.len() != src_channels {
+                return Err(HostError::RaggedChannelMatrix {
+                    row: d,
+                    got: row.len(),
+                    expected: src_channels,
+                });
+            }
+            rows.push(
+                row.iter()
+                    .enumerate()
+                    .filter(|(_, &gain)| gain != 0.0)
+                    .map(|(s, &gain)| (s, gain))
+                    .collect(),
+            );
+        }
+        Ok(Self::from_rows(src_channels, rows))
+    }
+
+    fn identity(channels: usize) -> Self {
+        Self::from_rows(channels, (0..channels).map(|c| vec![(c, 1.0)]).collect())
+    }
+
+    fn from_rows(src_channels: usize, rows: Vec<Vec<Tap>>) -> Self {
+        let identity = rows.len() == src_channels
+            && rows
+                .iter()
+                .enumerate()
+                .all(|(d, row)| row.as_slice() == [(d, 1.0)]);
+        Self {
+            src_channels,
+            rows: rows.into_iter().map(Vec::into_boxed_slice).collect(),
+            identity,
+        }
+    }
+
+    pub fn src_channels(&self) -> usize {
+        self.src_channels
+    }
+
+    pub fn dst_channels(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Whether every channel passes through unchanged.
+    pub fn is_identity(&self) -> bool {
+        self.identity
+    }
+
+    /// Gain from source channel `src` to destination channel `dst`.
+    pub fn gain(&self, dst: usize, src: usize) -> f64 {
+        self.rows[dst]
+            .iter()
+            .find(|&&(s, _)| s == src)
+            .map_or(0.0, |&(_, gain)| gain)
+    }
+
+    /// Write the first `frames` samples of each destination channel from the source
+    /// channels, summed with [`dsp::mix_add`](crate::dsp::mix_add) for 32-bit
+    /// samples. Channels either side does not have are skipped.
+    pub fn apply<T: Sample>(
+        &self,
+        src: &[impl AsRef<[T]>],
+        dst: &mut [impl AsMut<[T]>],
+        frames: usize,
+    ) {
+        for (row, out) in self.rows.iter().zip(dst.iter_mut()) {
+            let out = &mut out.as_mut()[..frames];
+            if let &[(s, gain)] = &**row {
+                if gain == 1.0 {
+                    if let Some(chan) = src.get(s) {
+                        out.copy_from_slice(&chan.as_ref()[..frames]);
+                        continue;
+                    }
+                }
+            }
+            out.fill(T::from_f64(0.0));
+            for &(s, gain) in row.iter() {
+                if let Some(chan) = src.get(s) {
+                    T::mix_add(&chan.as_ref()[..frames], out, gain);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn channel_map_defaults_fold_common_layouts() {
+        use openvst3_abi::speaker_arr;
+        let h = core::f64::consts::FRAC_1_SQRT_2;
+
+        let up = ChannelMap::from_counts(1, 2);
+        let mut stereo = [vec![0.0f32; 4], vec![0.0; 4]];
+        up.apply(&[[0.5f32; 4]], &mut stereo, 4);
+        assert_eq!(stereo, [vec![0.5; 4], vec![0.5; 4]]);
+
+        let down = ChannelMap::from_arrangements(speaker_arr::STEREO, speaker_arr::MONO);
+        let mut mono = [vec![0.0f64; 2]];
+        down.apply(&[[1.0f64, 0.5], [1.0, -0.5]], &mut mono, 2);
+        assert!((mono[0][0] - 2.0 * h).abs() < 1e-12);
+        assert!(mono[0][1].abs() < 1e-12);
+
+        // ITU 5.1 to stereo: L R C LFE Ls Rs; the LFE is dropped.
+        let itu = ChannelMap::from_arrangements(speaker_arr::SURROUND_51, speaker_arr::STEREO);
+        assert_eq!(
+            [0, 1, 2, 3, 4, 5].map(|s| itu.gain(0, s)),
+            [1.0, 0.0, h, 0.0, h, 0.0]
+        );
+        assert_eq!(
+            [0, 1, 2, 3, 4, 5].map(|s| itu.gain(1, s)),
+            [0.0, 1.0, h, 0.0, 0.0, h]
+        );
+        assert_eq!(ChannelMap::from_counts(6, 2), itu);
+
+        assert!(ChannelMap::from_counts(2, 2).is_identity());
+        // Nothing in common: channel n feeds channel n.
+        let ambi =
+            ChannelMap::from_arrangements(speaker_arr::AMBI_1ST_ORDER_ACN, speaker_arr::STEREO);
+        assert_eq!(
+            (ambi.gain(0, 0), ambi.gain(1, 1), ambi.gain(0, 2)),
+            (1.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn channel_map_from_matrix_checks_rows() {
+        let swap = ChannelMap::from_matrix(&[[0.0, 1.0], [1.0, 0.0]]).unwrap();
+        let mut out = [[0.0f32; 3], [0.0; 3]];
+        swap.apply(&[[1.0f32; 3], [2.0; 3]], &mut out, 3);
+        assert_eq!(out, [[2.0; 3], [1.0; 3]]);
+        assert!(!swap.is_identity());
+
+        let ragged: [&[f64]; 2] = [&[1.0, 0.0], &[1.0]];
+        assert!(matches!(
+            ChannelMap::from_matrix(&ragged),
+            Err(HostError::RaggedChannelMatrix {
+                row: 1,
+                got: 1,
+                expected: 2
+            })
+        ));
+    }
+}
@@ -0,0 +1,514 @@
+//! Factory info, class enumeration with `PClassInfo2` detail, CID lookup and
+//! category filters.
+
+use core::ffi::c_void;
+use std::collections::HashMap;
+use std::fmt;
+
+use openvst3_abi::{
+    classinfo_consts, factory_info_consts, iids, ClassFlags, FactoryFlags, IPluginFactory2,
+    PClassInfo, PClassInfo2, PFactoryInfo, Tuid, K_RESULT_OK,
+};
+
+use crate::{
+    cstr_from_i8_fixed, decode_fixed_cstr, ClassReadError, Encoding, HostError, InterfacePtr,
+    Module, ScanPhase,
+};
+
+/// `PClassInfo::cardinality`: how many instances of a class may be alive at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Cardinality {
+    /// kManyInstances: no limit.
+    ManyInstances,
+    /// Any other value, kept as reported. The SDK defines no other constant, so
+    /// values below 1 (often a field left zeroed) count as 1.
+    Limited(i32),
+}
+
+impl Cardinality {
+    pub fn from_raw(raw: i32) -> Self {
+        match raw {
+            classinfo_consts::K_MANY_INSTANCES => Cardinality::ManyInstances,
+            n => Cardinality::Limited(n),
+        }
+    }
+
+    pub fn as_raw(self) -> i32 {
+        match self {
+            Cardinality::ManyInstances => classinfo_consts::K_MANY_INSTANCES,
+            Cardinality::Limited(n) => n,
+        }
+    }
+
+    /// How many instances may be alive at once; `None` for no limit.
+    pub fn max_instances(self) -> Option<usize> {
+        match self {
+            Cardinality::ManyInstances => None,
+            Cardinality::Limited(n) => Some(n.max(1) as usize),
+        }
+    }
+}
+
+impl fmt::Display for Cardinality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max_instances() {
+            None => f.write_str("many instances"),
+            Some(1) => f.write_str("single instance"),
+            Some(n) => write!(f, "at most {n} instances"),
+        }
+    }
+}
+
+/// One factory class. The v2 fields are empty (and `class_flags` 0) when the factory
+/// only implements `IPluginFactory`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClassInfo {
+    pub index: i32,
+    pub cid: [u8; 16],
+    pub cardinality: Cardinality,
+    pub category: String,
+    pub name: String,
+    /// How `name` was decoded; `Lossy` names contain U+FFFD.
+    pub name_encoding: Encoding,
+    pub class_flags: u32,
+    /// `|`-separated tags, e.g. `"Fx|Delay"`.
+    pub sub_categories: String,
+    pub vendor: String,
+    pub version: String,
+    pub sdk_version: String,
+}
+
+impl ClassInfo {
+    /// The individual subcategory tags.
+    pub fn sub_category_tags(&self) -> impl Iterator<Item = &str> {
+        self.sub_categories
+            .split('|')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+    }
+
+    /// Whether `tag` is one of the subcategory tags (ASCII case-insensitive).
+    pub fn has_sub_category(&self, tag: &str) -> bool {
+        self.sub_category_tags()
+            .any(|t| t.eq_ignore_ascii_case(tag))
+    }
+
+    pub fn flags(&self) -> ClassFlags {
+        ClassFlags::from_bits_retain(self.class_flags)
+    }
+
+    pub fn parsed_sub_categories(&self) -> SubCategories {
+        SubCategories::parse(&self.sub_categories)
+    }
+
+    pub fn is_instrument(&self) -> bool {
+        self.parsed_sub_categories()
+            .contains(&SubCategory::Instrument)
+    }
+
+    /// Audio processor class not tagged `Instrument` (v1-only classes count as effects).
+    pub fn is_effect(&self) -> bool {
+        self.category == classinfo_consts::K_VST_AUDIO_EFFECT_CLASS && !self.is_instrument()
+    }
+
+    pub fn supports_distribution(&self) -> bool {
+        self.flags().contains(ClassFlags::DISTRIBUTABLE)
+    }
+
+    pub fn supports_simple_mode(&self) -> bool {
+        self.flags().contains(ClassFlags::SIMPLE_MODE_SUPPORTED)
+    }
+
+    /// Decode the `getClassInfo` entry at factory `index`.
+    pub fn from_v1(index: i32, info: &PClassInfo) -> Self {
+        let (name, name_encoding) = decode_fixed_cstr(&info.name);
+        Self {
+            index,
+            cid: cid_bytes(&info.cid),
+            cardinality: Cardinality::from_raw(info.cardinality),
+            category: cstr_from_i8_fixed(&info.category),
+            name,
+            name_encoding,
+            class_flags: 0,
+            sub_categories: String::new(),
+            vendor: String::new(),
+            version: String::new(),
+            sdk_version: String::new(),
+        }
+    }
+
+    /// Decode the `getClassInfo2` entry at factory `index`.
+    pub fn from_v2(index: i32, info: &PClassInfo2) -> Self {
+        let (name, name_encoding) = decode_fixed_cstr(&info.name);
+        Self {
+            index,
+            cid: cid_bytes(&info.cid),
+            cardinality: Cardinality::from_raw(info.cardinality),
+            category: cstr_from_i8_fixed(&info.category),
+            name,
+            name_encoding,
+            class_flags: info.class_flags,
+            sub_categories: cstr_from_i8_fixed(&info.sub_categories),
+            vendor: cstr_from_i8_fixed(&info.vendor),
+            version: cstr_from_i8_fixed(&info.version),
+            sdk_version: cstr_from_i8_fixed(&info.sdk_version),
+        }
+    }
+}
+
+/// `PFactoryInfo`: who made the plugin and how its factory may be treated.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FactoryInfo {
+    pub vendor: String,
+    pub url: String,
+    pub email: String,
+    pub flags: i32,
+}
+
+impl FactoryInfo {
+    pub fn flags(&self) -> FactoryFlags {
+        FactoryFlags::from_bits_retain(self.flags)
+    }
+}
+
+fn cid_bytes(cid: &[i8; 16]) -> [u8; 16] {
+    cid.map(|b| b as u8)
+}
+
+/// Result of reading one factory class.
+pub type ClassEntry = Result<ClassInfo, ClassReadError>;
+
+/// Lazily built class table of a module, one entry per factory index.
+pub(crate) struct ClassIndex {
+    entries: Vec<ClassEntry>,
+    by_cid: HashMap<[u8; 16], usize>,
+}
+
+impl ClassIndex {
+    fn build(module: &Module) -> Self {
+        let factory = module.factory.as_ptr();
+        let factory2 = unsafe {
+            crate::query_interface(factory as *mut c_void, iids::IPLUGIN_FACTORY2.0)
+                .ok()
+                .and_then(|raw| InterfacePtr::from_raw(raw as *mut IPluginFactory2))
+        };
+        let count = unsafe { (*factory).count_classes() };
+        let entries: Vec<ClassEntry> = (0..count)
+            .map(|index| {
+                crate::crash_marker::enter(ScanPhase::Class(index as u32));
+                match factory2.as_ref().and_then(|f2| read_v2(f2, index)) {
+                    Some(info) => Ok(info),
+                    None => read_v1(module, index),
+                }
+            })
+            .collect();
+        let by_cid = entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, e)| e.as_ref().ok().map(|c| (c.cid, i)))
+            .collect();
+        Self { entries, by_cid }
+    }
+}
+
+fn read_v2(factory2: &InterfacePtr<IPluginFactory2>, index: i32) -> Option<ClassInfo> {
+    let mut info = PClassInfo2 {
+        cid: [0; 16],
+        cardinality: 0,
+        category: [0; classinfo_consts::K_CATEGORY_SIZE],
+        name: [0; classinfo_consts::K_NAME_SIZE],
+        class_flags: 0,
+        sub_categories: [0; classinfo_consts::K_SUBCATS_SIZE],
+        vendor: [0; classinfo_consts::K_VENDOR_SIZE],
+        version: [0; classinfo_consts::K_VERSION_SIZE],
+        sdk_version: [0; classinfo_consts::K_VERSION_SIZE],
+    };
+    let tr = unsafe { (*factory2.as_ptr()).get_class_info2(index, &mut info) };
+    (tr == K_RESULT_OK).then(|| ClassInfo::from_v2(index, &info))
+}
+
+fn read_v1(module: &Module, index: i32) -> ClassEntry {
+    let mut info = PClassInfo {
+        cid: [0; 16],
+        cardinality: 0,
+        category: [0; classinfo_consts::K_CATEGORY_SIZE],
+        name: [0; classinfo_consts::K_NAME_SIZE],
+    };
+    let tr = unsafe { (*module.factory.as_ptr()).get_class_info(index, &mut info) };
+    if tr != K_RESULT_OK {
+        return Err(ClassReadError {
+            index,
+            source: HostError::TErr(tr),
+        });
+    }
+    Ok(ClassInfo::from_v1(index, &info))
+}
+
+impl Module {
+    pub fn factory_info(&self) -> Result<FactoryInfo, HostError> {
+        crate::crash_marker::enter(ScanPhase::Factory);
+        let mut info = PFactoryInfo {
+            vendor: [0; factory_info_consts::K_NAME_SIZE],
+            url: [0; factory_info_consts::K_URL_SIZE],
+            email: [0; factory_info_consts::K_EMAIL_SIZE],
+            flags: 0,
+        };
+        let tr = unsafe { (*self.factory.as_ptr()).get_factory_info(&mut info) };
+        if tr != K_RESULT_OK {
+            return Err(HostError::TErr(tr));
+        }
+        Ok(FactoryInfo {
+            vendor: cstr_from_i8_fixed(&info.vendor),
+            url: cstr_from_i8_fixed(&info.url),
+            email: cstr_from_i8_fixed(&info.email),
+            flags: info.flags,
+        })
+    }
+
+    fn class_index(&self) -> &ClassIndex {
+        self.class_index.get_or_init(|| ClassIndex::build(self))
+    }
+
+    /// Every factory class in index order, including the ones whose info could not be
+    /// read. Built once and cached.
+    pub fn classes(&self) -> impl Iterator<Item = Result<&ClassInfo, &ClassReadError>> {
+        self.class_index().entries.iter().map(Result::as_ref)
+    }
+
+    /// Readable classes accepted by `filter`.
+    pub fn classes_matching<'a>(
+        &'a self,
+        filter: &'a ClassFilter,
+    ) -> impl Iterator<Item = &'a ClassInfo> {
+        self.classes()
+            .filter_map(Result::ok)
+            .filter(move |c| filter.matches(c))
+    }
+
+    pub fn find_class_by_cid(&self, cid: &Tuid) -> Option<ClassInfo> {
+        let index = self.class_index();
+        let entry = &index.entries[*index.by_cid.get(&cid.0)?];
+        entry.as_ref().ok().cloned()
+    }
+}
+
+/// Composable predicate over [`ClassInfo`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ClassFilter {
+    Any,
+    /// Exact `category` match.
+    Category(String),
+    /// One of the `|`-separated subcategory tags equals this (ASCII case-insensitive).
+    SubCategory(String),
+    And(Box<ClassFilter>, Box<ClassFilter>),
+    Or(Box<ClassFilter>, Box<ClassFilter>),
+    Not(Box<ClassFilter>),
+}
+
+impl ClassFilter {
+    pub fn category(category: &str) -> Self {
+        Self::Category(category.to_string())
+    }
+
+    pub fn sub_category(tag: &str) -> Self {
+        Self::SubCategory(tag.to_string())
+    }
+
+    /// Audio processor classes that are not tagged `Instrument` (see [`ClassInfo::is_effect`]).
+    pub fn audio_effects() -> Self {
+        Self::category(classinfo_consts::K_VST_AUDIO_EFFECT_CLASS)
+            .and(Self::sub_category("Instrument").not())
+    }
+
+    /// Audio processor classes tagged `Instrument`.
+    pub fn instruments() -> Self {
+        Self::category(classinfo_consts::K_VST_AUDIO_EFFECT_CLASS)
+            .and(Self::sub_category("Instrument"))
+    }
+
+    pub fn controllers() -> Self {
+        Self::category(classinfo_consts::K_VST_COMPONENT_CONTROLLER_CLASS)
+    }
+
+    pub fn and(self, other: Self) -> Self {
+        Self::And(Box::new(self), Box::new(other))
+    }
+
+    pub fn or(self, other: Self) -> Self {
+        Self::Or(Box::new(self), Box::new(other))
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(self) -> Self {
+        Self::Not(Box::new(self))
+    }
+
+    pub fn matches(&self, class: &ClassInfo) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Category(c) => class.category == *c,
+            Self::SubCategory(t) => class.has_sub_category(t),
+            Self::And(a, b) => a.matches(class) && b.matches(class),
+            Self::Or(a, b) => a.matches(class) || b.matches(class),
+            Self::Not(f) => !f.matches(class),
+        }
+    }
+}
+
+// ----- Subcategories --------------------------------------------------------------
+
+macro_rules! sub_categories {
+    ($($variant:ident => $tag:literal,)*) => {
+        /// A well-known VST3 subcategory tag (`PlugType`), or any other tag verbatim.
+        #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum SubCategory {
+            $($variant,)*
+            Other(String),
+        }
+
+        impl SubCategory {
+            /// Known tags match ASCII case-insensitively.
+            pub fn parse(tag: &str) -> Self {
+                $(if tag.eq_ignore_ascii_case($tag) {
+                    return Self::$variant;
+                })*
+                Self::Other(tag.to_string())
+            }
+
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(Self::$variant => $tag,)*
+                    Self::Other(tag) => tag,
+                }
+            }
+        }
+    };
+}
+
+sub_categories! {
+    Fx => "Fx",
+    Instrument => "Instrument",
+    Spatial => "Spatial",
+    Analyzer => "Analyzer",
+    Delay => "Delay",
+    Distortion => "Distortion",
+    Dynamics => "Dynamics",
+    Eq => "EQ",
+    Filter => "Filter",
+    Generator => "Generator",
+    Mastering => "Mastering",
+    Modulation => "Modulation",
+    PitchShift => "Pitch Shift",
+    Restoration => "Restoration",
+    Reverb => "Reverb",
+    Surround => "Surround",
+    Tools => "Tools",
+    Network => "Network",
+    Drum => "Drum",
+    External => "External",
+    Piano => "Piano",
+    Sampler => "Sampler",
+    Synth => "Synth",
+    Mono => "Mono",
+    Stereo => "Stereo",
+    Ambisonics => "Ambisonics",
+    UpDownMix => "Up-Downmix",
+    OnlyRealTime => "OnlyRT",
+    OnlyOfflineProcess => "OnlyOfflineProcess",
+    NoOfflineProcess => "NoOfflineProcess",
+}
+
+impl fmt::Display for SubCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parsed `PClassInfo2::subCategories`, in declaration order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SubCategories(pub Vec<SubCategory>);
+
+impl SubCategories {
+    /// Split on `|`, trimming whitespace and dropping empty tags.
+    pub fn parse(s: &str) -> Self {
+        Self(
+            s.split('|')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(SubCategory::parse)
+                .collect(),
+        )
+    }
+
+    pub fn contains(&self, tag: &SubCategory) -> bool {
+        self.0.contains(tag)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SubCategory> {
+        self.0.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for SubCategories {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, tag) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str("|")?;
+            }
+            f.write_str(tag.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+/// Human-readable flag names, e.g. `"distributable, simple mode"`; empty if none.
+pub fn describe_class_flags(flags: ClassFlags) -> String {
+    let mut names = Vec::new();
+    if flags.contains(ClassFlags::DISTRIBUTABLE) {
+        names.push("distributable".to_string());
+    }
+    if flags.contains(ClassFlags::SIMPLE_MODE_SUPPORTED) {
+        names.push("simple mode".to_string());
+    }
+    let unknown = flags.bits() & !ClassFlags::all().bits();
+    if unknown != 0 {
+        names.push(format!("{unknown:#x}"));
+    }
+    names.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sub_categories_parse_real_world_strings() {
+        use SubCategory::*;
+        let cases: &[(&str, &[SubCategory])] = &[
+            ("Fx|Delay", &[Fx, Delay]),
+            ("Instrument|Synth", &[Instrument, Synth]),
+            ("Fx|EQ|Mono", &[Fx, Eq, Mono]),
+            ("Fx|Pitch Shift", &[Fx, PitchShift]),
+            ("Spatial|Fx|Ambisonics", &[Spatial, Fx, Ambisonics]),
+            ("Fx|Up-Downmix|Surround", &[Fx, UpDownMix, Surround]),
+            (
+                "Instrument|Sampler|OnlyRT",
+                &[Instrument, Sampler, OnlyRealTime],
+            ),
+            (" fx | reverb ", &[Fx, Reverb]),
+            ("", &[]),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(SubCategories::parse(input).0, *expected, "{input:?}");
+        }
+
+        let odd = SubCategories::parse("Fx|Vintage Tape||Stereo");
+        assert_eq!(odd.0, [Fx, Other("Vintage Tape".into()), Stereo]);
+        assert_eq!(odd.to_string(), "Fx|Vintage Tape|Stereo");
+        assert_eq!(SubCategories::parse("fx|eq").to_string(), "Fx|EQ");
+    }
+}
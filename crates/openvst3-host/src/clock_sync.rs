@@ -0,0 +1,197 @@
+//! Placing events stamped on the host's clock into blocks timed by the audio stream.
+//!
+//! An audio callback knows when it runs from the stream's own clock (cpal's
+//! `OutputCallbackInfo::timestamp()`), while MIDI input is stamped on the host's
+//! monotonic clock by another thread. Reading the host clock inside the callback is
+//! not good enough: the audio thread wakes up late by a varying amount, and every
+//! event would move with it. A [`ClockSync`] keeps a smoothed estimate of the offset
+//! between the two clocks instead, so each block start gets a host time as steady as
+//! the stream's timestamps, and maps host times to stream sample time from there.
+//!
+//! Call [`update`](ClockSync::update) at the start of each callback, then
+//! [`offset`](ClockSync::offset) for each event going into that block. Events land one
+//! block late, at the position they had within the previous block's span. When the
+//! stream clock jumps (a restart, an xrun, a backend that does not keep time) the
+//! mapping is rebuilt from the current callback rather than stretched to fit, and
+//! offsets are always clamped into the block.
+
+/// Wake-up lateness up to which a callback still counts as on time.
+const MAX_WAKEUP_NS: f64 = 5e6;
+
+/// See the module docs. Times are nanoseconds since each clock's own origin.
+#[derive(Clone, Debug)]
+pub struct ClockSync {
+    sample_rate: f64,
+    smoothing: f64,
+    /// Host time minus stream time, smoothed.
+    skew: f64,
+    /// Stream time of the previous callback and of the current one.
+    previous_stream: f64,
+    stream: Option<u64>,
+    /// Frames of the current block.
+    frames: usize,
+    /// Samples of the blocks before the current one.
+    position: i64,
+    resyncs: u64,
+}
+
+impl ClockSync {
+    /// A mapping that moves 1% of the way to each new measurement.
+    pub fn new(sample_rate: f64) -> Self {
+        Self {
+            sample_rate,
+            smoothing: 0.01,
+            skew: 0.0,
+            previous_stream: 0.0,
+            stream: None,
+            frames: 0,
+            position: 0,
+            resyncs: 0,
+        }
+    }
+
+    /// How far each callback moves the clock offset toward what it measured, from 0
+    /// (never) to 1 (all the way, i.e. no smoothing).
+    pub fn with_smoothing(mut self, smoothing: f64) -> Self {
+        self.smoothing = smoothing.clamp(0.0, 1.0);
+        self
+    }
+
+    /// A callback for a block of `frames` ran at `stream_ns` on the stream's clock and
+    /// `host_ns` on the host's.
+    pub fn update(&mut self, host_ns: u64, stream_ns: u64, frames: usize) {
+        let period = self.frames as f64 / self.sample_rate * 1e9;
+        let measured = host_ns as f64 - stream_ns as f64;
+        let steady = self.stream.is_some_and(|last| {
+            let gap = stream_ns as f64 - last as f64;
+            gap > 0.0
+                && (gap - period).abs() <= period
+                && (measured - self.skew).abs() <= period.max(MAX_WAKEUP_NS)
+        });
+        if steady {
+            self.skew += (measured - self.skew) * self.smoothing;
+            self.previous_stream = self.stream.unwrap_or_default() as f64;
+        } else {
+            if self.stream.is_some() {
+                self.resyncs += 1;
+            }
+            self.skew = measured;
+            // Pretend the previous block ran back to back with this one.
+            let span = if self.frames > 0 { self.frames } else { frames };
+            self.previous_stream = stream_ns as f64 - span as f64 / self.sample_rate * 1e9;
+        }
+        self.position += self.frames as i64;
+        self.stream = Some(stream_ns);
+        self.frames = frames;
+    }
+
+    /// The stream sample time of host time `at_ns`, counting from the first block.
+    pub fn sample_time(&self, at_ns: u64) -> f64 {
+        let start = self.stream.unwrap_or_default() as f64 + self.skew;
+        self.position as f64 + (at_ns as f64 - start) * self.sample_rate / 1e9
+    }
+
+    /// The sample offset in the current block of `frames` for an event the host
+    /// stamped `at_ns`: its offset within the previous block's span, clamped into
+    /// `0..frames`.
+    pub fn offset(&self, at_ns: u64, frames: usize) -> i32 {
+        let start = self.previous_stream + self.skew;
+        let samples = (at_ns as f64 - start) * self.sample_rate / 1e9;
+        samples.clamp(0.0, frames.saturating_sub(1) as f64) as i32
+    }
+
+    /// Times the mapping was rebuilt after a jump in either clock.
+    pub fn resyncs(&self) -> u64 {
+        self.resyncs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Callback times for `ClockSync` tests: 256-frame blocks at 48 kHz on a stream clock,
+    /// seen on a host clock 7 s ahead through up to 400 µs of wake-up lateness.
+    struct Callbacks {
+        block: u64,
+        seed: u64,
+    }
+
+    impl Callbacks {
+        const PERIOD_NS: f64 = 256.0 / 48_000.0 * 1e9;
+        const HOST_AHEAD_NS: u64 = 7_000_000_000;
+
+        fn stream_ns(block: u64) -> u64 {
+            (block as f64 * Self::PERIOD_NS) as u64
+        }
+
+        /// The next callback's host and stream time.
+        fn next(&mut self) -> (u64, u64) {
+            self.seed = self.seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            let late = (self.seed >> 33) % 400_000;
+            let stream = Self::stream_ns(self.block);
+            self.block += 1;
+            (stream + Self::HOST_AHEAD_NS + late, stream)
+        }
+    }
+
+    #[test]
+    fn clock_sync_keeps_event_jitter_within_two_samples() {
+        let mut sync = ClockSync::new(48_000.0);
+        let mut callbacks = Callbacks { block: 0, seed: 1 };
+        let (mut lowest, mut highest) = (f64::MAX, f64::MIN);
+        for block in 0..2_000u64 {
+            let (host, stream) = callbacks.next();
+            sync.update(host, stream, 256);
+            if block < 1_000 {
+                continue;
+            }
+            // An event a quarter into the previous block's span, stamped on the host clock
+            // without lateness, belongs at offset 64.
+            let at = Callbacks::stream_ns(block - 1) + Callbacks::HOST_AHEAD_NS + 1_333_333;
+            let error = sync.offset(at, 256) as f64 - 64.0;
+            lowest = lowest.min(error);
+            highest = highest.max(error);
+        }
+        assert!(highest - lowest <= 2.0, "{lowest}..{highest}");
+        // The lateness only shifts everything by its average, 200 µs (9.6 samples).
+        assert!((-12.0..=-8.0).contains(&lowest), "{lowest}");
+        assert_eq!(sync.resyncs(), 0);
+        let at = Callbacks::stream_ns(1_999) + Callbacks::HOST_AHEAD_NS;
+        assert!((sync.sample_time(at) - 1_999.0 * 256.0).abs() < 16.0);
+    }
+
+    #[test]
+    fn clock_sync_resyncs_on_discontinuities() {
+        let mut sync = ClockSync::new(48_000.0);
+        let mut callbacks = Callbacks { block: 0, seed: 7 };
+        for _ in 0..100 {
+            let (host, stream) = callbacks.next();
+            sync.update(host, stream, 256);
+        }
+
+        // An xrun: ten blocks go missing on both clocks.
+        callbacks.block += 10;
+        let (host, stream) = callbacks.next();
+        sync.update(host, stream, 256);
+        assert_eq!(sync.resyncs(), 1);
+
+        // A restart: the stream clock starts over while the host clock goes on.
+        let host = host + 1_000_000_000;
+        sync.update(host, 0, 256);
+        assert_eq!(sync.resyncs(), 2);
+        // Events from before the restart go first, not before the block.
+        assert_eq!(sync.offset(host - 1_000_000_000, 256), 0);
+        assert_eq!(sync.offset(host + 1_000_000_000, 256), 255);
+        // Right after a resync the previous block ends at this callback.
+        assert_eq!(sync.offset(host - 2_666_666, 256), 128);
+
+        let host = host + Callbacks::PERIOD_NS as u64;
+        sync.update(host, Callbacks::PERIOD_NS as u64, 256);
+        assert_eq!(sync.resyncs(), 2);
+
+        // The host clock jumping without the stream's is a discontinuity too.
+        sync.update(host + 60_000_000, 2 * Callbacks::PERIOD_NS as u64, 256);
+        assert_eq!(sync.resyncs(), 3);
+    }
+}
@@ -0,0 +1,285 @@
+//! Where in a plugin's lifecycle this process is, kept for a crash handler to report.
+//!
+//! The host marks each step ([`ScanPhase`]) with plain atomic stores as it loads a
+//! module, asks for its factory, reads each class, instantiates and processes. A
+//! worker process calls [`install`] first thing; if a plugin then kills it (SIGSEGV,
+//! SIGBUS, SIGILL, SIGFPE or SIGABRT, or a fatal exception on Windows), the handler
+//! writes a [`CRASH_TAG`] record naming the phase to stdout, followed on glibc by a
+//! raw backtrace, before the process dies the way it would have. The parent reads
+//! that from the worker's stdout pipe.
+//!
+//! The handler only does async-signal-safe work: atomic loads, `write`, resetting
+//! the signal disposition and re-raising.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+
+/// First field of the record the crash handler writes: `crash\t<phase>`.
+pub const CRASH_TAG: &str = "crash";
+
+/// A step of loading, scanning or running a plugin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub enum ScanPhase {
+    /// `dlopen` and the module entry point.
+    Load,
+    /// `GetPluginFactory` and the factory info.
+    Factory,
+    /// Reading the info of the class at this factory index.
+    Class(u32),
+    /// `createInstance` and `initialize`.
+    Instantiate,
+    Process,
+}
+
+const NONE: u8 = 0;
+const LOAD: u8 = 1;
+const FACTORY: u8 = 2;
+const CLASS: u8 = 3;
+const INSTANTIATE: u8 = 4;
+const PROCESS: u8 = 5;
+
+static PHASE: AtomicU8 = AtomicU8::new(NONE);
+static CLASS_INDEX: AtomicU32 = AtomicU32::new(0);
+
+fn code_name(code: u8) -> &'static str {
+    match code {
+        LOAD => "load",
+        FACTORY => "factory",
+        CLASS => "class",
+        INSTANTIATE => "instantiate",
+        PROCESS => "process",
+        _ => "unknown",
+    }
+}
+
+/// Mark the start of `phase`.
+pub(crate) fn enter(phase: ScanPhase) {
+    let code = match phase {
+        ScanPhase::Load => LOAD,
+        ScanPhase::Factory => FACTORY,
+        ScanPhase::Class(index) => {
+            CLASS_INDEX.store(index, Ordering::Relaxed);
+            CLASS
+        }
+        ScanPhase::Instantiate => INSTANTIATE,
+        ScanPhase::Process => PROCESS,
+    };
+    PHASE.store(code, Ordering::Release);
+}
+
+/// The phase last entered in this process, if any.
+pub fn current() -> Option<ScanPhase> {
+    Some(match PHASE.load(Ordering::Acquire) {
+        LOAD => ScanPhase::Load,
+        FACTORY => ScanPhase::Factory,
+        CLASS => ScanPhase::Class(CLASS_INDEX.load(Ordering::Relaxed)),
+        INSTANTIATE => ScanPhase::Instantiate,
+        PROCESS => ScanPhase::Process,
+        _ => return None,
+    })
+}
+
+impl fmt::Display for ScanPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanPhase::Class(index) => write!(f, "class {index}"),
+            ScanPhase::Load => f.write_str("load"),
+            ScanPhase::Factory => f.write_str("factory"),
+            ScanPhase::Instantiate => f.write_str("instantiate"),
+            ScanPhase::Process => f.write_str("process"),
+        }
+    }
+}
+
+impl FromStr for ScanPhase {
+    type Err = ();
+
+    /// The [`Display`](fmt::Display) form back.
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "load" => Ok(ScanPhase::Load),
+            "factory" => Ok(ScanPhase::Factory),
+            "instantiate" => Ok(ScanPhase::Instantiate),
+            "process" => Ok(ScanPhase::Process),
+            _ => s
+                .strip_prefix("class ")
+                .and_then(|i| i.parse().ok())
+                .map(ScanPhase::Class)
+                .ok_or(()),
+        }
+    }
+}
+
+/// `crash\t<phase>\n` for the current phase, built without allocating.
+fn marker(buf: &mut [u8; 48]) -> &[u8] {
+    let mut len = 0;
+    let mut push = |bytes: &[u8]| {
+        buf[len..len + bytes.len()].copy_from_slice(bytes);
+        len += bytes.len();
+    };
+    push(CRASH_TAG.as_bytes());
+    push(b"\t");
+    let code = PHASE.load(Ordering::Acquire);
+    push(code_name(code).as_bytes());
+    if code == CLASS {
+        push(b" ");
+        let mut index = CLASS_INDEX.load(Ordering::Relaxed);
+        let mut digits = [0u8; 10];
+        let mut n = digits.len();
+        loop {
+            n -= 1;
+            digits[n] = b'0' + (index % 10) as u8;
+            index /= 10;
+            if index == 0 {
+                break;
+            }
+        }
+        push(&digits[n..]);
+    }
+    push(b"\n");
+    &buf[..len]
+}
+
+/// Install the crash handlers for this process. Meant for worker processes whose
+/// stdout is a pipe to the parent; the handlers replace any the process had.
+pub fn install() {
+    sys::install();
+}
+
+#[cfg(unix)]
+mod sys {
+    use core::ffi::{c_int, c_void};
+
+    extern "C" {
+        fn signal(sig: c_int, handler: usize) -> usize;
+        fn raise(sig: c_int) -> c_int;
+        fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+    }
+
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    extern "C" {
+        fn backtrace(buffer: *mut *mut c_void, size: c_int) -> c_int;
+        fn backtrace_symbols_fd(buffer: *const *mut c_void, size: c_int, fd: c_int);
+    }
+
+    const SIG_DFL: usize = 0;
+    const STDOUT: c_int = 1;
+    const SIGILL: c_int = 4;
+    const SIGABRT: c_int = 6;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const SIGBUS: c_int = 7;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const SIGBUS: c_int = 10;
+    const SIGFPE: c_int = 8;
+    const SIGSEGV: c_int = 11;
+
+    /// Frames of raw backtrace written after the marker.
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    const FRAMES: usize = 32;
+
+    pub(super) fn install() {
+        // The first `backtrace` call loads libgcc and may allocate; do it now rather
+        // than in the handler.
+        #[cfg(all(target_os = "linux", target_env = "gnu"))]
+        unsafe {
+            let mut frames = [core::ptr::null_mut(); 1];
+            backtrace(frames.as_mut_ptr(), 1);
+        }
+        for sig in [SIGSEGV, SIGBUS, SIGILL, SIGFPE, SIGABRT] {
+            unsafe { signal(sig, on_signal as extern "C" fn(c_int) as usize) };
+        }
+    }
+
+    extern "C" fn on_signal(sig: c_int) {
+        unsafe {
+            signal(sig, SIG_DFL);
+            let mut buf = [0u8; 48];
+            let marker = super::marker(&mut buf);
+            write(STDOUT, marker.as_ptr().cast(), marker.len());
+            #[cfg(all(target_os = "linux", target_env = "gnu"))]
+            {
+                let mut frames = [core::ptr::null_mut(); FRAMES];
+                let n = backtrace(frames.as_mut_ptr(), FRAMES as c_int);
+                backtrace_symbols_fd(frames.as_ptr(), n, STDOUT);
+            }
+            // Blocked until the handler returns, then fatal with the default action.
+            raise(sig);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use core::ffi::c_void;
+
+    #[repr(C)]
+    struct ExceptionRecord {
+        code: u32,
+    }
+
+    #[repr(C)]
+    struct ExceptionPointers {
+        record: *const ExceptionRecord,
+        context: *mut c_void,
+    }
+
+    type Handler = unsafe extern "system" fn(*mut ExceptionPointers) -> i32;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AddVectoredExceptionHandler(first: u32, handler: Handler) -> *mut c_void;
+        fn GetStdHandle(which: u32) -> *mut c_void;
+        fn WriteFile(
+            file: *mut c_void,
+            buf: *const u8,
+            len: u32,
+            written: *mut u32,
+            overlapped: *mut c_void,
+        ) -> i32;
+    }
+
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const EXCEPTION_CONTINUE_SEARCH: i32 = 0;
+    /// Access violation, in-page error, illegal instruction, integer divide by zero,
+    /// stack overflow.
+    const FATAL: [u32; 5] = [
+        0xC000_0005,
+        0xC000_0006,
+        0xC000_001D,
+        0xC000_0094,
+        0xC000_00FD,
+    ];
+
+    pub(super) fn install() {
+        unsafe { AddVectoredExceptionHandler(1, on_exception) };
+    }
+
+    unsafe extern "system" fn on_exception(info: *mut ExceptionPointers) -> i32 {
+        let code = (*(*info).record).code;
+        if FATAL.contains(&code) {
+            let mut buf = [0u8; 48];
+            let marker = super::marker(&mut buf);
+            let mut written = 0;
+            WriteFile(
+                GetStdHandle(STD_OUTPUT_HANDLE),
+                marker.as_ptr(),
+                marker.len() as u32,
+                &mut written,
+                core::ptr::null_mut(),
+            );
+        }
+        // Let a structured handler in the plugin (or the default one) decide.
+        EXCEPTION_CONTINUE_SEARCH
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    pub(super) fn install() {}
+}
@@ -0,0 +1,297 @@
+//! Sample delays for lining up plugins with different latencies.
+//!
+//! [`DelayLine`] delays planar audio by a whole number of samples, crossfading from
+//! the old to the new delay when it changes so the jump does not click.
+//! [`LatencyCompensator`] keeps one delay line per branch of a parallel layout and
+//! sets each to the difference between its branch's latency and the largest one, so
+//! the branch outputs can be summed sample-aligned. Nothing allocates after `new`.
+
+use crate::limiter::Sample;
+use crate::HostError;
+
+/// Crossfade length after a delay change, in samples.
+pub const DEFAULT_CROSSFADE: usize = 128;
+
+/// See the module docs.
+#[derive(Debug)]
+pub struct DelayLine<T: Sample> {
+    /// Per channel; power-of-two length above `max_delay`.
+    rings: Box<[Box<[T]>]>,
+    mask: usize,
+    /// Running write position; wraps through `mask`.
+    write: usize,
+    delay: usize,
+    max_delay: usize,
+    crossfade: usize,
+    /// Delay being faded out and how many samples of the fade are done.
+    fade: Option<(usize, usize)>,
+}
+
+impl<T: Sample + Default> DelayLine<T> {
+    /// A delay line of `channels` channels that can delay up to `max_delay` samples,
+    /// starting at zero delay.
+    pub fn new(channels: usize, max_delay: usize) -> Self {
+        let len = (max_delay + 1).next_power_of_two();
+        Self {
+            rings: (0..channels)
+                .map(|_| vec![T::default(); len].into_boxed_slice())
+                .collect(),
+            mask: len - 1,
+            write: 0,
+            delay: 0,
+            max_delay,
+            crossfade: DEFAULT_CROSSFADE,
+            fade: None,
+        }
+    }
+
+    pub fn delay(&self) -> usize {
+        self.delay
+    }
+
+    pub fn max_delay(&self) -> usize {
+        self.max_delay
+    }
+
+    /// Samples to crossfade over when the delay changes; 0 switches at once.
+    pub fn set_crossfade(&mut self, samples: usize) {
+        self.crossfade = samples;
+    }
+
+    /// Delay by `samples` from the next block on, fading over from the current delay.
+    /// A change during a fade starts a new fade from the previous target.
+    pub fn set_delay(&mut self, samples: usize) -> Result<(), HostError> {
+        if samples > self.max_delay {
+            return Err(HostError::DelayTooLong {
+                got: samples,
+                max: self.max_delay,
+            });
+        }
+        if samples != self.delay {
+            self.fade = (self.crossfade > 0).then_some((self.delay, 0));
+            self.delay = samples;
+        }
+        Ok(())
+    }
+
+    /// Whether a crossfade after a delay change is still running.
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    /// Delay the first `frames` samples of each channel in place. Channels beyond the
+    /// line's own are left alone.
+    pub fn process(&mut self, planar: &mut [impl AsMut<[T]>], frames: usize) {
+        let start = self.write;
+        let (mask, delay, crossfade) = (self.mask, self.delay, self.crossfade);
+        for (ring, chan) in self.rings.iter_mut().zip(planar.iter_mut()) {
+            let mut w = start;
+            for (i, sample) in chan.as_mut()[..frames].iter_mut().enumerate() {
+                ring[w & mask] = *sample;
+                let delayed = ring[w.wrapping_sub(delay) & mask];
+                *sample = match self.fade {
+                    Some((from, done)) if done + i < crossfade => {
+                        let t = (done + i + 1) as f64 / (crossfade + 1) as f64;
+                        let old = ring[w.wrapping_sub(from) & mask].to_f64();
+                        T::from_f64(old + (delayed.to_f64() - old) * t)
+                    }
+                    _ => delayed,
+                };
+                w = w.wrapping_add(1);
+            }
+        }
+        self.write = start.wrapping_add(frames);
+        if let Some((from, done)) = self.fade {
+            let done = done + frames;
+            self.fade = (done < crossfade).then_some((from, done));
+        }
+    }
+
+    /// Silence the line's memory and finish any fade, keeping the delay.
+    pub fn reset(&mut self) {
+        for ring in self.rings.iter_mut() {
+            ring.fill(T::default());
+        }
+        self.fade = None;
+    }
+}
+
+/// See the module docs.
+#[derive(Debug)]
+pub struct LatencyCompensator<T: Sample> {
+    branches: Box<[DelayLine<T>]>,
+    latencies: Box<[usize]>,
+}
+
+impl<T: Sample + Default> LatencyCompensator<T> {
+    /// `branches` branches of `channels` channels, each allowed a latency of up to
+    /// `max_latency` samples. All start at zero latency.
+    pub fn new(branches: usize, channels: usize, max_latency: usize) -> Self {
+        Self {
+            branches: (0..branches)
+                .map(|_| DelayLine::new(channels, max_latency))
+                .collect(),
+            latencies: vec![0; branches].into_boxed_slice(),
+        }
+    }
+
+    /// Record `branch`'s reported latency and re-align every branch to the largest.
+    pub fn set_latency(&mut self, branch: usize, latency: usize) -> Result<(), HostError> {
+        let max = self.branches[branch].max_delay();
+        if latency > max {
+            return Err(HostError::DelayTooLong { got: latency, max });
+        }
+        self.latencies[branch] = latency;
+        let total = self.total_latency();
+        for (line, &own) in self.branches.iter_mut().zip(self.latencies.iter()) {
+            line.set_delay(total - own)?;
+        }
+        Ok(())
+    }
+
+    /// The latency every branch is aligned to: the largest reported.
+    pub fn total_latency(&self) -> usize {
+        self.latencies.iter().copied().max().unwrap_or(0)
+    }
+
+    /// The delay applied to `branch`.
+    pub fn delay(&self, branch: usize) -> usize {
+        self.branches[branch].delay()
+    }
+
+    /// Align one block of `branch`'s output in place.
+    pub fn process(&mut self, branch: usize, planar: &mut [impl AsMut<[T]>], frames: usize) {
+        self.branches[branch].process(planar, frames);
+    }
+
+    /// The branch's delay line, e.g. to change its crossfade.
+    pub fn branch_mut(&mut self, branch: usize) -> &mut DelayLine<T> {
+        &mut self.branches[branch]
+    }
+
+    pub fn reset(&mut self) {
+        for line in self.branches.iter_mut() {
+            line.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `input` through a plugin stand-in of latency `latency` and then `comp`'s
+    /// `branch`, in blocks of `block`.
+    fn run_branch(
+        plugin: &mut DelayLine<f32>,
+        comp: &mut LatencyCompensator<f32>,
+        branch: usize,
+        input: &[f32],
+        block: usize,
+    ) -> Vec<f32> {
+        let mut out = Vec::with_capacity(input.len());
+        for chunk in input.chunks(block) {
+            let mut buf = [chunk.to_vec()];
+            plugin.process(&mut buf, chunk.len());
+            comp.process(branch, &mut buf, chunk.len());
+            out.extend_from_slice(&buf[0]);
+        }
+        out
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "no unsafe code; too slow under Miri")]
+    fn latency_compensation_aligns_impulses_across_branches() {
+        let latencies = [0, 64, 480];
+        let mut comp = LatencyCompensator::<f32>::new(3, 1, 1024);
+        let mut plugins: Vec<DelayLine<f32>> = latencies
+            .iter()
+            .map(|&l| {
+                let mut line = DelayLine::new(1, 1024);
+                line.set_crossfade(0);
+                line.set_delay(l).unwrap();
+                line
+            })
+            .collect();
+        for (branch, &l) in latencies.iter().enumerate() {
+            comp.set_latency(branch, l).unwrap();
+        }
+        assert_eq!(comp.total_latency(), 480);
+        assert_eq!(
+            (0..3).map(|b| comp.delay(b)).collect::<Vec<_>>(),
+            [480, 416, 0]
+        );
+        // Settle the fades from zero delay before the first impulse.
+        let silence = vec![0.0f32; 256];
+        for (branch, plugin) in plugins.iter_mut().enumerate() {
+            run_branch(plugin, &mut comp, branch, &silence, 100);
+        }
+
+        let impulse_at = |outs: &[Vec<f32>]| -> Vec<Option<usize>> {
+            outs.iter()
+                .map(|o| o.iter().position(|&s| s != 0.0))
+                .collect()
+        };
+        let mut input = vec![0.0f32; 2048];
+        input[10] = 1.0;
+        let outs: Vec<Vec<f32>> = plugins
+            .iter_mut()
+            .enumerate()
+            .map(|(b, p)| run_branch(p, &mut comp, b, &input, 100))
+            .collect();
+        assert_eq!(impulse_at(&outs), [Some(490); 3]);
+        assert!(outs.iter().all(|o| o[490] == 1.0));
+
+        // Branch 1 now reports 600 samples: everyone moves to 600 once the fades end.
+        plugins[1].set_delay(600).unwrap();
+        comp.set_latency(1, 600).unwrap();
+        assert_eq!(
+            (0..3).map(|b| comp.delay(b)).collect::<Vec<_>>(),
+            [600, 0, 120]
+        );
+        let silence = vec![0.0f32; 1024];
+        for (branch, plugin) in plugins.iter_mut().enumerate() {
+            run_branch(plugin, &mut comp, branch, &silence, 100);
+        }
+        let outs: Vec<Vec<f32>> = plugins
+            .iter_mut()
+            .enumerate()
+            .map(|(b, p)| run_branch(p, &mut comp, b, &input, 64))
+            .collect();
+        assert_eq!(impulse_at(&outs), [Some(610); 3]);
+        assert!(outs.iter().all(|o| o[610] == 1.0));
+
+        assert!(matches!(
+            comp.set_latency(0, 2000),
+            Err(HostError::DelayTooLong {
+                got: 2000,
+                max: 1024
+            })
+        ));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "no unsafe code; too slow under Miri")]
+    fn delay_change_crossfades_without_a_jump() {
+        let mut line = DelayLine::<f64>::new(1, 256);
+        line.set_crossfade(64);
+        // A ramp delayed by 0, then by 32: the output may not jump by more than the
+        // ramp's own step plus the fade's share of the 32-sample difference.
+        let ramp: Vec<f64> = (0..512).map(|n| n as f64).collect();
+        let mut out = Vec::new();
+        for (k, chunk) in ramp.chunks(128).enumerate() {
+            if k == 2 {
+                line.set_delay(32).unwrap();
+                assert!(line.is_fading());
+            }
+            let mut buf = [chunk.to_vec()];
+            line.process(&mut buf, chunk.len());
+            out.extend_from_slice(&buf[0]);
+        }
+        assert!(!line.is_fading());
+        assert!(out
+            .windows(2)
+            .all(|w| (w[1] - w[0]).abs() <= 1.0 + 32.0 / 64.0));
+        assert_eq!(out[511], 511.0 - 32.0);
+    }
+}
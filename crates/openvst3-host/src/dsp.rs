@@ -0,0 +1,409 @@
+//! Sample kernels for the loops every block goes through: precision conversion between
+//! slots, gain and mixing.
+//!
+//! Each kernel has a scalar loop and SSE2 and AVX2 (x86_64) or NEON (aarch64)
+//! versions; the widest one the CPU has, [`Isa::detect`], runs. Every version
+//! rounds once per operation and never fuses a multiply into an add, so they all give
+//! the scalar loop's result bit for bit (NaN payloads aside). Slices of different
+//! lengths are processed up to the shorter one. Nothing allocates.
+
+use std::fmt;
+
+/// An instruction set the kernels have a version for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Isa {
+    Scalar,
+    Sse2,
+    Avx2,
+    Neon,
+}
+
+impl Isa {
+    pub const ALL: [Isa; 4] = [Isa::Scalar, Isa::Sse2, Isa::Avx2, Isa::Neon];
+
+    /// Whether this CPU runs it. SSE2 is part of x86_64 and NEON of aarch64.
+    pub fn supported(self) -> bool {
+        match self {
+            Isa::Scalar => true,
+            Isa::Sse2 => cfg!(target_arch = "x86_64"),
+            #[cfg(target_arch = "x86_64")]
+            Isa::Avx2 => std::arch::is_x86_feature_detected!("avx2"),
+            #[cfg(not(target_arch = "x86_64"))]
+            Isa::Avx2 => false,
+            Isa::Neon => cfg!(all(target_arch = "aarch64", target_feature = "neon")),
+        }
+    }
+
+    /// The widest supported set; the one [`convert_f32_to_f64`] and the other
+    /// kernels use.
+    pub fn detect() -> Self {
+        [Isa::Avx2, Isa::Sse2, Isa::Neon]
+            .into_iter()
+            .find(|isa| isa.supported())
+            .unwrap_or(Isa::Scalar)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Isa::Scalar => "scalar",
+            Isa::Sse2 => "sse2",
+            Isa::Avx2 => "avx2",
+            Isa::Neon => "neon",
+        }
+    }
+
+    /// `self`, or [`Isa::Scalar`] if this CPU does not run it.
+    fn checked(self) -> Self {
+        if self.supported() {
+            self
+        } else {
+            Isa::Scalar
+        }
+    }
+}
+
+impl fmt::Display for Isa {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Widen `src` into `dst`; exact.
+pub fn convert_f32_to_f64(src: &[f32], dst: &mut [f64]) {
+    convert_f32_to_f64_on(Isa::detect(), src, dst);
+}
+
+/// Narrow `src` into `dst`, rounding to nearest. Values beyond the 32-bit range,
+/// infinities included, clamp to `±f32::MAX`; NaN stays NaN.
+pub fn convert_f64_to_f32(src: &[f64], dst: &mut [f32]) {
+    convert_f64_to_f32_on(Isa::detect(), src, dst);
+}
+
+/// `buf *= gain`.
+pub fn apply_gain(buf: &mut [f32], gain: f32) {
+    apply_gain_on(Isa::detect(), buf, gain);
+}
+
+/// `b += a * gain`.
+pub fn mix_add(a: &[f32], b: &mut [f32], gain: f32) {
+    mix_add_on(Isa::detect(), a, b, gain);
+}
+
+/// [`convert_f32_to_f64`] on `isa`, or the scalar loop if the CPU lacks it.
+pub(crate) fn convert_f32_to_f64_on(isa: Isa, src: &[f32], dst: &mut [f64]) {
+    let n = src.len().min(dst.len());
+    let (src, dst) = (&src[..n], &mut dst[..n]);
+    let done = match isa.checked() {
+        #[cfg(target_arch = "x86_64")]
+        Isa::Sse2 => x86::f32_to_f64_sse2(src, dst),
+        #[cfg(target_arch = "x86_64")]
+        Isa::Avx2 => unsafe { x86::f32_to_f64_avx2(src, dst) },
+        #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+        Isa::Neon => neon::f32_to_f64(src, dst),
+        _ => 0,
+    };
+    for (d, &s) in dst[done..].iter_mut().zip(&src[done..]) {
+        *d = s as f64;
+    }
+}
+
+/// [`convert_f64_to_f32`] on `isa`, or the scalar loop if the CPU lacks it.
+pub(crate) fn convert_f64_to_f32_on(isa: Isa, src: &[f64], dst: &mut [f32]) {
+    let n = src.len().min(dst.len());
+    let (src, dst) = (&src[..n], &mut dst[..n]);
+    let done = match isa.checked() {
+        #[cfg(target_arch = "x86_64")]
+        Isa::Sse2 => x86::f64_to_f32_sse2(src, dst),
+        #[cfg(target_arch = "x86_64")]
+        Isa::Avx2 => unsafe { x86::f64_to_f32_avx2(src, dst) },
+        #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+        Isa::Neon => neon::f64_to_f32(src, dst),
+        _ => 0,
+    };
+    for (d, &s) in dst[done..].iter_mut().zip(&src[done..]) {
+        *d = s.clamp(-F32_MAX, F32_MAX) as f32;
+    }
+}
+
+/// [`apply_gain`] on `isa`, or the scalar loop if the CPU lacks it.
+pub(crate) fn apply_gain_on(isa: Isa, buf: &mut [f32], gain: f32) {
+    let done = match isa.checked() {
+        #[cfg(target_arch = "x86_64")]
+        Isa::Sse2 => x86::gain_sse2(buf, gain),
+        #[cfg(target_arch = "x86_64")]
+        Isa::Avx2 => unsafe { x86::gain_avx2(buf, gain) },
+        #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+        Isa::Neon => neon::gain(buf, gain),
+        _ => 0,
+    };
+    for s in &mut buf[done..] {
+        *s *= gain;
+    }
+}
+
+/// [`mix_add`] on `isa`, or the scalar loop if the CPU lacks it.
+pub(crate) fn mix_add_on(isa: Isa, a: &[f32], b: &mut [f32], gain: f32) {
+    let n = a.len().min(b.len());
+    let (a, b) = (&a[..n], &mut b[..n]);
+    let done = match isa.checked() {
+        #[cfg(target_arch = "x86_64")]
+        Isa::Sse2 => x86::mix_add_sse2(a, b, gain),
+        #[cfg(target_arch = "x86_64")]
+        Isa::Avx2 => unsafe { x86::mix_add_avx2(a, b, gain) },
+        #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+        Isa::Neon => neon::mix_add(a, b, gain),
+        _ => 0,
+    };
+    for (b, &a) in b[done..].iter_mut().zip(&a[done..]) {
+        *b += a * gain;
+    }
+}
+
+const F32_MAX: f64 = f32::MAX as f64;
+
+// The vector versions take slices of equal length, handle whole vectors and return
+// how many samples that was; the caller's scalar loop does the rest.
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    use super::F32_MAX;
+
+    pub fn f32_to_f64_sse2(src: &[f32], dst: &mut [f64]) -> usize {
+        for (s, d) in src.chunks_exact(4).zip(dst.chunks_exact_mut(4)) {
+            unsafe {
+                let v = _mm_loadu_ps(s.as_ptr());
+                _mm_storeu_pd(d.as_mut_ptr(), _mm_cvtps_pd(v));
+                _mm_storeu_pd(d.as_mut_ptr().add(2), _mm_cvtps_pd(_mm_movehl_ps(v, v)));
+            }
+        }
+        src.len() / 4 * 4
+    }
+
+    pub fn f64_to_f32_sse2(src: &[f64], dst: &mut [f32]) -> usize {
+        unsafe {
+            let (lo, hi) = (_mm_set1_pd(-F32_MAX), _mm_set1_pd(F32_MAX));
+            for (s, d) in src.chunks_exact(4).zip(dst.chunks_exact_mut(4)) {
+                // NaN is the second operand, so max and min pass it through.
+                let a = _mm_min_pd(hi, _mm_max_pd(lo, _mm_loadu_pd(s.as_ptr())));
+                let b = _mm_min_pd(hi, _mm_max_pd(lo, _mm_loadu_pd(s.as_ptr().add(2))));
+                _mm_storeu_ps(
+                    d.as_mut_ptr(),
+                    _mm_movelh_ps(_mm_cvtpd_ps(a), _mm_cvtpd_ps(b)),
+                );
+            }
+        }
+        src.len() / 4 * 4
+    }
+
+    pub fn gain_sse2(buf: &mut [f32], gain: f32) -> usize {
+        unsafe {
+            let g = _mm_set1_ps(gain);
+            for c in buf.chunks_exact_mut(4) {
+                _mm_storeu_ps(c.as_mut_ptr(), _mm_mul_ps(_mm_loadu_ps(c.as_ptr()), g));
+            }
+        }
+        buf.len() / 4 * 4
+    }
+
+    pub fn mix_add_sse2(a: &[f32], b: &mut [f32], gain: f32) -> usize {
+        unsafe {
+            let g = _mm_set1_ps(gain);
+            for (a, b) in a.chunks_exact(4).zip(b.chunks_exact_mut(4)) {
+                let sum = _mm_add_ps(
+                    _mm_loadu_ps(b.as_ptr()),
+                    _mm_mul_ps(_mm_loadu_ps(a.as_ptr()), g),
+                );
+                _mm_storeu_ps(b.as_mut_ptr(), sum);
+            }
+        }
+        a.len() / 4 * 4
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn f32_to_f64_avx2(src: &[f32], dst: &mut [f64]) -> usize {
+        for (s, d) in src.chunks_exact(4).zip(dst.chunks_exact_mut(4)) {
+            _mm256_storeu_pd(d.as_mut_ptr(), _mm256_cvtps_pd(_mm_loadu_ps(s.as_ptr())));
+        }
+        src.len() / 4 * 4
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn f64_to_f32_avx2(src: &[f64], dst: &mut [f32]) -> usize {
+        let (lo, hi) = (_mm256_set1_pd(-F32_MAX), _mm256_set1_pd(F32_MAX));
+        for (s, d) in src.chunks_exact(4).zip(dst.chunks_exact_mut(4)) {
+            let v = _mm256_min_pd(hi, _mm256_max_pd(lo, _mm256_loadu_pd(s.as_ptr())));
+            _mm_storeu_ps(d.as_mut_ptr(), _mm256_cvtpd_ps(v));
+        }
+        src.len() / 4 * 4
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn gain_avx2(buf: &mut [f32], gain: f32) -> usize {
+        let g = _mm256_set1_ps(gain);
+        for c in buf.chunks_exact_mut(8) {
+            _mm256_storeu_ps(
+                c.as_mut_ptr(),
+                _mm256_mul_ps(_mm256_loadu_ps(c.as_ptr()), g),
+            );
+        }
+        buf.len() / 8 * 8
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn mix_add_avx2(a: &[f32], b: &mut [f32], gain: f32) -> usize {
+        let g = _mm256_set1_ps(gain);
+        for (a, b) in a.chunks_exact(8).zip(b.chunks_exact_mut(8)) {
+            let sum = _mm256_add_ps(
+                _mm256_loadu_ps(b.as_ptr()),
+                _mm256_mul_ps(_mm256_loadu_ps(a.as_ptr()), g),
+            );
+            _mm256_storeu_ps(b.as_mut_ptr(), sum);
+        }
+        a.len() / 8 * 8
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::F32_MAX;
+
+    pub fn f32_to_f64(src: &[f32], dst: &mut [f64]) -> usize {
+        for (s, d) in src.chunks_exact(4).zip(dst.chunks_exact_mut(4)) {
+            unsafe {
+                let v = vld1q_f32(s.as_ptr());
+                vst1q_f64(d.as_mut_ptr(), vcvt_f64_f32(vget_low_f32(v)));
+                vst1q_f64(d.as_mut_ptr().add(2), vcvt_high_f64_f32(v));
+            }
+        }
+        src.len() / 4 * 4
+    }
+
+    pub fn f64_to_f32(src: &[f64], dst: &mut [f32]) -> usize {
+        unsafe {
+            let (lo, hi) = (vdupq_n_f64(-F32_MAX), vdupq_n_f64(F32_MAX));
+            for (s, d) in src.chunks_exact(4).zip(dst.chunks_exact_mut(4)) {
+                // FMAX and FMIN return NaN if either operand is NaN.
+                let a = vminq_f64(hi, vmaxq_f64(lo, vld1q_f64(s.as_ptr())));
+                let b = vminq_f64(hi, vmaxq_f64(lo, vld1q_f64(s.as_ptr().add(2))));
+                vst1q_f32(d.as_mut_ptr(), vcvt_high_f32_f64(vcvt_f32_f64(a), b));
+            }
+        }
+        src.len() / 4 * 4
+    }
+
+    pub fn gain(buf: &mut [f32], gain: f32) -> usize {
+        for c in buf.chunks_exact_mut(4) {
+            unsafe {
+                vst1q_f32(c.as_mut_ptr(), vmulq_n_f32(vld1q_f32(c.as_ptr()), gain));
+            }
+        }
+        buf.len() / 4 * 4
+    }
+
+    pub fn mix_add(a: &[f32], b: &mut [f32], gain: f32) -> usize {
+        for (a, b) in a.chunks_exact(4).zip(b.chunks_exact_mut(4)) {
+            unsafe {
+                // Not vmlaq: that may fuse, and the scalar loop rounds the product.
+                let sum = vaddq_f32(
+                    vld1q_f32(b.as_ptr()),
+                    vmulq_n_f32(vld1q_f32(a.as_ptr()), gain),
+                );
+                vst1q_f32(b.as_mut_ptr(), sum);
+            }
+        }
+        a.len() / 4 * 4
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `n` samples in ±2 from a linear congruential generator.
+    fn noise(n: usize, mut seed: u64) -> Vec<f64> {
+        (0..n)
+            .map(|_| {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (seed >> 11) as f64 / (1u64 << 53) as f64 * 4.0 - 2.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dsp_kernels_match_the_scalar_loop_on_every_isa() {
+        let isas: Vec<Isa> = Isa::ALL.into_iter().filter(|isa| isa.supported()).collect();
+        assert!(isas.contains(&Isa::detect()));
+        let bits32 = |v: &[f32]| v.iter().map(|s| s.to_bits()).collect::<Vec<_>>();
+        let bits64 = |v: &[f64]| v.iter().map(|s| s.to_bits()).collect::<Vec<_>>();
+        // Lengths around every vector width, so each tail loop runs.
+        for (i, len) in [0, 1, 3, 4, 5, 7, 8, 9, 15, 16, 17, 67]
+            .into_iter()
+            .enumerate()
+        {
+            let mut wide = noise(len, i as u64);
+            // Out of the 32-bit range, infinite, subnormal, signed zero.
+            for (at, special) in [1e300, f64::NEG_INFINITY, 1e-40, -0.0]
+                .into_iter()
+                .enumerate()
+            {
+                if let Some(s) = wide.get_mut(at * 3) {
+                    *s = special;
+                }
+            }
+            let narrow: Vec<f32> = noise(len, 100 + i as u64)
+                .iter()
+                .map(|&s| s as f32)
+                .collect();
+            let gain = 0.3 + i as f32 / 7.0;
+
+            let mut want = (
+                vec![0.0f64; len],
+                vec![0.0f32; len],
+                narrow.clone(),
+                narrow.clone(),
+            );
+            convert_f32_to_f64_on(Isa::Scalar, &narrow, &mut want.0);
+            convert_f64_to_f32_on(Isa::Scalar, &wide, &mut want.1);
+            apply_gain_on(Isa::Scalar, &mut want.2, gain);
+            mix_add_on(Isa::Scalar, &want.1, &mut want.3, gain);
+            for &isa in &isas {
+                let mut got = (
+                    vec![0.0f64; len],
+                    vec![0.0f32; len],
+                    narrow.clone(),
+                    narrow.clone(),
+                );
+                convert_f32_to_f64_on(isa, &narrow, &mut got.0);
+                convert_f64_to_f32_on(isa, &wide, &mut got.1);
+                apply_gain_on(isa, &mut got.2, gain);
+                mix_add_on(isa, &want.1, &mut got.3, gain);
+                assert_eq!(bits64(&got.0), bits64(&want.0), "{isa} f32 -> f64, {len}");
+                assert_eq!(bits32(&got.1), bits32(&want.1), "{isa} f64 -> f32, {len}");
+                assert_eq!(bits32(&got.2), bits32(&want.2), "{isa} gain, {len}");
+                assert_eq!(bits32(&got.3), bits32(&want.3), "{isa} mix, {len}");
+            }
+        }
+    }
+
+    #[test]
+    fn dsp_narrowing_clamps_and_stops_at_the_shorter_slice() {
+        let mut out = [9.0f32; 6];
+        convert_f64_to_f32(&[1e300, f64::NEG_INFINITY, f64::NAN, 0.1, -2.5], &mut out);
+        assert_eq!(out[..2], [f32::MAX, -f32::MAX]);
+        assert!(out[2].is_nan());
+        assert_eq!(out[3..], [0.1, -2.5, 9.0]);
+
+        let mut sum = [1.0f32; 3];
+        mix_add(&[1.0; 8], &mut sum, 0.5);
+        assert_eq!(sum, [1.5; 3]);
+        let mut wide = [0.0f64; 2];
+        convert_f32_to_f64(&[0.25; 5], &mut wide);
+        assert_eq!(wide, [0.25; 2]);
+    }
+}
@@ -0,0 +1,280 @@
+//! Plugin editors: the controller's `IPlugView` and the host's `IPlugFrame`.
+//!
+//! [`ControllerHandle::create_editor`] creates the `"editor"` view and hands it a
+//! [`PlugFrame`]. The host embeds the view in a native window with
+//! [`PlugView::attach`]; when the plugin wants another size it calls
+//! `IPlugFrame::resizeView`, which only records the request, so the window code can
+//! pick it up with [`PlugView::take_resize_request`] on its own schedule, resize the
+//! window and confirm with [`PlugView::set_size`]. All calls belong on the UI thread.
+//!
+//! Dropping a [`PlugView`] detaches it if needed and clears its frame before releasing
+//! it, so it must be dropped while the parent window still exists and before the
+//! controller is terminated.
+
+use core::ffi::c_void;
+use core::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use openvst3_abi::{
+    iids, tresult, FUnknown, Fuid, IPlugFrame, IPlugFrameVTable, IPlugView, ViewRect,
+    K_INTERNAL_ERR, K_INVALID_ARG, K_NO_INTERFACE, K_RESULT_OK, VIEW_TYPE_EDITOR,
+};
+
+use crate::callback::contain;
+use crate::plugin::check;
+use crate::{ControllerHandle, HostError, InterfacePtr};
+
+// --- PlugFrame ----------------------------------------------------------------
+
+/// Host `IPlugFrame`. Like [`MemoryStream`](crate::MemoryStream), the host owns it
+/// through the `Box`; plugin-side `addRef`/`release` never free it.
+#[repr(C)]
+pub struct PlugFrame {
+    vtbl: *const IPlugFrameVTable,
+    refs: AtomicU32,
+    poisoned: AtomicBool,
+    /// The latest size asked for through `resizeView`.
+    pending: Mutex<Option<ViewRect>>,
+}
+
+static PLUG_FRAME_VTBL: IPlugFrameVTable = IPlugFrameVTable {
+    query_interface: f_query_interface,
+    add_ref: f_add_ref,
+    release: f_release,
+    resize_view: f_resize_view,
+};
+
+impl PlugFrame {
+    pub fn new() -> Box<Self> {
+        Box::new(Self {
+            vtbl: &PLUG_FRAME_VTBL,
+            refs: AtomicU32::new(1),
+            poisoned: AtomicBool::new(false),
+            pending: Mutex::new(None),
+        })
+    }
+
+    pub fn as_iplug_frame(&mut self) -> *mut IPlugFrame {
+        self as *mut Self as *mut IPlugFrame
+    }
+
+    /// Take the size the plugin asked for most recently, if any.
+    pub fn take_request(&self) -> Option<ViewRect> {
+        self.pending.lock().unwrap().take()
+    }
+
+    /// Whether a plugin call on the frame panicked; it then refuses every call.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Acquire)
+    }
+}
+
+unsafe fn frame<'a>(this: *mut FUnknown) -> &'a PlugFrame {
+    &*(this as *mut PlugFrame)
+}
+
+unsafe extern "C" fn f_query_interface(
+    this_: *mut FUnknown,
+    iid: *const Fuid,
+    obj: *mut *mut c_void,
+) -> tresult {
+    let f = frame(this_);
+    contain(&f.poisoned, K_INTERNAL_ERR, || {
+        if obj.is_null() || iid.is_null() {
+            return K_INVALID_ARG;
+        }
+        if *iid == iids::IPLUG_FRAME || *iid == iids::FUNKNOWN {
+            f.refs.fetch_add(1, Ordering::Relaxed);
+            *obj = this_ as *mut c_void;
+            return K_RESULT_OK;
+        }
+        *obj = core::ptr::null_mut();
+        K_NO_INTERFACE
+    })
+}
+
+unsafe extern "C" fn f_add_ref(this_: *mut FUnknown) -> u32 {
+    let f = frame(this_);
+    contain(&f.poisoned, 1, || {
+        f.refs.fetch_add(1, Ordering::Relaxed) + 1
+    })
+}
+
+unsafe extern "C" fn f_release(this_: *mut FUnknown) -> u32 {
+    let f = frame(this_);
+    contain(&f.poisoned, 1, || crate::events::release(&f.refs))
+}
+
+unsafe extern "C" fn f_resize_view(
+    this_: *mut IPlugFrame,
+    view: *mut IPlugView,
+    new_size: *mut ViewRect,
+) -> tresult {
+    let f = frame(this_ as *mut FUnknown);
+    contain(&f.poisoned, K_INTERNAL_ERR, || {
+        if view.is_null() || new_size.is_null() {
+            return K_INVALID_ARG;
+        }
+        *f.pending.lock().unwrap() = Some(*new_size);
+        K_RESULT_OK
+    })
+}
+
+// --- PlugView -----------------------------------------------------------------
+
+/// A plugin editor view with the host frame it was given.
+pub struct PlugView {
+    ptr: InterfacePtr<IPlugView>,
+    // Dropped after `ptr`, so the frame outlives the plugin's last reference.
+    frame: SharedFrame,
+    attached: bool,
+}
+
+/// Owns a [`PlugFrame`] whose pointer the plugin holds. Unlike `Box`, it claims no
+/// unique access, so host reads and plugin calls may interleave; every field the
+/// plugin touches is atomic or behind a mutex.
+struct SharedFrame(NonNull<PlugFrame>);
+
+impl SharedFrame {
+    fn new(frame: Box<PlugFrame>) -> Self {
+        Self(NonNull::from(Box::leak(frame)))
+    }
+
+    fn as_iplug_frame(&self) -> *mut IPlugFrame {
+        self.0.as_ptr() as *mut IPlugFrame
+    }
+}
+
+impl core::ops::Deref for SharedFrame {
+    type Target = PlugFrame;
+
+    fn deref(&self) -> &PlugFrame {
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl Drop for SharedFrame {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.0.as_ptr()) });
+    }
+}
+
+impl ControllerHandle {
+    /// Create the controller's editor view and give it a frame. Fails with
+    /// [`HostError::NoInterface`] when the plugin has no editor.
+    pub fn create_editor(&self) -> Result<PlugView, HostError> {
+        let raw = unsafe { (*self.as_ptr()).create_view(VIEW_TYPE_EDITOR.as_ptr() as *const i8) };
+        traced!("IEditController", "createView", raw as usize);
+        let ptr = unsafe { InterfacePtr::from_raw(raw as *mut IPlugView) }
+            .ok_or_else(|| HostError::no_interface(iids::IPLUG_VIEW))?;
+        let frame = SharedFrame::new(PlugFrame::new());
+        check(traced!("IPlugView", "setFrame", unsafe {
+            (*ptr.as_ptr()).set_frame(frame.as_iplug_frame())
+        }))?;
+        Ok(PlugView {
+            ptr,
+            frame,
+            attached: false,
+        })
+    }
+}
+
+impl PlugView {
+    #[inline]
+    pub fn as_ptr(&self) -> *mut IPlugView {
+        self.ptr.as_ptr()
+    }
+
+    /// Whether the view can be embedded in a parent of platform type `ty`, one of
+    /// the NUL-terminated [`platform_types`](openvst3_abi::platform_types).
+    pub fn platform_type_supported(&self, ty: &[u8]) -> bool {
+        debug_assert_eq!(ty.last(), Some(&0));
+        traced!("IPlugView", "isPlatformTypeSupported", unsafe {
+            (*self.as_ptr()).is_platform_type_supported(ty.as_ptr() as *const i8)
+        }) == K_RESULT_OK
+    }
+
+    /// Embed the view in `parent`, a native window of platform type `ty`.
+    ///
+    /// # Safety
+    /// `parent` must be a live window of type `ty` that outlives the attachment.
+    pub unsafe fn attach(&mut self, parent: *mut c_void, ty: &[u8]) -> Result<(), HostError> {
+        debug_assert_eq!(ty.last(), Some(&0));
+        check(traced!("IPlugView", "attached", unsafe {
+            (*self.as_ptr()).attached(parent, ty.as_ptr() as *const i8)
+        }))?;
+        self.attached = true;
+        Ok(())
+    }
+
+    /// Detach the view from its parent; does nothing when not attached.
+    pub fn remove(&mut self) -> Result<(), HostError> {
+        if !self.attached {
+            return Ok(());
+        }
+        self.attached = false;
+        check(traced!("IPlugView", "removed", unsafe {
+            (*self.as_ptr()).removed()
+        }))
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.attached
+    }
+
+    /// The size the view currently wants.
+    pub fn size(&self) -> Result<ViewRect, HostError> {
+        let mut rect = ViewRect::default();
+        check(traced!("IPlugView", "getSize", unsafe {
+            (*self.as_ptr()).get_size(&mut rect)
+        }))?;
+        Ok(rect)
+    }
+
+    /// Tell the view its parent now has size `rect`.
+    pub fn set_size(&self, mut rect: ViewRect) -> Result<(), HostError> {
+        check(traced!(
+            "IPlugView",
+            "onSize",
+            unsafe { (*self.as_ptr()).on_size(&mut rect) },
+            width = rect.width(),
+            height = rect.height()
+        ))
+    }
+
+    /// Whether the user may resize the window.
+    pub fn can_resize(&self) -> bool {
+        traced!("IPlugView", "canResize", unsafe {
+            (*self.as_ptr()).can_resize()
+        }) == K_RESULT_OK
+    }
+
+    /// Adjust a proposed size to the nearest one the view accepts.
+    pub fn constrain(&self, mut rect: ViewRect) -> ViewRect {
+        let tr = traced!("IPlugView", "checkSizeConstraint", unsafe {
+            (*self.as_ptr()).check_size_constraint(&mut rect)
+        });
+        if tr == K_RESULT_OK {
+            rect
+        } else {
+            // Without a constraint answer, keep the window at the view's own size.
+            self.size().unwrap_or(rect)
+        }
+    }
+
+    /// The size the plugin asked for through `IPlugFrame::resizeView` since the last
+    /// call, if any.
+    pub fn take_resize_request(&self) -> Option<ViewRect> {
+        self.frame.take_request()
+    }
+}
+
+impl Drop for PlugView {
+    fn drop(&mut self) {
+        let _ = self.remove();
+        unsafe {
+            (*self.as_ptr()).set_frame(core::ptr::null_mut());
+        }
+    }
+}
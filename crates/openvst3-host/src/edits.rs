@@ -0,0 +1,261 @@
+//! Bounded queue of component handler calls for a consumer that polls.
+//!
+//! Some plugins call `performEdit` thousands of times a second, e.g. meters published
+//! as parameters. An [`EditEvents`] attached to a [`ComponentHandler`] with
+//! [`ComponentHandler::track_edits`] holds at most its capacity of [`HandlerEvent`]s
+//! between polls, in storage allocated when it is built. A call that does not fit is
+//! dropped and counted in [`dropped`](EditEvents::dropped), so an embedder can tell a
+//! pathological plugin from a slow consumer.
+//!
+//! In [`EditMode::Coalesce`] a `performEdit` for a parameter that already has one
+//! waiting overwrites that value in place instead of queueing. A `beginEdit` or
+//! `endEdit` for the parameter, or any `restartComponent`, ends the run: later edits
+//! queue after it, so gestures keep their shape. [`EditMode::KeepAll`] queues every
+//! call, for recording.
+//!
+//! [`ComponentHandler`]: crate::ComponentHandler
+//! [`ComponentHandler::track_edits`]: crate::ComponentHandler::track_edits
+
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+use openvst3_abi::ParamID;
+
+use crate::HandlerEvent;
+
+/// What [`EditEvents`] does with a `performEdit` for a parameter that already has one
+/// waiting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EditMode {
+    /// Keep only the latest value.
+    #[default]
+    Coalesce,
+    /// Queue it like any other call.
+    KeepAll,
+}
+
+/// See the module docs.
+#[derive(Debug)]
+pub struct EditEvents {
+    mode: EditMode,
+    capacity: usize,
+    queue: Mutex<Queue>,
+    received: AtomicU64,
+    coalesced: AtomicU64,
+    dropped: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Queue {
+    events: Vec<HandlerEvent>,
+    /// Where each parameter's coalescable `performEdit` sits in `events`.
+    pending: HashMap<ParamID, usize>,
+}
+
+impl EditEvents {
+    /// A queue of at most `capacity` calls (at least one).
+    pub fn new(capacity: usize, mode: EditMode) -> Self {
+        let capacity = capacity.max(1);
+        let pending = match mode {
+            EditMode::Coalesce => HashMap::with_capacity(capacity),
+            EditMode::KeepAll => HashMap::new(),
+        };
+        Self {
+            mode,
+            capacity,
+            queue: Mutex::new(Queue {
+                events: Vec::with_capacity(capacity),
+                pending,
+            }),
+            received: AtomicU64::new(0),
+            coalesced: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn mode(&self) -> EditMode {
+        self.mode
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Calls waiting for the next poll.
+    pub fn len(&self) -> usize {
+        self.lock().events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every call the queue was given, kept or not.
+    pub fn received(&self) -> u64 {
+        self.received.load(Ordering::Relaxed)
+    }
+
+    /// `performEdit` calls folded into one already waiting.
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced.load(Ordering::Relaxed)
+    }
+
+    /// Calls lost because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Bytes the queue holds; fixed when it is built, however many calls arrive.
+    pub fn allocated_bytes(&self) -> usize {
+        let queue = self.lock();
+        queue.events.capacity() * size_of::<HandlerEvent>()
+            + queue.pending.capacity() * size_of::<(ParamID, usize)>()
+    }
+
+    /// Pass the waiting calls to `f`, oldest first, and empty the queue; returns how
+    /// many there were. `f` runs under the queue's lock, which the plugin's handler
+    /// calls wait for.
+    pub fn take_events(&self, mut f: impl FnMut(HandlerEvent)) -> usize {
+        let mut queue = self.lock();
+        queue.pending.clear();
+        let n = queue.events.len();
+        for event in queue.events.drain(..) {
+            f(event);
+        }
+        n
+    }
+
+    /// Queue `event`, coalescing or dropping it as the module docs describe.
+    pub(crate) fn push(&self, event: HandlerEvent) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+        let mut queue = self.lock();
+        let queue = &mut *queue;
+        if self.mode == EditMode::Coalesce {
+            match event {
+                HandlerEvent::PerformEdit { id, value } => {
+                    if let Some(&at) = queue.pending.get(&id) {
+                        queue.events[at] = HandlerEvent::PerformEdit { id, value };
+                        self.coalesced.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                }
+                HandlerEvent::BeginEdit(id) | HandlerEvent::EndEdit(id) => {
+                    queue.pending.remove(&id);
+                }
+                HandlerEvent::Restart(_) => queue.pending.clear(),
+            }
+        }
+        if queue.events.len() == self.capacity {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        if let (EditMode::Coalesce, HandlerEvent::PerformEdit { id, .. }) = (self.mode, event) {
+            queue.pending.insert(id, queue.events.len());
+        }
+        queue.events.push(event);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ComponentHandler;
+
+    #[test]
+    fn edit_events_coalesce_per_parameter_between_polls() {
+        use std::sync::Arc;
+        let handler = ComponentHandler::new(|_| {});
+        let events = Arc::new(EditEvents::new(8, EditMode::Coalesce));
+        handler.track_edits(&events);
+        let raw = handler.as_icomponent_handler();
+        unsafe {
+            (*raw).perform_edit(1, 0.1);
+            (*raw).perform_edit(2, 0.2);
+            (*raw).perform_edit(1, 0.3);
+            // A gesture boundary ends the run: the next edit of 1 queues after it.
+            (*raw).end_edit(1);
+            (*raw).perform_edit(1, 0.4);
+            (*raw).perform_edit(1, 0.5);
+        }
+        let mut seen = Vec::new();
+        assert_eq!(events.take_events(|e| seen.push(e)), 4);
+        assert_eq!(
+            seen,
+            [
+                HandlerEvent::PerformEdit { id: 1, value: 0.3 },
+                HandlerEvent::PerformEdit { id: 2, value: 0.2 },
+                HandlerEvent::EndEdit(1),
+                HandlerEvent::PerformEdit { id: 1, value: 0.5 },
+            ]
+        );
+        assert_eq!((events.received(), events.coalesced()), (6, 2));
+        // After a poll the next edit queues again.
+        unsafe { (*raw).perform_edit(1, 0.6) };
+        assert_eq!(events.len(), 1);
+        assert_eq!(events.dropped(), 0);
+    }
+
+    #[test]
+    fn edit_events_keep_all_drops_what_does_not_fit() {
+        use std::sync::Arc;
+        let handler = ComponentHandler::new(|_| {});
+        let events = Arc::new(EditEvents::new(3, EditMode::KeepAll));
+        handler.track_edits(&events);
+        let raw = handler.as_icomponent_handler();
+        unsafe {
+            (*raw).begin_edit(4);
+            for k in 0..5 {
+                (*raw).perform_edit(4, f64::from(k) / 4.0);
+            }
+            (*raw).restart_component(0);
+        }
+        let mut seen = Vec::new();
+        events.take_events(|e| seen.push(e));
+        assert_eq!(
+            seen,
+            [
+                HandlerEvent::BeginEdit(4),
+                HandlerEvent::PerformEdit { id: 4, value: 0.0 },
+                HandlerEvent::PerformEdit { id: 4, value: 0.25 },
+            ]
+        );
+        assert_eq!((events.received(), events.dropped()), (7, 4));
+        assert_eq!(events.coalesced(), 0);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "a million calls")]
+    fn edit_storm_keeps_the_queue_bounded() {
+        use std::sync::Arc;
+        let handler = ComponentHandler::new(|_| {});
+        let coalesced = Arc::new(EditEvents::new(64, EditMode::Coalesce));
+        let kept = Arc::new(EditEvents::new(64, EditMode::KeepAll));
+        handler.track_edits(&coalesced);
+        handler.track_edits(&kept);
+        let bytes = (coalesced.allocated_bytes(), kept.allocated_bytes());
+        let raw = handler.as_icomponent_handler();
+        let mut polled = 0;
+        for k in 0..1_000_000u32 {
+            unsafe { (*raw).perform_edit(k % 32, f64::from(k) / 1e6) };
+            if k % 100_000 == 99_999 {
+                polled += coalesced.take_events(|_| {});
+                kept.take_events(|_| {});
+            }
+        }
+        assert_eq!((coalesced.allocated_bytes(), kept.allocated_bytes()), bytes);
+        assert!(coalesced.len() <= 64 && kept.len() <= 64);
+        // Coalesced, 32 parameters fit; nothing is lost.
+        assert_eq!(polled, 10 * 32);
+        assert_eq!(coalesced.dropped(), 0);
+        assert_eq!(coalesced.coalesced(), 1_000_000 - 10 * 32);
+        assert_eq!(kept.dropped(), 1_000_000 - 10 * 64);
+        assert_eq!(kept.received(), 1_000_000);
+    }
+}
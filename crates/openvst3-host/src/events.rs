@@ -0,0 +1,648 @@
+//! Host-side `IEventList` and `IParameterChanges` for the process call, and
+//! [`ParamRingBuffer`] for feeding parameter edits into the latter from another thread.
+//!
+//! The lists are sized once and never reallocate, so they can be filled and cleared on the
+//! audio thread: a full list refuses further entries instead of growing. Like
+//! [`MemoryStream`](crate::MemoryStream), the host owns them through the `Box`;
+//! plugin-side `addRef`/`release` are counted but never free the object. A panic in
+//! one of their methods poisons the list; see [`callback`](crate::callback).
+
+use core::ffi::c_void;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use openvst3_abi::{
+    iids, tresult, Event, FUnknown, Fuid, IEventList, IEventListVTable, IParamValueQueue,
+    IParamValueQueueVTable, IParameterChanges, IParameterChangesVTable, ParamID, ParamValue,
+    K_INTERNAL_ERR, K_INVALID_ARG, K_NO_INTERFACE, K_RESULT_FALSE, K_RESULT_OK,
+};
+
+use crate::callback::contain;
+use crate::ring::{self, Consumer, Producer};
+use crate::smoother::ParamSmoother;
+
+unsafe fn query(
+    this_: *mut FUnknown,
+    own: &Fuid,
+    iid: *const Fuid,
+    obj: *mut *mut c_void,
+    refs: &AtomicU32,
+) -> tresult {
+    if obj.is_null() || iid.is_null() {
+        return K_INVALID_ARG;
+    }
+    if *iid == *own || *iid == iids::FUNKNOWN {
+        refs.fetch_add(1, Ordering::Relaxed);
+        *obj = this_ as *mut c_void;
+        return K_RESULT_OK;
+    }
+    *obj = core::ptr::null_mut();
+    K_NO_INTERFACE
+}
+
+/// `kNoParamId`.
+const NO_PARAM_ID: ParamID = u32::MAX;
+
+pub(crate) fn release(refs: &AtomicU32) -> u32 {
+    if refs.load(Ordering::Relaxed) <= 1 {
+        return 1;
+    }
+    refs.fetch_sub(1, Ordering::Relaxed) - 1
+}
+
+// --- EventList ----------------------------------------------------------------
+
+/// Fixed-capacity `IEventList`, kept in sample-offset order.
+#[repr(C)]
+pub struct EventList {
+    vtbl: *const IEventListVTable,
+    refs: AtomicU32,
+    poisoned: AtomicBool,
+    events: Vec<Event>,
+    /// Pushes refused since the last `clear`.
+    dropped: usize,
+}
+
+// The vtable pointer targets a static; everything else is owned.
+unsafe impl Send for EventList {}
+
+static EVENT_LIST_VTBL: IEventListVTable = IEventListVTable {
+    query_interface: el_query_interface,
+    add_ref: el_add_ref,
+    release: el_release,
+    get_event_count: el_get_event_count,
+    get_event: el_get_event,
+    add_event: el_add_event,
+};
+
+impl EventList {
+    pub fn with_capacity(capacity: usize) -> Box<Self> {
+        Box::new(Self {
+            vtbl: &EVENT_LIST_VTBL,
+            refs: AtomicU32::new(1),
+            poisoned: AtomicBool::new(false),
+            events: Vec::with_capacity(capacity),
+            dropped: 0,
+        })
+    }
+
+    /// Insert `event` after any events at the same or an earlier offset. Returns false,
+    /// leaving the list unchanged, when it is full.
+    pub fn push(&mut self, event: Event) -> bool {
+        if self.events.len() == self.events.capacity() {
+            self.dropped += 1;
+            return false;
+        }
+        let at = self
+            .events
+            .partition_point(|e| e.sample_offset <= event.sample_offset);
+        self.events.insert(at, event);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.dropped = 0;
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.events.capacity()
+    }
+
+    /// Events refused for lack of room since the last [`clear`](Self::clear).
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Whether a plugin call on the list panicked; it then refuses every call.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Acquire)
+    }
+
+    /// Pointer for `ProcessData::input_events`/`output_events`. Valid while `self` is alive.
+    pub fn as_ievent_list(&mut self) -> *mut IEventList {
+        self as *mut Self as *mut IEventList
+    }
+}
+
+unsafe fn event_list<'a>(this: *mut IEventList) -> &'a mut EventList {
+    &mut *(this as *mut EventList)
+}
+
+unsafe extern "C" fn el_query_interface(
+    this_: *mut FUnknown,
+    iid: *const Fuid,
+    obj: *mut *mut c_void,
+) -> tresult {
+    let list = event_list(this_ as *mut IEventList);
+    contain(&list.poisoned, K_INTERNAL_ERR, || {
+        query(this_, &iids::IEVENT_LIST, iid, obj, &list.refs)
+    })
+}
+
+unsafe extern "C" fn el_add_ref(this_: *mut FUnknown) -> u32 {
+    let list = event_list(this_ as *mut IEventList);
+    contain(&list.poisoned, 1, || {
+        list.refs.fetch_add(1, Ordering::Relaxed) + 1
+    })
+}
+
+unsafe extern "C" fn el_release(this_: *mut FUnknown) -> u32 {
+    let list = event_list(this_ as *mut IEventList);
+    contain(&list.poisoned, 1, || release(&list.refs))
+}
+
+unsafe extern "C" fn el_get_event_count(this_: *mut IEventList) -> i32 {
+    let list = event_list(this_);
+    contain(&list.poisoned, 0, || list.events.len() as i32)
+}
+
+unsafe extern "C" fn el_get_event(this_: *mut IEventList, index: i32, e: *mut Event) -> tresult {
+    let list = event_list(this_);
+    contain(&list.poisoned, K_INTERNAL_ERR, || {
+        if e.is_null() {
+            return K_INVALID_ARG;
+        }
+        match usize::try_from(index).ok().and_then(|i| list.events.get(i)) {
+            Some(event) => {
+                *e = *event;
+                K_RESULT_OK
+            }
+            None => K_INVALID_ARG,
+        }
+    })
+}
+
+unsafe extern "C" fn el_add_event(this_: *mut IEventList, e: *mut Event) -> tresult {
+    let list = event_list(this_);
+    // Through a pointer, so the body can borrow the list mutably.
+    let poisoned = &list.poisoned as *const AtomicBool;
+    contain(&*poisoned, K_INTERNAL_ERR, || {
+        if e.is_null() {
+            return K_INVALID_ARG;
+        }
+        if list.push(*e) {
+            K_RESULT_OK
+        } else {
+            K_RESULT_FALSE
+        }
+    })
+}
+
+// --- ParameterChanges ---------------------------------------------------------
+
+/// One parameter's points within a block; an `IParamValueQueue`.
+#[repr(C)]
+pub struct ParamQueue {
+    vtbl: *const IParamValueQueueVTable,
+    refs: AtomicU32,
+    poisoned: AtomicBool,
+    id: ParamID,
+    points: Vec<(i32, ParamValue)>,
+}
+
+static PARAM_QUEUE_VTBL: IParamValueQueueVTable = IParamValueQueueVTable {
+    query_interface: pq_query_interface,
+    add_ref: pq_add_ref,
+    release: pq_release,
+    get_parameter_id: pq_get_parameter_id,
+    get_point_count: pq_get_point_count,
+    get_point: pq_get_point,
+    add_point: pq_add_point,
+};
+
+impl ParamQueue {
+    fn new(points: usize) -> Self {
+        Self {
+            vtbl: &PARAM_QUEUE_VTBL,
+            refs: AtomicU32::new(1),
+            poisoned: AtomicBool::new(false),
+            id: 0,
+            points: Vec::with_capacity(points),
+        }
+    }
+
+    pub fn id(&self) -> ParamID {
+        self.id
+    }
+
+    /// `(sample offset, normalized value)` in offset order.
+    pub fn points(&self) -> &[(i32, ParamValue)] {
+        &self.points
+    }
+
+    /// A point at an offset already present replaces its value. Returns the point's
+    /// index, or `None` when the queue is full.
+    fn add(&mut self, offset: i32, value: ParamValue) -> Option<usize> {
+        let at = self.points.partition_point(|&(o, _)| o < offset);
+        if self.points.get(at).is_some_and(|&(o, _)| o == offset) {
+            self.points[at].1 = value;
+            return Some(at);
+        }
+        if self.points.len() == self.points.capacity() {
+            return None;
+        }
+        self.points.insert(at, (offset, value));
+        Some(at)
+    }
+}
+
+/// Fixed-capacity `IParameterChanges`: up to `params` parameters with up to
+/// `points` points each per block.
+#[repr(C)]
+pub struct ParameterChanges {
+    vtbl: *const IParameterChangesVTable,
+    refs: AtomicU32,
+    poisoned: AtomicBool,
+    /// Allocated once so queue addresses handed to the plugin stay valid.
+    queues: Box<[ParamQueue]>,
+    used: usize,
+    /// `add_point` calls refused since the last `clear`.
+    dropped: usize,
+}
+
+// As for `EventList`.
+unsafe impl Send for ParameterChanges {}
+
+static PARAMETER_CHANGES_VTBL: IParameterChangesVTable = IParameterChangesVTable {
+    query_interface: pc_query_interface,
+    add_ref: pc_add_ref,
+    release: pc_release,
+    get_parameter_count: pc_get_parameter_count,
+    get_parameter_data: pc_get_parameter_data,
+    add_parameter_data: pc_add_parameter_data,
+};
+
+impl ParameterChanges {
+    pub fn with_capacity(params: usize, points: usize) -> Box<Self> {
+        Box::new(Self {
+            vtbl: &PARAMETER_CHANGES_VTBL,
+            refs: AtomicU32::new(1),
+            poisoned: AtomicBool::new(false),
+            queues: (0..params).map(|_| ParamQueue::new(points)).collect(),
+            used: 0,
+            dropped: 0,
+        })
+    }
+
+    /// Add a point for `id`, opening its queue if needed. Returns false when either
+    /// the parameter table or that parameter's queue is full.
+    pub fn add_point(&mut self, id: ParamID, offset: i32, value: ParamValue) -> bool {
+        let added = match self.queue_index(id) {
+            Some(i) => self.queues[i].add(offset, value).is_some(),
+            None => false,
+        };
+        self.dropped += usize::from(!added);
+        added
+    }
+
+    /// Index of `id`'s queue, opening a new one if there is room.
+    fn queue_index(&mut self, id: ParamID) -> Option<usize> {
+        if let Some(i) = self.queues[..self.used].iter().position(|q| q.id == id) {
+            return Some(i);
+        }
+        let queue = self.queues.get_mut(self.used)?;
+        queue.id = id;
+        queue.points.clear();
+        self.used += 1;
+        Some(self.used - 1)
+    }
+
+    pub fn clear(&mut self) {
+        for queue in &mut self.queues[..self.used] {
+            queue.points.clear();
+        }
+        self.used = 0;
+        self.dropped = 0;
+    }
+
+    /// Points refused for lack of room since the last [`clear`](Self::clear).
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Parameters with changes this block.
+    pub fn queues(&self) -> &[ParamQueue] {
+        &self.queues[..self.used]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.used == 0
+    }
+
+    /// Whether a plugin call on the changes or one of their queues panicked; that
+    /// object then refuses every call.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Acquire)
+            || self
+                .queues
+                .iter()
+                .any(|q| q.poisoned.load(Ordering::Acquire))
+    }
+
+    /// Pointer for `ProcessData::input_parameter_changes`/`output_parameter_changes`.
+    /// Valid while `self` is alive.
+    pub fn as_iparameter_changes(&mut self) -> *mut IParameterChanges {
+        self as *mut Self as *mut IParameterChanges
+    }
+}
+
+unsafe fn changes<'a>(this: *mut IParameterChanges) -> &'a mut ParameterChanges {
+    &mut *(this as *mut ParameterChanges)
+}
+
+unsafe fn param_queue<'a>(this: *mut IParamValueQueue) -> &'a mut ParamQueue {
+    &mut *(this as *mut ParamQueue)
+}
+
+unsafe extern "C" fn pc_query_interface(
+    this_: *mut FUnknown,
+    iid: *const Fuid,
+    obj: *mut *mut c_void,
+) -> tresult {
+    let c = changes(this_ as *mut IParameterChanges);
+    contain(&c.poisoned, K_INTERNAL_ERR, || {
+        query(this_, &iids::IPARAMETER_CHANGES, iid, obj, &c.refs)
+    })
+}
+
+unsafe extern "C" fn pc_add_ref(this_: *mut FUnknown) -> u32 {
+    let c = changes(this_ as *mut IParameterChanges);
+    contain(&c.poisoned, 1, || {
+        c.refs.fetch_add(1, Ordering::Relaxed) + 1
+    })
+}
+
+unsafe extern "C" fn pc_release(this_: *mut FUnknown) -> u32 {
+    let c = changes(this_ as *mut IParameterChanges);
+    contain(&c.poisoned, 1, || release(&c.refs))
+}
+
+unsafe extern "C" fn pc_get_parameter_count(this_: *mut IParameterChanges) -> i32 {
+    let c = changes(this_);
+    contain(&c.poisoned, 0, || c.used as i32)
+}
+
+unsafe extern "C" fn pc_get_parameter_data(
+    this_: *mut IParameterChanges,
+    index: i32,
+) -> *mut IParamValueQueue {
+    let c = changes(this_);
+    let poisoned = &c.poisoned as *const AtomicBool;
+    contain(
+        &*poisoned,
+        core::ptr::null_mut(),
+        || match usize::try_from(index).ok().filter(|&i| i < c.used) {
+            Some(i) => &mut c.queues[i] as *mut ParamQueue as *mut IParamValueQueue,
+            None => core::ptr::null_mut(),
+        },
+    )
+}
+
+unsafe extern "C" fn pc_add_parameter_data(
+    this_: *mut IParameterChanges,
+    id: *const ParamID,
+    index: *mut i32,
+) -> *mut IParamValueQueue {
+    let c = changes(this_);
+    let poisoned = &c.poisoned as *const AtomicBool;
+    contain(&*poisoned, core::ptr::null_mut(), || {
+        if id.is_null() {
+            return core::ptr::null_mut();
+        }
+        match c.queue_index(*id) {
+            Some(i) => {
+                if !index.is_null() {
+                    *index = i as i32;
+                }
+                &mut c.queues[i] as *mut ParamQueue as *mut IParamValueQueue
+            }
+            None => core::ptr::null_mut(),
+        }
+    })
+}
+
+unsafe extern "C" fn pq_query_interface(
+    this_: *mut FUnknown,
+    iid: *const Fuid,
+    obj: *mut *mut c_void,
+) -> tresult {
+    let q = param_queue(this_ as *mut IParamValueQueue);
+    contain(&q.poisoned, K_INTERNAL_ERR, || {
+        query(this_, &iids::IPARAM_VALUE_QUEUE, iid, obj, &q.refs)
+    })
+}
+
+unsafe extern "C" fn pq_add_ref(this_: *mut FUnknown) -> u32 {
+    let q = param_queue(this_ as *mut IParamValueQueue);
+    contain(&q.poisoned, 1, || {
+        q.refs.fetch_add(1, Ordering::Relaxed) + 1
+    })
+}
+
+unsafe extern "C" fn pq_release(this_: *mut FUnknown) -> u32 {
+    let q = param_queue(this_ as *mut IParamValueQueue);
+    contain(&q.poisoned, 1, || release(&q.refs))
+}
+
+unsafe extern "C" fn pq_get_parameter_id(this_: *mut IParamValueQueue) -> ParamID {
+    let q = param_queue(this_);
+    contain(&q.poisoned, NO_PARAM_ID, || q.id)
+}
+
+unsafe extern "C" fn pq_get_point_count(this_: *mut IParamValueQueue) -> i32 {
+    let q = param_queue(this_);
+    contain(&q.poisoned, 0, || q.points.len() as i32)
+}
+
+unsafe extern "C" fn pq_get_point(
+    this_: *mut IParamValueQueue,
+    index: i32,
+    sample_offset: *mut i32,
+    value: *mut ParamValue,
+) -> tresult {
+    let q = param_queue(this_);
+    contain(&q.poisoned, K_INTERNAL_ERR, || {
+        if sample_offset.is_null() || value.is_null() {
+            return K_INVALID_ARG;
+        }
+        match usize::try_from(index).ok().and_then(|i| q.points.get(i)) {
+            Some(&(offset, v)) => {
+                *sample_offset = offset;
+                *value = v;
+                K_RESULT_OK
+            }
+            None => K_INVALID_ARG,
+        }
+    })
+}
+
+unsafe extern "C" fn pq_add_point(
+    this_: *mut IParamValueQueue,
+    sample_offset: i32,
+    value: ParamValue,
+    index: *mut i32,
+) -> tresult {
+    let q = param_queue(this_);
+    let poisoned = &q.poisoned as *const AtomicBool;
+    contain(&*poisoned, K_INTERNAL_ERR, || {
+        match q.add(sample_offset, value) {
+            Some(i) => {
+                if !index.is_null() {
+                    *index = i as i32;
+                }
+                K_RESULT_OK
+            }
+            None => K_RESULT_FALSE,
+        }
+    })
+}
+
+// --- ParamRingBuffer ----------------------------------------------------------
+
+/// Sending end of a [`ParamRingBuffer`], for the thread making the edits.
+pub struct ParamSender {
+    ring: Producer<(ParamID, ParamValue)>,
+}
+
+impl ParamSender {
+    /// Queue `id = value` for the next block; false if the queue is full.
+    pub fn send(&mut self, id: ParamID, value: ParamValue) -> bool {
+        self.ring.push((id, value))
+    }
+}
+
+/// Parameter edits from a non-audio thread (a UI, a console), applied at the start
+/// of the next block. Lock-free in both directions.
+pub struct ParamRingBuffer {
+    ring: Consumer<(ParamID, ParamValue)>,
+}
+
+impl ParamRingBuffer {
+    /// A queue holding up to `capacity` edits.
+    pub fn with_capacity(capacity: usize) -> (ParamSender, ParamRingBuffer) {
+        let (producer, consumer) = ring::ring(capacity);
+        (
+            ParamSender { ring: producer },
+            ParamRingBuffer { ring: consumer },
+        )
+    }
+
+    /// Move queued edits into `changes` at sample offset 0; of two edits to the same
+    /// parameter the later wins. Edits that do not fit stay queued for the next block.
+    /// Returns how many were moved.
+    pub fn drain_into(&mut self, changes: &mut ParameterChanges) -> usize {
+        let mut moved = 0;
+        while let Some((id, value)) = self.ring.peek() {
+            if !changes.add_point(id, 0, value) {
+                break;
+            }
+            self.ring.skip(1);
+            moved += 1;
+        }
+        moved
+    }
+
+    /// Like [`drain_into`](Self::drain_into), but edits to parameters `smoother`
+    /// tracks become its new targets, and its ramps are then written for a block of
+    /// `frames`. Edits to other parameters land at sample offset 0 as before.
+    pub fn drain_smoothed(
+        &mut self,
+        smoother: &mut ParamSmoother,
+        frames: usize,
+        changes: &mut ParameterChanges,
+    ) -> usize {
+        let mut moved = 0;
+        while let Some((id, value)) = self.ring.peek() {
+            if !smoother.set_target(id, value) && !changes.add_point(id, 0, value) {
+                break;
+            }
+            self.ring.skip(1);
+            moved += 1;
+        }
+        smoother.write_block(frames, changes);
+        moved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn event_list_keeps_sample_order_and_capacity() {
+        let mut list = EventList::with_capacity(3);
+        assert!(list.push(openvst3_abi::Event::note_on(32, 0, 60, 1.0)));
+        assert!(list.push(openvst3_abi::Event::note_on(0, 0, 64, 1.0)));
+        assert!(list.push(openvst3_abi::Event::note_off(32, 0, 60, 0.0)));
+        assert!(!list.push(openvst3_abi::Event::note_off(40, 0, 64, 0.0)));
+        unsafe {
+            let ilist = &mut *list.as_ievent_list();
+            assert_eq!(ilist.get_event_count(), 3);
+            let mut e = openvst3_abi::Event::default();
+            assert_eq!(ilist.get_event(0, &mut e), K_RESULT_OK);
+            assert_eq!(e.sample_offset, 0);
+            // Equal offsets stay in arrival order: the note-on before its note-off.
+            assert_eq!(ilist.get_event(2, &mut e), K_RESULT_OK);
+            assert_eq!(e.type_, openvst3_abi::event_consts::NOTE_OFF);
+            assert_eq!(ilist.get_event(3, &mut e), K_INVALID_ARG);
+            assert_eq!(ilist.add_event(&mut e), K_RESULT_FALSE);
+        }
+        list.clear();
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn parameter_changes_group_points_per_parameter() {
+        let mut changes = ParameterChanges::with_capacity(2, 2);
+        assert!(changes.add_point(7, 0, 0.25));
+        assert!(changes.add_point(7, 0, 0.5));
+        assert!(changes.add_point(7, 10, 0.75));
+        assert!(!changes.add_point(7, 20, 1.0));
+        assert!(changes.add_point(9, 5, 1.0));
+        assert!(!changes.add_point(11, 5, 1.0));
+        unsafe {
+            let ichanges = &mut *changes.as_iparameter_changes();
+            assert_eq!(ichanges.get_parameter_count(), 2);
+            let queue = &mut *ichanges.get_parameter_data(0);
+            assert_eq!(queue.get_parameter_id(), 7);
+            assert_eq!(queue.get_point_count(), 2);
+            let (mut offset, mut value) = (0, 0.0);
+            assert_eq!(queue.get_point(0, &mut offset, &mut value), K_RESULT_OK);
+            assert_eq!((offset, value), (0, 0.5));
+            assert!(ichanges.get_parameter_data(2).is_null());
+        }
+        changes.clear();
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn param_ring_buffer_applies_edits_at_block_start() {
+        let (mut tx, mut rx) = ParamRingBuffer::with_capacity(8);
+        assert!(tx.send(1, 0.25));
+        assert!(tx.send(2, 0.5));
+        assert!(tx.send(1, 0.75));
+        assert!(tx.send(3, 1.0));
+        let mut changes = ParameterChanges::with_capacity(2, 4);
+        // The third parameter does not fit and waits for the next block.
+        assert_eq!(rx.drain_into(&mut changes), 3);
+        let queues = changes.queues();
+        assert_eq!(queues[0].id(), 1);
+        assert_eq!(queues[0].points(), &[(0, 0.75)]);
+        assert_eq!(queues[1].points(), &[(0, 0.5)]);
+        changes.clear();
+        assert_eq!(rx.drain_into(&mut changes), 1);
+        assert_eq!(changes.queues()[0].id(), 3);
+    }
+}
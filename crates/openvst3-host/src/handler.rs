@@ -0,0 +1,298 @@
+//! Host `IComponentHandler`: how an edit controller reports edits and restarts.
+//!
+//! [`ComponentHandler`] forwards each call as a [`HandlerEvent`] to a Rust callback
+//! and answers `kResultOk`. The callback runs on whatever thread the plugin calls
+//! from, usually the UI thread, under a lock, so it must not call back into the
+//! controller. A panic in it is contained (see [`callback`](crate::callback)):
+//! the plugin gets `kInternalError` and the handler refuses every later call.
+//! `restartComponent` first drops stale names from the tracked [`NameCache`]s,
+//! `performEdit` first stores the value in the tracked [`ParamCache`]s, the three
+//! edit calls go to the tracked [`AutomationRecorder`]s, and every call is queued in
+//! the tracked [`EditEvents`].
+
+use core::ffi::c_void;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, Weak};
+
+use openvst3_abi::{
+    iids, int32, tresult, FUnknown, Fuid, IComponentHandler, IComponentHandlerVTable, ParamID,
+    ParamValue, K_INTERNAL_ERR, K_INVALID_ARG, K_NO_INTERFACE, K_RESULT_OK,
+};
+
+use crate::callback::contain;
+use crate::plugin::check;
+use crate::{AutomationRecorder, ControllerHandle, EditEvents, HostError, NameCache, ParamCache};
+
+/// One call a plugin made on the [`ComponentHandler`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HandlerEvent {
+    BeginEdit(ParamID),
+    PerformEdit {
+        id: ParamID,
+        value: ParamValue,
+    },
+    EndEdit(ParamID),
+    /// `restartComponent` with its `RestartFlags`.
+    Restart(int32),
+}
+
+type Callback = Box<dyn FnMut(HandlerEvent) + Send>;
+
+/// See the module docs. Like [`MemoryStream`](crate::MemoryStream), the host owns it
+/// through the `Box`; plugin-side `addRef`/`release` never free it.
+#[repr(C)]
+pub struct ComponentHandler {
+    vtbl: *const IComponentHandlerVTable,
+    refs: AtomicU32,
+    poisoned: AtomicBool,
+    callback: Mutex<Callback>,
+    /// Invalidated by `restartComponent` before the callback sees it.
+    names: Mutex<Vec<Weak<NameCache>>>,
+    /// Given `performEdit` values before the callback sees them.
+    params: Mutex<Vec<Weak<ParamCache>>>,
+    /// Given the edit calls before the callback sees them.
+    recorders: Mutex<Vec<Weak<AutomationRecorder>>>,
+    /// Queue every call before the callback sees it.
+    edits: Mutex<Vec<Weak<EditEvents>>>,
+}
+
+static COMPONENT_HANDLER_VTBL: IComponentHandlerVTable = IComponentHandlerVTable {
+    query_interface: h_query_interface,
+    add_ref: h_add_ref,
+    release: h_release,
+    begin_edit: h_begin_edit,
+    perform_edit: h_perform_edit,
+    end_edit: h_end_edit,
+    restart_component: h_restart_component,
+};
+
+impl ComponentHandler {
+    pub fn new(callback: impl FnMut(HandlerEvent) + Send + 'static) -> Box<Self> {
+        Box::new(Self {
+            vtbl: &COMPONENT_HANDLER_VTBL,
+            refs: AtomicU32::new(1),
+            poisoned: AtomicBool::new(false),
+            callback: Mutex::new(Box::new(callback)),
+            names: Mutex::new(Vec::new()),
+            params: Mutex::new(Vec::new()),
+            recorders: Mutex::new(Vec::new()),
+            edits: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Drop names from `cache` when the plugin restarts with flags that make them
+    /// stale. Caches are held weakly.
+    pub fn track_names(&self, cache: &Arc<NameCache>) {
+        let mut names = self.names.lock().unwrap();
+        names.retain(|n| n.strong_count() > 0);
+        if !names.iter().any(|n| n.as_ptr() == Arc::as_ptr(cache)) {
+            names.push(Arc::downgrade(cache));
+        }
+    }
+
+    /// Store the values of `performEdit` calls in `cache`, subject to its rule that
+    /// the processor wins within a block. Caches are held weakly.
+    pub fn track_params(&self, cache: &Arc<ParamCache>) {
+        let mut params = self.params.lock().unwrap();
+        params.retain(|p| p.strong_count() > 0);
+        if !params.iter().any(|p| p.as_ptr() == Arc::as_ptr(cache)) {
+            params.push(Arc::downgrade(cache));
+        }
+    }
+
+    /// Record `beginEdit`, `performEdit` and `endEdit` calls in `recorder`.
+    /// Recorders are held weakly.
+    pub fn track_automation(&self, recorder: &Arc<AutomationRecorder>) {
+        let mut recorders = self.recorders.lock().unwrap();
+        recorders.retain(|r| r.strong_count() > 0);
+        if !recorders
+            .iter()
+            .any(|r| r.as_ptr() == Arc::as_ptr(recorder))
+        {
+            recorders.push(Arc::downgrade(recorder));
+        }
+    }
+
+    /// Queue every call in `events`, which bounds and coalesces them for a consumer
+    /// that polls. Queues are held weakly.
+    pub fn track_edits(&self, events: &Arc<EditEvents>) {
+        let mut edits = self.edits.lock().unwrap();
+        edits.retain(|e| e.strong_count() > 0);
+        if !edits.iter().any(|e| e.as_ptr() == Arc::as_ptr(events)) {
+            edits.push(Arc::downgrade(events));
+        }
+    }
+
+    /// Pass `event` to the tracked recorders and queues, unless the handler is
+    /// poisoned.
+    fn record(&self, event: HandlerEvent) {
+        if self.is_poisoned() {
+            return;
+        }
+        // Nothing here panics.
+        let tracked = self.edits.lock();
+        for events in tracked.unwrap_or_else(PoisonError::into_inner).iter() {
+            if let Some(events) = events.upgrade() {
+                events.push(event);
+            }
+        }
+        let tracked = self.recorders.lock();
+        for recorder in tracked.unwrap_or_else(PoisonError::into_inner).iter() {
+            let Some(recorder) = recorder.upgrade() else {
+                continue;
+            };
+            match event {
+                HandlerEvent::BeginEdit(id) => recorder.begin_edit(id),
+                HandlerEvent::PerformEdit { id, value } => recorder.perform_edit(id, value),
+                HandlerEvent::EndEdit(id) => recorder.end_edit(id),
+                HandlerEvent::Restart(_) => {}
+            }
+        }
+    }
+
+    /// Pointer to hand to `setComponentHandler`. Valid while `self` is alive; the
+    /// plugin's calls only touch atomics and mutexes, so `&self` is enough.
+    pub fn as_icomponent_handler(&self) -> *mut IComponentHandler {
+        self as *const Self as *mut IComponentHandler
+    }
+
+    /// Whether the callback panicked; the handler then refuses every call.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Acquire)
+    }
+}
+
+impl ControllerHandle {
+    /// Give the controller `handler`, which must outlive the controller's use of it:
+    /// until another handler is set or the controller is terminated. The handler
+    /// keeps this controller's [`names`](ControllerHandle::names) current.
+    pub fn set_component_handler(&self, handler: &ComponentHandler) -> Result<(), HostError> {
+        handler.track_names(self.names());
+        check(traced!("IEditController", "setComponentHandler", unsafe {
+            (*self.as_ptr()).set_component_handler(handler.as_icomponent_handler())
+        }))
+    }
+}
+
+unsafe fn handler<'a>(this: *mut IComponentHandler) -> &'a ComponentHandler {
+    &*(this as *mut ComponentHandler)
+}
+
+/// Pass `event` to the callback of the handler at `this`.
+unsafe fn dispatch(this: *mut IComponentHandler, event: HandlerEvent) -> tresult {
+    let h = handler(this);
+    contain(&h.poisoned, K_INTERNAL_ERR, || {
+        (h.callback.lock().unwrap())(event);
+        K_RESULT_OK
+    })
+}
+
+unsafe extern "C" fn h_query_interface(
+    this_: *mut FUnknown,
+    iid: *const Fuid,
+    obj: *mut *mut c_void,
+) -> tresult {
+    let h = handler(this_ as *mut IComponentHandler);
+    contain(&h.poisoned, K_INTERNAL_ERR, || {
+        if obj.is_null() || iid.is_null() {
+            return K_INVALID_ARG;
+        }
+        if *iid == iids::ICOMPONENT_HANDLER || *iid == iids::FUNKNOWN {
+            h.refs.fetch_add(1, Ordering::Relaxed);
+            *obj = this_ as *mut c_void;
+            return K_RESULT_OK;
+        }
+        *obj = core::ptr::null_mut();
+        K_NO_INTERFACE
+    })
+}
+
+unsafe extern "C" fn h_add_ref(this_: *mut FUnknown) -> u32 {
+    let h = handler(this_ as *mut IComponentHandler);
+    contain(&h.poisoned, 1, || {
+        h.refs.fetch_add(1, Ordering::Relaxed) + 1
+    })
+}
+
+unsafe extern "C" fn h_release(this_: *mut FUnknown) -> u32 {
+    let h = handler(this_ as *mut IComponentHandler);
+    contain(&h.poisoned, 1, || crate::events::release(&h.refs))
+}
+
+unsafe extern "C" fn h_begin_edit(this_: *mut IComponentHandler, id: ParamID) -> tresult {
+    handler(this_).record(HandlerEvent::BeginEdit(id));
+    dispatch(this_, HandlerEvent::BeginEdit(id))
+}
+
+unsafe extern "C" fn h_perform_edit(
+    this_: *mut IComponentHandler,
+    id: ParamID,
+    value: ParamValue,
+) -> tresult {
+    let h = handler(this_);
+    if !h.is_poisoned() {
+        // Nothing here panics.
+        let tracked = h.params.lock();
+        for params in tracked.unwrap_or_else(PoisonError::into_inner).iter() {
+            if let Some(params) = params.upgrade() {
+                params.edit(id, value);
+            }
+        }
+    }
+    h.record(HandlerEvent::PerformEdit { id, value });
+    dispatch(this_, HandlerEvent::PerformEdit { id, value })
+}
+
+unsafe extern "C" fn h_end_edit(this_: *mut IComponentHandler, id: ParamID) -> tresult {
+    handler(this_).record(HandlerEvent::EndEdit(id));
+    dispatch(this_, HandlerEvent::EndEdit(id))
+}
+
+unsafe extern "C" fn h_restart_component(this_: *mut IComponentHandler, flags: int32) -> tresult {
+    let h = handler(this_);
+    // Even for a poisoned handler: stale names would outlive it. Nothing here panics.
+    let tracked = h.names.lock();
+    for names in tracked.unwrap_or_else(PoisonError::into_inner).iter() {
+        if let Some(names) = names.upgrade() {
+            names.invalidate(flags);
+        }
+    }
+    h.record(HandlerEvent::Restart(flags));
+    dispatch(this_, HandlerEvent::Restart(flags))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::callback::take_last_callback_panic;
+
+    #[test]
+    fn panicking_handler_callback_is_contained_and_poisons() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        let calls = Arc::new(AtomicUsize::new(0));
+        let seen = calls.clone();
+        let handler = ComponentHandler::new(move |event| {
+            seen.fetch_add(1, Ordering::Relaxed);
+            if let HandlerEvent::PerformEdit { id, .. } = event {
+                panic!("edit of {id} went wrong");
+            }
+        });
+        let raw = handler.as_icomponent_handler();
+        unsafe {
+            assert_eq!((*raw).begin_edit(7), K_RESULT_OK);
+            assert!(take_last_callback_panic().is_none());
+            // The panic stays on this side of the vtable and reaches the plugin as an error.
+            assert_eq!((*raw).perform_edit(7, 0.5), K_INTERNAL_ERR);
+        }
+        assert_eq!(
+            take_last_callback_panic().as_deref(),
+            Some("edit of 7 went wrong")
+        );
+        assert!(handler.is_poisoned());
+        // Poisoned: later calls fail without reaching the callback.
+        assert_eq!(unsafe { (*raw).end_edit(7) }, K_INTERNAL_ERR);
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert!(take_last_callback_panic().is_none());
+    }
+}
@@ -0,0 +1,189 @@
+//! Run plugin work in a child process so a crash or hang cannot take the host down.
+//!
+//! The protocol is the caller's: typically a tool re-executes itself with a hidden
+//! worker flag, the worker prints its result to stdout, and the parent parses it.
+//! This module only spawns the child, enforces the deadline and tells a normal exit
+//! (successful or not) apart from an abnormal death.
+
+use std::io::Read;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::HostError;
+
+/// How often the parent polls a running child.
+const POLL: Duration = Duration::from_millis(5);
+
+/// A child that exited on its own, with whatever status it chose.
+#[derive(Debug)]
+pub struct WorkerOutput {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// From [`run_isolated_raw`]: how the child died, if it crashed, in the words of
+    /// [`HostError::Crashed`].
+    pub crash: Option<String>,
+}
+
+/// Spawn `cmd` with piped output and wait at most `timeout`.
+///
+/// A child that outlives the deadline is killed ([`HostError::TimedOut`] naming
+/// `what`); one that dies from a signal or an access violation becomes
+/// [`HostError::Crashed`] with the last line it wrote to stderr, if any.
+pub fn run_isolated(
+    cmd: &mut Command,
+    what: &'static str,
+    timeout: Duration,
+) -> Result<WorkerOutput, HostError> {
+    let out = run_isolated_raw(cmd, what, timeout)?;
+    match out.crash {
+        Some(crash) => Err(HostError::Crashed(crash)),
+        None => Ok(out),
+    }
+}
+
+/// [`run_isolated`], but a crashed child is reported in [`WorkerOutput::crash`] along
+/// with everything it wrote before dying, e.g. a [`crash_marker`](crate::crash_marker)
+/// record.
+pub fn run_isolated_raw(
+    cmd: &mut Command,
+    what: &'static str,
+    timeout: Duration,
+) -> Result<WorkerOutput, HostError> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| HostError::WorkerSpawn(e.to_string()))?;
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+    let Some(status) = wait_until(&mut child, Instant::now() + timeout) else {
+        // Don't join the readers: a grandchild may still hold the pipes open.
+        return Err(HostError::TimedOut {
+            method: what,
+            after: timeout,
+        });
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    let crash = crash_cause(&status).map(|cause| {
+        let last = String::from_utf8_lossy(&stderr)
+            .lines()
+            .rev()
+            .find(|l| !l.trim().is_empty())
+            .map(|l| format!(" after \"{}\"", l.trim()))
+            .unwrap_or_default();
+        format!("{cause}{last}")
+    });
+    Ok(WorkerOutput {
+        status,
+        stdout,
+        stderr,
+        crash,
+    })
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+        buf
+    })
+}
+
+/// `None` if the deadline passed; the child is then killed and reaped.
+fn wait_until(child: &mut Child, deadline: Instant) -> Option<ExitStatus> {
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(POLL),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn crash_cause(status: &ExitStatus) -> Option<String> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal().map(|sig| {
+        let name = match sig {
+            4 => " (SIGILL)",
+            6 => " (SIGABRT)",
+            7 => " (SIGBUS)",
+            8 => " (SIGFPE)",
+            9 => " (SIGKILL)",
+            11 => " (SIGSEGV)",
+            _ => "",
+        };
+        format!("signal {sig}{name}")
+    })
+}
+
+/// NTSTATUS exception codes (0xC0000005 access violation, ...) are crashes; anything
+/// else is an exit code the worker chose.
+#[cfg(windows)]
+fn crash_cause(status: &ExitStatus) -> Option<String> {
+    status
+        .code()
+        .map(|c| c as u32)
+        .filter(|&c| c >= 0xC000_0000)
+        .map(|c| format!("exception {c:#010x}"))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn crash_cause(_status: &ExitStatus) -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    #[cfg_attr(miri, ignore = "spawns processes")]
+    fn isolated_worker_crashes_and_hangs_are_classified() {
+        use std::process::Command;
+        use std::time::Duration;
+
+        let sh = |script: &str| {
+            let mut cmd = Command::new("sh");
+            cmd.args(["-c", script]);
+            cmd
+        };
+        let out = run_isolated(
+            &mut sh("echo hi; exit 3"),
+            "worker",
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert_eq!(out.status.code(), Some(3));
+        assert_eq!(out.stdout, b"hi\n");
+
+        match run_isolated(
+            &mut sh("echo about to fail >&2; kill -SEGV $$"),
+            "worker",
+            Duration::from_secs(10),
+        ) {
+            Err(HostError::Crashed(msg)) => {
+                assert_eq!(msg, "signal 11 (SIGSEGV) after \"about to fail\"")
+            }
+            other => panic!("expected a crash, got {other:?}"),
+        }
+        assert!(matches!(
+            run_isolated(&mut sh("sleep 5"), "worker", Duration::from_millis(50)),
+            Err(HostError::TimedOut {
+                method: "worker",
+                ..
+            })
+        ));
+    }
+}
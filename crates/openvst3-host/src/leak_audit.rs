@@ -0,0 +1,170 @@
+//! Reference-leak audit (`leak-audit` feature).
+//!
+//! Every [`InterfacePtr`](crate::InterfacePtr) registers itself in a global table on
+//! creation/clone and deregisters on drop, keyed by interface pointer. Pointers
+//! created through a [`Module`](crate::Module) are attributed to it, and anything
+//! derived from them (clones, `query`) inherits the attribution, so
+//! [`Module::leak_report`](crate::Module::leak_report) lists the references a host
+//! still holds into that module. Registration only happens on create/clone/drop,
+//! never on the process path.
+
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// Owner id for pointers not created through a module.
+pub const UNOWNED: u64 = 0;
+
+struct Entry {
+    owner: u64,
+    interface: &'static str,
+    refs: usize,
+    /// Where the first reference was taken, if `RUST_BACKTRACE` enabled capture.
+    backtrace: Option<String>,
+}
+
+fn table() -> &'static Mutex<HashMap<usize, Entry>> {
+    static TABLE: OnceLock<Mutex<HashMap<usize, Entry>>> = OnceLock::new();
+    TABLE.get_or_init(Default::default)
+}
+
+pub(crate) fn next_owner_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+/// `openvst3_abi::IComponent` -> `IComponent`.
+fn short_name<T>() -> &'static str {
+    let full = core::any::type_name::<T>();
+    full.rsplit("::").next().unwrap_or(full)
+}
+
+fn capture() -> Option<String> {
+    let bt = Backtrace::capture();
+    (bt.status() == BacktraceStatus::Captured).then(|| bt.to_string())
+}
+
+/// Record one more reference to `ptr`. `parent` (the pointer it was derived from)
+/// supplies the owner when `ptr` is new.
+pub(crate) fn register<T>(ptr: usize, parent: Option<usize>) {
+    let mut table = table().lock().unwrap();
+    if let Some(e) = table.get_mut(&ptr) {
+        e.refs += 1;
+        return;
+    }
+    let owner = parent
+        .and_then(|p| table.get(&p))
+        .map_or(UNOWNED, |e| e.owner);
+    table.insert(
+        ptr,
+        Entry {
+            owner,
+            interface: short_name::<T>(),
+            refs: 1,
+            backtrace: capture(),
+        },
+    );
+}
+
+pub(crate) fn unregister(ptr: usize) {
+    let mut table = table().lock().unwrap();
+    if let Some(e) = table.get_mut(&ptr) {
+        e.refs -= 1;
+        if e.refs == 0 {
+            table.remove(&ptr);
+        }
+    }
+}
+
+/// Attribute `ptr` (already registered) to module `owner`.
+pub(crate) fn adopt(ptr: usize, owner: u64) {
+    if let Some(e) = table().lock().unwrap().get_mut(&ptr) {
+        e.owner = owner;
+    }
+}
+
+/// A pointer the host still holds references to.
+#[derive(Clone, Debug)]
+pub struct LeakedInterface {
+    pub ptr: usize,
+    pub interface: &'static str,
+    pub refs: usize,
+    pub backtrace: Option<String>,
+}
+
+/// Outstanding host references, sorted by pointer.
+#[derive(Clone, Debug, Default)]
+pub struct LeakReport {
+    pub leaked: Vec<LeakedInterface>,
+}
+
+impl LeakReport {
+    pub fn is_clean(&self) -> bool {
+        self.leaked.is_empty()
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_clean() {
+            return write!(f, "no outstanding interface references");
+        }
+        writeln!(
+            f,
+            "{} interface pointer(s) still referenced:",
+            self.leaked.len()
+        )?;
+        for l in &self.leaked {
+            writeln!(f, "  {} @ {:#x} ({} ref(s))", l.interface, l.ptr, l.refs)?;
+            if let Some(bt) = &l.backtrace {
+                for line in bt.lines() {
+                    writeln!(f, "      {line}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Outstanding references attributed to `owner` (`None` = all of them).
+pub(crate) fn report(owner: Option<u64>) -> LeakReport {
+    let table = table().lock().unwrap();
+    let mut leaked: Vec<_> = table
+        .iter()
+        .filter(|(_, e)| owner.is_none_or(|o| e.owner == o))
+        .map(|(&ptr, e)| LeakedInterface {
+            ptr,
+            interface: e.interface,
+            refs: e.refs,
+            backtrace: e.backtrace.clone(),
+        })
+        .collect();
+    leaked.sort_by_key(|l| l.ptr);
+    LeakReport { leaked }
+}
+
+/// Every outstanding reference, whichever module it belongs to.
+pub fn leak_report() -> LeakReport {
+    report(None)
+}
+
+impl crate::Module {
+    /// References still held into this module through `InterfacePtr`s.
+    pub fn leak_report(&self) -> LeakReport {
+        report(Some(self.audit_id))
+    }
+
+    /// Unload the module unless interface pointers into it are still held, in which
+    /// case the module is handed back with the offending references.
+    #[allow(clippy::result_large_err)] // the module itself is the error payload
+    pub fn try_unload(self) -> Result<(), (Self, LeakReport)> {
+        let report = self.leak_report();
+        if report.is_clean() {
+            Ok(())
+        } else {
+            Err((self, report))
+        }
+    }
+}
@@ -1,56 +1,593 @@
-use libloading::{Library, Symbol};
+#![allow(clippy::missing_safety_doc)]
+
+#[cfg(feature = "dlopen")]
+use libloading::Library;
+
+/// Evaluate a plugin call; with the `trace` feature, also emit an `openvst3::call`
+/// event carrying a sequence number, the given key arguments and the result.
+/// `@sampled <cond>;` skips the event (but not the call) when `cond` is false.
+macro_rules! traced {
+    (@sampled $when:expr; $iface:literal, $method:literal, $call:expr $(, $k:ident = $v:expr)* $(,)?) => {{
+        #[cfg(feature = "trace")]
+        let when: bool = $when;
+        let result = $call;
+        #[cfg(feature = "trace")]
+        if when {
+            ::tracing::debug!(
+                target: $crate::trace::CALL_TARGET,
+                seq = $crate::trace::next_seq(),
+                interface = $iface,
+                method = $method,
+                $($k = $v,)*
+                result = result,
+            );
+        }
+        result
+    }};
+    ($iface:literal, $method:literal, $call:expr $(, $k:ident = $v:expr)* $(,)?) => {
+        traced!(@sampled true; $iface, $method, $call $(, $k = $v)*)
+    };
+}
+
+pub mod analysis;
+pub mod arrangement;
+pub mod automation;
+pub mod bundle;
+pub mod buses;
+pub mod callback;
+pub mod channel_map;
+pub mod classes;
+pub mod clock_sync;
+pub mod crash_marker;
+pub mod delay;
+pub mod dsp;
+pub mod editor;
+pub mod edits;
+pub mod events;
+pub mod handler;
+pub mod input_gate;
+pub mod isolate;
+#[cfg(feature = "leak-audit")]
+pub mod leak_audit;
+pub mod limiter;
+pub mod limits;
+pub mod live;
+pub mod load_error;
+pub mod meter;
+pub mod midi;
+pub mod names;
+pub mod param_cache;
+pub mod params;
+pub mod plugin;
+pub mod pool;
+pub mod precision;
+pub mod prelude;
+pub mod preset;
+pub mod probe;
+pub mod process_data;
+pub mod render;
+pub mod ring;
+#[cfg(feature = "rt-check")]
+pub mod rt_check;
+#[cfg(feature = "dlopen")]
+pub mod scan;
+#[cfg(feature = "serde")]
+pub mod session;
+pub mod setup;
+pub mod smf;
+pub mod smoother;
+pub mod snapshot;
+pub mod stream;
+pub mod stress;
+pub mod tail;
+#[cfg(test)]
+mod tests;
+#[cfg(any(test, feature = "mock"))]
+pub mod testsupport;
+pub mod thread_priority;
+pub mod timing;
+#[cfg(feature = "trace")]
+pub mod trace;
+pub mod transport;
+pub mod validate;
+pub mod watchdog;
+
+pub use analysis::{analyze_block, BlockAnalysis};
+pub use arrangement::{
+    explain_arrangement_mismatch, format_arrangement, format_speakers, parse_arrangement,
+    ArrangementReport, BusArrangement,
+};
+pub use automation::{AutomationRecorder, Gesture};
+pub use buses::{BusCheck, BusDesc, BusId, BusLayout};
+pub use callback::take_last_callback_panic;
+pub use channel_map::ChannelMap;
+pub use classes::{
+    describe_class_flags, Cardinality, ClassEntry, ClassFilter, ClassInfo, FactoryInfo,
+    SubCategories, SubCategory,
+};
+pub use clock_sync::ClockSync;
+pub use crash_marker::ScanPhase;
+pub use delay::{DelayLine, LatencyCompensator};
+pub use editor::{PlugFrame, PlugView};
+pub use edits::{EditEvents, EditMode};
+pub use events::{EventList, ParamRingBuffer, ParamSender, ParameterChanges};
+pub use handler::{ComponentHandler, HandlerEvent};
+pub use input_gate::{GateAction, InputGate};
+pub use limiter::SafetyLimiter;
+pub use limits::{HostLimits, Limit, LimitOrigin};
+pub use live::{LiveGuard, LiveProcessor};
+pub use load_error::{DlopenError, MissingFactory};
+pub use meter::{Meter, MeterConfig};
+pub use midi::{events_to_midi, ChannelPolicy, MidiConverter, MidiMessage};
+pub use names::NameCache;
+pub use param_cache::ParamCache;
+pub use params::{describe_param_flags, ParamInfo};
+pub use plugin::{
+    create_audio_plugin, AudioPlugin, ComponentHandle, ControllerHandle, CreationPath,
+    InterfacePtr, IoMode, PluginArchitecture, PluginBuilder, PluginInstance, ProcessMode,
+    ProcessorHandle, ReloadState, SymbolicSampleSize, OUTPUT_POINTS_PER_PARAM,
+};
+pub use pool::{BlockIo, BlockIoCounters, BlockIoPool};
+pub use precision::{pass_block, PrecisionPlan, SlotPrecision};
+pub use preset::{list_presets, preset_locations, PresetFile, PresetLocations, VstPreset};
+pub use probe::{instantiate_and_probe, BusSummary, Capabilities, ClassProbe, ProbeResult};
+pub use process_data::{BoundProcessData, BusSample, ProcessStats};
+pub use render::{RenderConfig, RenderEvents, RenderedAudio, RenderedBus};
+#[cfg(all(feature = "dlopen", feature = "async"))]
+pub use scan::ScanStream;
+#[cfg(feature = "dlopen")]
+pub use scan::{
+    compare_versions, DuplicateGroup, DuplicatePolicy, ScanCache, ScanDiff, ScanEvent, ScanEvents,
+    ScanFailure, ScanReport, ScannedPlugin, Scanner,
+};
+#[cfg(feature = "serde")]
+pub use session::{
+    AutomationLane, AutomationPoint, RenderSession, SessionBitDepth, SessionInput, SessionTail,
+};
+pub use setup::{ProcessSetupBuilder, SetupError, SetupWarning};
+pub use smf::read_smf;
+pub use smoother::ParamSmoother;
+pub use snapshot::{AbSlot, StateSnapshot};
+use std::fmt;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+pub use stream::MemoryStream;
+pub use stress::{StressAllow, StressFailure, StressOp, StressOptions, StressReport};
+pub use tail::{run_until_silent, SilenceDetector, Tail, TailRun};
 use thiserror::Error;
+pub use thread_priority::{promote_current_thread, Achieved, Profile};
+pub use timing::{Clock, MonotonicClock, TimingBudget, TimingCollector, TimingSnapshot};
+pub use transport::{SampleClock, Transport};
+pub use validate::{
+    BusPermutation, Check, CheckCase, CheckResult, Outcome, ProcessCase, ValidateOptions,
+    ValidationReport, MAX_EDITS_PER_SECOND,
+};
 
 use openvst3_abi::{
-    classinfo_consts, process_consts, AudioBusBuffers32, AudioBusBuffers64, BusInfo, FUnknown,
-    FactoryHandle, GetPluginFactoryProc, IAudioProcessor, IComponent, IPluginFactory, PClassInfo,
-    ProcessData32, ProcessData64, ProcessSetup, Tuid, BUS_DIR_OUTPUT, K_RESULT_OK,
+    classinfo_consts, iids, AudioBusBuffers32, AudioBusBuffers64, BusInfo, FUnknown, FactoryHandle,
+    GetPluginFactoryProc, IAudioProcessor, IComponent, IPluginFactory, PClassInfo, ProcessData32,
+    ProcessData64, ProcessSetup, Tuid, BUS_DIR_OUTPUT, K_NOT_IMPLEMENTED, K_RESULT_OK,
+    MEDIA_TYPE_AUDIO,
 };
 
+/// A factory class, by index or by CID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClassRef {
+    Index(i32),
+    Cid([u8; 16]),
+}
+
+impl fmt::Display for ClassRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClassRef::Index(i) => write!(f, "#{i}"),
+            ClassRef::Cid(cid) => write!(f, "{}", fmt_cid_hex(cid)),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum HostError {
     #[error("dlopen failed: {0}")]
-    Dlopen(String),
-    #[error("symbol `GetPluginFactory` not found")]
-    NoFactorySymbol,
+    Dlopen(DlopenError),
+    /// The binary loaded but exports `GetPluginFactory` under none of the names tried.
+    #[error("symbol `GetPluginFactory` not found: {0}")]
+    NoFactorySymbol(MissingFactory),
+    /// The binary loaded but exports none of the
+    /// [`VST3_ENTRY_POINTS`](load_error::VST3_ENTRY_POINTS); only told apart from
+    /// [`NoFactorySymbol`](Self::NoFactorySymbol) with the `diagnose` feature.
+    #[error("not a VST 3 module: {0}")]
+    NotVst3(MissingFactory),
     #[error("`GetPluginFactory` returned null")]
     NullFactory,
+    #[error("module entry point (ModuleEntry/InitDll) returned false")]
+    ModuleEntryFailed,
     #[error("not a valid VST3 bundle: {0}")]
     InvalidBundle(String),
     #[error("no platform binary found in bundle")]
     BinaryNotFound,
-    #[error("utf8 error in class info")]
-    Utf8,
+    #[error("I/O error: {0}")]
+    Io(String),
+    #[error("invalid IID: {0}")]
+    InvalidIid(String),
+    #[error("invalid .vstpreset: {0}")]
+    InvalidPreset(String),
+    #[error("invalid MIDI file: {0}")]
+    InvalidMidiFile(String),
+    #[error("invalid render session: {0}")]
+    InvalidSession(String),
+    #[error("invalid speaker arrangement: {0}")]
+    InvalidArrangement(String),
+    #[error("no class {0} in factory")]
+    ClassNotFound(ClassRef),
     #[error("tresult failure: {0}")]
     TErr(i32),
     #[error("allocation")]
     Alloc,
-    #[error("query interface failed")]
-    NoInterface,
+    /// `createInstance` refused the class or interface, or created nothing.
+    #[error(
+        "createInstance of {} as {} failed (tresult {result})",
+        describe_class(class_name, cid),
+        describe_iid(iid, name)
+    )]
+    CreateInstance {
+        cid: [u8; 16],
+        /// From the factory's class info, when it has the class.
+        class_name: Option<String>,
+        iid: Tuid,
+        /// The interface's name if it is a built-in one ([`iids::name_of`]).
+        name: Option<&'static str>,
+        result: i32,
+    },
+    /// `queryInterface` for `iid` failed or returned null.
+    #[error("QueryInterface for {} failed", describe_iid(iid, name))]
+    NoInterface {
+        iid: Tuid,
+        /// The interface's name if it is a built-in one ([`iids::name_of`]).
+        name: Option<&'static str>,
+    },
+    #[error("plugin rejected bus arrangements (inputs {inputs:#x?}, outputs {outputs:#x?})")]
+    ArrangementRejected { inputs: Vec<u64>, outputs: Vec<u64> },
+    #[error("`{call}` must be called before `{before}`")]
+    CallOrder {
+        call: &'static str,
+        before: &'static str,
+    },
+    #[error("block of {got} frames exceeds the maximum block size {max}")]
+    BlockSizeExceeded { got: usize, max: usize },
+    #[error("delay of {got} samples exceeds the maximum {max}")]
+    DelayTooLong { got: usize, max: usize },
+    /// An audio bus reported a channel count outside the [`HostLimits`].
+    #[error("{bus} reports {reported} channels")]
+    SuspiciousBusInfo { bus: BusId, reported: i32 },
+    /// A size taken from the plugin or a file is over one of the [`HostLimits`].
+    #[error("{limit} exceeded: {value} > {max}")]
+    LimitExceeded {
+        limit: Limit,
+        value: usize,
+        max: usize,
+        /// Only a [`LimitOrigin::Plugin`] overrun is the plugin's fault.
+        origin: LimitOrigin,
+    },
+    #[error("channel matrix row {row} has {got} gains, expected {expected}")]
+    RaggedChannelMatrix {
+        row: usize,
+        got: usize,
+        expected: usize,
+    },
+    #[error("plugin call `{method}` did not return within {after:?}")]
+    TimedOut {
+        method: &'static str,
+        after: Duration,
+    },
+    #[error("plugin call `{0}` panicked")]
+    Panicked(&'static str),
+    #[error("cannot start worker process: {0}")]
+    WorkerSpawn(String),
+    /// A sidechain was given to a plugin without an aux audio input bus.
+    #[error("the plugin has no aux audio input bus to feed a sidechain to")]
+    NoSidechainBus,
+    /// An isolated worker process died abnormally (signal, access violation).
+    #[error("plugin process crashed: {0}")]
+    Crashed(String),
+    /// A [`ProcessSetup`] that [`ProcessSetupBuilder`] refuses.
+    #[error("invalid process setup: {0}")]
+    InvalidSetup(#[from] SetupError),
+    /// The class's cardinality allows no more live instances from this module; see
+    /// [`PluginBuilder::ignore_cardinality`].
+    #[error("class {} allows at most {max} live instance(s)", fmt_cid_hex(cid))]
+    CardinalityExceeded { cid: [u8; 16], max: usize },
 }
 
-/// Handle for a loaded VST3 module binary
+impl HostError {
+    /// The plugin misbehaved: broken binary or bundle, failing entry points, error
+    /// results, missing interfaces, hangs. Scanners should blacklist on these.
+    pub fn is_plugin_fault(&self) -> bool {
+        matches!(
+            self,
+            HostError::Dlopen(_)
+                | HostError::NoFactorySymbol(_)
+                | HostError::NotVst3(_)
+                | HostError::NullFactory
+                | HostError::ModuleEntryFailed
+                | HostError::InvalidBundle(_)
+                | HostError::BinaryNotFound
+                | HostError::TErr(_)
+                | HostError::CreateInstance { .. }
+                | HostError::NoInterface { .. }
+                | HostError::ArrangementRejected { .. }
+                | HostError::SuspiciousBusInfo { .. }
+                | HostError::LimitExceeded {
+                    origin: LimitOrigin::Plugin,
+                    ..
+                }
+                | HostError::TimedOut { .. }
+                | HostError::Crashed(_)
+        )
+    }
+
+    /// [`NoInterface`](Self::NoInterface) for `iid`, named if it is built in.
+    pub fn no_interface(iid: Tuid) -> Self {
+        HostError::NoInterface {
+            iid,
+            name: iids::name_of(&iid),
+        }
+    }
+
+    /// The host asked for something invalid (bad IID, unknown class, oversized block)
+    /// or failed itself; the plugin is not to blame.
+    pub fn is_host_fault(&self) -> bool {
+        !self.is_plugin_fault()
+    }
+}
+
+/// Handle for a loaded VST3 module binary (or a statically-linked factory)
 pub struct Module {
-    lib: Library,
+    /// Keeps the plugin binary mapped; `None` for statically-linked factories.
+    #[cfg(feature = "dlopen")]
+    _lib: Option<Library>,
+    /// Whether the platform entry point (ModuleEntry/InitDll) succeeded and needs its exit twin.
+    #[cfg(feature = "dlopen")]
+    entered: bool,
+    /// Owner id for interface pointers created through this module.
+    #[cfg(feature = "leak-audit")]
+    audit_id: u64,
+    /// Class table, read from the factory on first use.
+    class_index: OnceLock<classes::ClassIndex>,
+    /// Live [`PluginInstance`]s per class id, for cardinality checks.
+    live_instances: Arc<plugin::InstanceCounts>,
     factory: FactoryHandle,
 }
 
+#[cfg(feature = "dlopen")]
+type ModuleEntryProc = unsafe extern "C" fn(*mut core::ffi::c_void) -> bool;
+#[cfg(feature = "dlopen")]
+type ModuleExitProc = unsafe extern "C" fn() -> bool;
+
+/// Names `GetPluginFactory` is looked up under before reading the exports. 32-bit
+/// Windows compilers decorate `__stdcall` and `__cdecl` names.
+#[cfg(all(feature = "dlopen", windows, target_arch = "x86"))]
+const FACTORY_SYMBOLS: &[&str] = &[
+    "GetPluginFactory",
+    "_GetPluginFactory@0",
+    "GetPluginFactory@0",
+    "_GetPluginFactory",
+];
+#[cfg(all(feature = "dlopen", not(all(windows, target_arch = "x86"))))]
+const FACTORY_SYMBOLS: &[&str] = &["GetPluginFactory"];
+
+/// `GetPluginFactory` only exported under the non-default symbol `version`, which
+/// `dlsym` passes over.
+#[cfg(all(feature = "dlopen", target_os = "linux", target_env = "gnu"))]
+unsafe fn versioned_symbol(
+    handle: *mut core::ffi::c_void,
+    version: &str,
+) -> Option<GetPluginFactoryProc> {
+    extern "C" {
+        fn dlvsym(
+            handle: *mut core::ffi::c_void,
+            symbol: *const core::ffi::c_char,
+            version: *const core::ffi::c_char,
+        ) -> *mut core::ffi::c_void;
+    }
+    let version = std::ffi::CString::new(version).ok()?;
+    let sym = dlvsym(handle, c"GetPluginFactory".as_ptr(), version.as_ptr());
+    (!sym.is_null())
+        .then(|| core::mem::transmute::<*mut core::ffi::c_void, GetPluginFactoryProc>(sym))
+}
+
+/// Versioned symbols need glibc's `dlvsym`.
+#[cfg(all(feature = "dlopen", not(all(target_os = "linux", target_env = "gnu"))))]
+unsafe fn versioned_symbol(
+    _handle: *mut core::ffi::c_void,
+    _version: &str,
+) -> Option<GetPluginFactoryProc> {
+    None
+}
+
+#[cfg(all(feature = "dlopen", target_os = "linux"))]
+const ENTRY_SYMBOLS: (&[u8], &[u8]) = (b"ModuleEntry\0", b"ModuleExit\0");
+#[cfg(all(feature = "dlopen", target_os = "windows"))]
+const ENTRY_SYMBOLS: (&[u8], &[u8]) = (b"InitDll\0", b"ExitDll\0");
+// macOS bundleEntry needs a CFBundleRef; not wired up yet.
+#[cfg(all(
+    feature = "dlopen",
+    not(any(target_os = "linux", target_os = "windows"))
+))]
+const ENTRY_SYMBOLS: (&[u8], &[u8]) = (b"\0", b"\0");
+
 impl Module {
+    #[cfg(feature = "dlopen")]
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, HostError> {
-        let lib =
-            unsafe { Library::new(path.as_ref()) }.map_err(|e| HostError::Dlopen(e.to_string()))?;
-        let get_factory: Symbol<GetPluginFactoryProc> = unsafe {
-            lib.get(b"GetPluginFactory\0")
-                .map_err(|_| HostError::NoFactorySymbol)?
+        crash_marker::enter(ScanPhase::Load);
+        let lib = unsafe { Library::new(path.as_ref()) }
+            .map_err(|e| HostError::Dlopen(DlopenError::new(path.as_ref(), &e)))?;
+        let (lib, handle, entered) = unsafe { Self::enter(lib)? };
+        crash_marker::enter(ScanPhase::Factory);
+        let raw = match unsafe { Self::factory_proc(&lib, handle, path.as_ref()) } {
+            Ok(get_factory) => Ok(unsafe { get_factory() }),
+            Err(missing) => Err(missing.into_error()),
         };
-        let raw = unsafe { get_factory() };
-        let factory = unsafe { FactoryHandle::new(raw) }.ok_or(HostError::NullFactory)?;
-        Ok(Self { lib, factory })
+        let factory =
+            raw.and_then(|raw| unsafe { FactoryHandle::new(raw) }.ok_or(HostError::NullFactory));
+        match factory {
+            Ok(factory) => Ok(Self {
+                _lib: Some(lib),
+                entered,
+                #[cfg(feature = "leak-audit")]
+                audit_id: leak_audit::next_owner_id(),
+                class_index: OnceLock::new(),
+                live_instances: Arc::default(),
+                factory,
+            }),
+            Err(e) => {
+                if entered {
+                    unsafe { Self::call_exit(&lib) };
+                }
+                Err(e)
+            }
+        }
     }
+
+    /// Call the platform entry point, if the binary exports one. Also returns the raw
+    /// `dlopen`/`LoadLibrary` handle.
+    #[cfg(feature = "dlopen")]
+    unsafe fn enter(lib: Library) -> Result<(Library, *mut core::ffi::c_void, bool), HostError> {
+        #[cfg(unix)]
+        let (lib, handle) = {
+            let raw = libloading::os::unix::Library::from(lib).into_raw();
+            (
+                Library::from(libloading::os::unix::Library::from_raw(raw)),
+                raw,
+            )
+        };
+        #[cfg(windows)]
+        let (lib, handle) = {
+            let raw = libloading::os::windows::Library::from(lib).into_raw();
+            (
+                Library::from(libloading::os::windows::Library::from_raw(raw)),
+                raw as *mut core::ffi::c_void,
+            )
+        };
+        if ENTRY_SYMBOLS.0.len() <= 1 {
+            return Ok((lib, handle, false));
+        }
+        let ok = match lib.get::<ModuleEntryProc>(ENTRY_SYMBOLS.0) {
+            Ok(entry) => entry(handle),
+            Err(_) => return Ok((lib, handle, false)),
+        };
+        if !ok {
+            return Err(HostError::ModuleEntryFailed);
+        }
+        Ok((lib, handle, true))
+    }
+
+    /// `GetPluginFactory` under the first of [`FACTORY_SYMBOLS`] the binary has, or
+    /// else under an [`is_factory_alias`](load_error::is_factory_alias) among its
+    /// [`exports`](load_error::exports).
+    #[cfg(feature = "dlopen")]
+    unsafe fn factory_proc(
+        lib: &Library,
+        handle: *mut core::ffi::c_void,
+        path: &Path,
+    ) -> Result<GetPluginFactoryProc, MissingFactory> {
+        let mut tried = Vec::new();
+        for name in FACTORY_SYMBOLS {
+            tried.push(name.to_string());
+            if let Ok(get_factory) = lib.get::<GetPluginFactoryProc>(name.as_bytes()) {
+                return Ok(*get_factory);
+            }
+        }
+        let exports = load_error::exports(path);
+        let aliases = exports
+            .iter()
+            .flatten()
+            .filter(|e| load_error::is_factory_alias(e));
+        for alias in aliases {
+            tried.push(alias.clone());
+            let found = match load_error::symbol_version(alias) {
+                Some(version) => versioned_symbol(handle, version),
+                None => lib
+                    .get::<GetPluginFactoryProc>(alias.as_bytes())
+                    .ok()
+                    .map(|f| *f),
+            };
+            if let Some(get_factory) = found {
+                return Ok(get_factory);
+            }
+        }
+        Err(MissingFactory {
+            path: path.to_path_buf(),
+            tried,
+            exports,
+        })
+    }
+
+    #[cfg(feature = "dlopen")]
+    unsafe fn call_exit(lib: &Library) {
+        if let Ok(exit) = lib.get::<ModuleExitProc>(ENTRY_SYMBOLS.1) {
+            exit();
+        }
+    }
+
+    /// Build a module from a `GetPluginFactory` function linked into this binary.
+    /// No dynamic loading is involved; the caller keeps the code alive.
+    ///
+    /// # Safety
+    /// `get_factory` must be a real `GetPluginFactory`: it returns null or a new
+    /// reference to a valid `IPluginFactory`. Its code and the factory must outlive the
+    /// module.
+    pub unsafe fn from_factory_proc(get_factory: GetPluginFactoryProc) -> Result<Self, HostError> {
+        Self::from_factory_ptr(get_factory())
+    }
+
+    /// Wrap an already-obtained factory pointer. The module takes over one reference
+    /// (as returned by `GetPluginFactory`) and releases it on drop.
+    ///
+    /// # Safety
+    /// `factory` must be null or point to a valid `IPluginFactory` that outlives the module.
+    pub unsafe fn from_factory_ptr(factory: *mut IPluginFactory) -> Result<Self, HostError> {
+        let factory = FactoryHandle::new(factory).ok_or(HostError::NullFactory)?;
+        Ok(Self {
+            #[cfg(feature = "dlopen")]
+            _lib: None,
+            #[cfg(feature = "dlopen")]
+            entered: false,
+            #[cfg(feature = "leak-audit")]
+            audit_id: leak_audit::next_owner_id(),
+            class_index: OnceLock::new(),
+            live_instances: Arc::default(),
+            factory,
+        })
+    }
+    /// The factory for code using other VST3 bindings. Borrowed: the module keeps
+    /// its reference, so the pointer is valid while the module lives and must not be
+    /// released; `addRef` it to keep it longer (the binary must stay loaded too).
+    #[inline]
+    pub fn factory_raw(&self) -> *mut IPluginFactory {
+        self.factory.as_ptr()
+    }
+
     #[inline]
     pub fn factory_mut(&mut self) -> &mut IPluginFactory {
-        self.factory.as_mut()
+        // Exclusive through `&mut self`; the factory lives as long as the module.
+        unsafe { &mut *self.factory.as_ptr() }
+    }
+}
+
+impl Drop for Module {
+    fn drop(&mut self) {
+        // Factory reference first, then the exit entry point, then dlclose (field drop).
+        unsafe {
+            let unk = self.factory.as_ptr() as *mut FUnknown;
+            FUnknown::release(unk);
+        }
+        #[cfg(feature = "dlopen")]
+        if let (true, Some(lib)) = (self.entered, self._lib.as_ref()) {
+            unsafe { Self::call_exit(lib) };
+        }
     }
 }
 unsafe impl Send for Module {}
@@ -68,73 +605,69 @@ impl BundlePath {
         if !b.is_dir() || b.extension().and_then(|s| s.to_str()) != Some("vst3") {
             return Err(HostError::InvalidBundle(format!("{}", b.display())));
         }
-        #[cfg(target_os = "macos")]
-        {
-            let p = b.join("Contents").join("MacOS");
-            let bin = std::fs::read_dir(&p)
-                .ok()
-                .and_then(|mut it| {
-                    it.find(|e| e.as_ref().ok().map_or(false, |ee| ee.path().is_file()))
-                        .ok()
-                })
-                .ok_or(HostError::BinaryNotFound)?;
-            return Ok(bin.path());
-        }
-        #[cfg(target_os = "linux")]
-        {
-            let arch = if cfg!(target_arch = "x86_64") {
-                "x86_64-linux"
-            } else if cfg!(target_arch = "aarch64") {
-                "aarch64-linux"
-            } else {
-                "unknown-linux"
-            };
-            let p = b.join("Contents").join(arch);
-            let bin = std::fs::read_dir(&p)
-                .ok()
-                .and_then(|mut it| {
-                    it.find(|e| e.as_ref().ok().map_or(false, |ee| ee.path().is_file()))
-                        .ok()
-                })
-                .ok_or(HostError::BinaryNotFound)?;
-            return Ok(bin.path());
-        }
-        #[cfg(target_os = "windows")]
-        {
-            let arch = if cfg!(target_arch = "x86_64") {
-                "x86_64-win"
-            } else {
-                "x86-win"
-            };
-            let p = b.join("Contents").join(arch);
-            let bin = std::fs::read_dir(&p)
-                .ok()
-                .and_then(|mut it| {
-                    it.find(|e| e.as_ref().ok().map_or(false, |ee| ee.path().is_file()))
-                        .ok()
-                })
-                .ok_or(HostError::BinaryNotFound)?;
-            return Ok(bin.path());
-        }
+        let p = b.join("Contents").join(bundle::platform_arch_dir());
+        let bin = std::fs::read_dir(&p)
+            .ok()
+            .and_then(|mut it| {
+                it.find(|e| e.as_ref().is_ok_and(|ee| ee.path().is_file()))
+                    .and_then(Result::ok)
+            })
+            .ok_or(HostError::BinaryNotFound)?;
+        Ok(bin.path())
     }
 }
 
 // ----- Class info helpers (v1) -----------------------------------------------
-fn cstr_from_i8_fixed(buf: &[i8]) -> Result<String, HostError> {
-    let mut bytes: Vec<u8> = Vec::with_capacity(buf.len());
-    for &ch in buf {
-        if ch == 0 {
-            break;
-        }
-        bytes.push(ch as u8);
+
+/// How a fixed-size class-info string was decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Ascii,
+    Utf8,
+    /// Not valid UTF-8 (e.g. Latin-1); invalid sequences became U+FFFD.
+    Lossy,
+}
+
+/// Decode a NUL-terminated fixed buffer: UTF-8 when valid, lossy replacement otherwise.
+pub fn decode_fixed_cstr(buf: &[i8]) -> (String, Encoding) {
+    let bytes: Vec<u8> = buf
+        .iter()
+        .take_while(|&&ch| ch != 0)
+        .map(|&ch| ch as u8)
+        .collect();
+    if bytes.is_ascii() {
+        return (String::from_utf8(bytes).unwrap(), Encoding::Ascii);
+    }
+    match String::from_utf8(bytes) {
+        Ok(s) => (s, Encoding::Utf8),
+        Err(e) => (
+            String::from_utf8_lossy(e.as_bytes()).into_owned(),
+            Encoding::Lossy,
+        ),
     }
-    String::from_utf8(bytes).map_err(|_| HostError::Utf8)
+}
+
+fn cstr_from_i8_fixed(buf: &[i8]) -> String {
+    decode_fixed_cstr(buf).0
+}
+
+/// A class whose info could not be read; keeps its factory index so index-based
+/// selection stays aligned with the factory.
+#[derive(Debug, Error)]
+#[error("class #{index}: {source}")]
+pub struct ClassReadError {
+    pub index: i32,
+    #[source]
+    pub source: HostError,
 }
 
 pub fn read_class_info_v1(
     module: &mut Module,
     index: i32,
 ) -> Result<(String, String, [u8; 16]), HostError> {
+    if !(0..count_classes(module)).contains(&index) {
+        return Err(HostError::ClassNotFound(ClassRef::Index(index)));
+    }
     let mut info = PClassInfo {
         cid: [0; 16],
         cardinality: 0,
@@ -149,8 +682,8 @@ pub fn read_class_info_v1(
     if tr != K_RESULT_OK {
         return Err(HostError::TErr(tr));
     }
-    let name = cstr_from_i8_fixed(&info.name)?;
-    let category = cstr_from_i8_fixed(&info.category)?;
+    let name = cstr_from_i8_fixed(&info.name);
+    let category = cstr_from_i8_fixed(&info.category);
     let mut cid = [0u8; 16];
     for (i, b) in info.cid.iter().enumerate() {
         cid[i] = *b as u8;
@@ -166,33 +699,85 @@ pub fn fmt_cid_hex(cid: &[u8; 16]) -> String {
     s
 }
 
+/// `id` in the registry form without braces, e.g. `E831FF31-F2D5-4301-928E-BBEE25697802`.
+pub fn fmt_uid(id: &[u8; 16]) -> String {
+    let hex = fmt_cid_hex(id);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// `IEditController (DCD7BBE3-7742-448D-A874-AACC979C759E)`, or just the IID.
+fn describe_iid(iid: &Tuid, name: &Option<&'static str>) -> String {
+    match name {
+        Some(name) => format!("{name} ({})", fmt_uid(&iid.0)),
+        None => fmt_uid(&iid.0),
+    }
+}
+
+/// `Gain (CID)`, or just the CID.
+fn describe_class(name: &Option<String>, cid: &[u8; 16]) -> String {
+    match name {
+        Some(name) => format!("{name} ({})", fmt_cid_hex(cid)),
+        None => fmt_cid_hex(cid),
+    }
+}
+
+/// The v1 class info name of `cid`, for error messages.
+unsafe fn class_name_in(factory: &mut IPluginFactory, cid: &[u8; 16]) -> Option<String> {
+    (0..factory.count_classes()).find_map(|index| {
+        let mut info = PClassInfo {
+            cid: [0; 16],
+            cardinality: 0,
+            category: [0; classinfo_consts::K_CATEGORY_SIZE],
+            name: [0; classinfo_consts::K_NAME_SIZE],
+        };
+        let found = factory.get_class_info(index, &mut info) == K_RESULT_OK
+            && info.cid.iter().zip(cid).all(|(&a, &b)| a as u8 == b);
+        found.then(|| cstr_from_i8_fixed(&info.name))
+    })
+}
+
+/// (index, name, category, cid) as returned by [`list_classes`].
+pub type ClassListEntry = (i32, String, String, [u8; 16]);
+
+/// One entry per factory index; unreadable classes are reported, not skipped.
 pub fn list_classes(
     module: &mut Module,
-) -> Result<Vec<(i32, String, String, [u8; 16])>, HostError> {
+) -> Result<Vec<Result<ClassListEntry, ClassReadError>>, HostError> {
     let n = count_classes(module);
-    let mut out = Vec::new();
-    for i in 0..n {
-        if let Ok((name, cat, cid)) = read_class_info_v1(module, i) {
-            out.push((i, name, cat, cid));
-        }
+    let mut out = Vec::with_capacity(n.max(0) as usize);
+    for index in 0..n {
+        out.push(
+            read_class_info_v1(module, index)
+                .map(|(name, cat, cid)| (index, name, cat, cid))
+                .map_err(|source| ClassReadError { index, source }),
+        );
     }
     Ok(out)
 }
 
 // ===== Phase 4/5 helpers: IID parsing, create/QI, process 32f/64f ============
 pub fn parse_hex_16(s: &str) -> Result<[u8; 16], HostError> {
-    let t = s
-        .trim()
-        .replace(|c: char| c == '-' || c == '{' || c == '}' || c == ' ', "");
+    let t = s.trim().replace(['-', '{', '}', ' '], "");
     if t.len() != 32 {
-        return Err(HostError::InvalidBundle(
-            "IID hex must be 16 bytes (32 hex chars)".into(),
-        ));
+        return Err(HostError::InvalidIid(format!(
+            "`{s}` must be 16 bytes (32 hex chars)"
+        )));
+    }
+    // Checked up front: slicing multi-byte characters would panic, and
+    // `from_str_radix` alone accepts a leading `+`.
+    if !t.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(HostError::InvalidIid(format!("`{s}` is not hex")));
     }
     let mut out = [0u8; 16];
-    for i in 0..16 {
-        out[i] = u8::from_str_radix(&t[2 * i..2 * i + 2], 16)
-            .map_err(|_| HostError::InvalidBundle("bad hex".into()))?;
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&t[2 * i..2 * i + 2], 16).expect("checked hex digits");
     }
     Ok(out)
 }
@@ -205,7 +790,13 @@ pub unsafe fn create_instance_raw(
     let mut obj: *mut core::ffi::c_void = core::ptr::null_mut();
     let tr = factory.create_instance_raw(&Tuid(cid), &Tuid(iid), &mut obj);
     if tr != K_RESULT_OK || obj.is_null() {
-        return Err(HostError::TErr(tr));
+        return Err(HostError::CreateInstance {
+            cid,
+            class_name: class_name_in(factory, &cid),
+            iid: Tuid(iid),
+            name: iids::name_of(&Tuid(iid)),
+            result: tr,
+        });
     }
     Ok(obj)
 }
@@ -218,16 +809,40 @@ pub unsafe fn query_interface(
     let mut out: *mut core::ffi::c_void = core::ptr::null_mut();
     let tr = fu.query_interface(&Tuid(iid), &mut out);
     if tr != K_RESULT_OK || out.is_null() {
-        return Err(HostError::NoInterface);
+        return Err(HostError::no_interface(Tuid(iid)));
     }
     Ok(out)
 }
 
-pub unsafe fn detect_output_channels(comp_ptr: *mut IComponent) -> i32 {
+/// What [`detect_output_channels`] found on a component's first audio output bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DetectedChannels {
+    /// Audio output buses the component reports.
+    pub bus_count: i32,
+    /// Channels of output bus 0; `None` without a bus or when `getBusInfo` fails.
+    pub reported: Option<i32>,
+    /// The reported count when it is within the default [`HostLimits`], else stereo.
+    pub suggested: i32,
+}
+
+impl DetectedChannels {
+    /// `suggested` is a stand-in, not what the plugin reported.
+    pub fn is_fallback(&self) -> bool {
+        self.reported != Some(self.suggested)
+    }
+}
+
+/// Read the channel count of the component's first audio output bus.
+pub unsafe fn detect_output_channels(comp_ptr: *mut IComponent) -> DetectedChannels {
     let comp = &mut *comp_ptr;
-    let count = comp.get_bus_count(0, BUS_DIR_OUTPUT);
-    if count <= 0 {
-        return 2;
+    let bus_count = comp.get_bus_count(MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT);
+    let mut detected = DetectedChannels {
+        bus_count,
+        reported: None,
+        suggested: 2,
+    };
+    if bus_count <= 0 {
+        return detected;
     }
     let mut info = BusInfo {
         media_type: 0,
@@ -237,15 +852,26 @@ pub unsafe fn detect_output_channels(comp_ptr: *mut IComponent) -> i32 {
         bus_type: 0,
         flags: 0,
     };
-    let _ = comp.get_bus_info(0, BUS_DIR_OUTPUT, 0, &mut info as *mut _);
-    if info.channel_count > 0 {
-        info.channel_count
-    } else {
-        2
+    if comp.get_bus_info(MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT, 0, &mut info as *mut _) != K_RESULT_OK {
+        return detected;
+    }
+    detected.reported = Some(info.channel_count);
+    let bus = BusId {
+        media_type: MEDIA_TYPE_AUDIO,
+        direction: BUS_DIR_OUTPUT,
+        index: 0,
+    };
+    if HostLimits::default()
+        .check_channels(bus, info.channel_count)
+        .is_ok()
+    {
+        detected.suggested = info.channel_count;
     }
+    detected
 }
 
-/// Call setBusArrangements with caller-provided arrangement IDs.
+/// Call setBusArrangements with caller-provided arrangement IDs. A plugin that does
+/// not implement it keeps its default layout; that is not an error.
 pub unsafe fn set_bus_arrangements(
     proc_ptr: *mut IAudioProcessor,
     in_arrs: &[u64],
@@ -258,39 +884,119 @@ pub unsafe fn set_bus_arrangements(
         out_arrs.as_ptr(),
         out_arrs.len() as i32,
     );
+    if tr == K_NOT_IMPLEMENTED {
+        return plugin::optional("IAudioProcessor", "setBusArrangements", tr).map(drop);
+    }
     if tr != K_RESULT_OK {
-        return Err(HostError::TErr(tr));
+        return Err(HostError::ArrangementRejected {
+            inputs: in_arrs.to_vec(),
+            outputs: out_arrs.to_vec(),
+        });
     }
     Ok(())
 }
 
-/// Drive one 32f process block on an IAudioProcessor* (param/events null)
-pub unsafe fn drive_null_process_32f(
-    proc_ptr: *mut IAudioProcessor,
-    sr: f64,
-    nframes: i32,
-    outs: i32,
-) -> Result<(), HostError> {
-    let proc = &mut *proc_ptr;
+/// One block driven by [`drive_process_capture_32f`] or [`drive_process_capture_64f`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapturedBlock<T> {
+    /// What `process` returned, success or not.
+    pub tresult: openvst3_abi::tresult,
+    /// The output bus, one `nframes`-long buffer per channel.
+    pub outputs: Vec<Vec<T>>,
+    /// The `silenceFlags` the plugin left on the output bus.
+    pub silence_flags: u64,
+}
+
+impl<T> CapturedBlock<T> {
+    /// The block as [`ProcessStats`]; a `tresult` other than `kResultOk` or
+    /// `kResultFalse` becomes [`HostError::TErr`].
+    pub fn stats(&self) -> Result<ProcessStats, HostError> {
+        // No output parameter or event lists to read.
+        unsafe {
+            ProcessStats::collect(
+                self.tresult,
+                None,
+                core::iter::once(self.silence_flags),
+                core::ptr::null_mut(),
+                core::ptr::null_mut(),
+            )
+        }
+    }
+}
 
+/// `nframes`-long copies of `input`'s channels, cut or padded with silence.
+fn input_buffers<T: Copy + Default>(input: Option<&[Vec<T>]>, nframes: i32) -> Vec<Vec<T>> {
+    let len = nframes.max(0) as usize;
+    input
+        .unwrap_or_default()
+        .iter()
+        .map(|chan| {
+            let mut buf = vec![T::default(); len];
+            let n = chan.len().min(len);
+            buf[..n].copy_from_slice(&chan[..n]);
+            buf
+        })
+        .collect()
+}
+
+/// Initialize the processor, set it up with `setup`, call `run` between
+/// `setProcessing(true)` and `setProcessing(false)`, and terminate it again.
+/// Returns what `run` returned.
+unsafe fn drive_lifecycle(
+    proc: &mut IAudioProcessor,
+    setup: &ProcessSetup,
+    run: impl FnOnce(&mut IAudioProcessor) -> openvst3_abi::tresult,
+) -> Result<openvst3_abi::tresult, HostError> {
     let tr = proc.initialize(core::ptr::null_mut::<FUnknown>());
     if tr != K_RESULT_OK {
         return Err(HostError::TErr(tr));
     }
 
-    let setup = ProcessSetup {
-        process_mode: process_consts::PROCESS_MODE_REALTIME,
-        sample_rate: sr,
-        max_samples_per_block: nframes,
-        symbolic_sample_size: process_consts::SYMBOLIC_SAMPLE_32,
-        flags: 0,
-    };
-    let tr = proc.setup_processing(&setup);
+    let tr = proc.setup_processing(setup);
     if tr != K_RESULT_OK {
+        let _ = proc.terminate();
         return Err(HostError::TErr(tr));
     }
 
-    let mut chans: Vec<Vec<f32>> = (0..outs).map(|_| vec![0.0f32; nframes as usize]).collect();
+    let tr = proc.set_processing(1);
+    if let Err(e) = plugin::optional("IAudioProcessor", "setProcessing", tr) {
+        let _ = proc.terminate();
+        return Err(e);
+    }
+
+    let tr = run(proc);
+    let _ = proc.set_processing(0);
+    let _ = proc.terminate();
+    Ok(tr)
+}
+
+/// Drive one 32f process block on an IAudioProcessor* (param/events null) and keep
+/// what it wrote to `outs` output channels. With `input`, its channels are the input
+/// bus, each cut or padded with silence to `nframes`. Only a failing lifecycle call is
+/// an error; `process`'s own result is in [`CapturedBlock::tresult`].
+pub unsafe fn drive_process_capture_32f(
+    proc_ptr: *mut IAudioProcessor,
+    sr: f64,
+    nframes: i32,
+    outs: i32,
+    input: Option<&[Vec<f32>]>,
+) -> Result<CapturedBlock<f32>, HostError> {
+    let setup = ProcessSetupBuilder::new()
+        .sample_rate(sr)
+        .max_block(nframes)
+        .precision(SymbolicSampleSize::Sample32)
+        .build()?;
+
+    let mut ins = input_buffers(input, nframes);
+    let mut in_ptrs: Vec<*mut f32> = ins.iter_mut().map(|c| c.as_mut_ptr()).collect();
+    let mut ins_bus = AudioBusBuffers32 {
+        num_channels: ins.len() as i32,
+        silence_flags: 0,
+        channel_buffers: in_ptrs.as_mut_ptr(),
+    };
+    let mut chans: Vec<Vec<f32>> = (0..outs)
+        .map(|_| vec![0.0f32; nframes.max(0) as usize])
+        .collect();
     let mut chan_ptrs: Vec<*mut f32> = chans.iter_mut().map(|c| c.as_mut_ptr()).collect();
     let mut outs_bus = AudioBusBuffers32 {
         num_channels: outs,
@@ -299,59 +1005,54 @@ pub unsafe fn drive_null_process_32f(
     };
 
     let mut data = ProcessData32 {
-        num_inputs: 0,
+        num_inputs: input.is_some() as i32,
         num_outputs: 1,
-        inputs: core::ptr::null_mut(),
+        inputs: if input.is_some() {
+            &mut ins_bus
+        } else {
+            core::ptr::null_mut()
+        },
         outputs: &mut outs_bus,
         num_samples: nframes,
         input_parameter_changes: core::ptr::null_mut(),
         output_parameter_changes: core::ptr::null_mut(),
         input_events: core::ptr::null_mut(),
         output_events: core::ptr::null_mut(),
+        process_context: core::ptr::null_mut(),
     };
 
-    let tr = proc.set_processing(1);
-    if tr != K_RESULT_OK {
-        return Err(HostError::TErr(tr));
-    }
-
-    let tr = proc.process_32f(&mut data);
-    let _ = proc.set_processing(0);
-    let _ = proc.terminate();
-
-    if tr != K_RESULT_OK {
-        return Err(HostError::TErr(tr));
-    }
-    Ok(())
+    let tr = drive_lifecycle(&mut *proc_ptr, &setup, |proc| proc.process_32f(&mut data))?;
+    Ok(CapturedBlock {
+        tresult: tr,
+        outputs: chans,
+        silence_flags: outs_bus.silence_flags,
+    })
 }
 
-/// Drive one 64f process block on an IAudioProcessor* (param/events null)
-pub unsafe fn drive_null_process_64f(
+/// The 64f twin of [`drive_process_capture_32f`].
+pub unsafe fn drive_process_capture_64f(
     proc_ptr: *mut IAudioProcessor,
     sr: f64,
     nframes: i32,
     outs: i32,
-) -> Result<(), HostError> {
-    let proc = &mut *proc_ptr;
-
-    let tr = proc.initialize(core::ptr::null_mut::<FUnknown>());
-    if tr != K_RESULT_OK {
-        return Err(HostError::TErr(tr));
-    }
+    input: Option<&[Vec<f64>]>,
+) -> Result<CapturedBlock<f64>, HostError> {
+    let setup = ProcessSetupBuilder::new()
+        .sample_rate(sr)
+        .max_block(nframes)
+        .precision(SymbolicSampleSize::Sample64)
+        .build()?;
 
-    let setup = ProcessSetup {
-        process_mode: process_consts::PROCESS_MODE_REALTIME,
-        sample_rate: sr,
-        max_samples_per_block: nframes,
-        symbolic_sample_size: process_consts::SYMBOLIC_SAMPLE_64,
-        flags: 0,
+    let mut ins = input_buffers(input, nframes);
+    let mut in_ptrs: Vec<*mut f64> = ins.iter_mut().map(|c| c.as_mut_ptr()).collect();
+    let mut ins_bus = AudioBusBuffers64 {
+        num_channels: ins.len() as i32,
+        silence_flags: 0,
+        channel_buffers: in_ptrs.as_mut_ptr(),
     };
-    let tr = proc.setup_processing(&setup);
-    if tr != K_RESULT_OK {
-        return Err(HostError::TErr(tr));
-    }
-
-    let mut chans: Vec<Vec<f64>> = (0..outs).map(|_| vec![0.0f64; nframes as usize]).collect();
+    let mut chans: Vec<Vec<f64>> = (0..outs)
+        .map(|_| vec![0.0f64; nframes.max(0) as usize])
+        .collect();
     let mut chan_ptrs: Vec<*mut f64> = chans.iter_mut().map(|c| c.as_mut_ptr()).collect();
     let mut outs_bus = AudioBusBuffers64 {
         num_channels: outs,
@@ -360,28 +1061,50 @@ pub unsafe fn drive_null_process_64f(
     };
 
     let mut data = ProcessData64 {
-        num_inputs: 0,
+        num_inputs: input.is_some() as i32,
         num_outputs: 1,
-        inputs: core::ptr::null_mut(),
+        inputs: if input.is_some() {
+            &mut ins_bus
+        } else {
+            core::ptr::null_mut()
+        },
         outputs: &mut outs_bus,
         num_samples: nframes,
         input_parameter_changes: core::ptr::null_mut(),
         output_parameter_changes: core::ptr::null_mut(),
         input_events: core::ptr::null_mut(),
         output_events: core::ptr::null_mut(),
+        process_context: core::ptr::null_mut(),
     };
 
-    let tr = proc.set_processing(1);
-    if tr != K_RESULT_OK {
-        return Err(HostError::TErr(tr));
-    }
+    let tr = drive_lifecycle(&mut *proc_ptr, &setup, |proc| proc.process_64f(&mut data))?;
+    Ok(CapturedBlock {
+        tresult: tr,
+        outputs: chans,
+        silence_flags: outs_bus.silence_flags,
+    })
+}
 
-    let tr = proc.process_64f(&mut data);
-    let _ = proc.set_processing(0);
-    let _ = proc.terminate();
+/// Drive one 32f process block on an IAudioProcessor* (param/events null).
+/// `kResultFalse` from `process` is not an error; see [`ProcessStats::nothing_to_do`].
+#[deprecated(note = "use drive_process_capture_32f, which keeps the output")]
+pub unsafe fn drive_null_process_32f(
+    proc_ptr: *mut IAudioProcessor,
+    sr: f64,
+    nframes: i32,
+    outs: i32,
+) -> Result<ProcessStats, HostError> {
+    drive_process_capture_32f(proc_ptr, sr, nframes, outs, None)?.stats()
+}
 
-    if tr != K_RESULT_OK {
-        return Err(HostError::TErr(tr));
-    }
-    Ok(())
+/// Drive one 64f process block on an IAudioProcessor* (param/events null).
+/// `kResultFalse` from `process` is not an error; see [`ProcessStats::nothing_to_do`].
+#[deprecated(note = "use drive_process_capture_64f, which keeps the output")]
+pub unsafe fn drive_null_process_64f(
+    proc_ptr: *mut IAudioProcessor,
+    sr: f64,
+    nframes: i32,
+    outs: i32,
+) -> Result<ProcessStats, HostError> {
+    drive_process_capture_64f(proc_ptr, sr, nframes, outs, None)?.stats()
 }
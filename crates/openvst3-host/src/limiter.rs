@@ -0,0 +1,217 @@
+//! Output safety stage for listening to plugins under development.
+//!
+//! [`SafetyLimiter`] is not a mastering limiter: gain drops instantly to keep every
+//! frame under the ceiling, recovers with a one-pole release, and a final clamp (which
+//! also silences NaN and infinity) catches anything left. It also watches for a
+//! sustained DC offset, which is inaudible but still bad for speakers. Frames in a
+//! row that get the same gain, which is every frame while nothing is limited, are
+//! scaled together through [`dsp::apply_gain`]. Nothing allocates after
+//! [`SafetyLimiter::new`].
+
+use crate::dsp;
+
+/// A sample type the limiter can process.
+pub trait Sample: Copy {
+    fn to_f64(self) -> f64;
+    fn from_f64(v: f64) -> Self;
+
+    /// `buf *= gain`.
+    #[doc(hidden)]
+    fn scale(buf: &mut [Self], gain: f64) {
+        for s in buf {
+            *s = Self::from_f64(s.to_f64() * gain);
+        }
+    }
+
+    /// `dst += src * gain`.
+    #[doc(hidden)]
+    fn mix_add(src: &[Self], dst: &mut [Self], gain: f64) {
+        for (d, s) in dst.iter_mut().zip(src) {
+            *d = Self::from_f64(d.to_f64() + s.to_f64() * gain);
+        }
+    }
+}
+
+impl Sample for f32 {
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn from_f64(v: f64) -> Self {
+        v as f32
+    }
+    fn scale(buf: &mut [Self], gain: f64) {
+        dsp::apply_gain(buf, gain as f32);
+    }
+    fn mix_add(src: &[Self], dst: &mut [Self], gain: f64) {
+        dsp::mix_add(src, dst, gain as f32);
+    }
+}
+
+impl Sample for f64 {
+    fn to_f64(self) -> f64 {
+        self
+    }
+    fn from_f64(v: f64) -> Self {
+        v
+    }
+}
+
+/// Release time constant.
+const RELEASE_SECONDS: f64 = 0.05;
+/// Time constant of the DC estimate, and how long it must stay high.
+const DC_SECONDS: f64 = 1.0;
+/// DC level (-20 dBFS) above which a channel counts as offset.
+const DC_THRESHOLD: f64 = 0.1;
+
+pub struct SafetyLimiter {
+    ceiling: f64,
+    gain: f64,
+    release: f64,
+    dc_coeff: f64,
+    dc_hold: u64,
+    /// Per channel: running mean and how many frames it has been over the threshold.
+    dc: Box<[(f64, u64)]>,
+}
+
+impl SafetyLimiter {
+    /// A limiter for `channels` interleaved channels at `sample_rate`, holding peaks
+    /// at or below `ceiling_dbfs`.
+    pub fn new(ceiling_dbfs: f64, channels: usize, sample_rate: f64) -> Self {
+        Self {
+            ceiling: 10f64.powf(ceiling_dbfs / 20.0),
+            gain: 1.0,
+            release: 1.0 - (-1.0 / (RELEASE_SECONDS * sample_rate)).exp(),
+            dc_coeff: 1.0 - (-1.0 / (DC_SECONDS * sample_rate)).exp(),
+            dc_hold: (DC_SECONDS * sample_rate) as u64,
+            dc: vec![(0.0, 0); channels.max(1)].into_boxed_slice(),
+        }
+    }
+
+    /// Linear ceiling.
+    pub fn ceiling(&self) -> f64 {
+        self.ceiling
+    }
+
+    /// Current gain; below 1.0 while limiting or releasing.
+    pub fn gain(&self) -> f64 {
+        self.gain
+    }
+
+    /// Limit an interleaved block in place.
+    pub fn process<T: Sample>(&mut self, data: &mut [T]) {
+        let channels = self.dc.len();
+        // Where the frames not yet scaled start, and their gain.
+        let mut run: Option<(usize, f64)> = None;
+        for start in (0..data.len()).step_by(channels) {
+            let frame = &data[start..(start + channels).min(data.len())];
+            let mut peak = 0.0f64;
+            for (sample, dc) in frame.iter().zip(self.dc.iter_mut()) {
+                let x = sample.to_f64();
+                if x.is_finite() {
+                    peak = peak.max(x.abs());
+                    dc.0 += (x - dc.0) * self.dc_coeff;
+                }
+                dc.1 = if dc.0.abs() > DC_THRESHOLD {
+                    dc.1.saturating_add(1)
+                } else {
+                    0
+                };
+            }
+            let target = if peak * self.gain > self.ceiling {
+                self.ceiling / peak
+            } else {
+                1.0
+            };
+            if target < self.gain {
+                self.gain = target;
+            } else {
+                self.gain += (target - self.gain) * self.release;
+            }
+            match run {
+                Some((_, gain)) if gain == self.gain => {}
+                _ => {
+                    if let Some((from, gain)) = run {
+                        self.apply(&mut data[from..start], gain);
+                    }
+                    run = Some((start, self.gain));
+                }
+            }
+        }
+        if let Some((from, gain)) = run {
+            self.apply(&mut data[from..], gain);
+        }
+    }
+
+    /// Scale `frames` by `gain`, then clamp them to the ceiling and silence NaN and
+    /// infinity.
+    fn apply<T: Sample>(&self, frames: &mut [T], gain: f64) {
+        if gain != 1.0 {
+            T::scale(frames, gain);
+        }
+        for sample in frames {
+            let x = sample.to_f64();
+            let y = if x.is_finite() {
+                x.clamp(-self.ceiling, self.ceiling)
+            } else {
+                0.0
+            };
+            *sample = T::from_f64(y);
+        }
+    }
+
+    /// The first channel whose DC offset has stayed above -20 dBFS for a second.
+    pub fn dc_offset(&self) -> Option<usize> {
+        self.dc.iter().position(|&(_, held)| held >= self.dc_hold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(
+        miri,
+        ignore = "too slow under Miri; the dsp kernels it calls have their own test"
+    )]
+    fn safety_limiter_holds_the_ceiling_and_releases_smoothly() {
+        let sr = 48_000.0;
+        let mut limiter = SafetyLimiter::new(-6.0, 2, sr);
+        let ceiling = limiter.ceiling();
+        let sine = |amp: f64, n: usize| -> f32 {
+            (amp * (2.0 * std::f64::consts::PI * 440.0 * n as f64 / sr).sin()) as f32
+        };
+
+        // +20 dBFS for half a second.
+        let mut loud: Vec<f32> = (0..24_000).flat_map(|n| [sine(10.0, n); 2]).collect();
+        limiter.process(&mut loud);
+        assert!(loud.iter().all(|s| (s.abs() as f64) <= ceiling + 1e-6));
+        assert!(limiter.gain() < 0.1);
+
+        // Back to -12 dBFS: the gain climbs back without jumps.
+        let quiet: Vec<f32> = (24_000..72_000).flat_map(|n| [sine(0.25, n); 2]).collect();
+        let mut out = quiet.clone();
+        limiter.process(&mut out);
+        let gains: Vec<f64> = quiet
+            .iter()
+            .zip(&out)
+            .step_by(2)
+            .filter(|(i, _)| i.abs() > 0.05)
+            .map(|(i, o)| (*o / *i) as f64)
+            .collect();
+        assert!(gains.windows(2).all(|w| (w[1] - w[0]).abs() < 0.01));
+        assert!((gains.last().unwrap() - 1.0).abs() < 1e-3);
+        assert_eq!(limiter.dc_offset(), None);
+
+        let mut broken = [f64::NAN, f64::INFINITY, 0.5, 0.5];
+        SafetyLimiter::new(0.0, 2, sr).process(&mut broken);
+        assert_eq!(&broken[..2], &[0.0, 0.0]);
+
+        let mut dc = vec![0.3f64; 2 * 60_000];
+        let mut limiter = SafetyLimiter::new(0.0, 2, sr);
+        limiter.process(&mut dc);
+        assert_eq!(limiter.dc_offset(), None);
+        limiter.process(&mut dc);
+        assert_eq!(limiter.dc_offset(), Some(0));
+    }
+}
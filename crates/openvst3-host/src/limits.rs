@@ -0,0 +1,122 @@
+//! Bounds on sizes the host takes from a plugin before allocating for them.
+//!
+//! A plugin reporting 10 000 channels on a bus would have the host allocate a
+//! buffer per channel; one reporting 0 leaves it with nothing to process. Bus,
+//! channel and parameter counts are checked against [`HostLimits`] when a
+//! [`PluginInstance`](crate::PluginInstance) is created (see
+//! [`PluginBuilder::limits`](crate::PluginBuilder::limits)), state streams refuse to
+//! grow past [`max_state_bytes`](HostLimits::max_state_bytes), and preset files and
+//! offline renders are held to the same numbers. Scanners meeting untrusted binaries
+//! keep the defaults; [`HostLimits::unlimited`] turns every check off.
+
+use std::fmt;
+
+use crate::buses::BusId;
+use crate::HostError;
+
+/// See the module docs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HostLimits {
+    /// Buses of one media type and direction.
+    pub max_buses: usize,
+    /// Audio channels one bus may have; every audio bus needs at least one.
+    pub max_channels_per_bus: i32,
+    pub max_parameters: usize,
+    /// Size of one component or controller state, and of one preset chunk.
+    pub max_state_bytes: usize,
+    /// Events and parameter points the host queues for one block.
+    pub max_events_per_block: usize,
+}
+
+impl Default for HostLimits {
+    fn default() -> Self {
+        Self {
+            max_buses: 64,
+            max_channels_per_bus: 64,
+            max_parameters: 65_536,
+            max_state_bytes: 256 << 20,
+            max_events_per_block: 4096,
+        }
+    }
+}
+
+/// One of the [`HostLimits`], named in [`HostError::LimitExceeded`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Limit {
+    Buses,
+    Parameters,
+    StateBytes,
+    EventsPerBlock,
+}
+
+/// Where a value over the [`HostLimits`] came from, in [`HostError::LimitExceeded`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LimitOrigin {
+    /// Reported or written by the plugin: bus and parameter counts, `getState`.
+    Plugin,
+    /// Handed to the host from outside the plugin: preset files, caller-supplied
+    /// state and render events.
+    External,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Limit::Buses => "max_buses",
+            Limit::Parameters => "max_parameters",
+            Limit::StateBytes => "max_state_bytes",
+            Limit::EventsPerBlock => "max_events_per_block",
+        })
+    }
+}
+
+impl HostLimits {
+    /// No limits at all, for trusted plugins that really are this big.
+    pub fn unlimited() -> Self {
+        Self {
+            max_buses: usize::MAX,
+            max_channels_per_bus: i32::MAX,
+            max_parameters: usize::MAX,
+            max_state_bytes: usize::MAX,
+            max_events_per_block: usize::MAX,
+        }
+    }
+
+    pub fn max(&self, limit: Limit) -> usize {
+        match limit {
+            Limit::Buses => self.max_buses,
+            Limit::Parameters => self.max_parameters,
+            Limit::StateBytes => self.max_state_bytes,
+            Limit::EventsPerBlock => self.max_events_per_block,
+        }
+    }
+
+    /// `value`, from `origin`, is within `limit`.
+    pub fn check(&self, limit: Limit, value: usize, origin: LimitOrigin) -> Result<(), HostError> {
+        let max = self.max(limit);
+        if value <= max {
+            Ok(())
+        } else {
+            Err(HostError::LimitExceeded {
+                limit,
+                value,
+                max,
+                origin,
+            })
+        }
+    }
+
+    /// `reported` audio channels on `bus` are within `1..=max_channels_per_bus`.
+    pub fn check_channels(&self, bus: BusId, reported: i32) -> Result<(), HostError> {
+        if (1..=self.max_channels_per_bus).contains(&reported) {
+            Ok(())
+        } else {
+            Err(HostError::SuspiciousBusInfo { bus, reported })
+        }
+    }
+
+    /// `reported` moved into `1..=max_channels_per_bus`.
+    pub fn clamp_channels(&self, reported: i32) -> i32 {
+        reported.clamp(1, self.max_channels_per_bus.max(1))
+    }
+}
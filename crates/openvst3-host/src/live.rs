@@ -0,0 +1,89 @@
+//! The processor pointer an audio callback calls, replaceable from another thread.
+//!
+//! The callback brackets each block with [`LiveProcessor::enter`]; a control thread
+//! swaps the pointer with [`LiveProcessor::replace`], which mutes the callback and
+//! waits for a running block before storing the new one. Muted blocks get `None` and
+//! should play silence. Neither side allocates or locks.
+
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+use openvst3_abi::IAudioProcessor;
+
+/// See the module docs. [`PluginInstance::live`](crate::PluginInstance::live) hands
+/// out the one [`PluginInstance::reload`](crate::PluginInstance::reload) updates.
+#[derive(Debug)]
+pub struct LiveProcessor {
+    ptr: AtomicPtr<IAudioProcessor>,
+    muted: AtomicBool,
+    /// Set by the callback between `enter` and the guard's drop.
+    busy: AtomicBool,
+}
+
+/// The processor for one block; the pointer is not replaced while this lives.
+pub struct LiveGuard<'a> {
+    live: &'a LiveProcessor,
+    ptr: *mut IAudioProcessor,
+}
+
+impl LiveGuard<'_> {
+    pub fn as_ptr(&self) -> *mut IAudioProcessor {
+        self.ptr
+    }
+}
+
+impl Drop for LiveGuard<'_> {
+    fn drop(&mut self) {
+        self.live.busy.store(false, Ordering::SeqCst);
+    }
+}
+
+impl LiveProcessor {
+    pub fn new(ptr: *mut IAudioProcessor) -> Self {
+        Self {
+            ptr: AtomicPtr::new(ptr),
+            muted: AtomicBool::new(false),
+            busy: AtomicBool::new(false),
+        }
+    }
+
+    /// Audio thread: the processor to call for this block, or `None` while muted.
+    /// Only one thread may enter at a time.
+    pub fn enter(&self) -> Option<LiveGuard<'_>> {
+        self.busy.store(true, Ordering::SeqCst);
+        if self.muted.load(Ordering::SeqCst) {
+            self.busy.store(false, Ordering::SeqCst);
+            return None;
+        }
+        Some(LiveGuard {
+            live: self,
+            ptr: self.ptr.load(Ordering::SeqCst),
+        })
+    }
+
+    /// The current processor, for calls that do not race the callback.
+    pub fn get(&self) -> *mut IAudioProcessor {
+        self.ptr.load(Ordering::SeqCst)
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted.load(Ordering::SeqCst)
+    }
+
+    /// Control thread: mute the callback, wait for a running block to finish, run
+    /// `f` and unmute. Must not be called from the callback itself.
+    pub fn while_muted<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.muted.store(true, Ordering::SeqCst);
+        while self.busy.load(Ordering::SeqCst) {
+            std::hint::spin_loop();
+        }
+        let result = f();
+        self.muted.store(false, Ordering::SeqCst);
+        result
+    }
+
+    /// Point the callback at `ptr` once no block is running; returns the old pointer,
+    /// which the callback no longer reaches.
+    pub fn replace(&self, ptr: *mut IAudioProcessor) -> *mut IAudioProcessor {
+        self.while_muted(|| self.ptr.swap(ptr, Ordering::SeqCst))
+    }
+}
@@ -0,0 +1,465 @@
+//! Why a module binary did not load.
+//!
+//! [`Module::load`](crate::Module::load) reports a failing `dlopen`/`LoadLibraryExW`
+//! as a [`DlopenError`]. It keeps the loader's own message verbatim, the
+//! `GetLastError` code on Windows, and the path that was tried. "cannot open shared object
+//! file" usually means a dependency of the plugin is missing rather than the plugin
+//! itself. With the `diagnose` feature on Linux, the binary's `DT_NEEDED` entries are
+//! looked up the way the dynamic loader would look for them, and the ones it cannot
+//! find are listed in [`missing_deps`](DlopenError::missing_deps).
+//!
+//! A binary that loads but has no `GetPluginFactory` is a [`MissingFactory`]. Some
+//! export it decorated (`_GetPluginFactory@0` from 32-bit Windows compilers) or
+//! only under a non-default ELF symbol version; with `diagnose` the loader reads the
+//! binary's [`exports`] and tries every [`is_factory_alias`] among them too. What it
+//! tried and what the binary exports end up in the error, and a binary without
+//! a single [`VST3_ENTRY_POINTS`] export is reported as not being a VST 3 module
+//! at all rather than as one missing its factory.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// See the module docs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DlopenError {
+    pub path: PathBuf,
+    /// `dlerror()` or `FormatMessage` text, verbatim.
+    pub os_message: String,
+    /// `GetLastError()` from `LoadLibraryExW`; `None` on unix, where `dlerror()` is
+    /// all `dlopen` reports.
+    pub code: Option<i32>,
+    /// Libraries the binary needs that the loader cannot find; `None` when they
+    /// were not looked for (no `diagnose` feature, not Linux, unreadable binary).
+    pub missing_deps: Option<Vec<String>>,
+}
+
+impl DlopenError {
+    #[cfg(feature = "dlopen")]
+    pub(crate) fn new(path: &Path, err: &libloading::Error) -> Self {
+        use std::error::Error as _;
+
+        // On Windows the message and code are in the source `io::Error`.
+        let source = err
+            .source()
+            .and_then(|s| s.downcast_ref::<std::io::Error>());
+        Self {
+            path: path.to_path_buf(),
+            os_message: source.map_or_else(|| err.to_string(), |s| s.to_string()),
+            code: source.and_then(std::io::Error::raw_os_error),
+            missing_deps: missing_dependencies(path),
+        }
+    }
+
+    /// Several lines for a person working out what went wrong.
+    pub fn diagnosis(&self) -> String {
+        let mut out = format!(
+            "  path: {}\n  loader: {}\n",
+            self.path.display(),
+            self.os_message
+        );
+        if let Some(code) = self.code {
+            out += &format!("  code: {code}\n");
+        }
+        match self.missing_deps.as_deref() {
+            None => {}
+            Some([]) => out += "  dependencies: all found\n",
+            Some(missing) => {
+                out += "  missing dependencies:\n";
+                for dep in missing {
+                    out += &format!("    {dep}\n");
+                }
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for DlopenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.os_message)?;
+        match self.missing_deps.as_deref() {
+            Some(missing) if !missing.is_empty() => write!(f, " (missing: {})", missing.join(", ")),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Names a VST 3 binary exports, besides decorations: the factory and the platform
+/// entry and exit points.
+pub const VST3_ENTRY_POINTS: [&str; 9] = [
+    "GetPluginFactory",
+    "ModuleEntry",
+    "ModuleExit",
+    "InitDll",
+    "ExitDll",
+    "bundleEntry",
+    "bundleExit",
+    "InitModule",
+    "DeinitModule",
+];
+
+/// What [`Module::load`](crate::Module::load) knows about a binary it loaded but
+/// found no `GetPluginFactory` in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MissingFactory {
+    pub path: PathBuf,
+    /// The names looked up, in order.
+    pub tried: Vec<String>,
+    /// What [`exports`] read from the binary; `None` when it was not read (no
+    /// `diagnose` feature, unreadable binary, unknown format).
+    pub exports: Option<Vec<String>>,
+}
+
+/// Exports listed in [`MissingFactory`]'s one-line form.
+const LISTED_EXPORTS: usize = 8;
+
+impl MissingFactory {
+    /// Whether any export is one of the [`VST3_ENTRY_POINTS`]; `None` without the
+    /// export list.
+    pub fn looks_like_vst3(&self) -> Option<bool> {
+        let exports = self.exports.as_ref()?;
+        Some(exports.iter().any(|e| {
+            let (base, _) = split_decoration(e);
+            VST3_ENTRY_POINTS.contains(&base)
+        }))
+    }
+
+    /// [`HostError::NotVst3`](crate::HostError::NotVst3) when the exports rule a VST 3
+    /// module out, [`NoFactorySymbol`](crate::HostError::NoFactorySymbol) otherwise.
+    #[cfg(feature = "dlopen")]
+    pub(crate) fn into_error(self) -> crate::HostError {
+        match self.looks_like_vst3() {
+            Some(false) => crate::HostError::NotVst3(self),
+            _ => crate::HostError::NoFactorySymbol(self),
+        }
+    }
+
+    /// Several lines for a person working out what went wrong.
+    pub fn diagnosis(&self) -> String {
+        let mut out = format!(
+            "  path: {}\n  tried: {}\n",
+            self.path.display(),
+            self.tried.join(", ")
+        );
+        match self.exports.as_deref() {
+            None => out += "  exports: not read\n",
+            Some([]) => out += "  exports: none\n",
+            Some(exports) => {
+                out += "  exports:\n";
+                for export in exports {
+                    out += &format!("    {export}\n");
+                }
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for MissingFactory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tried {}", self.tried.join(", "))?;
+        match self.exports.as_deref() {
+            None => Ok(()),
+            Some([]) => f.write_str("; the binary exports nothing"),
+            Some(exports) => {
+                let shown = exports.len().min(LISTED_EXPORTS);
+                write!(f, "; the binary exports {}", exports[..shown].join(", "))?;
+                if exports.len() > shown {
+                    write!(f, " and {} more", exports.len() - shown)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// `name` without leading underscores and the `@` suffix of a stdcall decoration or
+/// symbol version, and that suffix.
+fn split_decoration(name: &str) -> (&str, Option<&str>) {
+    let name = name.trim_start_matches('_');
+    match name.split_once('@') {
+        Some((base, suffix)) => (base, Some(suffix.trim_start_matches('@'))),
+        None => (name, None),
+    }
+}
+
+/// Whether `export` is `GetPluginFactory` with a decoration: leading underscores, an
+/// `@N` stdcall suffix, or an ELF symbol version (`GetPluginFactory@VERSION`).
+pub fn is_factory_alias(export: &str) -> bool {
+    export != "GetPluginFactory" && split_decoration(export).0 == "GetPluginFactory"
+}
+
+/// The ELF symbol version of `export` if it has a non-numeric one, which `dlsym`
+/// does not find when it is not the default.
+pub fn symbol_version(export: &str) -> Option<&str> {
+    split_decoration(export)
+        .1
+        .filter(|v| !v.is_empty() && !v.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// The names the binary at `path` exports. ELF: defined global dynamic symbols, those
+/// only under a non-default version as `name@VERSION`. PE: export names, and
+/// `#ordinal` for exports without one. Mach-O: exported symbols without the leading
+/// underscore the C compiler adds. Sorted. `None` when the binary cannot be read or
+/// parsed, or is a universal Mach-O.
+#[cfg(feature = "diagnose")]
+pub fn exports(path: &Path) -> Option<Vec<String>> {
+    use goblin::elf::sym::{STB_GLOBAL, STB_WEAK};
+
+    let bytes = std::fs::read(path).ok()?;
+    let mut names: Vec<String> = match bytes.get(..4)? {
+        b"\x7fELF" => {
+            let elf = goblin::elf::Elf::parse(&bytes).ok()?;
+            // Version index -> name, for the versions the binary defines.
+            let versions: Vec<(u16, &str)> = elf
+                .verdef
+                .iter()
+                .flat_map(|verdef| verdef.iter())
+                .filter_map(|def| {
+                    let aux = def.iter().next()?;
+                    Some((def.vd_ndx, elf.dynstrtab.get_at(aux.vda_name)?))
+                })
+                .collect();
+            elf.dynsyms
+                .iter()
+                .enumerate()
+                .filter(|(_, sym)| {
+                    sym.st_shndx != 0 && matches!(sym.st_bind(), STB_GLOBAL | STB_WEAK)
+                })
+                .filter_map(|(i, sym)| {
+                    let name = elf.dynstrtab.get_at(sym.st_name)?;
+                    let hidden = elf
+                        .versym
+                        .as_ref()
+                        .and_then(|versym| versym.get_at(i))
+                        .filter(|v| v.is_hidden())
+                        .and_then(|v| versions.iter().find(|(n, _)| *n == v.version()));
+                    Some(match hidden {
+                        Some((_, version)) => format!("{name}@{version}"),
+                        None => name.to_string(),
+                    })
+                })
+                .filter(|name| !name.is_empty())
+                .collect()
+        }
+        [b'M', b'Z', ..] => {
+            use goblin::pe::export::ExportAddressTableEntry;
+
+            let pe = goblin::pe::PE::parse(&bytes).ok()?;
+            let mut names: Vec<String> = pe
+                .exports
+                .iter()
+                .filter_map(|export| export.name.map(str::to_string))
+                .collect();
+            // Address table entries no name points at are exported by ordinal only.
+            if let Some(data) = &pe.export_data {
+                let base = data.export_directory_table.ordinal_base as usize;
+                names.extend(
+                    data.export_address_table
+                        .iter()
+                        .enumerate()
+                        .filter(|&(i, entry)| {
+                            !matches!(entry, ExportAddressTableEntry::ExportRVA(0))
+                                && !data.export_ordinal_table.contains(&(i as u16))
+                        })
+                        .map(|(i, _)| format!("#{}", base + i)),
+                );
+            }
+            names
+        }
+        _ => match goblin::mach::Mach::parse(&bytes).ok()? {
+            goblin::mach::Mach::Binary(macho) => macho
+                .exports()
+                .ok()?
+                .into_iter()
+                .map(|export| {
+                    export
+                        .name
+                        .strip_prefix('_')
+                        .unwrap_or(&export.name)
+                        .to_string()
+                })
+                .collect(),
+            goblin::mach::Mach::Fat(_) => return None,
+        },
+    };
+    names.sort();
+    names.dedup();
+    Some(names)
+}
+
+/// Needs the `diagnose` feature; always `None` without it.
+#[cfg(not(feature = "diagnose"))]
+pub fn exports(_path: &Path) -> Option<Vec<String>> {
+    None
+}
+
+/// The `DT_NEEDED` libraries of the ELF binary at `path` that are found neither in
+/// its `DT_RPATH`/`DT_RUNPATH` (with `$ORIGIN` expanded), nor in `LD_LIBRARY_PATH`,
+/// the `ld.so.conf` directories or the default ones. `None` when the binary cannot
+/// be read or parsed.
+#[cfg(all(feature = "diagnose", target_os = "linux"))]
+pub fn missing_dependencies(path: &Path) -> Option<Vec<String>> {
+    let bytes = std::fs::read(path).ok()?;
+    let elf = goblin::elf::Elf::parse(&bytes).ok()?;
+    let origin = path.parent().unwrap_or(Path::new(".")).to_string_lossy();
+    let expand = |list: &[&str]| -> Vec<PathBuf> {
+        list.iter()
+            .flat_map(|entry| entry.split(':'))
+            .filter(|dir| !dir.is_empty())
+            .map(|dir| {
+                PathBuf::from(
+                    dir.replace("${ORIGIN}", &origin)
+                        .replace("$ORIGIN", &origin),
+                )
+            })
+            .collect()
+    };
+
+    // ld.so's order: DT_RPATH only without DT_RUNPATH, then LD_LIBRARY_PATH, then
+    // DT_RUNPATH, then the cache (approximated by its configuration) and defaults.
+    let mut dirs = Vec::new();
+    if elf.runpaths.is_empty() {
+        dirs.extend(expand(&elf.rpaths));
+    }
+    if let Ok(env) = std::env::var("LD_LIBRARY_PATH") {
+        dirs.extend(expand(&[env.as_str()]));
+    }
+    dirs.extend(expand(&elf.runpaths));
+    ld_so_conf(Path::new("/etc/ld.so.conf"), &mut dirs, 0);
+    let multiarch = format!("{}-linux-gnu", std::env::consts::ARCH);
+    for base in ["/lib", "/usr/lib", "/lib64", "/usr/lib64"] {
+        dirs.push(Path::new(base).join(&multiarch));
+        dirs.push(PathBuf::from(base));
+    }
+
+    Some(
+        elf.libraries
+            .iter()
+            .filter(|lib| {
+                if lib.contains('/') {
+                    !Path::new(lib).is_file()
+                } else {
+                    !dirs.iter().any(|dir| dir.join(lib).is_file())
+                }
+            })
+            .map(|lib| lib.to_string())
+            .collect(),
+    )
+}
+
+/// Needs the `diagnose` feature on Linux; always `None` otherwise.
+#[cfg(not(all(feature = "diagnose", target_os = "linux")))]
+pub fn missing_dependencies(_path: &Path) -> Option<Vec<String>> {
+    None
+}
+
+/// The directories listed in an `ld.so.conf`, following `include` lines.
+#[cfg(all(feature = "diagnose", target_os = "linux"))]
+fn ld_so_conf(conf: &Path, dirs: &mut Vec<PathBuf>, depth: usize) {
+    let Ok(text) = std::fs::read_to_string(conf) else {
+        return;
+    };
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if let Some(pattern) = line.strip_prefix("include") {
+            if depth < 4 {
+                for file in include_files(pattern.trim(), conf) {
+                    ld_so_conf(&file, dirs, depth + 1);
+                }
+            }
+        } else if line.starts_with('/') {
+            dirs.push(PathBuf::from(line));
+        }
+    }
+}
+
+/// The files an `include` pattern names: a plain path, or `dir/prefix*suffix`.
+#[cfg(all(feature = "diagnose", target_os = "linux"))]
+fn include_files(pattern: &str, conf: &Path) -> Vec<PathBuf> {
+    let pattern = conf.parent().unwrap_or(Path::new("/")).join(pattern);
+    let name = pattern
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let Some((prefix, suffix)) = name.split_once('*') else {
+        return vec![pattern];
+    };
+    let Some(Ok(entries)) = pattern.parent().map(std::fs::read_dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<_> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .map(|n| n.to_string_lossy())
+                .is_some_and(|n| n.starts_with(prefix) && n.ends_with(suffix))
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decorated_factory_names_are_recognised() {
+        for alias in [
+            "_GetPluginFactory@0",
+            "GetPluginFactory@0",
+            "_GetPluginFactory",
+            "GetPluginFactory@VST_1",
+            "GetPluginFactory@@VST_1",
+        ] {
+            assert!(is_factory_alias(alias), "{alias}");
+        }
+        for other in ["GetPluginFactory", "GetPluginFactoryEx", "_ModuleEntry", ""] {
+            assert!(!is_factory_alias(other), "{other}");
+        }
+        assert_eq!(symbol_version("GetPluginFactory@VST_1"), Some("VST_1"));
+        assert_eq!(symbol_version("GetPluginFactory@@VST_1"), Some("VST_1"));
+        assert_eq!(symbol_version("_GetPluginFactory@0"), None);
+        assert_eq!(symbol_version("GetPluginFactory"), None);
+    }
+
+    #[cfg(feature = "dlopen")]
+    #[test]
+    fn missing_factory_tells_other_libraries_apart() {
+        use crate::HostError;
+
+        let missing = |exports: Option<&[&str]>| MissingFactory {
+            path: "/x/lib.so".into(),
+            tried: vec!["GetPluginFactory".into()],
+            exports: exports.map(|e| e.iter().map(|s| s.to_string()).collect()),
+        };
+
+        let unread = missing(None);
+        assert_eq!(unread.looks_like_vst3(), None);
+        assert!(matches!(unread.into_error(), HostError::NoFactorySymbol(_)));
+        let vst3 = missing(Some(&["ModuleEntry", "ModuleExit"]));
+        assert_eq!(vst3.looks_like_vst3(), Some(true));
+        assert!(matches!(vst3.into_error(), HostError::NoFactorySymbol(_)));
+        let other = missing(Some(&["deflate", "inflate"]));
+        assert_eq!(other.looks_like_vst3(), Some(false));
+        let e = other.into_error();
+        assert!(matches!(e, HostError::NotVst3(_)));
+        assert_eq!(
+            e.to_string(),
+            "not a VST 3 module: tried GetPluginFactory; the binary exports deflate, inflate"
+        );
+
+        let empty = missing(Some(&[]));
+        assert!(empty.to_string().ends_with("; the binary exports nothing"));
+        assert!(empty.diagnosis().contains("  exports: none\n"));
+        assert!(missing(None).diagnosis().contains("  exports: not read\n"));
+
+        let many: Vec<String> = (0..11).map(|i| format!("f{i:02}")).collect();
+        let many: Vec<&str> = many.iter().map(String::as_str).collect();
+        assert!(missing(Some(&many))
+            .to_string()
+            .ends_with("f06, f07 and 3 more"));
+    }
+}
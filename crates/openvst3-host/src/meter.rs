@@ -0,0 +1,341 @@
+//! Level metering for host output: decaying peak, windowed RMS and momentary loudness.
+//!
+//! [`Meter`] is meant for a level display, not for loudness compliance: the
+//! K-weighting follows ITU-R BS.1770, but every channel is weighted 1 and there is no
+//! gating. Non-finite samples count as silence. Nothing allocates after
+//! [`Meter::new`], so it can run in an audio callback.
+
+use crate::limiter::Sample;
+
+/// Length of the momentary loudness window.
+const MOMENTARY_SECONDS: f64 = 0.4;
+
+/// How a [`Meter`] measures.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeterConfig {
+    /// How fast [`Meter::peak`] falls back, in dB per second.
+    pub peak_decay_db_per_sec: f64,
+    /// Length of the RMS window in seconds.
+    pub rms_window: f64,
+    /// Also measure momentary loudness.
+    pub loudness: bool,
+}
+
+impl Default for MeterConfig {
+    fn default() -> Self {
+        Self {
+            peak_decay_db_per_sec: 12.0,
+            rms_window: 0.3,
+            loudness: true,
+        }
+    }
+}
+
+/// `20 * log10(linear)`; silence is negative infinity.
+pub fn to_db(linear: f64) -> f64 {
+    20.0 * linear.log10()
+}
+
+/// Transposed direct form II biquad; `a0` is normalized to 1.
+#[derive(Clone, Copy, Debug)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+}
+
+impl Biquad {
+    fn run(&self, state: &mut [f64; 2], x: f64) -> f64 {
+        let y = self.b[0] * x + state[0];
+        state[0] = self.b[1] * x - self.a[0] * y + state[1];
+        state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// The two K-weighting stages for `sample_rate`: a high shelf for the head and a
+/// high pass, with the BS.1770 analog prototypes mapped by the bilinear transform.
+fn k_weighting(sample_rate: f64) -> [Biquad; 2] {
+    let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (core::f64::consts::PI * f0 / sample_rate).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (core::f64::consts::PI * f0 / sample_rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let highpass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    };
+    [shelf, highpass]
+}
+
+/// Mean of the last `len` squared samples. The running sum is recomputed on every
+/// wrap so rounding does not build up.
+#[derive(Debug)]
+struct Window {
+    squares: Box<[f64]>,
+    pos: usize,
+    sum: f64,
+}
+
+impl Window {
+    fn new(len: usize) -> Self {
+        Self {
+            squares: vec![0.0; len.max(1)].into_boxed_slice(),
+            pos: 0,
+            sum: 0.0,
+        }
+    }
+
+    fn push(&mut self, square: f64) {
+        self.sum += square - self.squares[self.pos];
+        self.squares[self.pos] = square;
+        self.pos += 1;
+        if self.pos == self.squares.len() {
+            self.pos = 0;
+            self.sum = self.squares.iter().sum();
+        }
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum.max(0.0) / self.squares.len() as f64
+    }
+
+    fn clear(&mut self) {
+        self.squares.fill(0.0);
+        self.pos = 0;
+        self.sum = 0.0;
+    }
+}
+
+#[derive(Debug)]
+struct Channel {
+    peak: f64,
+    max_peak: f64,
+    rms: Window,
+    /// K-weighting filter states, shelf then high pass.
+    k_state: [[f64; 2]; 2],
+}
+
+/// See the module docs. Levels are linear; use [`to_db`] for display.
+#[derive(Debug)]
+pub struct Meter {
+    channels: Box<[Channel]>,
+    /// Peak fall per frame, as a factor.
+    decay: f64,
+    k_filters: [Biquad; 2],
+    /// Sum over channels of the K-weighted squares, when measuring loudness.
+    momentary: Option<Window>,
+    max_momentary: f64,
+}
+
+impl Meter {
+    pub fn new(channels: usize, sample_rate: f64, config: &MeterConfig) -> Self {
+        let rms_len = (config.rms_window * sample_rate).round() as usize;
+        Self {
+            channels: (0..channels)
+                .map(|_| Channel {
+                    peak: 0.0,
+                    max_peak: 0.0,
+                    rms: Window::new(rms_len),
+                    k_state: [[0.0; 2]; 2],
+                })
+                .collect(),
+            decay: 10f64.powf(-config.peak_decay_db_per_sec.max(0.0) / 20.0 / sample_rate),
+            k_filters: k_weighting(sample_rate),
+            momentary: config
+                .loudness
+                .then(|| Window::new((MOMENTARY_SECONDS * sample_rate).round() as usize)),
+            max_momentary: 0.0,
+        }
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Feed `frames` frames of planar channels; missing channels are not metered.
+    pub fn process_planar<T: Sample>(&mut self, planar: &[impl AsRef<[T]>], frames: usize) {
+        self.begin_block(frames);
+        for i in 0..frames {
+            let frame = planar.iter().map(|chan| chan.as_ref()[i]);
+            self.frame(frame);
+        }
+        self.end_block();
+    }
+
+    /// Feed whole frames of interleaved channels.
+    pub fn process_interleaved<T: Sample>(&mut self, data: &[T]) {
+        let channels = self.channels.len().max(1);
+        self.begin_block(data.len() / channels);
+        for frame in data.chunks_exact(channels) {
+            self.frame(frame.iter().copied());
+        }
+        self.end_block();
+    }
+
+    fn begin_block(&mut self, frames: usize) {
+        let fall = self.decay.powi(frames.min(i32::MAX as usize) as i32);
+        for chan in self.channels.iter_mut() {
+            chan.peak *= fall;
+        }
+    }
+
+    fn frame<T: Sample>(&mut self, frame: impl Iterator<Item = T>) {
+        let mut weighted = 0.0;
+        for (chan, x) in self.channels.iter_mut().zip(frame) {
+            let x = x.to_f64();
+            let x = if x.is_finite() { x } else { 0.0 };
+            chan.peak = chan.peak.max(x.abs());
+            chan.max_peak = chan.max_peak.max(x.abs());
+            chan.rms.push(x * x);
+            if self.momentary.is_some() {
+                let [shelf, highpass] = &self.k_filters;
+                let y = shelf.run(&mut chan.k_state[0], x);
+                let y = highpass.run(&mut chan.k_state[1], y);
+                weighted += y * y;
+            }
+        }
+        if let Some(window) = &mut self.momentary {
+            window.push(weighted);
+        }
+    }
+
+    fn end_block(&mut self) {
+        if let Some(window) = &self.momentary {
+            self.max_momentary = self.max_momentary.max(window.mean());
+        }
+    }
+
+    /// The channel's peak, falling back at the configured rate.
+    pub fn peak(&self, channel: usize) -> f64 {
+        self.channels[channel].peak
+    }
+
+    /// The highest sample of the channel since the last [`reset_max`](Self::reset_max).
+    pub fn max_peak(&self, channel: usize) -> f64 {
+        self.channels[channel].max_peak
+    }
+
+    /// RMS over the configured window, counting silence before the first samples.
+    pub fn rms(&self, channel: usize) -> f64 {
+        self.channels[channel].rms.mean().sqrt()
+    }
+
+    /// Momentary loudness in LUFS, if measured; counts silence before the first 400 ms.
+    pub fn momentary_lufs(&self) -> Option<f64> {
+        self.momentary.as_ref().map(|w| lufs(w.mean()))
+    }
+
+    /// The loudest momentary loudness since the last [`reset_max`](Self::reset_max),
+    /// taken at block ends.
+    pub fn max_momentary_lufs(&self) -> Option<f64> {
+        self.momentary.as_ref().map(|_| lufs(self.max_momentary))
+    }
+
+    /// Start a new interval for [`max_peak`](Self::max_peak) and
+    /// [`max_momentary_lufs`](Self::max_momentary_lufs).
+    pub fn reset_max(&mut self) {
+        for chan in self.channels.iter_mut() {
+            chan.max_peak = 0.0;
+        }
+        self.max_momentary = 0.0;
+    }
+
+    /// Forget everything, as after construction.
+    pub fn reset(&mut self) {
+        for chan in self.channels.iter_mut() {
+            chan.peak = 0.0;
+            chan.max_peak = 0.0;
+            chan.rms.clear();
+            chan.k_state = [[0.0; 2]; 2];
+        }
+        if let Some(window) = &mut self.momentary {
+            window.clear();
+        }
+        self.max_momentary = 0.0;
+    }
+}
+
+fn lufs(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore = "no unsafe code; too slow under Miri")]
+    fn meter_reads_peak_and_rms_of_a_sine() {
+        let sr = 48_000.0;
+        // 1 kHz: 48 samples a period, so the 300 ms window holds whole periods.
+        let sine =
+            |amp: f64, n: usize| amp * (2.0 * std::f64::consts::PI * 1000.0 * n as f64 / sr).sin();
+        let mut meter = Meter::new(2, sr, &MeterConfig::default());
+        for block in 0..100 {
+            let planar: Vec<Vec<f32>> = [0.5, 0.25]
+                .iter()
+                .map(|&amp| {
+                    (0..480)
+                        .map(|i| sine(amp, block * 480 + i) as f32)
+                        .collect()
+                })
+                .collect();
+            meter.process_planar(&planar, 480);
+        }
+        for (ch, amp) in [(0, 0.5), (1, 0.25)] {
+            assert!((meter.max_peak(ch) - amp).abs() < 1e-6);
+            assert!((meter.peak(ch) - amp).abs() < 1e-6);
+            assert!((meter.rms(ch) - amp / 2f64.sqrt()).abs() < 1e-6);
+        }
+        assert!((to_db(meter.rms(0)) + 9.03).abs() < 0.01);
+
+        // One second of silence: the peak falls 12 dB, the RMS window empties.
+        let silence = vec![0.0f64; 2 * 48_000];
+        meter.process_interleaved(&silence);
+        assert!((to_db(meter.peak(0) / 0.5) + 12.0).abs() < 0.01);
+        assert_eq!(meter.rms(0), 0.0);
+        assert!((meter.max_peak(0) - 0.5).abs() < 1e-6);
+        meter.reset_max();
+        meter.process_interleaved(&silence[..64]);
+        assert_eq!(meter.max_peak(0), 0.0);
+        assert!(meter.peak(0) > 0.0);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore = "no unsafe code; too slow under Miri")]
+    fn meter_momentary_loudness_of_a_full_scale_sine() {
+        // BS.1770: a 0 dBFS 1 kHz sine on one channel reads -3.01 LUFS.
+        for sr in [44_100.0, 48_000.0, 96_000.0] {
+            let mut meter = Meter::new(2, sr, &MeterConfig::default());
+            let frames = sr as usize;
+            let data: Vec<f32> = (0..frames)
+                .flat_map(|n| {
+                    let x = (2.0 * std::f64::consts::PI * 997.0 * n as f64 / sr).sin() as f32;
+                    [x, 0.0]
+                })
+                .collect();
+            meter.process_interleaved(&data);
+            let lufs = meter.momentary_lufs().unwrap();
+            assert!((lufs + 3.01).abs() < 0.05, "{sr} Hz: {lufs}");
+            assert!(meter.max_momentary_lufs().unwrap() >= lufs);
+        }
+        let quiet = Meter::new(1, 48_000.0, &MeterConfig::default());
+        assert_eq!(quiet.momentary_lufs(), Some(f64::NEG_INFINITY));
+        let off = MeterConfig {
+            loudness: false,
+            ..MeterConfig::default()
+        };
+        assert_eq!(Meter::new(1, 48_000.0, &off).momentary_lufs(), None);
+    }
+}
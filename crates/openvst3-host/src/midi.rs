@@ -0,0 +1,419 @@
+//! Raw MIDI messages to VST3 note events and parameter changes.
+//!
+//! VST3 has no MIDI input: notes and polyphonic pressure become [`Event`]s, while
+//! controllers, channel pressure and pitch bend only reach a plugin as changes of the
+//! parameters its `IMidiMapping` assigns to them. [`MidiConverter`] holds that
+//! assignment table (filled by [`MidiConverter::learn`] or by hand) and writes each
+//! message into the block's [`EventList`] or [`ParameterChanges`] without allocating.
+//!
+//! The other way, [`events_to_midi`] turns what a plugin put on its event output bus
+//! (an arpeggiator's notes, a MIDI effect's controllers) back into MIDI messages for
+//! hardware. Note ids and tuning have no MIDI equivalent and are dropped.
+
+use openvst3_abi::{event_consts, iids, Event, FUnknown, IMidiMapping, ParamID, K_RESULT_OK};
+
+use crate::events::{EventList, ParameterChanges};
+
+/// CC 0..=127 plus aftertouch and pitch bend.
+const CONTROLLERS: usize = 130;
+
+/// What [`MidiConverter::convert`] did with a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Converted {
+    Event,
+    Param(ParamID),
+    /// Not a channel voice message, or a controller without an assigned parameter.
+    Ignored,
+    /// The destination list was full; nothing was written, retry next block.
+    Full,
+}
+
+pub struct MidiConverter {
+    /// `[channel][controller]`.
+    assignments: Box<[[Option<ParamID>; CONTROLLERS]; 16]>,
+    bus_index: i32,
+}
+
+impl Default for MidiConverter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MidiConverter {
+    /// A converter for event bus 0 with no controller assignments.
+    pub fn new() -> Self {
+        Self {
+            assignments: Box::new([[None; CONTROLLERS]; 16]),
+            bus_index: 0,
+        }
+    }
+
+    /// Route notes to event bus `index` and ask `IMidiMapping` about that bus.
+    pub fn with_bus_index(mut self, index: i32) -> Self {
+        self.bus_index = index;
+        self
+    }
+
+    /// Send `controller` (0..=127, or `event_consts::AFTER_TOUCH`/`PITCH_BEND`) on
+    /// `channel` to parameter `id`.
+    pub fn assign(&mut self, channel: u8, controller: i16, id: ParamID) {
+        if let Some(slot) = self
+            .assignments
+            .get_mut(channel as usize)
+            .and_then(|c| c.get_mut(controller as usize))
+        {
+            *slot = Some(id);
+        }
+    }
+
+    pub fn assignment(&self, channel: u8, controller: i16) -> Option<ParamID> {
+        *self
+            .assignments
+            .get(channel as usize)?
+            .get(controller as usize)?
+    }
+
+    /// Fill the table from `obj`'s `IMidiMapping` (usually the edit controller).
+    /// Returns how many assignments were found; 0 if `obj` has no MIDI mapping.
+    pub unsafe fn learn(&mut self, obj: *mut FUnknown) -> usize {
+        if obj.is_null() {
+            return 0;
+        }
+        let mut mapping: *mut IMidiMapping = core::ptr::null_mut();
+        if (*obj).query_interface(&iids::IMIDI_MAPPING, &mut mapping) != K_RESULT_OK
+            || mapping.is_null()
+        {
+            return 0;
+        }
+        let mut found = 0;
+        for channel in 0..16u8 {
+            for controller in 0..CONTROLLERS as i16 {
+                let mut id: ParamID = 0;
+                let tr = (*mapping).get_midi_controller_assignment(
+                    self.bus_index,
+                    channel as i16,
+                    controller,
+                    &mut id,
+                );
+                if tr == K_RESULT_OK {
+                    self.assign(channel, controller, id);
+                    found += 1;
+                }
+            }
+        }
+        FUnknown::release(mapping as *mut FUnknown);
+        found
+    }
+
+    /// Convert one complete MIDI message (no running status) at `sample_offset`.
+    pub fn convert(
+        &self,
+        bytes: &[u8],
+        sample_offset: i32,
+        events: &mut EventList,
+        params: &mut ParameterChanges,
+    ) -> Converted {
+        let Some(&status) = bytes.first() else {
+            return Converted::Ignored;
+        };
+        let channel = status & 0x0F;
+        let data = |i: usize| bytes.get(i).copied().unwrap_or(0) & 0x7F;
+        let unit = |v: u8| v as f32 / 127.0;
+        let event = match status & 0xF0 {
+            0x90 if data(2) > 0 => {
+                Event::note_on(sample_offset, channel as i16, data(1) as i16, unit(data(2)))
+            }
+            // Note-on with velocity 0 is a note-off.
+            0x80 | 0x90 => {
+                let velocity = if status & 0xF0 == 0x80 { data(2) } else { 0 };
+                Event::note_off(
+                    sample_offset,
+                    channel as i16,
+                    data(1) as i16,
+                    unit(velocity),
+                )
+            }
+            0xA0 => {
+                Event::poly_pressure(sample_offset, channel as i16, data(1) as i16, unit(data(2)))
+            }
+            0xB0 => {
+                return self.param(
+                    channel,
+                    data(1) as i16,
+                    unit(data(2)) as f64,
+                    sample_offset,
+                    params,
+                )
+            }
+            0xD0 => {
+                return self.param(
+                    channel,
+                    event_consts::AFTER_TOUCH,
+                    unit(data(1)) as f64,
+                    sample_offset,
+                    params,
+                )
+            }
+            0xE0 => {
+                let bend = (data(1) as u16 | (data(2) as u16) << 7) as f64 / 16383.0;
+                return self.param(
+                    channel,
+                    event_consts::PITCH_BEND,
+                    bend,
+                    sample_offset,
+                    params,
+                );
+            }
+            _ => return Converted::Ignored,
+        };
+        let event = Event {
+            bus_index: self.bus_index,
+            flags: event_consts::IS_LIVE,
+            ..event
+        };
+        if events.push(event) {
+            Converted::Event
+        } else {
+            Converted::Full
+        }
+    }
+
+    fn param(
+        &self,
+        channel: u8,
+        controller: i16,
+        value: f64,
+        sample_offset: i32,
+        params: &mut ParameterChanges,
+    ) -> Converted {
+        match self.assignment(channel, controller) {
+            Some(id) if params.add_point(id, sample_offset, value) => Converted::Param(id),
+            Some(_) => Converted::Full,
+            None => Converted::Ignored,
+        }
+    }
+}
+
+/// Which MIDI channel [`events_to_midi`] sends on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChannelPolicy {
+    /// The event's own channel; events outside 0..=15 are dropped.
+    #[default]
+    Keep,
+    /// Everything on this channel (0..=15).
+    Force(u8),
+}
+
+/// One MIDI message from [`events_to_midi`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MidiMessage {
+    /// A channel voice message; program change and channel pressure leave the third
+    /// byte 0 and [`as_bytes`](Self::as_bytes) drops it.
+    Channel([u8; 3]),
+    /// A system exclusive message, `F0` to `F7`.
+    Sysex(Vec<u8>),
+}
+
+impl MidiMessage {
+    /// The bytes to send.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            MidiMessage::Channel(bytes) if matches!(bytes[0] & 0xF0, 0xC0 | 0xD0) => &bytes[..2],
+            MidiMessage::Channel(bytes) => bytes,
+            MidiMessage::Sysex(bytes) => bytes,
+        }
+    }
+}
+
+/// 0..=1 back to 0..=127, the inverse of [`MidiConverter`]'s scaling.
+fn quantize(value: f32) -> u8 {
+    if value.is_nan() {
+        return 0;
+    }
+    (value.clamp(0.0, 1.0) * 127.0).round() as u8
+}
+
+/// Convert a plugin's output events to MIDI messages, each with its sample offset,
+/// ordered by offset (events at the same offset keep their order). Notes, polyphonic
+/// pressure, legacy MIDI CC out events (controllers, channel pressure, pitch bend,
+/// program change) and sysex data events convert; other events are dropped. A note-on
+/// whose velocity rounds to 0 is sent with velocity 1, since velocity 0 would end it.
+///
+/// # Safety
+/// The `bytes` of every `DATA` event must be valid for `size` bytes. A plugin keeps
+/// them valid until its next `process` call, so convert before that.
+pub unsafe fn events_to_midi(
+    out_events: &[Event],
+    channel_policy: ChannelPolicy,
+) -> Vec<(i32, MidiMessage)> {
+    let status = |kind: u8, channel: i32| -> Option<u8> {
+        let channel = match channel_policy {
+            ChannelPolicy::Keep => u8::try_from(channel).ok().filter(|&c| c < 16)?,
+            ChannelPolicy::Force(channel) => channel & 0x0F,
+        };
+        Some(kind | channel)
+    };
+    let mut messages: Vec<(i32, MidiMessage)> = out_events
+        .iter()
+        .filter_map(|e| {
+            let message = match e.type_ {
+                event_consts::NOTE_ON => {
+                    let n = e.data.note_on;
+                    let velocity = quantize(n.velocity).max(1);
+                    MidiMessage::Channel([
+                        status(0x90, n.channel.into())?,
+                        n.pitch as u8 & 0x7F,
+                        velocity,
+                    ])
+                }
+                event_consts::NOTE_OFF => {
+                    let n = e.data.note_off;
+                    MidiMessage::Channel([
+                        status(0x80, n.channel.into())?,
+                        n.pitch as u8 & 0x7F,
+                        quantize(n.velocity),
+                    ])
+                }
+                event_consts::POLY_PRESSURE => {
+                    let p = e.data.poly_pressure;
+                    MidiMessage::Channel([
+                        status(0xA0, p.channel.into())?,
+                        p.pitch as u8 & 0x7F,
+                        quantize(p.pressure),
+                    ])
+                }
+                event_consts::LEGACY_MIDI_CC_OUT => {
+                    let cc = e.data.midi_cc_out;
+                    let (value, value2) = (cc.value as u8 & 0x7F, cc.value2 as u8 & 0x7F);
+                    let channel = cc.channel.into();
+                    MidiMessage::Channel(match cc.control_number as i16 {
+                        control @ 0..=127 => [status(0xB0, channel)?, control as u8, value],
+                        event_consts::AFTER_TOUCH => [status(0xD0, channel)?, value, 0],
+                        event_consts::PITCH_BEND => [status(0xE0, channel)?, value, value2],
+                        event_consts::PROGRAM_CHANGE => [status(0xC0, channel)?, value, 0],
+                        _ => return None,
+                    })
+                }
+                event_consts::DATA => {
+                    let data = e.data.data;
+                    if data.type_ != event_consts::DATA_MIDI_SYSEX || data.bytes.is_null() {
+                        return None;
+                    }
+                    let bytes = core::slice::from_raw_parts(data.bytes, data.size as usize);
+                    MidiMessage::Sysex(bytes.to_vec())
+                }
+                _ => return None,
+            };
+            Some((e.sample_offset, message))
+        })
+        .collect();
+    messages.sort_by_key(|&(offset, _)| offset);
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn midi_converter_maps_notes_and_assigned_controllers() {
+        use crate::midi::Converted;
+        use openvst3_abi::event_consts;
+
+        let mut midi = MidiConverter::new();
+        midi.assign(0, 74, 100);
+        midi.assign(0, event_consts::PITCH_BEND, 101);
+        let mut events = EventList::with_capacity(1);
+        let mut params = ParameterChanges::with_capacity(4, 4);
+
+        assert_eq!(
+            midi.convert(&[0x90, 60, 127], 4, &mut events, &mut params),
+            Converted::Event
+        );
+        // Velocity 0 is a note-off, but the list is full.
+        assert_eq!(
+            midi.convert(&[0x90, 60, 0], 8, &mut events, &mut params),
+            Converted::Full
+        );
+        let on = events.events()[0];
+        assert_eq!(on.type_, event_consts::NOTE_ON);
+        assert_eq!(on.flags, event_consts::IS_LIVE);
+        events.clear();
+        assert_eq!(
+            midi.convert(&[0x90, 60, 0], 8, &mut events, &mut params),
+            Converted::Event
+        );
+        assert_eq!(events.events()[0].type_, event_consts::NOTE_OFF);
+
+        assert_eq!(
+            midi.convert(&[0xB0, 74, 127], 2, &mut events, &mut params),
+            Converted::Param(100)
+        );
+        assert_eq!(
+            midi.convert(&[0xB1, 74, 127], 2, &mut events, &mut params),
+            Converted::Ignored
+        );
+        assert_eq!(
+            midi.convert(&[0xE0, 0x00, 0x40], 3, &mut events, &mut params),
+            Converted::Param(101)
+        );
+        assert_eq!(
+            midi.convert(&[0xF8], 0, &mut events, &mut params),
+            Converted::Ignored
+        );
+        let bend = &params.queues()[1];
+        assert_eq!(bend.id(), 101);
+        assert!((bend.points()[0].1 - 8192.0 / 16383.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn events_to_midi_converts_cc_out_and_sysex() {
+        use openvst3_abi::{event_consts, DataEvent, Event, EventData};
+
+        let sysex = [0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7];
+        let data = Event {
+            sample_offset: 9,
+            type_: event_consts::DATA,
+            data: EventData {
+                data: DataEvent {
+                    size: sysex.len() as u32,
+                    type_: event_consts::DATA_MIDI_SYSEX,
+                    bytes: sysex.as_ptr(),
+                },
+            },
+            ..Event::default()
+        };
+        let events = [
+            Event::midi_cc_out(5, 2, 7, 100, 0),
+            Event::midi_cc_out(1, 2, event_consts::PITCH_BEND as u8, 0x00, 0x40),
+            Event::midi_cc_out(2, 2, event_consts::AFTER_TOUCH as u8, 90, 0),
+            Event::midi_cc_out(3, 2, event_consts::PROGRAM_CHANGE as u8, 12, 0),
+            // kCtrlPolyPressure has its own event type; the legacy form is dropped.
+            Event::midi_cc_out(4, 2, 131, 1, 0),
+            data,
+            // Too quiet to round above 0, yet still a note-on.
+            Event::note_on(6, 0, 72, 0.001),
+            Event::note_on(7, 20, 72, 1.0),
+        ];
+        let messages = unsafe { events_to_midi(&events, ChannelPolicy::Keep) };
+        let bytes: Vec<_> = messages
+            .iter()
+            .map(|(offset, msg)| (*offset, msg.as_bytes()))
+            .collect();
+        assert_eq!(
+            bytes,
+            [
+                (1, &[0xE2, 0x00, 0x40][..]),
+                (2, &[0xD2, 90]),
+                (3, &[0xC2, 12]),
+                (5, &[0xB2, 7, 100]),
+                (6, &[0x90, 72, 1]),
+                (9, &sysex),
+            ]
+        );
+
+        let forced = unsafe { events_to_midi(&events[6..], ChannelPolicy::Force(9)) };
+        assert_eq!(forced[1], (7, MidiMessage::Channel([0x99, 72, 127])));
+    }
+}
@@ -0,0 +1,143 @@
+//! Decoded parameter titles and bus names, kept until the plugin says they changed.
+//!
+//! A [`NameCache`] sits behind each [`ControllerHandle`] and [`ComponentHandle`] (an
+//! instance's two handles share one). [`ControllerHandle::param_title`] and
+//! [`ComponentHandle::bus_name`] decode on the first call and afterwards cost a lock
+//! and a hash lookup, so UI code can ask every frame.
+//!
+//! `restartComponent` drops the affected names: `kParamTitlesChanged` the titles,
+//! `kIoChanged`/`kIoTitlesChanged` the bus names, `kReloadComponent` both. Handlers set
+//! through [`ControllerHandle::set_component_handler`] do this before the callback
+//! runs; other caches can be attached with [`ComponentHandler::track_names`]. Each drop
+//! bumps a generation, and a lookup that started decoding before it does not store
+//! what it decoded, so a title read while the plugin was renaming is never kept.
+//!
+//! [`ComponentHandler::track_names`]: crate::ComponentHandler::track_names
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use openvst3_abi::{restart_consts, BusInfo, ParamID};
+
+use crate::{ComponentHandle, ControllerHandle};
+
+/// (media type, direction, index) of a bus.
+type BusKey = (i32, i32, i32);
+type Titles = Arc<HashMap<ParamID, Arc<str>>>;
+
+/// See the module docs.
+#[derive(Debug, Default)]
+pub struct NameCache {
+    generation: AtomicU64,
+    /// Every readable parameter's title, once read.
+    params: Mutex<Option<Titles>>,
+    buses: Mutex<HashMap<BusKey, Arc<str>>>,
+}
+
+impl NameCache {
+    pub fn new() -> Arc<Self> {
+        Arc::default()
+    }
+
+    /// Bumped whenever names are dropped.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Drop the names a `restartComponent(flags)` makes stale. Returns whether the
+    /// flags touch names at all.
+    pub fn invalidate(&self, flags: i32) -> bool {
+        let reload = flags & restart_consts::K_RELOAD_COMPONENT != 0;
+        let params = reload || flags & restart_consts::K_PARAM_TITLES_CHANGED != 0;
+        let buses = reload
+            || flags & (restart_consts::K_IO_CHANGED | restart_consts::K_IO_TITLES_CHANGED) != 0;
+        if !params && !buses {
+            return false;
+        }
+        // Bump first: a lookup decoding right now must not store its result.
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        if params {
+            *self.params.lock().unwrap() = None;
+        }
+        if buses {
+            self.buses.lock().unwrap().clear();
+        }
+        true
+    }
+
+    /// Drop everything, e.g. after the plugin was reloaded.
+    pub fn clear(&self) {
+        self.invalidate(restart_consts::K_RELOAD_COMPONENT);
+    }
+
+    pub(crate) fn params(&self) -> Option<Titles> {
+        self.params.lock().unwrap().clone()
+    }
+
+    pub(crate) fn store_params(&self, generation: u64, titles: Titles) {
+        let mut params = self.params.lock().unwrap();
+        if self.generation() == generation {
+            *params = Some(titles);
+        }
+    }
+
+    fn bus(&self, key: BusKey) -> Option<Arc<str>> {
+        self.buses.lock().unwrap().get(&key).cloned()
+    }
+
+    fn store_bus(&self, generation: u64, key: BusKey, name: Arc<str>) {
+        let mut buses = self.buses.lock().unwrap();
+        if self.generation() == generation {
+            buses.insert(key, name);
+        }
+    }
+}
+
+impl ControllerHandle {
+    /// The title of parameter `id`, decoded once per [`NameCache`] generation. On a miss
+    /// every title is read; parameters whose info cannot be read have none.
+    pub fn param_title(&self, id: ParamID) -> Option<Arc<str>> {
+        let cache = self.names();
+        if let Some(titles) = cache.params() {
+            return titles.get(&id).cloned();
+        }
+        // Not under the lock: the plugin may restart from inside getParameterInfo.
+        let generation = cache.generation();
+        let titles: HashMap<ParamID, Arc<str>> = (0..self.parameter_count().max(0))
+            .filter_map(|i| self.parameter_info(i).ok())
+            .map(|p| (p.id, p.title.into()))
+            .collect();
+        let title = titles.get(&id).cloned();
+        cache.store_params(generation, Arc::new(titles));
+        title
+    }
+}
+
+impl ComponentHandle {
+    /// The name of a bus, decoded once per [`NameCache`] generation; `None` if its
+    /// info cannot be read.
+    pub fn bus_name(&self, media_type: i32, direction: i32, index: i32) -> Option<Arc<str>> {
+        let cache = self.names();
+        let key = (media_type, direction, index);
+        if let Some(name) = cache.bus(key) {
+            return Some(name);
+        }
+        let generation = cache.generation();
+        let info = self.bus_info(media_type, direction, index).ok()?;
+        let name: Arc<str> = bus_name(&info).into();
+        cache.store_bus(generation, key, name.clone());
+        Some(name)
+    }
+}
+
+/// `BusInfo::name` up to the first NUL; invalid UTF-8 becomes U+FFFD.
+fn bus_name(info: &BusInfo) -> String {
+    let bytes: Vec<u8> = info
+        .name
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
@@ -0,0 +1,216 @@
+//! Current parameter values for UI code, without asking the controller.
+//!
+//! A [`ParamCache`] holds one normalized value per parameter, read with
+//! `getParamNormalized` when it is built
+//! ([`PluginInstance::enable_param_cache`](crate::PluginInstance::enable_param_cache)).
+//! It changes from two sides:
+//! - the processor's output parameter changes: the instance gives the plugin an
+//!   output list in every process call and stores the last point of each queue;
+//! - the controller's `performEdit`, through a [`ComponentHandler`] the cache is
+//!   attached to with [`ComponentHandler::track_params`].
+//!
+//! Each write sets the parameter's bit in a change bitmap, which UI code can poll
+//! once per frame with [`take_changes`](ParamCache::take_changes). Values and flags
+//! are atomics, so reads never block the audio thread.
+//!
+//! Within one block the processor wins. Once it has reported a parameter, edits to
+//! that parameter are ignored until the next block begins. An edit that arrives
+//! during the process call is overwritten when the output changes are applied. A
+//! parameter the processor left alone keeps the last edit, and from the next block
+//! on the last writer wins again.
+//!
+//! [`ComponentHandler`]: crate::ComponentHandler
+//! [`ComponentHandler::track_params`]: crate::ComponentHandler::track_params
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use openvst3_abi::{IParameterChanges, ParamID, ParamValue, K_RESULT_OK};
+
+use crate::{ControllerHandle, HostError};
+
+/// No block yet: the processor has not reported the parameter.
+const NEVER: u64 = u64::MAX;
+
+/// See the module docs.
+#[derive(Debug)]
+pub struct ParamCache {
+    /// Sorted; a parameter's position is its index everywhere below.
+    ids: Box<[ParamID]>,
+    /// `f64` bits.
+    values: Box<[AtomicU64]>,
+    /// Bit `i % 64` of word `i / 64`: parameter `i` changed since the last poll.
+    changed: Box<[AtomicU64]>,
+    /// The block each parameter was last reported by the processor in.
+    reported_in: Box<[AtomicU64]>,
+    block: AtomicU64,
+}
+
+impl ParamCache {
+    /// A cache of these parameters and values; a repeated id keeps its last value.
+    pub fn new(params: impl IntoIterator<Item = (ParamID, ParamValue)>) -> Self {
+        let mut params: Vec<_> = params.into_iter().collect();
+        // Reversed and stably sorted, the last of equal ids comes first, which is the
+        // one `dedup_by_key` keeps.
+        params.reverse();
+        params.sort_by_key(|&(id, _)| id);
+        params.dedup_by_key(|&mut (id, _)| id);
+        let atomics = |n: usize, init: u64| (0..n).map(|_| AtomicU64::new(init)).collect();
+        Self {
+            ids: params.iter().map(|&(id, _)| id).collect(),
+            values: params
+                .iter()
+                .map(|&(_, value)| AtomicU64::new(value.to_bits()))
+                .collect(),
+            changed: atomics(params.len().div_ceil(64), 0),
+            reported_in: atomics(params.len(), NEVER),
+            block: AtomicU64::new(0),
+        }
+    }
+
+    /// Every parameter of `controller` with its current `getParamNormalized` value.
+    pub fn from_controller(controller: &ControllerHandle) -> Result<Self, HostError> {
+        let params = controller.parameters()?;
+        Ok(Self::new(
+            params
+                .iter()
+                .map(|p| (p.id, controller.get_param_normalized(p.id))),
+        ))
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// The cached parameters, in id order.
+    pub fn ids(&self) -> &[ParamID] {
+        &self.ids
+    }
+
+    fn index(&self, id: ParamID) -> Option<usize> {
+        self.ids.binary_search(&id).ok()
+    }
+
+    /// The current value of `id`; `NaN` for a parameter the cache does not hold.
+    pub fn value(&self, id: ParamID) -> ParamValue {
+        self.index(id).map_or(f64::NAN, |i| {
+            f64::from_bits(self.values[i].load(Ordering::Relaxed))
+        })
+    }
+
+    /// Whether `id` changed since the last [`take_changes`](Self::take_changes).
+    pub fn is_changed(&self, id: ParamID) -> bool {
+        self.index(id)
+            .is_some_and(|i| self.changed[i / 64].load(Ordering::Relaxed) & (1 << (i % 64)) != 0)
+    }
+
+    /// Clear the change bitmap, calling `f` with each parameter that changed since the
+    /// last call and its current value. Returns how many did.
+    pub fn take_changes(&self, mut f: impl FnMut(ParamID, ParamValue)) -> usize {
+        let mut count = 0;
+        for (word, flags) in self.changed.iter().enumerate() {
+            let mut bits = flags.swap(0, Ordering::AcqRel);
+            while bits != 0 {
+                let i = word * 64 + bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                f(
+                    self.ids[i],
+                    f64::from_bits(self.values[i].load(Ordering::Relaxed)),
+                );
+                count += 1;
+            }
+        }
+        count
+    }
+
+    fn store(&self, i: usize, value: ParamValue) {
+        self.values[i].store(value.to_bits(), Ordering::Relaxed);
+        self.changed[i / 64].fetch_or(1 << (i % 64), Ordering::Release);
+    }
+
+    /// Start a new block: edits to every parameter count again until the processor
+    /// reports it.
+    pub fn begin_block(&self) {
+        self.block.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// A value the processor reported in the current block.
+    pub fn processor_update(&self, id: ParamID, value: ParamValue) {
+        if let Some(i) = self.index(id) {
+            self.reported_in[i].store(self.block.load(Ordering::Acquire), Ordering::Relaxed);
+            self.store(i, value);
+        }
+    }
+
+    /// A value the controller edited. Returns false, storing nothing, for an unknown
+    /// parameter or one the processor already reported in the current block.
+    pub fn edit(&self, id: ParamID, value: ParamValue) -> bool {
+        let Some(i) = self.index(id) else {
+            return false;
+        };
+        if self.reported_in[i].load(Ordering::Relaxed) == self.block.load(Ordering::Acquire) {
+            return false;
+        }
+        self.store(i, value);
+        true
+    }
+
+    /// Store the last point of each queue in a block's output `changes` as a
+    /// [`processor_update`](Self::processor_update).
+    ///
+    /// # Safety
+    /// `changes` must be null or a live `IParameterChanges`.
+    pub unsafe fn apply_output_changes(&self, changes: *mut IParameterChanges) {
+        let Some(changes) = changes.as_mut() else {
+            return;
+        };
+        for index in 0..changes.get_parameter_count().max(0) {
+            let Some(queue) = changes.get_parameter_data(index).as_mut() else {
+                continue;
+            };
+            let points = queue.get_point_count();
+            let (mut offset, mut value) = (0, 0.0);
+            if points > 0 && queue.get_point(points - 1, &mut offset, &mut value) == K_RESULT_OK {
+                self.processor_update(queue.get_parameter_id(), value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn param_cache_prefers_the_processor_within_a_block() {
+        let cache = ParamCache::new([(5, 0.0), (3, 0.5), (5, 0.25)]);
+        assert_eq!(cache.ids(), [3, 5]);
+        assert_eq!(cache.value(5), 0.25);
+        assert!(cache.value(4).is_nan());
+        assert!(!cache.edit(4, 1.0));
+
+        cache.begin_block();
+        // An edit during the process call loses to what the processor reports...
+        assert!(cache.edit(5, 0.1));
+        cache.processor_update(5, 0.2);
+        assert_eq!(cache.value(5), 0.2);
+        // ...and so does one after it, until the next block.
+        assert!(!cache.edit(5, 0.3));
+        assert_eq!(cache.value(5), 0.2);
+        // Parameters the processor left alone take edits as usual.
+        assert!(cache.edit(3, 0.75));
+
+        cache.begin_block();
+        assert!(cache.edit(5, 0.4));
+        assert_eq!(cache.value(5), 0.4);
+
+        let mut seen = Vec::new();
+        assert_eq!(cache.take_changes(|id, value| seen.push((id, value))), 2);
+        assert_eq!(seen, [(3, 0.75), (5, 0.4)]);
+        assert!(!cache.is_changed(5));
+        assert_eq!(cache.take_changes(|_, _| unreachable!()), 0);
+    }
+}
@@ -0,0 +1,200 @@
+//! Parameter table and display strings of an edit controller.
+
+use openvst3_abi::{
+    ParamID, ParamValue, ParameterFlags, ParameterInfo, String128, K_NOT_IMPLEMENTED,
+    K_RESULT_FALSE, K_RESULT_OK,
+};
+
+use crate::plugin::check;
+use crate::{ControllerHandle, HostError};
+
+/// `ParameterInfo` with its strings decoded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamInfo {
+    /// Position in the controller's parameter list (not the id).
+    pub index: i32,
+    pub id: ParamID,
+    pub title: String,
+    pub short_title: String,
+    pub units: String,
+    /// 0 for continuous parameters.
+    pub step_count: i32,
+    pub default_normalized: ParamValue,
+    pub unit_id: i32,
+    /// Unknown bits are kept.
+    pub flags: ParameterFlags,
+}
+
+impl ParamInfo {
+    fn from_raw(index: i32, raw: &ParameterInfo) -> Self {
+        Self {
+            index,
+            id: raw.id,
+            title: string128(&raw.title),
+            short_title: string128(&raw.short_title),
+            units: string128(&raw.units),
+            step_count: raw.step_count,
+            default_normalized: raw.default_normalized_value,
+            unit_id: raw.unit_id,
+            flags: ParameterFlags::from_bits_retain(raw.flags),
+        }
+    }
+
+    pub fn can_automate(&self) -> bool {
+        self.flags.contains(ParameterFlags::CAN_AUTOMATE)
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.flags.contains(ParameterFlags::IS_READ_ONLY)
+    }
+
+    /// Hosts may change it: automatable and not read-only.
+    pub fn is_writable(&self) -> bool {
+        self.can_automate() && !self.is_read_only()
+    }
+}
+
+/// Human-readable flag names, e.g. `"automatable, list"`; empty if none.
+pub fn describe_param_flags(flags: ParameterFlags) -> String {
+    const NAMES: [(ParameterFlags, &str); 7] = [
+        (ParameterFlags::CAN_AUTOMATE, "automatable"),
+        (ParameterFlags::IS_READ_ONLY, "read-only"),
+        (ParameterFlags::IS_WRAP_AROUND, "wrap-around"),
+        (ParameterFlags::IS_LIST, "list"),
+        (ParameterFlags::IS_HIDDEN, "hidden"),
+        (ParameterFlags::IS_PROGRAM_CHANGE, "program change"),
+        (ParameterFlags::IS_BYPASS, "bypass"),
+    ];
+    let mut names: Vec<String> = NAMES
+        .iter()
+        .filter(|(f, _)| flags.contains(*f))
+        .map(|(_, n)| n.to_string())
+        .collect();
+    let unknown = flags.bits() & !ParameterFlags::all().bits();
+    if unknown != 0 {
+        names.push(format!("{unknown:#x}"));
+    }
+    names.join(", ")
+}
+
+/// NUL-terminated UTF-16; unpaired surrogates become U+FFFD.
+fn string128(s: &String128) -> String {
+    let len = s.iter().position(|&c| c == 0).unwrap_or(s.len());
+    String::from_utf16_lossy(&s[..len])
+}
+
+/// The number at the start of `text`, whatever the decimal separator: `"-6,5 dB"`,
+/// `"1.234,5"`, `"1,234.5"` and `"\u{2212}3"` all parse. Units after it are ignored.
+fn parse_number(text: &str) -> Option<f64> {
+    let text = text.trim_start().replace('\u{2212}', "-");
+    let end = text
+        .find(|c: char| !(c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | ',' | 'e' | 'E')))
+        .unwrap_or(text.len());
+    let number = text[..end].trim_end_matches(['e', 'E']);
+    // The last separator is the decimal one; any other is grouping.
+    let number = match number.rfind(['.', ',']) {
+        Some(at) => format!(
+            "{}.{}",
+            number[..at].replace(['.', ','], ""),
+            &number[at + 1..]
+        ),
+        None => number.to_owned(),
+    };
+    number.parse().ok()
+}
+
+impl ControllerHandle {
+    pub fn parameter_info(&self, index: i32) -> Result<ParamInfo, HostError> {
+        let mut raw: ParameterInfo = unsafe { core::mem::zeroed() };
+        check(traced!(
+            "IEditController",
+            "getParameterInfo",
+            unsafe { (*self.as_ptr()).get_parameter_info(index, &mut raw) },
+            index = index
+        ))?;
+        Ok(ParamInfo::from_raw(index, &raw))
+    }
+
+    /// Every parameter in list order; the first failing index aborts.
+    pub fn parameters(&self) -> Result<Vec<ParamInfo>, HostError> {
+        (0..self.parameter_count().max(0))
+            .map(|i| self.parameter_info(i))
+            .collect()
+    }
+
+    /// Look a parameter up by id.
+    pub fn find_parameter(&self, id: ParamID) -> Result<Option<ParamInfo>, HostError> {
+        Ok(self.parameters()?.into_iter().find(|p| p.id == id))
+    }
+
+    /// `normalizedParamToPlain`.
+    pub fn to_plain(&self, id: ParamID, normalized: ParamValue) -> f64 {
+        traced!(
+            "IEditController",
+            "normalizedParamToPlain",
+            unsafe { (*self.as_ptr()).normalized_param_to_plain(id, normalized) },
+            param_id = id,
+            value = normalized
+        )
+    }
+
+    /// `plainParamToNormalized`.
+    pub fn to_normalized(&self, id: ParamID, plain: f64) -> ParamValue {
+        traced!(
+            "IEditController",
+            "plainParamToNormalized",
+            unsafe { (*self.as_ptr()).plain_param_to_normalized(id, plain) },
+            param_id = id,
+            value = plain
+        )
+    }
+
+    /// `normalized` as the plugin displays it (e.g. `"-6.0"`; units are separate).
+    /// When the plugin has no string for it, or an empty one, the plain value is
+    /// printed instead.
+    pub fn format_param(&self, id: ParamID, normalized: ParamValue) -> String {
+        match self.param_string_by_value(id, normalized) {
+            Ok(s) if !s.trim().is_empty() => s.trim_end().to_owned(),
+            _ => format!("{:.3}", self.to_plain(id, normalized)),
+        }
+    }
+
+    /// The normalized value for a display string, through `getParamValueByString`.
+    /// If the plugin cannot parse `text` (`kResultFalse` or `kNotImplemented`), a
+    /// leading number is read as a plain value instead, with either `.` or `,` as the
+    /// decimal separator.
+    pub fn parse_param(&self, id: ParamID, text: &str) -> Option<ParamValue> {
+        let wide: Vec<u16> = text.encode_utf16().chain([0]).collect();
+        let mut value: ParamValue = 0.0;
+        let tr = traced!(
+            "IEditController",
+            "getParamValueByString",
+            unsafe { (*self.as_ptr()).get_param_value_by_string(id, wide.as_ptr(), &mut value) },
+            param_id = id
+        );
+        match tr {
+            K_RESULT_OK => Some(value),
+            K_RESULT_FALSE | K_NOT_IMPLEMENTED => {
+                parse_number(text).map(|plain| self.to_normalized(id, plain))
+            }
+            _ => None,
+        }
+    }
+
+    /// The controller's display string for `value` (e.g. `"-6.0"`); units are separate.
+    pub fn param_string_by_value(
+        &self,
+        id: ParamID,
+        value: ParamValue,
+    ) -> Result<String, HostError> {
+        let mut s: String128 = [0; 128];
+        check(traced!(
+            "IEditController",
+            "getParamStringByValue",
+            unsafe { (*self.as_ptr()).get_param_string_by_value(id, value, &mut s) },
+            param_id = id,
+            value = value
+        ))?;
+        Ok(string128(&s))
+    }
+}
@@ -0,0 +1,1519 @@
+//! Owning interface pointers and a lifecycle-managed plugin instance.
+//!
+//! `InterfacePtr<T>` holds exactly one reference and releases it on drop. The typed
+//! handles wrap the raw vtable calls and map `tresult`s to [`HostError`]. A
+//! [`PluginInstance`] walks the VST3 lifecycle (initialize → setupProcessing →
+//! setActive → setProcessing) and unwinds whatever steps succeeded when a later one
+//! fails or the instance is dropped.
+
+use core::cell::RefCell;
+use core::ffi::c_void;
+use core::ptr::NonNull;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
+
+use openvst3_abi::{
+    iids, io_mode_consts, process_consts, tresult, BusInfo, FUnknown, IAudioProcessor, IComponent,
+    IEditController, IPluginFactory, ParamID, ParamValue, ProcessData32, ProcessData64,
+    ProcessSetup, Tuid, K_NOT_IMPLEMENTED, K_RESULT_FALSE, K_RESULT_OK,
+};
+
+use crate::snapshot::AbSlots;
+use crate::{
+    BusCheck, BusId, ClassRef, HostError, HostLimits, Limit, LimitOrigin, LiveProcessor,
+    MemoryStream, Module, NameCache, ParamCache, ParameterChanges, ProcessSetupBuilder,
+    ProcessStats, ScanPhase, Tail, TimingCollector,
+};
+
+#[inline]
+pub(crate) fn check(tr: tresult) -> Result<(), HostError> {
+    if tr == K_RESULT_OK {
+        Ok(())
+    } else {
+        Err(HostError::TErr(tr))
+    }
+}
+
+/// Result of a call the spec lets plugins decline: `kNotImplemented` and
+/// `kResultFalse` are not errors (and are logged with `trace`). `Ok(false)` when the
+/// plugin does not implement the call.
+pub(crate) fn optional(
+    interface: &'static str,
+    method: &'static str,
+    tr: tresult,
+) -> Result<bool, HostError> {
+    match tr {
+        K_RESULT_OK => Ok(true),
+        K_NOT_IMPLEMENTED | K_RESULT_FALSE => {
+            #[cfg(feature = "trace")]
+            crate::trace::optional_call_declined(interface, method, tr);
+            #[cfg(not(feature = "trace"))]
+            let _ = (interface, method);
+            Ok(tr == K_RESULT_FALSE)
+        }
+        tr => Err(HostError::TErr(tr)),
+    }
+}
+
+/// Owns one reference to a plugin interface.
+pub struct InterfacePtr<T> {
+    raw: NonNull<T>,
+}
+
+// Plugin objects may be handed between threads (e.g. setup on one thread, process on
+// the audio thread); callers must still not invoke them concurrently.
+unsafe impl<T> Send for InterfacePtr<T> {}
+
+impl<T> InterfacePtr<T> {
+    /// Adopt a reference the caller already owns (e.g. from `createInstance`/`queryInterface`).
+    /// The caller no longer owes a `release`: dropping the result releases.
+    ///
+    /// # Safety
+    /// `raw` must be null or a live interface pointer whose vtable starts with FUnknown.
+    pub unsafe fn from_raw(raw: *mut T) -> Option<Self> {
+        Self::adopt(raw, None)
+    }
+
+    /// [`from_raw`](Self::from_raw) for a pointer known not to be null, e.g. one
+    /// from [`into_raw`](Self::into_raw) or another crate's wrapper.
+    ///
+    /// # Safety
+    /// As for `from_raw`, and `raw` must not be null.
+    pub unsafe fn from_raw_unchecked(raw: *mut T) -> Self {
+        debug_assert!(!raw.is_null(), "from_raw_unchecked on a null pointer");
+        Self::adopt(raw, None).unwrap_unchecked()
+    }
+
+    #[cfg_attr(not(feature = "leak-audit"), allow(unused_variables))]
+    unsafe fn adopt(raw: *mut T, parent: Option<usize>) -> Option<Self> {
+        let raw = NonNull::new(raw)?;
+        #[cfg(feature = "leak-audit")]
+        crate::leak_audit::register::<T>(raw.as_ptr() as usize, parent);
+        Some(Self { raw })
+    }
+
+    #[inline]
+    pub fn as_ptr(&self) -> *mut T {
+        self.raw.as_ptr()
+    }
+
+    /// Give up ownership without releasing: the caller now owes the one `release`,
+    /// directly or by handing the pointer back to [`from_raw`](Self::from_raw).
+    pub fn into_raw(self) -> *mut T {
+        let raw = self.raw.as_ptr();
+        #[cfg(feature = "leak-audit")]
+        crate::leak_audit::unregister(raw as usize);
+        core::mem::forget(self);
+        raw
+    }
+
+    #[inline]
+    fn unknown(&self) -> *mut FUnknown {
+        self.raw.as_ptr() as *mut FUnknown
+    }
+
+    /// `queryInterface` for `iid`; the result owns its own reference.
+    ///
+    /// # Safety
+    /// `U` must be the interface type identified by `iid`.
+    pub unsafe fn query<U>(&self, iid: &Tuid) -> Result<InterfacePtr<U>, HostError> {
+        let mut out: *mut c_void = core::ptr::null_mut();
+        let tr = (*self.unknown()).query_interface(iid, &mut out);
+        if tr != K_RESULT_OK {
+            return Err(HostError::no_interface(*iid));
+        }
+        InterfacePtr::adopt(out as *mut U, Some(self.as_ptr() as usize))
+            .ok_or_else(|| HostError::no_interface(*iid))
+    }
+}
+
+impl<T> Clone for InterfacePtr<T> {
+    fn clone(&self) -> Self {
+        unsafe { (*self.unknown()).add_ref() };
+        #[cfg(feature = "leak-audit")]
+        crate::leak_audit::register::<T>(self.as_ptr() as usize, None);
+        Self { raw: self.raw }
+    }
+}
+
+impl<T> Drop for InterfacePtr<T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "leak-audit")]
+        crate::leak_audit::unregister(self.as_ptr() as usize);
+        unsafe { FUnknown::release(self.unknown()) };
+    }
+}
+
+// ----- Typed handles ------------------------------------------------------------
+
+/// `Vst::IoModes`: how the host will drive the component. Fixed before `initialize`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IoMode {
+    #[default]
+    Simple,
+    Advanced,
+    /// Offline rendering; plugins may switch to slower, higher-quality algorithms.
+    OfflineProcessing,
+}
+
+impl IoMode {
+    pub fn as_raw(self) -> i32 {
+        match self {
+            IoMode::Simple => io_mode_consts::K_SIMPLE,
+            IoMode::Advanced => io_mode_consts::K_ADVANCED,
+            IoMode::OfflineProcessing => io_mode_consts::K_OFFLINE_PROCESSING,
+        }
+    }
+}
+
+/// `Vst::ProcessModes`: what drives `process`, given to `setupProcessing`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProcessMode {
+    #[default]
+    Realtime,
+    /// Faster than realtime, ahead of playback, but still timed like realtime.
+    Prefetch,
+    /// Offline rendering: no deadline, so plugins may use their best algorithms.
+    Offline,
+}
+
+impl ProcessMode {
+    pub fn as_raw(self) -> i32 {
+        match self {
+            ProcessMode::Realtime => process_consts::PROCESS_MODE_REALTIME,
+            ProcessMode::Prefetch => process_consts::PROCESS_MODE_PREFETCH,
+            ProcessMode::Offline => process_consts::PROCESS_MODE_OFFLINE,
+        }
+    }
+
+    pub fn from_raw(raw: i32) -> Option<Self> {
+        match raw {
+            process_consts::PROCESS_MODE_REALTIME => Some(ProcessMode::Realtime),
+            process_consts::PROCESS_MODE_PREFETCH => Some(ProcessMode::Prefetch),
+            process_consts::PROCESS_MODE_OFFLINE => Some(ProcessMode::Offline),
+            _ => None,
+        }
+    }
+}
+
+/// `Vst::SymbolicSampleSizes`: the sample type of every buffer `process` gets.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymbolicSampleSize {
+    #[default]
+    Sample32,
+    Sample64,
+}
+
+impl SymbolicSampleSize {
+    pub fn as_raw(self) -> i32 {
+        match self {
+            SymbolicSampleSize::Sample32 => process_consts::SYMBOLIC_SAMPLE_32,
+            SymbolicSampleSize::Sample64 => process_consts::SYMBOLIC_SAMPLE_64,
+        }
+    }
+
+    pub fn from_raw(raw: i32) -> Option<Self> {
+        match raw {
+            process_consts::SYMBOLIC_SAMPLE_32 => Some(SymbolicSampleSize::Sample32),
+            process_consts::SYMBOLIC_SAMPLE_64 => Some(SymbolicSampleSize::Sample64),
+            _ => None,
+        }
+    }
+
+    /// Bits per sample.
+    pub fn bits(self) -> u32 {
+        match self {
+            SymbolicSampleSize::Sample32 => 32,
+            SymbolicSampleSize::Sample64 => 64,
+        }
+    }
+}
+
+/// `IComponent` calls with `tresult` mapped to [`HostError`].
+#[derive(Clone)]
+pub struct ComponentHandle {
+    ptr: InterfacePtr<IComponent>,
+    names: Arc<NameCache>,
+    /// Between a successful `initialize` and `terminate`; shared by clones.
+    initialized: Arc<AtomicBool>,
+}
+
+impl ComponentHandle {
+    /// Wrap a component that has not been initialized yet.
+    pub fn new(ptr: InterfacePtr<IComponent>) -> Self {
+        Self {
+            ptr,
+            names: NameCache::new(),
+            initialized: Arc::default(),
+        }
+    }
+
+    /// Wrap a component the caller has already initialized.
+    fn initialized(ptr: InterfacePtr<IComponent>) -> Self {
+        let handle = Self::new(ptr);
+        handle.initialized.store(true, Ordering::Release);
+        handle
+    }
+
+    pub fn interface(&self) -> &InterfacePtr<IComponent> {
+        &self.ptr
+    }
+
+    /// The bus names decoded so far; shared by clones of this handle.
+    pub fn names(&self) -> &Arc<NameCache> {
+        &self.names
+    }
+
+    #[inline]
+    pub fn as_ptr(&self) -> *mut IComponent {
+        self.ptr.as_ptr()
+    }
+
+    /// The component for code using other VST3 bindings. Borrowed: no reference is
+    /// added, so the pointer is valid while `self` lives and must not be released;
+    /// `addRef` it to keep it longer.
+    #[inline]
+    pub fn as_raw_component(&self) -> *mut IComponent {
+        self.as_ptr()
+    }
+
+    /// # Safety
+    /// `context` must be null or a live host-context object.
+    pub unsafe fn initialize(&self, context: *mut FUnknown) -> Result<(), HostError> {
+        check(traced!(
+            "IComponent",
+            "initialize",
+            (*self.as_ptr()).initialize(context)
+        ))?;
+        self.initialized.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn terminate(&self) -> Result<(), HostError> {
+        self.initialized.store(false, Ordering::Release);
+        check(traced!("IComponent", "terminate", unsafe {
+            (*self.as_ptr()).terminate()
+        }))
+    }
+
+    /// `setIoMode`, which the spec only allows before `initialize`; later calls fail
+    /// with [`HostError::CallOrder`] without reaching the plugin. `Ok(false)` if the
+    /// plugin does not implement it.
+    pub fn set_io_mode(&self, mode: IoMode) -> Result<bool, HostError> {
+        if self.initialized.load(Ordering::Acquire) {
+            return Err(HostError::CallOrder {
+                call: "setIoMode",
+                before: "initialize",
+            });
+        }
+        let tr = traced!(
+            "IComponent",
+            "setIoMode",
+            unsafe { (*self.as_ptr()).set_io_mode(mode.as_raw()) },
+            mode = mode.as_raw()
+        );
+        optional("IComponent", "setIoMode", tr)
+    }
+
+    pub fn set_active(&self, state: bool) -> Result<(), HostError> {
+        check(traced!(
+            "IComponent",
+            "setActive",
+            unsafe { (*self.as_ptr()).set_active(state) },
+            state = state
+        ))
+    }
+
+    pub fn bus_count(&self, media_type: i32, direction: i32) -> i32 {
+        traced!(
+            "IComponent",
+            "getBusCount",
+            unsafe { (*self.as_ptr()).get_bus_count(media_type, direction) },
+            media_type = media_type,
+            direction = direction
+        )
+    }
+
+    pub fn bus_info(
+        &self,
+        media_type: i32,
+        direction: i32,
+        index: i32,
+    ) -> Result<BusInfo, HostError> {
+        let mut info = BusInfo {
+            media_type,
+            direction,
+            channel_count: 0,
+            name: [0; 64],
+            bus_type: 0,
+            flags: 0,
+        };
+        check(traced!(
+            "IComponent",
+            "getBusInfo",
+            unsafe { (*self.as_ptr()).get_bus_info(media_type, direction, index, &mut info) },
+            media_type = media_type,
+            direction = direction,
+            bus_index = index
+        ))?;
+        Ok(info)
+    }
+
+    pub fn get_state(&self, stream: &mut MemoryStream) -> Result<(), HostError> {
+        check(traced!("IComponent", "getState", unsafe {
+            (*self.as_ptr()).get_state(stream.as_ibstream())
+        }))
+    }
+
+    pub fn set_state(&self, stream: &mut MemoryStream) -> Result<(), HostError> {
+        check(traced!(
+            "IComponent",
+            "setState",
+            unsafe { (*self.as_ptr()).set_state(stream.as_ibstream()) },
+            bytes = stream.bytes().len()
+        ))
+    }
+
+    /// `getControllerClassId`: the class of a separate edit controller. `None` when the
+    /// call fails (`kResultFalse` is how single-component plugins answer) or gives
+    /// the nil CID.
+    pub fn controller_cid(&self) -> Option<Tuid> {
+        let mut cid = Tuid([0; 16]);
+        let tr = traced!("IComponent", "getControllerClassId", unsafe {
+            (*self.as_ptr()).get_controller_class_id(&mut cid)
+        });
+        (tr == K_RESULT_OK && cid.0 != [0; 16]).then_some(cid)
+    }
+}
+
+/// The `count` output buses at `buses`, none if the pointer is null.
+unsafe fn output_buses<'a, B>(buses: *const B, count: i32) -> &'a [B] {
+    if buses.is_null() || count <= 0 {
+        &[]
+    } else {
+        core::slice::from_raw_parts(buses, count as usize)
+    }
+}
+
+/// `IAudioProcessor` calls with `tresult` mapped to [`HostError`].
+///
+/// `initialize`, `setupProcessing` and `process` are mandatory. `setProcessing` and
+/// `setBusArrangements` are optional: a plugin answering `kNotImplemented` is not an
+/// error, it just clears the matching `supports_*` flag.
+pub struct ProcessorHandle {
+    ptr: InterfacePtr<IAudioProcessor>,
+    /// Time each `process` call into [`ProcessStats::duration`].
+    timing: AtomicBool,
+    /// Continuous accounting of every `process` call, when attached.
+    collector: Option<Arc<TimingCollector>>,
+    set_processing: AtomicBool,
+    set_bus_arrangements: AtomicBool,
+    /// Blocks seen by this handle, for rate-limited process tracing.
+    #[cfg(feature = "trace")]
+    blocks: core::sync::atomic::AtomicU64,
+}
+
+impl Clone for ProcessorHandle {
+    fn clone(&self) -> Self {
+        let mut clone = Self::new(self.ptr.clone());
+        clone.set_timing(self.timing());
+        clone.collector = self.collector.clone();
+        clone
+            .set_processing
+            .store(self.supports_set_processing(), Ordering::Relaxed);
+        clone
+            .set_bus_arrangements
+            .store(self.supports_set_bus_arrangements(), Ordering::Relaxed);
+        clone
+    }
+}
+
+impl ProcessorHandle {
+    pub fn new(ptr: InterfacePtr<IAudioProcessor>) -> Self {
+        Self {
+            ptr,
+            timing: AtomicBool::new(false),
+            collector: None,
+            set_processing: AtomicBool::new(true),
+            set_bus_arrangements: AtomicBool::new(true),
+            #[cfg(feature = "trace")]
+            blocks: core::sync::atomic::AtomicU64::new(0),
+        }
+    }
+
+    pub fn interface(&self) -> &InterfacePtr<IAudioProcessor> {
+        &self.ptr
+    }
+
+    /// Measure how long each `process` call takes (off by default).
+    pub fn set_timing(&self, on: bool) {
+        self.timing.store(on, Ordering::Relaxed);
+    }
+
+    pub fn timing(&self) -> bool {
+        self.timing.load(Ordering::Relaxed)
+    }
+
+    /// Feed every `process` call to `collector`, or stop with `None`. Clones of the
+    /// handle share the collector.
+    pub fn set_timing_collector(&mut self, collector: Option<Arc<TimingCollector>>) {
+        self.collector = collector;
+    }
+
+    pub fn timing_collector(&self) -> Option<&Arc<TimingCollector>> {
+        self.collector.as_ref()
+    }
+
+    /// False once `setProcessing` answered `kNotImplemented`.
+    pub fn supports_set_processing(&self) -> bool {
+        self.set_processing.load(Ordering::Relaxed)
+    }
+
+    /// False once `setBusArrangements` answered `kNotImplemented`; the plugin then
+    /// keeps its default layout.
+    pub fn supports_set_bus_arrangements(&self) -> bool {
+        self.set_bus_arrangements.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn as_ptr(&self) -> *mut IAudioProcessor {
+        self.ptr.as_ptr()
+    }
+
+    /// Whether `canProcessSampleSize` accepts `size`; any other answer, including
+    /// `kNotImplemented`, is a no.
+    pub fn can_process_sample_size(&self, size: SymbolicSampleSize) -> bool {
+        traced!(
+            "IAudioProcessor",
+            "canProcessSampleSize",
+            unsafe { (*self.as_ptr()).can_process_sample_size(size.as_raw()) },
+            sample_size = size.bits()
+        ) == K_RESULT_OK
+    }
+
+    pub fn setup_processing(&self, setup: &ProcessSetup) -> Result<(), HostError> {
+        check(traced!(
+            "IAudioProcessor",
+            "setupProcessing",
+            unsafe { (*self.as_ptr()).setup_processing(setup) },
+            sample_rate = setup.sample_rate,
+            block_size = setup.max_samples_per_block,
+            sample_size = setup.symbolic_sample_size,
+            process_mode = setup.process_mode
+        ))
+    }
+
+    /// Optional: `kNotImplemented` and `kResultFalse` are accepted.
+    pub fn set_processing(&self, state: bool) -> Result<(), HostError> {
+        let tr = traced!(
+            "IAudioProcessor",
+            "setProcessing",
+            unsafe { (*self.as_ptr()).set_processing(state as i32) },
+            state = state
+        );
+        if !optional("IAudioProcessor", "setProcessing", tr)? {
+            self.set_processing.store(false, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    pub fn set_bus_arrangements(&self, inputs: &[u64], outputs: &[u64]) -> Result<(), HostError> {
+        let tr = traced!(
+            "IAudioProcessor",
+            "setBusArrangements",
+            unsafe {
+                (*self.as_ptr()).set_bus_arrangements(
+                    inputs.as_ptr(),
+                    inputs.len() as i32,
+                    outputs.as_ptr(),
+                    outputs.len() as i32,
+                )
+            },
+            num_inputs = inputs.len(),
+            num_outputs = outputs.len()
+        );
+        // `kResultFalse` means this layout is refused, so only `kNotImplemented` is
+        // let through.
+        if tr == K_NOT_IMPLEMENTED {
+            optional("IAudioProcessor", "setBusArrangements", tr)?;
+            self.set_bus_arrangements.store(false, Ordering::Relaxed);
+            return Ok(());
+        }
+        if tr != K_RESULT_OK {
+            return Err(HostError::ArrangementRejected {
+                inputs: inputs.to_vec(),
+                outputs: outputs.to_vec(),
+            });
+        }
+        Ok(())
+    }
+
+    /// The speaker arrangement audio bus `index` in `direction` has now.
+    pub fn bus_arrangement(&self, direction: i32, index: i32) -> Result<u64, HostError> {
+        let mut arrangement = 0;
+        check(traced!(
+            "IAudioProcessor",
+            "getBusArrangement",
+            unsafe { (*self.as_ptr()).get_bus_arrangement(direction, index, &mut arrangement) },
+            direction = direction,
+            bus_index = index
+        ))?;
+        Ok(arrangement)
+    }
+
+    /// # Safety
+    /// Every buffer pointer in `data` must be valid for `num_samples` frames.
+    pub unsafe fn process_32f(&self, data: &mut ProcessData32) -> Result<ProcessStats, HostError> {
+        crate::crash_marker::enter(ScanPhase::Process);
+        let started = self.timing().then(Instant::now);
+        let clock = self.collector.as_ref().map(|c| c.start());
+        let tr = traced!(
+            @sampled crate::trace::sample_block(&self.blocks);
+            "IAudioProcessor",
+            "process",
+            (*self.as_ptr()).process_32f(data),
+            block_size = data.num_samples,
+            sample_size = 32
+        );
+        if let (Some(collector), Some(clock)) = (&self.collector, clock) {
+            collector.finish(clock, data.num_samples.max(0) as usize);
+        }
+        let outputs = output_buses(data.outputs, data.num_outputs);
+        ProcessStats::collect(
+            tr,
+            started,
+            outputs.iter().map(|bus| bus.silence_flags),
+            data.output_parameter_changes,
+            data.output_events,
+        )
+    }
+
+    /// # Safety
+    /// Every buffer pointer in `data` must be valid for `num_samples` frames.
+    pub unsafe fn process_64f(&self, data: &mut ProcessData64) -> Result<ProcessStats, HostError> {
+        crate::crash_marker::enter(ScanPhase::Process);
+        let started = self.timing().then(Instant::now);
+        let clock = self.collector.as_ref().map(|c| c.start());
+        let tr = traced!(
+            @sampled crate::trace::sample_block(&self.blocks);
+            "IAudioProcessor",
+            "process",
+            (*self.as_ptr()).process_64f(data),
+            block_size = data.num_samples,
+            sample_size = 64
+        );
+        if let (Some(collector), Some(clock)) = (&self.collector, clock) {
+            collector.finish(clock, data.num_samples.max(0) as usize);
+        }
+        let outputs = output_buses(data.outputs, data.num_outputs);
+        ProcessStats::collect(
+            tr,
+            started,
+            outputs.iter().map(|bus| bus.silence_flags),
+            data.output_parameter_changes,
+            data.output_events,
+        )
+    }
+
+    pub fn latency_samples(&self) -> u32 {
+        traced!("IAudioProcessor", "getLatencySamples", unsafe {
+            (*self.as_ptr()).get_latency_samples()
+        })
+    }
+
+    /// `process_consts::INFINITE_TAIL` if the plugin never goes silent on its own.
+    pub fn tail_samples(&self) -> u32 {
+        traced!("IAudioProcessor", "getTailSamples", unsafe {
+            (*self.as_ptr()).get_tail_samples()
+        })
+    }
+
+    /// [`tail_samples`](Self::tail_samples) as a [`Tail`].
+    pub fn tail(&self) -> Tail {
+        Tail::from_samples(self.tail_samples())
+    }
+}
+
+/// `IEditController` calls with `tresult` mapped to [`HostError`].
+#[derive(Clone)]
+pub struct ControllerHandle {
+    ptr: InterfacePtr<IEditController>,
+    names: Arc<NameCache>,
+}
+
+impl ControllerHandle {
+    pub fn new(ptr: InterfacePtr<IEditController>) -> Self {
+        Self {
+            ptr,
+            names: NameCache::new(),
+        }
+    }
+
+    /// A handle whose names live in `names`, e.g. its component's for a
+    /// single-component plugin.
+    pub(crate) fn with_names(ptr: InterfacePtr<IEditController>, names: &Arc<NameCache>) -> Self {
+        Self {
+            ptr,
+            names: names.clone(),
+        }
+    }
+
+    pub fn interface(&self) -> &InterfacePtr<IEditController> {
+        &self.ptr
+    }
+
+    /// The parameter titles decoded so far; shared by clones of this handle.
+    pub fn names(&self) -> &Arc<NameCache> {
+        &self.names
+    }
+
+    #[inline]
+    pub fn as_ptr(&self) -> *mut IEditController {
+        self.ptr.as_ptr()
+    }
+
+    pub fn parameter_count(&self) -> i32 {
+        traced!("IEditController", "getParameterCount", unsafe {
+            (*self.as_ptr()).get_parameter_count()
+        })
+    }
+
+    pub fn get_param_normalized(&self, id: ParamID) -> ParamValue {
+        traced!(
+            "IEditController",
+            "getParamNormalized",
+            unsafe { (*self.as_ptr()).get_param_normalized(id) },
+            param_id = id
+        )
+    }
+
+    pub fn set_param_normalized(&self, id: ParamID, value: ParamValue) -> Result<(), HostError> {
+        check(traced!(
+            "IEditController",
+            "setParamNormalized",
+            unsafe { (*self.as_ptr()).set_param_normalized(id, value) },
+            param_id = id,
+            value = value
+        ))
+    }
+
+    pub fn set_component_state(&self, stream: &mut MemoryStream) -> Result<(), HostError> {
+        check(traced!(
+            "IEditController",
+            "setComponentState",
+            unsafe { (*self.as_ptr()).set_component_state(stream.as_ibstream()) },
+            bytes = stream.bytes().len()
+        ))
+    }
+
+    pub fn get_state(&self, stream: &mut MemoryStream) -> Result<(), HostError> {
+        check(traced!("IEditController", "getState", unsafe {
+            (*self.as_ptr()).get_state(stream.as_ibstream())
+        }))
+    }
+
+    pub fn set_state(&self, stream: &mut MemoryStream) -> Result<(), HostError> {
+        check(traced!(
+            "IEditController",
+            "setState",
+            unsafe { (*self.as_ptr()).set_state(stream.as_ibstream()) },
+            bytes = stream.bytes().len()
+        ))
+    }
+}
+
+// ----- Creation ---------------------------------------------------------------------
+
+/// How a plugin splits processing and editing, from `getControllerClassId`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PluginArchitecture {
+    /// No separate controller class: whatever controller there is, is the component
+    /// object itself.
+    SingleComponent,
+    /// The edit controller is a class of its own, to be created separately and
+    /// connected to the component through `IConnectionPoint`. The factory is not
+    /// guaranteed to list it.
+    DualComponent { controller_cid: Tuid },
+}
+
+impl PluginArchitecture {
+    /// What `component` reports.
+    pub fn of(component: &ComponentHandle) -> Self {
+        match component.controller_cid() {
+            Some(controller_cid) => PluginArchitecture::DualComponent { controller_cid },
+            None => PluginArchitecture::SingleComponent,
+        }
+    }
+
+    /// The separate controller's class, for dual-component plugins.
+    pub fn controller_cid(self) -> Option<Tuid> {
+        match self {
+            PluginArchitecture::SingleComponent => None,
+            PluginArchitecture::DualComponent { controller_cid } => Some(controller_cid),
+        }
+    }
+}
+
+impl fmt::Display for PluginArchitecture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginArchitecture::SingleComponent => f.write_str("single component"),
+            PluginArchitecture::DualComponent { controller_cid } => write!(
+                f,
+                "dual component (controller {})",
+                crate::fmt_cid_hex(&controller_cid.0)
+            ),
+        }
+    }
+}
+
+/// Which `createInstance` call produced an [`AudioPlugin`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CreationPath {
+    /// Created as `IComponent` and queried for `IAudioProcessor`, as the spec has it.
+    Component,
+    /// Created with the `IAudioProcessor` IID directly, which some older plugins
+    /// expect instead.
+    DirectProcessor,
+}
+
+impl fmt::Display for CreationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CreationPath::Component => "IComponent",
+            CreationPath::DirectProcessor => "IAudioProcessor",
+        })
+    }
+}
+
+/// The interfaces of a freshly created, not yet initialized audio class.
+pub struct AudioPlugin {
+    /// Absent only if a directly created processor does not answer `IComponent`.
+    pub component: Option<ComponentHandle>,
+    pub processor: ProcessorHandle,
+    /// The controller, for single-component plugins.
+    pub controller: Option<ControllerHandle>,
+    pub path: CreationPath,
+}
+
+/// Create class `cid` as `IComponent` and query `IAudioProcessor` (and
+/// `IEditController`) from it; if the factory or the component refuses, try creating
+/// it with the `IAudioProcessor` IID instead. The error is the first path's.
+///
+/// # Safety
+/// `factory` must be a live plugin factory.
+pub unsafe fn create_audio_plugin(
+    factory: &mut IPluginFactory,
+    cid: [u8; 16],
+) -> Result<AudioPlugin, HostError> {
+    let query = |obj: &InterfacePtr<FUnknown>| {
+        let component = obj
+            .query::<IComponent>(&iids::ICOMPONENT)
+            .ok()
+            .map(ComponentHandle::new);
+        let controller = obj
+            .query::<IEditController>(&iids::IEDIT_CONTROLLER)
+            .ok()
+            .map(|c| match &component {
+                Some(component) => ControllerHandle::with_names(c, component.names()),
+                None => ControllerHandle::new(c),
+            });
+        (
+            component,
+            obj.query::<IAudioProcessor>(&iids::IAUDIO_PROCESSOR),
+            controller,
+        )
+    };
+    let mut create = |iid: Tuid| {
+        crate::create_instance_raw(factory, cid, iid.0).and_then(|raw| {
+            InterfacePtr::from_raw(raw as *mut FUnknown).ok_or_else(|| HostError::no_interface(iid))
+        })
+    };
+    let first = match create(iids::ICOMPONENT) {
+        Ok(obj) => match query(&obj) {
+            (Some(component), Ok(processor), controller) => {
+                return Ok(AudioPlugin {
+                    component: Some(component),
+                    processor: ProcessorHandle::new(processor),
+                    controller,
+                    path: CreationPath::Component,
+                })
+            }
+            (_, Err(e), _) => e,
+            (None, Ok(_), _) => HostError::no_interface(iids::ICOMPONENT),
+        },
+        Err(e) => e,
+    };
+    let obj = create(iids::IAUDIO_PROCESSOR).map_err(|_| first)?;
+    let (component, processor, controller) = query(&obj);
+    Ok(AudioPlugin {
+        component,
+        processor: ProcessorHandle::new(processor?),
+        controller,
+        path: CreationPath::DirectProcessor,
+    })
+}
+
+// ----- PluginInstance -------------------------------------------------------------
+
+/// Points per parameter in the output list of an instance with a [`ParamCache`]; the
+/// cache takes the last one the list kept.
+pub const OUTPUT_POINTS_PER_PARAM: usize = 16;
+
+/// A created and initialized plugin. Must be dropped before the [`Module`] it came from
+/// (after a [`reload`](PluginInstance::reload), the instance owns its module).
+pub struct PluginInstance {
+    cid: [u8; 16],
+    io_mode: IoMode,
+    limits: HostLimits,
+    component: ComponentHandle,
+    processor: ProcessorHandle,
+    controller: Option<ControllerHandle>,
+    architecture: PluginArchitecture,
+    active: bool,
+    processing: bool,
+    /// `maxSamplesPerBlock` of the last successful activation.
+    max_block: usize,
+    /// The last successful activation's setup, replayed by `reload`.
+    setup: Option<ProcessSetup>,
+    /// The last accepted bus arrangements (inputs, outputs), replayed by `reload`.
+    arrangements: RefCell<Option<(Vec<u64>, Vec<u64>)>>,
+    pub(crate) ab: AbSlots,
+    live: Arc<LiveProcessor>,
+    /// The parameter cache and the output list that feeds it, once enabled.
+    param_sync: Option<(Arc<ParamCache>, Box<ParameterChanges>)>,
+    /// This instance's count in its module's live instances; `None` when adopted
+    /// through `from_raw_parts`.
+    slot: Option<InstanceSlot>,
+    /// The module a reload loaded; declared last so it outlives the objects above.
+    module: Option<Module>,
+}
+
+/// Live instances per class id, shared by a [`Module`] and the instances it created.
+pub(crate) type InstanceCounts = Mutex<HashMap<[u8; 16], usize>>;
+
+/// One counted instance of a class; uncounts it when dropped.
+struct InstanceSlot {
+    counts: Arc<InstanceCounts>,
+    cid: [u8; 16],
+}
+
+impl InstanceSlot {
+    /// Count one more instance of `cid`, unless `max` are already alive.
+    fn claim(
+        counts: &Arc<InstanceCounts>,
+        cid: [u8; 16],
+        max: Option<usize>,
+    ) -> Result<Self, HostError> {
+        let mut map = counts.lock().unwrap_or_else(PoisonError::into_inner);
+        let live = map.entry(cid).or_insert(0);
+        if let Some(max) = max.filter(|&max| *live >= max) {
+            return Err(HostError::CardinalityExceeded { cid, max });
+        }
+        *live += 1;
+        Ok(InstanceSlot {
+            counts: counts.clone(),
+            cid,
+        })
+    }
+}
+
+impl Drop for InstanceSlot {
+    fn drop(&mut self) {
+        let mut map = self.counts.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(live) = map.get_mut(&self.cid) {
+            *live -= 1;
+            if *live == 0 {
+                map.remove(&self.cid);
+            }
+        }
+    }
+}
+
+/// Options fixed when a [`PluginInstance`] is created; from [`Module::plugin`].
+pub struct PluginBuilder<'m> {
+    module: &'m mut Module,
+    cid: [u8; 16],
+    io_mode: IoMode,
+    limits: HostLimits,
+    ignore_cardinality: bool,
+}
+
+impl PluginBuilder<'_> {
+    /// Passed to `setIoMode` before `initialize` (default [`IoMode::Simple`], which
+    /// is not sent).
+    pub fn io_mode(mut self, mode: IoMode) -> Self {
+        self.io_mode = mode;
+        self
+    }
+
+    /// Bounds on what the plugin reports (default [`HostLimits::default`]). Creation
+    /// fails when its bus, channel or parameter counts are over them; the instance
+    /// keeps them for its state streams and renders.
+    pub fn limits(mut self, limits: HostLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Create the instance even if the class's [`Cardinality`](crate::Cardinality)
+    /// allows no more (default `false`: fail with [`HostError::CardinalityExceeded`]).
+    /// The instance is still counted.
+    pub fn ignore_cardinality(mut self, ignore: bool) -> Self {
+        self.ignore_cardinality = ignore;
+        self
+    }
+
+    /// Create class `cid`, initialize it and query its processor (and, for
+    /// single-component plugins, its controller). On failure everything acquired so
+    /// far is terminated and released.
+    pub fn create(self) -> Result<PluginInstance, HostError> {
+        let instance = self.module.create_with(
+            self.cid,
+            self.io_mode,
+            self.limits,
+            self.ignore_cardinality,
+        )?;
+        instance.check_limits()?;
+        Ok(instance)
+    }
+}
+
+impl Module {
+    /// Start creating class `cid` with non-default options.
+    pub fn plugin(&mut self, cid: [u8; 16]) -> PluginBuilder<'_> {
+        PluginBuilder {
+            module: self,
+            cid,
+            io_mode: IoMode::Simple,
+            limits: HostLimits::default(),
+            ignore_cardinality: false,
+        }
+    }
+
+    /// Live instances of class `cid` created through this module, counting those
+    /// that moved to another module in a [`reload`](PluginInstance::reload) only
+    /// until then.
+    pub fn live_instances(&self, cid: [u8; 16]) -> usize {
+        let map = self
+            .live_instances
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        map.get(&cid).copied().unwrap_or(0)
+    }
+
+    /// `self.plugin(cid).create()`.
+    pub fn create_plugin(&mut self, cid: [u8; 16]) -> Result<PluginInstance, HostError> {
+        self.plugin(cid).create()
+    }
+
+    fn create_with(
+        &mut self,
+        cid: [u8; 16],
+        io_mode: IoMode,
+        limits: HostLimits,
+        ignore_cardinality: bool,
+    ) -> Result<PluginInstance, HostError> {
+        let max = if ignore_cardinality {
+            None
+        } else {
+            self.find_class_by_cid(&Tuid(cid))
+                .and_then(|class| class.cardinality.max_instances())
+        };
+        let slot = InstanceSlot::claim(&self.live_instances, cid, max)?;
+        crate::crash_marker::enter(ScanPhase::Instantiate);
+        let component = unsafe {
+            let raw = crate::create_instance_raw(self.factory_mut(), cid, iids::ICOMPONENT.0)
+                .map_err(|e| match self.find_class_by_cid(&Tuid(cid)) {
+                    None if self.classes().all(|c| c.is_ok()) => {
+                        HostError::ClassNotFound(ClassRef::Cid(cid))
+                    }
+                    _ => e,
+                })?;
+            ComponentHandle::new(
+                InterfacePtr::from_raw(raw as *mut IComponent)
+                    .ok_or_else(|| HostError::no_interface(iids::ICOMPONENT))?,
+            )
+        };
+        #[cfg(feature = "leak-audit")]
+        crate::leak_audit::adopt(component.as_ptr() as usize, self.audit_id);
+        if io_mode != IoMode::Simple {
+            component.set_io_mode(io_mode)?;
+        }
+        unsafe { component.initialize(core::ptr::null_mut())? };
+        let processor = match unsafe {
+            component
+                .interface()
+                .query::<IAudioProcessor>(&iids::IAUDIO_PROCESSOR)
+        } {
+            Ok(p) => ProcessorHandle::new(p),
+            Err(e) => {
+                let _ = component.terminate();
+                return Err(e);
+            }
+        };
+        let controller = unsafe {
+            component
+                .interface()
+                .query::<IEditController>(&iids::IEDIT_CONTROLLER)
+        }
+        .ok()
+        .map(|c| ControllerHandle::with_names(c, component.names()));
+        let mut instance =
+            PluginInstance::assemble(cid, io_mode, limits, component, processor, controller);
+        instance.slot = Some(slot);
+        Ok(instance)
+    }
+}
+
+impl PluginInstance {
+    fn assemble(
+        cid: [u8; 16],
+        io_mode: IoMode,
+        limits: HostLimits,
+        component: ComponentHandle,
+        processor: ProcessorHandle,
+        controller: Option<ControllerHandle>,
+    ) -> Self {
+        PluginInstance {
+            cid,
+            io_mode,
+            limits,
+            live: Arc::new(LiveProcessor::new(processor.as_ptr())),
+            architecture: PluginArchitecture::of(&component),
+            component,
+            processor,
+            controller,
+            active: false,
+            processing: false,
+            max_block: 0,
+            setup: None,
+            arrangements: RefCell::new(None),
+            ab: AbSlots::default(),
+            param_sync: None,
+            slot: None,
+            module: None,
+        }
+    }
+
+    /// Adopt an instance created elsewhere, e.g. through another crate's bindings,
+    /// taking over one reference to each object: the caller no longer owes any
+    /// `release`. The instance is checked against the default [`HostLimits`]; from
+    /// then on it is driven and torn down like one from [`Module::create_plugin`],
+    /// so it terminates the component when dropped (also when the check fails). A
+    /// separate controller is only released; terminating it stays with the caller.
+    /// The class id is unknown, so [`reload`](Self::reload) cannot find the class.
+    ///
+    /// # Safety
+    /// The pointers must be null or live interfaces of one plugin instance, its
+    /// component initialized and inactive, and the module they came from must outlive
+    /// the returned instance.
+    pub unsafe fn from_raw_parts(
+        component: *mut IComponent,
+        processor: *mut IAudioProcessor,
+        controller: *mut IEditController,
+    ) -> Result<Self, HostError> {
+        let component = InterfacePtr::from_raw(component).map(ComponentHandle::initialized);
+        let processor = InterfacePtr::from_raw(processor).map(ProcessorHandle::new);
+        let (component, processor) = match (component, processor) {
+            (Some(component), Some(processor)) => (component, processor),
+            (None, _) => return Err(HostError::no_interface(iids::ICOMPONENT)),
+            (_, None) => return Err(HostError::no_interface(iids::IAUDIO_PROCESSOR)),
+        };
+        let controller = InterfacePtr::from_raw(controller)
+            .map(|c| ControllerHandle::with_names(c, component.names()));
+        let instance = Self::assemble(
+            [0; 16],
+            IoMode::Simple,
+            HostLimits::default(),
+            component,
+            processor,
+            controller,
+        );
+        instance.check_limits()?;
+        Ok(instance)
+    }
+}
+
+impl PluginInstance {
+    pub fn component(&self) -> &ComponentHandle {
+        &self.component
+    }
+
+    pub fn processor(&self) -> &ProcessorHandle {
+        &self.processor
+    }
+
+    /// [`ProcessorHandle::set_timing_collector`]; the collector stays attached across
+    /// [`reload`](Self::reload).
+    pub fn set_timing_collector(&mut self, collector: Option<Arc<TimingCollector>>) {
+        self.processor.set_timing_collector(collector);
+    }
+
+    pub fn controller(&self) -> Option<&ControllerHandle> {
+        self.controller.as_ref()
+    }
+
+    /// What the component answered `getControllerClassId` with when the instance was
+    /// created (or last reloaded).
+    pub fn architecture(&self) -> PluginArchitecture {
+        self.architecture
+    }
+
+    /// Build the [`ParamCache`] from the controller (empty without one) and keep it
+    /// current from every later process call; a second call returns the same cache.
+    /// Attach it to a handler with [`ComponentHandler::track_params`] to follow
+    /// `performEdit` too. Each block the plugin gets an output list with room for
+    /// [`OUTPUT_POINTS_PER_PARAM`] points per parameter.
+    ///
+    /// [`ComponentHandler::track_params`]: crate::ComponentHandler::track_params
+    pub fn enable_param_cache(&mut self) -> Result<Arc<ParamCache>, HostError> {
+        if let Some((cache, _)) = &self.param_sync {
+            return Ok(cache.clone());
+        }
+        let cache = Arc::new(match &self.controller {
+            Some(controller) => ParamCache::from_controller(controller)?,
+            None => ParamCache::new([]),
+        });
+        let out = ParameterChanges::with_capacity(cache.len(), OUTPUT_POINTS_PER_PARAM);
+        self.param_sync = Some((cache.clone(), out));
+        Ok(cache)
+    }
+
+    pub fn param_cache(&self) -> Option<&Arc<ParamCache>> {
+        self.param_sync.as_ref().map(|(cache, _)| cache)
+    }
+
+    pub fn is_processing(&self) -> bool {
+        self.processing
+    }
+
+    /// The mode the instance was created with.
+    pub fn io_mode(&self) -> IoMode {
+        self.io_mode
+    }
+
+    /// The limits the instance was created with.
+    pub fn limits(&self) -> &HostLimits {
+        &self.limits
+    }
+
+    /// Bus and channel counts, then the parameter count, against the limits. Channel
+    /// counts only fail above the maximum here: a zero-channel bus allocates nothing.
+    fn check_limits(&self) -> Result<(), HostError> {
+        let layout = self
+            .component
+            .checked_bus_layout(&self.limits, BusCheck::Lenient)?;
+        let too_wide = layout.iter().find(|(_, _, bus)| {
+            bus.clamped_from
+                .is_some_and(|n| n > self.limits.max_channels_per_bus)
+        });
+        if let Some((media_type, direction, bus)) = too_wide {
+            return Err(HostError::SuspiciousBusInfo {
+                bus: BusId {
+                    media_type,
+                    direction,
+                    index: bus.index,
+                },
+                reported: bus.clamped_from.unwrap_or(bus.channel_count),
+            });
+        }
+        if let Some(controller) = &self.controller {
+            let count = controller.parameter_count().max(0) as usize;
+            self.limits
+                .check(Limit::Parameters, count, LimitOrigin::Plugin)?;
+        }
+        Ok(())
+    }
+
+    /// The processor pointer for a realtime callback, kept current across
+    /// [`reload`](Self::reload).
+    pub fn live(&self) -> Arc<LiveProcessor> {
+        self.live.clone()
+    }
+
+    /// `setBusArrangements`, remembered on success so a reload can repeat it.
+    pub fn set_bus_arrangements(&self, inputs: &[u64], outputs: &[u64]) -> Result<(), HostError> {
+        self.processor.set_bus_arrangements(inputs, outputs)?;
+        *self.arrangements.borrow_mut() = Some((inputs.to_vec(), outputs.to_vec()));
+        Ok(())
+    }
+
+    /// The component's `getState` bytes, at most
+    /// [`max_state_bytes`](HostLimits::max_state_bytes) of them.
+    pub fn component_state(&self) -> Result<Vec<u8>, HostError> {
+        read_state(self.limits.max_state_bytes, |s| self.component.get_state(s))
+    }
+
+    /// The controller's `getState` bytes, held to the same limit; `None` without a
+    /// controller on the component.
+    pub fn controller_state(&self) -> Result<Option<Vec<u8>>, HostError> {
+        self.controller
+            .as_ref()
+            .map(|c| read_state(self.limits.max_state_bytes, |s| c.get_state(s)))
+            .transpose()
+    }
+
+    /// `setState` on the component, then `setComponentState` on the controller (if
+    /// any) so both sides agree.
+    pub fn set_component_state(&self, state: &[u8]) -> Result<(), HostError> {
+        self.component
+            .set_state(&mut MemoryStream::from_bytes(state.to_vec()))?;
+        if let Some(controller) = &self.controller {
+            controller.set_component_state(&mut MemoryStream::from_bytes(state.to_vec()))?;
+        }
+        Ok(())
+    }
+
+    /// Activate the default buses (see [`buses`](crate::buses)), then setupProcessing →
+    /// setActive(true) → setProcessing(true); rolls back on failure. A setup that
+    /// [`ProcessSetupBuilder`] refuses fails with [`HostError::InvalidSetup`] before
+    /// anything is called.
+    pub fn activate(&mut self, setup: &ProcessSetup) -> Result<(), HostError> {
+        let setup = &ProcessSetupBuilder::from_setup(setup)?.build()?;
+        self.deactivate();
+        self.component.activate_default_buses()?;
+        self.processor.setup_processing(setup)?;
+        self.component.set_active(true)?;
+        self.active = true;
+        if let Err(e) = self.processor.set_processing(true) {
+            self.deactivate();
+            return Err(e);
+        }
+        self.processing = true;
+        self.max_block = setup.max_samples_per_block.max(0) as usize;
+        self.setup = Some(*setup);
+        Ok(())
+    }
+
+    /// The mode of the last setup, if the instance was ever activated.
+    pub fn process_mode(&self) -> Option<ProcessMode> {
+        self.setup
+            .and_then(|setup| ProcessMode::from_raw(setup.process_mode))
+    }
+
+    /// Switch the active instance to `mode`, e.g. offline for a bounce and back, with
+    /// the sample rate and block size kept: setProcessing(false) → setActive(false) →
+    /// setupProcessing → setActive(true) → setProcessing(true). When the plugin
+    /// refuses the new setup, the previous one is set up again and the instance
+    /// resumes in its old mode before the refusal is returned.
+    pub fn set_process_mode(&mut self, mode: ProcessMode) -> Result<(), HostError> {
+        let (true, Some(old)) = (self.active, self.setup) else {
+            return Err(HostError::CallOrder {
+                call: "activate",
+                before: "set_process_mode",
+            });
+        };
+        let setup = ProcessSetupBuilder::from_setup(&old)?.mode(mode).build()?;
+        self.deactivate();
+        let switched = self.processor.setup_processing(&setup);
+        if switched.is_err() {
+            self.processor.setup_processing(&old)?;
+        } else {
+            self.setup = Some(setup);
+        }
+        self.component.set_active(true)?;
+        self.active = true;
+        self.processor.set_processing(true)?;
+        self.processing = true;
+        switched
+    }
+
+    /// Undo [`activate`](Self::activate); errors from the plugin are ignored.
+    pub fn deactivate(&mut self) {
+        if core::mem::take(&mut self.processing) {
+            let _ = self.processor.set_processing(false);
+        }
+        if core::mem::take(&mut self.active) {
+            let _ = self.component.set_active(false);
+        }
+    }
+
+    /// # Safety
+    /// Every buffer pointer in `data` must be valid for `num_samples` frames.
+    pub unsafe fn process_32f(
+        &mut self,
+        data: &mut ProcessData32,
+    ) -> Result<ProcessStats, HostError> {
+        self.check_block(data.num_samples)?;
+        self.process_synced(
+            data,
+            |d| &mut d.output_parameter_changes,
+            |p, d| p.process_32f(d),
+        )
+    }
+
+    /// # Safety
+    /// Every buffer pointer in `data` must be valid for `num_samples` frames.
+    pub unsafe fn process_64f(
+        &mut self,
+        data: &mut ProcessData64,
+    ) -> Result<ProcessStats, HostError> {
+        self.check_block(data.num_samples)?;
+        self.process_synced(
+            data,
+            |d| &mut d.output_parameter_changes,
+            |p, d| p.process_64f(d),
+        )
+    }
+
+    /// Run `process` on `data`. With the parameter cache on, a new cache block begins
+    /// and the output parameter changes go into the cache: the caller's list if
+    /// `data` has one, otherwise the instance's own, lent for the call.
+    pub(crate) unsafe fn process_synced<D>(
+        &mut self,
+        data: &mut D,
+        out_changes: fn(&mut D) -> &mut *mut c_void,
+        process: impl FnOnce(&ProcessorHandle, &mut D) -> Result<ProcessStats, HostError>,
+    ) -> Result<ProcessStats, HostError> {
+        let Some((cache, own)) = &mut self.param_sync else {
+            return process(&self.processor, data);
+        };
+        cache.begin_block();
+        let lent = out_changes(data).is_null();
+        if lent {
+            own.clear();
+            *out_changes(data) = own.as_iparameter_changes().cast();
+        }
+        let result = process(&self.processor, data);
+        if result.is_ok() {
+            cache.apply_output_changes((*out_changes(data)).cast());
+        }
+        if lent {
+            *out_changes(data) = core::ptr::null_mut();
+        }
+        result
+    }
+
+    /// Replace the plugin with the same class from `new_module`, e.g. a rebuilt binary,
+    /// without stopping a realtime callback that goes through [`live`](Self::live).
+    ///
+    /// The new instance is created, given this one's [`ReloadState`] and bus
+    /// arrangements, and activated with the last setup if this one is active; the
+    /// processor's [`timing`](ProcessorHandle::timing) flag and collector carry over.
+    /// Only then is the callback muted for the pointer swap; the old instance is torn
+    /// down afterwards, when the callback can no longer reach it. On failure this
+    /// instance is left untouched and running. Handles cloned from the old controller
+    /// or processor are stale afterwards.
+    pub fn reload(&mut self, mut new_module: Module) -> Result<(), HostError> {
+        let state = ReloadState::capture(
+            Some(&self.component),
+            self.controller.as_ref(),
+            self.limits.max_state_bytes,
+        )?;
+        let mut fresh = new_module
+            .plugin(self.cid)
+            .io_mode(self.io_mode)
+            .limits(self.limits)
+            .create()?;
+        state.restore(Some(&fresh.component), fresh.controller.as_ref())?;
+        if let Some((inputs, outputs)) = self.arrangements.borrow().as_ref() {
+            fresh.set_bus_arrangements(inputs, outputs)?;
+        }
+        if let (true, Some(setup)) = (self.active, self.setup) {
+            fresh.activate(&setup)?;
+        }
+
+        fresh
+            .processor
+            .set_timing_collector(self.processor.collector.clone());
+        fresh.processor.set_timing(self.processor.timing());
+        self.live.replace(fresh.processor.as_ptr());
+        core::mem::swap(&mut self.component, &mut fresh.component);
+        core::mem::swap(&mut self.processor, &mut fresh.processor);
+        core::mem::swap(&mut self.controller, &mut fresh.controller);
+        core::mem::swap(&mut self.architecture, &mut fresh.architecture);
+        core::mem::swap(&mut self.active, &mut fresh.active);
+        core::mem::swap(&mut self.processing, &mut fresh.processing);
+        core::mem::swap(&mut self.max_block, &mut fresh.max_block);
+        core::mem::swap(&mut self.slot, &mut fresh.slot);
+        // `fresh` now holds the old objects; they go before the module they came from.
+        drop(fresh);
+        self.module = Some(new_module);
+        Ok(())
+    }
+
+    pub(crate) fn check_block(&self, num_samples: i32) -> Result<(), HostError> {
+        let got = num_samples.max(0) as usize;
+        if got > self.max_block {
+            return Err(HostError::BlockSizeExceeded {
+                got,
+                max: self.max_block,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// `getState` into a stream held to `limit` bytes.
+fn read_state(
+    limit: usize,
+    get_state: impl FnOnce(&mut MemoryStream) -> Result<(), HostError>,
+) -> Result<Vec<u8>, HostError> {
+    let mut stream = MemoryStream::with_limit(limit, LimitOrigin::Plugin);
+    let result = get_state(&mut stream);
+    stream.check_limit()?;
+    result?;
+    Ok(stream.into_bytes())
+}
+
+/// What a reload carries from the running instance to its replacement: the
+/// component's state and the controller's own. [`PluginInstance::reload`] uses it;
+/// hosts that drive raw interfaces hand state over the same way with it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReloadState {
+    /// `None` without a component.
+    pub component: Option<Vec<u8>>,
+    /// `None` without a controller.
+    pub controller: Option<Vec<u8>>,
+}
+
+impl ReloadState {
+    /// `getState` on the running instance's component and controller, each held to
+    /// `limit` bytes.
+    pub fn capture(
+        component: Option<&ComponentHandle>,
+        controller: Option<&ControllerHandle>,
+        limit: usize,
+    ) -> Result<Self, HostError> {
+        Ok(Self {
+            component: component
+                .map(|c| read_state(limit, |s| c.get_state(s)))
+                .transpose()?,
+            controller: controller
+                .map(|c| read_state(limit, |s| c.get_state(s)))
+                .transpose()?,
+        })
+    }
+
+    /// Hand the state to the new instance: `setState` on the component and
+    /// `setComponentState` on the controller with the component's bytes, then the
+    /// controller's own `setState`. Whatever either side lacks is skipped.
+    pub fn restore(
+        &self,
+        component: Option<&ComponentHandle>,
+        controller: Option<&ControllerHandle>,
+    ) -> Result<(), HostError> {
+        if let Some(state) = &self.component {
+            if let Some(component) = component {
+                component.set_state(&mut MemoryStream::from_bytes(state.clone()))?;
+            }
+            if let Some(controller) = controller {
+                controller.set_component_state(&mut MemoryStream::from_bytes(state.clone()))?;
+            }
+        }
+        if let (Some(state), Some(controller)) = (&self.controller, controller) {
+            controller.set_state(&mut MemoryStream::from_bytes(state.clone()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PluginInstance {
+    fn drop(&mut self) {
+        self.deactivate();
+        let _ = self.component.terminate();
+    }
+}
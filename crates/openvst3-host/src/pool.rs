@@ -0,0 +1,189 @@
+//! Event and parameter lists for the process call, drawn from a fixed pool.
+//!
+//! A [`BlockIoPool`] allocates all its [`BlockIo`] sets up front. The audio thread
+//! [`take`](BlockIoPool::take)s one per block, fills it, hands it to the process
+//! call and [`recycle`](BlockIoPool::recycle)s it, which clears it for the next
+//! block; nothing is allocated or freed on the way. A burst larger than a set's
+//! capacity is dropped, not grown into: the refused events and points are added to
+//! the pool's [`BlockIoCounters`], which another thread can read.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::{EventList, HostLimits, ParameterChanges};
+
+/// One block's input lists.
+pub struct BlockIo {
+    events: Box<EventList>,
+    params: Box<ParameterChanges>,
+}
+
+impl BlockIo {
+    pub fn events(&mut self) -> &mut EventList {
+        &mut self.events
+    }
+
+    pub fn params(&mut self) -> &mut ParameterChanges {
+        &mut self.params
+    }
+
+    /// Both lists, in the form [`BoundProcessData::prepare`] and
+    /// [`PluginInstance::process_bound`] take them.
+    ///
+    /// [`BoundProcessData::prepare`]: crate::BoundProcessData::prepare
+    /// [`PluginInstance::process_bound`]: crate::PluginInstance::process_bound
+    pub fn lists(&mut self) -> (&mut EventList, &mut ParameterChanges) {
+        (&mut self.events, &mut self.params)
+    }
+}
+
+/// What a [`BlockIoPool`] had to drop, shared with other threads.
+#[derive(Debug, Default)]
+pub struct BlockIoCounters {
+    dropped_events: AtomicU64,
+    dropped_points: AtomicU64,
+    /// `take` calls that found every set in use.
+    exhausted: AtomicU64,
+}
+
+impl BlockIoCounters {
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped_events.load(Ordering::Relaxed)
+    }
+
+    pub fn dropped_points(&self) -> u64 {
+        self.dropped_points.load(Ordering::Relaxed)
+    }
+
+    pub fn exhausted(&self) -> u64 {
+        self.exhausted.load(Ordering::Relaxed)
+    }
+}
+
+/// See the module docs.
+pub struct BlockIoPool {
+    /// Sets not handed out; its capacity is the pool size, so it never reallocates.
+    free: Vec<BlockIo>,
+    counters: Arc<BlockIoCounters>,
+}
+
+impl BlockIoPool {
+    /// `sets` sets of an event list holding `events` events and parameter changes
+    /// for `params` parameters of `points` points each.
+    pub fn new(sets: usize, events: usize, params: usize, points: usize) -> Self {
+        let mut free = Vec::with_capacity(sets);
+        free.extend((0..sets).map(|_| BlockIo {
+            events: EventList::with_capacity(events),
+            params: ParameterChanges::with_capacity(params, points),
+        }));
+        Self {
+            free,
+            counters: Arc::default(),
+        }
+    }
+
+    /// Sets sized by `limits`: [`max_events_per_block`] events, and as many points for
+    /// each of up to `params` parameters (at most [`max_parameters`]). Allocates what
+    /// the limits allow, so [`HostLimits::unlimited`] is no use here.
+    ///
+    /// [`max_events_per_block`]: HostLimits::max_events_per_block
+    /// [`max_parameters`]: HostLimits::max_parameters
+    pub fn with_limits(sets: usize, params: usize, limits: &HostLimits) -> Self {
+        let events = limits.max_events_per_block;
+        Self::new(sets, events, params.min(limits.max_parameters), events)
+    }
+
+    /// A cleared set, or `None` (counted as [`exhausted`](BlockIoCounters::exhausted))
+    /// while every set is handed out.
+    pub fn take(&mut self) -> Option<BlockIo> {
+        let io = self.free.pop();
+        if io.is_none() {
+            self.counters.exhausted.fetch_add(1, Ordering::Relaxed);
+        }
+        io
+    }
+
+    /// Count what `io` dropped, clear it and put it back. A set beyond the pool's size
+    /// (one it never handed out) is freed instead.
+    pub fn recycle(&mut self, mut io: BlockIo) {
+        let (events, params) = io.lists();
+        self.counters
+            .dropped_events
+            .fetch_add(events.dropped() as u64, Ordering::Relaxed);
+        self.counters
+            .dropped_points
+            .fetch_add(params.dropped() as u64, Ordering::Relaxed);
+        events.clear();
+        params.clear();
+        if self.free.len() < self.free.capacity() {
+            self.free.push(io);
+        }
+    }
+
+    /// Take a set, run `f` on it and recycle it; `None` while every set is in use.
+    pub fn with_block<R>(&mut self, f: impl FnOnce(&mut BlockIo) -> R) -> Option<R> {
+        let mut io = self.take()?;
+        let result = f(&mut io);
+        self.recycle(io);
+        Some(result)
+    }
+
+    /// Sets not handed out.
+    pub fn available(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn counters(&self) -> Arc<BlockIoCounters> {
+        self.counters.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_io_pool_recycles_sets_and_counts_overflow() {
+        let mut pool = BlockIoPool::new(2, 2, 1, 2);
+        let counters = pool.counters();
+        let mut a = pool.take().unwrap();
+        let mut b = pool.take().unwrap();
+        assert!(pool.take().is_none());
+        assert_eq!(counters.exhausted(), 1);
+
+        for key in 0..5 {
+            a.events()
+                .push(openvst3_abi::Event::note_on(0, 0, key, 1.0));
+        }
+        assert_eq!(a.events().dropped(), 3);
+        assert!(b.params().add_point(7, 0, 0.5));
+        assert!(b.params().add_point(7, 8, 0.75));
+        assert!(!b.params().add_point(7, 16, 1.0));
+        assert!(!b.params().add_point(9, 0, 1.0));
+        pool.recycle(a);
+        pool.recycle(b);
+        assert_eq!(
+            (counters.dropped_events(), counters.dropped_points()),
+            (3, 2)
+        );
+
+        // Recycled sets come back empty, and with nothing new dropped the totals hold.
+        assert_eq!(pool.available(), 2);
+        let len = pool.with_block(|io| {
+            assert!(io.params().is_empty());
+            io.events().len()
+        });
+        assert_eq!(len, Some(0));
+        assert_eq!(
+            (counters.dropped_events(), counters.dropped_points()),
+            (3, 2)
+        );
+
+        let mut limited = BlockIoPool::with_limits(1, 4, &HostLimits::default());
+        let mut io = limited.take().unwrap();
+        assert_eq!(
+            io.events().capacity(),
+            HostLimits::default().max_events_per_block
+        );
+    }
+}
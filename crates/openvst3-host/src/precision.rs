@@ -0,0 +1,213 @@
+//! Sample precision for plugins processed one after another.
+//!
+//! Running every plugin in a series at 32 bits throws away the precision of the ones
+//! that take 64; running them all at 64 breaks the ones that do not. A
+//! [`PrecisionPlan`] picks each slot's sample size from its [`SlotPrecision`] and what
+//! the plugin accepts, and counts the places where neighbours disagree. There the
+//! block moves on through [`pass_block`], which converts straight into the next
+//! slot's [`BoundProcessData`] buffers, allocated when it was built, with the
+//! [`dsp`](crate::dsp) kernels; where they agree it is a plain copy.
+//!
+//! The host has no chain type yet, so an embedder running instances in series
+//! activates slot `i` with [`ProcessSetupBuilder::precision`] set to
+//! [`plan.slots()[i]`](PrecisionPlan::slots) and binds it with the matching sample
+//! type.
+//!
+//! [`ProcessSetupBuilder::precision`]: crate::ProcessSetupBuilder::precision
+
+use std::fmt;
+
+use crate::plugin::{ProcessorHandle, SymbolicSampleSize};
+use crate::process_data::{BoundProcessData, BusSample};
+
+/// What a slot asks for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SlotPrecision {
+    /// The best the plugin supports.
+    #[default]
+    Auto,
+    /// This size; 64 bits falls back to 32 where the plugin refuses it, as
+    /// [`ProcessSetupBuilder`](crate::ProcessSetupBuilder) does.
+    Fixed(SymbolicSampleSize),
+}
+
+impl SlotPrecision {
+    /// The size used for a plugin that does (`float64`) or does not take 64-bit samples.
+    pub fn resolve(self, float64: bool) -> SymbolicSampleSize {
+        match (self, float64) {
+            (SlotPrecision::Auto, true)
+            | (SlotPrecision::Fixed(SymbolicSampleSize::Sample64), true) => {
+                SymbolicSampleSize::Sample64
+            }
+            _ => SymbolicSampleSize::Sample32,
+        }
+    }
+}
+
+/// The sample size of every slot, in processing order. Displays as e.g.
+/// `64-bit -> 32-bit -> 32-bit (1 conversion)`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PrecisionPlan {
+    slots: Vec<SymbolicSampleSize>,
+}
+
+impl PrecisionPlan {
+    /// Resolve each slot's request against whether its plugin takes 64-bit samples.
+    pub fn resolve(slots: impl IntoIterator<Item = (SlotPrecision, bool)>) -> Self {
+        Self {
+            slots: slots
+                .into_iter()
+                .map(|(precision, float64)| precision.resolve(float64))
+                .collect(),
+        }
+    }
+
+    /// [`resolve`](Self::resolve), asking each processor `canProcessSampleSize`.
+    pub fn for_processors<'a>(
+        slots: impl IntoIterator<Item = (SlotPrecision, &'a ProcessorHandle)>,
+    ) -> Self {
+        Self::resolve(slots.into_iter().map(|(precision, processor)| {
+            (
+                precision,
+                processor.can_process_sample_size(SymbolicSampleSize::Sample64),
+            )
+        }))
+    }
+
+    pub fn slots(&self) -> &[SymbolicSampleSize] {
+        &self.slots
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Whether the block is converted on its way into `slot`.
+    pub fn converts_before(&self, slot: usize) -> bool {
+        slot > 0 && slot < self.slots.len() && self.slots[slot - 1] != self.slots[slot]
+    }
+
+    /// Conversion stages between slots.
+    pub fn conversions(&self) -> usize {
+        self.slots.windows(2).filter(|w| w[0] != w[1]).count()
+    }
+}
+
+impl fmt::Display for PrecisionPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.slots.is_empty() {
+            return f.write_str("no slots");
+        }
+        for (i, size) in self.slots.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" -> ")?;
+            }
+            write!(f, "{}-bit", size.bits())?;
+        }
+        let n = self.conversions();
+        write!(f, " ({n} conversion{})", if n == 1 { "" } else { "s" })
+    }
+}
+
+/// Copy the first `frames` of `from`'s main outputs into `to`'s main inputs,
+/// converting the samples if the two bindings differ in type. 64-bit samples beyond
+/// the 32-bit range clamp to `±f32::MAX`, as in
+/// [`convert_f64_to_f32`](crate::dsp::convert_f64_to_f32). Input channels `from` has
+/// no output for, and frames past the end of a shorter output, are zeroed. Does not
+/// allocate.
+pub fn pass_block<S: BusSample, D: BusSample>(
+    from: &BoundProcessData<S>,
+    to: &mut BoundProcessData<D>,
+    frames: usize,
+) {
+    let sources = from.outputs();
+    for (c, dest) in to.inputs_mut().enumerate() {
+        let frames = frames.min(dest.len());
+        let dest = &mut dest[..frames];
+        let copied = sources.get(c).map_or(0, |source| {
+            let n = frames.min(source.len());
+            S::convert_slice(&source[..n], &mut dest[..n]);
+            n
+        });
+        dest[copied..].fill(D::default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn precision_plan_converts_only_where_neighbours_disagree() {
+        use SymbolicSampleSize::{Sample32, Sample64};
+        let plan = PrecisionPlan::resolve([
+            (SlotPrecision::Auto, true),
+            (SlotPrecision::Auto, false),
+            (SlotPrecision::Fixed(Sample32), true),
+            (SlotPrecision::Fixed(Sample64), false),
+            (SlotPrecision::Fixed(Sample64), true),
+        ]);
+        assert_eq!(
+            plan.slots(),
+            [Sample64, Sample32, Sample32, Sample32, Sample64]
+        );
+        assert_eq!(plan.conversions(), 2);
+        assert_eq!(
+            (0..6).map(|i| plan.converts_before(i)).collect::<Vec<_>>(),
+            [false, true, false, false, true, false]
+        );
+        assert_eq!(
+            plan.to_string(),
+            "64-bit -> 32-bit -> 32-bit -> 32-bit -> 64-bit (2 conversions)"
+        );
+
+        let all_32 = PrecisionPlan::resolve([(SlotPrecision::Fixed(Sample32), true); 4]);
+        assert_eq!(all_32.conversions(), 0);
+        assert_eq!(
+            all_32.to_string(),
+            "32-bit -> 32-bit -> 32-bit -> 32-bit (0 conversions)"
+        );
+        assert_eq!(PrecisionPlan::default().to_string(), "no slots");
+    }
+
+    #[test]
+    fn pass_block_copies_or_converts_between_slots() {
+        let mut from = BoundProcessData::<f64>::new(1, 2, 8);
+        for (c, chan) in from.outputs_mut().enumerate() {
+            chan.fill(0.1 * (c as f64 + 1.0));
+        }
+        let mut narrow = BoundProcessData::<f32>::new(3, 3, 8);
+        narrow.inputs_mut().for_each(|chan| chan.fill(-1.0));
+        pass_block(&from, &mut narrow, 5);
+        let inputs = narrow.inputs();
+        assert!(inputs[0][..5].iter().all(|&s| s == 0.1f32));
+        assert!(inputs[1][..5].iter().all(|&s| s == 0.2f32));
+        // No output to take from: silence, not what was there.
+        assert!(inputs[2][..5].iter().all(|&s| s == 0.0));
+        assert!(inputs
+            .iter()
+            .all(|chan| chan[5..].iter().all(|&s| s == -1.0)));
+
+        let mut same = BoundProcessData::<f64>::new(2, 2, 8);
+        pass_block(&from, &mut same, 8);
+        assert_eq!(same.inputs(), from.outputs());
+
+        // `from` bound for fewer frames than `to`: what it lacks is silence.
+        let mut long = BoundProcessData::<f32>::new(2, 2, 16);
+        long.inputs_mut().for_each(|chan| chan.fill(-1.0));
+        pass_block(&from, &mut long, 12);
+        let inputs = long.inputs();
+        assert!(inputs[0][..8].iter().all(|&s| s == 0.1f32));
+        assert!(inputs[1][..8].iter().all(|&s| s == 0.2f32));
+        assert!(inputs
+            .iter()
+            .all(|chan| chan[8..12].iter().all(|&s| s == 0.0)));
+        assert!(inputs
+            .iter()
+            .all(|chan| chan[12..].iter().all(|&s| s == -1.0)));
+    }
+}
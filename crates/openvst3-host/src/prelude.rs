@@ -0,0 +1,25 @@
+//! The common host API in one import: `use openvst3_host::prelude::*;`.
+//!
+//! Loading modules and creating plugins, the handles onto their interfaces, bound
+//! process buffers, event and parameter lists, the transport, [`HostError`], and the
+//! ABI types and constants a host meets along the way: interface IDs (with
+//! [`iids::REGISTRY`] naming them), result codes, bus, process and class constants and
+//! the flag types. Code importing this needs no direct `openvst3-abi` dependency.
+//! Everything more specialized stays under `openvst3_host::`.
+
+pub use crate::{
+    create_audio_plugin, AudioPlugin, BoundProcessData, BundlePath, BusLayout, BusSample,
+    ClassFilter, ClassInfo, ComponentHandle, ControllerHandle, EventList, HostError, IoMode,
+    MemoryStream, Module, ParamInfo, ParameterChanges, PluginBuilder, PluginInstance, ProcessStats,
+    ProcessorHandle, SampleClock, Transport,
+};
+
+pub use openvst3_abi::{
+    classinfo_consts, event_consts, iids, io_mode_consts, param_consts, platform_types,
+    process_consts, process_context_consts, speaker_arr, tresult, BusInfo, ClassFlags, Event,
+    FUnknown, FactoryFlags, IAudioProcessor, IComponent, IEditController, ParamID, ParamValue,
+    ParameterFlags, ProcessContext, ProcessSetup, Tuid, ViewRect, BUS_DIR_INPUT, BUS_DIR_OUTPUT,
+    BUS_FLAG_DEFAULT_ACTIVE, BUS_FLAG_IS_CONTROL_VOLTAGE, BUS_TYPE_AUX, BUS_TYPE_MAIN,
+    K_INTERNAL_ERR, K_INVALID_ARG, K_NOT_IMPLEMENTED, K_NO_INTERFACE, K_RESULT_FALSE, K_RESULT_OK,
+    MEDIA_TYPE_AUDIO, MEDIA_TYPE_EVENT,
+};
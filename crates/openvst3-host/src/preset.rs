@@ -0,0 +1,374 @@
+//! `.vstpreset` files: a 48-byte header, the chunk data, and a chunk list.
+//!
+//! ```text
+//! header  'VST3' | version i32 | class id (32 ASCII hex) | list offset i64
+//! list    'List' | count i32 | count × (id [4] | offset i64 | size i64)
+//! ```
+//!
+//! All integers are little-endian. `Comp` holds the component state, `Cont` the
+//! controller state; other chunks (e.g. `Info` metadata) are kept but not interpreted.
+//!
+//! [`preset_locations`] resolves the folders the VST3 convention puts a plugin's
+//! presets in, and [`list_presets`] finds the files there.
+
+use std::path::{Path, PathBuf};
+
+use crate::{
+    fmt_cid_hex, parse_hex_16, HostError, HostLimits, Limit, LimitOrigin, MemoryStream,
+    PluginInstance,
+};
+
+const HEADER_LEN: usize = 4 + 4 + 32 + 8;
+const VERSION: i32 = 1;
+const COMPONENT_CHUNK: [u8; 4] = *b"Comp";
+const CONTROLLER_CHUNK: [u8; 4] = *b"Cont";
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VstPreset {
+    pub class_id: [u8; 16],
+    /// Chunks in file order.
+    pub chunks: Vec<([u8; 4], Vec<u8>)>,
+}
+
+fn invalid(msg: impl Into<String>) -> HostError {
+    HostError::InvalidPreset(msg.into())
+}
+
+fn read_at<const N: usize>(bytes: &[u8], at: usize) -> Result<[u8; N], HostError> {
+    at.checked_add(N)
+        .and_then(|end| bytes.get(at..end))
+        .map(|b| b.try_into().unwrap())
+        .ok_or_else(|| invalid(format!("truncated at byte {at}")))
+}
+
+fn offset(v: i64) -> Result<usize, HostError> {
+    usize::try_from(v).map_err(|_| invalid(format!("negative offset or size {v}")))
+}
+
+impl VstPreset {
+    pub fn new(class_id: [u8; 16]) -> Self {
+        Self {
+            class_id,
+            chunks: Vec::new(),
+        }
+    }
+
+    /// Read a `.vstpreset`. Offsets and sizes are checked against the file, and the
+    /// chunks together may not be larger than it, so overlapping chunks cannot make
+    /// a small file expand into a huge preset. Chunks are held to the default
+    /// [`HostLimits`].
+    pub fn parse(bytes: &[u8]) -> Result<Self, HostError> {
+        Self::parse_with_limits(bytes, &HostLimits::default())
+    }
+
+    /// [`parse`](Self::parse) with no chunk larger than `limits.max_state_bytes`.
+    pub fn parse_with_limits(bytes: &[u8], limits: &HostLimits) -> Result<Self, HostError> {
+        if read_at::<4>(bytes, 0)? != *b"VST3" {
+            return Err(invalid("missing 'VST3' magic"));
+        }
+        let id_hex = read_at::<32>(bytes, 8)?;
+        let class_id = std::str::from_utf8(&id_hex)
+            .ok()
+            .and_then(|s| parse_hex_16(s).ok())
+            .ok_or_else(|| invalid("class id is not 32 hex characters"))?;
+        let list = offset(i64::from_le_bytes(read_at(bytes, 40)?))?;
+        if read_at::<4>(bytes, list)? != *b"List" {
+            return Err(invalid(format!("no chunk list at byte {list}")));
+        }
+        // Saturating: an offset that would wrap is past the end, which `read_at` reports.
+        let count = i32::from_le_bytes(read_at(bytes, list.saturating_add(4))?);
+        let mut chunks = Vec::new();
+        let mut total = 0usize;
+        for i in 0..count.max(0) as usize {
+            let entry = list.saturating_add(8).saturating_add(i.saturating_mul(20));
+            let id = read_at::<4>(bytes, entry)?;
+            let name = || String::from_utf8_lossy(&id).into_owned();
+            let start = offset(i64::from_le_bytes(read_at(bytes, entry.saturating_add(4))?))?;
+            let size = offset(i64::from_le_bytes(read_at(
+                bytes,
+                entry.saturating_add(12),
+            )?))?;
+            let data = start
+                .checked_add(size)
+                .and_then(|end| bytes.get(start..end))
+                .ok_or_else(|| invalid(format!("chunk '{}' exceeds the file", name())))?;
+            limits.check(Limit::StateBytes, size, LimitOrigin::External)?;
+            total = total.saturating_add(size);
+            if total > bytes.len() {
+                return Err(invalid(format!(
+                    "chunk '{}' overlaps earlier chunks",
+                    name()
+                )));
+            }
+            chunks.push((id, data.to_vec()));
+        }
+        Ok(Self { class_id, chunks })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN);
+        out.extend_from_slice(b"VST3");
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(fmt_cid_hex(&self.class_id).as_bytes());
+        out.extend_from_slice(&[0; 8]); // list offset, patched below
+        let mut entries = Vec::with_capacity(self.chunks.len());
+        for (id, data) in &self.chunks {
+            entries.push((*id, out.len() as i64, data.len() as i64));
+            out.extend_from_slice(data);
+        }
+        let list = out.len() as i64;
+        out[40..48].copy_from_slice(&list.to_le_bytes());
+        out.extend_from_slice(b"List");
+        out.extend_from_slice(&(entries.len() as i32).to_le_bytes());
+        for (id, start, size) in entries {
+            out.extend_from_slice(&id);
+            out.extend_from_slice(&start.to_le_bytes());
+            out.extend_from_slice(&size.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn chunk(&self, id: &[u8; 4]) -> Option<&[u8]> {
+        self.chunks
+            .iter()
+            .find(|(c, _)| c == id)
+            .map(|(_, d)| d.as_slice())
+    }
+
+    /// Replace (or append) chunk `id`.
+    pub fn set_chunk(&mut self, id: [u8; 4], data: Vec<u8>) {
+        match self.chunks.iter_mut().find(|(c, _)| *c == id) {
+            Some((_, d)) => *d = data,
+            None => self.chunks.push((id, data)),
+        }
+    }
+
+    pub fn component_state(&self) -> Option<&[u8]> {
+        self.chunk(&COMPONENT_CHUNK)
+    }
+
+    pub fn controller_state(&self) -> Option<&[u8]> {
+        self.chunk(&CONTROLLER_CHUNK)
+    }
+}
+
+impl PluginInstance {
+    /// Apply `preset`: the component state (mirrored to the controller), then the
+    /// controller state.
+    pub fn load_preset(&self, preset: &VstPreset) -> Result<(), HostError> {
+        if let Some(state) = preset.component_state() {
+            self.set_component_state(state)?;
+        }
+        if let (Some(state), Some(controller)) = (preset.controller_state(), self.controller()) {
+            controller.set_state(&mut MemoryStream::from_bytes(state.to_vec()))?;
+        }
+        Ok(())
+    }
+
+    /// Capture the current component and controller state as a preset for `class_id`.
+    pub fn save_preset(&self, class_id: [u8; 16]) -> Result<VstPreset, HostError> {
+        let mut preset = VstPreset::new(class_id);
+        preset.set_chunk(COMPONENT_CHUNK, self.component_state()?);
+        if let Some(state) = self.controller_state()? {
+            preset.set_chunk(CONTROLLER_CHUNK, state);
+        }
+        Ok(preset)
+    }
+}
+
+// ----- Locations --------------------------------------------------------------------
+
+/// Folders below a preset location are followed this deep.
+const MAX_DEPTH: usize = 4;
+
+/// Where one plugin's presets live. Only resolved; nothing is created, and folders that
+/// do not exist just hold no presets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PresetLocations {
+    /// The user's own presets, where a host saves new ones.
+    pub user: PathBuf,
+    /// Presets for all users (and the network or public folders), in search order.
+    pub shared: Vec<PathBuf>,
+    /// Presets shipped in the plugin's bundle; see [`PresetLocations::with_bundle`].
+    pub factory_in_bundle: Option<PathBuf>,
+}
+
+impl PresetLocations {
+    /// Also look in `Contents/Resources/Presets` of `bundle`.
+    pub fn with_bundle(mut self, bundle: &Path) -> Self {
+        self.factory_in_bundle = Some(bundle.join("Contents").join("Resources").join("Presets"));
+        self
+    }
+
+    /// Every location, user first and the bundle last.
+    pub fn all(&self) -> impl Iterator<Item = &Path> {
+        std::iter::once(&self.user)
+            .chain(&self.shared)
+            .chain(&self.factory_in_bundle)
+            .map(PathBuf::as_path)
+    }
+}
+
+/// A `.vstpreset` file found by [`list_presets`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PresetFile {
+    pub path: PathBuf,
+    /// The file name without the extension.
+    pub name: String,
+}
+
+/// The preset folders of `plugin_name` by `vendor` on this platform:
+///
+/// | | user | shared |
+/// |---|---|---|
+/// | Linux | `~/.vst3/presets` | `/usr/share/vst3/presets`, `/usr/local/share/vst3/presets` |
+/// | macOS | `~/Library/Audio/Presets` | `/Library/Audio/Presets`, `/Network/Library/Audio/Presets` |
+/// | Windows | `%USERPROFILE%\Documents\VST3 Presets` | `%ALLUSERSPROFILE%\VST3 Presets`, `%PUBLIC%\Documents\VST3 Presets` |
+///
+/// each followed by `<vendor>/<plugin_name>`. Characters that cannot appear in a file
+/// name are replaced with `_`. Without a home folder the user location is relative.
+pub fn preset_locations(vendor: &str, plugin_name: &str) -> PresetLocations {
+    let env = |var: &str| std::env::var_os(var).map(PathBuf::from);
+    let (user, shared) = if cfg!(target_os = "macos") {
+        (
+            env("HOME")
+                .unwrap_or_default()
+                .join("Library/Audio/Presets"),
+            vec![
+                PathBuf::from("/Library/Audio/Presets"),
+                PathBuf::from("/Network/Library/Audio/Presets"),
+            ],
+        )
+    } else if cfg!(windows) {
+        let shared = [
+            env("ALLUSERSPROFILE").map(|all| all.join("VST3 Presets")),
+            env("PUBLIC").map(|public| public.join("Documents").join("VST3 Presets")),
+        ];
+        (
+            env("USERPROFILE")
+                .unwrap_or_default()
+                .join("Documents")
+                .join("VST3 Presets"),
+            shared.into_iter().flatten().collect(),
+        )
+    } else {
+        (
+            env("HOME")
+                .unwrap_or_default()
+                .join(".vst3")
+                .join("presets"),
+            vec![
+                PathBuf::from("/usr/share/vst3/presets"),
+                PathBuf::from("/usr/local/share/vst3/presets"),
+            ],
+        )
+    };
+    let (vendor, plugin_name) = (file_name_safe(vendor), file_name_safe(plugin_name));
+    let below = |root: PathBuf| root.join(&vendor).join(&plugin_name);
+    PresetLocations {
+        user: below(user),
+        shared: shared.into_iter().map(below).collect(),
+        factory_in_bundle: None,
+    }
+}
+
+fn file_name_safe(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// The `.vstpreset` files in every location and their subfolders, in
+/// [`PresetLocations::all`] order and by path within a location. Missing or unreadable
+/// folders are skipped.
+pub fn list_presets(locations: &PresetLocations) -> Vec<PresetFile> {
+    let mut presets = Vec::new();
+    for root in locations.all() {
+        let mut found = Vec::new();
+        collect_presets(root, 0, &mut found);
+        found.sort();
+        presets.extend(found.into_iter().map(|path| {
+            PresetFile {
+                name: path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                path,
+            }
+        }));
+    }
+    presets
+}
+
+fn collect_presets(dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if depth < MAX_DEPTH {
+                collect_presets(&path, depth + 1, found);
+            }
+        } else if path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("vstpreset"))
+        {
+            found.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore = "file system")]
+    fn preset_locations_follow_the_convention_and_list_vstpresets() {
+        let locations = preset_locations("ACME", "Gain: Pro/2");
+        assert!(
+            locations.user.ends_with("ACME/Gain_ Pro_2"),
+            "{locations:?}"
+        );
+        if cfg!(all(unix, not(target_os = "macos"))) {
+            assert!(locations.user.ends_with(".vst3/presets/ACME/Gain_ Pro_2"));
+            assert_eq!(
+                locations.shared,
+                [
+                    PathBuf::from("/usr/share/vst3/presets/ACME/Gain_ Pro_2"),
+                    PathBuf::from("/usr/local/share/vst3/presets/ACME/Gain_ Pro_2"),
+                ]
+            );
+        }
+        assert_eq!(locations.factory_in_bundle, None);
+
+        let dir = std::env::temp_dir().join(format!("openvst3-presets-{}", std::process::id()));
+        let bundle = dir.join("Gain.vst3");
+        let locations = PresetLocations {
+            user: dir.join("user"),
+            shared: vec![dir.join("missing")],
+            factory_in_bundle: None,
+        }
+        .with_bundle(&bundle);
+        let factory = bundle.join("Contents/Resources/Presets");
+        for file in [
+            dir.join("user/b.vstpreset"),
+            dir.join("user/Leads/a.VSTPRESET"),
+            dir.join("user/notes.txt"),
+            factory.join("Init.vstpreset"),
+        ] {
+            std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+            std::fs::write(file, b"").unwrap();
+        }
+        let presets = list_presets(&locations);
+        let _ = std::fs::remove_dir_all(&dir);
+        let names: Vec<_> = presets.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "Init"]);
+        assert_eq!(presets[2].path, factory.join("Init.vstpreset"));
+    }
+}
@@ -0,0 +1,321 @@
+//! One-shot capability probe of a factory class.
+//!
+//! The class is created as an `IComponent`, initialized, asked what it implements and
+//! reports, then terminated and released again. Unlike
+//! [`Module::create_plugin`](crate::Module::create_plugin), a missing processor or
+//! controller is a finding rather than an error.
+//!
+//! [`Module::probe_capabilities`] goes further and hosts the class: it looks for the
+//! optional controller interfaces and tries 64-bit processing, a state round trip and
+//! null blocks at the sample rates in [`NULL_PROCESS_RATES`]. A trial that goes wrong
+//! is an [`Outcome::Fail`] of its own, so one broken capability does not hide the rest.
+//!
+//! [`instantiate_and_probe`] keeps the instance instead: it asks it for every interface
+//! in [`iids::REGISTRY`] and hands out typed handles only for the ones it answers, so a
+//! host never has to cast whatever `createInstance` returned to the interface it
+//! hoped for.
+
+use openvst3_abi::{
+    iids, FUnknown, IAudioProcessor, IComponent, IEditController, Tuid, BUS_DIR_INPUT,
+    BUS_DIR_OUTPUT, MEDIA_TYPE_AUDIO, MEDIA_TYPE_EVENT,
+};
+
+use crate::analysis::analyze_block;
+use crate::{
+    BoundProcessData, BusSample, ComponentHandle, ControllerHandle, HostError, InterfacePtr,
+    Module, Outcome, PluginArchitecture, PluginInstance, ProcessSetupBuilder, ProcessorHandle,
+    SymbolicSampleSize,
+};
+
+/// Sample rates [`Module::probe_capabilities`] processes a null block at.
+pub const NULL_PROCESS_RATES: [f64; 2] = [44_100.0, 192_000.0];
+
+/// Frames in each null block of [`Module::probe_capabilities`].
+pub const NULL_PROCESS_FRAMES: usize = 64;
+
+/// Channel count of every audio bus and the number of event buses, per direction.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BusSummary {
+    pub audio_inputs: Vec<i32>,
+    pub audio_outputs: Vec<i32>,
+    pub event_inputs: i32,
+    pub event_outputs: i32,
+}
+
+/// What a class implements and reports right after `initialize`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClassProbe {
+    pub audio_processor: bool,
+    /// The component itself is the controller (single-component plugin).
+    pub edit_controller: bool,
+    /// Whether `getControllerClassId` names a separate controller class.
+    pub architecture: PluginArchitecture,
+    pub buses: BusSummary,
+    /// `None` without an `IAudioProcessor`.
+    pub latency_samples: Option<u32>,
+}
+
+/// What [`Module::probe_capabilities`] found by hosting a class.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capabilities {
+    pub probe: ClassProbe,
+    /// `IUnitInfo` on the controller.
+    pub unit_info: bool,
+    /// `IMidiMapping` on the controller.
+    pub midi_mapping: bool,
+    /// `INoteExpressionController` on the controller.
+    pub note_expression: bool,
+    /// `getTailSamples`, as the plugin reports it.
+    pub tail_samples: u32,
+    /// `None` without a controller.
+    pub parameter_count: Option<i32>,
+    /// Activation with 64-bit samples and one null 64-bit block; skipped when
+    /// `canProcessSampleSize` refuses them.
+    pub float64: Outcome,
+    /// `setState(getState())` leaves `getState` unchanged.
+    pub state_round_trip: Outcome,
+    /// One 32-bit null block per rate of [`NULL_PROCESS_RATES`].
+    pub null_process: Vec<(f64, Outcome)>,
+}
+
+fn audio_channels(component: &ComponentHandle, direction: i32) -> Vec<i32> {
+    (0..component.bus_count(MEDIA_TYPE_AUDIO, direction).max(0))
+        .map(|i| {
+            component
+                .bus_info(MEDIA_TYPE_AUDIO, direction, i)
+                .map_or(0, |b| b.channel_count)
+        })
+        .collect()
+}
+
+impl Module {
+    /// Create class `cid`, record its interfaces, buses and latency, and tear it down.
+    pub fn probe_class(&mut self, cid: [u8; 16]) -> Result<ClassProbe, HostError> {
+        let component = unsafe {
+            let raw = crate::create_instance_raw(self.factory_mut(), cid, iids::ICOMPONENT.0)?;
+            ComponentHandle::new(
+                InterfacePtr::from_raw(raw as *mut IComponent)
+                    .ok_or_else(|| HostError::no_interface(iids::ICOMPONENT))?,
+            )
+        };
+        #[cfg(feature = "leak-audit")]
+        crate::leak_audit::adopt(component.as_ptr() as usize, self.audit_id);
+        unsafe { component.initialize(core::ptr::null_mut())? };
+
+        let processor = unsafe {
+            component
+                .interface()
+                .query::<IAudioProcessor>(&iids::IAUDIO_PROCESSOR)
+        }
+        .ok()
+        .map(ProcessorHandle::new);
+        let edit_controller = unsafe {
+            component
+                .interface()
+                .query::<IEditController>(&iids::IEDIT_CONTROLLER)
+        }
+        .is_ok();
+        let probe = ClassProbe {
+            audio_processor: processor.is_some(),
+            edit_controller,
+            architecture: PluginArchitecture::of(&component),
+            buses: BusSummary {
+                audio_inputs: audio_channels(&component, BUS_DIR_INPUT),
+                audio_outputs: audio_channels(&component, BUS_DIR_OUTPUT),
+                event_inputs: component.bus_count(MEDIA_TYPE_EVENT, BUS_DIR_INPUT).max(0),
+                event_outputs: component.bus_count(MEDIA_TYPE_EVENT, BUS_DIR_OUTPUT).max(0),
+            },
+            latency_samples: processor.as_ref().map(ProcessorHandle::latency_samples),
+        };
+        drop(processor);
+        let _ = component.terminate();
+        Ok(probe)
+    }
+
+    /// Probe class `cid` with [`probe_class`](Self::probe_class), then host it and
+    /// try out what it can do. Fails only when the class cannot be created and
+    /// initialized at all.
+    pub fn probe_capabilities(&mut self, cid: [u8; 16]) -> Result<Capabilities, HostError> {
+        let probe = self.probe_class(cid)?;
+        let mut instance = self.create_plugin(cid)?;
+        let on_controller = |iid: &Tuid| match instance.controller() {
+            Some(controller) => unsafe { controller.interface().query::<FUnknown>(iid) }.is_ok(),
+            None => false,
+        };
+        let unit_info = on_controller(&iids::IUNIT_INFO);
+        let midi_mapping = on_controller(&iids::IMIDI_MAPPING);
+        let note_expression = on_controller(&iids::INOTE_EXPRESSION_CONTROLLER);
+        let parameter_count = instance.controller().map(|c| c.parameter_count());
+        let tail_samples = instance.processor().tail_samples();
+        let state_round_trip = trial(state_round_trip(&instance));
+        let float64 = trial(null_block::<f64>(
+            &mut instance,
+            NULL_PROCESS_RATES[0],
+            SymbolicSampleSize::Sample64,
+        ));
+        let null_process = NULL_PROCESS_RATES
+            .iter()
+            .map(|&rate| {
+                let outcome = null_block::<f32>(&mut instance, rate, SymbolicSampleSize::Sample32);
+                (rate, trial(outcome))
+            })
+            .collect();
+        Ok(Capabilities {
+            probe,
+            unit_info,
+            midi_mapping,
+            note_expression,
+            tail_samples,
+            parameter_count,
+            float64,
+            state_round_trip,
+            null_process,
+        })
+    }
+}
+
+/// IIDs [`instantiate_and_probe`] creates a class with, in the order it tries them.
+pub const CREATION_IIDS: [Tuid; 4] = [
+    iids::ICOMPONENT,
+    iids::IAUDIO_PROCESSOR,
+    iids::IEDIT_CONTROLLER,
+    iids::FUNKNOWN,
+];
+
+/// A live instance and the interfaces it answers `queryInterface` for. Not
+/// initialized; dropping it releases every handle.
+pub struct ProbeResult {
+    /// The IID `createInstance` was called with.
+    pub created_as: Tuid,
+    /// The interfaces of [`iids::REGISTRY`] the instance answers, in registry order.
+    pub supported: Vec<Tuid>,
+    pub component: Option<ComponentHandle>,
+    pub processor: Option<ProcessorHandle>,
+    pub controller: Option<ControllerHandle>,
+    instance: InterfacePtr<FUnknown>,
+}
+
+impl ProbeResult {
+    /// Ask `instance`, created with `created_as`, for every registered interface.
+    pub fn of(instance: InterfacePtr<FUnknown>, created_as: Tuid) -> Self {
+        let supported = iids::REGISTRY
+            .iter()
+            .map(|&(_, iid)| iid)
+            .filter(|iid| unsafe { instance.query::<FUnknown>(iid) }.is_ok())
+            .collect::<Vec<_>>();
+        let component = unsafe { instance.query::<IComponent>(&iids::ICOMPONENT) }
+            .ok()
+            .map(ComponentHandle::new);
+        let processor = unsafe { instance.query::<IAudioProcessor>(&iids::IAUDIO_PROCESSOR) }
+            .ok()
+            .map(ProcessorHandle::new);
+        let controller = unsafe { instance.query::<IEditController>(&iids::IEDIT_CONTROLLER) }
+            .ok()
+            .map(|c| match &component {
+                Some(component) => ControllerHandle::with_names(c, component.names()),
+                None => ControllerHandle::new(c),
+            });
+        Self {
+            created_as,
+            supported,
+            component,
+            processor,
+            controller,
+            instance,
+        }
+    }
+
+    /// The object as created, for interfaces outside the registry.
+    pub fn instance(&self) -> &InterfacePtr<FUnknown> {
+        &self.instance
+    }
+
+    pub fn supports(&self, iid: &Tuid) -> bool {
+        self.supported.contains(iid)
+    }
+
+    /// Registry names of [`supported`](Self::supported).
+    pub fn supported_names(&self) -> Vec<&'static str> {
+        self.supported.iter().filter_map(iids::name_of).collect()
+    }
+
+    /// The processor, or [`HostError::NoInterface`] for `IAudioProcessor`.
+    pub fn require_processor(&self) -> Result<&ProcessorHandle, HostError> {
+        self.processor
+            .as_ref()
+            .ok_or_else(|| HostError::no_interface(iids::IAUDIO_PROCESSOR))
+    }
+}
+
+/// Create class `cid` with the first of [`CREATION_IIDS`] the factory accepts and
+/// probe the instance with [`ProbeResult::of`]. The error is the first attempt's.
+pub fn instantiate_and_probe(module: &mut Module, cid: [u8; 16]) -> Result<ProbeResult, HostError> {
+    let mut first = None;
+    for iid in CREATION_IIDS {
+        let created = unsafe { crate::create_instance_raw(module.factory_mut(), cid, iid.0) }
+            .and_then(|raw| {
+                unsafe { InterfacePtr::from_raw(raw as *mut FUnknown) }
+                    .ok_or_else(|| HostError::no_interface(iid))
+            });
+        match created {
+            Ok(instance) => {
+                #[cfg(feature = "leak-audit")]
+                crate::leak_audit::adopt(instance.as_ptr() as usize, module.audit_id);
+                return Ok(ProbeResult::of(instance, iid));
+            }
+            Err(e) => {
+                first.get_or_insert(e);
+            }
+        }
+    }
+    Err(first.expect("CREATION_IIDS is not empty"))
+}
+
+fn trial(result: Result<Outcome, HostError>) -> Outcome {
+    result.unwrap_or_else(|e| Outcome::Fail(e.to_string()))
+}
+
+fn state_round_trip(instance: &PluginInstance) -> Result<Outcome, HostError> {
+    let saved = instance.component_state()?;
+    instance.set_component_state(&saved)?;
+    Ok(if instance.component_state()? == saved {
+        Outcome::Pass
+    } else {
+        Outcome::Fail("getState after setState(getState()) returns different bytes".into())
+    })
+}
+
+/// Activate at `sample_rate` with `T` samples (`precision` being their symbolic
+/// size), process one block of silence and deactivate again. Skipped when the plugin
+/// does not take that precision.
+fn null_block<T: BusSample + crate::limiter::Sample>(
+    instance: &mut PluginInstance,
+    sample_rate: f64,
+    precision: SymbolicSampleSize,
+) -> Result<Outcome, HostError> {
+    let inputs = instance.main_bus_channels(BUS_DIR_INPUT);
+    let outputs = instance.main_bus_channels(BUS_DIR_OUTPUT);
+    let setup = ProcessSetupBuilder::new()
+        .sample_rate(sample_rate)
+        .max_block(NULL_PROCESS_FRAMES as i32)
+        .precision(precision)
+        .for_processor(instance.processor());
+    if let Some(warning) = setup.warnings().first() {
+        return Ok(Outcome::Skip(warning.to_string()));
+    }
+    instance.activate(&setup.build()?)?;
+    let mut bound = BoundProcessData::<T>::new(inputs, outputs, NULL_PROCESS_FRAMES);
+    let processed = instance.process_bound(&mut bound, NULL_PROCESS_FRAMES, None);
+    instance.deactivate();
+    processed?;
+    Ok(
+        match bound
+            .outputs()
+            .iter()
+            .position(|b| !analyze_block(b).is_finite())
+        {
+            Some(c) => Outcome::Fail(format!("non-finite output on channel {c}")),
+            None => Outcome::Pass,
+        },
+    )
+}
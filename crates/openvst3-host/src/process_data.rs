@@ -0,0 +1,586 @@
+//! `ProcessData` bound once to its buffers and reused for every block.
+//!
+//! A [`BoundProcessData`] owns the planar channel buffers of its buses (the main ones
+//! and, with [`with_buses`](BoundProcessData::with_buses), aux buses such as a
+//! sidechain input or an instrument's extra outputs), the channel pointer arrays, the
+//! `AudioBusBuffers` and the `ProcessData` itself, each in its own heap allocation so
+//! the pointers between them survive moves. [`prepare`](BoundProcessData::prepare)
+//! rewires them for every block (writing through
+//! [`inputs_mut`](BoundProcessData::inputs_mut) invalidates pointers taken before),
+//! writes the frame count, clears the silence flags and points at the block's event
+//! and parameter lists, and at an emptied output event list once one is
+//! [set up](BoundProcessData::set_output_events). None of that allocates. A `ProcessContext` given
+//! to [`set_context`](BoundProcessData::set_context) is copied into a box of its own
+//! and passed with every block until replaced or cleared. With an [`InputGate`] the
+//! input goes through [`write_input`](BoundProcessData::write_input), which skips the
+//! copy and flags the input bus silent while the source stays quiet.
+//!
+//! Every `process` wrapper returns a [`ProcessStats`] with what the plugin reported
+//! besides the audio.
+
+use core::ffi::c_void;
+use std::time::{Duration, Instant};
+
+use openvst3_abi::{
+    tresult, AudioBusBuffers32, AudioBusBuffers64, IEventList, IParameterChanges, ProcessContext,
+    ProcessData32, ProcessData64, K_RESULT_FALSE, K_RESULT_OK,
+};
+
+use crate::dsp;
+use crate::events::{EventList, ParameterChanges};
+use crate::input_gate::{GateAction, InputGate};
+use crate::{HostError, PluginInstance, ProcessorHandle};
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for f32 {}
+    impl Sealed for f64 {}
+}
+
+/// A sample type with its `AudioBusBuffers` and `ProcessData` layouts: `f32` or `f64`.
+pub trait BusSample: Copy + Default + sealed::Sealed + 'static {
+    type Bus;
+    type Data;
+    #[doc(hidden)]
+    fn bus(channels: usize, buffers: *mut *mut Self) -> Self::Bus;
+    #[doc(hidden)]
+    fn data(
+        num_inputs: i32,
+        inputs: *mut Self::Bus,
+        num_outputs: i32,
+        outputs: *mut Self::Bus,
+    ) -> Self::Data;
+    #[doc(hidden)]
+    fn silence_flags(bus: &Self::Bus) -> u64;
+    #[doc(hidden)]
+    unsafe fn begin_block(
+        data: &mut Self::Data,
+        frames: usize,
+        changes: *mut c_void,
+        events: *mut c_void,
+        input_silence: u64,
+    );
+    #[doc(hidden)]
+    fn output_parameter_changes(data: &mut Self::Data) -> &mut *mut c_void;
+    #[doc(hidden)]
+    fn output_events(data: &mut Self::Data) -> &mut *mut c_void;
+    #[doc(hidden)]
+    fn process_context(data: &mut Self::Data) -> &mut *mut ProcessContext;
+    #[doc(hidden)]
+    unsafe fn process(
+        processor: &ProcessorHandle,
+        data: &mut Self::Data,
+    ) -> Result<ProcessStats, HostError>;
+    #[doc(hidden)]
+    fn from_f64(v: f64) -> Self;
+    #[doc(hidden)]
+    fn to_f64(self) -> f64;
+    #[doc(hidden)]
+    fn from_f32_slice(src: &[f32], dst: &mut [Self]);
+    #[doc(hidden)]
+    fn from_f64_slice(src: &[f64], dst: &mut [Self]);
+    /// Write `src` into `dst` as `D`, through [`dsp`] where the types differ.
+    #[doc(hidden)]
+    fn convert_slice<D: BusSample>(src: &[Self], dst: &mut [D]);
+}
+
+/// `copy_from_slice` up to the shorter of the two, as the [`dsp`] kernels do.
+fn copy_slice<T: Copy>(src: &[T], dst: &mut [T]) {
+    let n = src.len().min(dst.len());
+    dst[..n].copy_from_slice(&src[..n]);
+}
+
+macro_rules! bus_sample {
+    (
+        $sample:ty, $bus:ident, $data:ident, $process:ident,
+        from_f32: $from_f32:path, from_f64: $from_f64:path, convert: $convert:ident
+    ) => {
+        impl BusSample for $sample {
+            type Bus = $bus;
+            type Data = $data;
+
+            fn bus(channels: usize, buffers: *mut *mut Self) -> $bus {
+                $bus {
+                    num_channels: channels as i32,
+                    silence_flags: 0,
+                    channel_buffers: buffers,
+                }
+            }
+
+            fn data(
+                num_inputs: i32,
+                inputs: *mut $bus,
+                num_outputs: i32,
+                outputs: *mut $bus,
+            ) -> $data {
+                $data {
+                    num_inputs,
+                    num_outputs,
+                    inputs,
+                    outputs,
+                    num_samples: 0,
+                    input_parameter_changes: core::ptr::null_mut(),
+                    output_parameter_changes: core::ptr::null_mut(),
+                    input_events: core::ptr::null_mut(),
+                    output_events: core::ptr::null_mut(),
+                    process_context: core::ptr::null_mut(),
+                }
+            }
+
+            fn silence_flags(bus: &$bus) -> u64 {
+                bus.silence_flags
+            }
+
+            unsafe fn begin_block(
+                data: &mut $data,
+                frames: usize,
+                changes: *mut c_void,
+                events: *mut c_void,
+                input_silence: u64,
+            ) {
+                data.num_samples = frames as i32;
+                data.input_parameter_changes = changes;
+                data.input_events = events;
+                (*data.inputs).silence_flags = input_silence;
+                for bus in 0..data.num_outputs.max(0) as usize {
+                    (*data.outputs.add(bus)).silence_flags = 0;
+                }
+            }
+
+            fn output_parameter_changes(data: &mut $data) -> &mut *mut c_void {
+                &mut data.output_parameter_changes
+            }
+
+            fn output_events(data: &mut $data) -> &mut *mut c_void {
+                &mut data.output_events
+            }
+
+            fn process_context(data: &mut $data) -> &mut *mut ProcessContext {
+                &mut data.process_context
+            }
+
+            unsafe fn process(
+                processor: &ProcessorHandle,
+                data: &mut $data,
+            ) -> Result<ProcessStats, HostError> {
+                processor.$process(data)
+            }
+
+            fn from_f64(v: f64) -> Self {
+                v as $sample
+            }
+
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn from_f32_slice(src: &[f32], dst: &mut [Self]) {
+                $from_f32(src, dst)
+            }
+
+            fn from_f64_slice(src: &[f64], dst: &mut [Self]) {
+                $from_f64(src, dst)
+            }
+
+            fn convert_slice<D: BusSample>(src: &[Self], dst: &mut [D]) {
+                D::$convert(src, dst)
+            }
+        }
+    };
+}
+
+bus_sample!(
+    f32, AudioBusBuffers32, ProcessData32, process_32f,
+    from_f32: copy_slice, from_f64: dsp::convert_f64_to_f32, convert: from_f32_slice
+);
+bus_sample!(
+    f64, AudioBusBuffers64, ProcessData64, process_64f,
+    from_f32: dsp::convert_f32_to_f64, from_f64: copy_slice, convert: from_f64_slice
+);
+
+/// Output buses whose silence flags [`ProcessStats`] keeps.
+pub const MAX_STATS_BUSES: usize = 8;
+
+/// What one successful `process` call reported besides the audio.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProcessStats {
+    /// `kResultOk`, or `kResultFalse` when the plugin had nothing to do.
+    pub tresult_raw: tresult,
+    /// The `silenceFlags` the plugin left on each output bus (bit `n`: channel `n` is
+    /// silent), for the first [`MAX_STATS_BUSES`] buses.
+    pub plugin_marked_outputs_silent: [u64; MAX_STATS_BUSES],
+    /// Parameters with output changes, when the host passed an output list.
+    pub out_params: usize,
+    /// Events in the output event list, when the host passed one.
+    pub out_events: usize,
+    /// How long the call took, with [`ProcessorHandle::set_timing`] on.
+    pub duration: Option<Duration>,
+    /// Input copies the [`InputGate`] of a [`BoundProcessData`] has skipped so far.
+    pub skipped_input_copies: u64,
+}
+
+impl ProcessStats {
+    /// `kResultOk` and `kResultFalse` (a legal "nothing to do") become stats; any
+    /// other result is an error.
+    pub(crate) unsafe fn collect(
+        tr: tresult,
+        started: Option<Instant>,
+        silence: impl Iterator<Item = u64>,
+        out_params: *mut c_void,
+        out_events: *mut c_void,
+    ) -> Result<Self, HostError> {
+        let duration = started.map(|t| t.elapsed());
+        if tr != K_RESULT_OK && tr != K_RESULT_FALSE {
+            return Err(HostError::TErr(tr));
+        }
+        let mut stats = Self {
+            tresult_raw: tr,
+            duration,
+            ..Self::default()
+        };
+        for (slot, flags) in stats.plugin_marked_outputs_silent.iter_mut().zip(silence) {
+            *slot = flags;
+        }
+        if let Some(changes) = (out_params as *mut IParameterChanges).as_mut() {
+            stats.out_params = changes.get_parameter_count().max(0) as usize;
+        }
+        if let Some(events) = (out_events as *mut IEventList).as_mut() {
+            stats.out_events = events.get_event_count().max(0) as usize;
+        }
+        Ok(stats)
+    }
+
+    /// The plugin answered `kResultFalse`: nothing was processed.
+    pub fn nothing_to_do(&self) -> bool {
+        self.tresult_raw == K_RESULT_FALSE
+    }
+
+    /// Whether the plugin flagged `channel` of output `bus` as silent.
+    pub fn output_silent(&self, bus: usize, channel: usize) -> bool {
+        channel < 64
+            && self
+                .plugin_marked_outputs_silent
+                .get(bus)
+                .is_some_and(|flags| flags & (1 << channel) != 0)
+    }
+}
+
+/// See the module docs. Without input channels the `ProcessData` has no input bus.
+pub struct BoundProcessData<T: BusSample> {
+    /// The channels of every input bus, bus after bus; likewise `outputs`.
+    inputs: Box<[Box<[T]>]>,
+    /// Channels per input bus; likewise `output_buses`.
+    input_buses: Box<[usize]>,
+    outputs: Box<[Box<[T]>]>,
+    output_buses: Box<[usize]>,
+    // Read by the plugin through `data`; only written by `wire`.
+    in_ptrs: Box<[*mut T]>,
+    out_ptrs: Box<[*mut T]>,
+    /// One per bus, and never empty so `data` always has a bus to point at.
+    in_bus: Box<[T::Bus]>,
+    out_bus: Box<[T::Bus]>,
+    data: Box<T::Data>,
+    context: Box<ProcessContext>,
+    has_context: bool,
+    /// Passed as `output_events`, emptied before every block.
+    out_events: Option<Box<EventList>>,
+    gate: Option<InputGate>,
+    max_frames: usize,
+}
+
+// Every pointer in `data` targets an allocation owned by the same value, except the
+// list pointers, which are only followed during a `prepare` caller's process call.
+unsafe impl<T: BusSample> Send for BoundProcessData<T> {}
+
+impl<T: BusSample> BoundProcessData<T> {
+    /// Zeroed buffers of `max_frames` for each channel, wired into a `ProcessData`.
+    pub fn new(input_channels: usize, output_channels: usize, max_frames: usize) -> Self {
+        let main = [input_channels];
+        let inputs = if input_channels > 0 { &main[..] } else { &[] };
+        Self::with_buses(inputs, &[output_channels], max_frames)
+    }
+
+    /// Like [`new`](Self::new), with one bus per entry of `inputs` and `outputs` (its
+    /// channel count, which may be 0), in bus index order.
+    pub fn with_buses(inputs: &[usize], outputs: &[usize], max_frames: usize) -> Self {
+        let input_channels = inputs.iter().sum();
+        let output_channels = outputs.iter().sum();
+        let channels = |n| {
+            (0..n)
+                .map(|_| vec![T::default(); max_frames].into_boxed_slice())
+                .collect::<Box<[_]>>()
+        };
+        let null = |n| vec![core::ptr::null_mut(); n].into_boxed_slice();
+        let buses = |n: usize| {
+            (0..n.max(1))
+                .map(|_| T::bus(0, core::ptr::null_mut()))
+                .collect()
+        };
+        let mut bound = Self {
+            inputs: channels(input_channels),
+            input_buses: inputs.into(),
+            outputs: channels(output_channels),
+            output_buses: outputs.into(),
+            in_ptrs: null(input_channels),
+            out_ptrs: null(output_channels),
+            in_bus: buses(inputs.len()),
+            out_bus: buses(outputs.len()),
+            data: Box::new(T::data(
+                inputs.len() as i32,
+                core::ptr::null_mut(),
+                outputs.len() as i32,
+                core::ptr::null_mut(),
+            )),
+            context: Box::default(),
+            has_context: false,
+            out_events: None,
+            gate: None,
+            max_frames,
+        };
+        bound.wire();
+        bound
+    }
+
+    /// Point `data` at the buses, the buses at the pointer arrays and those at the
+    /// channels, each pointer freshly derived from its owner.
+    fn wire(&mut self) {
+        for (ptr, channel) in self.in_ptrs.iter_mut().zip(self.inputs.iter_mut()) {
+            *ptr = channel.as_mut_ptr();
+        }
+        for (ptr, channel) in self.out_ptrs.iter_mut().zip(self.outputs.iter_mut()) {
+            *ptr = channel.as_mut_ptr();
+        }
+        wire_buses(&mut self.in_bus, &self.input_buses, &mut self.in_ptrs);
+        wire_buses(&mut self.out_bus, &self.output_buses, &mut self.out_ptrs);
+        *self.data = T::data(
+            self.input_buses.len() as i32,
+            self.in_bus.as_mut_ptr(),
+            self.output_buses.len() as i32,
+            self.out_bus.as_mut_ptr(),
+        );
+    }
+
+    /// Channels of the main input bus (bus 0).
+    fn main_inputs(&self) -> usize {
+        self.input_buses.first().copied().unwrap_or(0)
+    }
+
+    fn main_outputs(&self) -> usize {
+        self.output_buses.first().copied().unwrap_or(0)
+    }
+
+    pub fn max_frames(&self) -> usize {
+        self.max_frames
+    }
+
+    /// Main input bus channel buffers, `max_frames` long each.
+    pub fn inputs(&self) -> &[Box<[T]>] {
+        &self.inputs[..self.main_inputs()]
+    }
+
+    pub fn inputs_mut(&mut self) -> impl ExactSizeIterator<Item = &mut [T]> {
+        let main = self.main_inputs();
+        self.inputs[..main].iter_mut().map(|c| &mut c[..])
+    }
+
+    /// Input buses in the `ProcessData`, main bus included.
+    pub fn input_bus_count(&self) -> usize {
+        self.input_buses.len()
+    }
+
+    /// Channel buffers of input bus `bus`; empty for a bus that does not exist.
+    pub fn input_bus(&self, bus: usize) -> &[Box<[T]>] {
+        &self.inputs[bus_range(&self.input_buses, bus)]
+    }
+
+    pub fn input_bus_mut(&mut self, bus: usize) -> impl ExactSizeIterator<Item = &mut [T]> {
+        let range = bus_range(&self.input_buses, bus);
+        self.inputs[range].iter_mut().map(|c| &mut c[..])
+    }
+
+    /// Main output bus channel buffers, `max_frames` long each.
+    pub fn outputs(&self) -> &[Box<[T]>] {
+        &self.outputs[..self.main_outputs()]
+    }
+
+    pub fn outputs_mut(&mut self) -> impl ExactSizeIterator<Item = &mut [T]> {
+        let main = self.main_outputs();
+        self.outputs[..main].iter_mut().map(|c| &mut c[..])
+    }
+
+    /// Output buses in the `ProcessData`, main bus included.
+    pub fn output_bus_count(&self) -> usize {
+        self.output_buses.len()
+    }
+
+    /// Channel buffers of output bus `bus`; empty for a bus that does not exist.
+    pub fn output_bus(&self, bus: usize) -> &[Box<[T]>] {
+        &self.outputs[bus_range(&self.output_buses, bus)]
+    }
+
+    /// The `silenceFlags` the plugin left on output bus `bus` in the last block; 0
+    /// for a bus that does not exist.
+    pub fn output_silence_flags(&self, bus: usize) -> u64 {
+        match self.output_buses.get(bus) {
+            Some(_) => T::silence_flags(&self.out_bus[bus]),
+            None => 0,
+        }
+    }
+
+    /// The `ProcessContext` to pass from the next block on, or `None` to pass none.
+    pub fn set_context(&mut self, context: Option<ProcessContext>) {
+        self.has_context = context.is_some();
+        if let Some(context) = context {
+            *self.context = context;
+        }
+    }
+
+    /// Give the plugin an output event list of `capacity` events with every block from
+    /// the next on, or none for 0. Allocates; call before processing starts.
+    pub fn set_output_events(&mut self, capacity: usize) {
+        self.out_events = (capacity > 0).then(|| EventList::with_capacity(capacity));
+    }
+
+    /// What the plugin put on its event output buses in the last block, if the
+    /// [list](Self::set_output_events) is set up.
+    pub fn output_events(&self) -> Option<&EventList> {
+        self.out_events.as_deref()
+    }
+
+    /// Track the main input bus's silence with `gate`, or stop tracking it.
+    pub fn set_input_gate(&mut self, gate: Option<InputGate>) {
+        self.gate = gate;
+    }
+
+    pub fn input_gate(&self) -> Option<&InputGate> {
+        self.gate.as_ref()
+    }
+
+    /// Feed the next block's input through the gate. `peak` is the largest absolute
+    /// sample of its source; `copy` writes the block into the input channels and is
+    /// not called while the gate holds the bus silent. Without a gate it always is.
+    pub fn write_input(&mut self, peak: f64, copy: impl FnOnce(&mut [Box<[T]>])) {
+        let main = self.main_inputs();
+        match self.gate.as_mut().map(|gate| gate.next_block(peak)) {
+            None | Some(GateAction::Copy) => copy(&mut self.inputs[..main]),
+            Some(GateAction::Zero) => {
+                for channel in self.inputs[..main].iter_mut() {
+                    channel.fill(T::default());
+                }
+            }
+            Some(GateAction::Skip) => {}
+        }
+    }
+
+    /// Silence flags for the main input bus: every channel while the gate is closed.
+    fn input_silence(&self) -> u64 {
+        match &self.gate {
+            Some(gate) if gate.is_closed() => match self.main_inputs() {
+                n if n >= 64 => u64::MAX,
+                n => (1 << n) - 1,
+            },
+            _ => 0,
+        }
+    }
+
+    /// Set up the next block of `frames` and return the `ProcessData` to pass to
+    /// `process`. `lists` are the block's input events and parameter changes; they
+    /// must outlive that call.
+    pub fn prepare(
+        &mut self,
+        frames: usize,
+        lists: Option<(&mut EventList, &mut ParameterChanges)>,
+    ) -> Result<&mut T::Data, HostError> {
+        if frames > self.max_frames {
+            return Err(HostError::BlockSizeExceeded {
+                got: frames,
+                max: self.max_frames,
+            });
+        }
+        let (events, changes) = match lists {
+            Some((events, changes)) => (
+                events.as_ievent_list() as *mut c_void,
+                changes.as_iparameter_changes() as *mut c_void,
+            ),
+            None => (core::ptr::null_mut(), core::ptr::null_mut()),
+        };
+        let input_silence = self.input_silence();
+        self.wire();
+        // The bus pointers in `data` point at our own boxes.
+        unsafe { T::begin_block(&mut self.data, frames, changes, events, input_silence) };
+        *T::output_events(&mut self.data) = match &mut self.out_events {
+            Some(list) => {
+                list.clear();
+                list.as_ievent_list() as *mut c_void
+            }
+            None => core::ptr::null_mut(),
+        };
+        *T::process_context(&mut self.data) = if self.has_context {
+            &mut *self.context
+        } else {
+            core::ptr::null_mut()
+        };
+        Ok(&mut self.data)
+    }
+}
+
+/// Point each of `buses` at its `counts` channels' run of `ptrs`.
+fn wire_buses<T: BusSample>(buses: &mut [T::Bus], counts: &[usize], ptrs: &mut [*mut T]) {
+    let mut first = 0;
+    for (bus, &channels) in buses.iter_mut().zip(counts) {
+        // In bounds even for a 0-channel last bus: one past the end.
+        *bus = T::bus(channels, ptrs[first..].as_mut_ptr());
+        first += channels;
+    }
+}
+
+/// The channels of bus `bus` in the flat channel list of buses of `counts` channels.
+fn bus_range(counts: &[usize], bus: usize) -> core::ops::Range<usize> {
+    let first: usize = counts.iter().take(bus).sum();
+    first..first + counts.get(bus).copied().unwrap_or(0)
+}
+
+impl PluginInstance {
+    /// Process one block of `frames` on `bound`'s buffers. `lists` are the block's
+    /// input events and parameter changes. With the
+    /// [parameter cache](Self::enable_param_cache) on, the plugin also gets an output
+    /// parameter list, which goes into the cache.
+    pub fn process_bound<T: BusSample>(
+        &mut self,
+        bound: &mut BoundProcessData<T>,
+        frames: usize,
+        lists: Option<(&mut EventList, &mut ParameterChanges)>,
+    ) -> Result<ProcessStats, HostError> {
+        let data = bound.prepare(frames, lists)?;
+        self.check_block(frames as i32)?;
+        // Every buffer is owned by `bound` and at least `frames` long.
+        let mut stats = unsafe {
+            self.process_synced(data, T::output_parameter_changes, |p, d| T::process(p, d))?
+        };
+        stats.skipped_input_copies = bound.input_gate().map_or(0, InputGate::skipped);
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bound_process_data_wires_every_input_bus() {
+        let mut bound = BoundProcessData::<f32>::with_buses(&[2, 1], &[2], 16);
+        assert_eq!(bound.input_bus_count(), 2);
+        assert_eq!(bound.inputs().len(), 2);
+        assert_eq!(bound.input_bus(1).len(), 1);
+        assert!(bound.input_bus(2).is_empty());
+        for buf in bound.input_bus_mut(1) {
+            buf.fill(3.0);
+        }
+        assert!(bound.inputs().iter().all(|c| c.iter().all(|&s| s == 0.0)));
+        assert!(bound.input_bus(1)[0].iter().all(|&s| s == 3.0));
+
+        let empty = BoundProcessData::<f32>::new(0, 2, 16);
+        assert_eq!(empty.input_bus_count(), 0);
+        assert!(empty.inputs().is_empty());
+    }
+}
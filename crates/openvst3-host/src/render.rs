@@ -0,0 +1,549 @@
+//! Offline (faster than realtime) rendering of a [`PluginInstance`].
+//!
+//! [`PluginInstance::render_offline`] negotiates bus arrangements, activates the
+//! instance in `kOffline` mode and drives 32-bit blocks over planar input until the
+//! requested length plus tail has been produced; [`RenderConfig::plugin_tail`] adds
+//! the plugin's own tail, capped when it is infinite. Output is latency-compensated:
+//! the first `getLatencySamples` frames are rendered but dropped. Create the instance with
+//! [`IoMode::OfflineProcessing`](crate::IoMode) through [`Module::plugin`](crate::Module::plugin)
+//! as well: `setIoMode` must precede `initialize`, so render cannot send it itself.
+//!
+//! [`PluginInstance::render_offline_with`] also delivers [`RenderEvents`]: MIDI goes
+//! through a [`MidiConverter`] and automation breakpoints become per-block parameter
+//! queues, so a ramp between two breakpoints is a linear segment inside each block.
+//!
+//! A [`RenderConfig::sidechain`] feeds input bus 1, the first aux input (a compressor's
+//! key input, say), which is activated for the render and deactivated after it.
+//! [`RenderConfig::aux_outputs`] does the same for every aux output bus (a drum
+//! sampler's individual outs), and [`PluginInstance::render_offline_buses`] keeps
+//! each output bus apart in a [`RenderedAudio`].
+
+use openvst3_abi::{
+    speaker_arr, ParamID, ParamValue, BUS_DIR_INPUT, BUS_DIR_OUTPUT, MEDIA_TYPE_AUDIO,
+};
+
+use crate::events::{EventList, ParameterChanges};
+use crate::midi::{Converted, MidiConverter};
+use crate::thread_priority::{promote_current_thread, Achieved, Profile};
+use crate::{
+    BoundProcessData, HostError, Limit, LimitOrigin, PluginInstance, ProcessMode,
+    ProcessSetupBuilder,
+};
+
+/// The input bus [`RenderConfig::sidechain`] feeds.
+const SIDECHAIN_BUS: i32 = 1;
+
+/// Speaker arrangement for a plain `channels`-wide bus: mono is `kSpeakerM`, anything
+/// else takes the lowest `channels` speaker bits (so 2 is `kStereo`).
+pub fn arrangement_for_channels(channels: usize) -> u64 {
+    match channels {
+        0 => 0,
+        1 => speaker_arr::MONO,
+        n if n >= 64 => u64::MAX,
+        n => (1u64 << n) - 1,
+    }
+}
+
+/// [`arrangement_for_channels`] in the family of `proposed`, the layout a plugin has
+/// on the bus: next to an ambisonic layout, a channel count that makes a full order
+/// (4, 9 or 16) is that order, so a 16-channel ambisonic bus is not taken for a
+/// 16-speaker one.
+pub fn arrangement_for_channels_like(channels: usize, proposed: u64) -> u64 {
+    if speaker_arr::ambisonic_order(proposed).is_some() {
+        if let Some(ambi) = (1..=3)
+            .filter_map(speaker_arr::ambisonic_arrangement)
+            .find(|&arr| speaker_arr::channel_count(arr) == channels)
+        {
+            return ambi;
+        }
+    }
+    arrangement_for_channels(channels)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderConfig {
+    pub sample_rate: f64,
+    pub block_size: usize,
+    /// Channels of the main input bus; 0 for instruments.
+    pub input_channels: usize,
+    pub output_channels: usize,
+    /// Frames rendered after the input/duration ends.
+    pub tail: usize,
+    /// Also render the plugin's own tail after [`tail`](Self::tail), as it reports it
+    /// once activated, up to this many frames. An infinite tail, or a longer one, is
+    /// cut at this cap and sets [`RenderedAudio::tail_capped`].
+    pub plugin_tail: Option<usize>,
+    /// Planar audio for input bus 1, with as many channels as the plugin reports for
+    /// it; missing channels and frames past the end are silence. Rendering fails with
+    /// [`HostError::NoSidechainBus`] if the plugin has no such bus.
+    pub sidechain: Option<Vec<Vec<f32>>>,
+    /// Render the aux output buses too, each at the channels the plugin reports for
+    /// it. Only [`render_offline_buses`](PluginInstance::render_offline_buses) returns
+    /// them.
+    pub aux_outputs: bool,
+    /// Promote the rendering thread before the first block, reported in
+    /// [`RenderedAudio::priority`]. The thread is the caller's and stays promoted.
+    pub priority: Option<Profile>,
+}
+
+impl Default for RenderConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: 48_000.0,
+            block_size: 512,
+            input_channels: 2,
+            output_channels: 2,
+            tail: 0,
+            plugin_tail: None,
+            sidechain: None,
+            aux_outputs: false,
+            priority: None,
+        }
+    }
+}
+
+/// One output bus of a [`RenderedAudio`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderedBus {
+    /// Output bus index.
+    pub index: i32,
+    /// The name the plugin gives the bus.
+    pub name: String,
+    /// Planar, latency-compensated like [`PluginInstance::render_offline`]'s output.
+    pub channels: Vec<Vec<f32>>,
+    /// Whether the plugin flagged every channel of the bus silent in every block.
+    pub silent: bool,
+}
+
+/// What [`PluginInstance::render_offline_buses`] produced: one entry per rendered
+/// output bus, the main bus first.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderedAudio {
+    pub sample_rate: f64,
+    pub buses: Vec<RenderedBus>,
+    /// Whether [`RenderConfig::plugin_tail`] cut the plugin's tail short.
+    pub tail_capped: bool,
+    /// What [`RenderConfig::priority`] got the rendering thread, if it asked.
+    pub priority: Option<Achieved>,
+}
+
+impl RenderedAudio {
+    /// The main output bus's channels.
+    pub fn main(&self) -> &[Vec<f32>] {
+        self.buses.first().map_or(&[], |b| &b.channels)
+    }
+
+    pub fn into_main(self) -> Vec<Vec<f32>> {
+        self.buses
+            .into_iter()
+            .next()
+            .map(|b| b.channels)
+            .unwrap_or_default()
+    }
+
+    /// The buses the plugin did not keep silent throughout.
+    pub fn audible(&self) -> impl Iterator<Item = &RenderedBus> {
+        self.buses.iter().filter(|b| !b.silent)
+    }
+}
+
+/// Timed input for [`PluginInstance::render_offline_with`], in frames from the start
+/// of the render.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderEvents {
+    /// `(frame, message)` sorted by frame; two-byte messages are padded with 0.
+    pub midi: Vec<(usize, [u8; 3])>,
+    /// Breakpoints per parameter as `(frame, normalized value)`, sorted by frame. The
+    /// value holds before the first and after the last, and ramps linearly between.
+    pub automation: Vec<(ParamID, Vec<(usize, ParamValue)>)>,
+}
+
+impl RenderEvents {
+    pub fn is_empty(&self) -> bool {
+        self.midi.is_empty() && self.automation.is_empty()
+    }
+
+    /// Write everything that falls into the `n` frames from `start` into `events` and
+    /// `changes`. Returns how many MIDI messages did not fit; the lists
+    /// [`render_offline_with`](PluginInstance::render_offline_with) uses are sized so
+    /// none are dropped.
+    pub fn fill_block(
+        &self,
+        start: usize,
+        n: usize,
+        converter: &MidiConverter,
+        events: &mut EventList,
+        changes: &mut ParameterChanges,
+    ) -> usize {
+        events.clear();
+        changes.clear();
+        let end = start + n;
+        let mut dropped = 0;
+        let first = self.midi.partition_point(|&(f, _)| f < start);
+        for (frame, msg) in self.midi[first..].iter().take_while(|&&(f, _)| f < end) {
+            if converter.convert(msg, (frame - start) as i32, events, changes) == Converted::Full {
+                dropped += 1;
+            }
+        }
+        for (id, points) in &self.automation {
+            if points.is_empty() {
+                continue;
+            }
+            let inner = points.partition_point(|&(f, _)| f <= start)
+                ..points.partition_point(|&(f, _)| f < end.saturating_sub(1));
+            let at_start = value_at(points, start);
+            let at_end = value_at(points, end - 1);
+            // Nothing moves in this block: the plugin already has the value.
+            let before = if start == 0 {
+                None
+            } else {
+                Some(value_at(points, start - 1))
+            };
+            if inner.is_empty() && before == Some(at_start) && at_start == at_end {
+                continue;
+            }
+            changes.add_point(*id, 0, at_start);
+            for &(frame, value) in &points[inner] {
+                changes.add_point(*id, (frame - start) as i32, value);
+            }
+            if n > 1 {
+                changes.add_point(*id, (n - 1) as i32, at_end);
+            }
+        }
+        dropped
+    }
+
+    /// `(events, parameters, points per parameter)` that the busiest block of `block`
+    /// frames needs, so the lists never fill up.
+    fn capacity(&self, block: usize) -> (usize, usize, usize) {
+        let mut per_block = 0;
+        let mut run = (usize::MAX, 0);
+        let mut controllers = std::collections::BTreeSet::new();
+        for (frame, msg) in &self.midi {
+            let b = frame / block;
+            run = if run.0 == b { (b, run.1 + 1) } else { (b, 1) };
+            per_block = per_block.max(run.1);
+            // Controllers, aftertouch and pitch bend each land on their own parameter.
+            let controller = match msg[0] & 0xF0 {
+                0xB0 => Some(msg[1]),
+                0xD0 | 0xE0 => Some(0xFF),
+                _ => None,
+            };
+            if let Some(c) = controller {
+                controllers.insert((msg[0], c));
+            }
+        }
+        let breakpoints = self.automation.iter().map(|(_, p)| p.len()).max();
+        (
+            per_block,
+            self.automation.len() + controllers.len(),
+            per_block + breakpoints.unwrap_or(0).min(block) + 2,
+        )
+    }
+}
+
+/// Linear interpolation over sorted breakpoints; holds the end values outside them.
+fn value_at(points: &[(usize, ParamValue)], frame: usize) -> ParamValue {
+    let i = points.partition_point(|&(f, _)| f <= frame);
+    match (i.checked_sub(1).map(|j| points[j]), points.get(i)) {
+        (None, Some(&(_, v))) | (Some((_, v)), None) => v,
+        (Some((f0, v0)), Some(&(f1, v1))) => {
+            v0 + (v1 - v0) * (frame - f0) as f64 / (f1 - f0) as f64
+        }
+        (None, None) => 0.0,
+    }
+}
+
+impl PluginInstance {
+    /// Request `config`'s channel counts on the main buses; aux buses keep the
+    /// channels they report. If the plugin rejects them, fall back to what its main
+    /// buses report. Returns the (input, output) channels.
+    pub fn negotiate_channels(&self, inputs: usize, outputs: usize) -> (usize, usize) {
+        let (ins, outs) = self.channel_arrangements(inputs, outputs);
+        if self.set_bus_arrangements(&ins, &outs).is_ok() {
+            return (inputs, outputs);
+        }
+        (
+            self.main_bus_channels(BUS_DIR_INPUT),
+            self.main_bus_channels(BUS_DIR_OUTPUT),
+        )
+    }
+
+    /// The input and output arrangements [`negotiate_channels`](Self::negotiate_channels)
+    /// requests for these channel counts. Each bus keeps the family of the layout the
+    /// plugin has there, as [`arrangement_for_channels_like`] does.
+    pub fn channel_arrangements(&self, inputs: usize, outputs: usize) -> (Vec<u64>, Vec<u64>) {
+        let like = |direction: i32, index: usize, channels: usize| {
+            let proposed = self
+                .processor()
+                .bus_arrangement(direction, index as i32)
+                .unwrap_or(speaker_arr::EMPTY);
+            arrangement_for_channels_like(channels, proposed)
+        };
+        let mut ins: Vec<u64> = (inputs > 0)
+            .then(|| like(BUS_DIR_INPUT, 0, inputs))
+            .into_iter()
+            .collect();
+        if inputs > 0 {
+            let aux = self.input_bus_channels().into_iter().enumerate().skip(1);
+            ins.extend(aux.map(|(i, channels)| like(BUS_DIR_INPUT, i, channels)));
+        }
+        let mut outs = vec![like(BUS_DIR_OUTPUT, 0, outputs)];
+        let aux = self.output_bus_channels().into_iter().enumerate().skip(1);
+        outs.extend(aux.map(|(i, channels)| like(BUS_DIR_OUTPUT, i, channels)));
+        (ins, outs)
+    }
+
+    /// Channels of the first audio bus in `direction`; 0 if there is none. Capped at
+    /// the instance's [`HostLimits`](crate::HostLimits) so a bogus count cannot size the buffers.
+    pub(crate) fn main_bus_channels(&self, direction: i32) -> usize {
+        if self.component().bus_count(MEDIA_TYPE_AUDIO, direction) < 1 {
+            return 0;
+        }
+        let max = self.limits().max_channels_per_bus;
+        self.component()
+            .bus_info(MEDIA_TYPE_AUDIO, direction, 0)
+            .map_or(0, |b| b.channel_count.clamp(0, max) as usize)
+    }
+
+    /// Channels of every audio input bus, main bus first, each capped at
+    /// [`max_channels_per_bus`](crate::HostLimits::max_channels_per_bus).
+    pub fn input_bus_channels(&self) -> Vec<usize> {
+        self.bus_channels(BUS_DIR_INPUT)
+    }
+
+    /// Like [`input_bus_channels`](Self::input_bus_channels), for the outputs.
+    pub fn output_bus_channels(&self) -> Vec<usize> {
+        self.bus_channels(BUS_DIR_OUTPUT)
+    }
+
+    fn bus_channels(&self, direction: i32) -> Vec<usize> {
+        let max = self.limits().max_channels_per_bus;
+        let count = self.component().bus_count(MEDIA_TYPE_AUDIO, direction);
+        (0..count.clamp(0, self.limits().max_buses as i32))
+            .map(|i| {
+                self.component()
+                    .bus_info(MEDIA_TYPE_AUDIO, direction, i)
+                    .map_or(0, |b| b.channel_count.clamp(0, max) as usize)
+            })
+            .collect()
+    }
+
+    /// Tail to render after the input ends: `getTailSamples`, with an infinite tail
+    /// capped at `cap` frames.
+    pub fn tail_frames(&self, cap: usize) -> usize {
+        self.processor().tail().frames(cap).0
+    }
+
+    /// Render `frames + config.tail` frames of output, plus the plugin's tail with
+    /// [`plugin_tail`](RenderConfig::plugin_tail). `input` is planar; missing
+    /// channels and frames past its end are silence. `progress(done, total)` is called
+    /// after every block. The instance is left deactivated.
+    pub fn render_offline(
+        &mut self,
+        config: &RenderConfig,
+        input: &[Vec<f32>],
+        frames: usize,
+        progress: impl FnMut(usize, usize),
+    ) -> Result<Vec<Vec<f32>>, HostError> {
+        let events = RenderEvents::default();
+        self.render_offline_with(
+            config,
+            input,
+            frames,
+            &events,
+            &MidiConverter::new(),
+            progress,
+        )
+    }
+
+    /// [`render_offline`](Self::render_offline) with MIDI and automation. MIDI
+    /// controllers reach the plugin through `converter`'s assignments.
+    /// Fails with [`HostError::NoSidechainBus`] before activating when there is a
+    /// [`sidechain`](RenderConfig::sidechain) but no input bus 1 to feed it to.
+    /// Fails with [`HostError::LimitExceeded`] before activating when a block would
+    /// need more than [`max_events_per_block`](crate::HostLimits::max_events_per_block)
+    /// events or points.
+    pub fn render_offline_with(
+        &mut self,
+        config: &RenderConfig,
+        input: &[Vec<f32>],
+        frames: usize,
+        events: &RenderEvents,
+        converter: &MidiConverter,
+        progress: impl FnMut(usize, usize),
+    ) -> Result<Vec<Vec<f32>>, HostError> {
+        self.render_offline_buses(config, input, frames, events, converter, progress)
+            .map(RenderedAudio::into_main)
+    }
+
+    /// [`render_offline_with`](Self::render_offline_with), keeping the output buses
+    /// apart: the main bus and, with [`aux_outputs`](RenderConfig::aux_outputs), every
+    /// other one, each with its name and whether the plugin kept it silent.
+    pub fn render_offline_buses(
+        &mut self,
+        config: &RenderConfig,
+        input: &[Vec<f32>],
+        frames: usize,
+        events: &RenderEvents,
+        converter: &MidiConverter,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<RenderedAudio, HostError> {
+        let block = config.block_size.max(1);
+        let (per_block, params, points) = events.capacity(block);
+        let limits = self.limits();
+        limits.check(Limit::EventsPerBlock, per_block, LimitOrigin::External)?;
+        limits.check(Limit::EventsPerBlock, points, LimitOrigin::External)?;
+        let priority = config.priority.map(promote_current_thread);
+        let mut input_buses = vec![config.input_channels];
+        let mut aux = Vec::new();
+        if config.sidechain.is_some() {
+            let channels = self.input_bus_channels();
+            input_buses.push(*channels.get(1).ok_or(HostError::NoSidechainBus)?);
+            aux.push((BUS_DIR_INPUT, SIDECHAIN_BUS));
+        }
+        let mut output_buses = vec![config.output_channels];
+        if config.aux_outputs {
+            output_buses.extend(self.output_bus_channels().into_iter().skip(1));
+            aux.extend((1..output_buses.len() as i32).map(|i| (BUS_DIR_OUTPUT, i)));
+        }
+        let setup = ProcessSetupBuilder::new()
+            .sample_rate(config.sample_rate)
+            .max_block(block as i32)
+            .mode(ProcessMode::Offline)
+            .tail_flag(config.tail > 0 || config.plugin_tail.is_some())
+            .build()?;
+        let activated = aux
+            .iter()
+            .try_for_each(|&(direction, index)| {
+                self.component()
+                    .activate_bus(MEDIA_TYPE_AUDIO, direction, index, true)
+            })
+            .and_then(|()| self.activate(&setup));
+        let result = activated.and_then(|()| {
+            let latency = self.processor().latency_samples() as usize;
+            let (plugin_tail, tail_capped) = config
+                .plugin_tail
+                .map_or((0, false), |max| self.processor().tail().frames(max));
+            let wanted = frames + config.tail + plugin_tail;
+            let total = wanted + latency;
+
+            let mut bound = BoundProcessData::<f32>::with_buses(&input_buses, &output_buses, block);
+            let sources = [Some(input), config.sidechain.as_deref()];
+            let mut rendered: Vec<RenderedBus> = output_buses
+                .iter()
+                .enumerate()
+                .map(|(i, &channels)| RenderedBus {
+                    index: i as i32,
+                    name: self
+                        .component()
+                        .bus_name(MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT, i as i32)
+                        .map(|name| name.to_string())
+                        .unwrap_or_default(),
+                    channels: vec![Vec::with_capacity(wanted); channels],
+                    silent: true,
+                })
+                .collect();
+            let mut event_list = EventList::with_capacity(per_block.max(1));
+            let mut changes = ParameterChanges::with_capacity(params.max(1), points);
+            let mut done = 0;
+            while done < total {
+                let n = block.min(total - done);
+                for (bus, source) in sources.iter().enumerate() {
+                    fill_input(
+                        bound.input_bus_mut(bus),
+                        source.unwrap_or_default(),
+                        done,
+                        n,
+                    );
+                }
+                let queued = !events.is_empty();
+                if queued {
+                    events.fill_block(done, n, converter, &mut event_list, &mut changes);
+                }
+                let lists = queued.then_some((&mut *event_list, &mut *changes));
+                self.process_bound(&mut bound, n, lists)?;
+                // Drop the frames that only fill the plugin's latency.
+                let skip = latency.saturating_sub(done).min(n);
+                for (i, bus) in rendered.iter_mut().enumerate() {
+                    let all = match bus.channels.len() {
+                        c if c >= 64 => u64::MAX,
+                        c => (1 << c) - 1,
+                    };
+                    bus.silent &= bound.output_silence_flags(i) & all == all;
+                    for (dst, src) in bus.channels.iter_mut().zip(bound.output_bus(i)) {
+                        dst.extend_from_slice(&src[skip..n]);
+                    }
+                }
+                done += n;
+                progress(done, total);
+            }
+            Ok(RenderedAudio {
+                sample_rate: config.sample_rate,
+                buses: rendered,
+                tail_capped,
+                priority,
+            })
+        });
+        self.deactivate();
+        for &(direction, index) in &aux {
+            let _ = self
+                .component()
+                .activate_bus(MEDIA_TYPE_AUDIO, direction, index, false);
+        }
+        result
+    }
+}
+
+/// Copy the `n` frames of `source` from `start` into `channels`, padding with silence.
+fn fill_input<'a>(
+    channels: impl Iterator<Item = &'a mut [f32]>,
+    source: &[Vec<f32>],
+    start: usize,
+    n: usize,
+) {
+    for (c, buf) in channels.enumerate() {
+        buf.fill(0.0);
+        if let Some(src) = source.get(c).and_then(|ch| ch.get(start.min(ch.len())..)) {
+            let len = src.len().min(n);
+            buf[..len].copy_from_slice(&src[..len]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_events_fill_block_ramps_automation_and_places_midi() {
+        let events = RenderEvents {
+            midi: vec![(10, [0x90, 60, 100]), (130, [0x80, 60, 0])],
+            automation: vec![(7, vec![(64, 0.0), (192, 1.0)])],
+        };
+        let converter = MidiConverter::new();
+        let mut list = EventList::with_capacity(4);
+        let mut changes = ParameterChanges::with_capacity(2, 8);
+
+        // Before the ramp: the first block sends the initial value, the second nothing.
+        events.fill_block(0, 64, &converter, &mut list, &mut changes);
+        assert_eq!(list.events()[0].sample_offset, 10);
+        assert_eq!(changes.queues()[0].points(), &[(0, 0.0), (63, 0.0)]);
+        events.fill_block(64, 64, &converter, &mut list, &mut changes);
+        assert!(list.is_empty());
+        assert_eq!(
+            changes.queues()[0].points(),
+            &[(0, 0.0), (63, 63.0 / 128.0)]
+        );
+
+        // A breakpoint inside the block is passed through at its own offset.
+        events.fill_block(128, 128, &converter, &mut list, &mut changes);
+        assert_eq!(list.events()[0].sample_offset, 2);
+        assert_eq!(
+            changes.queues()[0].points(),
+            &[(0, 0.5), (64, 1.0), (127, 1.0)]
+        );
+        events.fill_block(256, 64, &converter, &mut list, &mut changes);
+        assert!(changes.is_empty());
+    }
+}
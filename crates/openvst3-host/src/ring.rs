@@ -0,0 +1,182 @@
+//! Fixed-capacity single-producer/single-consumer ring for realtime threads.
+//!
+//! Neither side allocates, locks or blocks after [`ring`] returns: the producer writes
+//! what fits and reports how much that was, the consumer reads what is there. Typical
+//! use is audio or MIDI data moving between an audio callback and another thread.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct Shared<T> {
+    slots: Box<[UnsafeCell<T>]>,
+    /// Total items ever read; only the consumer stores it.
+    read: AtomicUsize,
+    /// Total items ever written; only the producer stores it.
+    written: AtomicUsize,
+}
+
+// Each slot is accessed by exactly one side at a time, as ordered by the counters.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn slot(&self, pos: usize) -> *mut T {
+        self.slots[pos % self.slots.len()].get()
+    }
+}
+
+/// Writing end of a [`ring`].
+pub struct Producer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// Reading end of a [`ring`].
+pub struct Consumer<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// A ring holding at most `capacity` items (at least one).
+pub fn ring<T: Copy + Default + Send>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    let slots = (0..capacity.max(1))
+        .map(|_| UnsafeCell::new(T::default()))
+        .collect();
+    let shared = Arc::new(Shared {
+        slots,
+        read: AtomicUsize::new(0),
+        written: AtomicUsize::new(0),
+    });
+    (
+        Producer {
+            shared: shared.clone(),
+        },
+        Consumer { shared },
+    )
+}
+
+impl<T: Copy> Producer<T> {
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity()
+    }
+
+    /// Room for this many more items.
+    pub fn free(&self) -> usize {
+        let written = self.shared.written.load(Ordering::Relaxed);
+        let read = self.shared.read.load(Ordering::Acquire);
+        self.shared.capacity() - written.wrapping_sub(read)
+    }
+
+    /// Append as much of `items` as fits; returns how many were written.
+    pub fn push_slice(&mut self, items: &[T]) -> usize {
+        let n = items.len().min(self.free());
+        let written = self.shared.written.load(Ordering::Relaxed);
+        for (i, item) in items[..n].iter().enumerate() {
+            unsafe { *self.shared.slot(written.wrapping_add(i)) = *item };
+        }
+        self.shared
+            .written
+            .store(written.wrapping_add(n), Ordering::Release);
+        n
+    }
+
+    /// Append one item unless the ring is full.
+    pub fn push(&mut self, item: T) -> bool {
+        self.push_slice(std::slice::from_ref(&item)) == 1
+    }
+}
+
+impl<T: Copy> Consumer<T> {
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity()
+    }
+
+    /// Items ready to be read.
+    pub fn available(&self) -> usize {
+        let written = self.shared.written.load(Ordering::Acquire);
+        let read = self.shared.read.load(Ordering::Relaxed);
+        written.wrapping_sub(read)
+    }
+
+    /// Fill the front of `out` with the oldest items; returns how many were read.
+    pub fn pop_slice(&mut self, out: &mut [T]) -> usize {
+        let n = out.len().min(self.available());
+        let read = self.shared.read.load(Ordering::Relaxed);
+        for (i, slot) in out[..n].iter_mut().enumerate() {
+            *slot = unsafe { *self.shared.slot(read.wrapping_add(i)) };
+        }
+        self.shared
+            .read
+            .store(read.wrapping_add(n), Ordering::Release);
+        n
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let read = self.shared.read.load(Ordering::Relaxed);
+        if self.available() == 0 {
+            return None;
+        }
+        let item = unsafe { *self.shared.slot(read) };
+        self.shared
+            .read
+            .store(read.wrapping_add(1), Ordering::Release);
+        Some(item)
+    }
+
+    /// The oldest item, without consuming it.
+    pub fn peek(&self) -> Option<T> {
+        let read = self.shared.read.load(Ordering::Relaxed);
+        (self.available() > 0).then(|| unsafe { *self.shared.slot(read) })
+    }
+
+    /// Drop up to `n` of the oldest items; returns how many were dropped.
+    pub fn skip(&mut self, n: usize) -> usize {
+        let n = n.min(self.available());
+        let read = self.shared.read.load(Ordering::Relaxed);
+        self.shared
+            .read
+            .store(read.wrapping_add(n), Ordering::Release);
+        n
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_moves_items_between_threads_in_order() {
+        let (mut tx, mut rx) = ring::<u32>(5);
+        assert_eq!(tx.push_slice(&[1, 2, 3, 4, 5, 6, 7]), 5);
+        assert_eq!(tx.free(), 0);
+        assert!(!tx.push(8));
+        assert_eq!(rx.skip(2), 2);
+        assert_eq!(rx.peek(), Some(3));
+        let mut out = [0; 8];
+        assert_eq!(tx.push_slice(&[6, 7]), 2);
+        // Reads wrap around the end of the storage.
+        assert_eq!(rx.pop_slice(&mut out), 5);
+        assert_eq!(out[..5], [3, 4, 5, 6, 7]);
+        assert_eq!(rx.pop(), None);
+
+        let (mut tx, mut rx) = ring::<u64>(64);
+        let writer = std::thread::spawn(move || {
+            let mut next = 0;
+            while next < 10_000 {
+                next += tx.push_slice(&(next..next + 16).collect::<Vec<_>>()) as u64;
+            }
+        });
+        let mut expected = 0;
+        let mut buf = [0; 32];
+        while expected < 10_000 {
+            let n = rx.pop_slice(&mut buf);
+            for &v in &buf[..n] {
+                assert_eq!(v, expected);
+                expected += 1;
+            }
+        }
+        writer.join().unwrap();
+    }
+}
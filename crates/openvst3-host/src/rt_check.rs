@@ -0,0 +1,59 @@
+//! Allocation counting for tests of realtime code.
+//!
+//! A test binary installs [`CountingAllocator`] as its `#[global_allocator]` and
+//! wraps the code that must not allocate, e.g. a block through a
+//! [`BlockIoPool`](crate::pool::BlockIoPool), in [`assert_no_alloc`]. Counts are
+//! per thread, so other tests running in parallel do not disturb them.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn count() {
+    // `try_with`: the allocator also runs while thread locals are torn down.
+    let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+}
+
+/// The system allocator, counting allocations and reallocations per thread.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Allocations on this thread so far; always 0 without [`CountingAllocator`].
+pub fn allocations() -> usize {
+    ALLOCATIONS.try_with(Cell::get).unwrap_or(0)
+}
+
+/// Run `f`, panicking if it allocated on this thread.
+pub fn assert_no_alloc<R>(f: impl FnOnce() -> R) -> R {
+    let before = allocations();
+    let result = f();
+    let allocated = allocations() - before;
+    assert_eq!(
+        allocated, 0,
+        "{allocated} allocations in a realtime section"
+    );
+    result
+}
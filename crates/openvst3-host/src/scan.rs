@@ -0,0 +1,1198 @@
+//! Find `.vst3` bundles on disk and read their audio classes without instantiating
+//! anything.
+//!
+//! [`Scanner`] walks the standard VST3 locations plus any extra paths, scans bundles on
+//! a small thread pool and, with [`Scanner::isolated`], runs each bundle in a worker
+//! process so a crashing or hanging plugin becomes a [`ScanFailure`] instead of taking
+//! the host down. Results can be carried between runs in a [`ScanCache`]; unchanged
+//! bundles are not loaded again. A class installed in more than one place is reported
+//! as a [`DuplicateGroup`], with one install chosen by the [`DuplicatePolicy`].
+//! [`ScanDiff`] tells what changed between two scans.
+
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Waker};
+use std::time::{Duration, SystemTime};
+
+use openvst3_abi::classinfo_consts;
+
+use crate::crash_marker::{self, CRASH_TAG};
+use crate::{fmt_cid_hex, isolate, parse_hex_16, BundlePath, HostError, Module, ScanPhase};
+
+/// Folders below a search root are followed this deep looking for bundles.
+const MAX_DEPTH: usize = 8;
+
+/// One audio class found in a bundle.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct ScannedPlugin {
+    /// The bundle (or single-file module) the class came from.
+    pub path: PathBuf,
+    #[cfg_attr(feature = "serde", serde(with = "cid_hex"))]
+    pub cid: [u8; 16],
+    pub name: String,
+    /// The class vendor, or the factory's when the class has none.
+    pub vendor: String,
+    pub version: String,
+    /// `|`-separated subcategory tags, e.g. `"Fx|Delay"`.
+    pub sub_categories: String,
+    pub sdk_version: String,
+    /// SHA-256 of the module binary in hex, when the scanner
+    /// [hashes binaries](Scanner::hash_binaries).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub binary_hash: Option<String>,
+}
+
+/// Why a bundle produced no plugins.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase", tag = "kind")
+)]
+pub enum ScanFailure {
+    /// The module could not be resolved, loaded or asked for its factory.
+    Load { detail: String },
+    /// The isolated worker died from a signal or an access violation, in `phase` if
+    /// its crash handler reported one.
+    Crashed {
+        #[cfg_attr(feature = "serde", serde(default))]
+        phase: Option<ScanPhase>,
+        detail: String,
+        /// Raw frames the crash handler wrote, where the platform provides them.
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Vec::is_empty")
+        )]
+        backtrace: Vec<String>,
+    },
+    /// The isolated worker did not finish in time and was killed.
+    TimedOut { after: Duration },
+    /// The worker could not be started or its output made no sense.
+    Worker { detail: String },
+}
+
+impl fmt::Display for ScanFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanFailure::Load { detail } => write!(f, "load failed: {detail}"),
+            ScanFailure::Crashed {
+                phase: Some(phase),
+                detail,
+                ..
+            } => write!(f, "crashed in {phase}: {detail}"),
+            ScanFailure::Crashed { detail, .. } => write!(f, "crashed: {detail}"),
+            ScanFailure::TimedOut { after } => write!(f, "timed out after {after:?}"),
+            ScanFailure::Worker { detail } => write!(f, "scan worker failed: {detail}"),
+        }
+    }
+}
+
+impl From<HostError> for ScanFailure {
+    fn from(e: HostError) -> Self {
+        match e {
+            HostError::Crashed(detail) => ScanFailure::Crashed {
+                phase: None,
+                detail,
+                backtrace: Vec::new(),
+            },
+            HostError::TimedOut { after, .. } => ScanFailure::TimedOut { after },
+            HostError::WorkerSpawn(detail) => ScanFailure::Worker { detail },
+            e => ScanFailure::Load {
+                detail: e.to_string(),
+            },
+        }
+    }
+}
+
+pub type BundleResult = Result<Vec<ScannedPlugin>, ScanFailure>;
+
+/// A bundle that could not be scanned.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FailedBundle {
+    pub path: PathBuf,
+    pub failure: ScanFailure,
+}
+
+/// How [`Scanner::scan`] picks one install of a class found in several bundles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub enum DuplicatePolicy {
+    /// An install under a per-user folder wins; otherwise as [`PathOrder`].
+    ///
+    /// [`PathOrder`]: DuplicatePolicy::PathOrder
+    #[default]
+    UserDirWins,
+    /// The highest class version by [`compare_versions`]; equal versions go by path
+    /// order.
+    NewestVersion,
+    /// The install under the earliest search root wins, then the first path.
+    PathOrder,
+}
+
+/// One class (by CID) found in more than one bundle.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct DuplicateGroup {
+    #[cfg_attr(feature = "serde", serde(with = "cid_hex"))]
+    pub cid: [u8; 16],
+    /// The install the [`DuplicatePolicy`] picked.
+    pub chosen: ScannedPlugin,
+    /// The others, best first.
+    pub shadowed: Vec<ScannedPlugin>,
+}
+
+/// What [`Scanner::scan`] found, in bundle path order.
+#[derive(Clone, Debug, Default)]
+pub struct ScanReport {
+    /// Every install, including shadowed ones.
+    pub plugins: Vec<ScannedPlugin>,
+    /// Classes in [`plugins`](Self::plugins) more than once, by CID.
+    pub duplicates: Vec<DuplicateGroup>,
+    pub failures: Vec<FailedBundle>,
+    /// Bundles whose result came from the cache.
+    pub cached: usize,
+    /// Bundles that were loaded during this scan.
+    pub scanned: usize,
+}
+
+/// A cached result, valid while the module binary keeps its modification time.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct CacheEntry {
+    pub modified: Option<SystemTime>,
+    pub result: BundleResult,
+}
+
+/// Scan results keyed by bundle path. Serializable with the `serde` feature.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct ScanCache {
+    pub entries: BTreeMap<PathBuf, CacheEntry>,
+}
+
+type WorkerCommand = dyn Fn(&Path) -> Command + Send + Sync;
+
+/// Configurable bundle scanner; see the module docs.
+pub struct Scanner {
+    paths: Vec<PathBuf>,
+    /// Folders whose installs [`DuplicatePolicy::UserDirWins`] prefers.
+    user_paths: Vec<PathBuf>,
+    policy: DuplicatePolicy,
+    parallel: usize,
+    worker: Option<Arc<WorkerCommand>>,
+    timeout: Duration,
+    rescan_failed: bool,
+    #[cfg(feature = "hash")]
+    hash: bool,
+    cache: ScanCache,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scanner {
+    /// A scanner over [`Scanner::default_paths`], one bundle at a time, in-process.
+    pub fn new() -> Self {
+        Self {
+            paths: Self::default_paths(),
+            user_paths: Self::default_user_paths(),
+            policy: DuplicatePolicy::default(),
+            parallel: 1,
+            worker: None,
+            timeout: Duration::from_secs(30),
+            rescan_failed: false,
+            #[cfg(feature = "hash")]
+            hash: false,
+            cache: ScanCache::default(),
+        }
+    }
+
+    /// The per-user and system-wide VST3 folders of this platform, per-user first.
+    pub fn default_paths() -> Vec<PathBuf> {
+        let env = |var: &str| std::env::var_os(var).map(PathBuf::from);
+        let mut paths = Self::default_user_paths();
+        if cfg!(target_os = "macos") {
+            paths.push("/Library/Audio/Plug-ins/VST3".into());
+        } else if cfg!(windows) {
+            if let Some(common) = env("COMMONPROGRAMFILES") {
+                paths.push(common.join("VST3"));
+            }
+        } else {
+            paths.push("/usr/lib/vst3".into());
+            paths.push("/usr/local/lib/vst3".into());
+        }
+        paths
+    }
+
+    /// The per-user VST3 folders of this platform.
+    pub fn default_user_paths() -> Vec<PathBuf> {
+        let env = |var: &str| std::env::var_os(var).map(PathBuf::from);
+        let user = if cfg!(target_os = "macos") {
+            env("HOME").map(|home| home.join("Library/Audio/Plug-ins/VST3"))
+        } else if cfg!(windows) {
+            env("LOCALAPPDATA").map(|local| local.join("Programs").join("Common").join("VST3"))
+        } else {
+            env("HOME").map(|home| home.join(".vst3"))
+        };
+        user.into_iter().collect()
+    }
+
+    /// Forget the default paths; only [`Scanner::add_path`] roots are searched.
+    pub fn without_default_paths(mut self) -> Self {
+        self.paths.clear();
+        self
+    }
+
+    /// Also search `path`: a folder containing bundles, or a bundle itself.
+    pub fn add_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.paths.push(path.into());
+        self
+    }
+
+    /// Search `path` and count it as a per-user folder for
+    /// [`DuplicatePolicy::UserDirWins`].
+    pub fn add_user_path(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        self.user_paths.push(path.clone());
+        self.paths.push(path);
+        self
+    }
+
+    /// How to choose between installs of the same class (default
+    /// [`DuplicatePolicy::UserDirWins`]).
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Scan up to `n` bundles at the same time (at least one).
+    pub fn parallel(mut self, n: usize) -> Self {
+        self.parallel = n.max(1);
+        self
+    }
+
+    /// Scan each bundle in a child process built by `worker`, which must end up
+    /// calling [`run_worker_process`] (or [`run_worker`], without crash phases) for
+    /// the bundle path it was given.
+    pub fn isolated(mut self, worker: impl Fn(&Path) -> Command + Send + Sync + 'static) -> Self {
+        self.worker = Some(Arc::new(worker));
+        self
+    }
+
+    /// How long an isolated worker may take per bundle (default 30 s). In-process
+    /// scans are not interrupted.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Start from the results of an earlier scan.
+    pub fn with_cache(mut self, cache: ScanCache) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Rescan bundles whose cached result is a failure even if they did not change.
+    pub fn rescan_failed(mut self, yes: bool) -> Self {
+        self.rescan_failed = yes;
+        self
+    }
+
+    /// The search roots, in order.
+    /// Fill [`ScannedPlugin::binary_hash`], reading every module binary once per scan
+    /// (cached results keep the hash they were stored with).
+    #[cfg(feature = "hash")]
+    pub fn hash_binaries(mut self, yes: bool) -> Self {
+        self.hash = yes;
+        self
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Results of the last scan (and whatever the cache started with).
+    pub fn cache(&self) -> &ScanCache {
+        &self.cache
+    }
+
+    pub fn into_cache(self) -> ScanCache {
+        self.cache
+    }
+
+    /// Every bundle under the search roots, sorted and deduplicated. Missing roots are
+    /// skipped.
+    pub fn find_bundles(&self) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        for root in &self.paths {
+            collect_bundles(root, 0, &mut found);
+        }
+        found.sort();
+        found.dedup();
+        found
+    }
+
+    /// Scan every bundle. Failures are collected, never fatal; the cache is replaced
+    /// by this scan's results.
+    pub fn scan(&mut self) -> ScanReport {
+        self.scan_events().into_report()
+    }
+
+    /// Scan every bundle like [`scan`](Self::scan), handing out a [`ScanEvent`] as
+    /// each bundle starts and ends. Cached bundles come first; the rest follow in
+    /// the order the pool finishes them.
+    pub fn scan_events(&mut self) -> ScanEvents<'_> {
+        let mut pending = VecDeque::new();
+        let mut entries = BTreeMap::new();
+        let mut todo = Vec::new();
+        let mut modified_at = BTreeMap::new();
+        for path in self.find_bundles() {
+            let modified = modified(&path);
+            match self.cache.entries.get(&path) {
+                Some(entry)
+                    if entry.modified.is_some()
+                        && entry.modified == modified
+                        && (entry.result.is_ok() || !self.rescan_failed) =>
+                {
+                    #[allow(unused_mut)]
+                    let mut entry = entry.clone();
+                    #[cfg(feature = "hash")]
+                    if self.hash {
+                        if let Ok(plugins) = &mut entry.result {
+                            hash_plugins(&path, plugins);
+                        }
+                    }
+                    pending.push_back(ScanEvent::Started(path.clone()));
+                    pending.extend(ScanEvent::of(&path, &entry.result));
+                    entries.insert(path, entry);
+                }
+                _ => {
+                    modified_at.insert(path.clone(), modified);
+                    todo.push(path);
+                }
+            }
+        }
+
+        let cached = entries.len();
+        let total = cached + todo.len();
+        let threads = self.parallel.min(todo.len());
+        let jobs = Arc::new(Jobs {
+            bundles: todo,
+            next: AtomicUsize::new(0),
+            worker: self.worker.clone(),
+            timeout: self.timeout,
+            #[cfg(feature = "hash")]
+            hash: self.hash,
+            waker: Mutex::new(None),
+        });
+        // At most one finished bundle per thread waits to be taken, so a slow
+        // consumer holds the pool back instead of piling up results.
+        let (sender, results) = mpsc::sync_channel(self.parallel);
+        for _ in 0..threads {
+            let (jobs, sender) = (Arc::clone(&jobs), sender.clone());
+            std::thread::spawn(move || jobs.run(sender));
+        }
+        ScanEvents {
+            scanner: self,
+            jobs,
+            results,
+            pending,
+            entries,
+            modified: modified_at,
+            total,
+            cached,
+            done: false,
+        }
+    }
+
+    /// Scan every bundle as a stream of [`ScanEvent`]s, for async code; see
+    /// [`ScanStream`].
+    #[cfg(feature = "async")]
+    pub fn scan_stream(&mut self) -> ScanStream<'_> {
+        ScanStream(self.scan_events())
+    }
+
+    /// Group the classes in `plugins` that appear more than once by CID, and pick
+    /// one of each by the [`DuplicatePolicy`].
+    pub fn duplicates(&self, plugins: &[ScannedPlugin]) -> Vec<DuplicateGroup> {
+        let mut by_cid: BTreeMap<[u8; 16], Vec<&ScannedPlugin>> = BTreeMap::new();
+        for plugin in plugins {
+            by_cid.entry(plugin.cid).or_default().push(plugin);
+        }
+        by_cid
+            .into_iter()
+            .filter(|(_, installs)| installs.len() > 1)
+            .map(|(cid, mut installs)| {
+                installs.sort_by(|a, b| self.prefer(a, b));
+                let mut installs = installs.into_iter().cloned();
+                DuplicateGroup {
+                    cid,
+                    chosen: installs.next().expect("group has installs"),
+                    shadowed: installs.collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// `Less` when install `a` wins over `b`.
+    fn prefer(&self, a: &ScannedPlugin, b: &ScannedPlugin) -> CmpOrdering {
+        let root = |p: &ScannedPlugin| {
+            self.paths
+                .iter()
+                .position(|root| p.path.starts_with(root))
+                .unwrap_or(self.paths.len())
+        };
+        let by_path = root(a).cmp(&root(b)).then_with(|| a.path.cmp(&b.path));
+        match self.policy {
+            DuplicatePolicy::PathOrder => by_path,
+            DuplicatePolicy::UserDirWins => {
+                let user =
+                    |p: &ScannedPlugin| self.user_paths.iter().any(|u| p.path.starts_with(u));
+                user(b).cmp(&user(a)).then(by_path)
+            }
+            DuplicatePolicy::NewestVersion => {
+                compare_versions(&b.version, &a.version).then(by_path)
+            }
+        }
+    }
+}
+
+/// Something that happened during a scan, from [`Scanner::scan_events`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScanEvent {
+    /// The bundle at this path is being scanned (or read from the cache).
+    Started(PathBuf),
+    /// One audio class of a bundle that scanned; a bundle with several classes has
+    /// one event each, and one without any has none.
+    Finished(ScannedPlugin),
+    Failed(PathBuf, ScanFailure),
+}
+
+impl ScanEvent {
+    /// The events of a bundle's result.
+    fn of(path: &Path, result: &BundleResult) -> Vec<ScanEvent> {
+        match result {
+            Ok(plugins) => plugins.iter().cloned().map(ScanEvent::Finished).collect(),
+            Err(failure) => vec![ScanEvent::Failed(path.to_path_buf(), failure.clone())],
+        }
+    }
+}
+
+/// A message from a scan thread.
+enum Message {
+    Started(PathBuf),
+    Done(PathBuf, BundleResult),
+}
+
+/// The bundles of one scan, shared by its threads.
+struct Jobs {
+    bundles: Vec<PathBuf>,
+    next: AtomicUsize,
+    worker: Option<Arc<WorkerCommand>>,
+    timeout: Duration,
+    #[cfg(feature = "hash")]
+    hash: bool,
+    /// The task polling a [`ScanStream`], if it is waiting.
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Jobs {
+    /// Take bundles until there are none left or nobody listens any more.
+    fn run(&self, sender: SyncSender<Message>) {
+        self.take_bundles(&sender);
+        // The stream learns that the scan is over when the last sender is gone.
+        drop(sender);
+        self.wake();
+    }
+
+    fn take_bundles(&self, sender: &SyncSender<Message>) {
+        loop {
+            let i = self.next.fetch_add(1, Ordering::Relaxed);
+            let Some(path) = self.bundles.get(i) else {
+                break;
+            };
+            if !self.send(sender, Message::Started(path.clone())) {
+                break;
+            }
+            #[allow(unused_mut)]
+            let mut result = scan_one(self.worker.as_deref(), self.timeout, path);
+            #[cfg(feature = "hash")]
+            if self.hash {
+                if let Ok(plugins) = &mut result {
+                    hash_plugins(path, plugins);
+                }
+            }
+            if !self.send(sender, Message::Done(path.clone(), result)) {
+                break;
+            }
+        }
+    }
+
+    fn send(&self, sender: &SyncSender<Message>, message: Message) -> bool {
+        let sent = sender.send(message).is_ok();
+        self.wake();
+        sent
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
+/// The events of one scan, from [`Scanner::scan_events`]. Each `next` blocks until
+/// something happens. The scanner's cache is replaced by the scan's results once
+/// the last event was taken; dropping the iterator before that stops the scan after
+/// the bundles in progress and adds the results so far to the cache instead.
+pub struct ScanEvents<'a> {
+    scanner: &'a mut Scanner,
+    jobs: Arc<Jobs>,
+    results: Receiver<Message>,
+    /// Events not handed out yet.
+    pending: VecDeque<ScanEvent>,
+    entries: BTreeMap<PathBuf, CacheEntry>,
+    /// Modification times of the bundles being scanned.
+    modified: BTreeMap<PathBuf, Option<SystemTime>>,
+    total: usize,
+    cached: usize,
+    done: bool,
+}
+
+impl ScanEvents<'_> {
+    /// Bundles in this scan.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Bundles whose result comes from the cache.
+    pub fn cached(&self) -> usize {
+        self.cached
+    }
+
+    /// Bundles that are loaded during this scan.
+    pub fn scanned(&self) -> usize {
+        self.jobs.bundles.len()
+    }
+
+    /// Take the rest of the events and report what the scan found.
+    pub fn into_report(mut self) -> ScanReport {
+        self.by_ref().for_each(drop);
+        let mut report = ScanReport {
+            cached: self.cached,
+            scanned: self.scanned(),
+            ..ScanReport::default()
+        };
+        for (path, entry) in &self.entries {
+            match &entry.result {
+                Ok(plugins) => report.plugins.extend(plugins.iter().cloned()),
+                Err(failure) => report.failures.push(FailedBundle {
+                    path: path.clone(),
+                    failure: failure.clone(),
+                }),
+            }
+        }
+        report.duplicates = self.scanner.duplicates(&report.plugins);
+        report
+    }
+
+    /// The next event, or `Pending` if there is none yet and `blocking` is off.
+    fn poll_event(&mut self, blocking: bool) -> Poll<Option<ScanEvent>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+            if self.done {
+                return Poll::Ready(None);
+            }
+            let message = if blocking {
+                self.results.recv().map_err(|_| TryRecvError::Disconnected)
+            } else {
+                self.results.try_recv()
+            };
+            match message {
+                Ok(Message::Started(path)) => return Poll::Ready(Some(ScanEvent::Started(path))),
+                Ok(Message::Done(path, result)) => self.finish(path, result),
+                Err(TryRecvError::Empty) => return Poll::Pending,
+                Err(TryRecvError::Disconnected) => {
+                    // Every thread is gone; one that panicked left its bundle unscanned.
+                    for path in self.jobs.bundles.clone() {
+                        if !self.entries.contains_key(&path) {
+                            let detail = "scan thread panicked".to_string();
+                            self.finish(path, Err(ScanFailure::Worker { detail }));
+                        }
+                    }
+                    self.done = true;
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, path: PathBuf, result: BundleResult) {
+        self.pending.extend(ScanEvent::of(&path, &result));
+        let modified = self.modified.get(&path).copied().flatten();
+        self.entries.insert(path, CacheEntry { modified, result });
+    }
+}
+
+impl Iterator for ScanEvents<'_> {
+    type Item = ScanEvent;
+
+    fn next(&mut self) -> Option<ScanEvent> {
+        match self.poll_event(true) {
+            Poll::Ready(event) => event,
+            Poll::Pending => unreachable!("a blocking poll waits for the next event"),
+        }
+    }
+}
+
+impl Drop for ScanEvents<'_> {
+    fn drop(&mut self) {
+        let entries = std::mem::take(&mut self.entries);
+        if self.done {
+            self.scanner.cache = ScanCache { entries };
+        } else {
+            self.scanner.cache.entries.extend(entries);
+        }
+    }
+}
+
+/// The events of one scan for async code, from [`Scanner::scan_stream`]; otherwise
+/// the same as [`ScanEvents`].
+///
+/// The bundles are scanned on the scanner's own threads, so any executor can poll
+/// it. It is a [`futures_core::Stream`] for the `futures` combinators;
+/// [`next`](Self::next) needs none of them.
+#[cfg(feature = "async")]
+pub struct ScanStream<'a>(ScanEvents<'a>);
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for ScanStream<'_> {
+    type Item = ScanEvent;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<ScanEvent>> {
+        if let Poll::Ready(event) = self.0.poll_event(false) {
+            return Poll::Ready(event);
+        }
+        *self.0.jobs.waker.lock().unwrap() = Some(cx.waker().clone());
+        // A thread may have sent between the poll and storing the waker.
+        self.0.poll_event(false)
+    }
+}
+
+#[cfg(feature = "async")]
+impl ScanStream<'_> {
+    /// The next event, or `None` once the scan is over.
+    pub async fn next(&mut self) -> Option<ScanEvent> {
+        use futures_core::Stream;
+        std::future::poll_fn(|cx| std::pin::Pin::new(&mut *self).poll_next(cx)).await
+    }
+
+    /// See [`ScanEvents::total`].
+    pub fn total(&self) -> usize {
+        self.0.total()
+    }
+
+    pub fn cached(&self) -> usize {
+        self.0.cached()
+    }
+
+    pub fn scanned(&self) -> usize {
+        self.0.scanned()
+    }
+
+    /// Report what the scan found. Blocks until the scan is over, so call it once
+    /// the stream has ended.
+    pub fn into_report(self) -> ScanReport {
+        self.0.into_report()
+    }
+}
+
+/// Scan `bundle` in-process, or in a child process from `worker`.
+fn scan_one(worker: Option<&WorkerCommand>, timeout: Duration, bundle: &Path) -> BundleResult {
+    let Some(worker) = worker else {
+        return scan_bundle(bundle);
+    };
+    let out = isolate::run_isolated_raw(&mut worker(bundle), "bundle scan", timeout)?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    if let Some(detail) = out.crash {
+        return Err(crashed(detail, &text));
+    }
+    parse_worker_output(bundle, &text).unwrap_or_else(|detail| {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        let last = stderr.lines().last().unwrap_or("no output").trim();
+        Err(ScanFailure::Worker {
+            detail: format!("{detail} ({}: {last})", out.status),
+        })
+    })
+}
+
+/// Fill in the hash of the module binary, unless every plugin has one already.
+#[cfg(feature = "hash")]
+fn hash_plugins(bundle: &Path, plugins: &mut [ScannedPlugin]) {
+    if plugins.iter().all(|p| p.binary_hash.is_some()) {
+        return;
+    }
+    let hash = module_binary(bundle).ok().and_then(|b| hash_file(&b).ok());
+    for plugin in plugins {
+        plugin.binary_hash = hash.clone();
+    }
+}
+
+/// What changed between two scans; see [`ScanDiff::compare`]. Every list is sorted by
+/// CID, then path, so serialized diffs of the same scans are identical.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct ScanDiff {
+    pub added: Vec<ScannedPlugin>,
+    pub removed: Vec<ScannedPlugin>,
+    /// (old, new) pairs.
+    pub updated: Vec<(ScannedPlugin, ScannedPlugin)>,
+}
+
+impl ScanDiff {
+    /// Match the installs of `old` and `new` by CID; a class installed more than once
+    /// is matched by path first, and the rest in path order. A matched pair is
+    /// updated when its version, its binary hash (if both scans have one) or the
+    /// classes of its bundle (CIDs and names) differ.
+    pub fn compare(old: &[ScannedPlugin], new: &[ScannedPlugin]) -> Self {
+        type ByCid<'a> = BTreeMap<[u8; 16], Vec<&'a ScannedPlugin>>;
+        fn by_cid(plugins: &[ScannedPlugin]) -> ByCid<'_> {
+            let mut map: ByCid = BTreeMap::new();
+            for p in plugins {
+                map.entry(p.cid).or_default().push(p);
+            }
+            for installs in map.values_mut() {
+                installs.sort_by(|a, b| a.path.cmp(&b.path));
+            }
+            map
+        }
+        fn classes<'a>(plugins: &'a [ScannedPlugin], path: &Path) -> Vec<(&'a [u8; 16], &'a str)> {
+            let mut classes: Vec<_> = plugins
+                .iter()
+                .filter(|p| p.path == path)
+                .map(|p| (&p.cid, p.name.as_str()))
+                .collect();
+            classes.sort();
+            classes
+        }
+
+        let mut diff = ScanDiff::default();
+        let (mut olds, mut news) = (by_cid(old), by_cid(new));
+        let cids: Vec<[u8; 16]> = olds.keys().chain(news.keys()).copied().collect();
+        for cid in cids {
+            let mut was = olds.remove(&cid).unwrap_or_default();
+            let mut now = news.remove(&cid).unwrap_or_default();
+            let mut pairs = Vec::new();
+            was.retain(|a| match now.iter().position(|b| b.path == a.path) {
+                Some(i) => {
+                    pairs.push((*a, now.remove(i)));
+                    false
+                }
+                None => true,
+            });
+            let n = was.len().min(now.len());
+            pairs.extend(was.drain(..n).zip(now.drain(..n)));
+            for (a, b) in pairs {
+                let rehashed = matches!(
+                    (&a.binary_hash, &b.binary_hash),
+                    (Some(x), Some(y)) if x != y
+                );
+                if a.version != b.version
+                    || rehashed
+                    || classes(old, &a.path) != classes(new, &b.path)
+                {
+                    diff.updated.push((a.clone(), b.clone()));
+                }
+            }
+            diff.removed.extend(was.into_iter().cloned());
+            diff.added.extend(now.into_iter().cloned());
+        }
+        let key = |p: &ScannedPlugin| (p.cid, p.path.clone());
+        diff.added.sort_by_key(key);
+        diff.removed.sort_by_key(key);
+        diff.updated.sort_by_key(|(_, b)| key(b));
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.updated.is_empty()
+    }
+}
+
+/// A crash, with the phase and frames from the worker's last crash record, if any.
+fn crashed(detail: String, stdout: &str) -> ScanFailure {
+    let mut lines = stdout.lines();
+    let mut phase = None;
+    let mut backtrace = Vec::new();
+    while let Some(line) = lines.next() {
+        if let Some(name) = line
+            .strip_prefix(CRASH_TAG)
+            .and_then(|l| l.strip_prefix('\t'))
+        {
+            phase = name.parse().ok();
+            backtrace = lines.by_ref().map(str::trim).map(String::from).collect();
+            backtrace.retain(|frame| !frame.is_empty());
+        }
+    }
+    ScanFailure::Crashed {
+        phase,
+        detail,
+        backtrace,
+    }
+}
+
+/// A piece of a version string: a run of digits or of letters.
+enum VersionPart<'a> {
+    Number(&'a str),
+    Text(&'a str),
+}
+
+fn version_parts(version: &str) -> impl Iterator<Item = VersionPart<'_>> {
+    let version = match version.strip_prefix(['v', 'V']) {
+        Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => rest,
+        _ => version,
+    };
+    let mut rest = version;
+    std::iter::from_fn(move || {
+        rest = rest.trim_start_matches(|c: char| !c.is_alphanumeric());
+        let first = rest.chars().next()?;
+        let digits = first.is_ascii_digit();
+        let end = rest
+            .find(|c: char| !c.is_alphanumeric() || c.is_ascii_digit() != digits)
+            .unwrap_or(rest.len());
+        let (part, tail) = rest.split_at(end);
+        rest = tail;
+        Some(if digits {
+            VersionPart::Number(part.trim_start_matches('0'))
+        } else {
+            VersionPart::Text(part)
+        })
+    })
+}
+
+/// Order two class version strings leniently, semver-style.
+///
+/// Runs of digits compare as numbers (`"1.10" > "1.9"`, `"2021.06" == "2021.6"`),
+/// letters case-insensitively, and separators are ignored. Missing trailing numbers
+/// count as zero (`"1.2" == "1.2.0"`); letters where the other version has ended or
+/// has a number mark a pre-release (`"1.0-beta" < "1.0" < "1.0.1"`). A leading `v`
+/// before a digit is skipped.
+pub fn compare_versions(a: &str, b: &str) -> CmpOrdering {
+    use VersionPart::{Number, Text};
+    let (mut a, mut b) = (version_parts(a), version_parts(b));
+    loop {
+        let ord = match (a.next(), b.next()) {
+            (None, None) => return CmpOrdering::Equal,
+            (Some(Number(n)), None) | (None, Some(Number(n))) if n.is_empty() => continue,
+            (Some(Number(_)), None) | (None, Some(Text(_))) => CmpOrdering::Greater,
+            (None, Some(Number(_))) | (Some(Text(_)), None) => CmpOrdering::Less,
+            (Some(Number(_)), Some(Text(_))) => CmpOrdering::Greater,
+            (Some(Text(_)), Some(Number(_))) => CmpOrdering::Less,
+            // Leading zeros are gone, so the longer number is the bigger one.
+            (Some(Number(x)), Some(Number(y))) => x.len().cmp(&y.len()).then(x.cmp(y)),
+            (Some(Text(x)), Some(Text(y))) => x
+                .chars()
+                .map(|c| c.to_ascii_lowercase())
+                .cmp(y.chars().map(|c| c.to_ascii_lowercase())),
+        };
+        if ord != CmpOrdering::Equal {
+            return ord;
+        }
+    }
+}
+
+fn is_bundle(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("vst3"))
+}
+
+fn collect_bundles(dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+    if is_bundle(dir) {
+        found.push(dir.to_path_buf());
+        return;
+    }
+    if depth > MAX_DEPTH {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if is_bundle(&path) {
+            found.push(path);
+        } else if path.is_dir() {
+            collect_bundles(&path, depth + 1, found);
+        }
+    }
+}
+
+/// The module binary inside a bundle directory, or a single-file module itself.
+fn module_binary(bundle: &Path) -> Result<PathBuf, HostError> {
+    if bundle.is_file() {
+        Ok(bundle.to_path_buf())
+    } else {
+        BundlePath::resolve(bundle)
+    }
+}
+
+/// Lowercase hex SHA-256 of a file's contents.
+#[cfg(feature = "hash")]
+fn hash_file(path: &Path) -> io::Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
+
+fn modified(bundle: &Path) -> Option<SystemTime> {
+    let binary = module_binary(bundle).unwrap_or_else(|_| bundle.to_path_buf());
+    std::fs::metadata(binary).and_then(|m| m.modified()).ok()
+}
+
+/// Load `bundle` in this process and list its audio classes.
+pub fn scan_bundle(bundle: &Path) -> BundleResult {
+    let module = Module::load(module_binary(bundle)?)?;
+    let factory_vendor = module.factory_info().map(|f| f.vendor).unwrap_or_default();
+    let plugins = module
+        .classes()
+        .filter_map(Result::ok)
+        .filter(|c| c.category == classinfo_consts::K_VST_AUDIO_EFFECT_CLASS)
+        .map(|c| ScannedPlugin {
+            path: bundle.to_path_buf(),
+            cid: c.cid,
+            name: c.name.clone(),
+            vendor: if c.vendor.is_empty() {
+                factory_vendor.clone()
+            } else {
+                c.vendor.clone()
+            },
+            version: c.version.clone(),
+            sub_categories: c.sub_categories.clone(),
+            sdk_version: c.sdk_version.clone(),
+            binary_hash: None,
+        })
+        .collect();
+    Ok(plugins)
+}
+
+/// Entry point of a scan worker process: install the [`crash_marker`] handlers, so a
+/// crash is reported with its phase, then [`run_worker`] to stdout.
+pub fn run_worker_process(bundle: &Path) -> io::Result<()> {
+    crash_marker::install();
+    run_worker(bundle, &mut io::stdout().lock())
+}
+
+/// Worker side of [`Scanner::isolated`]: scan `bundle` in this process and write the
+/// result to `out` for the parent to read.
+///
+/// The format is one tab-separated record per line: `plugin CID name vendor version
+/// subcategories sdk` for each class, or a single `failure kind detail`, then `end`.
+pub fn run_worker(bundle: &Path, out: &mut impl Write) -> io::Result<()> {
+    match scan_bundle(bundle) {
+        Ok(plugins) => {
+            for p in plugins {
+                let fields = [
+                    fmt_cid_hex(&p.cid),
+                    p.name,
+                    p.vendor,
+                    p.version,
+                    p.sub_categories,
+                    p.sdk_version,
+                ];
+                write_record(out, "plugin", &fields)?;
+            }
+        }
+        Err(failure) => {
+            let (kind, detail) = match failure {
+                ScanFailure::Load { detail } => ("load", detail),
+                ScanFailure::Crashed { detail, .. } => ("crashed", detail),
+                ScanFailure::TimedOut { after } => ("timeout", after.as_millis().to_string()),
+                ScanFailure::Worker { detail } => ("worker", detail),
+            };
+            write_record(out, "failure", &[kind.to_string(), detail])?;
+        }
+    }
+    write_record(out, "end", &[])?;
+    out.flush()
+}
+
+fn write_record(out: &mut impl Write, tag: &str, fields: &[String]) -> io::Result<()> {
+    write!(out, "{tag}")?;
+    for field in fields {
+        let escaped = field
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n");
+        write!(out, "\t{escaped}")?;
+    }
+    writeln!(out)
+}
+
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// `Err` if the worker output is incomplete or malformed.
+fn parse_worker_output(bundle: &Path, text: &str) -> Result<BundleResult, String> {
+    let mut plugins = Vec::new();
+    for line in text.lines() {
+        let fields: Vec<String> = line.split('\t').map(unescape).collect();
+        match (fields[0].as_str(), &fields[1..]) {
+            ("plugin", [cid, name, vendor, version, sub_categories, sdk_version]) => {
+                plugins.push(ScannedPlugin {
+                    path: bundle.to_path_buf(),
+                    cid: parse_hex_16(cid).map_err(|e| e.to_string())?,
+                    name: name.clone(),
+                    vendor: vendor.clone(),
+                    version: version.clone(),
+                    sub_categories: sub_categories.clone(),
+                    sdk_version: sdk_version.clone(),
+                    binary_hash: None,
+                });
+            }
+            ("failure", [kind, detail]) => {
+                let detail = detail.clone();
+                let failure = match kind.as_str() {
+                    "crashed" => ScanFailure::Crashed {
+                        phase: None,
+                        detail,
+                        backtrace: Vec::new(),
+                    },
+                    "timeout" => ScanFailure::TimedOut {
+                        after: Duration::from_millis(detail.parse().unwrap_or(0)),
+                    },
+                    "worker" => ScanFailure::Worker { detail },
+                    _ => ScanFailure::Load { detail },
+                };
+                return Ok(Err(failure));
+            }
+            ("end", []) => return Ok(Ok(plugins)),
+            _ => return Err(format!("unexpected worker output {line:?}")),
+        }
+    }
+    Err("worker output ended early".into())
+}
+
+#[cfg(feature = "serde")]
+mod cid_hex {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(cid: &[u8; 16], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&crate::fmt_cid_hex(cid))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u8; 16], D::Error> {
+        let hex = String::deserialize(d)?;
+        crate::parse_hex_16(&hex).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_phase_round_trips_through_display() {
+        for phase in [
+            ScanPhase::Load,
+            ScanPhase::Factory,
+            ScanPhase::Class(0),
+            ScanPhase::Class(4_000_000_000),
+            ScanPhase::Instantiate,
+            ScanPhase::Process,
+        ] {
+            assert_eq!(phase.to_string().parse(), Ok(phase));
+        }
+        assert_eq!("class x".parse::<ScanPhase>(), Err(()));
+    }
+
+    #[cfg(feature = "dlopen")]
+    #[test]
+    fn version_strings_compare_leniently() {
+        use std::cmp::Ordering::{Equal, Greater, Less};
+        for (a, b, ord) in [
+            ("1.2.3.456", "1.2.3.45", Greater),
+            ("1.2.3.456", "1.2.3", Greater),
+            ("1.2.3.456", "1.2.4", Less),
+            ("1.2.3.456", "1.2.3.456", Equal),
+            ("2021.06", "2021.6", Equal),
+            ("2021.06", "2021.5", Greater),
+            ("2021.06", "2021.10", Less),
+            ("2021.06", "1.2.3.456", Greater),
+            ("1.10", "1.9", Greater),
+            ("1.2", "1.2.0.0", Equal),
+            ("v1.2", "1.2", Equal),
+            ("1.0.0-beta", "1.0.0", Less),
+            ("1.0.0-beta", "1.0.0-RC", Less),
+            ("1.0.1", "1.0.0-beta", Greater),
+            ("1.0 build 12", "1.0 build 9", Greater),
+            ("", "0.1", Less),
+            ("99999999999999999999999", "9", Greater),
+        ] {
+            assert_eq!(compare_versions(a, b), ord, "{a} vs {b}");
+            assert_eq!(compare_versions(b, a), ord.reverse(), "{b} vs {a}");
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! [`RenderSession`]: one offline render described as data.
+//!
+//! This is the schema of the `offline-render` example's session files, exported so
+//! other tools can build sessions programmatically and serialize them. Unknown fields
+//! are rejected, so a misspelt key is an error rather than a silently ignored setting.
+//! Times are in seconds and parameter values are normalized. Relative paths are meant
+//! relative to the session file; [`RenderSession::resolve_paths`] rewrites them.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use openvst3_abi::{ParamID, ParamValue};
+use serde::{Deserialize, Serialize};
+
+use crate::{ClassRef, HostError};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct RenderSession {
+    /// A `.vst3` bundle directory or the plugin binary.
+    pub plugin: PathBuf,
+    /// Factory index or 32-digit hex CID; the first class by default.
+    #[serde(default = "first_class", with = "class_ref")]
+    pub class: ClassRef,
+    /// `.vstpreset` loaded before rendering.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<PathBuf>,
+    #[serde(default)]
+    pub input: SessionInput,
+    /// Standard MIDI file played into the plugin's event input.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub midi: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub automation: Vec<AutomationLane>,
+    /// Defaults to the input file's rate, or 48 kHz.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<f64>,
+    #[serde(default = "default_block_size")]
+    pub block_size: usize,
+    /// Output channels; defaults to the input file's channel count, or 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channels: Option<usize>,
+    /// Seconds to render before the tail; defaults to the length of the input file or,
+    /// failing that, the MIDI file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub tail: SessionTail,
+    /// The WAV file to write.
+    pub output: PathBuf,
+    #[serde(default)]
+    pub bit_depth: SessionBitDepth,
+}
+
+/// What feeds the main input bus.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase",
+    deny_unknown_fields
+)]
+pub enum SessionInput {
+    /// No input bus audio (instruments, or effects rendered on silence).
+    #[default]
+    Silence,
+    /// A WAV file.
+    File { path: PathBuf },
+    Sine {
+        frequency: f64,
+        #[serde(default = "default_level")]
+        level_db: f64,
+    },
+    /// White noise.
+    Noise {
+        #[serde(default = "default_level")]
+        level_db: f64,
+    },
+    /// One full-scale sample at the start.
+    Impulse,
+}
+
+/// Breakpoints for one parameter; the value ramps linearly between them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AutomationLane {
+    pub param: ParamID,
+    pub points: Vec<AutomationPoint>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AutomationPoint {
+    /// Seconds from the start of the render.
+    pub time: f64,
+    /// Normalized value, 0..=1.
+    pub value: ParamValue,
+}
+
+/// What to render after `duration`: `"auto"` (the plugin's `getTailSamples`) or a
+/// number of seconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SessionTail {
+    #[default]
+    Auto,
+    Seconds(f64),
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionBitDepth {
+    #[serde(rename = "16")]
+    Int16,
+    #[default]
+    #[serde(rename = "24")]
+    Int24,
+    #[serde(rename = "32f")]
+    Float32,
+}
+
+fn first_class() -> ClassRef {
+    ClassRef::Index(0)
+}
+
+fn default_block_size() -> usize {
+    512
+}
+
+fn default_level() -> f64 {
+    -12.0
+}
+
+fn invalid(msg: impl Into<String>) -> HostError {
+    HostError::InvalidSession(msg.into())
+}
+
+impl RenderSession {
+    /// Check what the schema cannot express: positive rates and sizes, generator
+    /// settings, automation order and range, and that the render length is known.
+    pub fn validate(&self) -> Result<(), HostError> {
+        if let Some(sr) = self.sample_rate {
+            if !(sr.is_finite() && sr > 0.0) {
+                return Err(invalid(format!("sampleRate: {sr} is not a positive rate")));
+            }
+        }
+        if self.block_size == 0 {
+            return Err(invalid("blockSize: must be at least 1"));
+        }
+        if self.channels == Some(0) {
+            return Err(invalid("channels: must be at least 1"));
+        }
+        if let Some(d) = self.duration {
+            if !(d.is_finite() && d >= 0.0) {
+                return Err(invalid(format!("duration: {d} is not a length in seconds")));
+            }
+        }
+        if let SessionTail::Seconds(s) = self.tail {
+            if !(s.is_finite() && s >= 0.0) {
+                return Err(invalid(format!("tail: {s} is not a length in seconds")));
+            }
+        }
+        match self.input {
+            SessionInput::Sine {
+                frequency,
+                level_db,
+            } => {
+                if !(frequency.is_finite() && frequency > 0.0) {
+                    return Err(invalid(format!(
+                        "input.frequency: {frequency} is not a positive frequency"
+                    )));
+                }
+                check_level(level_db)?;
+            }
+            SessionInput::Noise { level_db } => check_level(level_db)?,
+            _ => {}
+        }
+        let mut params = BTreeSet::new();
+        for (i, lane) in self.automation.iter().enumerate() {
+            if !params.insert(lane.param) {
+                return Err(invalid(format!(
+                    "automation[{i}]: parameter {} has more than one lane",
+                    lane.param
+                )));
+            }
+            if lane.points.is_empty() {
+                return Err(invalid(format!("automation[{i}].points: empty")));
+            }
+            let mut last = 0.0;
+            for (j, p) in lane.points.iter().enumerate() {
+                let at = format!("automation[{i}].points[{j}]");
+                if !(p.time.is_finite() && p.time >= last) {
+                    return Err(invalid(format!(
+                        "{at}.time: {} is negative or before the previous point",
+                        p.time
+                    )));
+                }
+                if !(0.0..=1.0).contains(&p.value) {
+                    return Err(invalid(format!(
+                        "{at}.value: {} is not a normalized value (0..1)",
+                        p.value
+                    )));
+                }
+                last = p.time;
+            }
+        }
+        let file_input = matches!(self.input, SessionInput::File { .. });
+        if self.duration.is_none() && !file_input && self.midi.is_none() {
+            return Err(invalid(
+                "duration: required when neither an input file nor a MIDI file sets the length",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Make relative paths relative to `base`, usually the session file's directory.
+    pub fn resolve_paths(&mut self, base: &Path) {
+        let resolve = |p: &mut PathBuf| {
+            if p.is_relative() {
+                *p = base.join(&*p);
+            }
+        };
+        resolve(&mut self.plugin);
+        resolve(&mut self.output);
+        if let Some(p) = &mut self.preset {
+            resolve(p);
+        }
+        if let Some(p) = &mut self.midi {
+            resolve(p);
+        }
+        if let SessionInput::File { path } = &mut self.input {
+            resolve(path);
+        }
+    }
+
+    /// The automation lanes as frame breakpoints for
+    /// [`RenderEvents::automation`](crate::RenderEvents::automation).
+    pub fn automation_frames(&self, sample_rate: f64) -> Vec<(ParamID, Vec<(usize, ParamValue)>)> {
+        self.automation
+            .iter()
+            .map(|lane| {
+                let points = lane
+                    .points
+                    .iter()
+                    .map(|p| ((p.time * sample_rate).round() as usize, p.value))
+                    .collect();
+                (lane.param, points)
+            })
+            .collect()
+    }
+}
+
+fn check_level(level_db: f64) -> Result<(), HostError> {
+    if level_db.is_finite() && level_db <= 0.0 {
+        Ok(())
+    } else {
+        Err(invalid(format!(
+            "input.levelDb: {level_db} is not a level in dBFS (at most 0)"
+        )))
+    }
+}
+
+impl Serialize for SessionTail {
+    fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            SessionTail::Auto => s.serialize_str("auto"),
+            SessionTail::Seconds(secs) => s.serialize_f64(*secs),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SessionTail {
+    fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct Visitor;
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = SessionTail;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("\"auto\" or a number of seconds")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<SessionTail, E> {
+                if v == "auto" {
+                    Ok(SessionTail::Auto)
+                } else {
+                    Err(E::invalid_value(serde::de::Unexpected::Str(v), &self))
+                }
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, v: f64) -> Result<SessionTail, E> {
+                Ok(SessionTail::Seconds(v))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<SessionTail, E> {
+                Ok(SessionTail::Seconds(v as f64))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<SessionTail, E> {
+                Ok(SessionTail::Seconds(v as f64))
+            }
+        }
+        d.deserialize_any(Visitor)
+    }
+}
+
+/// A class index as a number, a CID as a 32-digit hex string.
+mod class_ref {
+    use std::fmt;
+
+    use serde::de::{self, Unexpected};
+    use serde::{Deserializer, Serializer};
+
+    use crate::ClassRef;
+
+    pub fn serialize<S: Serializer>(class: &ClassRef, s: S) -> Result<S::Ok, S::Error> {
+        match class {
+            ClassRef::Index(i) => s.serialize_i32(*i),
+            ClassRef::Cid(cid) => s.serialize_str(&crate::fmt_cid_hex(cid)),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<ClassRef, D::Error> {
+        struct Visitor;
+        impl de::Visitor<'_> for Visitor {
+            type Value = ClassRef;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a class index or a 32-digit hex CID")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<ClassRef, E> {
+                i32::try_from(v)
+                    .map(ClassRef::Index)
+                    .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<ClassRef, E> {
+                Err(E::invalid_value(Unexpected::Signed(v), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<ClassRef, E> {
+                crate::parse_hex_16(v)
+                    .map(ClassRef::Cid)
+                    .map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+            }
+        }
+        d.deserialize_any(Visitor)
+    }
+}
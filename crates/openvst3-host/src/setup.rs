@@ -0,0 +1,234 @@
+//! Checked construction of `setupProcessing` arguments.
+//!
+//! A [`ProcessSetup`] filled by hand is easy to get wrong: a zero block size, a
+//! negative sample rate, 64-bit samples for a plugin that only processes 32-bit ones.
+//! [`ProcessSetupBuilder`] starts from working defaults, checks ranges in
+//! [`build`](ProcessSetupBuilder::build) and, given the processor through
+//! [`for_processor`](ProcessSetupBuilder::for_processor), falls back to 32-bit samples
+//! where the plugin cannot do 64 and says so in
+//! [`warnings`](ProcessSetupBuilder::warnings).
+//! [`PluginInstance::activate`](crate::PluginInstance::activate) runs every setup
+//! through it.
+
+use std::fmt;
+
+use openvst3_abi::{process_consts, ProcessSetup};
+use thiserror::Error;
+
+use crate::plugin::{ProcessMode, ProcessorHandle, SymbolicSampleSize};
+
+/// Why [`ProcessSetupBuilder::build`] refused a setup.
+#[derive(Clone, Copy, Debug, Error, PartialEq)]
+pub enum SetupError {
+    #[error("sample rate {0} is not a positive finite number")]
+    SampleRate(f64),
+    #[error("maximum block size {0} is not positive")]
+    MaxBlock(i32),
+    #[error("unknown process mode {0}")]
+    ProcessMode(i32),
+    #[error("unknown symbolic sample size {0}")]
+    SampleSize(i32),
+}
+
+/// Something [`ProcessSetupBuilder`] changed rather than refuse.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetupWarning {
+    /// `canProcessSampleSize` refused `requested`, so the setup uses `used`.
+    PrecisionDowngraded {
+        requested: SymbolicSampleSize,
+        used: SymbolicSampleSize,
+    },
+}
+
+impl fmt::Display for SetupWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetupWarning::PrecisionDowngraded { requested, used } => write!(
+                f,
+                "plugin cannot process {}-bit samples; using {}-bit",
+                requested.bits(),
+                used.bits()
+            ),
+        }
+    }
+}
+
+/// See the module docs. Defaults: 48 kHz, 512-frame blocks, 32-bit samples,
+/// realtime, no tail flag.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProcessSetupBuilder {
+    sample_rate: f64,
+    max_block: i32,
+    precision: SymbolicSampleSize,
+    mode: ProcessMode,
+    tail: bool,
+    /// `canProcessSampleSize(kSample64)`, once asked by `for_processor`.
+    float64: Option<bool>,
+}
+
+impl Default for ProcessSetupBuilder {
+    fn default() -> Self {
+        Self {
+            sample_rate: 48_000.0,
+            max_block: 512,
+            precision: SymbolicSampleSize::Sample32,
+            mode: ProcessMode::Realtime,
+            tail: false,
+            float64: None,
+        }
+    }
+}
+
+impl ProcessSetupBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from an existing setup, e.g. to change its mode. Fails on a process
+    /// mode or sample size with no [`ProcessMode`] or [`SymbolicSampleSize`]; ranges
+    /// are left to [`build`](Self::build).
+    pub fn from_setup(setup: &ProcessSetup) -> Result<Self, SetupError> {
+        Ok(Self {
+            sample_rate: setup.sample_rate,
+            max_block: setup.max_samples_per_block,
+            precision: SymbolicSampleSize::from_raw(setup.symbolic_sample_size)
+                .ok_or(SetupError::SampleSize(setup.symbolic_sample_size))?,
+            mode: ProcessMode::from_raw(setup.process_mode)
+                .ok_or(SetupError::ProcessMode(setup.process_mode))?,
+            tail: setup.flags & process_consts::SETUP_FLAG_TAIL != 0,
+            float64: None,
+        })
+    }
+
+    /// Must be positive and finite.
+    pub fn sample_rate(mut self, sample_rate: f64) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// The longest block `process` will get; must be positive.
+    pub fn max_block(mut self, frames: i32) -> Self {
+        self.max_block = frames;
+        self
+    }
+
+    pub fn precision(mut self, precision: SymbolicSampleSize) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    pub fn mode(mut self, mode: ProcessMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set [`SETUP_FLAG_TAIL`](process_consts::SETUP_FLAG_TAIL): processing goes on
+    /// past the end of the input.
+    pub fn tail_flag(mut self, tail: bool) -> Self {
+        self.tail = tail;
+        self
+    }
+
+    /// Ask `processor` whether it takes 64-bit samples. If it does not, a 64-bit
+    /// [`precision`](Self::precision), set before or after, builds as 32-bit with a
+    /// [`SetupWarning::PrecisionDowngraded`]. 32-bit processing is mandatory, so it
+    /// is never asked about.
+    pub fn for_processor(mut self, processor: &ProcessorHandle) -> Self {
+        self.float64 = Some(processor.can_process_sample_size(SymbolicSampleSize::Sample64));
+        self
+    }
+
+    /// The sample size the setup will have, after any downgrade.
+    pub fn effective_precision(&self) -> SymbolicSampleSize {
+        match (self.precision, self.float64) {
+            (SymbolicSampleSize::Sample64, Some(false)) => SymbolicSampleSize::Sample32,
+            (precision, _) => precision,
+        }
+    }
+
+    /// What [`build`](Self::build) will change from what was asked for.
+    pub fn warnings(&self) -> Vec<SetupWarning> {
+        let used = self.effective_precision();
+        if used == self.precision {
+            return Vec::new();
+        }
+        vec![SetupWarning::PrecisionDowngraded {
+            requested: self.precision,
+            used,
+        }]
+    }
+
+    pub fn build(&self) -> Result<ProcessSetup, SetupError> {
+        if !(self.sample_rate.is_finite() && self.sample_rate > 0.0) {
+            return Err(SetupError::SampleRate(self.sample_rate));
+        }
+        if self.max_block <= 0 {
+            return Err(SetupError::MaxBlock(self.max_block));
+        }
+        Ok(ProcessSetup {
+            process_mode: self.mode.as_raw(),
+            sample_rate: self.sample_rate,
+            max_samples_per_block: self.max_block,
+            symbolic_sample_size: self.effective_precision().as_raw(),
+            flags: if self.tail {
+                process_consts::SETUP_FLAG_TAIL
+            } else {
+                0
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setup_builder_rejects_out_of_range_fields() {
+        let ok = ProcessSetupBuilder::new().tail_flag(true).build().unwrap();
+        assert_eq!(
+            ok,
+            ProcessSetup {
+                process_mode: process_consts::PROCESS_MODE_REALTIME,
+                sample_rate: 48_000.0,
+                max_samples_per_block: 512,
+                symbolic_sample_size: process_consts::SYMBOLIC_SAMPLE_32,
+                flags: process_consts::SETUP_FLAG_TAIL,
+            }
+        );
+        for rate in [0.0, -44_100.0, f64::INFINITY] {
+            assert_eq!(
+                ProcessSetupBuilder::new().sample_rate(rate).build(),
+                Err(SetupError::SampleRate(rate))
+            );
+        }
+        assert!(matches!(
+            ProcessSetupBuilder::new().sample_rate(f64::NAN).build(),
+            Err(SetupError::SampleRate(r)) if r.is_nan()
+        ));
+        for frames in [0, -512] {
+            assert_eq!(
+                ProcessSetupBuilder::new().max_block(frames).build(),
+                Err(SetupError::MaxBlock(frames))
+            );
+        }
+        assert_eq!(
+            ProcessSetupBuilder::from_setup(&ProcessSetup {
+                process_mode: 7,
+                ..ok
+            }),
+            Err(SetupError::ProcessMode(7))
+        );
+        assert_eq!(
+            ProcessSetupBuilder::from_setup(&ProcessSetup {
+                symbolic_sample_size: 2,
+                ..ok
+            }),
+            Err(SetupError::SampleSize(2))
+        );
+        assert_eq!(
+            ProcessSetupBuilder::from_setup(&ok).unwrap().build(),
+            Ok(ok)
+        );
+    }
+}
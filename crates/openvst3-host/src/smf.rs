@@ -0,0 +1,260 @@
+//! Standard MIDI files, reduced to what an offline render needs.
+//!
+//! [`read_smf`] merges all tracks of a format 0 or 1 file into one list of channel
+//! voice messages with their time in seconds, following the file's tempo changes.
+//! Meta events other than Set Tempo, and SysEx, are skipped.
+
+use crate::HostError;
+
+/// Microseconds per quarter note until the first Set Tempo (120 bpm).
+const DEFAULT_TEMPO: u32 = 500_000;
+
+enum Item {
+    Tempo(u32),
+    Message([u8; 3]),
+}
+
+fn invalid(msg: impl Into<String>) -> HostError {
+    HostError::InvalidMidiFile(msg.into())
+}
+
+/// A byte cursor over one chunk.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], HostError> {
+        let end = self.pos.checked_add(n).filter(|&e| e <= self.bytes.len());
+        let end = end.ok_or_else(|| invalid(format!("truncated at byte {}", self.pos)))?;
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn byte(&mut self) -> Result<u8, HostError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, HostError> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, HostError> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Variable-length quantity: at most four bytes of seven bits.
+    fn vlq(&mut self) -> Result<u32, HostError> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let b = self.byte()?;
+            value = (value << 7) | (b & 0x7F) as u32;
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid(format!(
+            "variable-length value too long at byte {}",
+            self.pos
+        )))
+    }
+
+    fn chunk(&mut self) -> Result<([u8; 4], &'a [u8]), HostError> {
+        let id = self.take(4)?;
+        let len = self.u32()? as usize;
+        Ok(([id[0], id[1], id[2], id[3]], self.take(len)?))
+    }
+}
+
+/// Data bytes following a channel voice status.
+fn data_len(status: u8) -> usize {
+    match status & 0xF0 {
+        0xC0 | 0xD0 => 1,
+        _ => 2,
+    }
+}
+
+fn read_track(
+    track: &[u8],
+    index: usize,
+    out: &mut Vec<(u64, usize, Item)>,
+) -> Result<(), HostError> {
+    let mut r = Reader {
+        bytes: track,
+        pos: 0,
+    };
+    let mut tick = 0u64;
+    let mut running = None;
+    let context = |e: HostError| match e {
+        HostError::InvalidMidiFile(msg) => invalid(format!("track {index}: {msg}")),
+        other => other,
+    };
+    while r.pos < track.len() {
+        tick += r.vlq().map_err(context)? as u64;
+        let mut status = r.byte().map_err(context)?;
+        match status {
+            0xFF => {
+                let kind = r.byte().map_err(context)?;
+                let len = r.vlq().map_err(context)? as usize;
+                let body = r.take(len).map_err(context)?;
+                match kind {
+                    0x2F => return Ok(()),
+                    0x51 if len == 3 => {
+                        let tempo = u32::from_be_bytes([0, body[0], body[1], body[2]]);
+                        out.push((tick, index, Item::Tempo(tempo.max(1))));
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            0xF0 | 0xF7 => {
+                let len = r.vlq().map_err(context)? as usize;
+                r.take(len).map_err(context)?;
+                // SysEx cancels running status.
+                running = None;
+                continue;
+            }
+            0x80..=0xEF => running = Some(status),
+            _ => {
+                // A data byte: running status repeats the previous status.
+                status = running.ok_or_else(|| {
+                    invalid(format!(
+                        "track {index}: data byte {status:#04x} without status"
+                    ))
+                })?;
+                r.pos -= 1;
+            }
+        }
+        let mut msg = [status, 0, 0];
+        for slot in msg.iter_mut().skip(1).take(data_len(status)) {
+            *slot = r.byte().map_err(context)? & 0x7F;
+        }
+        out.push((tick, index, Item::Message(msg)));
+    }
+    // Tolerate a missing End of Track.
+    Ok(())
+}
+
+/// The channel voice messages of a standard MIDI file with their time in seconds,
+/// sorted by time. Two-byte messages are padded with 0.
+pub fn read_smf(bytes: &[u8]) -> Result<Vec<(f64, [u8; 3])>, HostError> {
+    let mut r = Reader { bytes, pos: 0 };
+    let (id, header) = r.chunk().map_err(|_| invalid("no MThd header"))?;
+    if &id != b"MThd" || header.len() < 6 {
+        return Err(invalid("no MThd header"));
+    }
+    let mut h = Reader {
+        bytes: header,
+        pos: 0,
+    };
+    let format = h.u16()?;
+    let tracks = h.u16()? as usize;
+    let division = h.u16()?;
+    if format > 1 {
+        return Err(invalid(format!(
+            "format {format} files (independent sequences) are not supported"
+        )));
+    }
+    if division == 0 {
+        return Err(invalid("zero ticks per quarter note"));
+    }
+
+    let mut items = Vec::new();
+    let mut found = 0;
+    while found < tracks && r.pos < bytes.len() {
+        let (id, body) = r.chunk()?;
+        // Unknown chunk types are skipped, as the spec asks.
+        if &id == b"MTrk" {
+            read_track(body, found, &mut items)?;
+            found += 1;
+        }
+    }
+    if found < tracks {
+        return Err(invalid(format!(
+            "header announces {tracks} tracks, found {found}"
+        )));
+    }
+    // By tick, then track order, so a tempo change on track 0 applies to notes on
+    // the same tick in later tracks.
+    items.sort_by_key(|&(tick, track, _)| (tick, track));
+
+    // SMPTE division: frames per second in the (negative) high byte, ticks per frame
+    // in the low byte, independent of tempo.
+    let smpte = (division & 0x8000 != 0)
+        .then(|| (-((division >> 8) as i8) as f64) * (division & 0xFF) as f64);
+    let ppq = division as f64;
+    let mut tempo = DEFAULT_TEMPO;
+    let (mut last_tick, mut seconds) = (0u64, 0.0f64);
+    let mut out = Vec::new();
+    for (tick, _, item) in items {
+        let delta = (tick - last_tick) as f64;
+        seconds += match smpte {
+            Some(ticks_per_second) => delta / ticks_per_second,
+            None => delta * tempo as f64 / 1e6 / ppq,
+        };
+        last_tick = tick;
+        match item {
+            Item::Tempo(t) => tempo = t,
+            Item::Message(msg) => out.push((seconds, msg)),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An SMF with `tracks` MTrk chunks at 96 ticks per quarter note.
+    fn smf(tracks: &[&[u8]]) -> Vec<u8> {
+        let mut out = b"MThd\0\0\0\x06\0\x01".to_vec();
+        out.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+        out.extend_from_slice(&96u16.to_be_bytes());
+        for t in tracks {
+            out.extend_from_slice(b"MTrk");
+            out.extend_from_slice(&(t.len() as u32).to_be_bytes());
+            out.extend_from_slice(t);
+        }
+        out
+    }
+
+    #[test]
+    fn read_smf_merges_tracks_and_follows_tempo_changes() {
+        // Tempo map: 120 bpm, then 60 bpm from tick 96.
+        let tempo: &[u8] = &[
+            0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // 500000 us
+            0x60, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40, // 1000000 us
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        // Note on at tick 0, running-status note off at 96, program change at 192.
+        let notes: &[u8] = &[
+            0x00, 0x90, 60, 100, //
+            0x60, 60, 0, //
+            0x60, 0xC1, 5, //
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let events = read_smf(&smf(&[tempo, notes])).unwrap();
+        assert_eq!(
+            events,
+            vec![
+                (0.0, [0x90, 60, 100]),
+                (0.5, [0x90, 60, 0]),
+                (1.5, [0xC1, 5, 0]),
+            ]
+        );
+
+        assert!(matches!(
+            read_smf(b"RIFF\0\0\0\0"),
+            Err(HostError::InvalidMidiFile(_))
+        ));
+        let truncated = smf(&[&[0x00, 0x90, 60]]);
+        assert!(matches!(
+            read_smf(&truncated),
+            Err(HostError::InvalidMidiFile(msg)) if msg.starts_with("track 0")
+        ));
+    }
+}
@@ -0,0 +1,207 @@
+//! Ramping parameter edits that arrive as jumps.
+//!
+//! A UI or a console sets a parameter by jumping straight to the new value, tens of
+//! times a second. Passed on as is, every jump lands in one block at one sample
+//! offset, and a plugin that does not smooth its parameters itself clicks ("zipper
+//! noise"). A [`ParamSmoother`] turns each new target into a linear ramp over the
+//! configured time instead, written into each block's [`ParameterChanges`] as one
+//! point at the end of the block's part of the ramp until the target is reached; a
+//! plugin following the queue interpolates between those points. Parameters with a
+//! step count snap to the nearest step in one point instead.
+//!
+//! Feed edits from another thread through
+//! [`ParamRingBuffer::drain_smoothed`](crate::ParamRingBuffer::drain_smoothed).
+
+use std::time::Duration;
+
+use openvst3_abi::{ParamID, ParamValue};
+
+use crate::ParameterChanges;
+
+/// One tracked parameter.
+#[derive(Clone, Debug)]
+struct Ramp {
+    id: ParamID,
+    /// 0 for continuous parameters.
+    step_count: i32,
+    /// The value the plugin was last given.
+    value: ParamValue,
+    target: ParamValue,
+    /// Samples left until `target`; 0 when not ramping.
+    remaining: usize,
+}
+
+/// See the module docs.
+#[derive(Clone, Debug)]
+pub struct ParamSmoother {
+    ramp_samples: usize,
+    params: Vec<Ramp>,
+}
+
+impl ParamSmoother {
+    /// Ramps lasting `ramp` at `sample_rate` (at least one sample), for no
+    /// parameters yet.
+    pub fn new(sample_rate: f64, ramp: Duration) -> Self {
+        Self {
+            ramp_samples: ((ramp.as_secs_f64() * sample_rate).round() as usize).max(1),
+            params: Vec::new(),
+        }
+    }
+
+    /// Smooth parameter `id`, which is at `value` now; a `step_count` of 0 means
+    /// continuous. Tracking allocates, so do it before the audio starts.
+    pub fn track(mut self, id: ParamID, step_count: i32, value: ParamValue) -> Self {
+        self.params.retain(|p| p.id != id);
+        self.params.push(Ramp {
+            id,
+            step_count,
+            value,
+            target: value,
+            remaining: 0,
+        });
+        self
+    }
+
+    /// The ramp length in samples.
+    pub fn ramp_samples(&self) -> usize {
+        self.ramp_samples
+    }
+
+    pub fn is_tracked(&self, id: ParamID) -> bool {
+        self.params.iter().any(|p| p.id == id)
+    }
+
+    /// Whether any parameter has yet to reach its target.
+    pub fn is_ramping(&self) -> bool {
+        self.params.iter().any(|p| p.remaining > 0)
+    }
+
+    /// The value parameter `id` was last given, if it is tracked.
+    pub fn value(&self, id: ParamID) -> Option<ParamValue> {
+        self.params.iter().find(|p| p.id == id).map(|p| p.value)
+    }
+
+    /// Head for `value`, starting with the next block: a full ramp from wherever the
+    /// parameter is, or a single step for a stepped parameter. Returns false, and
+    /// does nothing, when `id` is not tracked.
+    pub fn set_target(&mut self, id: ParamID, value: ParamValue) -> bool {
+        let ramp_samples = self.ramp_samples;
+        let Some(param) = self.params.iter_mut().find(|p| p.id == id) else {
+            return false;
+        };
+        let value = value.clamp(0.0, 1.0);
+        if param.step_count > 0 {
+            let steps = param.step_count as f64;
+            param.target = (value * steps).round() / steps;
+            param.remaining = 1;
+        } else {
+            param.target = value;
+            param.remaining = ramp_samples;
+        }
+        if param.target == param.value {
+            param.remaining = 0;
+        }
+        true
+    }
+
+    /// Write the points of the next `frames` samples into `changes` and move every
+    /// ramp on. A point that does not fit leaves its ramp where it was, to be written
+    /// in the next block.
+    pub fn write_block(&mut self, frames: usize, changes: &mut ParameterChanges) {
+        if frames == 0 {
+            return;
+        }
+        for param in self.params.iter_mut().filter(|p| p.remaining > 0) {
+            let n = param.remaining.min(frames);
+            let value = if n == param.remaining {
+                param.target
+            } else {
+                param.value + (param.target - param.value) * n as f64 / param.remaining as f64
+            };
+            if changes.add_point(param.id, n as i32 - 1, value) {
+                param.value = value;
+                param.remaining -= n;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParamRingBuffer;
+
+    /// The points `smoother` writes into each of `blocks` blocks of `frames`.
+    fn smoothed_blocks(
+        smoother: &mut ParamSmoother,
+        frames: usize,
+        blocks: usize,
+    ) -> Vec<Vec<(i32, f64)>> {
+        let mut changes = ParameterChanges::with_capacity(4, 4);
+        (0..blocks)
+            .map(|_| {
+                changes.clear();
+                smoother.write_block(frames, &mut changes);
+                changes
+                    .queues()
+                    .iter()
+                    .flat_map(|q| q.points().iter().copied())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn param_smoother_ramps_continuous_parameters_across_blocks() {
+        // 10 ms at 10 kHz: 100 samples, over blocks of 64.
+        let mut smoother = ParamSmoother::new(10_000.0, Duration::from_millis(10)).track(7, 0, 0.0);
+        assert_eq!(smoother.ramp_samples(), 100);
+        assert!(smoother.set_target(7, 1.0));
+        assert!(!smoother.set_target(8, 1.0));
+        let blocks = smoothed_blocks(&mut smoother, 64, 3);
+        assert_eq!(blocks[0], vec![(63, 0.64)]);
+        // The ramp ends 36 samples into the second block; nothing after that.
+        assert_eq!(blocks[1], vec![(35, 1.0)]);
+        assert!(blocks[2].is_empty());
+        assert!(!smoother.is_ramping());
+
+        // A new target mid-ramp ramps on from where the parameter got to.
+        smoother.set_target(7, 0.0);
+        smoothed_blocks(&mut smoother, 50, 1);
+        assert_eq!(smoother.value(7), Some(0.5));
+        smoother.set_target(7, 1.0);
+        let blocks = smoothed_blocks(&mut smoother, 50, 2);
+        assert_eq!(blocks, vec![vec![(49, 0.75)], vec![(49, 1.0)]]);
+    }
+
+    #[test]
+    fn param_smoother_snaps_stepped_parameters() {
+        let mut smoother = ParamSmoother::new(48_000.0, Duration::from_millis(20))
+            .track(1, 0, 0.5)
+            .track(2, 4, 0.0);
+        smoother.set_target(2, 0.6);
+        // Same target: no point.
+        smoother.set_target(1, 0.5);
+        let blocks = smoothed_blocks(&mut smoother, 32, 2);
+        assert_eq!(blocks, vec![vec![(0, 0.5)], vec![]]);
+        assert_eq!(smoother.value(2), Some(0.5));
+    }
+
+    #[test]
+    fn param_ring_buffer_feeds_the_smoother() {
+        let (mut tx, mut rx) = ParamRingBuffer::with_capacity(8);
+        let mut smoother = ParamSmoother::new(1_000.0, Duration::from_millis(100)).track(1, 0, 0.0);
+        assert!(tx.send(1, 0.2));
+        assert!(tx.send(1, 1.0));
+        assert!(tx.send(9, 0.3));
+        let mut changes = ParameterChanges::with_capacity(2, 4);
+        assert_eq!(rx.drain_smoothed(&mut smoother, 25, &mut changes), 3);
+        let queues = changes.queues();
+        // The untracked parameter jumps; the tracked one heads for its latest target.
+        assert_eq!((queues[0].id(), queues[0].points()), (9, &[(0, 0.3)][..]));
+        assert_eq!((queues[1].id(), queues[1].points()), (1, &[(24, 0.25)][..]));
+        changes.clear();
+        assert_eq!(rx.drain_smoothed(&mut smoother, 25, &mut changes), 0);
+        assert_eq!(changes.queues()[0].points(), &[(24, 0.5)]);
+    }
+}
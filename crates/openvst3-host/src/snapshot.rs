@@ -0,0 +1,123 @@
+//! A/B comparison: whole-plugin state captured and recalled between blocks.
+//!
+//! A [`StateSnapshot`] holds the component state, the controller state and every
+//! parameter value. [`PluginInstance::recall`] applies it while the
+//! [`LiveProcessor`](crate::LiveProcessor) is muted, the same way
+//! [`reload`](PluginInstance::reload) swaps processors, so a realtime callback never
+//! runs a block against half-applied state. [`PluginInstance::toggle_ab`] keeps two
+//! such snapshots and switches between them.
+
+use openvst3_abi::{ParamID, ParamValue};
+
+use crate::{HostError, MemoryStream, PluginInstance};
+
+/// See the module docs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateSnapshot {
+    component: Vec<u8>,
+    /// `None` without a controller on the component.
+    controller: Option<Vec<u8>>,
+    /// Writable parameters and their normalized values, in list order.
+    params: Vec<(ParamID, ParamValue)>,
+}
+
+impl StateSnapshot {
+    /// Read the instance's current state.
+    pub fn capture(instance: &PluginInstance) -> Result<Self, HostError> {
+        let component = instance.component_state()?;
+        let (controller, params) = match instance.controller() {
+            Some(controller) => {
+                let state = instance.controller_state()?;
+                let params = controller
+                    .parameters()?
+                    .into_iter()
+                    .filter(|p| !p.is_read_only())
+                    .map(|p| (p.id, controller.get_param_normalized(p.id)))
+                    .collect();
+                (state, params)
+            }
+            None => (None, Vec::new()),
+        };
+        Ok(Self {
+            component,
+            controller,
+            params,
+        })
+    }
+
+    pub fn component_state(&self) -> &[u8] {
+        &self.component
+    }
+
+    pub fn controller_state(&self) -> Option<&[u8]> {
+        self.controller.as_deref()
+    }
+
+    pub fn params(&self) -> &[(ParamID, ParamValue)] {
+        &self.params
+    }
+}
+
+/// Which of the two [`toggle_ab`](PluginInstance::toggle_ab) slots is playing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AbSlot {
+    #[default]
+    A,
+    B,
+}
+
+impl AbSlot {
+    fn other(self) -> Self {
+        match self {
+            AbSlot::A => AbSlot::B,
+            AbSlot::B => AbSlot::A,
+        }
+    }
+}
+
+/// The snapshot of the slot that is not playing, once there is one.
+#[derive(Debug, Default)]
+pub(crate) struct AbSlots {
+    current: AbSlot,
+    other: Option<StateSnapshot>,
+}
+
+impl PluginInstance {
+    /// Apply `snapshot`: component `setState`, `setComponentState` and `setState` on
+    /// the controller, then the parameter values, which the controller shows
+    /// afterwards. A realtime callback is muted for the whole recall. Parameters the
+    /// controller refuses are skipped.
+    pub fn recall(&self, snapshot: &StateSnapshot) -> Result<(), HostError> {
+        self.live().while_muted(|| {
+            self.set_component_state(&snapshot.component)?;
+            let Some(controller) = self.controller() else {
+                return Ok(());
+            };
+            if let Some(state) = &snapshot.controller {
+                controller.set_state(&mut MemoryStream::from_bytes(state.clone()))?;
+            }
+            for &(id, value) in &snapshot.params {
+                let _ = controller.set_param_normalized(id, value);
+            }
+            Ok(())
+        })
+    }
+
+    /// The A/B slot currently playing; [`AbSlot::A`] until the first toggle.
+    pub fn ab_slot(&self) -> AbSlot {
+        self.ab.current
+    }
+
+    /// Store the current state in the playing slot and switch to the other one. The
+    /// first toggle starts B as a copy of A; later ones recall what the other slot
+    /// held when it was left. On failure the playing slot does not change.
+    pub fn toggle_ab(&mut self) -> Result<AbSlot, HostError> {
+        let leaving = StateSnapshot::capture(self)?;
+        if let Some(other) = &self.ab.other {
+            self.recall(other)?;
+        }
+        self.ab.other = Some(leaving);
+        self.ab.current = self.ab.current.other();
+        Ok(self.ab.current)
+    }
+}
@@ -0,0 +1,375 @@
+//! Host-side `IBStream` over a growable byte buffer, used for component/controller state.
+
+use core::ffi::c_void;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use openvst3_abi::{
+    iids, stream_consts, tresult, FUnknown, Fuid, IBStream, IBStreamVTable, K_INTERNAL_ERR,
+    K_INVALID_ARG, K_NO_INTERFACE, K_RESULT_FALSE, K_RESULT_OK,
+};
+
+use crate::callback::contain;
+use crate::{HostError, Limit, LimitOrigin};
+
+/// In-memory `IBStream`. The host owns it through the `Box`; plugin-side
+/// `addRef`/`release` are counted but never free the object. A panic in one of its
+/// methods poisons the stream; see [`callback`](crate::callback). A stream made
+/// [`with_limit`](Self::with_limit) refuses writes that would grow it past the limit.
+/// Every stream refuses writes that start more than
+/// [`MAX_WRITE_GAP`](Self::MAX_WRITE_GAP) bytes past its end, so a seek far ahead
+/// cannot make it allocate the gap.
+#[repr(C)]
+pub struct MemoryStream {
+    vtbl: *const IBStreamVTable,
+    refs: AtomicU32,
+    poisoned: AtomicBool,
+    data: Vec<u8>,
+    pos: usize,
+    /// Writes may not end past this many bytes.
+    limit: usize,
+    /// The end of the largest write refused for the limit, 0 for none.
+    refused: usize,
+    /// Who writes into the stream, for [`check_limit`](Self::check_limit).
+    origin: LimitOrigin,
+}
+
+static MEMORY_STREAM_VTBL: IBStreamVTable = IBStreamVTable {
+    query_interface: ms_query_interface,
+    add_ref: ms_add_ref,
+    release: ms_release,
+    read: ms_read,
+    write: ms_write,
+    seek: ms_seek,
+    tell: ms_tell,
+};
+
+impl MemoryStream {
+    /// How far past the end a write may start; the gap is zeroed.
+    pub const MAX_WRITE_GAP: usize = 64 << 10;
+
+    pub fn new() -> Box<Self> {
+        Self::from_bytes(Vec::new())
+    }
+
+    /// Stream positioned at the start of `data`, ready to be read by a plugin.
+    pub fn from_bytes(data: Vec<u8>) -> Box<Self> {
+        Box::new(Self {
+            vtbl: &MEMORY_STREAM_VTBL,
+            refs: AtomicU32::new(1),
+            poisoned: AtomicBool::new(false),
+            data,
+            pos: 0,
+            limit: usize::MAX,
+            refused: 0,
+            origin: LimitOrigin::Plugin,
+        })
+    }
+
+    /// Empty stream that refuses to grow past `limit` bytes, filled by `origin`:
+    /// [`LimitOrigin::Plugin`] for `getState` from a plugin that may be broken,
+    /// [`LimitOrigin::External`] for state the caller copies in. See
+    /// [`check_limit`](Self::check_limit).
+    pub fn with_limit(limit: usize, origin: LimitOrigin) -> Box<Self> {
+        let mut stream = Self::new();
+        stream.limit = limit;
+        stream.origin = origin;
+        stream
+    }
+
+    /// [`HostError::LimitExceeded`] if a write was refused for the limit, naming how
+    /// large it would have made the stream and who wrote it.
+    pub fn check_limit(&self) -> Result<(), HostError> {
+        match self.refused {
+            0 => Ok(()),
+            value => Err(HostError::LimitExceeded {
+                limit: Limit::StateBytes,
+                value,
+                max: self.limit,
+                origin: self.origin,
+            }),
+        }
+    }
+
+    /// Pointer to hand to `getState`/`setState`. Valid while `self` is alive.
+    pub fn as_ibstream(&mut self) -> *mut IBStream {
+        self as *mut Self as *mut IBStream
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn rewind(&mut self) {
+        self.pos = 0;
+    }
+
+    /// Whether a plugin call on the stream panicked; it then refuses every call.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Acquire)
+    }
+}
+
+unsafe fn stream<'a>(this: *mut IBStream) -> &'a mut MemoryStream {
+    &mut *(this as *mut MemoryStream)
+}
+
+/// Run the body of a vtable method on the stream at `this` under its poison flag.
+unsafe fn guarded<R>(
+    this: *mut IBStream,
+    failed: R,
+    body: impl FnOnce(&mut MemoryStream) -> R,
+) -> R {
+    let s = stream(this);
+    // Through a pointer, so the body can borrow the stream mutably.
+    let poisoned = &s.poisoned as *const AtomicBool;
+    contain(&*poisoned, failed, || body(s))
+}
+
+unsafe extern "C" fn ms_query_interface(
+    this_: *mut FUnknown,
+    iid: *const Fuid,
+    obj: *mut *mut c_void,
+) -> tresult {
+    guarded(this_ as *mut IBStream, K_INTERNAL_ERR, |s| {
+        if obj.is_null() || iid.is_null() {
+            return K_INVALID_ARG;
+        }
+        if *iid == iids::IBSTREAM || *iid == iids::FUNKNOWN {
+            s.refs.fetch_add(1, Ordering::Relaxed);
+            *obj = this_ as *mut c_void;
+            return K_RESULT_OK;
+        }
+        *obj = core::ptr::null_mut();
+        K_NO_INTERFACE
+    })
+}
+
+unsafe extern "C" fn ms_add_ref(this_: *mut FUnknown) -> u32 {
+    guarded(this_ as *mut IBStream, 1, |s| {
+        s.refs.fetch_add(1, Ordering::Relaxed) + 1
+    })
+}
+
+unsafe extern "C" fn ms_release(this_: *mut FUnknown) -> u32 {
+    guarded(this_ as *mut IBStream, 1, |s| {
+        crate::events::release(&s.refs)
+    })
+}
+
+unsafe extern "C" fn ms_read(
+    this_: *mut IBStream,
+    buffer: *mut c_void,
+    num_bytes: i32,
+    num_bytes_read: *mut i32,
+) -> tresult {
+    guarded(this_, K_INTERNAL_ERR, |s| {
+        if num_bytes < 0 || (buffer.is_null() && num_bytes > 0) {
+            return K_INVALID_ARG;
+        }
+        let avail = s.data.len().saturating_sub(s.pos);
+        let n = avail.min(num_bytes as usize);
+        if n > 0 {
+            core::ptr::copy_nonoverlapping(s.data.as_ptr().add(s.pos), buffer as *mut u8, n);
+        }
+        s.pos += n;
+        if !num_bytes_read.is_null() {
+            *num_bytes_read = n as i32;
+        }
+        if n == num_bytes as usize {
+            K_RESULT_OK
+        } else {
+            K_RESULT_FALSE
+        }
+    })
+}
+
+unsafe extern "C" fn ms_write(
+    this_: *mut IBStream,
+    buffer: *const c_void,
+    num_bytes: i32,
+    num_bytes_written: *mut i32,
+) -> tresult {
+    guarded(this_, K_INTERNAL_ERR, |s| {
+        if num_bytes < 0 || (buffer.is_null() && num_bytes > 0) {
+            return K_INVALID_ARG;
+        }
+        if s.pos.saturating_sub(s.data.len()) > MemoryStream::MAX_WRITE_GAP {
+            return K_INVALID_ARG;
+        }
+        let n = num_bytes as usize;
+        let end = match s.pos.checked_add(n) {
+            Some(end) if end <= s.limit => end,
+            end => {
+                s.refused = s.refused.max(end.unwrap_or(usize::MAX));
+                return K_RESULT_FALSE;
+            }
+        };
+        if s.data.len() < end {
+            s.data.resize(end, 0);
+        }
+        if n > 0 {
+            core::ptr::copy_nonoverlapping(buffer as *const u8, s.data.as_mut_ptr().add(s.pos), n);
+        }
+        s.pos = end;
+        if !num_bytes_written.is_null() {
+            *num_bytes_written = num_bytes;
+        }
+        K_RESULT_OK
+    })
+}
+
+unsafe extern "C" fn ms_seek(
+    this_: *mut IBStream,
+    pos: i64,
+    mode: i32,
+    result: *mut i64,
+) -> tresult {
+    guarded(this_, K_INTERNAL_ERR, |s| {
+        let base = match mode {
+            stream_consts::SEEK_SET => 0i64,
+            stream_consts::SEEK_CUR => s.pos as i64,
+            stream_consts::SEEK_END => s.data.len() as i64,
+            _ => return K_INVALID_ARG,
+        };
+        let target = match base.checked_add(pos) {
+            Some(target) if target >= 0 => target,
+            _ => return K_INVALID_ARG,
+        };
+        s.pos = target as usize;
+        if !result.is_null() {
+            *result = target;
+        }
+        K_RESULT_OK
+    })
+}
+
+unsafe extern "C" fn ms_tell(this_: *mut IBStream, pos: *mut i64) -> tresult {
+    guarded(this_, K_INTERNAL_ERR, |s| {
+        if pos.is_null() {
+            return K_INVALID_ARG;
+        }
+        *pos = s.pos as i64;
+        K_RESULT_OK
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_stream_vtable_reads_writes_and_seeks() {
+        use openvst3_abi::{stream_consts, IBStream};
+
+        let mut stream = MemoryStream::new();
+        unsafe {
+            let s = stream.as_ibstream();
+            let mut n = 0;
+            assert_eq!(
+                (*s).write(b"abcdef".as_ptr().cast(), 6, &mut n),
+                K_RESULT_OK
+            );
+            assert_eq!(n, 6);
+            let mut pos = 0;
+            assert_eq!(
+                (*s).seek(-4, stream_consts::SEEK_END, &mut pos),
+                K_RESULT_OK
+            );
+            assert_eq!(pos, 2);
+            let mut buf = [0u8; 8];
+            assert_eq!(
+                (*s).read(buf.as_mut_ptr().cast(), 8, &mut n),
+                K_RESULT_FALSE
+            );
+            assert_eq!((n, &buf[..4]), (4, &b"cdef"[..]));
+            assert_eq!((*s).tell(&mut pos), K_RESULT_OK);
+            assert_eq!(pos, 6);
+            assert_eq!(
+                (*s).seek(-7, stream_consts::SEEK_CUR, &mut pos),
+                K_INVALID_ARG
+            );
+            assert_eq!((*s).read(core::ptr::null_mut(), 1, &mut n), K_INVALID_ARG);
+            assert_eq!(
+                (*s).seek(8, stream_consts::SEEK_SET, core::ptr::null_mut()),
+                K_RESULT_OK
+            );
+            assert_eq!(
+                (*s).write(b"!".as_ptr().cast(), 1, core::ptr::null_mut()),
+                K_RESULT_OK
+            );
+
+            let mut out: *mut c_void = core::ptr::null_mut();
+            let unknown = s as *mut FUnknown;
+            assert_eq!(
+                (*unknown).query_interface(&iids::IBSTREAM, &mut out),
+                K_RESULT_OK
+            );
+            assert_eq!(out as *mut IBStream, s);
+            assert_eq!(
+                (*unknown).query_interface(&iids::ICOMPONENT, &mut out),
+                K_NO_INTERFACE
+            );
+            assert!(out.is_null());
+            assert_eq!((*unknown).add_ref(), 3);
+            // Plugin references never free the host's stream.
+            assert_eq!(FUnknown::release(unknown), 2);
+            assert_eq!(FUnknown::release(unknown), 1);
+            assert_eq!(FUnknown::release(unknown), 1);
+        }
+        assert_eq!(stream.bytes(), b"abcdef\0\0!");
+        assert!(!stream.is_poisoned());
+    }
+
+    #[test]
+    fn memory_stream_refuses_far_seeks_and_writes() {
+        use openvst3_abi::stream_consts;
+
+        let mut stream = MemoryStream::new();
+        unsafe {
+            let s = stream.as_ibstream();
+            let mut pos = 0;
+            assert_eq!(
+                (*s).seek(i64::MAX, stream_consts::SEEK_SET, &mut pos),
+                K_RESULT_OK
+            );
+            // Overflowing the position is refused, not wrapped.
+            assert_eq!(
+                (*s).seek(1, stream_consts::SEEK_CUR, &mut pos),
+                K_INVALID_ARG
+            );
+            assert_eq!(pos, i64::MAX);
+            // A write that far out would have to allocate everything before it.
+            let mut n = -1;
+            assert_eq!((*s).write(b"!".as_ptr().cast(), 1, &mut n), K_INVALID_ARG);
+            assert_eq!(n, -1);
+
+            let gap = MemoryStream::MAX_WRITE_GAP as i64;
+            assert_eq!(
+                (*s).seek(gap + 1, stream_consts::SEEK_SET, core::ptr::null_mut()),
+                K_RESULT_OK
+            );
+            assert_eq!(
+                (*s).write(b"!".as_ptr().cast(), 1, core::ptr::null_mut()),
+                K_INVALID_ARG
+            );
+            assert_eq!(
+                (*s).seek(gap, stream_consts::SEEK_SET, core::ptr::null_mut()),
+                K_RESULT_OK
+            );
+            assert_eq!(
+                (*s).write(b"!".as_ptr().cast(), 1, core::ptr::null_mut()),
+                K_RESULT_OK
+            );
+        }
+        assert_eq!(stream.bytes().len(), MemoryStream::MAX_WRITE_GAP + 1);
+        assert!(!stream.is_poisoned());
+    }
+}
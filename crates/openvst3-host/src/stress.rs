@@ -0,0 +1,400 @@
+//! Soak testing: a seeded random walk through legal host operations.
+//!
+//! [`PluginInstance::stress_test`] activates the instance and then, step after step,
+//! either processes a block of random length over noise or does one of the things
+//! [`StressAllow`] permits: reactivating, switching the sample rate or the maximum
+//! block size, a state round trip, a storm of parameter changes in one block, or
+//! flipping the bypass parameter. After every step it checks that the output is
+//! finite, the reported latency is not negative and the component state can still be
+//! read. The first violation or plugin error ends the walk.
+//!
+//! The walk is a function of the seed alone, so the same seed against the same
+//! plugin replays it exactly; the [`StressReport`] lists every operation up to the
+//! failure.
+
+use std::fmt;
+
+use openvst3_abi::{ParamID, ParameterFlags, ProcessSetup, BUS_DIR_INPUT, BUS_DIR_OUTPUT};
+
+use crate::{
+    analyze_block, BoundProcessData, EventList, HostError, ParameterChanges, PluginInstance,
+    ProcessSetupBuilder,
+};
+
+const SAMPLE_RATES: [f64; 6] = [22_050.0, 44_100.0, 48_000.0, 88_200.0, 96_000.0, 192_000.0];
+const BLOCK_SIZES: [usize; 8] = [1, 16, 64, 128, 256, 512, 1024, 2048];
+/// Parameters changed by one storm, and points per parameter, at most.
+const STORM_PARAMS: usize = 16;
+const STORM_POINTS: usize = 4;
+
+/// Which operations besides processing a stress run may pick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StressAllow {
+    /// Deactivate and activate again with the same setup.
+    pub activate_cycles: bool,
+    /// Reactivate at another sample rate.
+    pub sample_rate_changes: bool,
+    /// Reactivate with another maximum block size.
+    pub block_size_changes: bool,
+    /// Load the component state the plugin just saved.
+    pub state_roundtrips: bool,
+    /// Several points each for up to 16 parameters in one block.
+    pub param_storms: bool,
+    /// Flip the parameter flagged `kIsBypass`; never picked without one.
+    pub bypass_toggles: bool,
+}
+
+impl StressAllow {
+    pub fn all() -> Self {
+        Self {
+            activate_cycles: true,
+            sample_rate_changes: true,
+            block_size_changes: true,
+            state_roundtrips: true,
+            param_storms: true,
+            bypass_toggles: true,
+        }
+    }
+
+    /// Processing only.
+    pub fn none() -> Self {
+        Self {
+            activate_cycles: false,
+            sample_rate_changes: false,
+            block_size_changes: false,
+            state_roundtrips: false,
+            param_storms: false,
+            bypass_toggles: false,
+        }
+    }
+}
+
+impl Default for StressAllow {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StressOptions {
+    pub seed: u64,
+    /// Steps after the first activation.
+    pub iterations: usize,
+    pub allow: StressAllow,
+}
+
+impl Default for StressOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            iterations: 1000,
+            allow: StressAllow::all(),
+        }
+    }
+}
+
+/// One step of a stress run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StressOp {
+    /// Deactivate if active, then activate with the current setup.
+    Activate,
+    /// Process a block of this many frames.
+    Process(usize),
+    /// Reactivate at this sample rate.
+    SampleRate(f64),
+    /// Reactivate with this maximum block size.
+    BlockSize(usize),
+    StateRoundtrip,
+    /// Process a full block with changes to this many parameters.
+    ParamStorm(usize),
+    /// Process a full block that sets the bypass parameter on or off.
+    Bypass(bool),
+}
+
+impl fmt::Display for StressOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StressOp::Activate => f.write_str("activate"),
+            StressOp::Process(n) => write!(f, "process {n} frames"),
+            StressOp::SampleRate(sr) => write!(f, "sample rate {sr} Hz"),
+            StressOp::BlockSize(n) => write!(f, "max block size {n}"),
+            StressOp::StateRoundtrip => f.write_str("state round trip"),
+            StressOp::ParamStorm(n) => write!(f, "param storm over {n} parameters"),
+            StressOp::Bypass(on) => write!(f, "bypass {}", if *on { "on" } else { "off" }),
+        }
+    }
+}
+
+/// The step that ended a stress run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StressFailure {
+    /// Index into [`StressReport::ops`].
+    pub step: usize,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StressReport {
+    pub seed: u64,
+    /// Every operation performed, the failing one last.
+    pub ops: Vec<StressOp>,
+    pub failure: Option<StressFailure>,
+}
+
+impl StressReport {
+    pub fn is_success(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// One summary line; after a failure, the numbered operations up to it.
+impl fmt::Display for StressReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(failure) = &self.failure else {
+            return write!(f, "seed {}: {} steps, passed", self.seed, self.ops.len());
+        };
+        write!(
+            f,
+            "seed {}: failed at step {} ({}): {}",
+            self.seed, failure.step, self.ops[failure.step], failure.message
+        )?;
+        for (i, op) in self.ops.iter().enumerate() {
+            write!(f, "\n{i:>6}  {op}")?;
+        }
+        Ok(())
+    }
+}
+
+/// SplitMix64: tiny, and the same sequence everywhere for a seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..n`; `n` must not be 0.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Uniform in `0..1`.
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Activate,
+    SampleRate,
+    BlockSize,
+    StateRoundtrip,
+    ParamStorm,
+    Bypass,
+}
+
+/// What a run carries from step to step.
+struct Walk {
+    rng: Rng,
+    setup: ProcessSetup,
+    bound: BoundProcessData<f32>,
+    inputs: usize,
+    outputs: usize,
+    events: Box<EventList>,
+    changes: Box<ParameterChanges>,
+    params: Vec<ParamID>,
+    bypass: Option<(ParamID, bool)>,
+}
+
+impl Walk {
+    /// The next operation: processing half the time, else one of `menu`.
+    fn pick(&mut self, menu: &[Kind]) -> StressOp {
+        let block = self.bound.max_frames();
+        if menu.is_empty() || self.rng.below(2) == 0 {
+            return StressOp::Process(1 + self.rng.below(block));
+        }
+        match menu[self.rng.below(menu.len())] {
+            Kind::Activate => StressOp::Activate,
+            Kind::SampleRate => {
+                let others: Vec<f64> = SAMPLE_RATES
+                    .into_iter()
+                    .filter(|&sr| sr != self.setup.sample_rate)
+                    .collect();
+                StressOp::SampleRate(others[self.rng.below(others.len())])
+            }
+            Kind::BlockSize => {
+                let others: Vec<usize> = BLOCK_SIZES.into_iter().filter(|&n| n != block).collect();
+                StressOp::BlockSize(others[self.rng.below(others.len())])
+            }
+            Kind::StateRoundtrip => StressOp::StateRoundtrip,
+            Kind::ParamStorm => {
+                StressOp::ParamStorm(1 + self.rng.below(self.params.len().min(STORM_PARAMS)))
+            }
+            Kind::Bypass => StressOp::Bypass(!self.bypass.is_some_and(|(_, on)| on)),
+        }
+    }
+
+    /// Perform `op`, then check the invariants.
+    fn step(&mut self, plugin: &mut PluginInstance, op: StressOp) -> Result<(), String> {
+        let failed = |e: HostError| e.to_string();
+        match op {
+            StressOp::Activate => plugin.activate(&self.setup).map_err(failed)?,
+            StressOp::Process(n) => self.process(plugin, n, false)?,
+            StressOp::SampleRate(sr) => {
+                self.setup.sample_rate = sr;
+                plugin.activate(&self.setup).map_err(failed)?;
+            }
+            StressOp::BlockSize(n) => {
+                self.setup.max_samples_per_block = n as i32;
+                self.bound = BoundProcessData::new(self.inputs, self.outputs, n);
+                plugin.activate(&self.setup).map_err(failed)?;
+            }
+            StressOp::StateRoundtrip => {
+                let state = plugin.component_state().map_err(failed)?;
+                plugin.set_component_state(&state).map_err(failed)?;
+            }
+            StressOp::ParamStorm(count) => {
+                let frames = self.bound.max_frames();
+                self.changes.clear();
+                for _ in 0..count {
+                    let id = self.params[self.rng.below(self.params.len())];
+                    let mut offset = 0;
+                    for _ in 0..1 + self.rng.below(STORM_POINTS) {
+                        offset += self.rng.below(frames.div_ceil(STORM_POINTS));
+                        let value = self.rng.unit();
+                        self.changes
+                            .add_point(id, offset.min(frames - 1) as i32, value);
+                    }
+                }
+                self.process(plugin, frames, true)?;
+            }
+            StressOp::Bypass(on) => {
+                let Some((id, _)) = self.bypass else {
+                    return Ok(());
+                };
+                self.changes.clear();
+                self.changes.add_point(id, 0, if on { 1.0 } else { 0.0 });
+                self.bypass = Some((id, on));
+                self.process(plugin, self.bound.max_frames(), true)?;
+            }
+        }
+        let latency = plugin.processor().latency_samples() as i32;
+        if latency < 0 {
+            return Err(format!("getLatencySamples returned {latency}"));
+        }
+        plugin
+            .component_state()
+            .map(drop)
+            .map_err(|e| format!("state no longer retrievable: {e}"))
+    }
+
+    /// Process `frames` of noise, with `changes` when `with_changes`; the output must
+    /// be finite.
+    fn process(
+        &mut self,
+        plugin: &mut PluginInstance,
+        frames: usize,
+        with_changes: bool,
+    ) -> Result<(), String> {
+        for channel in self.bound.inputs_mut() {
+            for s in &mut channel[..frames] {
+                *s = (self.rng.unit() * 2.0 - 1.0) as f32 * 0.5;
+            }
+        }
+        if !with_changes {
+            self.changes.clear();
+        }
+        self.events.clear();
+        let lists = Some((&mut *self.events, &mut *self.changes));
+        let stats = plugin
+            .process_bound(&mut self.bound, frames, lists)
+            .map_err(|e| e.to_string())?;
+        if stats.nothing_to_do() {
+            return Ok(());
+        }
+        match self
+            .bound
+            .outputs()
+            .iter()
+            .position(|b| !analyze_block(&b[..frames]).is_finite())
+        {
+            Some(c) => Err(format!("non-finite output on channel {c}")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl PluginInstance {
+    /// Run a stress walk as `options` describe (see the module docs), leaving the
+    /// instance deactivated.
+    pub fn stress_test(&mut self, options: &StressOptions) -> StressReport {
+        let writable: Vec<(ParamID, bool)> = self
+            .controller()
+            .and_then(|c| c.parameters().ok())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|p| p.is_writable())
+            .map(|p| (p.id, p.flags.contains(ParameterFlags::IS_BYPASS)))
+            .collect();
+        let allow = options.allow;
+        let bypass = writable
+            .iter()
+            .find(|(_, b)| *b)
+            .map(|&(id, _)| (id, false));
+        let menu: Vec<Kind> = [
+            (allow.activate_cycles, Kind::Activate),
+            (allow.sample_rate_changes, Kind::SampleRate),
+            (allow.block_size_changes, Kind::BlockSize),
+            (allow.state_roundtrips, Kind::StateRoundtrip),
+            (allow.param_storms && !writable.is_empty(), Kind::ParamStorm),
+            (allow.bypass_toggles && bypass.is_some(), Kind::Bypass),
+        ]
+        .into_iter()
+        .filter_map(|(allowed, kind)| allowed.then_some(kind))
+        .collect();
+
+        let (inputs, outputs) = (
+            self.main_bus_channels(BUS_DIR_INPUT),
+            self.main_bus_channels(BUS_DIR_OUTPUT),
+        );
+        let block = 512;
+        let mut walk = Walk {
+            rng: Rng(options.seed),
+            setup: ProcessSetupBuilder::new()
+                .max_block(block as i32)
+                .build()
+                .expect("a 512-frame block at 48 kHz is valid"),
+            bound: BoundProcessData::new(inputs, outputs, block),
+            inputs,
+            outputs,
+            events: EventList::with_capacity(1),
+            changes: ParameterChanges::with_capacity(STORM_PARAMS, STORM_POINTS),
+            params: writable.iter().map(|&(id, _)| id).collect(),
+            bypass,
+        };
+        let mut report = StressReport {
+            seed: options.seed,
+            ops: Vec::with_capacity(options.iterations + 1),
+            failure: None,
+        };
+        for step in 0..=options.iterations {
+            let op = if step == 0 {
+                StressOp::Activate
+            } else {
+                walk.pick(&menu)
+            };
+            report.ops.push(op);
+            if let Err(message) = walk.step(self, op) {
+                report.failure = Some(StressFailure { step, message });
+                break;
+            }
+        }
+        self.deactivate();
+        report
+    }
+}
@@ -0,0 +1,156 @@
+//! Plugin tails: output that goes on after the input stops.
+//!
+//! `getTailSamples` says how long a plugin keeps sounding once its input goes silent:
+//! not at all, a number of samples, or `kInfiniteTail` (`u32::MAX`) for one that may
+//! never stop on its own, like a drone or a delay with full feedback. [`Tail`] keeps
+//! the three apart, so nothing ends up rendering four billion samples of it.
+//!
+//! The reported length is an upper bound at best, so [`run_until_silent`] listens
+//! instead: it processes silence until a [`SilenceDetector`] has heard the output stay
+//! below its threshold for a number of blocks in a row.
+
+use openvst3_abi::process_consts;
+
+use crate::analysis::analyze_block;
+use crate::{BoundProcessData, BusSample, HostError, PluginInstance};
+
+/// What a plugin reports from `getTailSamples`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tail {
+    None,
+    Samples(u32),
+    /// `kInfiniteTail`.
+    Infinite,
+}
+
+impl Tail {
+    /// The tail `getTailSamples` returned as `samples`.
+    pub fn from_samples(samples: u32) -> Self {
+        match samples {
+            process_consts::NO_TAIL => Tail::None,
+            process_consts::INFINITE_TAIL => Tail::Infinite,
+            n => Tail::Samples(n),
+        }
+    }
+
+    pub fn is_infinite(self) -> bool {
+        self == Tail::Infinite
+    }
+
+    /// Frames to render for this tail, at most `max`, and whether `max` cut it short.
+    pub fn frames(self, max: usize) -> (usize, bool) {
+        match self {
+            Tail::None => (0, false),
+            Tail::Samples(n) => ((n as usize).min(max), n as usize > max),
+            Tail::Infinite => (max, true),
+        }
+    }
+}
+
+/// Decides when a plugin's output has died away, from the peak of each block.
+#[derive(Clone, Debug)]
+pub struct SilenceDetector {
+    threshold: f64,
+    hold: u32,
+    /// Quiet blocks in a row.
+    quiet: u32,
+}
+
+impl SilenceDetector {
+    /// Silence is 8 blocks in a row at or below -90 dBFS.
+    pub fn new() -> Self {
+        Self {
+            threshold: 10f64.powf(-90.0 / 20.0),
+            hold: 8,
+            quiet: 0,
+        }
+    }
+
+    /// Peaks at or below `dbfs` count as quiet.
+    pub fn threshold_db(mut self, dbfs: f64) -> Self {
+        self.threshold = 10f64.powf(dbfs / 20.0);
+        self
+    }
+
+    /// Quiet blocks in a row that make silence; at least 1.
+    pub fn hold_blocks(mut self, blocks: u32) -> Self {
+        self.hold = blocks.max(1);
+        self
+    }
+
+    pub fn is_silent(&self) -> bool {
+        self.quiet >= self.hold
+    }
+
+    /// Take in the peak absolute sample of the next block and return whether the
+    /// output is silent now. A NaN peak counts as loud.
+    pub fn next_block(&mut self, peak: f64) -> bool {
+        if peak > self.threshold || peak.is_nan() {
+            self.quiet = 0;
+        } else {
+            self.quiet = self.quiet.saturating_add(1);
+        }
+        self.is_silent()
+    }
+
+    /// Forget the quiet blocks counted so far.
+    pub fn reset(&mut self) {
+        self.quiet = 0;
+    }
+}
+
+impl Default for SilenceDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What [`run_until_silent`] did.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TailRun {
+    pub blocks: usize,
+    pub frames: usize,
+    /// Whether the output fell silent; false when `max_blocks` ran out first.
+    pub silent: bool,
+}
+
+/// Process silent input through the active `plugin` in blocks of
+/// `bound.max_frames()` until `silence` hears every output bus of `bound` stay quiet,
+/// or `max_blocks` have run. `block(bound, frames)` sees the output of each block,
+/// the quiet ones included.
+pub fn run_until_silent<T: BusSample + crate::limiter::Sample>(
+    plugin: &mut PluginInstance,
+    bound: &mut BoundProcessData<T>,
+    mut silence: SilenceDetector,
+    max_blocks: usize,
+    mut block: impl FnMut(&BoundProcessData<T>, usize),
+) -> Result<TailRun, HostError> {
+    let frames = bound.max_frames();
+    let mut run = TailRun::default();
+    while run.blocks < max_blocks {
+        for bus in 0..bound.input_bus_count() {
+            for channel in bound.input_bus_mut(bus) {
+                channel.fill(T::default());
+            }
+        }
+        plugin.process_bound(bound, frames, None)?;
+        block(bound, frames);
+        run.blocks += 1;
+        run.frames += frames;
+        let peak = (0..bound.output_bus_count())
+            .flat_map(|bus| bound.output_bus(bus))
+            .map(|channel| analyze_block(&channel[..frames]))
+            .fold(0.0, |peak: f64, found| {
+                if peak.is_nan() || !found.is_finite() {
+                    f64::NAN
+                } else {
+                    peak.max(found.peak)
+                }
+            });
+        if silence.next_block(peak) {
+            run.silent = true;
+            break;
+        }
+    }
+    Ok(run)
+}
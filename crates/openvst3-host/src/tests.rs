@@ -0,0 +1,3595 @@
+//! Host helper tests against the in-process mock plugin.
+
+use core::ffi::c_void;
+
+use openvst3_abi::{
+    classinfo_consts, iids, param_consts, process_consts, AudioBusBuffers32, ClassFlags, FUnknown,
+    FactoryFlags, IAudioProcessor, IComponent, IEditController, ProcessData32, ProcessSetup,
+    BUS_DIR_INPUT, K_INTERNAL_ERR, K_INVALID_ARG, K_NOT_IMPLEMENTED, K_RESULT_FALSE, K_RESULT_OK,
+};
+
+use crate::testsupport::{
+    read_state_bytes, Method, MockClass, MockConfig, MockCounters, MockPlugin, MOCK_CATEGORY,
+    MOCK_CID, MOCK_URL, MOCK_VENDOR,
+};
+use crate::timing::tests::FakeClock;
+use crate::*;
+
+unsafe fn release(obj: *mut c_void) -> u32 {
+    FUnknown::release(obj as *mut FUnknown)
+}
+
+#[test]
+fn module_from_factory_proc_counts_classes() {
+    let mut module = unsafe { Module::from_factory_proc(MockPlugin::factory_proc()) }.unwrap();
+    assert_eq!(count_classes(&mut module), 1);
+}
+
+#[test]
+fn module_from_null_factory_is_rejected() {
+    unsafe extern "C" fn null_factory() -> *mut openvst3_abi::IPluginFactory {
+        core::ptr::null_mut()
+    }
+    assert!(matches!(
+        unsafe { Module::from_factory_proc(null_factory) },
+        Err(HostError::NullFactory)
+    ));
+}
+
+#[test]
+fn list_classes_reports_mock_class() {
+    let plugin = MockPlugin::new(MockConfig {
+        class_name: "Unit Gain".into(),
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let list = list_classes(&mut module).unwrap();
+    assert_eq!(list.len(), 1);
+    let (index, name, category, cid) = list[0].as_ref().unwrap();
+    assert_eq!(*index, 0);
+    assert_eq!(name, "Unit Gain");
+    assert_eq!(category, MOCK_CATEGORY);
+    assert_eq!(*cid, MOCK_CID);
+    assert!(matches!(
+        read_class_info_v1(&mut module, 5),
+        Err(HostError::ClassNotFound(ClassRef::Index(5)))
+    ));
+}
+
+#[test]
+fn hex_helpers_round_trip() {
+    let hex = fmt_cid_hex(&MOCK_CID);
+    assert_eq!(hex.len(), 32);
+    assert_eq!(parse_hex_16(&hex).unwrap(), MOCK_CID);
+    assert_eq!(
+        parse_hex_16("{42043F99-B7DA-453C-A569-E79D9AAEC33D}").unwrap(),
+        iids::IAUDIO_PROCESSOR.0
+    );
+    assert!(matches!(
+        parse_hex_16("1234"),
+        Err(HostError::InvalidIid(_))
+    ));
+    let err = parse_hex_16(&"zz".repeat(16)).unwrap_err();
+    assert!(matches!(err, HostError::InvalidIid(_)));
+    assert!(err.is_host_fault());
+    // 32 bytes, but pairs would split a character; `+` is no hex digit either.
+    for bad in [format!("a{}a", "\u{e9}".repeat(15)), "+1".repeat(16)] {
+        assert!(matches!(parse_hex_16(&bad), Err(HostError::InvalidIid(_))));
+    }
+}
+
+#[test]
+fn create_and_query_interfaces() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    unsafe {
+        let proc =
+            create_instance_raw(module.factory_mut(), MOCK_CID, iids::IAUDIO_PROCESSOR.0).unwrap();
+        let comp = query_interface(proc, iids::ICOMPONENT.0).unwrap();
+        let ctrl = query_interface(proc, iids::IEDIT_CONTROLLER.0).unwrap();
+        assert!(matches!(
+            query_interface(proc, [0xAB; 16]),
+            Err(HostError::NoInterface { iid, name: None }) if iid == Tuid([0xAB; 16])
+        ));
+        assert!(create_instance_raw(module.factory_mut(), [0; 16], iids::ICOMPONENT.0).is_err());
+        assert_eq!(MockCounters::get(&plugin.counters().live_instances), 1);
+        release(ctrl);
+        release(comp);
+        release(proc);
+    }
+    assert_eq!(MockCounters::get(&plugin.counters().live_instances), 0);
+}
+
+#[test]
+fn interface_errors_name_the_interface_and_class() {
+    let err = HostError::no_interface(iids::IEDIT_CONTROLLER);
+    assert_eq!(
+        err.to_string(),
+        "QueryInterface for IEditController (DCD7BBE3-7742-448D-A874-AACC979C759E) failed"
+    );
+    assert_eq!(
+        HostError::no_interface(Tuid([0xAB; 16])).to_string(),
+        "QueryInterface for ABABABAB-ABAB-ABAB-ABAB-ABABABABABAB failed"
+    );
+
+    let plugin = MockPlugin::new(MockConfig {
+        create_instance_result: K_INTERNAL_ERR,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let err =
+        unsafe { create_instance_raw(module.factory_mut(), MOCK_CID, iids::IAUDIO_PROCESSOR.0) }
+            .err()
+            .unwrap();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "createInstance of OpenVST3 Mock Gain ({}) as IAudioProcessor ({}) failed (tresult {K_INTERNAL_ERR})",
+            fmt_cid_hex(&MOCK_CID),
+            fmt_uid(&iids::IAUDIO_PROCESSOR.0)
+        )
+    );
+}
+
+#[test]
+fn create_instance_failure_is_reported() {
+    let plugin = MockPlugin::new(MockConfig {
+        create_instance_result: K_INTERNAL_ERR,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let res = unsafe { create_instance_raw(module.factory_mut(), MOCK_CID, iids::ICOMPONENT.0) };
+    assert!(matches!(
+        res,
+        Err(HostError::CreateInstance {
+            cid: MOCK_CID,
+            result: K_INTERNAL_ERR,
+            ..
+        })
+    ));
+    assert!(matches!(
+        module.create_plugin(MOCK_CID),
+        Err(HostError::CreateInstance {
+            result: K_INTERNAL_ERR,
+            ..
+        })
+    ));
+    assert_eq!(MockCounters::get(&plugin.counters().created), 0);
+}
+
+#[test]
+fn detect_output_channels_reads_bus_info() {
+    let plugin = MockPlugin::new(MockConfig {
+        channels: 6,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    unsafe {
+        let comp = create_instance_raw(module.factory_mut(), MOCK_CID, iids::ICOMPONENT.0).unwrap();
+        let detected = detect_output_channels(comp as *mut IComponent);
+        assert_eq!(
+            detected,
+            DetectedChannels {
+                bus_count: 1,
+                reported: Some(6),
+                suggested: 6
+            }
+        );
+        assert!(!detected.is_fallback());
+        release(comp);
+    }
+
+    // Zero and absurd counts are reported as such, with stereo as the suggestion.
+    for channels in [0, 10_000] {
+        let plugin = MockPlugin::new(MockConfig {
+            channels,
+            ..MockConfig::default()
+        });
+        let mut module = plugin.module().unwrap();
+        unsafe {
+            let comp =
+                create_instance_raw(module.factory_mut(), MOCK_CID, iids::ICOMPONENT.0).unwrap();
+            let detected = detect_output_channels(comp as *mut IComponent);
+            assert_eq!(detected.reported, Some(channels));
+            assert_eq!(detected.suggested, 2);
+            assert!(detected.is_fallback());
+            release(comp);
+        }
+    }
+}
+
+#[test]
+fn suspicious_bus_channel_counts_fail_strict_and_clamp_lenient() {
+    let limits = HostLimits {
+        max_channels_per_bus: 8,
+        ..HostLimits::default()
+    };
+    for (channels, clamped) in [(0, 1), (10_000, 8)] {
+        let plugin = MockPlugin::new(MockConfig {
+            channels,
+            ..MockConfig::default()
+        });
+        let mut module = plugin.module().unwrap();
+        // Creation would refuse the 10 000 channels already.
+        let inst = module
+            .plugin(MOCK_CID)
+            .limits(HostLimits::unlimited())
+            .create()
+            .unwrap();
+        let component = inst.component();
+
+        match component.checked_bus_layout(&limits, BusCheck::Strict) {
+            Err(e @ HostError::SuspiciousBusInfo { bus, reported }) => {
+                assert_eq!(bus.to_string(), "audio input bus 0");
+                assert_eq!(reported, channels);
+                assert!(e.is_plugin_fault());
+            }
+            other => panic!("expected SuspiciousBusInfo, got {other:?}"),
+        }
+
+        let layout = component
+            .checked_bus_layout(&limits, BusCheck::Lenient)
+            .unwrap();
+        let out = &layout.audio_outputs[0];
+        assert_eq!(
+            (out.channel_count, out.clamped_from),
+            (clamped, Some(channels))
+        );
+        assert_eq!(layout.clamped().count(), 2);
+        assert_eq!(
+            layout.clamped().last(),
+            Some(BusId {
+                media_type: MEDIA_TYPE_AUDIO,
+                direction: BUS_DIR_OUTPUT,
+                index: 0
+            })
+        );
+        // The unchecked layout still shows what the plugin said.
+        assert_eq!(
+            component.bus_layout().unwrap().audio_outputs[0].channel_count,
+            channels
+        );
+
+        drop(inst);
+        let case: CheckCase = "buses".parse().unwrap();
+        match module.run_check(MOCK_CID, case).outcome {
+            Outcome::Fail(msg) => assert!(
+                msg.contains(&format!("audio input bus 0 reports {channels} channels")),
+                "{msg}"
+            ),
+            other => panic!("expected failure, got {other:?}"),
+        }
+    }
+
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let inst = module.create_plugin(MOCK_CID).unwrap();
+    let layout = inst
+        .component()
+        .checked_bus_layout(&HostLimits::default(), BusCheck::Strict)
+        .unwrap();
+    assert_eq!(layout, inst.component().bus_layout().unwrap());
+    assert_eq!(layout.clamped().count(), 0);
+}
+
+#[test]
+fn raw_pointers_round_trip_with_balanced_references() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let live = || MockCounters::get(&plugin.counters().live_instances);
+    assert_eq!(
+        module.factory_raw(),
+        module.factory_mut() as *mut IPluginFactory
+    );
+
+    unsafe {
+        let raw = create_instance_raw(module.factory_mut(), MOCK_CID, iids::ICOMPONENT.0).unwrap();
+        let owned = InterfacePtr::from_raw(raw as *mut IComponent).unwrap();
+        let copy = owned.clone();
+        // Back out and in again: the reference is passed along, never duplicated.
+        let again = InterfacePtr::from_raw_unchecked(copy.into_raw());
+        let handle = ComponentHandle::new(again);
+        let borrowed = handle.as_raw_component();
+        assert_eq!(borrowed, owned.as_ptr());
+        // Two owners hold the only references: an addRef makes three.
+        assert_eq!(FUnknown::add_ref(&mut *(borrowed as *mut FUnknown)), 3);
+        release(borrowed as *mut c_void);
+        drop(handle);
+        assert_eq!(live(), 1);
+        drop(owned);
+        assert_eq!(live(), 0);
+    }
+
+    // A whole instance created by hand is adopted and torn down by the host.
+    unsafe {
+        let comp = create_instance_raw(module.factory_mut(), MOCK_CID, iids::ICOMPONENT.0).unwrap();
+        let comp = comp as *mut IComponent;
+        assert_eq!((*comp).initialize(core::ptr::null_mut()), K_RESULT_OK);
+        let proc = query_interface(comp as *mut c_void, iids::IAUDIO_PROCESSOR.0).unwrap();
+        let ctrl = query_interface(comp as *mut c_void, iids::IEDIT_CONTROLLER.0).unwrap();
+        let mut inst = PluginInstance::from_raw_parts(
+            comp,
+            proc as *mut IAudioProcessor,
+            ctrl as *mut IEditController,
+        )
+        .unwrap();
+        // Adopted as initialized, so the call-order guards apply.
+        assert!(matches!(
+            inst.component().set_io_mode(IoMode::Advanced),
+            Err(HostError::CallOrder {
+                call: "setIoMode",
+                ..
+            })
+        ));
+        inst.activate(&setup_32(16)).unwrap();
+        let mut bound = BoundProcessData::<f32>::new(2, 2, 16);
+        inst.process_bound(&mut bound, 16, None).unwrap();
+        assert!(inst.controller().is_some());
+        drop(inst);
+    }
+    assert_torn_down(&plugin);
+    assert_eq!(live(), 0);
+
+    // A missing component still releases the processor it was given.
+    unsafe {
+        let proc =
+            create_instance_raw(module.factory_mut(), MOCK_CID, iids::IAUDIO_PROCESSOR.0).unwrap();
+        let adopted = PluginInstance::from_raw_parts(
+            core::ptr::null_mut(),
+            proc as *mut IAudioProcessor,
+            core::ptr::null_mut(),
+        );
+        assert!(matches!(
+            adopted,
+            Err(HostError::NoInterface {
+                name: Some("IComponent"),
+                ..
+            })
+        ));
+    }
+    assert_eq!(live(), 0);
+}
+
+#[test]
+fn set_bus_arrangements_propagates_result() {
+    let ok = MockPlugin::default();
+    let refusing = MockPlugin::new(MockConfig {
+        set_bus_arrangements_result: K_RESULT_FALSE,
+        ..MockConfig::default()
+    });
+    // Not implemented: the plugin keeps its layout, which is not an error.
+    let unimplemented = MockPlugin::new(MockConfig {
+        set_bus_arrangements_result: K_NOT_IMPLEMENTED,
+        ..MockConfig::default()
+    });
+    for (plugin, expect_ok) in [(&ok, true), (&refusing, false), (&unimplemented, true)] {
+        let mut module = plugin.module().unwrap();
+        unsafe {
+            let proc =
+                create_instance_raw(module.factory_mut(), MOCK_CID, iids::IAUDIO_PROCESSOR.0)
+                    .unwrap();
+            let res = set_bus_arrangements(proc as *mut IAudioProcessor, &[0b11], &[0b11]);
+            if expect_ok {
+                assert!(res.is_ok());
+            } else {
+                let err = res.unwrap_err();
+                assert!(matches!(
+                    &err,
+                    HostError::ArrangementRejected { inputs, outputs }
+                        if inputs == &[0b11] && outputs == &[0b11]
+                ));
+                assert!(err.is_plugin_fault());
+            }
+            release(proc);
+        }
+    }
+}
+
+#[test]
+#[allow(deprecated)]
+fn drive_null_process_runs_full_lifecycle() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    unsafe {
+        let proc =
+            create_instance_raw(module.factory_mut(), MOCK_CID, iids::IAUDIO_PROCESSOR.0).unwrap();
+        drive_null_process_32f(proc as *mut IAudioProcessor, 44100.0, 128, 2).unwrap();
+        let setup = plugin.last_setup().unwrap();
+        assert_eq!(setup.max_block, 128);
+        assert_eq!(
+            setup.symbolic_sample_size,
+            process_consts::SYMBOLIC_SAMPLE_32
+        );
+
+        drive_null_process_64f(proc as *mut IAudioProcessor, 96000.0, 64, 2).unwrap();
+        let setup = plugin.last_setup().unwrap();
+        assert_eq!(setup.sample_rate, 96000.0);
+        assert_eq!(
+            setup.symbolic_sample_size,
+            process_consts::SYMBOLIC_SAMPLE_64
+        );
+        release(proc);
+    }
+    let c = plugin.counters();
+    assert_eq!(MockCounters::get(&c.process_calls), 2);
+    assert_eq!(MockCounters::get(&c.initialize_calls), 2);
+    assert_eq!(MockCounters::get(&c.terminate_calls), 2);
+}
+
+/// Process one 32f block of `input` through `proc`, returning the output channels.
+unsafe fn process_block(proc: *mut IAudioProcessor, input: &mut [Vec<f32>]) -> Vec<Vec<f32>> {
+    let frames = input[0].len();
+    let mut out: Vec<Vec<f32>> = input.iter().map(|_| vec![0.0; frames]).collect();
+    let mut in_ptrs: Vec<*mut f32> = input.iter_mut().map(|c| c.as_mut_ptr()).collect();
+    let mut out_ptrs: Vec<*mut f32> = out.iter_mut().map(|c| c.as_mut_ptr()).collect();
+    let mut in_bus = AudioBusBuffers32 {
+        num_channels: in_ptrs.len() as i32,
+        silence_flags: 0,
+        channel_buffers: in_ptrs.as_mut_ptr(),
+    };
+    let mut out_bus = AudioBusBuffers32 {
+        num_channels: out_ptrs.len() as i32,
+        silence_flags: 0,
+        channel_buffers: out_ptrs.as_mut_ptr(),
+    };
+    let mut data = ProcessData32 {
+        num_inputs: 1,
+        num_outputs: 1,
+        inputs: &mut in_bus,
+        outputs: &mut out_bus,
+        num_samples: frames as i32,
+        input_parameter_changes: core::ptr::null_mut(),
+        output_parameter_changes: core::ptr::null_mut(),
+        input_events: core::ptr::null_mut(),
+        output_events: core::ptr::null_mut(),
+        process_context: core::ptr::null_mut(),
+    };
+    assert_eq!((*proc).process_32f(&mut data), K_RESULT_OK);
+    out
+}
+
+#[test]
+fn gain_parameter_scales_output() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    unsafe {
+        let proc =
+            create_instance_raw(module.factory_mut(), MOCK_CID, iids::IAUDIO_PROCESSOR.0).unwrap();
+        let ctrl = query_interface(proc, iids::IEDIT_CONTROLLER.0).unwrap() as *mut IEditController;
+        assert_eq!((*ctrl).get_parameter_count(), 1);
+        assert_eq!((*ctrl).set_param_normalized(0, 0.25), K_RESULT_OK);
+
+        let mut input = vec![vec![1.0f32; 16], vec![-0.5f32; 16]];
+        let out = process_block(proc as *mut IAudioProcessor, &mut input);
+        assert!(out[0].iter().all(|&s| s == 0.25));
+        assert!(out[1].iter().all(|&s| s == -0.125));
+        release(ctrl as *mut c_void);
+        release(proc);
+    }
+}
+
+#[test]
+fn nan_fault_reaches_output() {
+    let plugin = MockPlugin::new(MockConfig {
+        write_nans: true,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    unsafe {
+        let proc =
+            create_instance_raw(module.factory_mut(), MOCK_CID, iids::IAUDIO_PROCESSOR.0).unwrap();
+        let out = process_block(proc as *mut IAudioProcessor, &mut [vec![0.1f32; 8]]);
+        assert!(out[0].iter().all(|s| s.is_nan()));
+        release(proc);
+    }
+}
+
+#[test]
+fn component_state_round_trips_through_memory_stream() {
+    let plugin = MockPlugin::new(MockConfig {
+        num_params: 3,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    unsafe {
+        let a = create_instance_raw(module.factory_mut(), MOCK_CID, iids::ICOMPONENT.0).unwrap();
+        let a_ctrl = query_interface(a, iids::IEDIT_CONTROLLER.0).unwrap() as *mut IEditController;
+        (*a_ctrl).set_param_normalized(2, 0.75);
+
+        let mut stream = MemoryStream::new();
+        assert_eq!(
+            (*(a as *mut IComponent)).get_state(stream.as_ibstream()),
+            K_RESULT_OK
+        );
+        assert_eq!(
+            read_state_bytes(stream.bytes()).unwrap(),
+            vec![1.0, 0.5, 0.75]
+        );
+
+        let b = create_instance_raw(module.factory_mut(), MOCK_CID, iids::ICOMPONENT.0).unwrap();
+        let b_ctrl = query_interface(b, iids::IEDIT_CONTROLLER.0).unwrap() as *mut IEditController;
+        stream.rewind();
+        assert_eq!(
+            (*(b as *mut IComponent)).set_state(stream.as_ibstream()),
+            K_RESULT_OK
+        );
+        assert_eq!((*b_ctrl).get_param_normalized(2), 0.75);
+
+        let mut truncated = MemoryStream::from_bytes(vec![9, 0, 0, 0, 1]);
+        assert_ne!(
+            (*(b as *mut IComponent)).set_state(truncated.as_ibstream()),
+            K_RESULT_OK
+        );
+
+        for p in [a_ctrl as *mut c_void, a, b_ctrl as *mut c_void, b] {
+            release(p);
+        }
+    }
+    assert_eq!(MockCounters::get(&plugin.counters().live_instances), 0);
+}
+
+// ----- Fault injection ----------------------------------------------------------
+
+fn setup_32(max_block: i32) -> ProcessSetup {
+    ProcessSetup {
+        process_mode: process_consts::PROCESS_MODE_REALTIME,
+        sample_rate: 48000.0,
+        max_samples_per_block: max_block,
+        symbolic_sample_size: process_consts::SYMBOLIC_SAMPLE_32,
+        flags: 0,
+    }
+}
+
+/// Every instance released and every successful initialize matched by a terminate.
+fn assert_torn_down(plugin: &MockPlugin) {
+    let c = plugin.counters();
+    assert_eq!(MockCounters::get(&c.live_instances), 0);
+    assert_eq!(MockCounters::get(&c.active_instances), 0);
+    assert_eq!(
+        MockCounters::get(&c.initialize_calls),
+        MockCounters::get(&c.terminate_calls)
+    );
+}
+
+#[test]
+fn get_class_info_failure_is_reported() {
+    let plugin = MockPlugin::new(MockConfig {
+        fail_get_class_info: Some(K_INVALID_ARG),
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    assert!(matches!(
+        read_class_info_v1(&mut module, 0),
+        Err(HostError::TErr(K_INVALID_ARG))
+    ));
+    let list = list_classes(&mut module).unwrap();
+    assert_eq!(list.len(), 1);
+    let err = list[0].as_ref().unwrap_err();
+    assert_eq!(err.index, 0);
+    assert!(matches!(err.source, HostError::TErr(K_INVALID_ARG)));
+}
+
+#[test]
+fn initialize_failure_releases_component() {
+    let plugin = MockPlugin::new(MockConfig {
+        initialize_result: K_RESULT_FALSE,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    assert!(matches!(
+        module.create_plugin(MOCK_CID),
+        Err(HostError::TErr(K_RESULT_FALSE))
+    ));
+    assert_eq!(MockCounters::get(&plugin.counters().created), 1);
+    assert_eq!(MockCounters::get(&plugin.counters().terminate_calls), 0);
+    assert_torn_down(&plugin);
+}
+
+#[test]
+fn setup_processing_failure_rolls_back() {
+    let plugin = MockPlugin::new(MockConfig {
+        setup_processing_result: K_INVALID_ARG,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    assert!(matches!(
+        inst.activate(&setup_32(64)),
+        Err(HostError::TErr(K_INVALID_ARG))
+    ));
+    assert!(!inst.is_processing());
+    assert_eq!(MockCounters::get(&plugin.counters().active_instances), 0);
+    drop(inst);
+    assert_torn_down(&plugin);
+
+    // The raw helper must not leave the processor initialized either.
+    unsafe {
+        let proc =
+            create_instance_raw(module.factory_mut(), MOCK_CID, iids::IAUDIO_PROCESSOR.0).unwrap();
+        assert!(matches!(
+            drive_process_capture_32f(proc as *mut IAudioProcessor, 48000.0, 64, 2, None),
+            Err(HostError::TErr(K_INVALID_ARG))
+        ));
+        release(proc);
+    }
+    assert_torn_down(&plugin);
+}
+
+#[test]
+fn drive_process_capture_keeps_the_output() {
+    let plugin = MockPlugin::new(MockConfig {
+        channels: 3,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    unsafe {
+        let proc =
+            create_instance_raw(module.factory_mut(), MOCK_CID, iids::IAUDIO_PROCESSOR.0).unwrap();
+        let proc = proc as *mut IAudioProcessor;
+
+        // Two input channels for three outputs; the short one is padded with silence.
+        let input = [vec![0.5f32; 8], vec![-0.25f32; 4]];
+        let block = drive_process_capture_32f(proc, 48000.0, 8, 3, Some(&input)).unwrap();
+        assert_eq!(block.tresult, K_RESULT_OK);
+        assert_eq!(block.outputs.len(), 3);
+        assert_eq!(block.outputs[0], vec![0.5; 8]);
+        assert_eq!(block.outputs[1][..4], [-0.25; 4]);
+        assert_eq!(block.outputs[1][4..], [0.0; 4]);
+        // The mock flags output channels without an input as silent.
+        assert_eq!(block.silence_flags, 0b100);
+        assert!(block.stats().unwrap().output_silent(0, 2));
+
+        let block = drive_process_capture_64f(proc, 48000.0, 4, 2, None).unwrap();
+        assert_eq!(block.outputs, vec![vec![0.0f64; 4]; 2]);
+        assert_eq!(block.silence_flags, 0b11);
+        release(proc.cast());
+    }
+
+    // A failing process call is reported, not turned into an error.
+    let plugin = MockPlugin::new(MockConfig {
+        process_returns_after_n_blocks: Some((0, K_INTERNAL_ERR)),
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    unsafe {
+        let proc =
+            create_instance_raw(module.factory_mut(), MOCK_CID, iids::IAUDIO_PROCESSOR.0).unwrap();
+        let block =
+            drive_process_capture_32f(proc as *mut IAudioProcessor, 48000.0, 16, 2, None).unwrap();
+        assert_eq!(block.tresult, K_INTERNAL_ERR);
+        assert!(matches!(
+            block.stats(),
+            Err(HostError::TErr(K_INTERNAL_ERR))
+        ));
+        release(proc);
+    }
+    assert_torn_down(&plugin);
+}
+
+#[test]
+fn process_failure_after_n_blocks_is_reported() {
+    let plugin = MockPlugin::new(MockConfig {
+        process_returns_after_n_blocks: Some((2, K_INTERNAL_ERR)),
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    inst.activate(&setup_32(16)).unwrap();
+    assert_eq!(MockCounters::get(&plugin.counters().active_instances), 1);
+
+    let proc = inst.processor().as_ptr();
+    let mut input = vec![vec![1.0f32; 16]; 2];
+    unsafe {
+        process_block(proc, &mut input);
+        process_block(proc, &mut input);
+    }
+    let mut out = vec![0.0f32; 16];
+    let mut out_ptrs = [out.as_mut_ptr()];
+    let mut out_bus = AudioBusBuffers32 {
+        num_channels: 1,
+        silence_flags: 0,
+        channel_buffers: out_ptrs.as_mut_ptr(),
+    };
+    let mut data = ProcessData32 {
+        num_inputs: 0,
+        num_outputs: 1,
+        inputs: core::ptr::null_mut(),
+        outputs: &mut out_bus,
+        num_samples: 16,
+        input_parameter_changes: core::ptr::null_mut(),
+        output_parameter_changes: core::ptr::null_mut(),
+        input_events: core::ptr::null_mut(),
+        output_events: core::ptr::null_mut(),
+        process_context: core::ptr::null_mut(),
+    };
+    assert!(matches!(
+        unsafe { inst.process_32f(&mut data) },
+        Err(HostError::TErr(K_INTERNAL_ERR))
+    ));
+    drop(inst);
+    assert_eq!(MockCounters::get(&plugin.counters().process_calls), 3);
+    assert_torn_down(&plugin);
+}
+
+#[test]
+fn optional_calls_may_be_unimplemented() {
+    for (processing, arrangements) in [
+        (K_NOT_IMPLEMENTED, K_RESULT_OK),
+        (K_RESULT_OK, K_NOT_IMPLEMENTED),
+        (K_RESULT_FALSE, K_RESULT_OK),
+    ] {
+        let plugin = MockPlugin::new(MockConfig {
+            set_processing_result: processing,
+            set_bus_arrangements_result: arrangements,
+            ..MockConfig::default()
+        });
+        let mut module = plugin.module().unwrap();
+        let mut inst = module.create_plugin(MOCK_CID).unwrap();
+        inst.set_bus_arrangements(&[0b11], &[0b11]).unwrap();
+        inst.activate(&setup_32(16)).unwrap();
+        let mut bound = BoundProcessData::<f32>::new(2, 2, 16);
+        inst.process_bound(&mut bound, 16, None).unwrap();
+        let processor = inst.processor();
+        assert_eq!(
+            processor.supports_set_processing(),
+            processing != K_NOT_IMPLEMENTED
+        );
+        assert_eq!(
+            processor.supports_set_bus_arrangements(),
+            arrangements != K_NOT_IMPLEMENTED
+        );
+        inst.deactivate();
+
+        // The raw helpers carry on too.
+        unsafe {
+            let proc =
+                create_instance_raw(module.factory_mut(), MOCK_CID, iids::IAUDIO_PROCESSOR.0)
+                    .unwrap();
+            set_bus_arrangements(proc as *mut IAudioProcessor, &[0b11], &[0b11]).unwrap();
+            let block =
+                drive_process_capture_32f(proc as *mut IAudioProcessor, 48000.0, 16, 2, None)
+                    .unwrap();
+            assert_eq!(block.tresult, K_RESULT_OK);
+            release(proc);
+        }
+    }
+
+    // Anything else from setProcessing still fails activation.
+    let plugin = MockPlugin::new(MockConfig {
+        set_processing_result: K_INTERNAL_ERR,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    assert!(matches!(
+        inst.activate(&setup_32(16)),
+        Err(HostError::TErr(K_INTERNAL_ERR))
+    ));
+    assert!(!inst.is_processing());
+}
+
+#[test]
+fn create_audio_plugin_falls_back_to_the_processor_iid() {
+    for (only, path) in [
+        (iids::ICOMPONENT, CreationPath::Component),
+        (iids::IAUDIO_PROCESSOR, CreationPath::DirectProcessor),
+    ] {
+        let plugin = MockPlugin::new(MockConfig {
+            create_only_iid: Some(only),
+            ..MockConfig::default()
+        });
+        let mut module = plugin.module().unwrap();
+        let created = unsafe { create_audio_plugin(module.factory_mut(), MOCK_CID) }.unwrap();
+        assert_eq!(created.path, path);
+        assert!(created.component.is_some());
+        assert!(created.controller.is_some());
+    }
+
+    // Neither path gets a processor out of a component-only class.
+    let plugin = MockPlugin::new(MockConfig {
+        hide_processor: true,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    assert!(matches!(
+        unsafe { create_audio_plugin(module.factory_mut(), MOCK_CID) },
+        Err(HostError::NoInterface {
+            name: Some("IAudioProcessor"),
+            ..
+        })
+    ));
+}
+
+#[test]
+fn event_buses_are_listed_and_activated_with_the_audio_buses() {
+    use openvst3_abi::{
+        Event, BUS_DIR_INPUT, BUS_TYPE_AUX, BUS_TYPE_MAIN, MEDIA_TYPE_AUDIO, MEDIA_TYPE_EVENT,
+    };
+
+    let plugin = MockPlugin::new(MockConfig {
+        event_inputs: 2,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    let layout = inst.component().bus_layout().unwrap();
+    assert_eq!(layout.audio_inputs.len(), 1);
+    assert_eq!(layout.audio_outputs.len(), 1);
+    assert_eq!(layout.event_inputs.len(), 2);
+    assert!(layout.event_outputs.is_empty());
+    let main = &layout.event_inputs[0];
+    assert_eq!((main.channel_count, main.bus_type), (16, BUS_TYPE_MAIN));
+    assert!(main.default_active);
+    assert_eq!(layout.event_inputs[1].bus_type, BUS_TYPE_AUX);
+    assert_eq!(layout.buses(MEDIA_TYPE_EVENT, BUS_DIR_INPUT).len(), 2);
+    assert_eq!(layout.iter().count(), 4);
+    assert_eq!(
+        inst.component()
+            .bus_name(MEDIA_TYPE_EVENT, BUS_DIR_INPUT, 0)
+            .as_deref(),
+        Some("Event In")
+    );
+
+    // Activation turns on every default-active bus, the event bus included.
+    inst.activate(&setup_32(16)).unwrap();
+    let mut bound = BoundProcessData::<f32>::new(2, 2, 16);
+    let stats = inst.process_bound(&mut bound, 16, None).unwrap();
+    assert_eq!(stats.plugin_marked_outputs_silent[0], 0);
+
+    // With its event bus off the instrument goes quiet.
+    inst.component()
+        .activate_bus(MEDIA_TYPE_EVENT, BUS_DIR_INPUT, 0, false)
+        .unwrap();
+    let stats = inst.process_bound(&mut bound, 16, None).unwrap();
+    assert_eq!(stats.plugin_marked_outputs_silent[0], 0b11);
+    assert!(inst
+        .component()
+        .activate_bus(MEDIA_TYPE_EVENT, BUS_DIR_INPUT, 2, true)
+        .is_err());
+    assert!(inst
+        .component()
+        .activate_bus(MEDIA_TYPE_AUDIO, BUS_DIR_INPUT, 0, true)
+        .is_ok());
+
+    assert_eq!(Event::note_on(0, 0, 60, 1.0).on_bus(1).bus_index, 1);
+}
+
+#[test]
+fn process_result_false_is_ok_with_a_flag() {
+    let plugin = MockPlugin::new(MockConfig {
+        process_returns_after_n_blocks: Some((1, K_RESULT_FALSE)),
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    inst.processor().set_timing(true);
+    inst.activate(&setup_32(16)).unwrap();
+    let mut bound = BoundProcessData::<f32>::new(0, 2, 16);
+
+    let stats = inst.process_bound(&mut bound, 16, None).unwrap();
+    assert!(!stats.nothing_to_do());
+    assert_eq!(stats.tresult_raw, K_RESULT_OK);
+    // No input bus: the mock flags both outputs silent.
+    assert_eq!(stats.plugin_marked_outputs_silent[0], 0b11);
+    assert!(stats.output_silent(0, 1) && !stats.output_silent(0, 2));
+    assert!(stats.duration.is_some());
+
+    let stats = inst.process_bound(&mut bound, 16, None).unwrap();
+    assert!(stats.nothing_to_do());
+    assert_eq!((stats.out_params, stats.out_events), (0, 0));
+    drop(inst);
+
+    // The raw helper used to treat kResultFalse as a failure.
+    let plugin = MockPlugin::new(MockConfig {
+        process_returns_after_n_blocks: Some((0, K_RESULT_FALSE)),
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    unsafe {
+        let proc =
+            create_instance_raw(module.factory_mut(), MOCK_CID, iids::IAUDIO_PROCESSOR.0).unwrap();
+        let block =
+            drive_process_capture_32f(proc as *mut IAudioProcessor, 48000.0, 64, 2, None).unwrap();
+        assert_eq!(block.tresult, K_RESULT_FALSE);
+        let stats = block.stats().unwrap();
+        assert!(stats.nothing_to_do());
+        assert_eq!(stats.duration, None);
+        release(proc);
+    }
+}
+
+#[test]
+fn timing_collector_follows_process_calls_only_while_attached() {
+    let plugin = MockPlugin::new(MockConfig::default());
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    let clock = FakeClock::default();
+    let timing = std::sync::Arc::new(
+        TimingCollector::new(TimingBudget::Fixed(Duration::from_millis(1)))
+            .with_clock(clock.clone()),
+    );
+    inst.set_timing_collector(Some(timing.clone()));
+    inst.activate(&setup_32(16)).unwrap();
+    let mut bound = BoundProcessData::<f32>::new(0, 2, 16);
+
+    for _ in 0..3 {
+        inst.process_bound(&mut bound, 16, None).unwrap();
+    }
+    // Two clock reads per call.
+    assert_eq!(clock.reads(), 6);
+    let snap = timing.snapshot();
+    assert_eq!((snap.calls, snap.overruns), (3, 0));
+    assert_eq!(snap.last, Duration::ZERO);
+
+    // Clones share the collector.
+    let clone = inst.processor().clone();
+    assert!(std::sync::Arc::ptr_eq(
+        clone.timing_collector().unwrap(),
+        &timing
+    ));
+    drop(clone);
+
+    inst.set_timing_collector(None);
+    inst.process_bound(&mut bound, 16, None).unwrap();
+    assert_eq!(clock.reads(), 6);
+    assert_eq!(timing.snapshot().calls, 3);
+}
+
+#[test]
+fn bound_process_data_passes_the_context() {
+    use openvst3_abi::process_context_consts::K_SYSTEM_TIME_VALID;
+
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    inst.activate(&setup_32(16)).unwrap();
+    let mut bound = BoundProcessData::<f32>::new(2, 2, 16);
+    inst.process_bound(&mut bound, 16, None).unwrap();
+    assert_eq!(plugin.last_context(), None);
+
+    let mut clock = SampleClock::with_clock(48_000.0, FakeClock::default());
+    for block in 0..3 {
+        bound.set_context(Some(clock.next_block(16)));
+        inst.process_bound(&mut bound, 16, None).unwrap();
+        let context = plugin.last_context().unwrap();
+        assert_ne!(context.state & K_SYSTEM_TIME_VALID, 0);
+        assert_eq!(context.continuous_time_samples, block * 16);
+    }
+
+    bound.set_context(None);
+    inst.process_bound(&mut bound, 16, None).unwrap();
+    assert_eq!(plugin.last_context(), None);
+}
+
+#[test]
+fn input_gate_skips_copies_of_a_silent_bus() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    inst.activate(&setup_32(16)).unwrap();
+    let mut bound = BoundProcessData::<f32>::new(2, 2, 16);
+    bound.set_input_gate(Some(InputGate::new().hold_blocks(3)));
+    let mut copies = 0;
+    let mut block = |bound: &mut BoundProcessData<f32>, level: f32| {
+        bound.write_input(level as f64, |inputs| {
+            copies += 1;
+            for channel in inputs.iter_mut() {
+                channel.fill(level);
+            }
+        });
+        let stats = inst.process_bound(bound, 16, None).unwrap();
+        (stats, copies)
+    };
+
+    let (stats, _) = block(&mut bound, 0.5);
+    assert_eq!(stats.plugin_marked_outputs_silent[0], 0);
+    assert_eq!(bound.outputs()[1][15], 0.5);
+
+    // Two quiet blocks are still copied; the third closes the gate, which zeroes the
+    // buffers rather than copying and flags both channels silent.
+    for _ in 0..2 {
+        let (stats, _) = block(&mut bound, 1e-7);
+        assert_eq!(stats.plugin_marked_outputs_silent[0], 0);
+        assert_eq!(bound.outputs()[0][0], 1e-7);
+    }
+    let (stats, copies) = block(&mut bound, 1e-7);
+    assert_eq!(copies, 3);
+    assert!(bound.input_gate().unwrap().is_closed());
+    assert!(bound.inputs().iter().all(|c| c.iter().all(|&x| x == 0.0)));
+    assert_eq!(stats.plugin_marked_outputs_silent[0], 0b11);
+
+    for skipped in 1..=4 {
+        let (stats, copies) = block(&mut bound, 0.0);
+        assert_eq!(copies, 3);
+        assert_eq!(stats.skipped_input_copies, skipped);
+        assert_eq!(stats.plugin_marked_outputs_silent[0], 0b11);
+    }
+
+    // The first loud block, or a NaN, opens it again.
+    let (stats, copies) = block(&mut bound, 0.25);
+    assert_eq!(copies, 4);
+    assert_eq!(stats.plugin_marked_outputs_silent[0], 0);
+    assert_eq!(bound.outputs()[0][0], 0.25);
+    let mut gate = InputGate::new().hold_blocks(1);
+    assert_eq!(gate.next_block(0.0), GateAction::Zero);
+    assert_eq!(gate.next_block(f64::NAN), GateAction::Copy);
+    assert_eq!(gate.next_block(0.0), GateAction::Zero);
+    assert_eq!(gate.next_block(0.0), GateAction::Skip);
+
+    // Without a gate every block is copied and nothing is flagged.
+    bound.set_input_gate(None);
+    let (stats, copies) = block(&mut bound, 0.0);
+    assert_eq!((copies, stats.skipped_input_copies), (5, 0));
+    assert_eq!(stats.plugin_marked_outputs_silent[0], 0);
+}
+
+#[test]
+fn oversized_block_is_rejected_before_the_plugin_sees_it() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    inst.activate(&setup_32(16)).unwrap();
+    let mut out = vec![0.0f32; 32];
+    let mut out_ptrs = [out.as_mut_ptr()];
+    let mut out_bus = AudioBusBuffers32 {
+        num_channels: 1,
+        silence_flags: 0,
+        channel_buffers: out_ptrs.as_mut_ptr(),
+    };
+    let mut data = ProcessData32 {
+        num_inputs: 0,
+        num_outputs: 1,
+        inputs: core::ptr::null_mut(),
+        outputs: &mut out_bus,
+        num_samples: 32,
+        input_parameter_changes: core::ptr::null_mut(),
+        output_parameter_changes: core::ptr::null_mut(),
+        input_events: core::ptr::null_mut(),
+        output_events: core::ptr::null_mut(),
+        process_context: core::ptr::null_mut(),
+    };
+    let err = unsafe { inst.process_32f(&mut data) }.unwrap_err();
+    assert!(matches!(
+        err,
+        HostError::BlockSizeExceeded { got: 32, max: 16 }
+    ));
+    assert!(err.is_host_fault());
+    assert_eq!(MockCounters::get(&plugin.counters().process_calls), 0);
+}
+
+#[test]
+fn bound_process_data_is_reused_across_moves_and_block_sizes() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    inst.controller()
+        .unwrap()
+        .set_param_normalized(0, 0.5)
+        .unwrap();
+    inst.activate(&ProcessSetup {
+        symbolic_sample_size: process_consts::SYMBOLIC_SAMPLE_64,
+        ..setup_32(16)
+    })
+    .unwrap();
+    // Moving the binding must not invalidate the pointers it set up.
+    let mut bound = Box::new(BoundProcessData::<f64>::new(2, 2, 16));
+    for (c, chan) in bound.inputs_mut().enumerate() {
+        chan.fill(c as f64 + 1.0);
+    }
+    for frames in [16, 5] {
+        bound.outputs_mut().for_each(|chan| chan.fill(-1.0));
+        inst.process_bound(&mut bound, frames, None).unwrap();
+        for (c, chan) in bound.outputs().iter().enumerate() {
+            assert!(chan[..frames].iter().all(|&s| s == (c as f64 + 1.0) * 0.5));
+            assert!(chan[frames..].iter().all(|&s| s == -1.0));
+        }
+    }
+    assert!(matches!(
+        bound.prepare(17, None),
+        Err(HostError::BlockSizeExceeded { got: 17, max: 16 })
+    ));
+}
+
+#[test]
+fn reload_moves_state_and_setup_to_the_new_module() {
+    let old = MockPlugin::default();
+    let new = MockPlugin::default();
+    let mut module = old.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    let stereo = crate::render::arrangement_for_channels(2);
+    inst.set_bus_arrangements(&[stereo], &[stereo]).unwrap();
+    let controller = inst.controller().unwrap();
+    controller.set_param_normalized(0, 0.25).unwrap();
+    controller
+        .set_state(&mut MemoryStream::from_bytes(b"zoom 2".to_vec()))
+        .unwrap();
+    inst.activate(&setup_32(64)).unwrap();
+    inst.processor().set_timing(true);
+    let live = inst.live();
+    let before = live.get();
+
+    inst.reload(new.module().unwrap()).unwrap();
+    assert_eq!(
+        inst.controller_state().unwrap().as_deref(),
+        Some(&b"zoom 2"[..])
+    );
+    assert!(inst.processor().timing());
+    assert_ne!(live.get(), before);
+    assert_eq!(live.get(), inst.processor().as_ptr());
+    assert_torn_down(&old);
+    drop(module);
+    assert!(inst.is_processing());
+    assert_eq!(new.last_setup().unwrap().max_block, 64);
+    assert_eq!(MockCounters::get(&new.counters().active_instances), 1);
+
+    let guard = live.enter().unwrap();
+    let out = unsafe { process_block(guard.as_ptr(), &mut [vec![1.0f32; 16], vec![2.0; 16]]) };
+    drop(guard);
+    assert!(out[0].iter().all(|&s| s == 0.25));
+    assert!(out[1].iter().all(|&s| s == 0.5));
+    live.while_muted(|| assert!(live.enter().is_none()));
+
+    drop(inst);
+    assert_torn_down(&new);
+}
+
+#[test]
+fn failed_reload_keeps_the_old_instance_running() {
+    let old = MockPlugin::default();
+    let broken = MockPlugin::new(MockConfig {
+        setup_processing_result: K_INVALID_ARG,
+        ..MockConfig::default()
+    });
+    let mut module = old.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    inst.activate(&setup_32(16)).unwrap();
+    let live = inst.live();
+    let before = live.get();
+
+    assert!(matches!(
+        inst.reload(broken.module().unwrap()),
+        Err(HostError::TErr(K_INVALID_ARG))
+    ));
+    assert_torn_down(&broken);
+    assert_eq!(live.get(), before);
+    assert!(inst.is_processing());
+    let out = unsafe { process_block(live.get(), &mut [vec![1.0f32; 16]]) };
+    assert!(out[0].iter().all(|&s| s == 1.0));
+    drop(inst);
+    assert_torn_down(&old);
+}
+
+#[test]
+fn unknown_cid_is_class_not_found() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let err = module.create_plugin([9; 16]).err().unwrap();
+    assert!(matches!(
+        err,
+        HostError::ClassNotFound(ClassRef::Cid([9, ..]))
+    ));
+    assert!(err.to_string().contains(&fmt_cid_hex(&[9; 16])));
+    assert!(err.is_host_fault());
+
+    // A known class the factory refuses to create is the plugin's fault.
+    let refusing = MockPlugin::new(MockConfig {
+        create_instance_result: K_INTERNAL_ERR,
+        ..MockConfig::default()
+    });
+    let mut module = refusing.module().unwrap();
+    let err = module.create_plugin(MOCK_CID).err().unwrap();
+    assert!(matches!(
+        err,
+        HostError::CreateInstance {
+            result: K_INTERNAL_ERR,
+            ..
+        }
+    ));
+    assert!(err.is_plugin_fault());
+}
+
+#[test]
+fn interface_ptr_clone_and_query_balance_refcounts() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let inst = module.create_plugin(MOCK_CID).unwrap();
+    let extra = inst.component().clone();
+    assert!(matches!(
+        unsafe {
+            extra
+                .interface()
+                .query::<FUnknown>(&openvst3_abi::Tuid([0xAB; 16]))
+        },
+        Err(HostError::NoInterface { name: None, .. })
+    ));
+    drop(inst);
+    assert_eq!(MockCounters::get(&plugin.counters().live_instances), 1);
+    drop(extra);
+    assert_torn_down(&plugin);
+}
+
+#[test]
+fn watchdog_fires_for_hung_setup() {
+    let plugin = MockPlugin::new(MockConfig {
+        hang_in: Some(Method::SetupProcessing),
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let inst = module.create_plugin(MOCK_CID).unwrap();
+    let res = watchdog::call_with_timeout(
+        "setupProcessing",
+        std::time::Duration::from_millis(50),
+        move || {
+            let mut inst = inst;
+            inst.activate(&setup_32(64)).map(|_| inst)
+        },
+    );
+    assert!(matches!(
+        res,
+        Err(HostError::TimedOut {
+            method: "setupProcessing",
+            ..
+        })
+    ));
+
+    // Unblock the worker so it can tear the instance down before the module goes.
+    plugin.release_hang();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+    while MockCounters::get(&plugin.counters().live_instances) != 0 {
+        assert!(
+            std::time::Instant::now() < deadline,
+            "worker never finished"
+        );
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_torn_down(&plugin);
+
+    let ok = watchdog::call_with_timeout("noop", std::time::Duration::from_secs(5), || 7);
+    assert_eq!(ok.unwrap(), 7);
+}
+
+// ----- Class lookup and filtering ------------------------------------------------
+
+fn catalog_plugin(factory2: bool) -> MockPlugin {
+    let class = |cid: &[u8; 16], name: &str, category: &str, subs: &str| MockClass {
+        cid: *cid,
+        name: name.into(),
+        category: category.into(),
+        sub_categories: subs.into(),
+        class_flags: 0,
+        cardinality: classinfo_consts::K_MANY_INSTANCES,
+        raw_name: None,
+        fail_info: None,
+    };
+    MockPlugin::new(MockConfig {
+        sub_categories: "Fx|Delay".into(),
+        extra_classes: vec![
+            class(
+                b"OpenVST3MockSynt",
+                "Synth",
+                classinfo_consts::K_VST_AUDIO_EFFECT_CLASS,
+                "Instrument|Synth",
+            ),
+            class(
+                b"OpenVST3MockCtrl",
+                "Gain Controller",
+                classinfo_consts::K_VST_COMPONENT_CONTROLLER_CLASS,
+                "",
+            ),
+        ],
+        factory2,
+        ..MockConfig::default()
+    })
+}
+
+#[test]
+fn find_class_by_cid_uses_class_info2() {
+    let plugin = catalog_plugin(true);
+    let module = plugin.module().unwrap();
+    let gain = module.find_class_by_cid(&Tuid(MOCK_CID)).unwrap();
+    assert_eq!(gain.index, 0);
+    assert_eq!(gain.sub_categories, "Fx|Delay");
+    assert_eq!(gain.vendor, MOCK_VENDOR);
+    assert!(gain.has_sub_category("delay"));
+    assert!(!gain.has_sub_category("Del"));
+    let synth = module
+        .find_class_by_cid(&Tuid(*b"OpenVST3MockSynt"))
+        .unwrap();
+    assert_eq!(synth.index, 1);
+    assert_eq!(synth.name, "Synth");
+    assert!(module.find_class_by_cid(&Tuid([7; 16])).is_none());
+    assert_eq!(module.classes().count(), 3);
+}
+
+fn odd_names_plugin(factory2: bool) -> MockPlugin {
+    let class = |cid: &[u8; 16], raw_name: &[u8], fail_info| MockClass {
+        cid: *cid,
+        name: String::new(),
+        category: MOCK_CATEGORY.into(),
+        sub_categories: "Fx".into(),
+        class_flags: 0,
+        cardinality: classinfo_consts::K_MANY_INSTANCES,
+        raw_name: Some(raw_name.to_vec()),
+        fail_info,
+    };
+    MockPlugin::new(MockConfig {
+        extra_classes: vec![
+            // "Crème Brûlée" in Latin-1.
+            class(b"OpenVST3MockLat1", b"Cr\xe8me Br\xfbl\xe9e", None),
+            class(b"OpenVST3MockUtf8", "Crème".as_bytes(), None),
+            class(b"OpenVST3MockJunk", &[0xff, 0xfe, b'x', 0xc3], None),
+            class(b"OpenVST3MockFail", b"", Some(K_INTERNAL_ERR)),
+        ],
+        factory2,
+        ..MockConfig::default()
+    })
+}
+
+#[test]
+fn class_names_decode_lossily_with_encoding() {
+    for factory2 in [true, false] {
+        let plugin = odd_names_plugin(factory2);
+        let module = plugin.module().unwrap();
+        let entries: Vec<_> = module.classes().collect();
+        assert_eq!(entries.len(), 5);
+
+        let gain = entries[0].unwrap();
+        assert_eq!(gain.name_encoding, Encoding::Ascii);
+        let latin1 = entries[1].unwrap();
+        assert_eq!(latin1.name, "Cr\u{fffd}me Br\u{fffd}l\u{fffd}e");
+        assert_eq!(latin1.name_encoding, Encoding::Lossy);
+        let utf8 = entries[2].unwrap();
+        assert_eq!(utf8.name, "Crème");
+        assert_eq!(utf8.name_encoding, Encoding::Utf8);
+        let junk = entries[3].unwrap();
+        assert_eq!(junk.name, "\u{fffd}\u{fffd}x\u{fffd}");
+        assert_eq!(junk.name_encoding, Encoding::Lossy);
+
+        // The unreadable class keeps its slot instead of shifting later indices.
+        let err = entries[4].unwrap_err();
+        assert_eq!(err.index, 4);
+        assert!(matches!(err.source, HostError::TErr(K_INTERNAL_ERR)));
+        assert_eq!(err.to_string(), format!("class #4: {}", err.source));
+        assert!(module
+            .find_class_by_cid(&Tuid(*b"OpenVST3MockFail"))
+            .is_none());
+        assert_eq!(module.classes_matching(&ClassFilter::Any).count(), 4);
+    }
+}
+
+#[test]
+fn list_classes_keeps_failed_indices() {
+    let plugin = odd_names_plugin(false);
+    let mut module = plugin.module().unwrap();
+    let list = list_classes(&mut module).unwrap();
+    assert_eq!(list.len(), 5);
+    let (index, name, _, _) = list[3].as_ref().unwrap();
+    assert_eq!((*index, name.as_str()), (3, "\u{fffd}\u{fffd}x\u{fffd}"));
+    assert_eq!(list[4].as_ref().unwrap_err().index, 4);
+}
+
+#[test]
+fn cardinality_decodes_per_class() {
+    let plugin = MockPlugin::new(MockConfig {
+        extra_classes: vec![MockClass {
+            cid: *b"OpenVST3MockOnce",
+            name: "Once".into(),
+            category: MOCK_CATEGORY.into(),
+            sub_categories: "Fx".into(),
+            class_flags: 0,
+            cardinality: 1,
+            raw_name: None,
+            fail_info: None,
+        }],
+        ..MockConfig::default()
+    });
+    let module = plugin.module().unwrap();
+    let gain = module.find_class_by_cid(&Tuid(MOCK_CID)).unwrap();
+    assert_eq!(gain.cardinality, Cardinality::ManyInstances);
+    assert_eq!(gain.cardinality.max_instances(), None);
+    let once = module
+        .find_class_by_cid(&Tuid(*b"OpenVST3MockOnce"))
+        .unwrap();
+    assert_eq!(once.cardinality, Cardinality::Limited(1));
+    assert_eq!(once.cardinality.max_instances(), Some(1));
+    assert_eq!(once.cardinality.to_string(), "single instance");
+}
+
+#[test]
+fn single_instance_class_refuses_a_second_live_instance() {
+    let plugin = MockPlugin::new(MockConfig {
+        cardinality: 1,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let first = module.create_plugin(MOCK_CID).unwrap();
+    assert_eq!(module.live_instances(MOCK_CID), 1);
+    let err = module.create_plugin(MOCK_CID).err().unwrap();
+    assert!(matches!(
+        err,
+        HostError::CardinalityExceeded {
+            cid: MOCK_CID,
+            max: 1
+        }
+    ));
+    assert!(err.is_host_fault());
+    assert_eq!(MockCounters::get(&plugin.counters().created), 1);
+
+    let second = module
+        .plugin(MOCK_CID)
+        .ignore_cardinality(true)
+        .create()
+        .unwrap();
+    assert_eq!(module.live_instances(MOCK_CID), 2);
+    drop((first, second));
+    assert_eq!(module.live_instances(MOCK_CID), 0);
+    drop(module.create_plugin(MOCK_CID).unwrap());
+
+    // The state check loads its second instance after dropping the first.
+    let report = module.validate(
+        MOCK_CID,
+        &ValidateOptions {
+            checks: vec![Check::State],
+            ..ValidateOptions::default()
+        },
+    );
+    assert!(report.is_success());
+}
+
+#[test]
+fn many_instance_classes_are_not_limited() {
+    let plugin = MockPlugin::new(MockConfig::default());
+    let mut module = plugin.module().unwrap();
+    let instances: Vec<_> = (0..3)
+        .map(|_| module.create_plugin(MOCK_CID).unwrap())
+        .collect();
+    assert_eq!(module.live_instances(MOCK_CID), 3);
+    drop(instances);
+    assert_eq!(module.live_instances(MOCK_CID), 0);
+}
+
+#[test]
+fn factory_info_and_probe() {
+    let plugin = MockPlugin::new(MockConfig {
+        latency_samples: 64,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let info = module.factory_info().unwrap();
+    assert_eq!(info.vendor, MOCK_VENDOR);
+    assert_eq!(info.url, MOCK_URL);
+    assert_eq!(info.flags(), FactoryFlags::UNICODE);
+
+    let probe = module.probe_class(MOCK_CID).unwrap();
+    assert!(probe.audio_processor);
+    assert!(probe.edit_controller);
+    assert_eq!(probe.buses.audio_inputs, [2]);
+    assert_eq!(probe.buses.audio_outputs, [2]);
+    assert_eq!(probe.latency_samples, Some(64));
+    assert!(matches!(
+        module.probe_class([3; 16]),
+        Err(HostError::CreateInstance {
+            cid: [3, ..],
+            class_name: None,
+            ..
+        })
+    ));
+    assert_torn_down(&plugin);
+}
+
+#[test]
+fn capability_probe_keeps_each_trial_apart() {
+    let plugin = MockPlugin::new(MockConfig {
+        num_params: 3,
+        tail_samples: 512,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let caps = module.probe_capabilities(MOCK_CID).unwrap();
+    assert_eq!(caps.probe, module.probe_class(MOCK_CID).unwrap());
+    assert!(!caps.unit_info && !caps.midi_mapping && !caps.note_expression);
+    assert_eq!((caps.tail_samples, caps.parameter_count), (512, Some(3)));
+    assert_eq!(caps.float64, Outcome::Pass);
+    assert_eq!(caps.state_round_trip, Outcome::Pass);
+    assert_eq!(
+        caps.null_process,
+        [(44_100.0, Outcome::Pass), (192_000.0, Outcome::Pass)]
+    );
+    let setup = plugin.last_setup().unwrap();
+    assert_eq!((setup.sample_rate, setup.max_block), (192_000.0, 64));
+    assert_torn_down(&plugin);
+
+    // A plugin that cannot process still reports everything else.
+    let broken = MockPlugin::new(MockConfig {
+        write_nans: true,
+        ..MockConfig::default()
+    });
+    let mut module = broken.module().unwrap();
+    let caps = module.probe_capabilities(MOCK_CID).unwrap();
+    assert_eq!(caps.state_round_trip, Outcome::Pass);
+    assert!(matches!(caps.float64, Outcome::Fail(_)));
+    assert!(caps
+        .null_process
+        .iter()
+        .all(|(_, outcome)| matches!(outcome, Outcome::Fail(d) if d.contains("non-finite"))));
+    assert!(matches!(
+        module.probe_capabilities([3; 16]),
+        Err(HostError::CreateInstance { .. })
+    ));
+}
+
+#[test]
+fn instance_probe_hands_out_only_what_the_instance_answers() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let probe = instantiate_and_probe(&mut module, MOCK_CID).unwrap();
+    assert_eq!(probe.created_as, iids::ICOMPONENT);
+    assert_eq!(
+        probe.supported_names(),
+        [
+            "FUnknown",
+            "IPluginBase",
+            "IComponent",
+            "IAudioProcessor",
+            "IEditController"
+        ]
+    );
+    assert!(probe.component.is_some() && probe.controller.is_some());
+    assert!(probe.require_processor().is_ok());
+    drop(probe);
+    assert_torn_down(&plugin);
+
+    // A component without a processor: no handle to misuse, and a named refusal.
+    let plugin = MockPlugin::new(MockConfig {
+        hide_processor: true,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let probe = instantiate_and_probe(&mut module, MOCK_CID).unwrap();
+    assert!(probe.processor.is_none());
+    assert!(!probe.supports(&iids::IAUDIO_PROCESSOR));
+    assert!(probe.supports(&iids::IEDIT_CONTROLLER));
+    assert!(matches!(
+        probe.require_processor(),
+        Err(HostError::NoInterface {
+            name: Some("IAudioProcessor"),
+            ..
+        })
+    ));
+
+    // A factory that only creates through one IID is still reached.
+    let plugin = MockPlugin::new(MockConfig {
+        create_only_iid: Some(iids::IEDIT_CONTROLLER),
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let probe = instantiate_and_probe(&mut module, MOCK_CID).unwrap();
+    assert_eq!(probe.created_as, iids::IEDIT_CONTROLLER);
+    assert!(probe.processor.is_some());
+    assert!(matches!(
+        instantiate_and_probe(&mut module, [3; 16]),
+        Err(HostError::CreateInstance { iid, .. }) if iid == iids::ICOMPONENT
+    ));
+}
+
+#[test]
+fn io_mode_is_sent_before_initialize_and_refused_after() {
+    use openvst3_abi::io_mode_consts;
+
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let inst = module.create_plugin(MOCK_CID).unwrap();
+    assert_eq!(inst.io_mode(), IoMode::Simple);
+    // Simple is the default and not sent.
+    assert_eq!(plugin.io_mode(), None);
+    drop(inst);
+
+    let mut inst = module
+        .plugin(MOCK_CID)
+        .io_mode(IoMode::OfflineProcessing)
+        .create()
+        .unwrap();
+    assert_eq!(inst.io_mode(), IoMode::OfflineProcessing);
+    // The mock only records modes it receives before initialize.
+    assert_eq!(plugin.io_mode(), Some(io_mode_consts::K_OFFLINE_PROCESSING));
+    assert!(matches!(
+        inst.component().set_io_mode(IoMode::Advanced),
+        Err(HostError::CallOrder {
+            call: "setIoMode",
+            before: "initialize"
+        })
+    ));
+    assert_eq!(plugin.io_mode(), Some(io_mode_consts::K_OFFLINE_PROCESSING));
+
+    let config = RenderConfig::default();
+    inst.render_offline(&config, &[], 64, |_, _| ()).unwrap();
+    assert_eq!(
+        plugin.last_setup().unwrap().process_mode,
+        process_consts::PROCESS_MODE_OFFLINE
+    );
+
+    // Reloading keeps the mode.
+    let fresh = plugin.module().unwrap();
+    inst.reload(fresh).unwrap();
+    assert_eq!(inst.io_mode(), IoMode::OfflineProcessing);
+    assert_eq!(MockCounters::get(&plugin.counters().initialize_calls), 3);
+}
+
+#[test]
+fn ab_snapshots_recall_the_captured_sound() {
+    let plugin = MockPlugin::new(MockConfig {
+        num_params: 2,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    inst.activate(&setup_32(16)).unwrap();
+    let mut bound = BoundProcessData::<f32>::new(2, 2, 16);
+    for ch in bound.inputs_mut() {
+        ch.fill(0.5);
+    }
+    let mut render = |inst: &mut PluginInstance| {
+        inst.process_bound(&mut bound, 16, None).unwrap();
+        bound.outputs()[0][15]
+    };
+
+    let controller = inst.controller().unwrap().clone();
+    controller.set_param_normalized(0, 0.25).unwrap();
+    controller.set_param_normalized(1, 0.9).unwrap();
+    let a_sound = render(&mut inst);
+    let a = StateSnapshot::capture(&inst).unwrap();
+    assert_eq!(a.params(), [(0, 0.25), (1, 0.9)]);
+    assert_eq!(
+        read_state_bytes(a.component_state()).unwrap(),
+        vec![0.25, 0.9]
+    );
+
+    assert_eq!(inst.ab_slot(), AbSlot::A);
+    assert_eq!(inst.toggle_ab().unwrap(), AbSlot::B);
+    // B starts as a copy of A.
+    assert_eq!(render(&mut inst), a_sound);
+    controller.set_param_normalized(0, 1.0).unwrap();
+    controller.set_param_normalized(1, 0.1).unwrap();
+    let b_sound = render(&mut inst);
+    assert_ne!(b_sound, a_sound);
+
+    assert_eq!(inst.toggle_ab().unwrap(), AbSlot::A);
+    assert_eq!(render(&mut inst), a_sound);
+    assert_eq!(controller.get_param_normalized(1), 0.9);
+    assert_eq!(inst.toggle_ab().unwrap(), AbSlot::B);
+    assert_eq!(render(&mut inst), b_sound);
+    assert_eq!(controller.get_param_normalized(1), 0.1);
+
+    inst.recall(&a).unwrap();
+    assert_eq!(render(&mut inst), a_sound);
+    assert_eq!(StateSnapshot::capture(&inst).unwrap(), a);
+    assert!(!inst.live().is_muted());
+}
+
+#[test]
+fn render_offline_compensates_latency_and_renders_tail() {
+    let plugin = MockPlugin::new(MockConfig {
+        latency_samples: 64,
+        tail_samples: 100,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut instance = module.create_plugin(MOCK_CID).unwrap();
+    assert_eq!(instance.negotiate_channels(2, 2), (2, 2));
+    assert_eq!(instance.tail_frames(48_000), 100);
+    assert_eq!(instance.tail_frames(10), 10);
+    instance
+        .controller()
+        .unwrap()
+        .set_param_normalized(0, 0.5)
+        .unwrap();
+
+    let ramp: Vec<f32> = (0..1000).map(|i| i as f32).collect();
+    let config = RenderConfig {
+        block_size: 128,
+        tail: 100,
+        ..RenderConfig::default()
+    };
+    let mut calls = Vec::new();
+    let out = instance
+        .render_offline(&config, std::slice::from_ref(&ramp), 1000, |done, total| {
+            calls.push((done, total))
+        })
+        .unwrap();
+    assert_eq!(out.len(), 2);
+    assert_eq!(out[0].len(), 1100);
+    // The mock doesn't actually delay, so compensation shifts the input forward.
+    assert_eq!(out[0][0], 32.0);
+    assert_eq!(out[0][935], 999.0 * 0.5);
+    assert!(out[0][936..].iter().all(|&s| s == 0.0));
+    assert!(out[1].iter().all(|&s| s == 0.0));
+    assert_eq!(calls.last(), Some(&(1164, 1164)));
+    assert!(!instance.is_processing());
+
+    drop(instance);
+    assert_torn_down(&plugin);
+}
+
+#[test]
+fn render_offline_feeds_the_sidechain_bus() {
+    let plugin = MockPlugin::new(MockConfig {
+        sidechain_channels: 2,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut instance = module.create_plugin(MOCK_CID).unwrap();
+    assert_eq!(instance.input_bus_channels(), [2, 2]);
+    assert_eq!(instance.negotiate_channels(2, 2), (2, 2));
+    instance
+        .controller()
+        .unwrap()
+        .set_param_normalized(0, 0.5)
+        .unwrap();
+
+    let main = vec![vec![1.0f32; 300]; 2];
+    let key: Vec<f32> = (0..200).map(|i| i as f32).collect();
+    let config = RenderConfig {
+        block_size: 64,
+        sidechain: Some(vec![key]),
+        ..RenderConfig::default()
+    };
+    let out = instance
+        .render_offline(&config, &main, 300, |_, _| ())
+        .unwrap();
+    // The mock outputs the sidechain instead of the main input; its missing right
+    // channel and the frames past its end are silence.
+    assert_eq!(
+        out[0][..200],
+        (0..200).map(|i| i as f32 * 0.5).collect::<Vec<_>>()
+    );
+    assert!(out[0][200..].iter().all(|&s| s == 0.0));
+    assert!(out[1].iter().all(|&s| s == 0.0));
+    assert!(!instance.is_processing());
+
+    // The render deactivated the sidechain bus again, so the main bus alone will do;
+    // with the bus active it will not.
+    let mut bound = BoundProcessData::<f32>::new(2, 2, 64);
+    instance
+        .activate(&ProcessSetup {
+            process_mode: 0,
+            sample_rate: 48_000.0,
+            max_samples_per_block: 64,
+            symbolic_sample_size: 0,
+            flags: 0,
+        })
+        .unwrap();
+    instance.process_bound(&mut bound, 64, None).unwrap();
+    instance
+        .component()
+        .activate_bus(MEDIA_TYPE_AUDIO, openvst3_abi::BUS_DIR_INPUT, 1, true)
+        .unwrap();
+    assert!(instance.process_bound(&mut bound, 64, None).is_err());
+    instance.deactivate();
+    drop(instance);
+    assert_torn_down(&plugin);
+
+    let plain = MockPlugin::new(MockConfig::default());
+    let mut module = plain.module().unwrap();
+    let mut instance = module.create_plugin(MOCK_CID).unwrap();
+    assert_eq!(instance.input_bus_channels(), [2]);
+    let config = RenderConfig {
+        sidechain: Some(vec![vec![0.0; 10]]),
+        ..RenderConfig::default()
+    };
+    assert!(matches!(
+        instance.render_offline(&config, &[], 10, |_, _| ()),
+        Err(HostError::NoSidechainBus)
+    ));
+    assert!(!instance.is_processing());
+}
+
+#[test]
+fn validate_sidechain_feeds_aux_inputs() {
+    let plugin = MockPlugin::new(MockConfig {
+        sidechain_channels: 1,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let case: CheckCase = "sidechain".parse().unwrap();
+    assert_eq!(module.run_check(MOCK_CID, case).outcome, Outcome::Pass);
+
+    let nans = MockPlugin::new(MockConfig {
+        sidechain_channels: 1,
+        write_nans: true,
+        ..MockConfig::default()
+    });
+    let mut module = nans.module().unwrap();
+    match module.run_check(MOCK_CID, case).outcome {
+        Outcome::Fail(msg) => assert!(msg.starts_with("non-finite output"), "{msg}"),
+        other => panic!("expected failure, got {other:?}"),
+    }
+}
+
+#[test]
+fn render_offline_buses_keeps_each_output_bus() {
+    // An instrument with three aux outs; Aux 3 has no gain parameter, so it stays
+    // silent, and Aux 2's is turned down.
+    let plugin = MockPlugin::new(MockConfig {
+        num_params: 3,
+        event_inputs: 1,
+        aux_outputs: 3,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut instance = module.create_plugin(MOCK_CID).unwrap();
+    assert_eq!(instance.output_bus_channels(), [2, 2, 2, 2]);
+    instance
+        .controller()
+        .unwrap()
+        .set_param_normalized(2, 0.0)
+        .unwrap();
+
+    let input = vec![vec![0.5f32; 200], vec![-0.25; 200]];
+    let config = RenderConfig {
+        block_size: 64,
+        aux_outputs: true,
+        ..RenderConfig::default()
+    };
+    let events = RenderEvents::default();
+    let rendered = instance
+        .render_offline_buses(
+            &config,
+            &input,
+            200,
+            &events,
+            &MidiConverter::new(),
+            |_, _| (),
+        )
+        .unwrap();
+    let names: Vec<&str> = rendered.buses.iter().map(|b| b.name.as_str()).collect();
+    assert_eq!(names, ["Main Out", "Aux 1", "Aux 2", "Aux 3"]);
+    let audible: Vec<i32> = rendered.audible().map(|b| b.index).collect();
+    assert_eq!(audible, [0, 1]);
+    assert_eq!(rendered.main(), &input[..]);
+    let aux = &rendered.buses[1].channels;
+    assert!(aux[0].iter().all(|&s| s == 0.25) && aux[1].iter().all(|&s| s == -0.125));
+    assert!(rendered.buses[2].channels[0].iter().all(|&s| s == 0.0));
+    assert!(!instance.is_processing());
+
+    // Without aux_outputs only the main bus is rendered, and plain renders match it.
+    let config = RenderConfig {
+        block_size: 64,
+        ..RenderConfig::default()
+    };
+    let rendered = instance
+        .render_offline_buses(
+            &config,
+            &input,
+            200,
+            &events,
+            &MidiConverter::new(),
+            |_, _| (),
+        )
+        .unwrap();
+    assert_eq!(rendered.buses.len(), 1);
+    let plain = instance
+        .render_offline(&config, &input, 200, |_, _| ())
+        .unwrap();
+    assert_eq!(rendered.into_main(), plain);
+}
+
+#[test]
+#[cfg_attr(
+    miri,
+    ignore = "too slow under Miri; the failing-step test covers the FFI"
+)]
+fn stress_test_walks_every_operation_and_replays_from_the_seed() {
+    let plugin = MockPlugin::new(MockConfig {
+        num_params: 3,
+        param_flags: vec![
+            param_consts::K_CAN_AUTOMATE,
+            param_consts::K_CAN_AUTOMATE,
+            param_consts::K_CAN_AUTOMATE | param_consts::K_IS_BYPASS,
+        ],
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut instance = module.create_plugin(MOCK_CID).unwrap();
+    let options = StressOptions {
+        seed: 42,
+        iterations: 200,
+        ..StressOptions::default()
+    };
+    let report = instance.stress_test(&options);
+    assert!(report.is_success(), "{report}");
+    assert_eq!(report.to_string(), "seed 42: 201 steps, passed");
+    assert_eq!(report.ops[0], StressOp::Activate);
+    let seen = |f: fn(&StressOp) -> bool| report.ops[1..].iter().any(f);
+    assert!(seen(|op| matches!(op, StressOp::Activate)));
+    assert!(seen(|op| matches!(op, StressOp::Process(_))));
+    assert!(seen(|op| matches!(op, StressOp::SampleRate(_))));
+    assert!(seen(|op| matches!(op, StressOp::BlockSize(_))));
+    assert!(seen(|op| matches!(op, StressOp::StateRoundtrip)));
+    assert!(seen(|op| matches!(op, StressOp::ParamStorm(1..=3))));
+    assert!(seen(|op| matches!(op, StressOp::Bypass(true))));
+    assert!(!instance.is_processing());
+    assert_eq!(instance.stress_test(&options), report);
+    assert_ne!(
+        instance
+            .stress_test(&StressOptions {
+                seed: 43,
+                ..options
+            })
+            .ops,
+        report.ops
+    );
+
+    let only_process = instance.stress_test(&StressOptions {
+        allow: StressAllow::none(),
+        ..options
+    });
+    assert!(only_process.ops[1..]
+        .iter()
+        .all(|op| matches!(op, StressOp::Process(1..=512))));
+}
+
+#[test]
+fn stress_test_reports_the_failing_step() {
+    // The third processing block fails.
+    let plugin = MockPlugin::new(MockConfig {
+        process_returns_after_n_blocks: Some((2, K_INTERNAL_ERR)),
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut instance = module.create_plugin(MOCK_CID).unwrap();
+    let options = StressOptions {
+        seed: 7,
+        iterations: 100,
+        allow: StressAllow {
+            activate_cycles: false,
+            ..StressAllow::all()
+        },
+    };
+    let report = instance.stress_test(&options);
+    let failure = report.failure.clone().unwrap();
+    assert_eq!(failure.step, report.ops.len() - 1);
+    assert_eq!(failure.message, "tresult failure: -4");
+    let processed = report
+        .ops
+        .iter()
+        .filter(|op| matches!(op, StressOp::Process(_) | StressOp::ParamStorm(_)))
+        .count();
+    assert_eq!(processed, 3);
+    let text = report.to_string();
+    let mut lines = text.lines();
+    assert_eq!(
+        lines.next().unwrap(),
+        format!(
+            "seed 7: failed at step {} ({}): tresult failure: -4",
+            failure.step, report.ops[failure.step]
+        )
+    );
+    assert_eq!(lines.next(), Some("     0  activate"));
+    assert_eq!(lines.count(), report.ops.len() - 1);
+    assert!(!instance.is_processing());
+
+    let nans = MockPlugin::new(MockConfig {
+        write_nans: true,
+        ..MockConfig::default()
+    });
+    let mut module = nans.module().unwrap();
+    let mut instance = module.create_plugin(MOCK_CID).unwrap();
+    let report = instance.stress_test(&options);
+    assert_eq!(
+        report.failure.unwrap().message,
+        "non-finite output on channel 0"
+    );
+}
+
+#[test]
+fn vstpreset_round_trips_and_rejects_garbage() {
+    let plugin = MockPlugin::new(MockConfig {
+        num_params: 2,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let a = module.create_plugin(MOCK_CID).unwrap();
+    a.controller()
+        .unwrap()
+        .set_param_normalized(1, 0.25)
+        .unwrap();
+    let preset = a.save_preset(MOCK_CID).unwrap();
+    assert_eq!(
+        read_state_bytes(preset.component_state().unwrap()).unwrap(),
+        vec![1.0, 0.25]
+    );
+
+    let bytes = preset.to_bytes();
+    assert_eq!(&bytes[..4], b"VST3");
+    let parsed = VstPreset::parse(&bytes).unwrap();
+    assert_eq!(parsed, preset);
+
+    let b = module.create_plugin(MOCK_CID).unwrap();
+    b.load_preset(&parsed).unwrap();
+    assert_eq!(b.controller().unwrap().get_param_normalized(1), 0.25);
+
+    for bad in [&b"VST2"[..], &bytes[..40], &bytes[..bytes.len() - 1]] {
+        assert!(matches!(
+            VstPreset::parse(bad),
+            Err(HostError::InvalidPreset(_))
+        ));
+    }
+}
+
+#[test]
+fn vstpreset_chunks_are_bounded_by_the_file() {
+    // Header, 64 bytes of data at 48, then a list of `entries`.
+    fn preset(entries: &[(i64, i64)]) -> Vec<u8> {
+        let mut bytes = b"VST3".to_vec();
+        bytes.extend(1i32.to_le_bytes());
+        bytes.extend(fmt_cid_hex(&MOCK_CID).bytes());
+        bytes.extend((48i64 + 64).to_le_bytes());
+        bytes.extend([7u8; 64]);
+        bytes.extend(b"List");
+        bytes.extend((entries.len() as i32).to_le_bytes());
+        for &(start, size) in entries {
+            bytes.extend(b"Comp");
+            bytes.extend(start.to_le_bytes());
+            bytes.extend(size.to_le_bytes());
+        }
+        bytes
+    }
+
+    let ok = VstPreset::parse(&preset(&[(48, 32), (80, 32)])).unwrap();
+    assert_eq!(ok.chunks.len(), 2);
+
+    let invalid = |bytes: Vec<u8>| match VstPreset::parse(&bytes) {
+        Err(HostError::InvalidPreset(msg)) => msg,
+        other => panic!("expected InvalidPreset, got {other:?}"),
+    };
+    assert!(invalid(preset(&[(48, 64); 4])).contains("overlaps"));
+    assert!(invalid(preset(&[(48, i64::MAX)])).contains("exceeds"));
+    assert!(invalid(preset(&[(i64::MAX, 1)])).contains("exceeds"));
+    assert!(invalid(preset(&[(-1, 1)])).contains("negative"));
+    let mut far_list = preset(&[]);
+    far_list[40..48].copy_from_slice(&i64::MAX.to_le_bytes());
+    assert!(invalid(far_list).contains("truncated"));
+    // A count far beyond the entries in the file stops at the end of the file.
+    let mut many = preset(&[(48, 1)]);
+    let count_at = 48 + 64 + 4;
+    many[count_at..count_at + 4].copy_from_slice(&i32::MAX.to_le_bytes());
+    assert!(invalid(many).contains("truncated"));
+}
+
+#[test]
+fn host_limits_bound_what_plugins_and_files_report() {
+    let limit = |result: Result<PluginInstance, HostError>| match result {
+        Err(
+            e @ HostError::LimitExceeded {
+                limit,
+                value,
+                max,
+                origin: LimitOrigin::Plugin,
+            },
+        ) => {
+            assert!(e.is_plugin_fault());
+            (limit, value, max)
+        }
+        Err(e) => panic!("expected LimitExceeded, got {e}"),
+        Ok(_) => panic!("expected LimitExceeded, got an instance"),
+    };
+    let small = HostLimits {
+        max_buses: 8,
+        max_parameters: 4,
+        max_state_bytes: 16,
+        max_events_per_block: 2,
+        ..HostLimits::default()
+    };
+
+    let buses = MockPlugin::new(MockConfig {
+        event_inputs: 100,
+        ..MockConfig::default()
+    });
+    let mut module = buses.module().unwrap();
+    let result = module.plugin(MOCK_CID).limits(small).create();
+    assert_eq!(limit(result), (Limit::Buses, 100, 8));
+    assert_torn_down(&buses);
+    module
+        .plugin(MOCK_CID)
+        .limits(HostLimits::unlimited())
+        .create()
+        .unwrap();
+    drop(module);
+
+    let params = MockPlugin::new(MockConfig {
+        num_params: 10,
+        ..MockConfig::default()
+    });
+    let mut module = params.module().unwrap();
+    let result = module.plugin(MOCK_CID).limits(small).create();
+    assert_eq!(limit(result), (Limit::Parameters, 10, 4));
+    assert_torn_down(&params);
+    drop(module);
+
+    // Too many channels fail creation; too few are left to `checked_bus_layout`.
+    let wide = MockPlugin::new(MockConfig {
+        channels: 10_000,
+        ..MockConfig::default()
+    });
+    let mut module = wide.module().unwrap();
+    match module.create_plugin(MOCK_CID) {
+        Err(HostError::SuspiciousBusInfo { reported, .. }) => assert_eq!(reported, 10_000),
+        Err(e) => panic!("expected SuspiciousBusInfo, got {e}"),
+        Ok(_) => panic!("expected SuspiciousBusInfo, got an instance"),
+    }
+    drop(module);
+    let silent = MockPlugin::new(MockConfig {
+        channels: 0,
+        ..MockConfig::default()
+    });
+    let mut module = silent.module().unwrap();
+    module.create_plugin(MOCK_CID).unwrap();
+    drop(module);
+
+    // Three parameters serialize to more than 16 bytes.
+    let state = MockPlugin::new(MockConfig {
+        num_params: 3,
+        ..MockConfig::default()
+    });
+    let mut module = state.module().unwrap();
+    let mut inst = module.plugin(MOCK_CID).limits(small).create().unwrap();
+    assert_eq!(inst.limits(), &small);
+    match inst.component_state() {
+        Err(HostError::LimitExceeded {
+            limit,
+            value,
+            max,
+            origin,
+        }) => {
+            assert_eq!(
+                (limit, max, origin),
+                (Limit::StateBytes, 16, LimitOrigin::Plugin)
+            );
+            assert!(value > 16, "{value}");
+        }
+        other => panic!("expected LimitExceeded, got {other:?}"),
+    }
+    assert!(StateSnapshot::capture(&inst).is_err());
+    let events = RenderEvents {
+        midi: (0..3).map(|i| (i, [0x90, 60, 100])).collect(),
+        automation: Vec::new(),
+    };
+    let result = inst.render_offline_with(
+        &RenderConfig::default(),
+        &[],
+        64,
+        &events,
+        &MidiConverter::new(),
+        |_, _| {},
+    );
+    // Too many events for a block is the caller's doing, not the plugin's.
+    let err = result.unwrap_err();
+    assert!(matches!(
+        err,
+        HostError::LimitExceeded {
+            limit: Limit::EventsPerBlock,
+            value: 3,
+            max: 2,
+            origin: LimitOrigin::External,
+        }
+    ));
+    assert!(!err.is_plugin_fault());
+    assert!(!inst.is_processing());
+    drop(inst);
+
+    let mut preset = VstPreset::new(MOCK_CID);
+    preset.set_chunk(*b"Comp", vec![0; 32]);
+    let bytes = preset.to_bytes();
+    assert!(VstPreset::parse(&bytes).is_ok());
+    let err = VstPreset::parse_with_limits(&bytes, &small).unwrap_err();
+    assert!(matches!(
+        err,
+        HostError::LimitExceeded {
+            limit: Limit::StateBytes,
+            value: 32,
+            max: 16,
+            origin: LimitOrigin::External,
+        }
+    ));
+    assert!(!err.is_plugin_fault());
+
+    // State copied in by the caller is external too.
+    let mut stream = MemoryStream::with_limit(16, LimitOrigin::External);
+    let mut written = 0;
+    unsafe {
+        let raw = stream.as_ibstream();
+        (*raw).write([0u8; 32].as_ptr().cast(), 32, &mut written);
+    }
+    let err = stream.check_limit().unwrap_err();
+    assert!(matches!(
+        err,
+        HostError::LimitExceeded {
+            origin: LimitOrigin::External,
+            ..
+        }
+    ));
+    assert!(!err.is_plugin_fault());
+}
+
+#[test]
+fn params_format_and_parse_through_the_plugin() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let instance = module.create_plugin(MOCK_CID).unwrap();
+    let controller = instance.controller().unwrap();
+    assert_eq!(controller.format_param(0, 0.354), "0.35");
+    assert_eq!(controller.to_plain(0, 0.25), 0.25);
+    assert_eq!(controller.to_normalized(0, 1.5), 1.0);
+
+    assert_eq!(controller.parse_param(0, "0.5"), Some(0.5));
+    // The mock only parses "0.25"; the fallback reads other separators and units.
+    assert_eq!(controller.parse_param(0, "0,25"), Some(0.25));
+    assert_eq!(controller.parse_param(0, " 0,75 dB"), Some(0.75));
+    assert_eq!(controller.parse_param(0, "\u{2212}1"), Some(0.0));
+    assert_eq!(controller.parse_param(0, "5,0e-2"), Some(0.05));
+    assert_eq!(controller.parse_param(0, "loud"), None);
+    // Unknown ids are errors, not fallbacks.
+    assert_eq!(controller.parse_param(9, "0,5"), None);
+
+    // Nothing displayable: the plain value instead.
+    let plugin = MockPlugin::new(MockConfig {
+        param_string: Some(Vec::new()),
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let instance = module.create_plugin(MOCK_CID).unwrap();
+    assert_eq!(instance.controller().unwrap().format_param(0, 0.5), "0.500");
+
+    // 128 units and no terminator: all of them, nothing past the buffer.
+    let plugin = MockPlugin::new(MockConfig {
+        param_string: Some("-6.0 ".encode_utf16().cycle().take(200).collect()),
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let instance = module.create_plugin(MOCK_CID).unwrap();
+    let text = instance.controller().unwrap().format_param(0, 0.5);
+    assert_eq!(text.len(), 128);
+    assert!(text.starts_with("-6.0 -6.0") && text.ends_with(" -6."));
+}
+
+#[test]
+fn parameter_table_and_display_strings() {
+    let plugin = MockPlugin::new(MockConfig {
+        num_params: 3,
+        param_flags: vec![
+            param_consts::K_CAN_AUTOMATE,
+            param_consts::K_CAN_AUTOMATE | param_consts::K_IS_READ_ONLY,
+            param_consts::K_IS_LIST | 0x4000_0000,
+        ],
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let instance = module.create_plugin(MOCK_CID).unwrap();
+    let controller = instance.controller().unwrap();
+    let params = controller.parameters().unwrap();
+    assert_eq!(params.len(), 3);
+    assert_eq!(params[0].title, "Gain");
+    assert_eq!(params[0].default_normalized, 1.0);
+    assert!(params[0].is_writable());
+    assert!(params[1].is_read_only() && !params[1].is_writable());
+    assert!(!params[2].can_automate());
+    assert_eq!(describe_param_flags(params[2].flags), "list, 0x40000000");
+    assert_eq!(
+        controller.find_parameter(2).unwrap(),
+        Some(params[2].clone())
+    );
+    assert_eq!(controller.find_parameter(7).unwrap(), None);
+    assert_eq!(controller.param_string_by_value(0, 0.25).unwrap(), "0.25");
+    assert!(matches!(
+        controller.parameter_info(3),
+        Err(HostError::TErr(K_INVALID_ARG))
+    ));
+
+    controller.set_param_normalized(0, 0.25).unwrap();
+    let state = instance.component_state().unwrap();
+    assert_eq!(read_state_bytes(&state).unwrap(), vec![0.25, 0.5, 0.5]);
+    let other = module.create_plugin(MOCK_CID).unwrap();
+    other.set_component_state(&state).unwrap();
+    assert_eq!(other.controller().unwrap().get_param_normalized(0), 0.25);
+}
+
+#[test]
+fn validate_passes_mock_and_flags_nan_output() {
+    let plugin = MockPlugin::new(MockConfig {
+        num_params: 2,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    // Automation sweeps a second per parameter; it has a test of its own.
+    let options = ValidateOptions {
+        checks: Check::ALL
+            .into_iter()
+            .filter(|&c| c != Check::Automation)
+            .collect(),
+        sample_rates: vec![48_000.0],
+        block_sizes: vec![32, 100],
+    };
+    let report = module.validate(MOCK_CID, &options);
+    let names: Vec<String> = report.results.iter().map(|r| r.case.to_string()).collect();
+    assert_eq!(
+        names,
+        [
+            "state",
+            "buses",
+            "activation",
+            "arrangement",
+            "controller",
+            "process@48000/32",
+            "process@48000/100",
+            "sidechain",
+            "params",
+            "ranges",
+            "edits",
+            "teardown"
+        ]
+    );
+    for (i, r) in report.results[..11].iter().enumerate() {
+        if i == 7 {
+            assert!(matches!(r.outcome, Outcome::Skip(_)), "{:?}", r.outcome);
+        } else {
+            assert_eq!(r.outcome, Outcome::Pass, "{}", r.case);
+        }
+    }
+    let teardown = &report.results[11].outcome;
+    if cfg!(feature = "leak-audit") {
+        assert_eq!(*teardown, Outcome::Pass);
+    } else {
+        assert!(matches!(teardown, Outcome::Skip(_)));
+    }
+    assert!(report.is_success());
+    assert_torn_down(&plugin);
+
+    let nans = MockPlugin::new(MockConfig {
+        write_nans: true,
+        ..MockConfig::default()
+    });
+    let mut module = nans.module().unwrap();
+    let case: CheckCase = "process@44100/64".parse().unwrap();
+    let result = module.run_check(MOCK_CID, case);
+    match &result.outcome {
+        Outcome::Fail(msg) => assert!(msg.starts_with("non-finite output on channel 0"), "{msg}"),
+        other => panic!("expected failure, got {other:?}"),
+    }
+
+    let refusing = MockPlugin::new(MockConfig {
+        setup_processing_result: K_INTERNAL_ERR,
+        ..MockConfig::default()
+    });
+    let mut module = refusing.module().unwrap();
+    let report = module.validate(
+        MOCK_CID,
+        &ValidateOptions {
+            checks: vec![Check::State, Check::Process],
+            sample_rates: vec![48_000.0],
+            block_sizes: vec![64],
+        },
+    );
+    assert_eq!(
+        (report.passed(), report.failed(), report.skipped()),
+        (1, 1, 0)
+    );
+    assert!(!report.is_success());
+}
+
+#[test]
+fn bus_permutations_cover_every_bus() {
+    use openvst3_abi::{BUS_DIR_OUTPUT, MEDIA_TYPE_AUDIO, MEDIA_TYPE_EVENT};
+
+    let plugin = MockPlugin::new(MockConfig {
+        sidechain_channels: 1,
+        event_inputs: 1,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let inst = module.create_plugin(MOCK_CID).unwrap();
+    let layout = inst.component().bus_layout().unwrap();
+    let bus = |media_type, direction, index| BusId {
+        media_type,
+        direction,
+        index,
+    };
+    let main_in = bus(MEDIA_TYPE_AUDIO, BUS_DIR_INPUT, 0);
+    let sidechain = bus(MEDIA_TYPE_AUDIO, BUS_DIR_INPUT, 1);
+    let main_out = bus(MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT, 0);
+    let events = bus(MEDIA_TYPE_EVENT, BUS_DIR_INPUT, 0);
+    assert_eq!(
+        BusPermutation::AuxOnly.steps(&layout),
+        [
+            (main_in, false),
+            (sidechain, true),
+            (main_out, false),
+            (events, false)
+        ]
+    );
+    assert_eq!(
+        BusPermutation::Reversed.steps(&layout),
+        [
+            (events, true),
+            (main_out, true),
+            (sidechain, true),
+            (main_in, true)
+        ]
+    );
+    let toggled = BusPermutation::Toggled.steps(&layout);
+    assert_eq!(toggled.len(), 4 * 5);
+    // Each bus ends where it started: the sidechain off, the rest on.
+    assert_eq!(
+        &toggled[5..10],
+        [true, false, true, false, false].map(|on| (sidechain, on))
+    );
+    assert_eq!(toggled[19], (events, true));
+    assert_eq!(
+        BusPermutation::Toggled.to_string(),
+        "toggled while inactive"
+    );
+}
+
+#[test]
+fn validate_activation_names_the_permutation() {
+    let sidechained = MockPlugin::new(MockConfig {
+        sidechain_channels: 1,
+        event_inputs: 1,
+        ..MockConfig::default()
+    });
+    let result = sidechained
+        .module()
+        .unwrap()
+        .run_check(MOCK_CID, "activation".parse().unwrap());
+    assert_eq!(result.outcome, Outcome::Pass);
+    assert_eq!(MockCounters::get(&sidechained.counters().process_calls), 5);
+    assert_eq!(
+        MockCounters::get(&sidechained.counters().active_instances),
+        0
+    );
+
+    let clingy = MockPlugin::new(MockConfig {
+        deactivate_bus_result: K_INTERNAL_ERR,
+        ..MockConfig::default()
+    });
+    let result = clingy
+        .module()
+        .unwrap()
+        .run_check(MOCK_CID, "activation".parse().unwrap());
+    let Outcome::Fail(msg) = &result.outcome else {
+        panic!("{:?}", result.outcome);
+    };
+    assert!(
+        msg.starts_with(&format!(
+            "all off: activateBus(audio input bus 0, off) returned tresult {K_INTERNAL_ERR}; \
+             all off: activateBus(audio output bus 0, off)"
+        )),
+        "{msg}"
+    );
+    assert!(msg.ends_with("; and 3 more"), "{msg}");
+    assert_torn_down(&clingy);
+}
+
+#[test]
+fn validate_arrangement_compares_layouts_with_bus_info() {
+    let plugin = MockPlugin::new(MockConfig {
+        sidechain_channels: 1,
+        ..MockConfig::default()
+    });
+    let result = plugin
+        .module()
+        .unwrap()
+        .run_check(MOCK_CID, "arrangement".parse().unwrap());
+    assert_eq!(result.outcome, Outcome::Pass);
+
+    let confused = MockPlugin::new(MockConfig {
+        sidechain_channels: 1,
+        reported_arrangement: Some(openvst3_abi::speaker_arr::STEREO),
+        ..MockConfig::default()
+    });
+    let result = confused
+        .module()
+        .unwrap()
+        .run_check(MOCK_CID, "arrangement".parse().unwrap());
+    assert_eq!(
+        result.outcome,
+        Outcome::Fail(
+            "audio input bus 1: getBusArrangement is stereo (2 speakers) but getBusInfo reports \
+             channel count 1"
+                .into()
+        )
+    );
+}
+
+#[test]
+fn validate_ranges_names_bad_defaults_and_steps() {
+    let plugin = MockPlugin::new(MockConfig {
+        num_params: 4,
+        param_defaults: vec![1.0, 1.5],
+        // Shown with two decimals: 4 steps read 0.00, 0.25, ..., 200 steps repeat.
+        param_steps: vec![0, 0, 4, 200],
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let result = module.run_check(MOCK_CID, "ranges".parse().unwrap());
+    let Outcome::Fail(msg) = &result.outcome else {
+        panic!("{:?}", result.outcome);
+    };
+    assert_eq!(
+        msg,
+        "parameter 1 (\"Param 1\"): default 1.5 is outside [0, 1]; \
+         parameter 3 (\"Param 3\"): 200 steps (201 values) but 101 distinct display strings"
+    );
+
+    let fine = MockPlugin::new(MockConfig {
+        num_params: 3,
+        param_steps: vec![0, 1, 4],
+        ..MockConfig::default()
+    });
+    let result = fine
+        .module()
+        .unwrap()
+        .run_check(MOCK_CID, "ranges".parse().unwrap());
+    assert_eq!(result.outcome, Outcome::Pass);
+}
+
+#[test]
+#[cfg_attr(miri, ignore = "sweeps a second of audio per parameter")]
+fn validate_automation_sweeps_every_automatable_parameter() {
+    let options = ValidateOptions {
+        checks: vec![Check::Automation],
+        ..ValidateOptions::default()
+    };
+    let following = MockPlugin::new(MockConfig {
+        num_params: 2,
+        follow_automation: true,
+        ..MockConfig::default()
+    });
+    let report = following.module().unwrap().validate(MOCK_CID, &options);
+    assert_eq!(report.results[0].outcome, Outcome::Pass);
+    // Two parameters, each 94 ramp blocks of 512 frames and one restoring block.
+    assert_eq!(
+        MockCounters::get(&following.counters().process_calls),
+        2 * 95
+    );
+    assert_torn_down(&following);
+
+    let deaf = MockPlugin::new(MockConfig {
+        num_params: 2,
+        ..MockConfig::default()
+    });
+    let report = deaf.module().unwrap().validate(MOCK_CID, &options);
+    assert_eq!(
+        report.results[0].outcome,
+        Outcome::Warn("automating none of the 2 automatable parameters changed the output".into())
+    );
+    assert!(report.is_success());
+
+    let nans = MockPlugin::new(MockConfig {
+        write_nans: true,
+        ..MockConfig::default()
+    });
+    let report = nans.module().unwrap().validate(MOCK_CID, &options);
+    assert_eq!(
+        report.results[0].outcome,
+        Outcome::Fail(
+            "parameter 0 (\"Gain\"): non-finite output on channel 0 in block 0 of the sweep".into()
+        )
+    );
+
+    let read_only = MockPlugin::new(MockConfig {
+        param_flags: vec![param_consts::K_IS_READ_ONLY],
+        ..MockConfig::default()
+    });
+    let report = read_only.module().unwrap().validate(MOCK_CID, &options);
+    assert_eq!(
+        report.results[0].outcome,
+        Outcome::Skip("no automatable parameters".into())
+    );
+}
+
+#[test]
+#[cfg_attr(miri, ignore = "processes in real time")]
+fn validate_warns_about_edit_storms() {
+    let options = ValidateOptions {
+        checks: vec![Check::Edits],
+        ..ValidateOptions::default()
+    };
+    let quiet = MockPlugin::default();
+    let report = quiet.module().unwrap().validate(MOCK_CID, &options);
+    assert_eq!(report.results[0].outcome, Outcome::Pass);
+
+    // 16 edits per 512-frame block at 48 kHz is 1500 a second.
+    let storm = MockPlugin::new(MockConfig {
+        edits_per_block: 16,
+        ..MockConfig::default()
+    });
+    let report = storm.module().unwrap().validate(MOCK_CID, &options);
+    let Outcome::Warn(msg) = &report.results[0].outcome else {
+        panic!("{:?}", report.results[0].outcome);
+    };
+    assert!(msg.contains("(1500/s, over 100/s)"), "{msg}");
+    assert!(report.is_success());
+    assert_torn_down(&storm);
+}
+
+#[test]
+fn class_filters_compose() {
+    let plugin = catalog_plugin(true);
+    let module = plugin.module().unwrap();
+    let names = |f: ClassFilter| -> Vec<String> {
+        module
+            .classes_matching(&f)
+            .map(|c| c.name.clone())
+            .collect()
+    };
+    assert_eq!(names(ClassFilter::audio_effects()), ["OpenVST3 Mock Gain"]);
+    assert_eq!(names(ClassFilter::instruments()), ["Synth"]);
+    assert_eq!(names(ClassFilter::controllers()), ["Gain Controller"]);
+    assert_eq!(
+        names(ClassFilter::category(
+            classinfo_consts::K_VST_AUDIO_EFFECT_CLASS
+        )),
+        ["OpenVST3 Mock Gain", "Synth"]
+    );
+    assert_eq!(
+        names(ClassFilter::sub_category("Delay").or(ClassFilter::controllers())),
+        ["OpenVST3 Mock Gain", "Gain Controller"]
+    );
+    assert_eq!(names(ClassFilter::Any.not()), Vec::<String>::new());
+}
+
+#[test]
+fn v1_factory_classes_have_no_subcategories() {
+    let plugin = catalog_plugin(false);
+    let module = plugin.module().unwrap();
+    let gain = module.find_class_by_cid(&Tuid(MOCK_CID)).unwrap();
+    assert_eq!(gain.sub_categories, "");
+    assert_eq!(gain.vendor, "");
+    // Without subcategories nothing is known to be an instrument.
+    assert_eq!(
+        module
+            .classes_matching(&ClassFilter::audio_effects())
+            .count(),
+        2
+    );
+}
+
+#[test]
+fn class_flags_and_kind_helpers() {
+    let plugin = MockPlugin::new(MockConfig {
+        sub_categories: "Instrument|Synth|Stereo".into(),
+        class_flags: (ClassFlags::DISTRIBUTABLE | ClassFlags::SIMPLE_MODE_SUPPORTED).bits() | 0x100,
+        ..MockConfig::default()
+    });
+    let module = plugin.module().unwrap();
+    let class = module.find_class_by_cid(&Tuid(MOCK_CID)).unwrap();
+    assert!(class.is_instrument());
+    assert!(!class.is_effect());
+    assert!(class.supports_distribution());
+    assert!(class.supports_simple_mode());
+    assert_eq!(
+        describe_class_flags(class.flags()),
+        "distributable, simple mode, 0x100"
+    );
+    assert_eq!(describe_class_flags(ClassFlags::empty()), "");
+
+    let fx = MockPlugin::default();
+    let module = fx.module().unwrap();
+    let class = module.find_class_by_cid(&Tuid(MOCK_CID)).unwrap();
+    assert!(class.is_effect());
+    assert!(!class.supports_distribution());
+}
+
+#[test]
+fn midi_round_trips_through_a_thru_plugin() {
+    let plugin = MockPlugin::new(MockConfig {
+        event_inputs: 1,
+        event_outputs: 1,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    inst.activate(&setup_32(64)).unwrap();
+    let sent: [(i32, [u8; 3]); 5] = [
+        (0, [0x90, 60, 100]),
+        (3, [0x91, 64, 1]),
+        (17, [0xA0, 60, 33]),
+        (17, [0x80, 60, 64]),
+        (63, [0x81, 64, 127]),
+    ];
+    let converter = MidiConverter::new();
+    let mut events = EventList::with_capacity(8);
+    let mut params = ParameterChanges::with_capacity(1, 1);
+    for (offset, bytes) in &sent {
+        converter.convert(bytes, *offset, &mut events, &mut params);
+    }
+    let mut bound = BoundProcessData::<f32>::new(2, 2, 64);
+    bound.set_output_events(8);
+    let stats = inst
+        .process_bound(&mut bound, 64, Some((&mut events, &mut params)))
+        .unwrap();
+    assert_eq!(stats.out_events, 5);
+
+    let out = bound.output_events().unwrap().events();
+    let received = unsafe { events_to_midi(out, ChannelPolicy::Keep) };
+    let received: Vec<_> = received
+        .iter()
+        .map(|(offset, msg)| (*offset, msg.as_bytes().to_vec()))
+        .collect();
+    let sent: Vec<_> = sent.iter().map(|(o, b)| (*o, b.to_vec())).collect();
+    assert_eq!(received, sent);
+
+    // The list starts empty every block.
+    events.clear();
+    inst.process_bound(&mut bound, 64, Some((&mut events, &mut params)))
+        .unwrap();
+    assert!(bound.output_events().unwrap().is_empty());
+}
+
+#[test]
+fn rejected_mono_is_explained_against_a_stereo_plugin() {
+    use openvst3_abi::speaker_arr::*;
+    let plugin = MockPlugin::new(MockConfig {
+        supported_arrangements: vec![STEREO],
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let inst = module.create_plugin(MOCK_CID).unwrap();
+    assert!(matches!(
+        inst.set_bus_arrangements(&[MONO], &[MONO]),
+        Err(HostError::ArrangementRejected { .. })
+    ));
+
+    let report = explain_arrangement_mismatch(inst.processor(), &[MONO], &[MONO]);
+    let bus = |direction| BusArrangement {
+        direction,
+        index: 0,
+        requested: Some(MONO),
+        plugin: Some(STEREO),
+        suggested: Some(STEREO),
+    };
+    assert_eq!(report.inputs, [bus(BUS_DIR_INPUT)]);
+    assert_eq!(report.outputs, [bus(BUS_DIR_OUTPUT)]);
+    assert_eq!(report.mismatches().count(), 2);
+    assert_eq!(
+        report.to_string(),
+        "input bus 0: requested mono (M), plugin has stereo (L R); \
+         nearest it accepts: stereo (L R)\n\
+         output bus 0: requested mono (M), plugin has stereo (L R); \
+         nearest it accepts: stereo (L R)"
+    );
+}
+
+#[test]
+fn rejected_surround_suggests_the_nearest_accepted_layout() {
+    use openvst3_abi::speaker_arr::*;
+    let plugin = MockPlugin::new(MockConfig {
+        aux_outputs: 1,
+        supported_arrangements: vec![STEREO, QUAD],
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let inst = module.create_plugin(MOCK_CID).unwrap();
+    let (ins, outs) = ([STEREO], [SURROUND_51, STEREO]);
+    assert!(inst.set_bus_arrangements(&ins, &outs).is_err());
+
+    let report = explain_arrangement_mismatch(inst.processor(), &ins, &outs);
+    assert!(report.inputs.iter().all(|bus| !bus.is_mismatch()));
+    let main = &report.outputs[0];
+    assert_eq!(
+        (main.plugin, main.suggested),
+        (Some(STEREO), Some(QUAD)),
+        "4 channels are nearer 6 than 2 are"
+    );
+    assert_eq!(report.mismatches().count(), 1);
+    let lines: Vec<String> = report.to_string().lines().map(str::to_owned).collect();
+    assert_eq!(
+        lines,
+        [
+            "input bus 0: stereo (L R)",
+            "output bus 0: requested 5.1 (L R C LFE Ls Rs), plugin has stereo (L R); \
+             nearest it accepts: quad (L R Ls Rs)",
+            "output bus 1: stereo (L R)",
+        ]
+    );
+    // The probes leave the plugin on its own layouts.
+    assert_eq!(
+        inst.processor().bus_arrangement(BUS_DIR_OUTPUT, 0).unwrap(),
+        STEREO
+    );
+    assert!(inst.processor().bus_arrangement(BUS_DIR_OUTPUT, 2).is_err());
+}
+
+#[test]
+fn negotiation_keeps_a_third_order_ambisonic_bus() {
+    use crate::render::arrangement_for_channels;
+    use openvst3_abi::speaker_arr::*;
+    let plugin = MockPlugin::new(MockConfig {
+        channels: 16,
+        supported_arrangements: vec![AMBI_3RD_ORDER_ACN],
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let inst = module.create_plugin(MOCK_CID).unwrap();
+    let speakers = arrangement_for_channels(16);
+    assert!(inst.set_bus_arrangements(&[speakers], &[speakers]).is_err());
+
+    let (ins, outs) = inst.channel_arrangements(16, 16);
+    assert_eq!(
+        (&ins[..], &outs[..]),
+        (&[AMBI_3RD_ORDER_ACN][..], &[AMBI_3RD_ORDER_ACN][..])
+    );
+    assert_eq!(inst.negotiate_channels(16, 16), (16, 16));
+    assert!(inst.set_bus_arrangements(&ins, &outs).is_ok());
+
+    // Asked for 16 speakers, the explainer names the format it has instead.
+    let report = explain_arrangement_mismatch(inst.processor(), &[speakers], &[speakers]);
+    let main = &report.outputs[0];
+    assert_eq!(
+        (main.plugin, main.suggested),
+        (Some(AMBI_3RD_ORDER_ACN), Some(AMBI_3RD_ORDER_ACN))
+    );
+    assert_eq!(
+        main.to_string(),
+        format!(
+            "output bus 0: requested 16ch ({}), plugin has ambi3 (3rd-order ambisonics, \
+             ACN/SN3D); nearest it accepts: ambi3 (3rd-order ambisonics, ACN/SN3D)",
+            format_speakers(speakers)
+        )
+    );
+}
+
+#[test]
+fn component_handler_reaches_the_controller() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let inst = module.create_plugin(MOCK_CID).unwrap();
+    let handler = ComponentHandler::new(|_| {});
+    inst.controller()
+        .unwrap()
+        .set_component_handler(&handler)
+        .unwrap();
+    assert!(!handler.is_poisoned());
+}
+
+#[test]
+fn param_cache_follows_output_changes_and_perform_edit() {
+    use std::sync::{Arc, Mutex};
+    let plugin = MockPlugin::new(MockConfig {
+        num_params: 70,
+        // Parameter 1 moves twice in a block; the last point counts.
+        output_params: vec![(1, 0.25), (1, 0.75), (66, 0.5)],
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    let controller = inst.controller().unwrap().clone();
+    controller.set_param_normalized(2, 0.125).unwrap();
+    let cache = inst.enable_param_cache().unwrap();
+    assert!(Arc::ptr_eq(&cache, &inst.enable_param_cache().unwrap()));
+    assert_eq!(cache.len(), 70);
+    assert_eq!(cache.value(2), 0.125);
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let seen = events.clone();
+    let handler = ComponentHandler::new(move |e| seen.lock().unwrap().push(e));
+    handler.track_params(&cache);
+    controller.set_component_handler(&handler).unwrap();
+    let raw = handler.as_icomponent_handler();
+
+    inst.activate(&setup_32(16)).unwrap();
+    let mut bound = BoundProcessData::<f32>::new(2, 2, 16);
+    let stats = inst.process_bound(&mut bound, 16, None).unwrap();
+    assert_eq!(stats.out_params, 2);
+    assert_eq!((cache.value(1), cache.value(66)), (0.75, 0.5));
+
+    // The processor reported parameter 1 this block; parameter 2 is the controller's.
+    unsafe {
+        assert_eq!((*raw).perform_edit(1, 0.1), K_RESULT_OK);
+        assert_eq!((*raw).perform_edit(2, 0.9), K_RESULT_OK);
+    }
+    assert_eq!((cache.value(1), cache.value(2)), (0.75, 0.9));
+    // The callback sees every edit, kept or not.
+    assert_eq!(events.lock().unwrap().len(), 2);
+    let mut changed = Vec::new();
+    cache.take_changes(|id, _| changed.push(id));
+    assert_eq!(changed, [1, 2, 66]);
+
+    // Next block: the edit counts until the processor overwrites it.
+    cache.begin_block();
+    unsafe { (*raw).perform_edit(1, 0.1) };
+    assert_eq!(cache.value(1), 0.1);
+    // The raw path lends the instance's list too.
+    let mut out = [vec![0.0f32; 16], vec![0.0f32; 16]];
+    let mut ptrs = [out[0].as_mut_ptr(), out[1].as_mut_ptr()];
+    let mut bus = AudioBusBuffers32 {
+        num_channels: 2,
+        silence_flags: 0,
+        channel_buffers: ptrs.as_mut_ptr(),
+    };
+    let mut data = ProcessData32 {
+        num_inputs: 0,
+        num_outputs: 1,
+        inputs: core::ptr::null_mut(),
+        outputs: &mut bus,
+        num_samples: 16,
+        input_parameter_changes: core::ptr::null_mut(),
+        output_parameter_changes: core::ptr::null_mut(),
+        input_events: core::ptr::null_mut(),
+        output_events: core::ptr::null_mut(),
+        process_context: core::ptr::null_mut(),
+    };
+    unsafe { inst.process_32f(&mut data) }.unwrap();
+    assert!(data.output_parameter_changes.is_null());
+    assert_eq!(cache.value(1), 0.75);
+    drop(inst);
+}
+
+#[test]
+fn names_are_cached_until_a_restart_changes_them() {
+    use openvst3_abi::{restart_consts, MEDIA_TYPE_AUDIO};
+    use std::sync::Arc;
+    let plugin = MockPlugin::new(MockConfig {
+        num_params: 2,
+        ..MockConfig::default()
+    });
+    let info_calls = || MockCounters::get(&plugin.counters().parameter_info_calls);
+    let mut module = plugin.module().unwrap();
+    let inst = module.create_plugin(MOCK_CID).unwrap();
+    let controller = inst.controller().unwrap().clone();
+    let handler = ComponentHandler::new(|_| {});
+    controller.set_component_handler(&handler).unwrap();
+
+    assert_eq!(controller.param_title(1).as_deref(), Some("Param 1"));
+    assert_eq!(info_calls(), 2);
+    let gain = controller.param_title(0).unwrap();
+    assert_eq!(&*gain, "Gain");
+    assert!(Arc::ptr_eq(
+        &gain,
+        &inst.controller().unwrap().param_title(0).unwrap()
+    ));
+    assert_eq!(controller.param_title(9), None);
+    assert_eq!(info_calls(), 2);
+    let component = inst.component();
+    let out = component
+        .bus_name(MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT, 0)
+        .unwrap();
+    assert_eq!(&*out, "Main Out");
+
+    // Restarts that do not touch names keep them.
+    let raw = handler.as_icomponent_handler();
+    let latency = restart_consts::K_LATENCY_CHANGED | restart_consts::K_PARAM_VALUES_CHANGED;
+    assert_eq!(unsafe { (*raw).restart_component(latency) }, K_RESULT_OK);
+    controller.param_title(0).unwrap();
+    assert_eq!(info_calls(), 2);
+
+    // A rename drops every title, but not the bus names.
+    assert_eq!(plugin.rename_param(1, "Drive"), K_RESULT_OK);
+    assert_eq!(controller.param_title(1).as_deref(), Some("Drive"));
+    assert_eq!(info_calls(), 4);
+    assert!(Arc::ptr_eq(
+        &out,
+        &component
+            .bus_name(MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT, 0)
+            .unwrap()
+    ));
+    let io = restart_consts::K_IO_CHANGED;
+    assert_eq!(unsafe { (*raw).restart_component(io) }, K_RESULT_OK);
+    assert!(!Arc::ptr_eq(
+        &out,
+        &component
+            .bus_name(MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT, 0)
+            .unwrap()
+    ));
+
+    // Titles decoded before a restart are not stored after it.
+    let names = controller.names();
+    let generation = names.generation();
+    assert!(names.invalidate(restart_consts::K_PARAM_TITLES_CHANGED));
+    names.store_params(generation, Arc::default());
+    assert!(names.params().is_none());
+    assert!(!names.invalidate(restart_consts::K_LATENCY_CHANGED));
+}
+
+#[cfg(feature = "dlopen")]
+#[test]
+fn scan_diff_matches_by_cid_then_path() {
+    let install = |path: &str, cid: [u8; 16], name: &str, version: &str| ScannedPlugin {
+        path: path.into(),
+        cid,
+        name: name.into(),
+        vendor: "Vendor".into(),
+        version: version.into(),
+        sub_categories: "Fx".into(),
+        sdk_version: "VST 3.7.0".into(),
+        binary_hash: None,
+    };
+    let gain = install("/a/Gain.vst3", MOCK_CID, "Gain", "1.0");
+    let comp = install("/a/Suite.vst3", [1; 16], "Comp", "2.0");
+    let eq = install("/a/Suite.vst3", [2; 16], "EQ", "2.0");
+    let old = [gain.clone(), comp.clone(), eq.clone()];
+    assert!(ScanDiff::compare(&old, &old).is_empty());
+
+    // Version bump.
+    let newer = install("/a/Gain.vst3", MOCK_CID, "Gain", "1.1");
+    let diff = ScanDiff::compare(&old, &[newer.clone(), comp.clone(), eq.clone()]);
+    assert_eq!(diff.updated, vec![(gain.clone(), newer)]);
+    assert!(diff.added.is_empty() && diff.removed.is_empty());
+
+    // A class renamed in place changes its bundle's class list, so both classes of
+    // the bundle are updated.
+    let renamed = install("/a/Suite.vst3", [2; 16], "Equalizer", "2.0");
+    let diff = ScanDiff::compare(&old, &[gain.clone(), comp.clone(), renamed.clone()]);
+    assert_eq!(
+        diff.updated,
+        vec![(comp.clone(), comp.clone()), (eq.clone(), renamed)]
+    );
+
+    // A moved bundle is the same plugin; a new CID is added, a missing one removed.
+    let moved = install("/b/Gain.vst3", MOCK_CID, "Gain", "1.0");
+    let delay = install("/a/Delay.vst3", [3; 16], "Delay", "1.0");
+    let diff = ScanDiff::compare(&old, &[moved, comp.clone(), delay.clone()]);
+    assert!(diff.updated.iter().all(|(a, _)| a.cid == [1; 16]));
+    assert_eq!(diff.added, vec![delay]);
+    assert_eq!(diff.removed, vec![eq.clone()]);
+
+    // Duplicate installs pair up by path; hashes only count when both scans have one.
+    let mut hashed = gain.clone();
+    hashed.binary_hash = Some("aa".into());
+    let copy = install("/c/Gain.vst3", MOCK_CID, "Gain", "1.0");
+    let diff = ScanDiff::compare(std::slice::from_ref(&gain), &[copy.clone(), hashed.clone()]);
+    assert!(diff.updated.is_empty());
+    assert_eq!(diff.added, vec![copy]);
+    let mut rehashed = hashed.clone();
+    rehashed.binary_hash = Some("bb".into());
+    let diff = ScanDiff::compare(
+        std::slice::from_ref(&hashed),
+        std::slice::from_ref(&rehashed),
+    );
+    assert_eq!(diff.updated, vec![(hashed, rehashed)]);
+}
+
+#[cfg(feature = "dlopen")]
+#[test]
+fn duplicate_installs_are_chosen_by_policy() {
+    let install = |path: &str, version: &str| ScannedPlugin {
+        path: path.into(),
+        cid: MOCK_CID,
+        name: "Gain".into(),
+        vendor: "Vendor".into(),
+        version: version.into(),
+        sub_categories: "Fx".into(),
+        sdk_version: "VST 3.7.0".into(),
+        binary_hash: None,
+    };
+    let system = install("/system/Gain.vst3", "1.10");
+    let user = install("/home/me/.vst3/Gain.vst3", "1.9");
+    let extra = install("/extra/Gain.vst3", "1.10.0-beta");
+    let mut other = install("/system/Other.vst3", "1.0");
+    other.cid = [7; 16];
+    let plugins = [system.clone(), other, user.clone(), extra.clone()];
+    let scanner = |policy| {
+        Scanner::new()
+            .without_default_paths()
+            .add_path("/system")
+            .add_user_path("/home/me/.vst3")
+            .add_path("/extra")
+            .duplicate_policy(policy)
+    };
+
+    for (policy, order) in [
+        (DuplicatePolicy::UserDirWins, [&user, &system, &extra]),
+        (DuplicatePolicy::NewestVersion, [&system, &extra, &user]),
+        (DuplicatePolicy::PathOrder, [&system, &user, &extra]),
+    ] {
+        let groups = scanner(policy).duplicates(&plugins);
+        assert_eq!(groups.len(), 1, "{policy:?}");
+        assert_eq!(groups[0].cid, MOCK_CID);
+        assert_eq!(&groups[0].chosen, order[0], "{policy:?}");
+        assert_eq!(
+            groups[0].shadowed.iter().collect::<Vec<_>>(),
+            order[1..],
+            "{policy:?}"
+        );
+    }
+}
+
+#[test]
+fn editor_view_attaches_resizes_and_detaches_on_drop() {
+    use openvst3_abi::platform_types;
+
+    let plain = MockPlugin::default();
+    let mut module = plain.module().unwrap();
+    let instance = module.create_plugin(MOCK_CID).unwrap();
+    assert!(matches!(
+        instance.controller().unwrap().create_editor(),
+        Err(HostError::NoInterface {
+            name: Some("IPlugView"),
+            ..
+        })
+    ));
+    drop(instance);
+
+    let mock = MockPlugin::new(MockConfig {
+        editor_size: Some((400, 300)),
+        ..MockConfig::default()
+    });
+    let mut module = mock.module().unwrap();
+    let instance = module.create_plugin(MOCK_CID).unwrap();
+    let mut view = instance.controller().unwrap().create_editor().unwrap();
+    let counters = mock.counters();
+    assert_eq!(MockCounters::get(&counters.live_views), 1);
+    assert_eq!(view.size().unwrap().width(), 400);
+    assert!(view.platform_type_supported(platform_types::X11_EMBED_WINDOW_ID));
+    assert!(!view.platform_type_supported(b"Wayland\0"));
+
+    let mut parent = 0u64;
+    unsafe {
+        assert!(view
+            .attach(core::ptr::null_mut(), platform_types::X11_EMBED_WINDOW_ID)
+            .is_err());
+        view.attach(
+            &mut parent as *mut u64 as *mut c_void,
+            platform_types::X11_EMBED_WINDOW_ID,
+        )
+        .unwrap();
+    }
+    assert_eq!(MockCounters::get(&counters.attached_views), 1);
+
+    // The plugin asks for a new size; the host applies it later.
+    assert_eq!(mock.request_editor_resize(640, 480), K_RESULT_OK);
+    let wanted = view.take_resize_request().unwrap();
+    assert_eq!((wanted.width(), wanted.height()), (640, 480));
+    assert_eq!(view.take_resize_request(), None);
+    view.set_size(wanted).unwrap();
+    assert_eq!(view.size().unwrap(), wanted);
+    let small = view.constrain(openvst3_abi::ViewRect {
+        right: 10,
+        bottom: 10,
+        ..Default::default()
+    });
+    assert_eq!((small.width(), small.height()), (100, 100));
+
+    drop(view);
+    assert_eq!(MockCounters::get(&counters.attached_views), 0);
+    assert_eq!(MockCounters::get(&counters.live_views), 0);
+    assert_eq!(mock.request_editor_resize(640, 480), K_RESULT_FALSE);
+}
+
+#[test]
+fn tail_tells_none_finite_and_infinite_apart() {
+    assert_eq!(Tail::from_samples(process_consts::NO_TAIL), Tail::None);
+    assert_eq!(Tail::from_samples(480), Tail::Samples(480));
+    assert_eq!(
+        Tail::from_samples(process_consts::INFINITE_TAIL),
+        Tail::Infinite
+    );
+    assert_eq!(Tail::None.frames(1000), (0, false));
+    assert_eq!(Tail::Samples(480).frames(1000), (480, false));
+    assert_eq!(Tail::Samples(4800).frames(1000), (1000, true));
+    assert_eq!(Tail::Infinite.frames(1000), (1000, true));
+
+    let plugin = MockPlugin::new(MockConfig {
+        tail_samples: process_consts::INFINITE_TAIL,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let instance = module.create_plugin(MOCK_CID).unwrap();
+    assert!(instance.processor().tail().is_infinite());
+    assert_eq!(instance.tail_frames(1000), 1000);
+}
+
+#[test]
+fn render_offline_caps_an_infinite_plugin_tail() {
+    let plugin = MockPlugin::new(MockConfig {
+        channels: 1,
+        tail_samples: process_consts::INFINITE_TAIL,
+        feedback: 0.999,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut instance = module.create_plugin(MOCK_CID).unwrap();
+    let mut impulse = vec![0.0f32; 100];
+    impulse[0] = 1.0;
+    let config = RenderConfig {
+        block_size: 64,
+        input_channels: 1,
+        output_channels: 1,
+        tail: 50,
+        plugin_tail: Some(1000),
+        ..RenderConfig::default()
+    };
+    let events = RenderEvents::default();
+    let converter = MidiConverter::new();
+    let rendered = instance
+        .render_offline_buses(
+            &config,
+            &[impulse.clone()],
+            100,
+            &events,
+            &converter,
+            |_, _| (),
+        )
+        .unwrap();
+    assert!(rendered.tail_capped);
+    let main = rendered.main();
+    assert_eq!(main[0].len(), 100 + 50 + 1000);
+    // Still ringing where the cap cut it.
+    assert!(main[0][1149] > 0.2);
+
+    // Without a plugin tail, only `tail` is rendered and nothing is capped.
+    let config = RenderConfig {
+        plugin_tail: None,
+        ..config
+    };
+    let rendered = instance
+        .render_offline_buses(&config, &[impulse], 100, &events, &converter, |_, _| ())
+        .unwrap();
+    assert!(!rendered.tail_capped);
+    assert_eq!(rendered.main()[0].len(), 150);
+}
+
+#[test]
+fn run_until_silent_waits_out_an_exponential_tail() {
+    // An impulse through a one-pole feedback of 0.999 falls below -60 dBFS after
+    // ln(0.001) / ln(0.999), about 6904 samples.
+    let plugin = MockPlugin::new(MockConfig {
+        channels: 1,
+        tail_samples: process_consts::INFINITE_TAIL,
+        feedback: 0.999,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut instance = module.create_plugin(MOCK_CID).unwrap();
+    instance.activate(&setup_32(64)).unwrap();
+    let mut bound = BoundProcessData::<f32>::new(1, 1, 64);
+    bound.inputs_mut().next().unwrap()[0] = 1.0;
+    instance.process_bound(&mut bound, 64, None).unwrap();
+
+    let silence = SilenceDetector::new().threshold_db(-60.0).hold_blocks(4);
+    let mut last = Vec::new();
+    let run = run_until_silent(&mut instance, &mut bound, silence.clone(), 1000, |b, n| {
+        last = b.outputs()[0][..n].to_vec();
+    })
+    .unwrap();
+    assert!(run.silent);
+    assert_eq!(run.frames, run.blocks * 64);
+    // 6904 samples after the impulse, plus the 4 quiet blocks it takes to be sure.
+    let quiet_from = 6904 - 64;
+    assert!(
+        (quiet_from + 3 * 64..=quiet_from + 5 * 64).contains(&run.frames),
+        "{run:?}"
+    );
+    assert!(last.iter().all(|s| s.abs() <= 0.001));
+
+    // A budget shorter than the tail runs out first.
+    instance.deactivate();
+    instance.activate(&setup_32(64)).unwrap();
+    bound.inputs_mut().next().unwrap()[0] = 1.0;
+    instance.process_bound(&mut bound, 64, None).unwrap();
+    let run = run_until_silent(&mut instance, &mut bound, silence, 10, |_, _| ()).unwrap();
+    assert_eq!((run.blocks, run.silent), (10, false));
+    instance.deactivate();
+}
+
+#[test]
+fn architecture_comes_from_get_controller_class_id() {
+    let plugin = catalog_plugin(true);
+    let mut module = plugin.module().unwrap();
+    let instance = module.create_plugin(MOCK_CID).unwrap();
+    assert_eq!(instance.component().controller_cid(), None);
+    assert_eq!(instance.architecture(), PluginArchitecture::SingleComponent);
+    assert_eq!(instance.architecture().to_string(), "single component");
+    drop(instance);
+    let probe = module.probe_class(MOCK_CID).unwrap();
+    assert_eq!(probe.architecture, PluginArchitecture::SingleComponent);
+
+    // A controller class the factory lists as one.
+    let ctrl = *b"OpenVST3MockCtrl";
+    let dual = |controller_cid: [u8; 16]| {
+        let catalog = catalog_plugin(true);
+        MockPlugin::new(MockConfig {
+            controller_cid: Some(controller_cid),
+            ..catalog.config().clone()
+        })
+    };
+    let plugin = dual(ctrl);
+    let mut module = plugin.module().unwrap();
+    let instance = module.create_plugin(MOCK_CID).unwrap();
+    assert_eq!(
+        instance.architecture(),
+        PluginArchitecture::DualComponent {
+            controller_cid: Tuid(ctrl)
+        }
+    );
+    assert_eq!(
+        instance.architecture().to_string(),
+        format!("dual component (controller {})", fmt_cid_hex(&ctrl))
+    );
+    drop(instance);
+    let probe = module.probe_class(MOCK_CID).unwrap();
+    assert_eq!(probe.architecture.controller_cid(), Some(Tuid(ctrl)));
+    let controller: CheckCase = "controller".parse().unwrap();
+    assert_eq!(
+        module.run_check(MOCK_CID, controller).outcome,
+        Outcome::Pass
+    );
+
+    // Naming a class the factory does not list, or one that is not a controller,
+    // only warns.
+    let missing = *b"NoSuchController";
+    let plugin = dual(missing);
+    let mut module = plugin.module().unwrap();
+    let report = module.validate(
+        MOCK_CID,
+        &ValidateOptions {
+            checks: vec![Check::Controller],
+            ..ValidateOptions::default()
+        },
+    );
+    assert_eq!(
+        report.results[0].outcome,
+        Outcome::Warn(format!(
+            "getControllerClassId names {}, which the factory does not list",
+            fmt_cid_hex(&missing)
+        ))
+    );
+    assert_eq!((report.passed(), report.warned()), (0, 1));
+    assert!(report.is_success());
+
+    let plugin = dual(*b"OpenVST3MockSynt");
+    let mut module = plugin.module().unwrap();
+    match module.run_check(MOCK_CID, controller).outcome {
+        Outcome::Warn(msg) => assert!(msg.ends_with("a \"Audio Module Class\" class"), "{msg}"),
+        other => panic!("expected a warning, got {other:?}"),
+    }
+}
+
+#[test]
+fn process_mode_switch_keeps_the_setup_of_a_live_instance() {
+    let plugin = MockPlugin::new(MockConfig {
+        reject_process_mode: Some(process_consts::PROCESS_MODE_PREFETCH),
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    assert!(matches!(
+        inst.set_process_mode(ProcessMode::Offline),
+        Err(HostError::CallOrder { .. })
+    ));
+    inst.activate(&setup_32(64)).unwrap();
+    assert_eq!(inst.process_mode(), Some(ProcessMode::Realtime));
+
+    inst.set_process_mode(ProcessMode::Offline).unwrap();
+    let setup = plugin.last_setup().unwrap();
+    assert_eq!(
+        (setup.process_mode, setup.sample_rate, setup.max_block),
+        (process_consts::PROCESS_MODE_OFFLINE, 48_000.0, 64)
+    );
+    assert_eq!(MockCounters::get(&plugin.counters().active_instances), 1);
+    assert!(inst.is_processing());
+
+    assert!(inst.set_process_mode(ProcessMode::Prefetch).is_err());
+    assert_eq!(
+        plugin.last_setup().unwrap().process_mode,
+        process_consts::PROCESS_MODE_OFFLINE
+    );
+    assert_eq!(inst.process_mode(), Some(ProcessMode::Offline));
+    let mut bound = BoundProcessData::<f32>::new(2, 2, 64);
+    inst.process_bound(&mut bound, 64, None).unwrap();
+
+    inst.set_process_mode(ProcessMode::Realtime).unwrap();
+    assert_eq!(
+        ProcessMode::from_raw(plugin.last_setup().unwrap().process_mode),
+        Some(ProcessMode::Realtime)
+    );
+}
+
+#[test]
+#[cfg_attr(miri, ignore = "foreign scheduling calls")]
+fn thread_promotion_falls_back_instead_of_failing() {
+    // On its own thread: promotion lasts until the thread exits.
+    let (realtime, high) = std::thread::spawn(|| {
+        (
+            promote_current_thread(Profile::Realtime),
+            promote_current_thread(Profile::High),
+        )
+    })
+    .join()
+    .unwrap();
+    for achieved in [&realtime, &high] {
+        match achieved {
+            Achieved::Realtime(how) | Achieved::High(how) => assert!(!how.is_empty()),
+            Achieved::Unchanged(why) => assert!(!why.is_empty()),
+        }
+    }
+    assert!(!matches!(high, Achieved::Realtime(_)), "{high}");
+
+    let plugin = MockPlugin::new(MockConfig::default());
+    let rendered = std::thread::spawn(move || {
+        let mut module = plugin.module().unwrap();
+        let mut inst = module.create_plugin(MOCK_CID).unwrap();
+        let config = RenderConfig {
+            block_size: 64,
+            priority: Some(Profile::High),
+            ..RenderConfig::default()
+        };
+        let input = vec![vec![0.0; 256]; 2];
+        inst.render_offline_buses(
+            &config,
+            &input,
+            256,
+            &RenderEvents::default(),
+            &MidiConverter::new(),
+            |_, _| {},
+        )
+        .unwrap()
+    })
+    .join()
+    .unwrap();
+    assert!(rendered.priority.is_some());
+    assert_eq!(rendered.main()[0].len(), 256);
+}
+
+#[test]
+fn setup_builder_downgrades_precision_the_plugin_refuses() {
+    let plugin = MockPlugin::new(MockConfig {
+        float64: false,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+
+    // Asked before the precision is set: the downgrade still applies.
+    let builder = ProcessSetupBuilder::new()
+        .for_processor(inst.processor())
+        .precision(SymbolicSampleSize::Sample64);
+    assert_eq!(
+        builder.warnings(),
+        [SetupWarning::PrecisionDowngraded {
+            requested: SymbolicSampleSize::Sample64,
+            used: SymbolicSampleSize::Sample32,
+        }]
+    );
+    let setup = builder.build().unwrap();
+    assert_eq!(
+        setup.symbolic_sample_size,
+        process_consts::SYMBOLIC_SAMPLE_32
+    );
+    inst.activate(&setup).unwrap();
+    inst.deactivate();
+
+    // Without asking, the 64-bit setup goes through and the plugin refuses it.
+    let unchecked = ProcessSetupBuilder::new().precision(SymbolicSampleSize::Sample64);
+    assert!(unchecked.warnings().is_empty());
+    assert!(inst.activate(&unchecked.build().unwrap()).is_err());
+
+    let capable = MockPlugin::new(MockConfig::default());
+    let mut module = capable.module().unwrap();
+    let inst = module.create_plugin(MOCK_CID).unwrap();
+    let builder = ProcessSetupBuilder::new()
+        .precision(SymbolicSampleSize::Sample64)
+        .for_processor(inst.processor());
+    assert!(builder.warnings().is_empty());
+    assert_eq!(
+        builder.build().unwrap().symbolic_sample_size,
+        process_consts::SYMBOLIC_SAMPLE_64
+    );
+}
+
+#[test]
+fn mixed_precision_chain_matches_an_f64_reference() {
+    const FRAMES: usize = 64;
+    let gains = [0.75, 0.4, 0.9];
+    let slot = |float64: bool| {
+        MockPlugin::new(MockConfig {
+            float64,
+            feedback: 0.3,
+            ..MockConfig::default()
+        })
+    };
+    let mixed = [slot(true), slot(false), slot(true)];
+    let reference = [slot(true), slot(true), slot(true)];
+    let mut modules: Vec<_> = mixed
+        .iter()
+        .chain(&reference)
+        .map(|plugin| plugin.module().unwrap())
+        .collect();
+    let mut instances: Vec<_> = modules
+        .iter_mut()
+        .zip(gains.iter().cycle())
+        .map(|(module, &gain)| {
+            let inst = module.create_plugin(MOCK_CID).unwrap();
+            inst.controller()
+                .unwrap()
+                .set_param_normalized(0, gain)
+                .unwrap();
+            inst
+        })
+        .collect();
+
+    let plan = PrecisionPlan::for_processors(
+        instances[..3]
+            .iter()
+            .map(|inst| (SlotPrecision::Auto, inst.processor())),
+    );
+    assert_eq!(
+        plan.to_string(),
+        "64-bit -> 32-bit -> 64-bit (2 conversions)"
+    );
+    let all_64 = PrecisionPlan::for_processors(
+        instances[3..]
+            .iter()
+            .map(|inst| (SlotPrecision::Auto, inst.processor())),
+    );
+    assert_eq!(all_64.conversions(), 0);
+    let sizes = plan.slots().iter().chain(all_64.slots());
+    for (inst, &size) in instances.iter_mut().zip(sizes) {
+        let setup = ProcessSetupBuilder::new()
+            .max_block(FRAMES as i32)
+            .precision(size)
+            .for_processor(inst.processor());
+        assert!(setup.warnings().is_empty());
+        inst.activate(&setup.build().unwrap()).unwrap();
+    }
+
+    let (mixed_insts, reference_insts) = instances.split_at_mut(3);
+    let mut a = BoundProcessData::<f64>::new(2, 2, FRAMES);
+    let mut b = BoundProcessData::<f32>::new(2, 2, FRAMES);
+    let mut c = BoundProcessData::<f64>::new(2, 2, FRAMES);
+    let mut refs: Vec<_> = (0..3)
+        .map(|_| BoundProcessData::<f64>::new(2, 2, FRAMES))
+        .collect();
+    let mut worst = 0.0f64;
+    for block in 0..16 {
+        let signal = |chan: &mut [f64]| {
+            for (i, s) in chan.iter_mut().enumerate() {
+                *s = ((block * FRAMES + i) as f64 * 0.05).sin();
+            }
+        };
+        a.inputs_mut().for_each(signal);
+        refs[0].inputs_mut().for_each(signal);
+
+        mixed_insts[0].process_bound(&mut a, FRAMES, None).unwrap();
+        pass_block(&a, &mut b, FRAMES);
+        mixed_insts[1].process_bound(&mut b, FRAMES, None).unwrap();
+        pass_block(&b, &mut c, FRAMES);
+        mixed_insts[2].process_bound(&mut c, FRAMES, None).unwrap();
+
+        for k in 0..3 {
+            reference_insts[k]
+                .process_bound(&mut refs[k], FRAMES, None)
+                .unwrap();
+            if k < 2 {
+                let (done, next) = refs.split_at_mut(k + 1);
+                pass_block(&done[k], &mut next[0], FRAMES);
+            }
+        }
+        for (got, want) in c.outputs().iter().zip(refs[2].outputs()) {
+            for (&g, &w) in got.iter().zip(want.iter()) {
+                worst = worst.max((g - w).abs());
+            }
+        }
+    }
+    // Two roundings to f32 on the way through slot 1, carried on by slot 2's
+    // feedback; signals stay under 2.
+    assert!(worst > 0.0, "the 32-bit slot rounded nothing");
+    assert!(worst <= 8.0 * f32::EPSILON as f64, "worst error {worst}");
+}
+
+#[test]
+fn activate_refuses_an_invalid_setup_before_calling_the_plugin() {
+    let plugin = MockPlugin::new(MockConfig::default());
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    let bad = ProcessSetup {
+        max_samples_per_block: 0,
+        ..ProcessSetupBuilder::new().build().unwrap()
+    };
+    assert!(matches!(
+        inst.activate(&bad),
+        Err(HostError::InvalidSetup(SetupError::MaxBlock(0)))
+    ));
+    assert_eq!(MockCounters::get(&plugin.counters().setup_calls), 0);
+    assert!(!inst.is_processing());
+}
@@ -0,0 +1,219 @@
+use core::ffi::c_void;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use openvst3_abi::{
+    iids, tresult, FUnknown, FactoryFlags, Fuid, IPluginFactory, IPluginFactory2,
+    IPluginFactory2VTable, IPluginFactoryVTable, PClassInfo, PClassInfo2, PFactoryInfo, Tuid,
+    K_INVALID_ARG, K_NO_INTERFACE, K_RESULT_OK,
+};
+
+use super::instance::MockInstance;
+use super::{
+    copy_bytes, copy_cstr, Method, MockClass, MockShared, MOCK_CATEGORY, MOCK_CID, MOCK_EMAIL,
+    MOCK_URL, MOCK_VENDOR,
+};
+
+#[repr(C)]
+pub(crate) struct MockFactory {
+    vtbl: *const IPluginFactory2VTable,
+    refs: AtomicU32,
+    shared: Arc<MockShared>,
+}
+
+static FACTORY_VTBL: IPluginFactory2VTable = IPluginFactory2VTable {
+    base: IPluginFactoryVTable {
+        query_interface: f_query_interface,
+        add_ref: f_add_ref,
+        release: f_release,
+        get_factory_info: f_get_factory_info,
+        count_classes: f_count_classes,
+        get_class_info: f_get_class_info,
+        create_instance: f_create_instance,
+    },
+    get_class_info2: f_get_class_info2,
+};
+
+impl MockFactory {
+    pub(crate) fn create(shared: Arc<MockShared>) -> *mut MockFactory {
+        Box::into_raw(Box::new(MockFactory {
+            vtbl: &FACTORY_VTBL,
+            refs: AtomicU32::new(1),
+            shared,
+        }))
+    }
+
+    pub(crate) unsafe fn destroy(this: *mut MockFactory) {
+        drop(Box::from_raw(this));
+    }
+}
+
+unsafe fn factory<'a>(this: *mut FUnknown) -> &'a MockFactory {
+    &*(this as *const MockFactory)
+}
+
+unsafe extern "C" fn f_query_interface(
+    this_: *mut FUnknown,
+    iid: *const Fuid,
+    obj: *mut *mut c_void,
+) -> tresult {
+    if iid.is_null() || obj.is_null() {
+        return K_INVALID_ARG;
+    }
+    let v2 = *iid == iids::IPLUGIN_FACTORY2 && factory(this_).shared.config.factory2;
+    if v2 || *iid == iids::IPLUGIN_FACTORY || *iid == iids::FUNKNOWN {
+        f_add_ref(this_);
+        *obj = this_ as *mut c_void;
+        return K_RESULT_OK;
+    }
+    *obj = core::ptr::null_mut();
+    K_NO_INTERFACE
+}
+
+// The factory is owned by `MockPlugin`; plugin-side refcounting never frees it.
+unsafe extern "C" fn f_add_ref(this_: *mut FUnknown) -> u32 {
+    factory(this_).refs.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+unsafe extern "C" fn f_release(this_: *mut FUnknown) -> u32 {
+    let f = factory(this_);
+    if f.refs.load(Ordering::SeqCst) <= 1 {
+        return 1;
+    }
+    f.refs.fetch_sub(1, Ordering::SeqCst) - 1
+}
+
+unsafe extern "C" fn f_get_factory_info(
+    _this: *mut IPluginFactory,
+    info: *mut PFactoryInfo,
+) -> tresult {
+    if info.is_null() {
+        return K_INVALID_ARG;
+    }
+    let info = &mut *info;
+    copy_cstr(&mut info.vendor, MOCK_VENDOR);
+    copy_cstr(&mut info.url, MOCK_URL);
+    copy_cstr(&mut info.email, MOCK_EMAIL);
+    info.flags = FactoryFlags::UNICODE.bits();
+    K_RESULT_OK
+}
+
+/// The instantiable gain class followed by the descriptor-only extra classes.
+fn classes(shared: &MockShared) -> Vec<MockClass> {
+    let config = &shared.config;
+    let mut all = vec![MockClass {
+        cid: MOCK_CID,
+        name: config.class_name.clone(),
+        category: MOCK_CATEGORY.into(),
+        sub_categories: config.sub_categories.clone(),
+        class_flags: config.class_flags,
+        cardinality: config.cardinality,
+        raw_name: None,
+        fail_info: None,
+    }];
+    all.extend(config.extra_classes.iter().cloned());
+    all
+}
+
+unsafe fn class_at(this_: *mut FUnknown, index: i32) -> Result<MockClass, tresult> {
+    let f = factory(this_);
+    f.shared.maybe_fault(Method::GetClassInfo);
+    if let Some(tr) = f.shared.config.fail_get_class_info {
+        return Err(tr);
+    }
+    let class = usize::try_from(index)
+        .ok()
+        .and_then(|i| classes(&f.shared).into_iter().nth(i))
+        .ok_or(K_INVALID_ARG)?;
+    match class.fail_info {
+        Some(tr) => Err(tr),
+        None => Ok(class),
+    }
+}
+
+impl MockClass {
+    fn name_bytes(&self) -> &[u8] {
+        self.raw_name.as_deref().unwrap_or(self.name.as_bytes())
+    }
+}
+
+unsafe extern "C" fn f_count_classes(this_: *mut IPluginFactory) -> i32 {
+    classes(&factory(this_ as *mut FUnknown).shared).len() as i32
+}
+
+unsafe extern "C" fn f_get_class_info(
+    this_: *mut IPluginFactory,
+    index: i32,
+    info: *mut PClassInfo,
+) -> tresult {
+    if info.is_null() {
+        return K_INVALID_ARG;
+    }
+    let class = match class_at(this_ as *mut FUnknown, index) {
+        Ok(c) => c,
+        Err(tr) => return tr,
+    };
+    let info = &mut *info;
+    for (d, s) in info.cid.iter_mut().zip(class.cid.iter()) {
+        *d = *s as i8;
+    }
+    info.cardinality = class.cardinality;
+    copy_cstr(&mut info.category, &class.category);
+    copy_bytes(&mut info.name, class.name_bytes());
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn f_get_class_info2(
+    this_: *mut IPluginFactory2,
+    index: i32,
+    info: *mut PClassInfo2,
+) -> tresult {
+    if info.is_null() {
+        return K_INVALID_ARG;
+    }
+    let class = match class_at(this_ as *mut FUnknown, index) {
+        Ok(c) => c,
+        Err(tr) => return tr,
+    };
+    let info = &mut *info;
+    for (d, s) in info.cid.iter_mut().zip(class.cid.iter()) {
+        *d = *s as i8;
+    }
+    info.cardinality = class.cardinality;
+    copy_cstr(&mut info.category, &class.category);
+    copy_bytes(&mut info.name, class.name_bytes());
+    info.class_flags = class.class_flags;
+    copy_cstr(&mut info.sub_categories, &class.sub_categories);
+    copy_cstr(&mut info.vendor, MOCK_VENDOR);
+    copy_cstr(&mut info.version, env!("CARGO_PKG_VERSION"));
+    copy_cstr(&mut info.sdk_version, "VST 3.7.0");
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn f_create_instance(
+    this_: *mut IPluginFactory,
+    cid: *const Tuid,
+    iid: *const Tuid,
+    obj: *mut *mut c_void,
+) -> tresult {
+    if cid.is_null() || iid.is_null() || obj.is_null() {
+        return K_INVALID_ARG;
+    }
+    *obj = core::ptr::null_mut();
+    let f = factory(this_ as *mut FUnknown);
+    if (*cid).0 != MOCK_CID {
+        return K_NO_INTERFACE;
+    }
+    let tr = f.shared.config.create_instance_result;
+    if tr != K_RESULT_OK {
+        return tr;
+    }
+    if matches!(f.shared.config.create_only_iid, Some(only) if only != *iid) {
+        return K_NO_INTERFACE;
+    }
+    let inst = MockInstance::create(f.shared.clone());
+    let tr = MockInstance::query(inst, &*iid, obj);
+    // Drop the creation reference; on success the caller holds the QI reference.
+    MockInstance::release(inst);
+    tr
+}
@@ -0,0 +1,1170 @@
+use core::ffi::{c_void, CStr};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use openvst3_abi::{
+    iids, param_consts, process_consts, speaker_arr, tresult, AudioBusBuffers32, AudioBusBuffers64,
+    BusInfo, Event, FUnknown, Fuid, IAudioProcessor, IAudioProcessorVTable, IBStream, IComponent,
+    IComponentHandler, IComponentVTable, IEditController, IEditControllerVTable, IEventList,
+    IParameterChanges, ParamID, ParamValue, ParameterInfo, ProcessData32, ProcessData64,
+    ProcessSetup, String128, Tuid, BUS_DIR_INPUT, BUS_DIR_OUTPUT, BUS_FLAG_DEFAULT_ACTIVE,
+    BUS_TYPE_AUX, BUS_TYPE_MAIN, K_INVALID_ARG, K_NO_INTERFACE, K_RESULT_FALSE, K_RESULT_OK,
+    MEDIA_TYPE_AUDIO, MEDIA_TYPE_EVENT, VIEW_TYPE_EDITOR,
+};
+
+use super::{copy_cstr, copy_str16, view, Method, MockSetup, MockShared};
+
+/// Interface sub-object: the vtable pointer the plugin ABI sees, followed by a
+/// back-pointer to the owning instance.
+#[repr(C)]
+struct Slot {
+    vtbl: *const c_void,
+    owner: *const MockInstance,
+}
+
+pub(crate) struct MockInstance {
+    component: Slot,
+    processor: Slot,
+    controller: Slot,
+    refs: AtomicU32,
+    shared: Arc<MockShared>,
+    params: Vec<AtomicU64>,
+    active: AtomicBool,
+    /// Set by a successful `initialize`; `setIoMode` is refused afterwards.
+    initialized: AtomicBool,
+    /// `activateBus` state of event input 0.
+    event_bus_active: AtomicBool,
+    /// `activateBus` state of the sidechain input.
+    sidechain_active: AtomicBool,
+    /// Blocks processed so far, for `process_returns_after_n_blocks`.
+    blocks: AtomicUsize,
+    /// The last output sample of each main output channel, for `feedback`; sized by
+    /// `setActive` so `process` does not allocate.
+    feedback: Mutex<Vec<f64>>,
+    /// The input and output arrangements of the last accepted `setBusArrangements`.
+    arrangements: Mutex<Option<(Vec<u64>, Vec<u64>)>>,
+    /// The controller's own state: whatever its last `setState` read, empty before.
+    controller_state: Mutex<Vec<u8>>,
+}
+
+static COMPONENT_VTBL: IComponentVTable = IComponentVTable {
+    query_interface: i_query_interface,
+    add_ref: i_add_ref,
+    release: i_release,
+    initialize: c_initialize,
+    terminate: c_terminate,
+    get_controller_class_id: c_get_controller_class_id,
+    get_bus_count: c_get_bus_count,
+    get_bus_info: c_get_bus_info,
+    activate_bus: c_activate_bus,
+    set_active: c_set_active,
+    set_state: c_set_state,
+    get_state: c_get_state,
+    set_io_mode: c_set_io_mode,
+};
+
+static PROCESSOR_VTBL: IAudioProcessorVTable = IAudioProcessorVTable {
+    query_interface: i_query_interface,
+    add_ref: i_add_ref,
+    release: i_release,
+    initialize: p_initialize,
+    terminate: p_terminate,
+    set_processing: p_set_processing,
+    setup_processing: p_setup_processing,
+    set_bus_arrangements: p_set_bus_arrangements,
+    process_32f: p_process_32f,
+    process_64f: p_process_64f,
+    get_latency_samples: p_get_latency_samples,
+    get_tail_samples: p_get_tail_samples,
+    can_process_sample_size: p_can_process_sample_size,
+    get_bus_arrangement: p_get_bus_arrangement,
+};
+
+static CONTROLLER_VTBL: IEditControllerVTable = IEditControllerVTable {
+    query_interface: i_query_interface,
+    add_ref: i_add_ref,
+    release: i_release,
+    initialize: e_initialize,
+    terminate: e_terminate,
+    set_component_state: e_set_component_state,
+    set_state: e_set_state,
+    get_state: e_get_state,
+    get_parameter_count: e_get_parameter_count,
+    get_parameter_info: e_get_parameter_info,
+    get_param_string_by_value: e_get_param_string_by_value,
+    get_param_value_by_string: e_get_param_value_by_string,
+    normalized_param_to_plain: e_normalized_param_to_plain,
+    plain_param_to_normalized: e_plain_param_to_normalized,
+    get_param_normalized: e_get_param_normalized,
+    set_param_normalized: e_set_param_normalized,
+    set_component_handler: e_set_component_handler,
+    create_view: e_create_view,
+};
+
+impl MockInstance {
+    pub(crate) fn create(shared: Arc<MockShared>) -> *mut MockInstance {
+        let params = (0..shared.config.num_params.max(1))
+            .map(|i| AtomicU64::new(default_value(i).to_bits()))
+            .collect();
+        shared.counters.created.fetch_add(1, Ordering::SeqCst);
+        shared
+            .counters
+            .live_instances
+            .fetch_add(1, Ordering::SeqCst);
+        let raw = Box::into_raw(Box::new(MockInstance {
+            component: Slot {
+                vtbl: &COMPONENT_VTBL as *const _ as *const c_void,
+                owner: core::ptr::null(),
+            },
+            processor: Slot {
+                vtbl: &PROCESSOR_VTBL as *const _ as *const c_void,
+                owner: core::ptr::null(),
+            },
+            controller: Slot {
+                vtbl: &CONTROLLER_VTBL as *const _ as *const c_void,
+                owner: core::ptr::null(),
+            },
+            refs: AtomicU32::new(1),
+            shared,
+            params,
+            active: AtomicBool::new(false),
+            initialized: AtomicBool::new(false),
+            event_bus_active: AtomicBool::new(false),
+            sidechain_active: AtomicBool::new(false),
+            blocks: AtomicUsize::new(0),
+            feedback: Mutex::new(Vec::new()),
+            arrangements: Mutex::new(None),
+            controller_state: Mutex::new(Vec::new()),
+        }));
+        unsafe {
+            (*raw).component.owner = raw;
+            (*raw).processor.owner = raw;
+            (*raw).controller.owner = raw;
+        }
+        raw
+    }
+
+    pub(crate) unsafe fn query(
+        this: *mut MockInstance,
+        iid: &Tuid,
+        obj: *mut *mut c_void,
+    ) -> tresult {
+        let shared = &(*this).shared;
+        let slot: *mut Slot =
+            if *iid == iids::ICOMPONENT || *iid == iids::IPLUGIN_BASE || *iid == iids::FUNKNOWN {
+                core::ptr::addr_of_mut!((*this).component)
+            } else if *iid == iids::IAUDIO_PROCESSOR && !shared.config.hide_processor {
+                core::ptr::addr_of_mut!((*this).processor)
+            } else if *iid == iids::IEDIT_CONTROLLER {
+                core::ptr::addr_of_mut!((*this).controller)
+            } else {
+                *obj = core::ptr::null_mut();
+                return K_NO_INTERFACE;
+            };
+        (*this).refs.fetch_add(1, Ordering::SeqCst);
+        *obj = slot as *mut c_void;
+        K_RESULT_OK
+    }
+
+    pub(crate) unsafe fn release(this: *mut MockInstance) -> u32 {
+        let left = (*this).refs.fetch_sub(1, Ordering::SeqCst) - 1;
+        if left == 0 {
+            let inst = Box::from_raw(this);
+            if inst.active.load(Ordering::SeqCst) {
+                inst.shared
+                    .counters
+                    .active_instances
+                    .fetch_sub(1, Ordering::SeqCst);
+            }
+            inst.shared
+                .counters
+                .live_instances
+                .fetch_sub(1, Ordering::SeqCst);
+        }
+        left
+    }
+
+    fn param(&self, id: ParamID) -> Option<f64> {
+        self.params
+            .get(id as usize)
+            .map(|v| f64::from_bits(v.load(Ordering::Relaxed)))
+    }
+
+    fn set_param(&self, id: ParamID, value: f64) -> bool {
+        match self.params.get(id as usize) {
+            Some(v) => {
+                v.store(value.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn values(&self) -> Vec<f64> {
+        self.params
+            .iter()
+            .map(|v| f64::from_bits(v.load(Ordering::Relaxed)))
+            .collect()
+    }
+}
+
+fn default_value(index: usize) -> f64 {
+    if index == 0 {
+        1.0
+    } else {
+        0.5
+    }
+}
+
+unsafe fn owner<'a, T>(this: *mut T) -> &'a MockInstance {
+    &*(*(this as *const Slot)).owner
+}
+
+// ----- State format: u32 count, then count x f64 (little-endian) --------------
+
+/// Serialize parameter values in the mock's state format.
+pub fn write_state_bytes(values: &[f64]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + values.len() * 8);
+    out.extend_from_slice(&(values.len() as u32).to_le_bytes());
+    for v in values {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    out
+}
+
+/// Parse the mock's state format; `None` if the data is truncated.
+pub fn read_state_bytes(bytes: &[u8]) -> Option<Vec<f64>> {
+    let count = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as usize;
+    let body = bytes.get(4..4 + count.checked_mul(8)?)?;
+    Some(
+        body.chunks_exact(8)
+            .map(|c| f64::from_le_bytes(c.try_into().unwrap()))
+            .collect(),
+    )
+}
+
+unsafe fn write_stream(stream: *mut IBStream, bytes: &[u8]) -> tresult {
+    if stream.is_null() {
+        return K_INVALID_ARG;
+    }
+    let mut written = 0;
+    let tr = (*stream).write(
+        bytes.as_ptr() as *const c_void,
+        bytes.len() as i32,
+        &mut written,
+    );
+    if tr != K_RESULT_OK || written as usize != bytes.len() {
+        return K_RESULT_FALSE;
+    }
+    K_RESULT_OK
+}
+
+unsafe fn read_stream(stream: *mut IBStream) -> Option<Vec<f64>> {
+    if stream.is_null() {
+        return None;
+    }
+    let mut head = [0u8; 4];
+    let mut got = 0;
+    (*stream).read(head.as_mut_ptr() as *mut c_void, 4, &mut got);
+    if got != 4 {
+        return None;
+    }
+    let count = u32::from_le_bytes(head) as usize;
+    let mut body = vec![0u8; count.checked_mul(8)?];
+    (*stream).read(
+        body.as_mut_ptr() as *mut c_void,
+        body.len() as i32,
+        &mut got,
+    );
+    if got as usize != body.len() {
+        return None;
+    }
+    let mut bytes = head.to_vec();
+    bytes.extend_from_slice(&body);
+    read_state_bytes(&bytes)
+}
+
+unsafe fn load_state(inst: &MockInstance, stream: *mut IBStream) -> tresult {
+    match read_stream(stream) {
+        Some(values) => {
+            for (id, v) in values.into_iter().enumerate() {
+                inst.set_param(id as ParamID, v);
+            }
+            K_RESULT_OK
+        }
+        None => K_RESULT_FALSE,
+    }
+}
+
+// ----- FUnknown (shared by all three interfaces) ------------------------------
+
+unsafe extern "C" fn i_query_interface(
+    this_: *mut FUnknown,
+    iid: *const Fuid,
+    obj: *mut *mut c_void,
+) -> tresult {
+    if iid.is_null() || obj.is_null() {
+        return K_INVALID_ARG;
+    }
+    let inst = (*(this_ as *const Slot)).owner as *mut MockInstance;
+    MockInstance::query(inst, &*iid, obj)
+}
+
+unsafe extern "C" fn i_add_ref(this_: *mut FUnknown) -> u32 {
+    owner(this_).refs.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+unsafe extern "C" fn i_release(this_: *mut FUnknown) -> u32 {
+    let inst = (*(this_ as *const Slot)).owner as *mut MockInstance;
+    MockInstance::release(inst)
+}
+
+// ----- IComponent -------------------------------------------------------------
+
+unsafe fn initialize(inst: &MockInstance) -> tresult {
+    inst.shared.maybe_fault(Method::Initialize);
+    let tr = inst.shared.config.initialize_result;
+    if tr == K_RESULT_OK {
+        inst.initialized.store(true, Ordering::SeqCst);
+        inst.shared
+            .counters
+            .initialize_calls
+            .fetch_add(1, Ordering::SeqCst);
+    }
+    tr
+}
+
+unsafe fn terminate(inst: &MockInstance) -> tresult {
+    inst.shared.maybe_fault(Method::Terminate);
+    inst.shared
+        .counters
+        .terminate_calls
+        .fetch_add(1, Ordering::SeqCst);
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn c_initialize(this_: *mut IComponent, _ctx: *mut FUnknown) -> tresult {
+    initialize(owner(this_))
+}
+
+unsafe extern "C" fn c_terminate(this_: *mut IComponent) -> tresult {
+    terminate(owner(this_))
+}
+
+unsafe extern "C" fn c_set_io_mode(this_: *mut IComponent, mode: i32) -> tresult {
+    let inst = owner(this_);
+    if inst.initialized.load(Ordering::SeqCst) {
+        return K_RESULT_FALSE;
+    }
+    *inst.shared.io_mode.lock().unwrap() = Some(mode);
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn c_get_controller_class_id(this_: *mut IComponent, cid: *mut Tuid) -> tresult {
+    let controller = owner(this_).shared.config.controller_cid;
+    if !cid.is_null() {
+        *cid = Tuid(controller.unwrap_or([0; 16]));
+    }
+    match controller {
+        Some(_) => K_RESULT_OK,
+        // Single-component plugin: the controller lives on the same object.
+        None => K_RESULT_FALSE,
+    }
+}
+
+unsafe extern "C" fn c_get_bus_count(this_: *mut IComponent, media_type: i32, dir: i32) -> i32 {
+    bus_count(owner(this_), media_type, dir)
+}
+
+fn bus_count(inst: &MockInstance, media_type: i32, dir: i32) -> i32 {
+    let config = &inst.shared.config;
+    match (media_type, dir) {
+        (MEDIA_TYPE_AUDIO, BUS_DIR_INPUT) if config.sidechain_channels > 0 => 2,
+        (MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT) => 1 + config.aux_outputs.max(0),
+        (MEDIA_TYPE_AUDIO, _) => 1,
+        (MEDIA_TYPE_EVENT, BUS_DIR_INPUT) => config.event_inputs.max(0),
+        (MEDIA_TYPE_EVENT, BUS_DIR_OUTPUT) => config.event_outputs.max(0),
+        _ => 0,
+    }
+}
+
+fn is_sidechain(inst: &MockInstance, media_type: i32, direction: i32, index: i32) -> bool {
+    media_type == MEDIA_TYPE_AUDIO
+        && direction == BUS_DIR_INPUT
+        && index == 1
+        && inst.shared.config.sidechain_channels > 0
+}
+
+fn is_aux_output(inst: &MockInstance, media_type: i32, direction: i32, index: i32) -> bool {
+    media_type == MEDIA_TYPE_AUDIO
+        && direction == BUS_DIR_OUTPUT
+        && (1..=inst.shared.config.aux_outputs).contains(&index)
+}
+
+fn is_event_input(inst: &MockInstance, media_type: i32, direction: i32, index: i32) -> bool {
+    media_type == MEDIA_TYPE_EVENT
+        && direction == BUS_DIR_INPUT
+        && (0..inst.shared.config.event_inputs).contains(&index)
+}
+
+fn is_event_output(inst: &MockInstance, media_type: i32, direction: i32, index: i32) -> bool {
+    media_type == MEDIA_TYPE_EVENT
+        && direction == BUS_DIR_OUTPUT
+        && (0..inst.shared.config.event_outputs).contains(&index)
+}
+
+unsafe extern "C" fn c_get_bus_info(
+    this_: *mut IComponent,
+    media_type: i32,
+    direction: i32,
+    index: i32,
+    info: *mut BusInfo,
+) -> tresult {
+    if info.is_null() {
+        return K_INVALID_ARG;
+    }
+    let inst = owner(this_);
+    let info = &mut *info;
+    let event_output = is_event_output(inst, media_type, direction, index);
+    if event_output || is_event_input(inst, media_type, direction, index) {
+        info.media_type = media_type;
+        info.direction = direction;
+        info.channel_count = 16;
+        copy_cstr(
+            &mut info.name,
+            if event_output {
+                "Event Out"
+            } else {
+                "Event In"
+            },
+        );
+        info.bus_type = if index == 0 {
+            BUS_TYPE_MAIN
+        } else {
+            BUS_TYPE_AUX
+        };
+        info.flags = BUS_FLAG_DEFAULT_ACTIVE;
+        return K_RESULT_OK;
+    }
+    if is_sidechain(inst, media_type, direction, index) {
+        info.media_type = media_type;
+        info.direction = direction;
+        info.channel_count = inst.shared.config.sidechain_channels;
+        copy_cstr(&mut info.name, "Sidechain In");
+        info.bus_type = BUS_TYPE_AUX;
+        info.flags = 0;
+        return K_RESULT_OK;
+    }
+    if is_aux_output(inst, media_type, direction, index) {
+        info.media_type = media_type;
+        info.direction = direction;
+        info.channel_count = inst.shared.config.channels;
+        copy_cstr(&mut info.name, &format!("Aux {index}"));
+        info.bus_type = BUS_TYPE_AUX;
+        info.flags = 0;
+        return K_RESULT_OK;
+    }
+    if media_type != MEDIA_TYPE_AUDIO || index != 0 {
+        return K_INVALID_ARG;
+    }
+    info.media_type = media_type;
+    info.direction = direction;
+    info.channel_count = inst.shared.config.channels;
+    copy_cstr(
+        &mut info.name,
+        if direction == BUS_DIR_INPUT {
+            "Main In"
+        } else {
+            "Main Out"
+        },
+    );
+    info.bus_type = BUS_TYPE_MAIN;
+    info.flags = BUS_FLAG_DEFAULT_ACTIVE;
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn c_activate_bus(
+    this_: *mut IComponent,
+    media_type: i32,
+    direction: i32,
+    index: i32,
+    state: u8,
+) -> tresult {
+    let inst = owner(this_);
+    let result = inst.shared.config.deactivate_bus_result;
+    if state == 0 && result != K_RESULT_OK {
+        return result;
+    }
+    if is_event_input(inst, media_type, direction, index) {
+        if index == 0 {
+            inst.event_bus_active.store(state != 0, Ordering::SeqCst);
+        }
+        return K_RESULT_OK;
+    }
+    if is_sidechain(inst, media_type, direction, index) {
+        inst.sidechain_active.store(state != 0, Ordering::SeqCst);
+        return K_RESULT_OK;
+    }
+    if is_aux_output(inst, media_type, direction, index)
+        || is_event_output(inst, media_type, direction, index)
+    {
+        return K_RESULT_OK;
+    }
+    if media_type != MEDIA_TYPE_AUDIO || index != 0 || !(0..=1).contains(&direction) {
+        return K_INVALID_ARG;
+    }
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn c_set_active(this_: *mut IComponent, state: u8) -> tresult {
+    let inst = owner(this_);
+    inst.shared.maybe_fault(Method::SetActive);
+    let state = state != 0;
+    *inst.feedback.lock().unwrap() = vec![0.0; inst.shared.config.channels.max(0) as usize];
+    if inst.active.swap(state, Ordering::SeqCst) != state {
+        let active = &inst.shared.counters.active_instances;
+        if state {
+            active.fetch_add(1, Ordering::SeqCst);
+        } else {
+            active.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn c_set_state(this_: *mut IComponent, state: *mut IBStream) -> tresult {
+    let inst = owner(this_);
+    inst.shared.maybe_fault(Method::SetState);
+    load_state(inst, state)
+}
+
+unsafe extern "C" fn c_get_state(this_: *mut IComponent, state: *mut IBStream) -> tresult {
+    let inst = owner(this_);
+    inst.shared.maybe_fault(Method::GetState);
+    write_stream(state, &write_state_bytes(&inst.values()))
+}
+
+// ----- IAudioProcessor --------------------------------------------------------
+
+unsafe extern "C" fn p_initialize(this_: *mut IAudioProcessor, _ctx: *mut FUnknown) -> tresult {
+    initialize(owner(this_))
+}
+
+unsafe extern "C" fn p_terminate(this_: *mut IAudioProcessor) -> tresult {
+    terminate(owner(this_))
+}
+
+unsafe extern "C" fn p_set_processing(this_: *mut IAudioProcessor, _state: i32) -> tresult {
+    let inst = owner(this_);
+    inst.shared.maybe_fault(Method::SetProcessing);
+    inst.shared.config.set_processing_result
+}
+
+unsafe extern "C" fn p_setup_processing(
+    this_: *mut IAudioProcessor,
+    setup: *const ProcessSetup,
+) -> tresult {
+    if setup.is_null() {
+        return K_INVALID_ARG;
+    }
+    let inst = owner(this_);
+    inst.shared.maybe_fault(Method::SetupProcessing);
+    inst.shared
+        .counters
+        .setup_calls
+        .fetch_add(1, Ordering::SeqCst);
+    let tr = inst.shared.config.setup_processing_result;
+    if tr != K_RESULT_OK {
+        return tr;
+    }
+    if inst.shared.config.reject_process_mode == Some((*setup).process_mode)
+        || p_can_process_sample_size(this_, (*setup).symbolic_sample_size) != K_RESULT_OK
+    {
+        return K_RESULT_FALSE;
+    }
+    *inst.shared.last_setup.lock().unwrap() = Some(MockSetup {
+        sample_rate: (*setup).sample_rate,
+        max_block: (*setup).max_samples_per_block,
+        symbolic_sample_size: (*setup).symbolic_sample_size,
+        process_mode: (*setup).process_mode,
+    });
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn p_set_bus_arrangements(
+    this_: *mut IAudioProcessor,
+    inputs: *const u64,
+    num_ins: i32,
+    outputs: *const u64,
+    num_outs: i32,
+) -> tresult {
+    let inst = owner(this_);
+    let config = &inst.shared.config;
+    if config.set_bus_arrangements_result != K_RESULT_OK {
+        return config.set_bus_arrangements_result;
+    }
+    let arrangements = |ptr: *const u64, n: i32| match n {
+        0 => Some(Vec::new()),
+        n if n > 0 && !ptr.is_null() => Some(core::slice::from_raw_parts(ptr, n as usize).to_vec()),
+        _ => None,
+    };
+    let (Some(ins), Some(outs)) = (
+        arrangements(inputs, num_ins),
+        arrangements(outputs, num_outs),
+    ) else {
+        return K_INVALID_ARG;
+    };
+    if !config.supported_arrangements.is_empty() {
+        let fits = |arrangements: &[u64], dir| {
+            arrangements.len() as i32 == bus_count(inst, MEDIA_TYPE_AUDIO, dir)
+                && arrangements
+                    .iter()
+                    .all(|a| config.supported_arrangements.contains(a))
+        };
+        if !fits(&ins, BUS_DIR_INPUT) || !fits(&outs, BUS_DIR_OUTPUT) {
+            return K_RESULT_FALSE;
+        }
+    }
+    *inst.arrangements.lock().unwrap() = Some((ins, outs));
+    K_RESULT_OK
+}
+
+/// The last accepted arrangement of a bus, or the one for its channel count.
+unsafe extern "C" fn p_get_bus_arrangement(
+    this_: *mut IAudioProcessor,
+    dir: i32,
+    index: i32,
+    arr: *mut u64,
+) -> tresult {
+    let inst = owner(this_);
+    if arr.is_null() || !(0..bus_count(inst, MEDIA_TYPE_AUDIO, dir)).contains(&index) {
+        return K_INVALID_ARG;
+    }
+    if let Some(reported) = inst.shared.config.reported_arrangement {
+        *arr = reported;
+        return K_RESULT_OK;
+    }
+    let set = match &*inst.arrangements.lock().unwrap() {
+        Some((ins, _)) if dir == BUS_DIR_INPUT => ins.get(index as usize).copied(),
+        Some((_, outs)) => outs.get(index as usize).copied(),
+        None => None,
+    };
+    let channels = if is_sidechain(inst, MEDIA_TYPE_AUDIO, dir, index) {
+        inst.shared.config.sidechain_channels
+    } else {
+        inst.shared.config.channels
+    };
+    // With fixed layouts, the first of them that fits the bus.
+    let fixed = inst
+        .shared
+        .config
+        .supported_arrangements
+        .iter()
+        .copied()
+        .find(|&a| speaker_arr::channel_count(a) as i32 == channels);
+    *arr = set.or(fixed).unwrap_or(match channels {
+        ..=0 => 0,
+        1 => speaker_arr::MONO,
+        n => (1u64 << n.min(63)) - 1,
+    });
+    K_RESULT_OK
+}
+
+trait MockSample: Copy {
+    fn from_f64(v: f64) -> Self;
+    fn to_f64(self) -> f64;
+}
+impl MockSample for f32 {
+    fn from_f64(v: f64) -> Self {
+        v as f32
+    }
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+impl MockSample for f64 {
+    fn from_f64(v: f64) -> Self {
+        v
+    }
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// One bus of a `ProcessData`: channels, channel buffers and silence flags.
+type MockBus<T> = (i32, *mut *mut T, *mut u64);
+
+/// Gain stage: output channel `c` = input channel `c` (or silence) * parameter 0, the
+/// input being the sidechain while it is active. Output channels without an input are
+/// flagged silent, and so is everything while an instrument's event bus is inactive.
+unsafe fn process_buses<T: MockSample>(
+    inst: &MockInstance,
+    inputs: Option<MockBus<T>>,
+    sidechain: Option<MockBus<T>>,
+    outputs: Option<MockBus<T>>,
+    frames: usize,
+) -> tresult {
+    inst.shared.maybe_fault(Method::Process);
+    inst.shared
+        .counters
+        .process_calls
+        .fetch_add(1, Ordering::SeqCst);
+    let done = inst.blocks.fetch_add(1, Ordering::SeqCst);
+    let handler = inst.shared.handler.load(Ordering::SeqCst);
+    if !handler.is_null() {
+        let n = inst.shared.config.edits_per_block;
+        for k in 0..n {
+            (*handler).perform_edit(0, k as f64 / n as f64);
+        }
+    }
+    if let Some((n, tr)) = inst.shared.config.process_returns_after_n_blocks {
+        if done >= n {
+            return tr;
+        }
+    }
+    let inputs = if inst.sidechain_active.load(Ordering::SeqCst) {
+        if sidechain.is_none() {
+            return K_INVALID_ARG;
+        }
+        sidechain
+    } else {
+        inputs
+    };
+    let Some((out_ch, out_bufs, out_silence)) = outputs else {
+        return K_RESULT_OK;
+    };
+    if out_bufs.is_null() {
+        return K_INVALID_ARG;
+    }
+    let gain = inst.param(0).unwrap_or(1.0);
+    let nan = inst.shared.config.write_nans;
+    let muted =
+        inst.shared.config.event_inputs > 0 && !inst.event_bus_active.load(Ordering::SeqCst);
+    let feedback = inst.shared.config.feedback;
+    let mut last = inst.feedback.lock().unwrap();
+    *out_silence = 0;
+    for c in 0..out_ch as usize {
+        let out = *out_bufs.add(c);
+        if out.is_null() {
+            continue;
+        }
+        let input = match inputs {
+            Some((in_ch, in_bufs, in_silence))
+                if !muted && !in_bufs.is_null() && (c as i32) < in_ch =>
+            {
+                // Input channels the host flags silent are treated as absent.
+                if c < 64 && *in_silence & (1 << c) != 0 {
+                    core::ptr::null_mut()
+                } else {
+                    *in_bufs.add(c)
+                }
+            }
+            _ => core::ptr::null_mut(),
+        };
+        for i in 0..frames {
+            let v = if nan {
+                f64::NAN
+            } else if input.is_null() {
+                0.0
+            } else {
+                (*input.add(i)).to_f64() * gain
+            };
+            let v = match last.get_mut(c) {
+                Some(last) => {
+                    *last = v + feedback * *last;
+                    *last
+                }
+                None => v,
+            };
+            *out.add(i) = T::from_f64(v);
+        }
+        if input.is_null() && !nan && c < 64 && last.get(c).is_none_or(|&l| l == 0.0) {
+            *out_silence |= 1 << c;
+        }
+    }
+    K_RESULT_OK
+}
+
+/// Aux output bus `k` of `aux` (numbered from 1) = the main output * parameter `k`,
+/// flagged silent where either is.
+unsafe fn process_aux_outputs<T: MockSample>(
+    inst: &MockInstance,
+    main: Option<MockBus<T>>,
+    aux: impl Iterator<Item = MockBus<T>>,
+    frames: usize,
+) {
+    for (k, (out_ch, out_bufs, out_silence)) in aux.enumerate() {
+        let gain = inst.param(k as ParamID + 1).unwrap_or(0.0);
+        *out_silence = 0;
+        for c in 0..out_ch.clamp(0, 64) as usize {
+            let out = *out_bufs.add(c);
+            if out.is_null() {
+                continue;
+            }
+            let source = match main {
+                Some((main_ch, main_bufs, main_silence))
+                    if gain != 0.0 && (c as i32) < main_ch && *main_silence & (1 << c) == 0 =>
+                {
+                    *main_bufs.add(c)
+                }
+                _ => core::ptr::null_mut(),
+            };
+            if source.is_null() {
+                *out_silence |= 1 << c;
+            }
+            for i in 0..frames {
+                let v = if source.is_null() {
+                    0.0
+                } else {
+                    (*source.add(i)).to_f64() * gain
+                };
+                *out.add(i) = T::from_f64(v);
+            }
+        }
+    }
+}
+
+/// With event outputs, copy every input event to `output`, if the host passed both
+/// lists, like a MIDI thru.
+unsafe fn echo_events(inst: &MockInstance, input: *mut IEventList, output: *mut IEventList) {
+    let (Some(input), Some(output)) = (input.as_mut(), output.as_mut()) else {
+        return;
+    };
+    if inst.shared.config.event_outputs <= 0 {
+        return;
+    }
+    for index in 0..input.get_event_count() {
+        let mut event = Event::default();
+        if input.get_event(index, &mut event) == K_RESULT_OK {
+            output.add_event(&mut event);
+        }
+    }
+}
+
+/// Set each parameter in `changes` to its last point, if the mock follows automation.
+unsafe fn follow_automation(inst: &MockInstance, changes: *mut IParameterChanges) {
+    let Some(changes) = changes.as_mut() else {
+        return;
+    };
+    if !inst.shared.config.follow_automation {
+        return;
+    }
+    for i in 0..changes.get_parameter_count() {
+        let Some(queue) = changes.get_parameter_data(i).as_mut() else {
+            continue;
+        };
+        let (mut offset, mut value) = (0, 0.0);
+        let last = queue.get_point_count() - 1;
+        if last >= 0 && queue.get_point(last, &mut offset, &mut value) == K_RESULT_OK {
+            inst.set_param(queue.get_parameter_id(), value);
+        }
+    }
+}
+
+/// Add the configured output parameter points to `changes`, if the host passed a list.
+unsafe fn report_output_params(inst: &MockInstance, changes: *mut IParameterChanges) {
+    let Some(changes) = changes.as_mut() else {
+        return;
+    };
+    for (offset, (id, value)) in inst.shared.config.output_params.iter().enumerate() {
+        let mut index = 0;
+        if let Some(queue) = changes.add_parameter_data(id, &mut index).as_mut() {
+            queue.add_point(offset as i32, *value, &mut index);
+        }
+    }
+}
+
+unsafe extern "C" fn p_process_32f(
+    this_: *mut IAudioProcessor,
+    data: *mut ProcessData32,
+) -> tresult {
+    if data.is_null() {
+        return K_INVALID_ARG;
+    }
+    let d = &*data;
+    *owner(this_).shared.last_context.lock().unwrap() = d.process_context.as_ref().copied();
+    let bus = |n: i32, b: *mut AudioBusBuffers32| {
+        (n > 0 && !b.is_null()).then(|| {
+            (
+                (*b).num_channels,
+                (*b).channel_buffers,
+                core::ptr::addr_of_mut!((*b).silence_flags),
+            )
+        })
+    };
+    let sidechain = if d.num_inputs > 1 && !d.inputs.is_null() {
+        bus(1, d.inputs.add(1))
+    } else {
+        None
+    };
+    let frames = d.num_samples.max(0) as usize;
+    follow_automation(owner(this_), d.input_parameter_changes.cast());
+    let main = bus(d.num_outputs, d.outputs);
+    let tr = process_buses(
+        owner(this_),
+        bus(d.num_inputs, d.inputs),
+        sidechain,
+        main,
+        frames,
+    );
+    if tr == K_RESULT_OK {
+        let aux = (1..d.num_outputs.max(0) as usize).filter_map(|k| bus(1, d.outputs.add(k)));
+        process_aux_outputs(owner(this_), main, aux, frames);
+        report_output_params(owner(this_), d.output_parameter_changes.cast());
+        echo_events(owner(this_), d.input_events.cast(), d.output_events.cast());
+    }
+    tr
+}
+
+unsafe extern "C" fn p_process_64f(
+    this_: *mut IAudioProcessor,
+    data: *mut ProcessData64,
+) -> tresult {
+    if data.is_null() {
+        return K_INVALID_ARG;
+    }
+    let d = &*data;
+    *owner(this_).shared.last_context.lock().unwrap() = d.process_context.as_ref().copied();
+    let bus = |n: i32, b: *mut AudioBusBuffers64| {
+        (n > 0 && !b.is_null()).then(|| {
+            (
+                (*b).num_channels,
+                (*b).channel_buffers,
+                core::ptr::addr_of_mut!((*b).silence_flags),
+            )
+        })
+    };
+    let sidechain = if d.num_inputs > 1 && !d.inputs.is_null() {
+        bus(1, d.inputs.add(1))
+    } else {
+        None
+    };
+    let frames = d.num_samples.max(0) as usize;
+    follow_automation(owner(this_), d.input_parameter_changes.cast());
+    let main = bus(d.num_outputs, d.outputs);
+    let tr = process_buses(
+        owner(this_),
+        bus(d.num_inputs, d.inputs),
+        sidechain,
+        main,
+        frames,
+    );
+    if tr == K_RESULT_OK {
+        let aux = (1..d.num_outputs.max(0) as usize).filter_map(|k| bus(1, d.outputs.add(k)));
+        process_aux_outputs(owner(this_), main, aux, frames);
+        report_output_params(owner(this_), d.output_parameter_changes.cast());
+        echo_events(owner(this_), d.input_events.cast(), d.output_events.cast());
+    }
+    tr
+}
+
+unsafe extern "C" fn p_get_latency_samples(this_: *mut IAudioProcessor) -> u32 {
+    owner(this_).shared.config.latency_samples
+}
+
+unsafe extern "C" fn p_get_tail_samples(this_: *mut IAudioProcessor) -> u32 {
+    owner(this_).shared.config.tail_samples
+}
+
+unsafe extern "C" fn p_can_process_sample_size(this_: *mut IAudioProcessor, size: i32) -> tresult {
+    match size {
+        process_consts::SYMBOLIC_SAMPLE_32 => K_RESULT_OK,
+        process_consts::SYMBOLIC_SAMPLE_64 if owner(this_).shared.config.float64 => K_RESULT_OK,
+        _ => K_RESULT_FALSE,
+    }
+}
+
+// ----- IEditController --------------------------------------------------------
+
+unsafe extern "C" fn e_initialize(_this: *mut IEditController, _ctx: *mut FUnknown) -> tresult {
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn e_terminate(_this: *mut IEditController) -> tresult {
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn e_set_component_state(
+    this_: *mut IEditController,
+    state: *mut IBStream,
+) -> tresult {
+    load_state(owner(this_), state)
+}
+
+unsafe extern "C" fn e_set_state(this_: *mut IEditController, state: *mut IBStream) -> tresult {
+    if state.is_null() {
+        return K_INVALID_ARG;
+    }
+    let mut bytes = Vec::new();
+    let mut chunk = [0u8; 64];
+    loop {
+        let mut got = 0;
+        (*state).read(
+            chunk.as_mut_ptr() as *mut c_void,
+            chunk.len() as i32,
+            &mut got,
+        );
+        if got <= 0 {
+            break;
+        }
+        bytes.extend_from_slice(&chunk[..got as usize]);
+    }
+    *owner(this_).controller_state.lock().unwrap() = bytes;
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn e_get_state(this_: *mut IEditController, state: *mut IBStream) -> tresult {
+    let bytes = owner(this_).controller_state.lock().unwrap().clone();
+    write_stream(state, &bytes)
+}
+
+unsafe extern "C" fn e_get_parameter_count(this_: *mut IEditController) -> i32 {
+    owner(this_).params.len() as i32
+}
+
+unsafe extern "C" fn e_get_parameter_info(
+    this_: *mut IEditController,
+    index: i32,
+    info: *mut ParameterInfo,
+) -> tresult {
+    let inst = owner(this_);
+    if index < 0 || index as usize >= inst.params.len() || info.is_null() {
+        return K_INVALID_ARG;
+    }
+    inst.shared
+        .counters
+        .parameter_info_calls
+        .fetch_add(1, Ordering::SeqCst);
+    let info = &mut *info;
+    let renamed = inst
+        .shared
+        .titles
+        .lock()
+        .unwrap()
+        .get(&(index as ParamID))
+        .cloned();
+    let title = renamed.unwrap_or_else(|| {
+        if index == 0 {
+            "Gain".to_string()
+        } else {
+            format!("Param {index}")
+        }
+    });
+    info.id = index as ParamID;
+    copy_str16(&mut info.title, &title);
+    copy_str16(&mut info.short_title, &title);
+    copy_str16(&mut info.units, "");
+    let config = &inst.shared.config;
+    info.step_count = config.param_steps.get(index as usize).copied().unwrap_or(0);
+    info.default_normalized_value = config
+        .param_defaults
+        .get(index as usize)
+        .copied()
+        .unwrap_or_else(|| default_value(index as usize));
+    info.unit_id = 0;
+    info.flags = inst
+        .shared
+        .config
+        .param_flags
+        .get(index as usize)
+        .copied()
+        .unwrap_or(param_consts::K_CAN_AUTOMATE);
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn e_get_param_string_by_value(
+    this_: *mut IEditController,
+    id: ParamID,
+    value: ParamValue,
+    string: *mut String128,
+) -> tresult {
+    let inst = owner(this_);
+    if string.is_null() || inst.param(id).is_none() {
+        return K_INVALID_ARG;
+    }
+    match &inst.shared.config.param_string {
+        Some(raw) => {
+            let n = raw.len().min(128);
+            (&mut *string)[..n].copy_from_slice(&raw[..n]);
+        }
+        None => copy_str16(&mut *string, &format!("{value:.2}")),
+    }
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn e_get_param_value_by_string(
+    this_: *mut IEditController,
+    id: ParamID,
+    string: *const u16,
+    value: *mut ParamValue,
+) -> tresult {
+    if string.is_null() || value.is_null() || owner(this_).param(id).is_none() {
+        return K_INVALID_ARG;
+    }
+    let mut len = 0;
+    while *string.add(len) != 0 && len < 128 {
+        len += 1;
+    }
+    let text = String::from_utf16_lossy(core::slice::from_raw_parts(string, len));
+    match text.trim().parse::<f64>() {
+        Ok(v) => {
+            *value = v.clamp(0.0, 1.0);
+            K_RESULT_OK
+        }
+        Err(_) => K_RESULT_FALSE,
+    }
+}
+
+unsafe extern "C" fn e_normalized_param_to_plain(
+    _this: *mut IEditController,
+    _id: ParamID,
+    value: ParamValue,
+) -> ParamValue {
+    value
+}
+
+unsafe extern "C" fn e_plain_param_to_normalized(
+    _this: *mut IEditController,
+    _id: ParamID,
+    plain: ParamValue,
+) -> ParamValue {
+    plain.clamp(0.0, 1.0)
+}
+
+unsafe extern "C" fn e_get_param_normalized(
+    this_: *mut IEditController,
+    id: ParamID,
+) -> ParamValue {
+    owner(this_).param(id).unwrap_or(0.0)
+}
+
+unsafe extern "C" fn e_set_param_normalized(
+    this_: *mut IEditController,
+    id: ParamID,
+    value: ParamValue,
+) -> tresult {
+    if owner(this_).set_param(id, value) {
+        K_RESULT_OK
+    } else {
+        K_INVALID_ARG
+    }
+}
+
+unsafe extern "C" fn e_set_component_handler(
+    this_: *mut IEditController,
+    handler: *mut IComponentHandler,
+) -> tresult {
+    owner(this_).shared.handler.store(handler, Ordering::SeqCst);
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn e_create_view(this: *mut IEditController, name: *const i8) -> *mut c_void {
+    let shared = &owner(this).shared;
+    let Some((width, height)) = shared.config.editor_size else {
+        return core::ptr::null_mut();
+    };
+    if name.is_null() || CStr::from_ptr(name).to_bytes_with_nul() != VIEW_TYPE_EDITOR {
+        return core::ptr::null_mut();
+    }
+    view::MockView::create(shared.clone(), width, height) as *mut c_void
+}
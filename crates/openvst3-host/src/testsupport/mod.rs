@@ -0,0 +1,440 @@
+//! In-process mock plugin implementing the ABI vtables in Rust.
+//!
+//! `MockPlugin` exposes a factory with one "Audio Module Class": a gain/pass-through
+//! effect with stereo (configurable) buses, an optional sidechain input and aux outputs, N
+//! parameters (parameter 0 is the linear gain) and state that serializes the parameter
+//! values. Faults can be injected through [`MockConfig`] so host error paths can be
+//! exercised without a real plugin: calls can return scripted `tresult`s or block in a
+//! given [`Method`] until [`MockPlugin::release_hang`].
+
+mod factory;
+mod instance;
+mod view;
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+
+use openvst3_abi::{
+    classinfo_consts, restart_consts, tresult, FUnknown, GetPluginFactoryProc, IComponentHandler,
+    IPluginFactory, ParamID, ParamValue, ProcessContext, Tuid, K_RESULT_OK,
+};
+
+use crate::{HostError, Module};
+
+pub use instance::{read_state_bytes, write_state_bytes};
+
+/// CID of the mock effect class.
+pub const MOCK_CID: [u8; 16] = *b"OpenVST3MockGain";
+pub const MOCK_CATEGORY: &str = classinfo_consts::K_VST_AUDIO_EFFECT_CLASS;
+pub const MOCK_VENDOR: &str = "OpenVST3";
+pub const MOCK_URL: &str = "https://github.com/BobTheZombie/OpenVST3";
+pub const MOCK_EMAIL: &str = "openvst3@example.invalid";
+
+/// Behaviour and fault plan of a [`MockPlugin`].
+#[derive(Clone, Debug)]
+pub struct MockConfig {
+    pub class_name: String,
+    /// `PClassInfo2::subCategories` of the gain class.
+    pub sub_categories: String,
+    /// `PClassInfo2::classFlags` of the gain class.
+    pub class_flags: u32,
+    /// `PClassInfo::cardinality` of the gain class (default kManyInstances).
+    pub cardinality: i32,
+    /// Descriptor-only classes listed after the gain class; they cannot be created.
+    pub extra_classes: Vec<MockClass>,
+    /// Answer `queryInterface(IPluginFactory2)`; when false only v1 class info exists.
+    pub factory2: bool,
+    /// Number of parameters; parameter 0 is the gain.
+    pub num_params: usize,
+    /// `ParameterInfo::flags` by parameter index; missing entries are `kCanAutomate`.
+    pub param_flags: Vec<i32>,
+    /// `ParameterInfo::stepCount` by parameter index; missing entries are 0 (continuous).
+    pub param_steps: Vec<i32>,
+    /// `ParameterInfo::defaultNormalizedValue` by parameter index, reported as is;
+    /// missing entries are the values parameters start at (1 for the gain, 0.5 else).
+    pub param_defaults: Vec<f64>,
+    /// Written by `getParamStringByValue` verbatim (up to 128 units, no terminator
+    /// added) instead of the value, e.g. an empty or unterminated string.
+    pub param_string: Option<Vec<u16>>,
+    /// Channels on the main input and output audio bus.
+    pub channels: i32,
+    /// Channels of an aux audio input bus after the main one; 0 for none. While the
+    /// host has it activated the mock is a sidechain fixture: the output is the
+    /// sidechain input times the gain, and `process` without a second input bus
+    /// fails with `kInvalidArgument`.
+    pub sidechain_channels: i32,
+    /// Aux audio output buses after the main one, as wide as it and named "Aux 1",
+    /// "Aux 2", ...: output bus `k` carries the main output times parameter `k`, and
+    /// is flagged silent where that parameter is 0 or missing.
+    pub aux_outputs: i32,
+    /// Event input buses (16 MIDI channels each). With any, the mock behaves like an
+    /// instrument that stays silent until event bus 0 is activated.
+    pub event_inputs: i32,
+    /// Event output buses. With any, every successful `process` copies its input events
+    /// to the output event list, like a MIDI thru.
+    pub event_outputs: i32,
+    /// Reported by `getLatencySamples`.
+    pub latency_samples: u32,
+    /// Reported by `getTailSamples`.
+    pub tail_samples: u32,
+    /// Each output sample also carries this much of the one before it on its channel,
+    /// so anything played rings out as an exponential tail, like a reverb's. Cleared
+    /// by `setActive`.
+    pub feedback: f64,
+    /// Make `getClassInfo` return this code instead of filling the info.
+    pub fail_get_class_info: Option<tresult>,
+    /// Value returned from `createInstance` for the mock CID; anything but
+    /// `kResultOk` creates nothing.
+    pub create_instance_result: tresult,
+    /// Make `createInstance` refuse every other IID with `kNoInterface`, like a
+    /// factory that only creates through one interface. The instance itself still
+    /// answers `queryInterface` as usual.
+    pub create_only_iid: Option<Tuid>,
+    /// Value returned from `initialize` (component and processor).
+    pub initialize_result: tresult,
+    /// Value returned from `setupProcessing`.
+    pub setup_processing_result: tresult,
+    /// `setupProcessing` answers `kResultFalse` to this process mode, like a plugin
+    /// that cannot render offline.
+    pub reject_process_mode: Option<i32>,
+    /// Answer `canProcessSampleSize(kSample64)` with `kResultOk`; when false it answers
+    /// `kResultFalse` and `setupProcessing` refuses 64-bit setups the same way.
+    pub float64: bool,
+    /// Value returned from `setBusArrangements`.
+    pub set_bus_arrangements_result: tresult,
+    /// When not empty, `setBusArrangements` only accepts one of these layouts on every
+    /// audio bus and answers `kResultFalse` otherwise, like a plugin with fixed layouts.
+    /// Until one is set, `getBusArrangement` answers the first that fits the bus.
+    pub supported_arrangements: Vec<u64>,
+    /// `getBusArrangement` answers this for every audio bus instead of its layout,
+    /// like a processor that disagrees with the component's `getBusInfo`.
+    pub reported_arrangement: Option<u64>,
+    /// Value returned from `activateBus(false)` for every bus, like a plugin that will
+    /// not let a bus go.
+    pub deactivate_bus_result: tresult,
+    /// Value returned from `setProcessing`.
+    pub set_processing_result: tresult,
+    /// After this many successful blocks, `process` returns the given code.
+    pub process_returns_after_n_blocks: Option<(usize, tresult)>,
+    /// Block inside this method until [`MockPlugin::release_hang`] is called.
+    pub hang_in: Option<Method>,
+    /// Abort the whole process inside this method, like a segfaulting plugin. Only
+    /// useful when the host runs the plugin in a child process.
+    pub crash_in: Option<Method>,
+    /// Fill every output sample with NaN.
+    pub write_nans: bool,
+    /// Answer `queryInterface(IAudioProcessor)` with `kNoInterface`, like a class
+    /// that is only a component.
+    pub hide_processor: bool,
+    /// Returned by `getControllerClassId` with `kResultOk`, like a dual-component
+    /// plugin; `None` answers `kResultFalse`. The controller stays on the component
+    /// object either way, and the factory only lists the class if it is among
+    /// `extra_classes`.
+    pub controller_cid: Option<[u8; 16]>,
+    /// Initial editor size; `None` makes `createView` return null.
+    pub editor_size: Option<(i32, i32)>,
+    /// Added to the host's output parameter changes by every successful `process`,
+    /// point `n` at sample offset `n`, like a plugin moving its own parameters.
+    pub output_params: Vec<(ParamID, ParamValue)>,
+    /// `performEdit` calls on parameter 0 the component handler gets from every
+    /// `process`, like a meter published as a parameter.
+    pub edits_per_block: usize,
+    /// Apply the last point of each input parameter queue before processing the block,
+    /// so automation reaches the gain.
+    pub follow_automation: bool,
+}
+
+/// A class entry reported by the mock factory.
+#[derive(Clone, Debug)]
+pub struct MockClass {
+    pub cid: [u8; 16],
+    pub name: String,
+    pub category: String,
+    pub sub_categories: String,
+    pub class_flags: u32,
+    /// `PClassInfo::cardinality`.
+    pub cardinality: i32,
+    /// Written to `name` verbatim instead of `name`, e.g. Latin-1 bytes.
+    pub raw_name: Option<Vec<u8>>,
+    /// Make `getClassInfo`/`getClassInfo2` fail with this code for this class only.
+    pub fail_info: Option<tresult>,
+}
+
+/// Plugin methods a fault can be attached to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// `getClassInfo`/`getClassInfo2` on the factory.
+    GetClassInfo,
+    Initialize,
+    Terminate,
+    SetupProcessing,
+    SetActive,
+    SetProcessing,
+    Process,
+    GetState,
+    SetState,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            class_name: "OpenVST3 Mock Gain".into(),
+            sub_categories: "Fx".into(),
+            class_flags: 0,
+            cardinality: classinfo_consts::K_MANY_INSTANCES,
+            extra_classes: Vec::new(),
+            factory2: true,
+            num_params: 1,
+            param_flags: Vec::new(),
+            param_steps: Vec::new(),
+            param_defaults: Vec::new(),
+            param_string: None,
+            channels: 2,
+            sidechain_channels: 0,
+            aux_outputs: 0,
+            event_inputs: 0,
+            event_outputs: 0,
+            latency_samples: 0,
+            tail_samples: 0,
+            feedback: 0.0,
+            fail_get_class_info: None,
+            create_instance_result: K_RESULT_OK,
+            initialize_result: K_RESULT_OK,
+            setup_processing_result: K_RESULT_OK,
+            reject_process_mode: None,
+            float64: true,
+            set_bus_arrangements_result: K_RESULT_OK,
+            supported_arrangements: Vec::new(),
+            reported_arrangement: None,
+            deactivate_bus_result: K_RESULT_OK,
+            set_processing_result: K_RESULT_OK,
+            process_returns_after_n_blocks: None,
+            hang_in: None,
+            crash_in: None,
+            write_nans: false,
+            hide_processor: false,
+            controller_cid: None,
+            create_only_iid: None,
+            editor_size: None,
+            output_params: Vec::new(),
+            edits_per_block: 0,
+            follow_automation: false,
+        }
+    }
+}
+
+/// Call counters shared by the factory and all instances it created.
+#[derive(Debug, Default)]
+pub struct MockCounters {
+    pub live_instances: AtomicUsize,
+    pub created: AtomicUsize,
+    pub initialize_calls: AtomicUsize,
+    pub terminate_calls: AtomicUsize,
+    pub setup_calls: AtomicUsize,
+    pub process_calls: AtomicUsize,
+    /// Instances currently between `setActive(true)` and `setActive(false)`.
+    pub active_instances: AtomicUsize,
+    /// Editor views not yet released.
+    pub live_views: AtomicUsize,
+    /// Editor views currently between `attached` and `removed`.
+    pub attached_views: AtomicUsize,
+    pub parameter_info_calls: AtomicUsize,
+}
+
+impl MockCounters {
+    pub fn get(counter: &AtomicUsize) -> usize {
+        counter.load(Ordering::SeqCst)
+    }
+}
+
+/// Last `ProcessSetup` a mock instance received.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MockSetup {
+    pub sample_rate: f64,
+    pub max_block: i32,
+    pub symbolic_sample_size: i32,
+    pub process_mode: i32,
+}
+
+pub(crate) struct MockShared {
+    pub config: MockConfig,
+    pub counters: MockCounters,
+    pub last_setup: Mutex<Option<MockSetup>>,
+    /// The `ProcessContext` of the last `process` call, `None` if it passed none.
+    pub last_context: Mutex<Option<ProcessContext>>,
+    /// Mode passed to `setIoMode` before `initialize`; later calls are refused.
+    pub io_mode: Mutex<Option<i32>>,
+    /// The most recently created editor view while it is alive, else null.
+    pub view: AtomicPtr<view::MockView>,
+    /// The last `IComponentHandler` any instance was given, else null.
+    pub handler: AtomicPtr<IComponentHandler>,
+    /// Parameter titles changed by [`MockPlugin::rename_param`].
+    pub titles: Mutex<BTreeMap<ParamID, String>>,
+    /// Set by `release_hang`; hung calls wait on the condvar until it is true.
+    hang_released: (Mutex<bool>, Condvar),
+}
+
+impl MockShared {
+    /// Abort the process or block if the fault plan crashes or hangs in `method`.
+    pub(crate) fn maybe_fault(&self, method: Method) {
+        if self.config.crash_in == Some(method) {
+            eprintln!("mock plugin: crashing in {method:?}");
+            std::process::abort();
+        }
+        if self.config.hang_in != Some(method) {
+            return;
+        }
+        let (lock, cv) = &self.hang_released;
+        let mut released = lock.lock().unwrap();
+        while !*released {
+            released = cv.wait(released).unwrap();
+        }
+    }
+}
+
+/// Owns a mock factory. Modules built from it must not outlive it.
+pub struct MockPlugin {
+    factory: *mut factory::MockFactory,
+    shared: Arc<MockShared>,
+}
+
+// The factory is only mutated through atomics and the shared mutex, so a plugin can
+// live in a `static` (as the cdylib test plugin's does).
+unsafe impl Send for MockPlugin {}
+unsafe impl Sync for MockPlugin {}
+
+impl MockPlugin {
+    pub fn new(config: MockConfig) -> Self {
+        let shared = Arc::new(MockShared {
+            config,
+            counters: MockCounters::default(),
+            last_setup: Mutex::new(None),
+            last_context: Mutex::new(None),
+            io_mode: Mutex::new(None),
+            view: AtomicPtr::new(core::ptr::null_mut()),
+            handler: AtomicPtr::new(core::ptr::null_mut()),
+            titles: Mutex::new(BTreeMap::new()),
+            hang_released: (Mutex::new(false), Condvar::new()),
+        });
+        let factory = factory::MockFactory::create(shared.clone());
+        Self { factory, shared }
+    }
+
+    /// `GetPluginFactory` for a process-wide mock with the default configuration,
+    /// suitable for [`Module::from_factory_proc`].
+    pub fn factory_proc() -> GetPluginFactoryProc {
+        mock_get_plugin_factory
+    }
+
+    pub fn factory_ptr(&self) -> *mut IPluginFactory {
+        self.factory as *mut IPluginFactory
+    }
+
+    /// Wrap the factory in a [`Module`], which holds its own reference.
+    pub fn module(&self) -> Result<Module, HostError> {
+        unsafe {
+            (*(self.factory as *mut FUnknown)).add_ref();
+            Module::from_factory_ptr(self.factory_ptr())
+        }
+    }
+
+    pub fn config(&self) -> &MockConfig {
+        &self.shared.config
+    }
+
+    pub fn counters(&self) -> &MockCounters {
+        &self.shared.counters
+    }
+
+    pub fn last_setup(&self) -> Option<MockSetup> {
+        *self.shared.last_setup.lock().unwrap()
+    }
+
+    /// The `ProcessContext` the last `process` call got, if any.
+    pub fn last_context(&self) -> Option<ProcessContext> {
+        *self.shared.last_context.lock().unwrap()
+    }
+
+    /// The `setIoMode` an instance accepted before its `initialize`, if any.
+    pub fn io_mode(&self) -> Option<i32> {
+        *self.shared.io_mode.lock().unwrap()
+    }
+
+    /// Make the live editor view ask its frame for a new size, as a plugin does when
+    /// its content changes. Returns the frame's answer, or `kResultFalse` when there
+    /// is no view or it has no frame.
+    pub fn request_editor_resize(&self, width: i32, height: i32) -> tresult {
+        let view = self.shared.view.load(Ordering::SeqCst);
+        if view.is_null() {
+            return openvst3_abi::K_RESULT_FALSE;
+        }
+        unsafe { view::MockView::request_resize(view, width, height) }
+    }
+
+    /// Give parameter `id` a new title and tell the component handler with
+    /// `restartComponent(kParamTitlesChanged)`; the handler last set must still be
+    /// alive. Returns its answer, or `kResultFalse` when no instance has a handler.
+    pub fn rename_param(&self, id: ParamID, title: &str) -> tresult {
+        self.shared.titles.lock().unwrap().insert(id, title.into());
+        let handler = self.shared.handler.load(Ordering::SeqCst);
+        if handler.is_null() {
+            return openvst3_abi::K_RESULT_FALSE;
+        }
+        unsafe { (*handler).restart_component(restart_consts::K_PARAM_TITLES_CHANGED) }
+    }
+
+    /// Let calls blocked by [`MockConfig::hang_in`] (and all later ones) return.
+    pub fn release_hang(&self) {
+        let (lock, cv) = &self.shared.hang_released;
+        *lock.lock().unwrap() = true;
+        cv.notify_all();
+    }
+}
+
+impl Default for MockPlugin {
+    fn default() -> Self {
+        Self::new(MockConfig::default())
+    }
+}
+
+impl Drop for MockPlugin {
+    fn drop(&mut self) {
+        unsafe { factory::MockFactory::destroy(self.factory) };
+    }
+}
+
+unsafe extern "C" fn mock_get_plugin_factory() -> *mut IPluginFactory {
+    static DEFAULT: OnceLock<AtomicPtr<IPluginFactory>> = OnceLock::new();
+    let factory = DEFAULT
+        .get_or_init(|| {
+            let plugin = std::mem::ManuallyDrop::new(MockPlugin::default());
+            AtomicPtr::new(plugin.factory as *mut IPluginFactory)
+        })
+        .load(Ordering::Relaxed);
+    (*(factory as *mut FUnknown)).add_ref();
+    factory
+}
+
+/// Copy `s` into a fixed NUL-terminated `i8` buffer, truncating if needed.
+pub(crate) fn copy_cstr(dst: &mut [i8], s: &str) {
+    copy_bytes(dst, s.as_bytes());
+}
+
+/// Copy raw bytes into a fixed NUL-terminated buffer, truncating if needed.
+pub(crate) fn copy_bytes(dst: &mut [i8], bytes: &[u8]) {
+    dst.fill(0);
+    let max = dst.len().saturating_sub(1);
+    for (d, &b) in dst.iter_mut().zip(bytes.iter().take(max)) {
+        *d = b as i8;
+    }
+}
+
+/// Copy `s` into a fixed NUL-terminated UTF-16 buffer, truncating if needed.
+pub(crate) fn copy_str16(dst: &mut [u16], s: &str) {
+    dst.fill(0);
+    let max = dst.len().saturating_sub(1);
+    for (d, c) in dst.iter_mut().zip(s.encode_utf16().take(max)) {
+        *d = c;
+    }
+}
@@ -0,0 +1,239 @@
+//! Mock `IPlugView` returned by `createView("editor")` when
+//! [`MockConfig::editor_size`](super::MockConfig::editor_size) is set.
+
+use core::ffi::{c_void, CStr};
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use openvst3_abi::{
+    iids, platform_types, tresult, FUnknown, Fuid, IPlugFrame, IPlugView, IPlugViewVTable,
+    ViewRect, K_INVALID_ARG, K_NO_INTERFACE, K_RESULT_FALSE, K_RESULT_OK,
+};
+
+use super::MockShared;
+
+/// Smallest size the mock editor accepts.
+pub(crate) const MIN_EDITOR_SIZE: i32 = 100;
+
+#[repr(C)]
+pub(crate) struct MockView {
+    vtbl: *const IPlugViewVTable,
+    refs: AtomicU32,
+    shared: Arc<MockShared>,
+    size: Mutex<ViewRect>,
+    frame: AtomicPtr<IPlugFrame>,
+    attached: AtomicBool,
+}
+
+static VIEW_VTBL: IPlugViewVTable = IPlugViewVTable {
+    query_interface: v_query_interface,
+    add_ref: v_add_ref,
+    release: v_release,
+    is_platform_type_supported: v_is_platform_type_supported,
+    attached: v_attached,
+    removed: v_removed,
+    on_wheel: v_on_wheel,
+    on_key_down: v_on_key,
+    on_key_up: v_on_key,
+    get_size: v_get_size,
+    on_size: v_on_size,
+    on_focus: v_on_focus,
+    set_frame: v_set_frame,
+    can_resize: v_can_resize,
+    check_size_constraint: v_check_size_constraint,
+};
+
+impl MockView {
+    pub(crate) fn create(shared: Arc<MockShared>, width: i32, height: i32) -> *mut IPlugView {
+        shared.counters.live_views.fetch_add(1, Ordering::SeqCst);
+        let raw = Box::into_raw(Box::new(MockView {
+            vtbl: &VIEW_VTBL,
+            refs: AtomicU32::new(1),
+            shared: shared.clone(),
+            size: Mutex::new(ViewRect {
+                left: 0,
+                top: 0,
+                right: width,
+                bottom: height,
+            }),
+            frame: AtomicPtr::new(core::ptr::null_mut()),
+            attached: AtomicBool::new(false),
+        }));
+        shared.view.store(raw, Ordering::SeqCst);
+        raw as *mut IPlugView
+    }
+
+    /// Ask the host frame to resize the view, as an editor does when its content
+    /// changes size.
+    pub(crate) unsafe fn request_resize(this: *mut MockView, width: i32, height: i32) -> tresult {
+        let frame = (*this).frame.load(Ordering::SeqCst);
+        if frame.is_null() {
+            return K_RESULT_FALSE;
+        }
+        let mut rect = ViewRect {
+            left: 0,
+            top: 0,
+            right: width,
+            bottom: height,
+        };
+        (*frame).resize_view(this as *mut IPlugView, &mut rect)
+    }
+}
+
+unsafe fn view<'a>(this: *mut IPlugView) -> &'a MockView {
+    &*(this as *const MockView)
+}
+
+unsafe extern "C" fn v_query_interface(
+    this_: *mut FUnknown,
+    iid: *const Fuid,
+    obj: *mut *mut c_void,
+) -> tresult {
+    if obj.is_null() || iid.is_null() {
+        return K_INVALID_ARG;
+    }
+    if *iid == iids::IPLUG_VIEW || *iid == iids::FUNKNOWN {
+        v_add_ref(this_);
+        *obj = this_ as *mut c_void;
+        return K_RESULT_OK;
+    }
+    *obj = core::ptr::null_mut();
+    K_NO_INTERFACE
+}
+
+unsafe extern "C" fn v_add_ref(this_: *mut FUnknown) -> u32 {
+    view(this_ as *mut IPlugView)
+        .refs
+        .fetch_add(1, Ordering::SeqCst)
+        + 1
+}
+
+unsafe extern "C" fn v_release(this_: *mut FUnknown) -> u32 {
+    let this = this_ as *mut MockView;
+    let left = (*this).refs.fetch_sub(1, Ordering::SeqCst) - 1;
+    if left == 0 {
+        let view = Box::from_raw(this);
+        let counters = &view.shared.counters;
+        if view.attached.load(Ordering::SeqCst) {
+            counters.attached_views.fetch_sub(1, Ordering::SeqCst);
+        }
+        counters.live_views.fetch_sub(1, Ordering::SeqCst);
+        let _ = view.shared.view.compare_exchange(
+            this,
+            core::ptr::null_mut(),
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+    }
+    left
+}
+
+unsafe fn supported(type_: *const i8) -> bool {
+    if type_.is_null() {
+        return false;
+    }
+    let type_ = CStr::from_ptr(type_).to_bytes_with_nul();
+    [
+        platform_types::HWND,
+        platform_types::NSVIEW,
+        platform_types::X11_EMBED_WINDOW_ID,
+    ]
+    .contains(&type_)
+}
+
+unsafe extern "C" fn v_is_platform_type_supported(
+    _this: *mut IPlugView,
+    type_: *const i8,
+) -> tresult {
+    if supported(type_) {
+        K_RESULT_OK
+    } else {
+        K_RESULT_FALSE
+    }
+}
+
+unsafe extern "C" fn v_attached(
+    this_: *mut IPlugView,
+    parent: *mut c_void,
+    type_: *const i8,
+) -> tresult {
+    let view = view(this_);
+    if parent.is_null() || !supported(type_) {
+        return K_INVALID_ARG;
+    }
+    if view.attached.swap(true, Ordering::SeqCst) {
+        return K_RESULT_FALSE;
+    }
+    view.shared
+        .counters
+        .attached_views
+        .fetch_add(1, Ordering::SeqCst);
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn v_removed(this_: *mut IPlugView) -> tresult {
+    let view = view(this_);
+    if !view.attached.swap(false, Ordering::SeqCst) {
+        return K_RESULT_FALSE;
+    }
+    view.shared
+        .counters
+        .attached_views
+        .fetch_sub(1, Ordering::SeqCst);
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn v_on_wheel(_this: *mut IPlugView, _distance: f32) -> tresult {
+    K_RESULT_FALSE
+}
+
+unsafe extern "C" fn v_on_key(
+    _this: *mut IPlugView,
+    _key: i16,
+    _key_code: i16,
+    _modifiers: i16,
+) -> tresult {
+    K_RESULT_FALSE
+}
+
+unsafe extern "C" fn v_get_size(this_: *mut IPlugView, size: *mut ViewRect) -> tresult {
+    if size.is_null() {
+        return K_INVALID_ARG;
+    }
+    *size = *view(this_).size.lock().unwrap();
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn v_on_size(this_: *mut IPlugView, new_size: *mut ViewRect) -> tresult {
+    if new_size.is_null() {
+        return K_INVALID_ARG;
+    }
+    *view(this_).size.lock().unwrap() = *new_size;
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn v_on_focus(_this: *mut IPlugView, _state: u8) -> tresult {
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn v_set_frame(this_: *mut IPlugView, frame: *mut IPlugFrame) -> tresult {
+    view(this_).frame.store(frame, Ordering::SeqCst);
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn v_can_resize(_this: *mut IPlugView) -> tresult {
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn v_check_size_constraint(
+    _this: *mut IPlugView,
+    rect: *mut ViewRect,
+) -> tresult {
+    if rect.is_null() {
+        return K_INVALID_ARG;
+    }
+    let rect = &mut *rect;
+    rect.right = rect.right.max(rect.left + MIN_EDITOR_SIZE);
+    rect.bottom = rect.bottom.max(rect.top + MIN_EDITOR_SIZE);
+    K_RESULT_OK
+}
@@ -0,0 +1,341 @@
+//! Raise the scheduling priority of threads that feed a plugin.
+//!
+//! Audio callbacks usually arrive on a thread the audio backend already promoted, but
+//! an offline render, a MIDI input thread or a parameter feeder runs at normal
+//! priority and can be starved by everything else on the machine.
+//! [`promote_current_thread`] asks the OS for more, falls back to what the process is
+//! allowed, and reports what it actually got:
+//!
+//! | | [`Profile::Realtime`] | [`Profile::High`] |
+//! |---|---|---|
+//! | Linux | `SCHED_FIFO`, then `SCHED_RR`, then as High | nice -10, or the lowest `RLIMIT_NICE` allows |
+//! | macOS | thread time-constraint policy, then as High | QoS class user-interactive |
+//! | Windows | MMCSS "Pro Audio" at critical priority, then time-critical | `THREAD_PRIORITY_HIGHEST` |
+//!
+//! Promotion lasts until the thread exits; there is no undo.
+
+use std::fmt;
+
+/// How much priority to ask for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    /// A realtime scheduling class, for short bursts of work with a deadline (MIDI and
+    /// parameter feeders). A realtime thread that spins can lock up a core, so this is
+    /// not for long CPU-bound work.
+    Realtime,
+    /// Above normal but in the normal scheduling class: for long CPU-bound work such
+    /// as an offline render.
+    High,
+}
+
+/// What [`promote_current_thread`] achieved. Each level carries how it got there or,
+/// for [`Unchanged`](Self::Unchanged), why not.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Achieved {
+    /// A realtime class: "SCHED_FIFO priority 40", "time-constraint policy",
+    /// "MMCSS Pro Audio, critical".
+    Realtime(String),
+    /// A raised normal priority: "nice -10", "QoS user-interactive".
+    High(String),
+    /// The thread kept its priority.
+    Unchanged(String),
+}
+
+impl Achieved {
+    /// Whether the thread got anything above normal priority.
+    pub fn is_promoted(&self) -> bool {
+        !matches!(self, Self::Unchanged(_))
+    }
+}
+
+impl fmt::Display for Achieved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Realtime(how) => write!(f, "realtime ({how})"),
+            Self::High(how) => write!(f, "high ({how})"),
+            Self::Unchanged(why) => write!(f, "unchanged: {why}"),
+        }
+    }
+}
+
+/// Raise the calling thread's priority to `profile`, or as close as the OS and the
+/// process's limits allow. Refusals (EPERM, a stopped MMCSS service) are not errors:
+/// they come back as a lower [`Achieved`] level.
+pub fn promote_current_thread(profile: Profile) -> Achieved {
+    sys::promote(profile)
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+mod sys {
+    use core::ffi::{c_int, c_uint, c_ulong};
+    use std::io;
+
+    use super::{Achieved, Profile};
+
+    #[repr(C)]
+    struct SchedParam {
+        sched_priority: c_int,
+    }
+
+    #[repr(C)]
+    struct RLimit {
+        cur: c_ulong,
+        max: c_ulong,
+    }
+
+    extern "C" {
+        fn pthread_self() -> c_ulong;
+        fn pthread_setschedparam(thread: c_ulong, policy: c_int, param: *const SchedParam)
+            -> c_int;
+        fn getrlimit(resource: c_int, rlim: *mut RLimit) -> c_int;
+        fn setpriority(which: c_int, who: c_uint, prio: c_int) -> c_int;
+    }
+
+    const SCHED_FIFO: c_int = 1;
+    const SCHED_RR: c_int = 2;
+    const PRIO_PROCESS: c_int = 0;
+    const RLIMIT_NICE: c_int = 13;
+    const RLIMIT_RTPRIO: c_int = 14;
+    /// Below the 70-88 that JACK and PipeWire give their audio threads, so a feeder
+    /// never preempts the callback it feeds.
+    const REALTIME_PRIORITY: c_int = 40;
+    const HIGH_NICE: c_int = -10;
+
+    fn soft_limit(resource: c_int) -> Option<c_ulong> {
+        let mut limit = RLimit { cur: 0, max: 0 };
+        (unsafe { getrlimit(resource, &mut limit) } == 0).then_some(limit.cur)
+    }
+
+    pub(super) fn promote(profile: Profile) -> Achieved {
+        let refused = match profile {
+            Profile::Realtime => match realtime() {
+                Ok(achieved) => return achieved,
+                Err(why) => Some(why),
+            },
+            Profile::High => None,
+        };
+        nice().unwrap_or_else(|why| {
+            Achieved::Unchanged(match refused {
+                Some(refused) => format!("{refused}; {why}"),
+                None => why,
+            })
+        })
+    }
+
+    fn realtime() -> Result<Achieved, String> {
+        // Without CAP_SYS_NICE the priority may not exceed RLIMIT_RTPRIO; a limit of
+        // 0 still leaves root (or the capability) able to go to REALTIME_PRIORITY.
+        let priority = match soft_limit(RLIMIT_RTPRIO) {
+            Some(limit @ 1..) => (limit as c_int).clamp(1, REALTIME_PRIORITY),
+            _ => REALTIME_PRIORITY,
+        };
+        let param = SchedParam {
+            sched_priority: priority,
+        };
+        let mut refused = Vec::new();
+        for (policy, name) in [(SCHED_FIFO, "SCHED_FIFO"), (SCHED_RR, "SCHED_RR")] {
+            match unsafe { pthread_setschedparam(pthread_self(), policy, &param) } {
+                0 => return Ok(Achieved::Realtime(format!("{name} priority {priority}"))),
+                err => refused.push(format!(
+                    "{name} refused ({})",
+                    io::Error::from_raw_os_error(err)
+                )),
+            }
+        }
+        Err(refused.join(", "))
+    }
+
+    fn nice() -> Result<Achieved, String> {
+        // RLIMIT_NICE of n allows nice values down to 20 - n.
+        let floor = match soft_limit(RLIMIT_NICE) {
+            Some(limit) if limit <= 40 => 20 - limit as c_int,
+            _ => HIGH_NICE,
+        };
+        let mut attempts = vec![HIGH_NICE];
+        if floor > HIGH_NICE && floor < 0 {
+            attempts.push(floor);
+        }
+        let mut last = None;
+        for value in attempts {
+            // On Linux the "process" of PRIO_PROCESS 0 is the calling thread.
+            if unsafe { setpriority(PRIO_PROCESS, 0, value) } == 0 {
+                return Ok(Achieved::High(format!("nice {value}")));
+            }
+            last = Some(io::Error::last_os_error());
+        }
+        Err(match last {
+            Some(e) => format!("nice refused ({e})"),
+            None => "nice refused".into(),
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod sys {
+    use core::ffi::{c_int, c_uint};
+
+    use super::{Achieved, Profile};
+
+    #[repr(C)]
+    struct TimeConstraintPolicy {
+        period: u32,
+        computation: u32,
+        constraint: u32,
+        preemptible: c_int,
+    }
+
+    #[repr(C)]
+    struct TimebaseInfo {
+        numer: u32,
+        denom: u32,
+    }
+
+    extern "C" {
+        static mach_task_self_: c_uint;
+        fn mach_thread_self() -> c_uint;
+        fn mach_port_deallocate(task: c_uint, name: c_uint) -> c_int;
+        fn mach_timebase_info(info: *mut TimebaseInfo) -> c_int;
+        fn thread_policy_set(
+            thread: c_uint,
+            flavor: c_uint,
+            policy: *mut c_int,
+            count: c_uint,
+        ) -> c_int;
+        fn pthread_set_qos_class_self_np(class: c_uint, relative_priority: c_int) -> c_int;
+    }
+
+    const THREAD_TIME_CONSTRAINT_POLICY: c_uint = 2;
+    const THREAD_TIME_CONSTRAINT_POLICY_COUNT: c_uint = 4;
+    const QOS_CLASS_USER_INTERACTIVE: c_uint = 0x21;
+    /// No fixed period: feeders wake when there is work. Within each wakeup the
+    /// thread asks for up to 1 ms of CPU inside a 5 ms window.
+    const COMPUTATION_NS: u64 = 1_000_000;
+    const CONSTRAINT_NS: u64 = 5_000_000;
+
+    pub(super) fn promote(profile: Profile) -> Achieved {
+        let refused = match profile {
+            Profile::Realtime => match time_constraint() {
+                Ok(achieved) => return achieved,
+                Err(why) => Some(why),
+            },
+            Profile::High => None,
+        };
+        match unsafe { pthread_set_qos_class_self_np(QOS_CLASS_USER_INTERACTIVE, 0) } {
+            0 => Achieved::High("QoS user-interactive".into()),
+            err => {
+                let why = format!(
+                    "QoS class refused ({})",
+                    std::io::Error::from_raw_os_error(err)
+                );
+                Achieved::Unchanged(match refused {
+                    Some(refused) => format!("{refused}; {why}"),
+                    None => why,
+                })
+            }
+        }
+    }
+
+    fn time_constraint() -> Result<Achieved, String> {
+        let mut timebase = TimebaseInfo { numer: 0, denom: 0 };
+        if unsafe { mach_timebase_info(&mut timebase) } != 0 || timebase.numer == 0 {
+            return Err("no mach timebase".into());
+        }
+        let ticks = |ns: u64| (ns * timebase.denom as u64 / timebase.numer as u64) as u32;
+        let mut policy = TimeConstraintPolicy {
+            period: 0,
+            computation: ticks(COMPUTATION_NS),
+            constraint: ticks(CONSTRAINT_NS),
+            preemptible: 1,
+        };
+        let kr = unsafe {
+            let thread = mach_thread_self();
+            let kr = thread_policy_set(
+                thread,
+                THREAD_TIME_CONSTRAINT_POLICY,
+                (&mut policy as *mut TimeConstraintPolicy).cast(),
+                THREAD_TIME_CONSTRAINT_POLICY_COUNT,
+            );
+            mach_port_deallocate(mach_task_self_, thread);
+            kr
+        };
+        match kr {
+            0 => Ok(Achieved::Realtime("time-constraint policy".into())),
+            kr => Err(format!("time-constraint policy refused (kern_return {kr})")),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use core::ffi::c_void;
+    use std::io;
+
+    use super::{Achieved, Profile};
+
+    #[link(name = "avrt")]
+    extern "system" {
+        fn AvSetMmThreadCharacteristicsW(task: *const u16, index: *mut u32) -> *mut c_void;
+        fn AvSetMmThreadPriority(handle: *mut c_void, priority: i32) -> i32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentThread() -> *mut c_void;
+        fn SetThreadPriority(thread: *mut c_void, priority: i32) -> i32;
+    }
+
+    const AVRT_PRIORITY_CRITICAL: i32 = 2;
+    const THREAD_PRIORITY_HIGHEST: i32 = 2;
+    const THREAD_PRIORITY_TIME_CRITICAL: i32 = 15;
+
+    pub(super) fn promote(profile: Profile) -> Achieved {
+        let refused = match profile {
+            Profile::Realtime => match mmcss() {
+                Ok(achieved) => return achieved,
+                Err(why) => Some(why),
+            },
+            Profile::High => None,
+        };
+        let (priority, name) = match profile {
+            Profile::Realtime => (THREAD_PRIORITY_TIME_CRITICAL, "time-critical"),
+            Profile::High => (THREAD_PRIORITY_HIGHEST, "highest"),
+        };
+        if unsafe { SetThreadPriority(GetCurrentThread(), priority) } != 0 {
+            return Achieved::High(format!("thread priority {name}"));
+        }
+        let why = format!("thread priority refused ({})", io::Error::last_os_error());
+        Achieved::Unchanged(match refused {
+            Some(refused) => format!("{refused}; {why}"),
+            None => why,
+        })
+    }
+
+    /// Register with the multimedia class scheduler. The registration is never
+    /// reverted; MMCSS drops it when the thread exits.
+    fn mmcss() -> Result<Achieved, String> {
+        let task: Vec<u16> = "Pro Audio\0".encode_utf16().collect();
+        let mut index = 0;
+        let handle = unsafe { AvSetMmThreadCharacteristicsW(task.as_ptr(), &mut index) };
+        if handle.is_null() {
+            return Err(format!("MMCSS refused ({})", io::Error::last_os_error()));
+        }
+        if unsafe { AvSetMmThreadPriority(handle, AVRT_PRIORITY_CRITICAL) } == 0 {
+            return Ok(Achieved::Realtime("MMCSS Pro Audio".into()));
+        }
+        Ok(Achieved::Realtime("MMCSS Pro Audio, critical".into()))
+    }
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    windows
+)))]
+mod sys {
+    use super::{Achieved, Profile};
+
+    pub(super) fn promote(_profile: Profile) -> Achieved {
+        Achieved::Unchanged("not supported on this platform".into())
+    }
+}
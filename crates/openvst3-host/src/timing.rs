@@ -0,0 +1,257 @@
+//! Continuous per-instance accounting of `process` calls.
+//!
+//! A [`TimingCollector`] attached with [`ProcessorHandle::set_timing_collector`]
+//! (or driven by hand with [`start`](TimingCollector::start) and
+//! [`finish`](TimingCollector::finish)) reads its [`Clock`] before and after each
+//! call, keeps an exponentially decaying load average (time spent over the
+//! [`TimingBudget`]) and counts calls that ran over budget. Everything lives in
+//! atomics, so a UI thread can take a [`TimingSnapshot`] at any time without
+//! locking the audio thread. A handle without a collector does no timing at all.
+//!
+//! [`ProcessorHandle::set_timing_collector`]: crate::ProcessorHandle::set_timing_collector
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// A monotonic time source; tests substitute one they advance by hand.
+pub trait Clock: Send + Sync {
+    /// Nanoseconds since a fixed, arbitrary origin. Never goes backwards.
+    fn now_ns(&self) -> u64;
+}
+
+/// [`Instant`]-based [`Clock`], the default.
+#[derive(Clone, Copy, Debug)]
+pub struct MonotonicClock {
+    origin: Instant,
+}
+
+impl MonotonicClock {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MonotonicClock {
+    #[inline]
+    fn now_ns(&self) -> u64 {
+        self.origin.elapsed().as_nanos() as u64
+    }
+}
+
+/// How long a `process` call may take before it counts as an overrun.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimingBudget {
+    /// The same limit for every call.
+    Fixed(Duration),
+    /// The time the block's audio lasts at this sample rate: 512 frames at 48 kHz
+    /// may take 10.67 ms.
+    Realtime { sample_rate: f64 },
+}
+
+impl TimingBudget {
+    /// The budget of a `frames`-long block, in nanoseconds.
+    pub fn nanos(&self, frames: usize) -> u64 {
+        match *self {
+            TimingBudget::Fixed(limit) => limit.as_nanos() as u64,
+            TimingBudget::Realtime { sample_rate } if sample_rate > 0.0 => {
+                (frames as f64 / sample_rate * 1e9) as u64
+            }
+            TimingBudget::Realtime { .. } => 0,
+        }
+    }
+}
+
+/// What a [`TimingCollector`] has seen. Each field is read atomically on its own;
+/// a snapshot taken during a call may count that call in some fields only.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimingSnapshot {
+    pub calls: u64,
+    /// Calls that took longer than their budget.
+    pub overruns: u64,
+    /// The most recent call.
+    pub last: Duration,
+    /// The longest call since creation or the last [`TimingCollector::take_max`].
+    pub max: Duration,
+    /// Decaying average of call time over budget: 1.0 means a call takes as long
+    /// as its audio lasts. Calls with a zero budget are left out.
+    pub load: f64,
+}
+
+/// See the module docs.
+pub struct TimingCollector {
+    clock: Box<dyn Clock>,
+    budget: TimingBudget,
+    /// Weight of the newest call in the load average.
+    smoothing: f64,
+    calls: AtomicU64,
+    overruns: AtomicU64,
+    last_ns: AtomicU64,
+    max_ns: AtomicU64,
+    /// `f64` bits; `NaN` until the first call with a budget.
+    load: AtomicU64,
+}
+
+impl fmt::Debug for TimingCollector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimingCollector")
+            .field("budget", &self.budget)
+            .field("smoothing", &self.smoothing)
+            .field("snapshot", &self.snapshot())
+            .finish()
+    }
+}
+
+impl TimingCollector {
+    /// A collector on the [`MonotonicClock`], with a smoothing of 0.05 (the load
+    /// average follows roughly the last 20 calls).
+    pub fn new(budget: TimingBudget) -> Self {
+        Self {
+            clock: Box::new(MonotonicClock::new()),
+            budget,
+            smoothing: 0.05,
+            calls: AtomicU64::new(0),
+            overruns: AtomicU64::new(0),
+            last_ns: AtomicU64::new(0),
+            max_ns: AtomicU64::new(0),
+            load: AtomicU64::new(f64::NAN.to_bits()),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Weight of the newest call in the load average, clamped to `(0, 1]`.
+    pub fn with_smoothing(mut self, smoothing: f64) -> Self {
+        self.smoothing = smoothing.clamp(f64::MIN_POSITIVE, 1.0);
+        self
+    }
+
+    pub fn budget(&self) -> TimingBudget {
+        self.budget
+    }
+
+    /// Read the clock before a call; pass the result to [`finish`](Self::finish).
+    #[inline]
+    pub fn start(&self) -> u64 {
+        self.clock.now_ns()
+    }
+
+    /// Read the clock after a `frames`-long call that began at `started` and account
+    /// for it; returns how long it took, in nanoseconds. Calls for one processor are
+    /// serialized, so the load average is updated without a compare-and-swap loop.
+    pub fn finish(&self, started: u64, frames: usize) -> u64 {
+        let elapsed = self.clock.now_ns().saturating_sub(started);
+        let budget = self.budget.nanos(frames);
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        self.last_ns.store(elapsed, Ordering::Relaxed);
+        self.max_ns.fetch_max(elapsed, Ordering::Relaxed);
+        if budget > 0 {
+            if elapsed > budget {
+                self.overruns.fetch_add(1, Ordering::Relaxed);
+            }
+            let sample = elapsed as f64 / budget as f64;
+            let old = f64::from_bits(self.load.load(Ordering::Relaxed));
+            let new = if old.is_nan() {
+                sample
+            } else {
+                old + self.smoothing * (sample - old)
+            };
+            self.load.store(new.to_bits(), Ordering::Relaxed);
+        }
+        elapsed
+    }
+
+    /// The current counters; safe from any thread while calls are running.
+    pub fn snapshot(&self) -> TimingSnapshot {
+        let load = f64::from_bits(self.load.load(Ordering::Relaxed));
+        TimingSnapshot {
+            calls: self.calls.load(Ordering::Relaxed),
+            overruns: self.overruns.load(Ordering::Relaxed),
+            last: Duration::from_nanos(self.last_ns.load(Ordering::Relaxed)),
+            max: Duration::from_nanos(self.max_ns.load(Ordering::Relaxed)),
+            load: if load.is_nan() { 0.0 } else { load },
+        }
+    }
+
+    /// The longest call so far, restarting the maximum; for per-interval reports.
+    pub fn take_max(&self) -> Duration {
+        Duration::from_nanos(self.max_ns.swap(0, Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A clock that moves only when told to, counting its reads.
+    #[derive(Clone, Default)]
+    pub(crate) struct FakeClock {
+        now: std::sync::Arc<std::sync::atomic::AtomicU64>,
+        reads: std::sync::Arc<std::sync::atomic::AtomicU64>,
+    }
+
+    impl FakeClock {
+        pub(crate) fn advance(&self, ns: u64) {
+            self.now.fetch_add(ns, std::sync::atomic::Ordering::Relaxed);
+        }
+
+        pub(crate) fn reads(&self) -> u64 {
+            self.reads.load(std::sync::atomic::Ordering::Relaxed)
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now_ns(&self) -> u64 {
+            self.reads
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            self.now.load(std::sync::atomic::Ordering::Relaxed)
+        }
+    }
+
+    #[test]
+    fn timing_collector_averages_load_and_counts_overruns() {
+        let clock = FakeClock::default();
+        // 480 frames at 48 kHz: a 10 ms budget.
+        let timing = TimingCollector::new(TimingBudget::Realtime {
+            sample_rate: 48_000.0,
+        })
+        .with_clock(clock.clone())
+        .with_smoothing(0.5);
+        assert_eq!(timing.snapshot(), TimingSnapshot::default());
+
+        for ms in [5, 15, 5] {
+            let started = timing.start();
+            clock.advance(ms * 1_000_000);
+            assert_eq!(timing.finish(started, 480), ms * 1_000_000);
+        }
+        let snap = timing.snapshot();
+        assert_eq!((snap.calls, snap.overruns), (3, 1));
+        assert_eq!(snap.last, Duration::from_millis(5));
+        assert_eq!(snap.max, Duration::from_millis(15));
+        // 0.5, then halfway to 1.5, then halfway back to 0.5.
+        assert!((snap.load - 0.75).abs() < 1e-9, "{}", snap.load);
+        assert_eq!(timing.take_max(), Duration::from_millis(15));
+        assert_eq!(timing.snapshot().max, Duration::ZERO);
+
+        // An empty block has no budget: counted, but neither an overrun nor a load sample.
+        let started = timing.start();
+        clock.advance(1_000_000);
+        timing.finish(started, 0);
+        let after = timing.snapshot();
+        assert_eq!((after.calls, after.overruns, after.load), (4, 1, snap.load));
+
+        let fixed = TimingBudget::Fixed(Duration::from_millis(2));
+        assert_eq!(fixed.nanos(0), fixed.nanos(4096));
+    }
+}
@@ -0,0 +1,161 @@
+//! Call tracing (`trace` feature).
+//!
+//! Every handle method in [`plugin`](crate::plugin) emits a `tracing` event on the
+//! [`CALL_TARGET`] target with a process-wide sequence number, the interface and
+//! method names, key arguments and the returned value. `process` events are sampled
+//! per handle (see [`set_process_sampling`]) so the audio thread is not flooded.
+//! [`TraceRecorder`] is a minimal subscriber that keeps the events for assertions.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// `tracing` target of call events.
+pub const CALL_TARGET: &str = "openvst3::call";
+/// `tracing` target of optional calls a plugin declined (see
+/// [`ProcessorHandle`](crate::ProcessorHandle)); the host carried on without them.
+pub const OPTIONAL_TARGET: &str = "openvst3::optional";
+
+static SEQ: AtomicU64 = AtomicU64::new(0);
+static PROCESS_FIRST: AtomicU64 = AtomicU64::new(8);
+static PROCESS_EVERY: AtomicU64 = AtomicU64::new(1000);
+
+#[doc(hidden)]
+pub fn next_seq() -> u64 {
+    SEQ.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Trace the first `first` process blocks of each processor handle, then every
+/// `every`th one (`0` disables the periodic events). Defaults: 8 and 1000.
+pub fn set_process_sampling(first: u64, every: u64) {
+    PROCESS_FIRST.store(first, Ordering::Relaxed);
+    PROCESS_EVERY.store(every, Ordering::Relaxed);
+}
+
+pub(crate) fn optional_call_declined(interface: &'static str, method: &'static str, result: i32) {
+    tracing::info!(
+        target: OPTIONAL_TARGET,
+        interface,
+        method,
+        result,
+        "optional call declined, continuing"
+    );
+}
+
+/// Count one block on `counter` and decide whether it is traced.
+pub(crate) fn sample_block(counter: &AtomicU64) -> bool {
+    let n = counter.fetch_add(1, Ordering::Relaxed);
+    let first = PROCESS_FIRST.load(Ordering::Relaxed);
+    let every = PROCESS_EVERY.load(Ordering::Relaxed);
+    n < first || (every != 0 && (n - first + 1).is_multiple_of(every))
+}
+
+/// One recorded plugin call.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TracedCall {
+    pub seq: u64,
+    pub interface: String,
+    pub method: String,
+    /// Key arguments and `result`, formatted, in emission order.
+    pub fields: Vec<(&'static str, String)>,
+}
+
+impl TracedCall {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The `tresult` (or count) the call returned, if integral.
+    pub fn result_code(&self) -> Option<i64> {
+        self.field("result")?.parse().ok()
+    }
+}
+
+/// Subscriber that records call events. Install it with
+/// `tracing::subscriber::with_default` (or as the global default) and inspect
+/// [`calls`](Self::calls) afterwards; clones share the same log.
+#[derive(Clone, Default)]
+pub struct TraceRecorder {
+    calls: Arc<Mutex<Vec<TracedCall>>>,
+    spans: Arc<AtomicU64>,
+}
+
+impl TraceRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn calls(&self) -> Vec<TracedCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// `"Interface::method"` for every recorded call, in order.
+    pub fn methods(&self) -> Vec<String> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|c| format!("{}::{}", c.interface, c.method))
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.calls.lock().unwrap().clear();
+    }
+}
+
+#[derive(Default)]
+struct CallVisitor(TracedCall);
+
+impl Visit for CallVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "seq" {
+            self.0.seq = value;
+        } else {
+            self.0.fields.push((field.name(), value.to_string()));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "interface" => self.0.interface = value.to_string(),
+            "method" => self.0.method = value.to_string(),
+            name => self.0.fields.push((name, value.to_string())),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.fields.push((field.name(), format!("{value:?}")));
+    }
+}
+
+impl Subscriber for TraceRecorder {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target() == CALL_TARGET
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(self.spans.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut visitor = CallVisitor::default();
+        event.record(&mut visitor);
+        self.calls.lock().unwrap().push(visitor.0);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
@@ -0,0 +1,265 @@
+//! The time a host reports to the plugin in each block's `ProcessContext`.
+//!
+//! Two clocks run side by side. A [`SampleClock`] counts every sample processed and
+//! reads the wall clock for `systemTime`, block after block, whether or not anything
+//! is playing; plenty of plugins need only that (and some process nothing without
+//! `kSystemTimeValid`). A [`Transport`] wraps one and adds the musical timeline:
+//! tempo, time signature and a project position that only moves while playing and
+//! can be relocated. Stopping, starting or locating the transport never touches the
+//! continuous time.
+//!
+//! Both hand out a [`ProcessContext`] per block with `next_block`; attach it with
+//! [`BoundProcessData::set_context`](crate::BoundProcessData::set_context).
+
+use openvst3_abi::{process_context_consts as ctx, ProcessContext};
+
+use crate::timing::{Clock, MonotonicClock};
+
+/// Continuous sample time and `systemTime`; see the module docs.
+#[derive(Clone, Debug)]
+pub struct SampleClock<C: Clock = MonotonicClock> {
+    sample_rate: f64,
+    samples: i64,
+    clock: C,
+}
+
+impl SampleClock {
+    /// Starting at sample 0, with `systemTime` from a [`MonotonicClock`].
+    pub fn new(sample_rate: f64) -> Self {
+        Self::with_clock(sample_rate, MonotonicClock::new())
+    }
+}
+
+impl<C: Clock> SampleClock<C> {
+    pub fn with_clock(sample_rate: f64, clock: C) -> Self {
+        Self {
+            sample_rate,
+            samples: 0,
+            clock,
+        }
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// The clock `systemTime` is read from.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Samples counted so far: the `continuousTimeSamples` of the next block.
+    pub fn continuous_samples(&self) -> i64 {
+        self.samples
+    }
+
+    /// The context of a block starting now, with only the continuous and system time
+    /// valid.
+    pub fn context(&self) -> ProcessContext {
+        ProcessContext {
+            state: ctx::K_SYSTEM_TIME_VALID | ctx::K_CONT_TIME_VALID,
+            sample_rate: self.sample_rate,
+            system_time: self.clock.now_ns() as i64,
+            continuous_time_samples: self.samples,
+            ..ProcessContext::default()
+        }
+    }
+
+    /// Count `frames` processed samples.
+    pub fn advance(&mut self, frames: usize) {
+        self.samples += frames as i64;
+    }
+
+    /// The [`context`](Self::context) of a block of `frames`, counting them.
+    pub fn next_block(&mut self, frames: usize) -> ProcessContext {
+        let context = self.context();
+        self.advance(frames);
+        context
+    }
+}
+
+/// A musical timeline on top of a [`SampleClock`]; see the module docs. Starts
+/// stopped at the project start, at 120 BPM in 4/4.
+#[derive(Clone, Debug)]
+pub struct Transport<C: Clock = MonotonicClock> {
+    clock: SampleClock<C>,
+    playing: bool,
+    position: i64,
+    tempo: f64,
+    time_signature: (i32, i32),
+}
+
+impl Transport {
+    pub fn new(sample_rate: f64) -> Self {
+        Self::with_clock(SampleClock::new(sample_rate))
+    }
+}
+
+impl<C: Clock> Transport<C> {
+    pub fn with_clock(clock: SampleClock<C>) -> Self {
+        Self {
+            clock,
+            playing: false,
+            position: 0,
+            tempo: 120.0,
+            time_signature: (4, 4),
+        }
+    }
+
+    /// Beats per minute.
+    pub fn tempo(mut self, bpm: f64) -> Self {
+        self.tempo = bpm;
+        self
+    }
+
+    pub fn time_signature(mut self, numerator: i32, denominator: i32) -> Self {
+        self.time_signature = (numerator, denominator);
+        self
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    /// Stop where the transport is; [`play`](Self::play) resumes from there.
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Move the project position to `samples`.
+    pub fn locate(&mut self, samples: i64) {
+        self.position = samples;
+    }
+
+    /// The project position of the next block, in samples.
+    pub fn project_samples(&self) -> i64 {
+        self.position
+    }
+
+    pub fn clock(&self) -> &SampleClock<C> {
+        &self.clock
+    }
+
+    /// The context of a block of `frames`: the clock's, plus the project position,
+    /// tempo, time signature and `kPlaying` while playing. The clock always counts
+    /// the block; the project position only moves while playing.
+    pub fn next_block(&mut self, frames: usize) -> ProcessContext {
+        let mut context = self.clock.next_block(frames);
+        let (numerator, denominator) = self.time_signature;
+        let sample_rate = self.clock.sample_rate();
+        let quarters = if sample_rate > 0.0 {
+            self.position as f64 / sample_rate * self.tempo / 60.0
+        } else {
+            0.0
+        };
+        let bar = numerator as f64 * 4.0 / denominator.max(1) as f64;
+        context.state |= ctx::K_PROJECT_TIME_MUSIC_VALID
+            | ctx::K_BAR_POSITION_VALID
+            | ctx::K_TEMPO_VALID
+            | ctx::K_TIME_SIG_VALID;
+        if self.playing {
+            context.state |= ctx::K_PLAYING;
+        }
+        context.project_time_samples = self.position;
+        context.project_time_music = quarters;
+        context.bar_position_music = if bar > 0.0 {
+            (quarters / bar).floor() * bar
+        } else {
+            0.0
+        };
+        context.tempo = self.tempo;
+        context.time_sig_numerator = numerator;
+        context.time_sig_denominator = denominator;
+        if self.playing {
+            self.position += frames as i64;
+        }
+        context
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timing::tests::FakeClock;
+
+    #[test]
+    fn sample_clock_is_monotonic_across_block_sizes() {
+        use openvst3_abi::process_context_consts::{K_CONT_TIME_VALID, K_SYSTEM_TIME_VALID};
+
+        let clock = FakeClock::default();
+        let mut samples = SampleClock::with_clock(48_000.0, clock.clone());
+        let (mut expected, mut last_system) = (0i64, -1i64);
+        for frames in [64, 1, 512, 0, 333, 64] {
+            let context = samples.next_block(frames);
+            assert_eq!(context.state, K_SYSTEM_TIME_VALID | K_CONT_TIME_VALID);
+            assert_eq!(context.sample_rate, 48_000.0);
+            assert_eq!(context.continuous_time_samples, expected);
+            assert!(context.system_time >= last_system);
+            expected += frames as i64;
+            last_system = context.system_time;
+            clock.advance(frames as u64 * 20_833);
+        }
+        assert_eq!(samples.continuous_samples(), expected);
+        assert_eq!(clock.reads(), 6);
+    }
+
+    #[test]
+    fn transport_stop_and_start_leave_continuous_time_running() {
+        use openvst3_abi::process_context_consts::{K_PLAYING, K_SYSTEM_TIME_VALID, K_TEMPO_VALID};
+
+        let clock = FakeClock::default();
+        let mut transport = Transport::with_clock(SampleClock::with_clock(48_000.0, clock.clone()))
+            .time_signature(3, 4);
+        let mut continuous = 0;
+        let mut last_system = -1;
+        let mut block = |transport: &mut Transport<FakeClock>, frames: usize| {
+            let context = transport.next_block(frames);
+            assert_eq!(context.continuous_time_samples, continuous);
+            assert!(context.system_time > last_system);
+            assert_ne!(context.state & K_SYSTEM_TIME_VALID, 0);
+            continuous += frames as i64;
+            last_system = context.system_time;
+            clock.advance(1_000_000);
+            context
+        };
+
+        // Stopped from the start: only the clock moves.
+        let context = block(&mut transport, 128);
+        assert_eq!(context.state & K_PLAYING, 0);
+        assert_ne!(context.state & K_TEMPO_VALID, 0);
+        assert_eq!((context.project_time_samples, context.tempo), (0, 120.0));
+
+        transport.play();
+        for frames in [24_000, 48_000, 12_000] {
+            let context = block(&mut transport, frames);
+            assert_ne!(context.state & K_PLAYING, 0);
+        }
+        // 84 000 samples at 120 BPM: 3.5 quarters, in the second bar of 3/4.
+        assert_eq!(transport.project_samples(), 84_000);
+
+        transport.stop();
+        for frames in [256, 17, 4_096] {
+            let context = block(&mut transport, frames);
+            assert_eq!(context.state & K_PLAYING, 0);
+            assert_eq!(context.project_time_samples, 84_000);
+            assert_eq!(context.project_time_music, 3.5);
+            assert_eq!(context.bar_position_music, 3.0);
+            assert_eq!(
+                (context.time_sig_numerator, context.time_sig_denominator),
+                (3, 4)
+            );
+        }
+
+        transport.play();
+        let context = block(&mut transport, 64);
+        assert_eq!(context.project_time_samples, 84_000);
+        assert_eq!(transport.project_samples(), 84_064);
+        transport.locate(0);
+        assert_eq!(block(&mut transport, 64).project_time_music, 0.0);
+        assert_eq!(transport.clock().continuous_samples(), continuous);
+    }
+}
@@ -0,0 +1,1013 @@
+//! Plugin validation: a fixed set of checks run against one class.
+//!
+//! Every check creates its own instance, so a failure in one cannot poison the next.
+//! [`Module::validate`] runs them all in-process. Tools that must survive a crashing
+//! plugin run each [`CheckCase`] in a worker process instead (see
+//! [`crate::isolate`]) and call [`Module::run_check`] there.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use openvst3_abi::{
+    classinfo_consts, Tuid, BUS_DIR_INPUT, BUS_DIR_OUTPUT, BUS_TYPE_MAIN, K_NOT_IMPLEMENTED,
+    K_RESULT_FALSE, K_RESULT_OK, MEDIA_TYPE_AUDIO, MEDIA_TYPE_EVENT,
+};
+
+use crate::{
+    analyze_block, format_arrangement, BoundProcessData, BusId, BusLayout, ComponentHandler,
+    EventList, HandlerEvent, HostError, HostLimits, MemoryStream, Module, ParamInfo,
+    ParameterChanges, ProcessSetupBuilder,
+};
+
+/// At most this many problems are spelled out in a failure message.
+const MAX_PROBLEMS: usize = 5;
+
+/// `performEdit` calls a second that [`Check::Edits`] lets a plugin make with no one
+/// touching it.
+pub const MAX_EDITS_PER_SECOND: f64 = 100.0;
+
+/// Stepped parameters with more steps than this are not listed by [`Check::Ranges`].
+const MAX_LISTED_STEPS: i32 = 1024;
+
+/// Frames [`Check::Automation`] ramps each parameter over: a second at 48 kHz.
+const SWEEP_FRAMES: usize = 48_000;
+
+/// How long [`Check::Edits`] processes, in real time, counting edits.
+const EDIT_WATCH: Duration = Duration::from_millis(250);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Check {
+    /// Component state survives `setState(getState())` and a second instance.
+    State,
+    /// Bus counts and `getBusInfo` are consistent, and audio buses have between 1
+    /// and the default [`HostLimits`] channels.
+    Buses,
+    /// Buses switched in a few unusual patterns while inactive (see
+    /// [`BusPermutation`]), each followed by a processed block: `activateBus` answers
+    /// `kResultOk`, `kResultFalse` or `kNotImplemented`, and processing works.
+    Activation,
+    /// After the host negotiates the reported layouts, `getBusArrangement` has as
+    /// many speakers as `getBusInfo` has channels on every audio bus.
+    Arrangement,
+    /// A controller class named by `getControllerClassId` is one the factory lists
+    /// as a controller. Only warns otherwise: the component still works on its own.
+    Controller,
+    /// Activation and 32-bit processing with varying block sizes, per setup: finite
+    /// output, and channels flagged silent really are.
+    Process,
+    /// Aux audio inputs (sidechains) can be activated and processed with audio on
+    /// them: no error and finite output. Skipped without aux inputs.
+    Sidechain,
+    /// Parameter table sanity and set/get round trips.
+    Params,
+    /// Parameter defaults are normalized, and a stepped parameter shows a different
+    /// display string at each of its steps.
+    Ranges,
+    /// Every automatable parameter ramped from 0 to 1 over a second of processing,
+    /// through the input parameter changes: no error and finite output. Only warns
+    /// if none of them changes the output.
+    Automation,
+    /// The controller leaves the component handler alone while the plugin processes
+    /// untouched: over [`MAX_EDITS_PER_SECOND`] `performEdit` calls only warn, as the
+    /// plugin still works, but hosts will coalesce or drop them.
+    Edits,
+    /// No interface references outlive the instance (needs `leak-audit`).
+    Teardown,
+}
+
+impl Check {
+    pub const ALL: [Check; 12] = [
+        Check::State,
+        Check::Buses,
+        Check::Activation,
+        Check::Arrangement,
+        Check::Controller,
+        Check::Process,
+        Check::Sidechain,
+        Check::Params,
+        Check::Ranges,
+        Check::Automation,
+        Check::Edits,
+        Check::Teardown,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Check::State => "state",
+            Check::Buses => "buses",
+            Check::Activation => "activation",
+            Check::Arrangement => "arrangement",
+            Check::Controller => "controller",
+            Check::Process => "process",
+            Check::Sidechain => "sidechain",
+            Check::Params => "params",
+            Check::Ranges => "ranges",
+            Check::Automation => "automation",
+            Check::Edits => "edits",
+            Check::Teardown => "teardown",
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Check {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Check::ALL
+            .into_iter()
+            .find(|c| c.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                let names: Vec<_> = Check::ALL.iter().map(|c| c.name()).collect();
+                format!("unknown check {s:?} (expected one of {})", names.join(", "))
+            })
+    }
+}
+
+/// A pattern of `activateBus` calls [`Check::Activation`] makes while the component
+/// is inactive, over every audio and event bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusPermutation {
+    AllOn,
+    AllOff,
+    /// Main buses off, aux buses on.
+    AuxOnly,
+    /// Every bus on, last bus first.
+    Reversed,
+    /// Each bus on, off, on and off again, then back to its default.
+    Toggled,
+}
+
+impl BusPermutation {
+    pub const ALL: [BusPermutation; 5] = [
+        BusPermutation::AllOn,
+        BusPermutation::AllOff,
+        BusPermutation::AuxOnly,
+        BusPermutation::Reversed,
+        BusPermutation::Toggled,
+    ];
+
+    /// The calls to make on `layout`, in order.
+    pub fn steps(self, layout: &BusLayout) -> Vec<(BusId, bool)> {
+        let buses: Vec<_> = layout
+            .iter()
+            .map(|(media_type, direction, bus)| {
+                let id = BusId {
+                    media_type,
+                    direction,
+                    index: bus.index,
+                };
+                (id, bus.bus_type == BUS_TYPE_MAIN, bus.default_active)
+            })
+            .collect();
+        let buses = buses.into_iter();
+        match self {
+            BusPermutation::AllOn => buses.map(|(id, ..)| (id, true)).collect(),
+            BusPermutation::AllOff => buses.map(|(id, ..)| (id, false)).collect(),
+            BusPermutation::AuxOnly => buses.map(|(id, main, _)| (id, !main)).collect(),
+            BusPermutation::Reversed => buses.rev().map(|(id, ..)| (id, true)).collect(),
+            BusPermutation::Toggled => buses
+                .flat_map(|(id, _, default)| {
+                    [true, false, true, false, default].map(|state| (id, state))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for BusPermutation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BusPermutation::AllOn => "all on",
+            BusPermutation::AllOff => "all off",
+            BusPermutation::AuxOnly => "aux only",
+            BusPermutation::Reversed => "all on, last bus first",
+            BusPermutation::Toggled => "toggled while inactive",
+        })
+    }
+}
+
+/// Setup a `process` check runs with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProcessCase {
+    pub sample_rate: f64,
+    pub block_size: usize,
+}
+
+/// One unit of validation work: a check and, for [`Check::Process`], its setup.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CheckCase {
+    pub check: Check,
+    pub process: Option<ProcessCase>,
+}
+
+/// `state`, `process@48000/512`.
+impl fmt::Display for CheckCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.check)?;
+        if let Some(p) = self.process {
+            write!(f, "@{}/{}", p.sample_rate, p.block_size)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CheckCase {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let (check, setup) = match s.split_once('@') {
+            Some((c, setup)) => (c.parse::<Check>()?, Some(setup)),
+            None => (s.parse::<Check>()?, None),
+        };
+        let process = match (check, setup) {
+            (Check::Process, Some(setup)) => {
+                let parsed = setup.split_once('/').and_then(|(sr, bs)| {
+                    Some(ProcessCase {
+                        sample_rate: sr.parse().ok()?,
+                        block_size: bs.parse().ok()?,
+                    })
+                });
+                Some(parsed.ok_or_else(|| format!("expected process@RATE/BLOCK, got {s:?}"))?)
+            }
+            (Check::Process, None) => return Err("process needs @RATE/BLOCK".into()),
+            (_, Some(_)) => return Err(format!("only process takes a setup, got {s:?}")),
+            (_, None) => None,
+        };
+        Ok(Self { check, process })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Fail(String),
+    /// The check does not apply (e.g. no controller); not a failure.
+    Skip(String),
+    /// Passed, but with something worth telling the plugin's author about.
+    Warn(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckResult {
+    pub case: CheckCase,
+    pub outcome: Outcome,
+    pub elapsed: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationReport {
+    pub cid: [u8; 16],
+    pub results: Vec<CheckResult>,
+}
+
+impl ValidationReport {
+    fn count(&self, f: impl Fn(&Outcome) -> bool) -> usize {
+        self.results.iter().filter(|r| f(&r.outcome)).count()
+    }
+
+    pub fn passed(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Pass))
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Fail(_)))
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Skip(_)))
+    }
+
+    pub fn warned(&self) -> usize {
+        self.count(|o| matches!(o, Outcome::Warn(_)))
+    }
+
+    /// No check that ran failed.
+    pub fn is_success(&self) -> bool {
+        self.failed() == 0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidateOptions {
+    pub checks: Vec<Check>,
+    pub sample_rates: Vec<f64>,
+    pub block_sizes: Vec<usize>,
+}
+
+impl Default for ValidateOptions {
+    fn default() -> Self {
+        Self {
+            checks: Check::ALL.to_vec(),
+            sample_rates: vec![44_100.0, 48_000.0],
+            block_sizes: vec![64, 512],
+        }
+    }
+}
+
+impl ValidateOptions {
+    /// Every unit of work, in run order; `process` expands to rates × block sizes.
+    pub fn cases(&self) -> Vec<CheckCase> {
+        let mut cases = Vec::new();
+        for &check in &self.checks {
+            if check != Check::Process {
+                cases.push(CheckCase {
+                    check,
+                    process: None,
+                });
+                continue;
+            }
+            for &sample_rate in &self.sample_rates {
+                for &block_size in &self.block_sizes {
+                    cases.push(CheckCase {
+                        check,
+                        process: Some(ProcessCase {
+                            sample_rate,
+                            block_size,
+                        }),
+                    });
+                }
+            }
+        }
+        cases
+    }
+}
+
+/// `parameter 3 ("Mix")`, for problems with one parameter.
+fn param_label(p: &ParamInfo) -> String {
+    format!("parameter {} ({:?})", p.id, p.title)
+}
+
+fn verdict(problems: Vec<String>) -> Outcome {
+    if problems.is_empty() {
+        return Outcome::Pass;
+    }
+    let mut msg = problems[..problems.len().min(MAX_PROBLEMS)].join("; ");
+    if problems.len() > MAX_PROBLEMS {
+        msg += &format!("; and {} more", problems.len() - MAX_PROBLEMS);
+    }
+    Outcome::Fail(msg)
+}
+
+impl Module {
+    /// Run every case of `options` against class `cid`, in this process.
+    pub fn validate(&mut self, cid: [u8; 16], options: &ValidateOptions) -> ValidationReport {
+        ValidationReport {
+            cid,
+            results: options
+                .cases()
+                .into_iter()
+                .map(|case| self.run_check(cid, case))
+                .collect(),
+        }
+    }
+
+    /// Run one case. Plugin errors and host panics become [`Outcome::Fail`]; a plugin
+    /// that crashes takes the process with it, hence [`crate::isolate`].
+    pub fn run_check(&mut self, cid: [u8; 16], case: CheckCase) -> CheckResult {
+        let start = Instant::now();
+        let outcome = match panic::catch_unwind(AssertUnwindSafe(|| self.check(cid, case))) {
+            Ok(Ok(outcome)) => outcome,
+            Ok(Err(e)) => Outcome::Fail(e.to_string()),
+            Err(_) => Outcome::Fail("host panicked during the check".into()),
+        };
+        CheckResult {
+            case,
+            outcome,
+            elapsed: start.elapsed(),
+        }
+    }
+
+    fn check(&mut self, cid: [u8; 16], case: CheckCase) -> Result<Outcome, HostError> {
+        match (case.check, case.process) {
+            (Check::State, _) => self.check_state(cid),
+            (Check::Buses, _) => self.check_buses(cid),
+            (Check::Activation, _) => self.check_activation(cid),
+            (Check::Arrangement, _) => self.check_arrangement(cid),
+            (Check::Controller, _) => self.check_controller(cid),
+            (Check::Process, Some(setup)) => self.check_process(cid, setup),
+            (Check::Process, None) => Ok(Outcome::Skip("no process setup given".into())),
+            (Check::Sidechain, _) => self.check_sidechain(cid),
+            (Check::Params, _) => self.check_params(cid),
+            (Check::Ranges, _) => self.check_ranges(cid),
+            (Check::Automation, _) => self.check_automation(cid),
+            (Check::Edits, _) => self.check_edits(cid),
+            (Check::Teardown, _) => self.check_teardown(cid),
+        }
+    }
+
+    fn check_state(&mut self, cid: [u8; 16]) -> Result<Outcome, HostError> {
+        let a = self.create_plugin(cid)?;
+        let saved = a.component_state()?;
+        a.set_component_state(&saved)?;
+        let mut problems = Vec::new();
+        if a.component_state()? != saved {
+            problems.push("getState after setState(getState()) returns different bytes".into());
+        }
+        if let Some(controller) = a.controller() {
+            let mut stream = MemoryStream::new();
+            controller.get_state(&mut stream)?;
+            controller.set_state(&mut MemoryStream::from_bytes(stream.into_bytes()))?;
+        }
+        // A class allowing one instance gets its second only once the first is gone.
+        let class = self.find_class_by_cid(&Tuid(cid));
+        if class.and_then(|class| class.cardinality.max_instances()) == Some(1) {
+            drop(a);
+        }
+        let b = self.create_plugin(cid)?;
+        b.set_component_state(&saved)?;
+        if b.component_state()? != saved {
+            problems.push("a second instance loaded with the state saves different bytes".into());
+        }
+        Ok(verdict(problems))
+    }
+
+    fn check_buses(&mut self, cid: [u8; 16]) -> Result<Outcome, HostError> {
+        let instance = self.create_plugin(cid)?;
+        let component = instance.component();
+        let limits = HostLimits::default();
+        let mut problems = Vec::new();
+        let mut total = 0;
+        for (media, media_name) in [(MEDIA_TYPE_AUDIO, "audio"), (MEDIA_TYPE_EVENT, "event")] {
+            for (dir, dir_name) in [(BUS_DIR_INPUT, "input"), (BUS_DIR_OUTPUT, "output")] {
+                let count = component.bus_count(media, dir);
+                if count < 0 {
+                    problems.push(format!(
+                        "negative {media_name} {dir_name} bus count {count}"
+                    ));
+                    continue;
+                }
+                total += count;
+                for i in 0..count {
+                    let bus = format!("{media_name} {dir_name} bus {i}");
+                    match component.bus_info(media, dir, i) {
+                        Err(e) => problems.push(format!("{bus}: {e}")),
+                        Ok(info) if info.media_type != media || info.direction != dir => problems
+                            .push(format!(
+                                "{bus} describes itself as media type {}, direction {}",
+                                info.media_type, info.direction
+                            )),
+                        Ok(info) if media == MEDIA_TYPE_AUDIO => {
+                            let id = BusId {
+                                media_type: media,
+                                direction: dir,
+                                index: i,
+                            };
+                            if let Err(e) = limits.check_channels(id, info.channel_count) {
+                                problems.push(e.to_string());
+                            }
+                        }
+                        Ok(_) => {}
+                    }
+                }
+            }
+        }
+        if total == 0 {
+            problems.push("no audio or event buses".into());
+        }
+        Ok(verdict(problems))
+    }
+
+    fn check_activation(&mut self, cid: [u8; 16]) -> Result<Outcome, HostError> {
+        let instance = self.create_plugin(cid)?;
+        let component = instance.component();
+        let processor = instance.processor();
+        let layout = component.bus_layout()?;
+        if layout.iter().next().is_none() {
+            return Ok(Outcome::Skip("no buses".into()));
+        }
+        let block = 256;
+        let setup = ProcessSetupBuilder::new().max_block(block as i32).build()?;
+        let inputs = instance.input_bus_channels();
+        let outputs = instance.output_bus_channels();
+        let mut bound = BoundProcessData::<f32>::with_buses(&inputs, &outputs, block);
+        for bus in 0..inputs.len() {
+            for (c, buf) in bound.input_bus_mut(bus).enumerate() {
+                for (i, s) in buf.iter_mut().enumerate() {
+                    *s = (0.05 * (i + c + bus) as f32).sin() * 0.5;
+                }
+            }
+        }
+        let mut problems = Vec::new();
+        for permutation in BusPermutation::ALL {
+            // A plugin that crashes takes a worker with it; the isolation machinery
+            // reports the last line written to stderr.
+            eprintln!("activation: {permutation}");
+            for (id, state) in permutation.steps(&layout) {
+                let tr = component.activate_bus_raw(id.media_type, id.direction, id.index, state);
+                if !matches!(tr, K_RESULT_OK | K_RESULT_FALSE | K_NOT_IMPLEMENTED) {
+                    let state = if state { "on" } else { "off" };
+                    problems.push(format!(
+                        "{permutation}: activateBus({id}, {state}) returned tresult {tr}"
+                    ));
+                }
+            }
+            // Driven through the handles: `PluginInstance::activate` would switch the
+            // default buses back on.
+            let run = |bound: &mut BoundProcessData<f32>| -> Result<Option<usize>, HostError> {
+                processor.setup_processing(&setup)?;
+                component.set_active(true)?;
+                let processed = processor.set_processing(true).and_then(|()| {
+                    let data = bound.prepare(block, None)?;
+                    // Every buffer is owned by `bound` and `block` frames long.
+                    unsafe { processor.process_32f(data) }
+                });
+                let _ = processor.set_processing(false);
+                let _ = component.set_active(false);
+                if processed?.nothing_to_do() {
+                    return Ok(None);
+                }
+                Ok(bound
+                    .outputs()
+                    .iter()
+                    .position(|b| !analyze_block(b).is_finite()))
+            };
+            match run(&mut bound) {
+                Ok(None) => {}
+                Ok(Some(c)) => {
+                    problems.push(format!("{permutation}: non-finite output on channel {c}"))
+                }
+                Err(e) => problems.push(format!("{permutation}: {e}")),
+            }
+        }
+        Ok(verdict(problems))
+    }
+
+    fn check_arrangement(&mut self, cid: [u8; 16]) -> Result<Outcome, HostError> {
+        let instance = self.create_plugin(cid)?;
+        let inputs = instance.main_bus_channels(BUS_DIR_INPUT);
+        let outputs = instance.main_bus_channels(BUS_DIR_OUTPUT);
+        if inputs == 0 && outputs == 0 {
+            return Ok(Outcome::Skip("no audio buses".into()));
+        }
+        instance.negotiate_channels(inputs, outputs);
+        let layout = instance.component().bus_layout()?;
+        let processor = instance.processor();
+        let mut problems = Vec::new();
+        let mut answered = 0;
+        for (direction, buses) in [
+            (BUS_DIR_INPUT, &layout.audio_inputs),
+            (BUS_DIR_OUTPUT, &layout.audio_outputs),
+        ] {
+            for bus in buses {
+                let id = BusId {
+                    media_type: MEDIA_TYPE_AUDIO,
+                    direction,
+                    index: bus.index,
+                };
+                let arrangement = match processor.bus_arrangement(direction, bus.index) {
+                    Ok(arrangement) => arrangement,
+                    Err(HostError::TErr(K_NOT_IMPLEMENTED)) => continue,
+                    Err(e) => {
+                        problems.push(format!("{id}: getBusArrangement failed: {e}"));
+                        continue;
+                    }
+                };
+                answered += 1;
+                let speakers = arrangement.count_ones();
+                if speakers as i32 != bus.channel_count {
+                    problems.push(format!(
+                        "{id}: getBusArrangement is {} ({speakers} speakers) but getBusInfo \
+                         reports channel count {}",
+                        format_arrangement(arrangement),
+                        bus.channel_count
+                    ));
+                }
+            }
+        }
+        if answered == 0 && problems.is_empty() {
+            return Ok(Outcome::Skip("getBusArrangement is not implemented".into()));
+        }
+        Ok(verdict(problems))
+    }
+
+    fn check_controller(&mut self, cid: [u8; 16]) -> Result<Outcome, HostError> {
+        let architecture = self.create_plugin(cid)?.architecture();
+        let Some(controller) = architecture.controller_cid() else {
+            return Ok(Outcome::Pass);
+        };
+        let hex = crate::fmt_cid_hex(&controller.0);
+        Ok(match self.find_class_by_cid(&controller) {
+            None => Outcome::Warn(format!(
+                "getControllerClassId names {hex}, which the factory does not list"
+            )),
+            Some(info) if info.category != classinfo_consts::K_VST_COMPONENT_CONTROLLER_CLASS => {
+                Outcome::Warn(format!(
+                    "getControllerClassId names {hex}, a {:?} class",
+                    info.category
+                ))
+            }
+            Some(_) => Outcome::Pass,
+        })
+    }
+
+    fn check_process(&mut self, cid: [u8; 16], case: ProcessCase) -> Result<Outcome, HostError> {
+        let mut instance = self.create_plugin(cid)?;
+        let outputs = instance.main_bus_channels(BUS_DIR_OUTPUT);
+        if outputs == 0 {
+            return Ok(Outcome::Skip("no audio output bus".into()));
+        }
+        let inputs = instance.main_bus_channels(BUS_DIR_INPUT);
+        let block = case.block_size.max(1);
+        let setup = ProcessSetupBuilder::new()
+            .sample_rate(case.sample_rate)
+            .max_block(block as i32)
+            .build()?;
+        // Full, partial and single-frame blocks; hosts may shrink any block.
+        let sizes = [block, block.div_ceil(2), 1, block];
+        let mut bound = BoundProcessData::<f32>::new(inputs, outputs, block);
+        for round in ["first activation", "reactivation"] {
+            instance.activate(&setup)?;
+            for (k, &n) in sizes.iter().enumerate() {
+                for (c, buf) in bound.inputs_mut().enumerate() {
+                    for (i, s) in buf.iter_mut().enumerate() {
+                        *s = if k == 0 {
+                            (i == 0) as u8 as f32
+                        } else {
+                            (0.05 * (i + c) as f32).sin() * 0.5
+                        };
+                    }
+                }
+                let stats = instance.process_bound(&mut bound, n, None)?;
+                if stats.nothing_to_do() {
+                    continue;
+                }
+                if let Some(c) = bound
+                    .outputs()
+                    .iter()
+                    .position(|b| !analyze_block(&b[..n]).is_finite())
+                {
+                    instance.deactivate();
+                    return Ok(Outcome::Fail(format!(
+                        "non-finite output on channel {c} in block {k} ({n} frames, {round})"
+                    )));
+                }
+                if let Some(c) = bound.outputs().iter().enumerate().position(|(c, b)| {
+                    stats.output_silent(0, c) && b[..n].iter().any(|&s| s != 0.0)
+                }) {
+                    instance.deactivate();
+                    return Ok(Outcome::Fail(format!(
+                        "output channel {c} flagged silent but not silent in block {k} \
+                         ({n} frames, {round})"
+                    )));
+                }
+            }
+            instance.deactivate();
+        }
+        Ok(Outcome::Pass)
+    }
+
+    fn check_sidechain(&mut self, cid: [u8; 16]) -> Result<Outcome, HostError> {
+        let mut instance = self.create_plugin(cid)?;
+        let inputs = instance.input_bus_channels();
+        if inputs.len() < 2 {
+            return Ok(Outcome::Skip("no aux audio input bus".into()));
+        }
+        let outputs = instance.main_bus_channels(BUS_DIR_OUTPUT);
+        if outputs == 0 {
+            return Ok(Outcome::Skip("no audio output bus".into()));
+        }
+        let component = instance.component();
+        for bus in 1..inputs.len() as i32 {
+            if let Err(e) = component.activate_bus(MEDIA_TYPE_AUDIO, BUS_DIR_INPUT, bus, true) {
+                return Ok(Outcome::Fail(format!(
+                    "activating aux input bus {bus} failed: {e}"
+                )));
+            }
+        }
+        let block = 256;
+        instance.activate(&ProcessSetupBuilder::new().max_block(block as i32).build()?)?;
+        let mut bound = BoundProcessData::<f32>::with_buses(&inputs, &[outputs], block);
+        for bus in 0..inputs.len() {
+            for (c, buf) in bound.input_bus_mut(bus).enumerate() {
+                for (i, s) in buf.iter_mut().enumerate() {
+                    *s = (0.05 * (i + c + bus) as f32).sin() * 0.5;
+                }
+            }
+        }
+        let mut outcome = Outcome::Pass;
+        for k in 0..4 {
+            match instance.process_bound(&mut bound, block, None) {
+                Err(e) => {
+                    outcome = Outcome::Fail(format!("block {k} with aux inputs fed: {e}"));
+                    break;
+                }
+                Ok(stats) if stats.nothing_to_do() => {}
+                Ok(_) => {
+                    if let Some(c) = bound
+                        .outputs()
+                        .iter()
+                        .position(|b| !analyze_block(b).is_finite())
+                    {
+                        outcome = Outcome::Fail(format!(
+                            "non-finite output on channel {c} in block {k} with aux inputs fed"
+                        ));
+                        break;
+                    }
+                }
+            }
+        }
+        instance.deactivate();
+        Ok(outcome)
+    }
+
+    fn check_params(&mut self, cid: [u8; 16]) -> Result<Outcome, HostError> {
+        let instance = self.create_plugin(cid)?;
+        let Some(controller) = instance.controller() else {
+            return Ok(Outcome::Skip("no edit controller on the component".into()));
+        };
+        let params = controller.parameters()?;
+        if params.is_empty() {
+            return Ok(Outcome::Skip("no parameters".into()));
+        }
+        let mut problems = Vec::new();
+        let mut ids = HashSet::new();
+        for p in &params {
+            let id = p.id;
+            if !ids.insert(id) {
+                problems.push(format!("parameter id {id} is used twice"));
+            }
+            if p.title.is_empty() {
+                problems.push(format!("parameter {id} has no title"));
+            }
+            if let Err(e) = controller.param_string_by_value(id, p.default_normalized) {
+                problems.push(format!("parameter {id} has no display string: {e}"));
+            }
+            if !p.is_writable() {
+                continue;
+            }
+            let tolerance = if p.step_count > 0 {
+                0.5 / p.step_count as f64
+            } else {
+                1e-6
+            };
+            for value in [0.0, 1.0, p.default_normalized] {
+                if let Err(e) = controller.set_param_normalized(id, value) {
+                    problems.push(format!("parameter {id}: setting {value} failed: {e}"));
+                    continue;
+                }
+                let got = controller.get_param_normalized(id);
+                if (got - value).abs() > tolerance {
+                    problems.push(format!("parameter {id}: set {value}, read back {got}"));
+                }
+            }
+        }
+        Ok(verdict(problems))
+    }
+
+    fn check_ranges(&mut self, cid: [u8; 16]) -> Result<Outcome, HostError> {
+        let instance = self.create_plugin(cid)?;
+        let Some(controller) = instance.controller() else {
+            return Ok(Outcome::Skip("no edit controller on the component".into()));
+        };
+        // Creating the instance already refused more than the limits' parameters.
+        let params = controller.parameters()?;
+        if params.is_empty() {
+            return Ok(Outcome::Skip("no parameters".into()));
+        }
+        let mut problems = Vec::new();
+        'params: for p in &params {
+            let label = param_label(p);
+            if !(0.0..=1.0).contains(&p.default_normalized) {
+                problems.push(format!(
+                    "{label}: default {} is outside [0, 1]",
+                    p.default_normalized
+                ));
+            }
+            let steps = p.step_count;
+            if steps < 0 {
+                problems.push(format!("{label}: negative step count {steps}"));
+            }
+            if !(1..=MAX_LISTED_STEPS).contains(&steps) {
+                continue;
+            }
+            let mut shown = HashSet::new();
+            for k in 0..=steps {
+                let value = f64::from(k) / f64::from(steps);
+                match controller.param_string_by_value(p.id, value) {
+                    Ok(text) => {
+                        shown.insert(text);
+                    }
+                    Err(e) => {
+                        problems.push(format!("{label}: no display string for step {k}: {e}"));
+                        continue 'params;
+                    }
+                }
+            }
+            if shown.len() != steps as usize + 1 {
+                problems.push(format!(
+                    "{label}: {steps} steps ({} values) but {} distinct display strings",
+                    steps + 1,
+                    shown.len()
+                ));
+            }
+        }
+        Ok(verdict(problems))
+    }
+
+    fn check_automation(&mut self, cid: [u8; 16]) -> Result<Outcome, HostError> {
+        let mut instance = self.create_plugin(cid)?;
+        let Some(controller) = instance.controller() else {
+            return Ok(Outcome::Skip("no edit controller on the component".into()));
+        };
+        let params: Vec<_> = controller
+            .parameters()?
+            .into_iter()
+            .filter(ParamInfo::is_writable)
+            .collect();
+        if params.is_empty() {
+            return Ok(Outcome::Skip("no automatable parameters".into()));
+        }
+        let outputs = instance.main_bus_channels(BUS_DIR_OUTPUT);
+        if outputs == 0 {
+            return Ok(Outcome::Skip("no audio output bus".into()));
+        }
+        let inputs = instance.main_bus_channels(BUS_DIR_INPUT);
+        let block = 512;
+        instance.activate(&ProcessSetupBuilder::new().max_block(block as i32).build()?)?;
+        let mut bound = BoundProcessData::<f32>::new(inputs, outputs, block);
+        // The same input every block, so only the parameter moves the output.
+        for (c, buf) in bound.inputs_mut().enumerate() {
+            for (i, s) in buf.iter_mut().enumerate() {
+                *s = (0.05 * (i + c) as f32).sin() * 0.5;
+            }
+        }
+        let mut events = EventList::with_capacity(1);
+        let mut changes = ParameterChanges::with_capacity(1, 2);
+        let mut first = vec![vec![0.0f32; block]; outputs];
+        let blocks = SWEEP_FRAMES.div_ceil(block);
+        let last_frame = (blocks * block - 1) as f64;
+        let mut moved = 0;
+        for p in &params {
+            let label = param_label(p);
+            let mut changed = false;
+            // The ramp's blocks, then one putting the default back for the next sweep.
+            for k in 0..=blocks {
+                changes.clear();
+                if k < blocks {
+                    let start = k * block;
+                    changes.add_point(p.id, 0, start as f64 / last_frame);
+                    changes.add_point(
+                        p.id,
+                        block as i32 - 1,
+                        (start + block - 1) as f64 / last_frame,
+                    );
+                } else {
+                    changes.add_point(p.id, 0, p.default_normalized.clamp(0.0, 1.0));
+                }
+                let stats = match instance.process_bound(
+                    &mut bound,
+                    block,
+                    Some((&mut events, &mut changes)),
+                ) {
+                    Ok(stats) => stats,
+                    Err(e) => {
+                        instance.deactivate();
+                        return Ok(Outcome::Fail(format!(
+                            "{label}: block {k} of the sweep: {e}"
+                        )));
+                    }
+                };
+                if stats.nothing_to_do() || k == blocks {
+                    continue;
+                }
+                if let Some(c) = bound
+                    .outputs()
+                    .iter()
+                    .position(|b| !analyze_block(b).is_finite())
+                {
+                    instance.deactivate();
+                    return Ok(Outcome::Fail(format!(
+                        "{label}: non-finite output on channel {c} in block {k} of the sweep"
+                    )));
+                }
+                if k == 0 {
+                    for (keep, out) in first.iter_mut().zip(bound.outputs()) {
+                        keep.copy_from_slice(out);
+                    }
+                } else if !changed {
+                    changed = first
+                        .iter()
+                        .zip(bound.outputs())
+                        .any(|(a, b)| a.iter().zip(b.iter()).any(|(a, b)| (a - b).abs() > 1e-6));
+                }
+            }
+            moved += usize::from(changed);
+        }
+        instance.deactivate();
+        Ok(if moved == 0 {
+            Outcome::Warn(format!(
+                "automating none of the {} automatable parameters changed the output",
+                params.len()
+            ))
+        } else {
+            Outcome::Pass
+        })
+    }
+
+    fn check_edits(&mut self, cid: [u8; 16]) -> Result<Outcome, HostError> {
+        let edits = Arc::new(AtomicU64::new(0));
+        let seen = edits.clone();
+        // Declared before the instance, so it outlives the controller's use of it.
+        let handler = ComponentHandler::new(move |event| {
+            if let HandlerEvent::PerformEdit { .. } = event {
+                seen.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+        let mut instance = self.create_plugin(cid)?;
+        let Some(controller) = instance.controller() else {
+            return Ok(Outcome::Skip("no edit controller on the component".into()));
+        };
+        controller.set_component_handler(&handler)?;
+        let outputs = instance.main_bus_channels(BUS_DIR_OUTPUT);
+        if outputs == 0 {
+            return Ok(Outcome::Skip("no audio output bus".into()));
+        }
+        let inputs = instance.main_bus_channels(BUS_DIR_INPUT);
+        let (rate, block) = (48_000.0, 512);
+        instance.activate(
+            &ProcessSetupBuilder::new()
+                .sample_rate(rate)
+                .max_block(block as i32)
+                .build()?,
+        )?;
+        let mut bound = BoundProcessData::<f32>::new(inputs, outputs, block);
+        let blocks = (EDIT_WATCH.as_secs_f64() * rate / block as f64).ceil() as u32;
+        let start = Instant::now();
+        // Paced like a device callback, so edits from the plugin's own threads count too.
+        for k in 1..=blocks {
+            if let Err(e) = instance.process_bound(&mut bound, block, None) {
+                instance.deactivate();
+                return Ok(Outcome::Fail(format!("block {k}: {e}")));
+            }
+            let due = start + Duration::from_secs_f64(f64::from(k) * block as f64 / rate);
+            if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+        }
+        instance.deactivate();
+        let seconds = f64::from(blocks) * block as f64 / rate;
+        let edits = edits.load(Ordering::Relaxed);
+        let per_second = edits as f64 / seconds;
+        Ok(if per_second > MAX_EDITS_PER_SECOND {
+            Outcome::Warn(format!(
+                "{edits} performEdit calls in {:.0} ms of processing with no user \
+                 interaction ({per_second:.0}/s, over {MAX_EDITS_PER_SECOND}/s)",
+                seconds * 1000.0
+            ))
+        } else {
+            Outcome::Pass
+        })
+    }
+
+    #[cfg(feature = "leak-audit")]
+    fn check_teardown(&mut self, cid: [u8; 16]) -> Result<Outcome, HostError> {
+        drop(self.create_plugin(cid)?);
+        let report = self.leak_report();
+        Ok(if report.is_clean() {
+            Outcome::Pass
+        } else {
+            Outcome::Fail(report.to_string().trim_end().replace('\n', "; "))
+        })
+    }
+
+    #[cfg(not(feature = "leak-audit"))]
+    fn check_teardown(&mut self, _cid: [u8; 16]) -> Result<Outcome, HostError> {
+        Ok(Outcome::Skip(
+            "needs openvst3-host's leak-audit feature".into(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_cases_parse() {
+        let case: CheckCase = "process@96000/128".parse().unwrap();
+        assert_eq!(
+            case.process,
+            Some(ProcessCase {
+                sample_rate: 96_000.0,
+                block_size: 128
+            })
+        );
+        assert_eq!(case.to_string(), "process@96000/128");
+        assert_eq!("PARAMS".parse::<Check>(), Ok(Check::Params));
+        for bad in ["process", "state@48000/64", "process@fast/64", "lint"] {
+            assert!(bad.parse::<CheckCase>().is_err(), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! Deadline for plugin calls that may never return.
+//!
+//! A hung vtable call cannot be interrupted, so the call runs on a worker thread and
+//! the caller gets [`HostError::TimedOut`] once the deadline passes. Whatever the
+//! closure owns (typically a [`PluginInstance`](crate::PluginInstance)) stays with the
+//! worker and is dropped only if the call eventually returns; the module that produced
+//! it must therefore be kept loaded.
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::HostError;
+
+/// Run `f` on a worker thread and wait at most `timeout` for it to finish.
+/// `call` names the plugin method for the error message.
+pub fn call_with_timeout<T, F>(call: &'static str, timeout: Duration, f: F) -> Result<T, HostError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let (tx, rx) = mpsc::sync_channel(1);
+    std::thread::Builder::new()
+        .name(format!("openvst3-watchdog:{call}"))
+        .spawn(move || {
+            let _ = tx.send(f());
+        })
+        .map_err(|_| HostError::Alloc)?;
+    match rx.recv_timeout(timeout) {
+        Ok(v) => Ok(v),
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(HostError::TimedOut {
+            method: call,
+            after: timeout,
+        }),
+        // The worker panicked before sending.
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err(HostError::Panicked(call)),
+    }
+}
@@ -0,0 +1,121 @@
+use openvst3_abi::{
+    speaker_arr, BUS_DIR_INPUT, BUS_DIR_OUTPUT, MEDIA_TYPE_AUDIO, MEDIA_TYPE_EVENT,
+};
+
+/// One bus as `getBusInfo` reports it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bus {
+    pub name: String,
+    /// Audio channels, or MIDI channels of an event bus.
+    pub channels: i32,
+    /// Main buses are active by default, aux buses until the host activates them.
+    pub default_active: bool,
+}
+
+impl Bus {
+    /// What `getBusArrangement` reports before the host sets an arrangement: mono for
+    /// one channel, otherwise the first `channels` speakers.
+    pub(crate) fn default_arrangement(&self) -> u64 {
+        match self.channels {
+            ..=0 => speaker_arr::EMPTY,
+            1 => speaker_arr::MONO,
+            n if n >= 64 => u64::MAX,
+            n => (1u64 << n) - 1,
+        }
+    }
+}
+
+/// The buses of a processor, built up one bus at a time. The first bus of each
+/// media type and direction is the main bus; later ones are aux buses.
+///
+/// ```
+/// # use openvst3_plugin::BusConfig;
+/// let sidechained = BusConfig::effect(2).audio_input("Sidechain", 2);
+/// assert_eq!(sidechained.audio_inputs().len(), 2);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BusConfig {
+    audio_inputs: Vec<Bus>,
+    audio_outputs: Vec<Bus>,
+    event_inputs: Vec<Bus>,
+}
+
+impl BusConfig {
+    /// No buses at all.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Main audio input and output with `channels` each.
+    pub fn effect(channels: i32) -> Self {
+        Self::new()
+            .audio_input("Main In", channels)
+            .audio_output("Main Out", channels)
+    }
+
+    /// A 16-channel event input and a main audio output with `channels`.
+    pub fn instrument(channels: i32) -> Self {
+        Self::new()
+            .event_input("Event In", 16)
+            .audio_output("Main Out", channels)
+    }
+
+    pub fn audio_input(mut self, name: &str, channels: i32) -> Self {
+        push(&mut self.audio_inputs, name, channels);
+        self
+    }
+
+    pub fn audio_output(mut self, name: &str, channels: i32) -> Self {
+        push(&mut self.audio_outputs, name, channels);
+        self
+    }
+
+    pub fn event_input(mut self, name: &str, channels: i32) -> Self {
+        push(&mut self.event_inputs, name, channels);
+        self
+    }
+
+    pub fn audio_inputs(&self) -> &[Bus] {
+        &self.audio_inputs
+    }
+
+    pub fn audio_outputs(&self) -> &[Bus] {
+        &self.audio_outputs
+    }
+
+    pub fn event_inputs(&self) -> &[Bus] {
+        &self.event_inputs
+    }
+
+    /// The buses `getBusCount`/`getBusInfo` report for a media type and direction.
+    pub(crate) fn list(&self, media_type: i32, direction: i32) -> &[Bus] {
+        match (media_type, direction) {
+            (MEDIA_TYPE_AUDIO, BUS_DIR_INPUT) => &self.audio_inputs,
+            (MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT) => &self.audio_outputs,
+            (MEDIA_TYPE_EVENT, BUS_DIR_INPUT) => &self.event_inputs,
+            _ => &[],
+        }
+    }
+
+    /// Whether `setBusArrangements` can be accepted: one arrangement per audio bus,
+    /// each with the bus's channel count.
+    pub(crate) fn accepts(&self, inputs: &[u64], outputs: &[u64]) -> bool {
+        let fits = |buses: &[Bus], arrangements: &[u64]| {
+            buses.len() == arrangements.len()
+                && buses
+                    .iter()
+                    .zip(arrangements)
+                    .all(|(b, a)| a.count_ones() as i32 == b.channels)
+        };
+        fits(&self.audio_inputs, inputs) && fits(&self.audio_outputs, outputs)
+    }
+}
+
+fn push(buses: &mut Vec<Bus>, name: &str, channels: i32) {
+    let default_active = buses.is_empty();
+    buses.push(Bus {
+        name: name.into(),
+        channels,
+        default_active,
+    });
+}
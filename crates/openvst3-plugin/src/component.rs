@@ -0,0 +1,545 @@
+use core::ffi::c_void;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use openvst3_abi::{
+    iids, process_consts, tresult, BusInfo, FUnknown, Fuid, IAudioProcessor, IAudioProcessorVTable,
+    IBStream, IComponent, IComponentHandler, IComponentVTable, IParameterChanges, ParamValue,
+    ProcessData32, ProcessData64, ProcessSetup, Tuid, BUS_DIR_INPUT, K_INVALID_ARG,
+    K_NOT_IMPLEMENTED, K_NO_INTERFACE, K_RESULT_FALSE, K_RESULT_OK, MEDIA_TYPE_AUDIO,
+};
+
+use crate::controller::CONTROLLER_VTBL;
+use crate::process::{AudioIo, BusBuffers, Io, ProcessCtx};
+use crate::state::IbStream;
+use crate::{
+    copy_cstr, AudioProcessor, BusConfig, ParamStore, ProcessMode, SampleSize, SetupInfo,
+    StateReader, StateWriter,
+};
+
+/// `BusInfo::flags` bit for a bus the host should treat as active from the start.
+const K_DEFAULT_ACTIVE: u32 = 1 << 0;
+
+/// Interface sub-object: the vtable pointer the host sees, followed by a back-pointer
+/// to the owning instance.
+#[repr(C)]
+struct Slot {
+    vtbl: *const c_void,
+    owner: *const Instance,
+}
+
+/// One `createInstance` result: `IComponent`, `IAudioProcessor` and, with parameters,
+/// `IEditController` over a boxed [`AudioProcessor`].
+pub(crate) struct Instance {
+    component: Slot,
+    processor: Slot,
+    controller: Slot,
+    refs: AtomicU32,
+    buses: BusConfig,
+    pub(crate) params: ParamStore,
+    /// Set by `setComponentHandler`; holds one reference.
+    pub(crate) handler: Mutex<*mut IComponentHandler>,
+    state: Mutex<State>,
+}
+
+struct State {
+    processor: Box<dyn AudioProcessor>,
+    setup: Option<SetupInfo>,
+    active: bool,
+    processing: bool,
+    /// The input and output arrangements of the last accepted `setBusArrangements`.
+    arrangements: Option<(Vec<u64>, Vec<u64>)>,
+}
+
+static COMPONENT_VTBL: IComponentVTable = IComponentVTable {
+    query_interface: i_query_interface,
+    add_ref: i_add_ref,
+    release: i_release,
+    initialize: c_initialize,
+    terminate: c_terminate,
+    get_controller_class_id: c_get_controller_class_id,
+    get_bus_count: c_get_bus_count,
+    get_bus_info: c_get_bus_info,
+    activate_bus: c_activate_bus,
+    set_active: c_set_active,
+    set_state: c_set_state,
+    get_state: c_get_state,
+    set_io_mode: c_set_io_mode,
+};
+
+static PROCESSOR_VTBL: IAudioProcessorVTable = IAudioProcessorVTable {
+    query_interface: i_query_interface,
+    add_ref: i_add_ref,
+    release: i_release,
+    initialize: p_initialize,
+    terminate: p_terminate,
+    set_processing: p_set_processing,
+    setup_processing: p_setup_processing,
+    set_bus_arrangements: p_set_bus_arrangements,
+    process_32f: p_process_32f,
+    process_64f: p_process_64f,
+    get_latency_samples: p_get_latency_samples,
+    get_tail_samples: p_get_tail_samples,
+    can_process_sample_size: p_can_process_sample_size,
+    get_bus_arrangement: p_get_bus_arrangement,
+};
+
+impl Instance {
+    pub(crate) fn create(processor: Box<dyn AudioProcessor>) -> *mut Instance {
+        let buses = processor.buses();
+        let params = ParamStore::new(processor.params());
+        let raw = Box::into_raw(Box::new(Instance {
+            component: Slot {
+                vtbl: &COMPONENT_VTBL as *const _ as *const c_void,
+                owner: core::ptr::null(),
+            },
+            processor: Slot {
+                vtbl: &PROCESSOR_VTBL as *const _ as *const c_void,
+                owner: core::ptr::null(),
+            },
+            controller: Slot {
+                vtbl: &CONTROLLER_VTBL as *const _ as *const c_void,
+                owner: core::ptr::null(),
+            },
+            refs: AtomicU32::new(1),
+            buses,
+            params,
+            handler: Mutex::new(core::ptr::null_mut()),
+            state: Mutex::new(State {
+                processor,
+                setup: None,
+                active: false,
+                processing: false,
+                arrangements: None,
+            }),
+        }));
+        unsafe {
+            (*raw).component.owner = raw;
+            (*raw).processor.owner = raw;
+            (*raw).controller.owner = raw;
+        }
+        raw
+    }
+
+    pub(crate) unsafe fn query(this: *mut Instance, iid: &Tuid, obj: *mut *mut c_void) -> tresult {
+        let slot: *mut Slot =
+            if *iid == iids::ICOMPONENT || *iid == iids::IPLUGIN_BASE || *iid == iids::FUNKNOWN {
+                core::ptr::addr_of_mut!((*this).component)
+            } else if *iid == iids::IAUDIO_PROCESSOR {
+                core::ptr::addr_of_mut!((*this).processor)
+            } else if *iid == iids::IEDIT_CONTROLLER && !(*this).params.params().is_empty() {
+                core::ptr::addr_of_mut!((*this).controller)
+            } else {
+                *obj = core::ptr::null_mut();
+                return K_NO_INTERFACE;
+            };
+        (*this).refs.fetch_add(1, Ordering::SeqCst);
+        *obj = slot as *mut c_void;
+        K_RESULT_OK
+    }
+
+    pub(crate) unsafe fn release(this: *mut Instance) -> u32 {
+        let left = (*this).refs.fetch_sub(1, Ordering::SeqCst) - 1;
+        if left == 0 {
+            drop(Box::from_raw(this));
+        }
+        left
+    }
+
+    /// Swap the stored component handler for `handler`, taking a reference to it
+    /// and releasing the old one.
+    pub(crate) unsafe fn set_handler(&self, handler: *mut IComponentHandler) {
+        if !handler.is_null() {
+            (*(handler as *mut FUnknown)).add_ref();
+        }
+        let old = core::mem::replace(&mut *lock(&self.handler), handler);
+        if !old.is_null() {
+            FUnknown::release(old as *mut FUnknown);
+        }
+    }
+
+    /// A panicking `process` poisons the lock; the processor is still usable for
+    /// the host to deactivate and release it.
+    fn state(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        unsafe { self.set_handler(core::ptr::null_mut()) };
+    }
+}
+
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub(crate) unsafe fn owner<'a, T>(this: *mut T) -> &'a Instance {
+    &*(*(this as *const Slot)).owner
+}
+
+pub(crate) unsafe fn owner_ptr<T>(this: *mut T) -> *mut Instance {
+    (*(this as *const Slot)).owner as *mut Instance
+}
+
+// ----- FUnknown (shared by all slots) ----------------------------------------
+
+pub(crate) unsafe extern "C" fn i_query_interface(
+    this_: *mut FUnknown,
+    iid: *const Fuid,
+    obj: *mut *mut c_void,
+) -> tresult {
+    if iid.is_null() || obj.is_null() {
+        return K_INVALID_ARG;
+    }
+    Instance::query(owner_ptr(this_), &*iid, obj)
+}
+
+pub(crate) unsafe extern "C" fn i_add_ref(this_: *mut FUnknown) -> u32 {
+    owner(this_).refs.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+pub(crate) unsafe extern "C" fn i_release(this_: *mut FUnknown) -> u32 {
+    Instance::release(owner_ptr(this_))
+}
+
+// ----- IComponent -------------------------------------------------------------
+
+unsafe extern "C" fn c_initialize(_this: *mut IComponent, _context: *mut FUnknown) -> tresult {
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn c_terminate(_this: *mut IComponent) -> tresult {
+    K_RESULT_OK
+}
+
+/// No separate controller class yet.
+unsafe extern "C" fn c_get_controller_class_id(_this: *mut IComponent, cid: *mut Tuid) -> tresult {
+    if cid.is_null() {
+        return K_INVALID_ARG;
+    }
+    *cid = Tuid([0; 16]);
+    K_RESULT_FALSE
+}
+
+unsafe extern "C" fn c_get_bus_count(
+    this_: *mut IComponent,
+    media_type: i32,
+    direction: i32,
+) -> i32 {
+    owner(this_).buses.list(media_type, direction).len() as i32
+}
+
+unsafe extern "C" fn c_get_bus_info(
+    this_: *mut IComponent,
+    media_type: i32,
+    direction: i32,
+    index: i32,
+    info: *mut BusInfo,
+) -> tresult {
+    if info.is_null() {
+        return K_INVALID_ARG;
+    }
+    let buses = owner(this_).buses.list(media_type, direction);
+    let Some(bus) = usize::try_from(index).ok().and_then(|i| buses.get(i)) else {
+        return K_INVALID_ARG;
+    };
+    let info = &mut *info;
+    info.media_type = media_type;
+    info.direction = direction;
+    info.channel_count = bus.channels;
+    copy_cstr(&mut info.name, &bus.name);
+    info.bus_type = if index == 0 { 0 } else { 1 };
+    info.flags = if bus.default_active {
+        K_DEFAULT_ACTIVE
+    } else {
+        0
+    };
+    K_RESULT_OK
+}
+
+/// Buses are always processed; activation is only validated.
+unsafe extern "C" fn c_activate_bus(
+    this_: *mut IComponent,
+    media_type: i32,
+    direction: i32,
+    index: i32,
+    _state: u8,
+) -> tresult {
+    let buses = owner(this_).buses.list(media_type, direction);
+    match usize::try_from(index) {
+        Ok(i) if i < buses.len() => K_RESULT_OK,
+        _ => K_INVALID_ARG,
+    }
+}
+
+unsafe extern "C" fn c_set_active(this_: *mut IComponent, state: u8) -> tresult {
+    let mut s = owner(this_).state();
+    if state != 0 {
+        if s.setup.is_none() {
+            return K_RESULT_FALSE;
+        }
+        if !s.active {
+            s.processor.reset();
+        }
+        s.active = true;
+    } else {
+        s.active = false;
+        s.processing = false;
+    }
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn c_set_state(this_: *mut IComponent, state: *mut IBStream) -> tresult {
+    if state.is_null() {
+        return K_INVALID_ARG;
+    }
+    let Ok(reader) = StateReader::read_from(&mut IbStream(state)) else {
+        return K_RESULT_FALSE;
+    };
+    let inst = owner(this_);
+    match inst.state().processor.load_state(&inst.params, &reader) {
+        Ok(()) => K_RESULT_OK,
+        Err(_) => K_RESULT_FALSE,
+    }
+}
+
+// Every mode processes the same way, so there is nothing to switch.
+unsafe extern "C" fn c_set_io_mode(_this: *mut IComponent, _mode: i32) -> tresult {
+    K_NOT_IMPLEMENTED
+}
+
+unsafe extern "C" fn c_get_state(this_: *mut IComponent, state: *mut IBStream) -> tresult {
+    if state.is_null() {
+        return K_INVALID_ARG;
+    }
+    let inst = owner(this_);
+    let writer = {
+        let s = inst.state();
+        let mut w = StateWriter::new(s.processor.state_version());
+        s.processor.save_state(&inst.params, &mut w);
+        w
+    };
+    match writer.finish(&mut IbStream(state)) {
+        Ok(()) => K_RESULT_OK,
+        Err(_) => K_RESULT_FALSE,
+    }
+}
+
+// ----- IAudioProcessor --------------------------------------------------------
+
+unsafe extern "C" fn p_initialize(_this: *mut IAudioProcessor, _context: *mut FUnknown) -> tresult {
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn p_terminate(_this: *mut IAudioProcessor) -> tresult {
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn p_set_processing(this_: *mut IAudioProcessor, state: i32) -> tresult {
+    let mut s = owner(this_).state();
+    if state != 0 && !s.active {
+        return K_RESULT_FALSE;
+    }
+    s.processing = state != 0;
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn p_setup_processing(
+    this_: *mut IAudioProcessor,
+    setup: *const ProcessSetup,
+) -> tresult {
+    if setup.is_null() {
+        return K_INVALID_ARG;
+    }
+    let setup = &*setup;
+    let sample_size = match setup.symbolic_sample_size {
+        process_consts::SYMBOLIC_SAMPLE_32 => SampleSize::F32,
+        process_consts::SYMBOLIC_SAMPLE_64 => SampleSize::F64,
+        _ => return K_INVALID_ARG,
+    };
+    let mode = match setup.process_mode {
+        process_consts::PROCESS_MODE_PREFETCH => ProcessMode::Prefetch,
+        process_consts::PROCESS_MODE_OFFLINE => ProcessMode::Offline,
+        _ => ProcessMode::Realtime,
+    };
+    if setup.sample_rate <= 0.0 || setup.max_samples_per_block < 0 {
+        return K_INVALID_ARG;
+    }
+    let info = SetupInfo {
+        sample_rate: setup.sample_rate,
+        max_block: setup.max_samples_per_block as usize,
+        sample_size,
+        mode,
+    };
+    let mut s = owner(this_).state();
+    if s.active {
+        return K_RESULT_FALSE;
+    }
+    match s.processor.setup(info) {
+        Ok(()) => {
+            s.setup = Some(info);
+            K_RESULT_OK
+        }
+        Err(e) => e.tresult(),
+    }
+}
+
+unsafe extern "C" fn p_set_bus_arrangements(
+    this_: *mut IAudioProcessor,
+    inputs: *const u64,
+    num_ins: i32,
+    outputs: *const u64,
+    num_outs: i32,
+) -> tresult {
+    let arrangements = |ptr: *const u64, n: i32| match n {
+        0 => Some(&[][..]),
+        n if n > 0 && !ptr.is_null() => Some(core::slice::from_raw_parts(ptr, n as usize)),
+        _ => None,
+    };
+    let (Some(ins), Some(outs)) = (
+        arrangements(inputs, num_ins),
+        arrangements(outputs, num_outs),
+    ) else {
+        return K_INVALID_ARG;
+    };
+    let inst = owner(this_);
+    if inst.state().active {
+        return K_RESULT_FALSE;
+    }
+    if inst.buses.accepts(ins, outs) {
+        inst.state().arrangements = Some((ins.to_vec(), outs.to_vec()));
+        K_RESULT_OK
+    } else {
+        K_RESULT_FALSE
+    }
+}
+
+unsafe extern "C" fn p_get_bus_arrangement(
+    this_: *mut IAudioProcessor,
+    direction: i32,
+    index: i32,
+    arr: *mut u64,
+) -> tresult {
+    if arr.is_null() {
+        return K_INVALID_ARG;
+    }
+    let inst = owner(this_);
+    let buses = inst.buses.list(MEDIA_TYPE_AUDIO, direction);
+    let Some((i, bus)) = usize::try_from(index)
+        .ok()
+        .and_then(|i| Some((i, buses.get(i)?)))
+    else {
+        return K_INVALID_ARG;
+    };
+    let set = match &inst.state().arrangements {
+        Some((ins, _)) if direction == BUS_DIR_INPUT => ins.get(i).copied(),
+        Some((_, outs)) => outs.get(i).copied(),
+        None => None,
+    };
+    *arr = set.unwrap_or_else(|| bus.default_arrangement());
+    K_RESULT_OK
+}
+
+/// Store the last point of every queue in `changes`; later queues for the same id
+/// win.
+unsafe fn apply_changes(params: &ParamStore, changes: *mut IParameterChanges) {
+    if changes.is_null() {
+        return;
+    }
+    for i in 0..(*changes).get_parameter_count() {
+        let queue = (*changes).get_parameter_data(i);
+        if queue.is_null() {
+            continue;
+        }
+        let last = (*queue).get_point_count() - 1;
+        let (mut offset, mut value): (i32, ParamValue) = (0, 0.0);
+        if last >= 0 && (*queue).get_point(last, &mut offset, &mut value) == K_RESULT_OK {
+            params.set_normalized((*queue).get_parameter_id(), value);
+        }
+    }
+}
+
+/// Check the block against the processing state, apply its parameter changes, then
+/// hand it to the processor.
+unsafe fn process<'a>(
+    this_: *mut IAudioProcessor,
+    sample_size: SampleSize,
+    num_samples: i32,
+    changes: *mut c_void,
+    audio: impl FnOnce(usize) -> AudioIo<'a>,
+) -> tresult {
+    let inst = owner(this_);
+    let mut s = inst.state();
+    let Some(setup) = s.setup else {
+        return K_RESULT_FALSE;
+    };
+    if !s.processing || setup.sample_size != sample_size {
+        return K_RESULT_FALSE;
+    }
+    let frames = match usize::try_from(num_samples) {
+        Ok(n) if n <= setup.max_block => n,
+        _ => return K_INVALID_ARG,
+    };
+    apply_changes(&inst.params, changes as *mut IParameterChanges);
+    s.processor
+        .process(ProcessCtx::new(audio(frames), &inst.params));
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn p_process_32f(
+    this_: *mut IAudioProcessor,
+    data: *mut ProcessData32,
+) -> tresult {
+    if data.is_null() {
+        return K_INVALID_ARG;
+    }
+    let d = &*data;
+    let changes = d.input_parameter_changes;
+    process(this_, SampleSize::F32, d.num_samples, changes, |frames| {
+        AudioIo::F32(Io::new(
+            d.inputs as *const BusBuffers<f32>,
+            d.num_inputs,
+            d.outputs as *const BusBuffers<f32>,
+            d.num_outputs,
+            frames,
+        ))
+    })
+}
+
+unsafe extern "C" fn p_process_64f(
+    this_: *mut IAudioProcessor,
+    data: *mut ProcessData64,
+) -> tresult {
+    if data.is_null() {
+        return K_INVALID_ARG;
+    }
+    let d = &*data;
+    let changes = d.input_parameter_changes;
+    process(this_, SampleSize::F64, d.num_samples, changes, |frames| {
+        AudioIo::F64(Io::new(
+            d.inputs as *const BusBuffers<f64>,
+            d.num_inputs,
+            d.outputs as *const BusBuffers<f64>,
+            d.num_outputs,
+            frames,
+        ))
+    })
+}
+
+unsafe extern "C" fn p_get_latency_samples(this_: *mut IAudioProcessor) -> u32 {
+    owner(this_).state().processor.latency()
+}
+
+unsafe extern "C" fn p_get_tail_samples(this_: *mut IAudioProcessor) -> u32 {
+    owner(this_).state().processor.tail()
+}
+
+/// Every [`AudioProcessor`] gets both sample sizes through [`ProcessCtx`].
+unsafe extern "C" fn p_can_process_sample_size(_this: *mut IAudioProcessor, size: i32) -> tresult {
+    match size {
+        process_consts::SYMBOLIC_SAMPLE_32 | process_consts::SYMBOLIC_SAMPLE_64 => K_RESULT_OK,
+        _ => K_RESULT_FALSE,
+    }
+}
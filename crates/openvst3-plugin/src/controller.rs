@@ -0,0 +1,191 @@
+use core::ffi::c_void;
+
+use openvst3_abi::{
+    tchar, tresult, FUnknown, IBStream, IComponentHandler, IEditController, IEditControllerVTable,
+    ParamID, ParamValue, ParameterInfo, String128, K_INVALID_ARG, K_RESULT_FALSE, K_RESULT_OK,
+};
+
+use crate::component::{i_add_ref, i_query_interface, i_release, owner};
+use crate::state::IbStream;
+use crate::{copy_str16, read_str16, StateReader};
+
+/// `ParameterInfo::unit_id` of the root unit.
+const K_ROOT_UNIT_ID: i32 = 0;
+
+pub(crate) static CONTROLLER_VTBL: IEditControllerVTable = IEditControllerVTable {
+    query_interface: i_query_interface,
+    add_ref: i_add_ref,
+    release: i_release,
+    initialize: e_initialize,
+    terminate: e_terminate,
+    set_component_state: e_set_component_state,
+    set_state: e_set_state,
+    get_state: e_get_state,
+    get_parameter_count: e_get_parameter_count,
+    get_parameter_info: e_get_parameter_info,
+    get_param_string_by_value: e_get_param_string_by_value,
+    get_param_value_by_string: e_get_param_value_by_string,
+    normalized_param_to_plain: e_normalized_param_to_plain,
+    plain_param_to_normalized: e_plain_param_to_normalized,
+    get_param_normalized: e_get_param_normalized,
+    set_param_normalized: e_set_param_normalized,
+    set_component_handler: e_set_component_handler,
+    create_view: e_create_view,
+};
+
+unsafe extern "C" fn e_initialize(_this: *mut IEditController, _context: *mut FUnknown) -> tresult {
+    K_RESULT_OK
+}
+
+/// Drops the component handler, as hosts expect after `terminate`.
+unsafe extern "C" fn e_terminate(this_: *mut IEditController) -> tresult {
+    owner(this_).set_handler(core::ptr::null_mut());
+    K_RESULT_OK
+}
+
+/// Controller and processor share one value store, which `setState` on the component
+/// already updated; this covers hosts that hand the controller a state on its own.
+unsafe extern "C" fn e_set_component_state(
+    this_: *mut IEditController,
+    state: *mut IBStream,
+) -> tresult {
+    if state.is_null() {
+        return K_INVALID_ARG;
+    }
+    match StateReader::read_from(&mut IbStream(state)) {
+        Ok(reader) => {
+            owner(this_).params.load(&reader);
+            K_RESULT_OK
+        }
+        Err(_) => K_RESULT_FALSE,
+    }
+}
+
+// The controller has no state of its own.
+
+unsafe extern "C" fn e_set_state(_this: *mut IEditController, _state: *mut IBStream) -> tresult {
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn e_get_state(_this: *mut IEditController, _state: *mut IBStream) -> tresult {
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn e_get_parameter_count(this_: *mut IEditController) -> i32 {
+    owner(this_).params.params().defs().len() as i32
+}
+
+unsafe extern "C" fn e_get_parameter_info(
+    this_: *mut IEditController,
+    index: i32,
+    info: *mut ParameterInfo,
+) -> tresult {
+    if info.is_null() {
+        return K_INVALID_ARG;
+    }
+    let Some(def) = owner(this_).params.def_at(index) else {
+        return K_INVALID_ARG;
+    };
+    let info = &mut *info;
+    info.id = def.id;
+    copy_str16(&mut info.title, &def.name);
+    copy_str16(&mut info.short_title, &def.short_name);
+    copy_str16(&mut info.units, &def.unit);
+    info.step_count = def.step_count();
+    info.default_normalized_value = def.default_normalized();
+    info.unit_id = K_ROOT_UNIT_ID;
+    info.flags = def.flags.bits();
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn e_get_param_string_by_value(
+    this_: *mut IEditController,
+    id: ParamID,
+    value_normalized: ParamValue,
+    string: *mut String128,
+) -> tresult {
+    if string.is_null() {
+        return K_INVALID_ARG;
+    }
+    let Some(def) = owner(this_).params.params().get(id) else {
+        return K_INVALID_ARG;
+    };
+    copy_str16(&mut *string, &def.format(value_normalized));
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn e_get_param_value_by_string(
+    this_: *mut IEditController,
+    id: ParamID,
+    string: *const tchar,
+    value_normalized: *mut ParamValue,
+) -> tresult {
+    if string.is_null() || value_normalized.is_null() {
+        return K_INVALID_ARG;
+    }
+    let Some(def) = owner(this_).params.params().get(id) else {
+        return K_INVALID_ARG;
+    };
+    match def.parse(&read_str16(string)) {
+        Some(v) => {
+            *value_normalized = v;
+            K_RESULT_OK
+        }
+        None => K_RESULT_FALSE,
+    }
+}
+
+unsafe extern "C" fn e_normalized_param_to_plain(
+    this_: *mut IEditController,
+    id: ParamID,
+    value_normalized: ParamValue,
+) -> ParamValue {
+    owner(this_)
+        .params
+        .params()
+        .get(id)
+        .map_or(0.0, |d| d.to_plain(value_normalized))
+}
+
+unsafe extern "C" fn e_plain_param_to_normalized(
+    this_: *mut IEditController,
+    id: ParamID,
+    plain_value: ParamValue,
+) -> ParamValue {
+    owner(this_)
+        .params
+        .params()
+        .get(id)
+        .map_or(0.0, |d| d.to_normalized(plain_value))
+}
+
+unsafe extern "C" fn e_get_param_normalized(
+    this_: *mut IEditController,
+    id: ParamID,
+) -> ParamValue {
+    owner(this_).params.normalized(id).unwrap_or(0.0)
+}
+
+unsafe extern "C" fn e_set_param_normalized(
+    this_: *mut IEditController,
+    id: ParamID,
+    value: ParamValue,
+) -> tresult {
+    if owner(this_).params.set_normalized(id, value) {
+        K_RESULT_OK
+    } else {
+        K_INVALID_ARG
+    }
+}
+
+unsafe extern "C" fn e_set_component_handler(
+    this_: *mut IEditController,
+    handler: *mut IComponentHandler,
+) -> tresult {
+    owner(this_).set_handler(handler);
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn e_create_view(_this: *mut IEditController, _name: *const i8) -> *mut c_void {
+    core::ptr::null_mut()
+}
@@ -0,0 +1,263 @@
+use core::ffi::c_void;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use openvst3_abi::{
+    classinfo_consts, iids, tresult, FUnknown, FactoryFlags, Fuid, IPluginFactory, IPluginFactory2,
+    IPluginFactory2VTable, IPluginFactoryVTable, PClassInfo, PClassInfo2, PFactoryInfo, Tuid,
+    K_INVALID_ARG, K_NO_INTERFACE, K_RESULT_OK,
+};
+
+use crate::component::Instance;
+use crate::{copy_cstr, AudioProcessor};
+
+/// One instantiable class of a [`Factory`].
+#[derive(Clone)]
+pub struct PluginClass {
+    pub cid: [u8; 16],
+    pub name: String,
+    /// `PClassInfo::category`; audio processors use the default.
+    pub category: String,
+    /// `|`-separated, e.g. `"Fx|Dynamics"`.
+    pub sub_categories: String,
+    pub version: String,
+    create: fn() -> Box<dyn AudioProcessor>,
+}
+
+impl PluginClass {
+    /// A class whose instances start from `P::default()`.
+    pub fn new<P: AudioProcessor + Default + 'static>(cid: [u8; 16], name: &str) -> Self {
+        Self::from_fn(cid, name, || Box::new(P::default()))
+    }
+
+    pub fn from_fn(cid: [u8; 16], name: &str, create: fn() -> Box<dyn AudioProcessor>) -> Self {
+        Self {
+            cid,
+            name: name.into(),
+            category: classinfo_consts::K_VST_AUDIO_EFFECT_CLASS.into(),
+            sub_categories: "Fx".into(),
+            version: "1.0.0".into(),
+            create,
+        }
+    }
+
+    pub fn sub_categories(mut self, sub_categories: &str) -> Self {
+        self.sub_categories = sub_categories.into();
+        self
+    }
+
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = version.into();
+        self
+    }
+}
+
+/// The classes a module exports, served as `IPluginFactory2`.
+///
+/// ```no_run
+/// # use openvst3_plugin::{Factory, PluginClass};
+/// # #[derive(Default)] struct Gain;
+/// # impl openvst3_plugin::AudioProcessor for Gain {
+/// #     fn process(&mut self, _: openvst3_plugin::ProcessCtx<'_>) {}
+/// # }
+/// #[no_mangle]
+/// pub extern "C" fn GetPluginFactory() -> *mut openvst3_abi::IPluginFactory {
+///     Factory::new("Example Audio")
+///         .class(PluginClass::new::<Gain>(*b"ExampleGain\0\0\0\0\0", "Gain"))
+///         .into_raw()
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Factory {
+    vendor: String,
+    url: String,
+    email: String,
+    classes: Vec<PluginClass>,
+}
+
+impl Factory {
+    pub fn new(vendor: &str) -> Self {
+        Self {
+            vendor: vendor.into(),
+            url: String::new(),
+            email: String::new(),
+            classes: Vec::new(),
+        }
+    }
+
+    pub fn url(mut self, url: &str) -> Self {
+        self.url = url.into();
+        self
+    }
+
+    pub fn email(mut self, email: &str) -> Self {
+        self.email = email.into();
+        self
+    }
+
+    pub fn class(mut self, class: PluginClass) -> Self {
+        self.classes.push(class);
+        self
+    }
+
+    /// The factory as `GetPluginFactory` returns it: one reference, owned by the
+    /// caller. The last `release` frees it.
+    pub fn into_raw(self) -> *mut IPluginFactory {
+        Box::into_raw(Box::new(RawFactory {
+            vtbl: &FACTORY_VTBL,
+            refs: AtomicU32::new(1),
+            factory: self,
+        })) as *mut IPluginFactory
+    }
+}
+
+#[repr(C)]
+struct RawFactory {
+    vtbl: *const IPluginFactory2VTable,
+    refs: AtomicU32,
+    factory: Factory,
+}
+
+static FACTORY_VTBL: IPluginFactory2VTable = IPluginFactory2VTable {
+    base: IPluginFactoryVTable {
+        query_interface: f_query_interface,
+        add_ref: f_add_ref,
+        release: f_release,
+        get_factory_info: f_get_factory_info,
+        count_classes: f_count_classes,
+        get_class_info: f_get_class_info,
+        create_instance: f_create_instance,
+    },
+    get_class_info2: f_get_class_info2,
+};
+
+unsafe fn raw<'a, T>(this: *mut T) -> &'a RawFactory {
+    &*(this as *const RawFactory)
+}
+
+unsafe fn class_at<'a, T>(this: *mut T, index: i32) -> Option<&'a PluginClass> {
+    usize::try_from(index)
+        .ok()
+        .and_then(|i| raw(this).factory.classes.get(i))
+}
+
+unsafe extern "C" fn f_query_interface(
+    this_: *mut FUnknown,
+    iid: *const Fuid,
+    obj: *mut *mut c_void,
+) -> tresult {
+    if iid.is_null() || obj.is_null() {
+        return K_INVALID_ARG;
+    }
+    let iid = &*iid;
+    if *iid == iids::IPLUGIN_FACTORY2 || *iid == iids::IPLUGIN_FACTORY || *iid == iids::FUNKNOWN {
+        f_add_ref(this_);
+        *obj = this_ as *mut c_void;
+        return K_RESULT_OK;
+    }
+    *obj = core::ptr::null_mut();
+    K_NO_INTERFACE
+}
+
+unsafe extern "C" fn f_add_ref(this_: *mut FUnknown) -> u32 {
+    raw(this_).refs.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+unsafe extern "C" fn f_release(this_: *mut FUnknown) -> u32 {
+    let left = raw(this_).refs.fetch_sub(1, Ordering::SeqCst) - 1;
+    if left == 0 {
+        drop(Box::from_raw(this_ as *mut RawFactory));
+    }
+    left
+}
+
+unsafe extern "C" fn f_get_factory_info(
+    this_: *mut IPluginFactory,
+    info: *mut PFactoryInfo,
+) -> tresult {
+    if info.is_null() {
+        return K_INVALID_ARG;
+    }
+    let f = &raw(this_).factory;
+    let info = &mut *info;
+    copy_cstr(&mut info.vendor, &f.vendor);
+    copy_cstr(&mut info.url, &f.url);
+    copy_cstr(&mut info.email, &f.email);
+    info.flags = FactoryFlags::UNICODE.bits();
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn f_count_classes(this_: *mut IPluginFactory) -> i32 {
+    raw(this_).factory.classes.len() as i32
+}
+
+unsafe extern "C" fn f_get_class_info(
+    this_: *mut IPluginFactory,
+    index: i32,
+    info: *mut PClassInfo,
+) -> tresult {
+    if info.is_null() {
+        return K_INVALID_ARG;
+    }
+    let Some(class) = class_at(this_, index) else {
+        return K_INVALID_ARG;
+    };
+    let info = &mut *info;
+    for (d, s) in info.cid.iter_mut().zip(class.cid.iter()) {
+        *d = *s as i8;
+    }
+    info.cardinality = classinfo_consts::K_MANY_INSTANCES;
+    copy_cstr(&mut info.category, &class.category);
+    copy_cstr(&mut info.name, &class.name);
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn f_get_class_info2(
+    this_: *mut IPluginFactory2,
+    index: i32,
+    info: *mut PClassInfo2,
+) -> tresult {
+    if info.is_null() {
+        return K_INVALID_ARG;
+    }
+    let Some(class) = class_at(this_, index) else {
+        return K_INVALID_ARG;
+    };
+    let info = &mut *info;
+    for (d, s) in info.cid.iter_mut().zip(class.cid.iter()) {
+        *d = *s as i8;
+    }
+    info.cardinality = classinfo_consts::K_MANY_INSTANCES;
+    copy_cstr(&mut info.category, &class.category);
+    copy_cstr(&mut info.name, &class.name);
+    info.class_flags = 0;
+    copy_cstr(&mut info.sub_categories, &class.sub_categories);
+    copy_cstr(&mut info.vendor, &raw(this_).factory.vendor);
+    copy_cstr(&mut info.version, &class.version);
+    copy_cstr(&mut info.sdk_version, "VST 3.7.0");
+    K_RESULT_OK
+}
+
+unsafe extern "C" fn f_create_instance(
+    this_: *mut IPluginFactory,
+    cid: *const Tuid,
+    iid: *const Tuid,
+    obj: *mut *mut c_void,
+) -> tresult {
+    if cid.is_null() || iid.is_null() || obj.is_null() {
+        return K_INVALID_ARG;
+    }
+    *obj = core::ptr::null_mut();
+    let Some(class) = raw(this_)
+        .factory
+        .classes
+        .iter()
+        .find(|c| c.cid == (*cid).0)
+    else {
+        return K_NO_INTERFACE;
+    };
+    let inst = Instance::create((class.create)());
+    let tr = Instance::query(inst, &*iid, obj);
+    // Drop the creation reference; on success the caller holds the QI reference.
+    Instance::release(inst);
+    tr
+}
@@ -0,0 +1,228 @@
+#![allow(clippy::missing_safety_doc)]
+//! Write VST3 plugins in safe Rust on top of the openvst3-abi vtables.
+//!
+//! Implement [`AudioProcessor`], register it with a [`Factory`] as a [`PluginClass`]
+//! and return [`Factory::into_raw`] from `GetPluginFactory`. This crate implements
+//! `IPluginFactory2`, `IComponent` and `IAudioProcessor` over the boxed processor:
+//! bus queries, activation and processing state, and 32/64-bit dispatch are handled
+//! here, so a processor only declares its buses and fills its outputs.
+//!
+//! A processor that declares [`Params`] also gets an `IEditController` on the same
+//! object. Its values live in a [`ParamStore`] the processor reads from
+//! [`ProcessCtx::params`].
+//!
+//! Component state is a tagged binary blob ([`StateWriter`]/[`StateReader`]). By
+//! default it holds the declared parameters; processors with more to keep override
+//! [`AudioProcessor::save_state`] and [`AudioProcessor::load_state`].
+//!
+//! A plugin library exports its factory and the platform entry points with
+//! [`export_plugin!`].
+
+mod bus;
+mod component;
+mod controller;
+mod factory;
+mod params;
+mod process;
+mod state;
+
+pub use bus::{Bus, BusConfig};
+pub use factory::{Factory, PluginClass};
+pub use params::{ParamDef, ParamRange, ParamStore, Params};
+pub use process::{AudioIo, Channel, Io, ProcessCtx, Sample};
+pub use state::{StateError, StateReader, StateWriter, PARAMS_TAG};
+
+#[doc(hidden)]
+pub use openvst3_abi as __abi;
+
+use openvst3_abi::{process_consts, tresult, K_INVALID_ARG, K_NOT_IMPLEMENTED, K_RESULT_FALSE};
+
+/// Why a processor refused a call; the host sees [`PluginError::tresult`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum PluginError {
+    #[error("invalid argument")]
+    InvalidArgument,
+    #[error("not supported")]
+    Unsupported,
+    #[error("refused")]
+    Refused,
+}
+
+impl PluginError {
+    pub fn tresult(self) -> tresult {
+        match self {
+            Self::InvalidArgument => K_INVALID_ARG,
+            Self::Unsupported => K_NOT_IMPLEMENTED,
+            Self::Refused => K_RESULT_FALSE,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProcessMode {
+    Realtime,
+    Prefetch,
+    Offline,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SampleSize {
+    F32,
+    F64,
+}
+
+/// The host's `setupProcessing` arguments.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SetupInfo {
+    pub sample_rate: f64,
+    /// No block will be longer than this.
+    pub max_block: usize,
+    /// The sample type of every following [`ProcessCtx`].
+    pub sample_size: SampleSize,
+    pub mode: ProcessMode,
+}
+
+/// A plugin's audio processing. Only [`process`](Self::process) is required: the
+/// default is a stereo effect without latency or tail.
+///
+/// Calls are serialized, so the processor needs no locking of its own, but they may
+/// come from different threads.
+pub trait AudioProcessor: Send {
+    /// The buses the component reports; asked once, when an instance is created.
+    fn buses(&self) -> BusConfig {
+        BusConfig::effect(2)
+    }
+
+    /// The parameters the controller reports; asked once, when an instance is
+    /// created. Without any, the instance has no `IEditController`.
+    fn params(&self) -> Params {
+        Params::new()
+    }
+
+    /// Called from `setupProcessing`, while inactive. Refusing makes the host's
+    /// setup fail.
+    fn setup(&mut self, setup: SetupInfo) -> Result<(), PluginError> {
+        let _ = setup;
+        Ok(())
+    }
+
+    /// Called on activation, after [`setup`](Self::setup); clear delay lines and
+    /// other running state here.
+    fn reset(&mut self) {}
+
+    /// Fill the outputs for one block. Only called while the host has the instance
+    /// active and processing, after the block's parameter changes are applied to
+    /// [`ProcessCtx::params`].
+    fn process(&mut self, ctx: ProcessCtx<'_>);
+
+    /// Latency in samples, reported through `getLatencySamples`.
+    fn latency(&self) -> u32 {
+        0
+    }
+
+    /// Version written into saved state, handed back as [`StateReader::version`].
+    fn state_version(&self) -> u32 {
+        1
+    }
+
+    /// Called from `getState`. The default saves the declared parameters; call
+    /// [`ParamStore::save`] from an override to keep them.
+    fn save_state(&self, params: &ParamStore, state: &mut StateWriter) {
+        params.save(state);
+    }
+
+    /// Called from `setState`. The default restores the declared parameters; an
+    /// error makes the host's `setState` fail.
+    fn load_state(&mut self, params: &ParamStore, state: &StateReader) -> Result<(), StateError> {
+        params.load(state);
+        Ok(())
+    }
+
+    /// Samples of output after the input goes silent; `process_consts::INFINITE_TAIL`
+    /// for a processor that never goes silent on its own.
+    fn tail(&self) -> u32 {
+        process_consts::NO_TAIL
+    }
+}
+
+/// Export `GetPluginFactory` for the [`Factory`] built by `$factory`, plus the
+/// module entry and exit points the platform's hosts look for. Use once, at the root
+/// of a `cdylib`.
+///
+/// ```ignore
+/// openvst3_plugin::export_plugin!(
+///     Factory::new("Example Audio").class(PluginClass::new::<Gain>(GAIN_CID, "Gain"))
+/// );
+/// ```
+#[macro_export]
+macro_rules! export_plugin {
+    ($factory:expr) => {
+        #[no_mangle]
+        pub extern "C" fn GetPluginFactory() -> *mut $crate::__abi::IPluginFactory {
+            $crate::Factory::into_raw($factory)
+        }
+
+        #[cfg(target_os = "linux")]
+        #[no_mangle]
+        pub extern "C" fn ModuleEntry(_handle: *mut ::core::ffi::c_void) -> bool {
+            true
+        }
+
+        #[cfg(target_os = "linux")]
+        #[no_mangle]
+        pub extern "C" fn ModuleExit() -> bool {
+            true
+        }
+
+        #[cfg(target_os = "windows")]
+        #[no_mangle]
+        pub extern "C" fn InitDll() -> bool {
+            true
+        }
+
+        #[cfg(target_os = "windows")]
+        #[no_mangle]
+        pub extern "C" fn ExitDll() -> bool {
+            true
+        }
+
+        #[cfg(target_os = "macos")]
+        #[no_mangle]
+        pub extern "C" fn bundleEntry(_bundle: *mut ::core::ffi::c_void) -> bool {
+            true
+        }
+
+        #[cfg(target_os = "macos")]
+        #[no_mangle]
+        pub extern "C" fn bundleExit() -> bool {
+            true
+        }
+    };
+}
+
+/// Copy `s` into a fixed NUL-terminated `i8` buffer, truncating if needed.
+pub(crate) fn copy_cstr(dst: &mut [i8], s: &str) {
+    dst.fill(0);
+    let max = dst.len().saturating_sub(1);
+    for (d, &b) in dst.iter_mut().zip(s.as_bytes().iter().take(max)) {
+        *d = b as i8;
+    }
+}
+
+/// Copy `s` into a fixed NUL-terminated UTF-16 buffer, truncating if needed.
+pub(crate) fn copy_str16(dst: &mut [u16], s: &str) {
+    dst.fill(0);
+    let max = dst.len().saturating_sub(1);
+    for (d, u) in dst.iter_mut().zip(s.encode_utf16().take(max)) {
+        *d = u;
+    }
+}
+
+/// Read a NUL-terminated UTF-16 string of at most `String128` length.
+///
+/// # Safety
+/// `src` must point to a NUL-terminated string or to 128 readable units.
+pub(crate) unsafe fn read_str16(src: *const u16) -> String {
+    let len = (0..128).take_while(|&i| *src.add(i) != 0).count();
+    String::from_utf16_lossy(core::slice::from_raw_parts(src, len))
+}
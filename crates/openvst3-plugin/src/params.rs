@@ -0,0 +1,236 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use openvst3_abi::{ParamID, ParamValue, ParameterFlags};
+
+/// The values a parameter can take, in plain units.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParamRange {
+    /// Any value between `min` and `max`, mapped linearly.
+    Continuous { min: f64, max: f64 },
+    /// One of the labels; the plain value is the label's index.
+    List(Vec<String>),
+}
+
+/// One parameter as the controller reports it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamDef {
+    pub id: ParamID,
+    pub name: String,
+    pub short_name: String,
+    /// Shown after the value, e.g. `"dB"`; empty for none.
+    pub unit: String,
+    pub range: ParamRange,
+    /// Plain default: a value in the range, or a list index.
+    pub default: f64,
+    pub flags: ParameterFlags,
+    /// Decimals of a continuous parameter's display string.
+    pub precision: usize,
+}
+
+impl ParamDef {
+    /// An automatable continuous parameter.
+    pub fn range(id: ParamID, name: &str, min: f64, max: f64, default: f64) -> Self {
+        Self {
+            id,
+            name: name.into(),
+            short_name: name.into(),
+            unit: String::new(),
+            range: ParamRange::Continuous { min, max },
+            default,
+            flags: ParameterFlags::CAN_AUTOMATE,
+            precision: 2,
+        }
+    }
+
+    /// An automatable list parameter starting at `labels[default]`.
+    pub fn list(id: ParamID, name: &str, labels: &[&str], default: usize) -> Self {
+        Self {
+            id,
+            name: name.into(),
+            short_name: name.into(),
+            unit: String::new(),
+            range: ParamRange::List(labels.iter().map(|&l| l.into()).collect()),
+            default: default as f64,
+            flags: ParameterFlags::CAN_AUTOMATE | ParameterFlags::IS_LIST,
+            precision: 0,
+        }
+    }
+
+    pub fn short_name(mut self, short_name: &str) -> Self {
+        self.short_name = short_name.into();
+        self
+    }
+
+    pub fn unit(mut self, unit: &str) -> Self {
+        self.unit = unit.into();
+        self
+    }
+
+    pub fn flags(mut self, flags: ParameterFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// `ParameterInfo::step_count`: 0 for continuous, labels - 1 for lists.
+    pub fn step_count(&self) -> i32 {
+        match &self.range {
+            ParamRange::Continuous { .. } => 0,
+            ParamRange::List(labels) => labels.len().saturating_sub(1) as i32,
+        }
+    }
+
+    pub fn default_normalized(&self) -> ParamValue {
+        self.to_normalized(self.default)
+    }
+
+    pub fn to_plain(&self, normalized: ParamValue) -> f64 {
+        let n = normalized.clamp(0.0, 1.0);
+        match &self.range {
+            ParamRange::Continuous { min, max } => min + n * (max - min),
+            ParamRange::List(_) => (n * self.step_count() as f64).round(),
+        }
+    }
+
+    pub fn to_normalized(&self, plain: f64) -> ParamValue {
+        let n = match &self.range {
+            ParamRange::Continuous { min, max } if max != min => (plain - min) / (max - min),
+            ParamRange::List(_) if self.step_count() > 0 => {
+                plain.round() / self.step_count() as f64
+            }
+            _ => 0.0,
+        };
+        n.clamp(0.0, 1.0)
+    }
+
+    /// The display string for `normalized`, without the unit.
+    pub fn format(&self, normalized: ParamValue) -> String {
+        let plain = self.to_plain(normalized);
+        match &self.range {
+            ParamRange::Continuous { .. } => format!("{plain:.*}", self.precision),
+            ParamRange::List(labels) => labels[plain as usize].clone(),
+        }
+    }
+
+    /// Inverse of [`format`](Self::format); a trailing unit is accepted.
+    pub fn parse(&self, text: &str) -> Option<ParamValue> {
+        let text = text.trim();
+        let plain = match &self.range {
+            ParamRange::Continuous { .. } => text
+                .strip_suffix(self.unit.as_str())
+                .filter(|_| !self.unit.is_empty())
+                .unwrap_or(text)
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())?,
+            ParamRange::List(labels) => {
+                labels.iter().position(|l| l.eq_ignore_ascii_case(text))? as f64
+            }
+        };
+        Some(self.to_normalized(plain))
+    }
+}
+
+/// A processor's parameters, in the order the controller reports them.
+///
+/// ```
+/// # use openvst3_plugin::{ParamDef, Params};
+/// let params = Params::new()
+///     .param(ParamDef::range(0, "Gain", -60.0, 6.0, 0.0).unit("dB"))
+///     .param(ParamDef::list(1, "Mode", &["Clean", "Warm"], 0));
+/// assert_eq!(params.get(0).unwrap().format(1.0), "6.00");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Params {
+    defs: Vec<ParamDef>,
+}
+
+impl Params {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// # Panics
+    /// If `def.id` is already declared.
+    pub fn param(mut self, def: ParamDef) -> Self {
+        assert!(
+            self.get(def.id).is_none(),
+            "parameter id {} declared twice",
+            def.id
+        );
+        self.defs.push(def);
+        self
+    }
+
+    pub fn defs(&self) -> &[ParamDef] {
+        &self.defs
+    }
+
+    pub fn get(&self, id: ParamID) -> Option<&ParamDef> {
+        self.defs.iter().find(|d| d.id == id)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defs.is_empty()
+    }
+}
+
+/// Current normalized values, shared by the controller and the processor. Reads and
+/// writes are lock-free, so both sides may use it from any thread.
+pub struct ParamStore {
+    params: Params,
+    values: Vec<AtomicU64>,
+}
+
+impl ParamStore {
+    pub(crate) fn new(params: Params) -> Self {
+        let values = params
+            .defs
+            .iter()
+            .map(|d| AtomicU64::new(d.default_normalized().to_bits()))
+            .collect();
+        Self { params, values }
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub(crate) fn def_at(&self, index: i32) -> Option<&ParamDef> {
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| self.params.defs.get(i))
+    }
+
+    fn slot(&self, id: ParamID) -> Option<(&ParamDef, &AtomicU64)> {
+        let i = self.params.defs.iter().position(|d| d.id == id)?;
+        Some((&self.params.defs[i], &self.values[i]))
+    }
+
+    pub fn normalized(&self, id: ParamID) -> Option<ParamValue> {
+        self.slot(id)
+            .map(|(_, v)| f64::from_bits(v.load(Ordering::Relaxed)))
+    }
+
+    /// The current value in the parameter's plain units.
+    pub fn plain(&self, id: ParamID) -> Option<f64> {
+        self.slot(id)
+            .map(|(d, v)| d.to_plain(f64::from_bits(v.load(Ordering::Relaxed))))
+    }
+
+    /// Store `value` clamped to 0..=1; false for an unknown id.
+    pub fn set_normalized(&self, id: ParamID, value: ParamValue) -> bool {
+        match self.slot(id) {
+            Some((_, v)) if !value.is_nan() => {
+                v.store(value.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use core::marker::PhantomData;
+use core::ops::{Add, Mul, Sub};
+
+use crate::{ParamStore, SampleSize};
+
+/// A sample type the host can process in: `f32` or `f64`.
+pub trait Sample:
+    Copy
+    + Default
+    + PartialEq
+    + PartialOrd
+    + Send
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + 'static
+{
+    const ZERO: Self;
+    fn from_f64(v: f64) -> Self;
+    fn to_f64(self) -> f64;
+}
+
+impl Sample for f32 {
+    const ZERO: Self = 0.0;
+    fn from_f64(v: f64) -> Self {
+        v as f32
+    }
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Sample for f64 {
+    const ZERO: Self = 0.0;
+    fn from_f64(v: f64) -> Self {
+        v
+    }
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// `AudioBusBuffers32`/`AudioBusBuffers64` over either sample type; both share this
+/// layout.
+#[repr(C)]
+pub(crate) struct BusBuffers<T> {
+    pub num_channels: i32,
+    pub silence_flags: u64,
+    pub channel_buffers: *mut *mut T,
+}
+
+/// The audio buses of one block in sample type `T`, as slices of
+/// [`frames`](Self::frames) samples.
+///
+/// Hosts may pass the same buffer as input and output channel; [`channel`](Self::channel)
+/// hands such a pair out as one [`Channel::InPlace`] slice.
+pub struct Io<'a, T> {
+    inputs: &'a [BusBuffers<T>],
+    outputs: &'a [BusBuffers<T>],
+    frames: usize,
+    _buffers: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T: Sample> Io<'a, T> {
+    /// # Safety
+    /// Every non-null channel pointer must be valid for `frames` samples for `'a`,
+    /// and output channels must not overlap each other or an input other than their
+    /// own (same bus and channel index).
+    pub(crate) unsafe fn new(
+        inputs: *const BusBuffers<T>,
+        num_inputs: i32,
+        outputs: *const BusBuffers<T>,
+        num_outputs: i32,
+        frames: usize,
+    ) -> Self {
+        let buses = |ptr: *const BusBuffers<T>, n: i32| {
+            if ptr.is_null() || n <= 0 {
+                &[][..]
+            } else {
+                core::slice::from_raw_parts(ptr, n as usize)
+            }
+        };
+        Self {
+            inputs: buses(inputs, num_inputs),
+            outputs: buses(outputs, num_outputs),
+            frames,
+            _buffers: PhantomData,
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn input_buses(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn output_buses(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn input_channels(&self, bus: usize) -> usize {
+        self.inputs.get(bus).map_or(0, channel_count)
+    }
+
+    pub fn output_channels(&self, bus: usize) -> usize {
+        self.outputs.get(bus).map_or(0, channel_count)
+    }
+
+    pub fn input(&self, bus: usize, channel: usize) -> Option<&[T]> {
+        let ptr = channel_ptr(self.inputs, bus, channel)?;
+        Some(unsafe { core::slice::from_raw_parts(ptr, self.frames) })
+    }
+
+    pub fn output(&mut self, bus: usize, channel: usize) -> Option<&mut [T]> {
+        let ptr = channel_ptr(self.outputs, bus, channel)?;
+        Some(unsafe { core::slice::from_raw_parts_mut(ptr, self.frames) })
+    }
+
+    /// Output `channel` of `bus` with the input of the same index, if any.
+    pub fn channel(&mut self, bus: usize, channel: usize) -> Option<Channel<'_, T>> {
+        let out = channel_ptr(self.outputs, bus, channel)?;
+        let frames = self.frames;
+        let output = unsafe { core::slice::from_raw_parts_mut(out, frames) };
+        Some(match channel_ptr(self.inputs, bus, channel) {
+            Some(input) if input == out => Channel::InPlace(output),
+            Some(input) => Channel::Split {
+                input: unsafe { core::slice::from_raw_parts(input, frames) },
+                output,
+            },
+            None => Channel::Output(output),
+        })
+    }
+
+    /// Run `f` over every output sample, fed the matching input sample (or zero).
+    pub fn map(&mut self, mut f: impl FnMut(T) -> T) {
+        for bus in 0..self.output_buses() {
+            for c in 0..self.output_channels(bus) {
+                if let Some(channel) = self.channel(bus, c) {
+                    channel.map(&mut f);
+                }
+            }
+        }
+    }
+
+    /// Zero every output channel.
+    pub fn silence(&mut self) {
+        for bus in 0..self.output_buses() {
+            for c in 0..self.output_channels(bus) {
+                if let Some(out) = self.output(bus, c) {
+                    out.fill(T::ZERO);
+                }
+            }
+        }
+    }
+}
+
+fn channel_count<T>(bus: &BusBuffers<T>) -> usize {
+    if bus.channel_buffers.is_null() {
+        0
+    } else {
+        bus.num_channels.max(0) as usize
+    }
+}
+
+fn channel_ptr<T>(buses: &[BusBuffers<T>], bus: usize, channel: usize) -> Option<*mut T> {
+    let b = buses.get(bus)?;
+    if channel >= channel_count(b) {
+        return None;
+    }
+    let ptr = unsafe { *b.channel_buffers.add(channel) };
+    (!ptr.is_null()).then_some(ptr)
+}
+
+/// One output channel and where its input comes from.
+pub enum Channel<'b, T> {
+    Split {
+        input: &'b [T],
+        output: &'b mut [T],
+    },
+    /// The host passed one buffer as both input and output.
+    InPlace(&'b mut [T]),
+    /// There is no input with this bus and channel index.
+    Output(&'b mut [T]),
+}
+
+impl<T: Sample> Channel<'_, T> {
+    /// Write `f(input)` to every output sample, in order; `f(0)` without an input.
+    pub fn map(self, mut f: impl FnMut(T) -> T) {
+        match self {
+            Channel::Split { input, output } => {
+                for (o, &i) in output.iter_mut().zip(input) {
+                    *o = f(i);
+                }
+            }
+            Channel::InPlace(buf) => {
+                for s in buf {
+                    *s = f(*s);
+                }
+            }
+            Channel::Output(out) => {
+                for s in out {
+                    *s = f(T::ZERO);
+                }
+            }
+        }
+    }
+}
+
+/// A block's audio in the sample type chosen at setup.
+pub enum AudioIo<'a> {
+    F32(Io<'a, f32>),
+    F64(Io<'a, f64>),
+}
+
+impl AudioIo<'_> {
+    pub fn frames(&self) -> usize {
+        match self {
+            AudioIo::F32(io) => io.frames(),
+            AudioIo::F64(io) => io.frames(),
+        }
+    }
+
+    pub fn sample_size(&self) -> SampleSize {
+        match self {
+            AudioIo::F32(_) => SampleSize::F32,
+            AudioIo::F64(_) => SampleSize::F64,
+        }
+    }
+
+    /// [`Io::map`] in `f64` whatever the block's sample type, for processors that
+    /// do not need to be generic over it.
+    pub fn map(&mut self, mut f: impl FnMut(f64) -> f64) {
+        match self {
+            AudioIo::F32(io) => io.map(|x| f32::from_f64(f(x.to_f64()))),
+            AudioIo::F64(io) => io.map(f),
+        }
+    }
+
+    pub fn silence(&mut self) {
+        match self {
+            AudioIo::F32(io) => io.silence(),
+            AudioIo::F64(io) => io.silence(),
+        }
+    }
+}
+
+/// Everything [`AudioProcessor::process`](crate::AudioProcessor::process) gets for one
+/// block.
+pub struct ProcessCtx<'a> {
+    audio: AudioIo<'a>,
+    params: &'a ParamStore,
+}
+
+impl<'a> ProcessCtx<'a> {
+    pub(crate) fn new(audio: AudioIo<'a>, params: &'a ParamStore) -> Self {
+        Self { audio, params }
+    }
+
+    /// Frames in this block; 0 for a parameter flush.
+    pub fn frames(&self) -> usize {
+        self.audio.frames()
+    }
+
+    pub fn audio(&mut self) -> &mut AudioIo<'a> {
+        &mut self.audio
+    }
+
+    /// Parameter values with this block's changes applied.
+    pub fn params(&self) -> &'a ParamStore {
+        self.params
+    }
+}
@@ -0,0 +1,279 @@
+use std::io::{self, Read, Write};
+
+use openvst3_abi::{IBStream, K_RESULT_FALSE, K_RESULT_OK};
+
+use crate::ParamStore;
+
+/// First bytes of every state blob.
+const MAGIC: [u8; 4] = *b"OV3S";
+
+/// Tag of the field holding the declared parameters; not available to processors.
+pub const PARAMS_TAG: u32 = u32::MAX;
+
+const TYPE_BOOL: u8 = 0;
+const TYPE_I64: u8 = 1;
+const TYPE_F64: u8 = 2;
+const TYPE_BYTES: u8 = 3;
+const TYPE_STR: u8 = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StateError {
+    #[error("not an OpenVST3 plugin state")]
+    BadHeader,
+    #[error("state ends inside a field")]
+    Truncated,
+    /// Rejected by the processor's [`load_state`](crate::AudioProcessor::load_state).
+    #[error("invalid state: {0}")]
+    Invalid(String),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl StateError {
+    fn from_read(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => StateError::Truncated,
+            _ => StateError::Io(e),
+        }
+    }
+}
+
+/// Builds a state blob: `"OV3S"`, the version, the body length, then the fields.
+/// Every field is a little-endian `u32` tag, a type byte, a `u32` length and the
+/// payload, so readers skip fields they do not know.
+///
+/// ```
+/// # use openvst3_plugin::{StateReader, StateWriter};
+/// let mut w = StateWriter::new(2);
+/// w.f64(1, 0.25).str(2, "warm");
+/// let mut bytes = Vec::new();
+/// w.finish(&mut bytes).unwrap();
+///
+/// let r = StateReader::read_from(&mut &bytes[..]).unwrap();
+/// assert_eq!((r.version(), r.f64(1), r.str(2)), (2, Some(0.25), Some("warm")));
+/// ```
+pub struct StateWriter {
+    version: u32,
+    body: Vec<u8>,
+}
+
+impl StateWriter {
+    /// `version` is the processor's own format version, for migrations on load.
+    pub fn new(version: u32) -> Self {
+        Self {
+            version,
+            body: Vec::new(),
+        }
+    }
+
+    fn field(&mut self, tag: u32, ty: u8, payload: &[u8]) -> &mut Self {
+        self.body.extend_from_slice(&tag.to_le_bytes());
+        self.body.push(ty);
+        self.body
+            .extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        self.body.extend_from_slice(payload);
+        self
+    }
+
+    pub fn bool(&mut self, tag: u32, v: bool) -> &mut Self {
+        self.field(tag, TYPE_BOOL, &[v as u8])
+    }
+
+    pub fn i64(&mut self, tag: u32, v: i64) -> &mut Self {
+        self.field(tag, TYPE_I64, &v.to_le_bytes())
+    }
+
+    pub fn f64(&mut self, tag: u32, v: f64) -> &mut Self {
+        self.field(tag, TYPE_F64, &v.to_le_bytes())
+    }
+
+    pub fn bytes(&mut self, tag: u32, v: &[u8]) -> &mut Self {
+        self.field(tag, TYPE_BYTES, v)
+    }
+
+    pub fn str(&mut self, tag: u32, v: &str) -> &mut Self {
+        self.field(tag, TYPE_STR, v.as_bytes())
+    }
+
+    /// Write the header and all fields to `out`.
+    pub fn finish(self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&MAGIC)?;
+        out.write_all(&self.version.to_le_bytes())?;
+        out.write_all(&(self.body.len() as u32).to_le_bytes())?;
+        out.write_all(&self.body)
+    }
+}
+
+/// A parsed state blob. Lookups return `None` for a missing field or one of another
+/// type; the last field with a tag wins.
+pub struct StateReader {
+    version: u32,
+    fields: Vec<(u32, u8, Vec<u8>)>,
+}
+
+impl StateReader {
+    /// Read one blob from `input`, consuming exactly its bytes.
+    pub fn read_from(input: &mut impl Read) -> Result<Self, StateError> {
+        let mut header = [0u8; 12];
+        input.read_exact(&mut header).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => StateError::BadHeader,
+            _ => StateError::Io(e),
+        })?;
+        if header[..4] != MAGIC {
+            return Err(StateError::BadHeader);
+        }
+        let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let len = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+        let mut body = Vec::new();
+        input
+            .take(len as u64)
+            .read_to_end(&mut body)
+            .map_err(StateError::from_read)?;
+        if body.len() != len {
+            return Err(StateError::Truncated);
+        }
+
+        let mut fields = Vec::new();
+        let mut rest = &body[..];
+        while !rest.is_empty() {
+            if rest.len() < 9 {
+                return Err(StateError::Truncated);
+            }
+            let tag = u32::from_le_bytes(rest[..4].try_into().unwrap());
+            let ty = rest[4];
+            let n = u32::from_le_bytes(rest[5..9].try_into().unwrap()) as usize;
+            let payload = rest.get(9..9 + n).ok_or(StateError::Truncated)?;
+            fields.push((tag, ty, payload.to_vec()));
+            rest = &rest[9 + n..];
+        }
+        Ok(Self { version, fields })
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    fn field(&self, tag: u32, ty: u8) -> Option<&[u8]> {
+        self.fields
+            .iter()
+            .rev()
+            .find(|(t, _, _)| *t == tag)
+            .filter(|(_, t, _)| *t == ty)
+            .map(|(_, _, p)| &p[..])
+    }
+
+    fn fixed<const N: usize>(&self, tag: u32, ty: u8) -> Option<[u8; N]> {
+        self.field(tag, ty)?.try_into().ok()
+    }
+
+    pub fn bool(&self, tag: u32) -> Option<bool> {
+        self.fixed::<1>(tag, TYPE_BOOL).map(|[b]| b != 0)
+    }
+
+    pub fn i64(&self, tag: u32) -> Option<i64> {
+        self.fixed(tag, TYPE_I64).map(i64::from_le_bytes)
+    }
+
+    pub fn f64(&self, tag: u32) -> Option<f64> {
+        self.fixed(tag, TYPE_F64).map(f64::from_le_bytes)
+    }
+
+    pub fn bytes(&self, tag: u32) -> Option<&[u8]> {
+        self.field(tag, TYPE_BYTES)
+    }
+
+    pub fn str(&self, tag: u32) -> Option<&str> {
+        core::str::from_utf8(self.field(tag, TYPE_STR)?).ok()
+    }
+}
+
+impl ParamStore {
+    /// Store every parameter's normalized value under [`PARAMS_TAG`].
+    pub fn save(&self, w: &mut StateWriter) {
+        let mut payload = Vec::with_capacity(self.params().defs().len() * 12);
+        for def in self.params().defs() {
+            payload.extend_from_slice(&def.id.to_le_bytes());
+            payload.extend_from_slice(&self.normalized(def.id).unwrap_or(0.0).to_le_bytes());
+        }
+        w.bytes(PARAMS_TAG, &payload);
+    }
+
+    /// Restore values saved by [`save`](Self::save). Ids no longer declared are
+    /// skipped; parameters missing from the state keep their current value.
+    pub fn load(&self, r: &StateReader) {
+        let Some(payload) = r.bytes(PARAMS_TAG) else {
+            return;
+        };
+        for entry in payload.chunks_exact(12) {
+            let id = u32::from_le_bytes(entry[..4].try_into().unwrap());
+            let value = f64::from_le_bytes(entry[4..].try_into().unwrap());
+            self.set_normalized(id, value);
+        }
+    }
+}
+
+/// `io::Read`/`io::Write` over a host `IBStream`.
+pub(crate) struct IbStream(pub *mut IBStream);
+
+impl IbStream {
+    fn check(tr: i32, done: i32) -> io::Result<usize> {
+        match tr {
+            K_RESULT_OK | K_RESULT_FALSE => Ok(done.max(0) as usize),
+            tr => Err(io::Error::other(format!("IBStream call failed ({tr})"))),
+        }
+    }
+}
+
+impl Read for IbStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min(i32::MAX as usize) as i32;
+        let mut done = 0;
+        let tr = unsafe { (*self.0).read(buf.as_mut_ptr().cast(), n, &mut done) };
+        Self::check(tr, done)
+    }
+}
+
+impl Write for IbStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(i32::MAX as usize) as i32;
+        let mut done = 0;
+        let tr = unsafe { (*self.0).write(buf.as_ptr().cast(), n, &mut done) };
+        Self::check(tr, done)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookups_match_tag_and_type_last_field_wins() {
+        let mut w = StateWriter::new(7);
+        w.i64(1, -5).f64(2, 1.5).bool(3, true).i64(1, 9);
+        let mut bytes = Vec::new();
+        w.finish(&mut bytes).unwrap();
+        bytes.extend_from_slice(b"trailing");
+
+        let mut input = &bytes[..];
+        let r = StateReader::read_from(&mut input).unwrap();
+        assert_eq!(input, b"trailing");
+        assert_eq!(r.i64(1), Some(9));
+        assert_eq!(r.f64(2), Some(1.5));
+        assert_eq!(r.bool(3), Some(true));
+        assert_eq!(r.i64(2), None);
+        assert_eq!(r.str(4), None);
+
+        // A field whose length runs past the body.
+        let mut bad = bytes[..12].to_vec();
+        bad[8..12].copy_from_slice(&9u32.to_le_bytes());
+        bad.extend_from_slice(&[1, 0, 0, 0, TYPE_BYTES, 200, 0, 0, 0]);
+        assert!(matches!(
+            StateReader::read_from(&mut &bad[..]),
+            Err(StateError::Truncated)
+        ));
+    }
+}
@@ -0,0 +1,503 @@
+//! Trait-based processors hosted through openvst3-host, as a real host would see them.
+
+use openvst3_abi::{
+    process_consts, AudioBusBuffers32, AudioBusBuffers64, ParameterFlags, ProcessData32,
+    ProcessData64, ProcessSetup, BUS_DIR_INPUT, BUS_DIR_OUTPUT, K_RESULT_FALSE, MEDIA_TYPE_AUDIO,
+    MEDIA_TYPE_EVENT,
+};
+use openvst3_host::validate::{Check, Outcome, ValidateOptions};
+use openvst3_host::{
+    decode_fixed_cstr, list_classes, HostError, Module, ParameterChanges, RenderConfig,
+    RenderEvents, SymbolicSampleSize, VstPreset,
+};
+use openvst3_plugin::{
+    AudioIo, AudioProcessor, BusConfig, Channel, Factory, ParamDef, ParamStore, Params,
+    PluginClass, PluginError, ProcessCtx, ProcessMode, SampleSize, SetupInfo, StateError,
+    StateReader, StateWriter, PARAMS_TAG,
+};
+
+const GAIN_CID: [u8; 16] = *b"openvst3-gain\0\0\0";
+const SYNTH_CID: [u8; 16] = *b"openvst3-synth\0\0";
+
+const GAIN: u32 = 7;
+const CURVE: u32 = 8;
+
+/// Stereo gain; the factor is the `GAIN` parameter, 0.5 by default.
+#[derive(Default)]
+struct Gain;
+
+impl AudioProcessor for Gain {
+    fn params(&self) -> Params {
+        Params::new()
+            .param(ParamDef::range(GAIN, "Gain", 0.0, 2.0, 0.5).unit("x"))
+            .param(
+                ParamDef::list(CURVE, "Curve", &["Linear", "Square", "Cube"], 0).short_name("Crv"),
+            )
+    }
+
+    fn process(&mut self, mut ctx: ProcessCtx<'_>) {
+        let gain = ctx.params().plain(GAIN).unwrap();
+        ctx.audio().map(|x| x * gain);
+    }
+}
+
+/// Mono instrument with latency and a tail; writes a constant through [`Channel`].
+/// Keeps its processed block count as custom state.
+#[derive(Default)]
+struct Drone {
+    setup: Option<SetupInfo>,
+    blocks: i64,
+}
+
+const BLOCKS_TAG: u32 = 1;
+
+impl AudioProcessor for Drone {
+    fn buses(&self) -> BusConfig {
+        BusConfig::instrument(1)
+    }
+
+    fn setup(&mut self, setup: SetupInfo) -> Result<(), PluginError> {
+        if setup.sample_size != SampleSize::F32 {
+            return Err(PluginError::Unsupported);
+        }
+        self.setup = Some(setup);
+        Ok(())
+    }
+
+    fn process(&mut self, mut ctx: ProcessCtx<'_>) {
+        assert_eq!(self.setup.map(|s| s.mode), Some(ProcessMode::Offline));
+        self.blocks += 1;
+        if let AudioIo::F32(io) = ctx.audio() {
+            if let Some(Channel::Output(out)) = io.channel(0, 0) {
+                out.fill(0.25);
+            }
+        }
+    }
+
+    fn latency(&self) -> u32 {
+        32
+    }
+
+    fn state_version(&self) -> u32 {
+        2
+    }
+
+    fn save_state(&self, _params: &ParamStore, state: &mut StateWriter) {
+        state.i64(BLOCKS_TAG, self.blocks);
+    }
+
+    fn load_state(&mut self, _params: &ParamStore, state: &StateReader) -> Result<(), StateError> {
+        if state.version() > 2 {
+            return Err(StateError::Invalid(format!("version {}", state.version())));
+        }
+        self.blocks = state.i64(BLOCKS_TAG).unwrap_or(0);
+        Ok(())
+    }
+
+    fn tail(&self) -> u32 {
+        process_consts::INFINITE_TAIL
+    }
+}
+
+fn module() -> Module {
+    let factory = Factory::new("OpenVST3")
+        .url("https://example.invalid")
+        .class(PluginClass::new::<Gain>(GAIN_CID, "Gain"))
+        .class(PluginClass::new::<Drone>(SYNTH_CID, "Drone").sub_categories("Instrument"))
+        .into_raw();
+    unsafe { Module::from_factory_ptr(factory) }.unwrap()
+}
+
+fn setup(sample_size: i32, block: i32) -> ProcessSetup {
+    ProcessSetup {
+        process_mode: process_consts::PROCESS_MODE_REALTIME,
+        sample_rate: 48_000.0,
+        max_samples_per_block: block,
+        symbolic_sample_size: sample_size,
+        flags: 0,
+    }
+}
+
+#[test]
+fn factory_lists_classes_and_buses() {
+    let mut module = module();
+    let classes: Vec<_> = list_classes(&mut module)
+        .unwrap()
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(classes.len(), 2);
+    assert_eq!((classes[0].1.as_str(), classes[0].3), ("Gain", GAIN_CID));
+    assert_eq!(classes[0].2, "Audio Module Class");
+    assert_eq!((classes[1].1.as_str(), classes[1].3), ("Drone", SYNTH_CID));
+
+    let gain = module.create_plugin(GAIN_CID).unwrap();
+    let component = gain.component();
+    assert_eq!(component.bus_count(MEDIA_TYPE_AUDIO, BUS_DIR_INPUT), 1);
+    assert_eq!(component.bus_count(MEDIA_TYPE_EVENT, BUS_DIR_INPUT), 0);
+    let out = component
+        .bus_info(MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT, 0)
+        .unwrap();
+    assert_eq!(out.channel_count, 2);
+    assert_eq!(decode_fixed_cstr(&out.name).0, "Main Out");
+    assert_eq!(out.flags & 1, 1);
+    assert!(component
+        .bus_info(MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT, 1)
+        .is_err());
+
+    let drone = module.create_plugin(SYNTH_CID).unwrap();
+    assert_eq!(
+        drone.component().bus_count(MEDIA_TYPE_EVENT, BUS_DIR_INPUT),
+        1
+    );
+    assert_eq!(
+        drone.component().bus_count(MEDIA_TYPE_AUDIO, BUS_DIR_INPUT),
+        0
+    );
+    // No parameters, no controller.
+    assert!(drone.controller().is_none());
+
+    assert!(module.create_plugin(*b"not-a-class-cid!").is_err());
+}
+
+#[test]
+fn offline_render_runs_the_trait_process() {
+    let mut module = module();
+    let mut gain = module.create_plugin(GAIN_CID).unwrap();
+    let input: Vec<Vec<f32>> = (0..2)
+        .map(|c| (0..1000).map(|i| (i + c) as f32 / 1000.0).collect())
+        .collect();
+    let config = RenderConfig {
+        block_size: 256,
+        ..RenderConfig::default()
+    };
+    let out = gain
+        .render_offline(&config, &input, 1000, |_, _| {})
+        .unwrap();
+    for (o, i) in out.iter().zip(&input) {
+        let expected: Vec<f32> = i.iter().map(|x| x * 0.5).collect();
+        assert_eq!(o, &expected);
+    }
+
+    let mut drone = module.create_plugin(SYNTH_CID).unwrap();
+    assert_eq!(drone.negotiate_channels(0, 1), (0, 1));
+    assert_eq!(drone.processor().latency_samples(), 32);
+    assert_eq!(
+        drone.processor().tail_samples(),
+        process_consts::INFINITE_TAIL
+    );
+    let config = RenderConfig {
+        input_channels: 0,
+        output_channels: 1,
+        ..RenderConfig::default()
+    };
+    // The latency is rendered and dropped by the host.
+    let out = drone.render_offline(&config, &[], 64, |_, _| {}).unwrap();
+    assert_eq!(out, vec![vec![0.25; 64]]);
+    // Refused by the processor's own setup.
+    assert!(drone
+        .activate(&setup(process_consts::SYMBOLIC_SAMPLE_64, 64))
+        .is_err());
+}
+
+#[test]
+fn double_precision_blocks_reach_the_processor() {
+    let mut module = module();
+    let mut gain = module.create_plugin(GAIN_CID).unwrap();
+    gain.activate(&setup(process_consts::SYMBOLIC_SAMPLE_64, 4))
+        .unwrap();
+
+    let mut left = [1.0f64, 2.0, 3.0, 4.0];
+    let mut right = [-1.0f64; 4];
+    // In place: the host hands the same buffers in and out.
+    let mut channels = [left.as_mut_ptr(), right.as_mut_ptr()];
+    let mut bus = AudioBusBuffers64 {
+        num_channels: 2,
+        silence_flags: 0,
+        channel_buffers: channels.as_mut_ptr(),
+    };
+    let mut data = ProcessData64 {
+        num_inputs: 1,
+        num_outputs: 1,
+        inputs: &mut bus,
+        outputs: &mut bus,
+        num_samples: 4,
+        input_parameter_changes: core::ptr::null_mut(),
+        output_parameter_changes: core::ptr::null_mut(),
+        input_events: core::ptr::null_mut(),
+        output_events: core::ptr::null_mut(),
+        process_context: core::ptr::null_mut(),
+    };
+    unsafe { gain.process_64f(&mut data) }.unwrap();
+    assert_eq!(left, [0.5, 1.0, 1.5, 2.0]);
+    assert_eq!(right, [-0.5; 4]);
+
+    // The instance was set up for 64-bit samples: 32-bit blocks are refused with
+    // kResultFalse, which is "nothing to do" rather than an error.
+    let mut silent = openvst3_abi::ProcessData32 {
+        num_inputs: 0,
+        num_outputs: 0,
+        inputs: core::ptr::null_mut(),
+        outputs: core::ptr::null_mut(),
+        num_samples: 4,
+        input_parameter_changes: core::ptr::null_mut(),
+        output_parameter_changes: core::ptr::null_mut(),
+        input_events: core::ptr::null_mut(),
+        output_events: core::ptr::null_mut(),
+        process_context: core::ptr::null_mut(),
+    };
+    let stats = unsafe { gain.process_32f(&mut silent) }.unwrap();
+    assert_eq!(stats.tresult_raw, K_RESULT_FALSE);
+    assert!(stats.nothing_to_do());
+}
+
+#[test]
+fn calls_out_of_order_are_refused() {
+    let mut module = module();
+    let mut gain = module.create_plugin(GAIN_CID).unwrap();
+    let processor = gain.processor();
+    let component = gain.component();
+
+    // Activation needs a setup first.
+    assert!(component.set_active(true).is_err());
+    // setProcessing is optional, so the handle lets its kResultFalse through.
+    assert_eq!(
+        unsafe { (*processor.as_ptr()).set_processing(1) },
+        K_RESULT_FALSE
+    );
+    assert!(processor.set_processing(true).is_ok());
+    assert!(processor.setup_processing(&setup(7, 512)).is_err());
+    assert!(processor.can_process_sample_size(SymbolicSampleSize::Sample32));
+    assert!(processor.can_process_sample_size(SymbolicSampleSize::Sample64));
+
+    // Only the declared stereo layout is accepted; the host falls back to it.
+    assert_eq!(gain.negotiate_channels(1, 1), (2, 2));
+    assert_eq!(gain.negotiate_channels(2, 2), (2, 2));
+    assert_eq!(
+        gain.processor().bus_arrangement(BUS_DIR_OUTPUT, 0).unwrap(),
+        openvst3_abi::speaker_arr::STEREO
+    );
+    assert!(gain.processor().bus_arrangement(BUS_DIR_OUTPUT, 1).is_err());
+
+    gain.activate(&setup(process_consts::SYMBOLIC_SAMPLE_32, 512))
+        .unwrap();
+    assert!(gain
+        .processor()
+        .setup_processing(&setup(process_consts::SYMBOLIC_SAMPLE_32, 512))
+        .is_err());
+    assert!(matches!(
+        gain.processor().set_bus_arrangements(&[3], &[3]),
+        Err(HostError::ArrangementRejected { .. })
+    ));
+    gain.deactivate();
+    assert!(gain
+        .processor()
+        .setup_processing(&setup(process_consts::SYMBOLIC_SAMPLE_32, 512))
+        .is_ok());
+    assert_eq!(gain.processor().tail_samples(), process_consts::NO_TAIL);
+}
+
+#[test]
+fn declared_params_pass_the_validator() {
+    let mut module = module();
+    let report = module.validate(GAIN_CID, &ValidateOptions::default());
+    for r in &report.results {
+        assert!(
+            !matches!(r.outcome, Outcome::Fail(_)),
+            "{}: {:?}",
+            r.case,
+            r.outcome
+        );
+    }
+    let params = report
+        .results
+        .iter()
+        .find(|r| r.case.check == Check::Params)
+        .unwrap();
+    assert_eq!(params.outcome, Outcome::Pass);
+}
+
+#[test]
+fn controller_reports_and_converts_params() {
+    let mut module = module();
+    let gain = module.create_plugin(GAIN_CID).unwrap();
+    let controller = gain.controller().unwrap();
+    let params = controller.parameters().unwrap();
+    assert_eq!(params.len(), 2);
+    assert_eq!((params[0].id, params[0].title.as_str()), (GAIN, "Gain"));
+    assert_eq!(params[0].units, "x");
+    assert_eq!(params[0].step_count, 0);
+    assert_eq!(params[0].default_normalized, 0.25);
+    assert_eq!(params[1].short_title, "Crv");
+    assert_eq!(params[1].step_count, 2);
+    assert_eq!(
+        params[1].flags,
+        ParameterFlags::CAN_AUTOMATE | ParameterFlags::IS_LIST
+    );
+
+    assert_eq!(controller.param_string_by_value(GAIN, 0.5).unwrap(), "1.00");
+    assert_eq!(
+        controller.param_string_by_value(CURVE, 1.0).unwrap(),
+        "Cube"
+    );
+    assert!(controller.param_string_by_value(99, 0.5).is_err());
+    let raw = controller.as_ptr();
+    let text: Vec<u16> = "1.5 x\0".encode_utf16().collect();
+    let mut value = 0.0;
+    unsafe {
+        assert_eq!(
+            (*raw).get_param_value_by_string(GAIN, text.as_ptr(), &mut value),
+            0
+        );
+        assert_eq!(value, 0.75);
+        assert_eq!((*raw).normalized_param_to_plain(CURVE, 0.5), 1.0);
+        assert_eq!((*raw).plain_param_to_normalized(GAIN, 4.0), 1.0);
+    }
+
+    // The controller writes the store the processor reads.
+    controller.set_param_normalized(GAIN, 1.0).unwrap();
+    assert_eq!(controller.get_param_normalized(GAIN), 1.0);
+    assert!(controller.set_param_normalized(99, 1.0).is_err());
+}
+
+#[test]
+fn block_changes_reach_the_processor_last_point_wins() {
+    let mut module = module();
+    let mut gain = module.create_plugin(GAIN_CID).unwrap();
+    gain.activate(&setup(process_consts::SYMBOLIC_SAMPLE_32, 4))
+        .unwrap();
+    let mut changes = ParameterChanges::with_capacity(1, 4);
+    changes.add_point(GAIN, 0, 0.0);
+    changes.add_point(GAIN, 3, 0.75);
+
+    let mut buf = [1.0f32; 4];
+    let mut channels = [buf.as_mut_ptr()];
+    let mut bus = AudioBusBuffers32 {
+        num_channels: 1,
+        silence_flags: 0,
+        channel_buffers: channels.as_mut_ptr(),
+    };
+    let mut data = ProcessData32 {
+        num_inputs: 1,
+        num_outputs: 1,
+        inputs: &mut bus,
+        outputs: &mut bus,
+        num_samples: 4,
+        input_parameter_changes: changes.as_iparameter_changes() as *mut _,
+        output_parameter_changes: core::ptr::null_mut(),
+        input_events: core::ptr::null_mut(),
+        output_events: core::ptr::null_mut(),
+        process_context: core::ptr::null_mut(),
+    };
+    unsafe { gain.process_32f(&mut data) }.unwrap();
+    assert_eq!(buf, [1.5; 4]);
+    assert_eq!(gain.controller().unwrap().get_param_normalized(GAIN), 0.75);
+    gain.deactivate();
+
+    // Automation through the host's offline renderer: silence from frame 4 on.
+    let config = RenderConfig {
+        block_size: 4,
+        input_channels: 1,
+        output_channels: 1,
+        ..RenderConfig::default()
+    };
+    let events = RenderEvents {
+        automation: vec![(GAIN, vec![(3, 0.5), (4, 0.0)])],
+        ..RenderEvents::default()
+    };
+    let out = gain
+        .render_offline_with(
+            &config,
+            &[vec![1.0; 8]],
+            8,
+            &events,
+            &Default::default(),
+            |_, _| {},
+        )
+        .unwrap();
+    assert_eq!(out, vec![vec![1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0]]);
+}
+
+#[test]
+fn params_persist_through_component_state_and_presets() {
+    let mut module = module();
+    let a = module.create_plugin(GAIN_CID).unwrap();
+    a.controller()
+        .unwrap()
+        .set_param_normalized(GAIN, 0.8)
+        .unwrap();
+    a.controller()
+        .unwrap()
+        .set_param_normalized(CURVE, 1.0)
+        .unwrap();
+    let saved = a.component_state().unwrap();
+
+    let b = module.create_plugin(GAIN_CID).unwrap();
+    b.set_component_state(&saved).unwrap();
+    let controller = b.controller().unwrap();
+    assert_eq!(controller.get_param_normalized(GAIN), 0.8);
+    assert_eq!(controller.get_param_normalized(CURVE), 1.0);
+    assert_eq!(b.component_state().unwrap(), saved);
+
+    // Through a .vstpreset file written and parsed by the host.
+    let file = a.save_preset(GAIN_CID).unwrap().to_bytes();
+    let c = module.create_plugin(GAIN_CID).unwrap();
+    c.load_preset(&VstPreset::parse(&file).unwrap()).unwrap();
+    assert_eq!(c.controller().unwrap().get_param_normalized(GAIN), 0.8);
+    assert_eq!(c.component_state().unwrap(), saved);
+
+    assert!(c.set_component_state(b"not a state").is_err());
+    assert!(c.set_component_state(&saved[..saved.len() - 1]).is_err());
+}
+
+#[test]
+fn newer_states_load_with_unknown_fields_skipped() {
+    // A later plugin version: an extra field and a parameter this one lacks.
+    let mut params = Vec::new();
+    for (id, value) in [(99u32, 0.1f64), (GAIN, 0.3)] {
+        params.extend_from_slice(&id.to_le_bytes());
+        params.extend_from_slice(&value.to_le_bytes());
+    }
+    let mut w = StateWriter::new(1);
+    w.str(42, "from the future").bytes(PARAMS_TAG, &params);
+    let mut state = Vec::new();
+    w.finish(&mut state).unwrap();
+
+    let mut module = module();
+    let gain = module.create_plugin(GAIN_CID).unwrap();
+    gain.set_component_state(&state).unwrap();
+    let controller = gain.controller().unwrap();
+    assert_eq!(controller.get_param_normalized(GAIN), 0.3);
+    // Not in the state: keeps its default.
+    assert_eq!(controller.get_param_normalized(CURVE), 0.0);
+}
+
+#[test]
+fn custom_state_replaces_param_persistence() {
+    let mut module = module();
+    let mut drone = module.create_plugin(SYNTH_CID).unwrap();
+    let config = RenderConfig {
+        block_size: 16,
+        input_channels: 0,
+        output_channels: 1,
+        ..RenderConfig::default()
+    };
+    drone.render_offline(&config, &[], 64, |_, _| {}).unwrap();
+    let saved = drone.component_state().unwrap();
+    let reader = StateReader::read_from(&mut &saved[..]).unwrap();
+    assert_eq!(reader.version(), 2);
+    // 64 frames plus 32 of latency.
+    assert_eq!(reader.i64(BLOCKS_TAG), Some(6));
+    assert_eq!(reader.bytes(PARAMS_TAG), None);
+
+    let other = module.create_plugin(SYNTH_CID).unwrap();
+    other.set_component_state(&saved).unwrap();
+    assert_eq!(other.component_state().unwrap(), saved);
+
+    let mut newer = Vec::new();
+    StateWriter::new(3).finish(&mut newer).unwrap();
+    assert!(matches!(
+        other.set_component_state(&newer),
+        Err(HostError::TErr(K_RESULT_FALSE))
+    ));
+}
@@ -1,8 +1,13 @@
 use std::{env, path::PathBuf};
 
 fn main() {
-    let sdk = env::var("VST3_SDK_DIR").expect("Set VST3_SDK_DIR to your local vst3sdk path");
     println!("cargo:rerun-if-env-changed=VST3_SDK_DIR");
+    // Without the SDK this crate builds empty, so a workspace build works on machines
+    // that only use the pure-ABI path. openvst3-sys reports the missing SDK when its
+    // `shim` feature asks for the functions.
+    let Ok(sdk) = env::var("VST3_SDK_DIR") else {
+        return;
+    };
 
     let out = PathBuf::from(env::var("OUT_DIR").unwrap());
     let wrapper_h = out.join("v3shim.h");
@@ -27,6 +32,7 @@ typedef void* v3_factory;
 typedef void* v3_component;
 typedef void* v3_audio_processor;
 typedef void* v3_funknown;
+typedef void* v3_edit_controller;
 
 typedef struct {
     int32_t media_type;
@@ -37,6 +43,89 @@ typedef struct {
     char name[128];
 } v3_bus_info;
 
+// ParameterInfo with UTF-8 strings (truncated on a character boundary).
+typedef struct {
+    uint32_t id;
+    char title[128];
+    char short_title[128];
+    char units[128];
+    int32_t step_count;
+    double default_normalized;
+    int32_t unit_id;
+    int32_t flags;
+} v3_param_info;
+
+// Event types for v3_event.type
+#define V3_EVENT_NOTE_ON  0
+#define V3_EVENT_NOTE_OFF 1
+
+// A note on or off; `tuning` is in cents, `note_id` -1 when unused.
+typedef struct {
+    int32_t type;
+    int32_t bus_index;
+    int32_t sample_offset;
+    int16_t channel;
+    int16_t pitch;
+    float velocity;
+    int32_t note_id;
+    float tuning;
+} v3_event;
+
+// One point of a parameter's value queue for the block; normalized value.
+typedef struct {
+    uint32_t param_id;
+    int32_t sample_offset;
+    double value;
+} v3_param_point;
+
+// Bits of v3_process_context.flags. The first three are transport state; the rest
+// mark which fields the caller filled in.
+#define V3_CTX_PLAYING             (1u << 0)
+#define V3_CTX_RECORDING           (1u << 1)
+#define V3_CTX_CYCLE_ACTIVE        (1u << 2)
+#define V3_CTX_TEMPO_VALID         (1u << 3)
+#define V3_CTX_TIME_SIG_VALID      (1u << 4)
+#define V3_CTX_PROJECT_MUSIC_VALID (1u << 5)
+#define V3_CTX_BAR_POSITION_VALID  (1u << 6)
+#define V3_CTX_CYCLE_VALID         (1u << 7)
+#define V3_CTX_CONT_TIME_VALID     (1u << 8)
+
+// Transport for one block, translated into Steinberg::Vst::ProcessContext.
+// `sample_rate` and `project_time_samples` are always passed; musical positions
+// are in quarter notes.
+typedef struct {
+    double sample_rate;
+    int64_t project_time_samples;
+    int64_t continuous_time_samples;
+    double project_time_music;
+    double bar_position_music;
+    double cycle_start_music;
+    double cycle_end_music;
+    double tempo;
+    int32_t time_sig_numerator;
+    int32_t time_sig_denominator;
+    uint32_t flags;
+} v3_process_context;
+
+// get_tail's answer for kInfiniteTail (and anything that does not fit an int).
+#define V3_INFINITE_TAIL 0x7fffffff
+
+// Status codes. Functions return 0 (or a count) on success and a negative code on
+// failure; the tens digit is the category:
+//   -1x  bad arguments, caught before the plugin is called
+//   -2x  the plugin returned a failing tresult; v3_last_tresult() has it
+//   -3x  the plugin lacks the interface or class asked for
+#define V3_ERR_NULL_HANDLE   (-10) // a null handle or output pointer
+#define V3_ERR_INVALID_ARG   (-11) // a negative count, missing array or unknown event type
+#define V3_ERR_SHORT_CHANNEL (-12) // reserved for callers' channel-length checks
+#define V3_ERR_TRESULT       (-20)
+#define V3_ERR_NO_INTERFACE  (-30) // queryInterface or createInstance gave no object
+#define V3_ERR_NO_CONTROLLER (-31) // the component names no separate controller class
+
+// The tresult behind the last -2x or -3x status returned on this thread. The
+// shim's own allocation failures are reported as kOutOfMemory.
+int32_t v3_last_tresult(void);
+
 // Factory utilities
 int  v3_factory_class_count(v3_factory f);
 int  v3_factory_class_info(v3_factory f, int idx, v3_class_info* out_info);
@@ -52,6 +141,26 @@ int  v3_component_terminate(v3_component c);
 int  v3_component_get_bus_count(v3_component c, int32_t media_type, int32_t direction);
 int  v3_component_get_bus_info(v3_component c, int32_t media_type, int32_t direction, int32_t index, v3_bus_info* out_info);
 int  v3_component_activate_bus(v3_component c, int32_t media_type, int32_t direction, int32_t index, int state);
+// V3_ERR_NO_CONTROLLER when the component names no separate controller class.
+int  v3_component_get_controller_cid(v3_component c, uint8_t out_cid[16]);
+// The component's IAudioProcessor, with its own reference (release with v3_release).
+int  v3_component_get_audio_processor(v3_component c, v3_audio_processor* out_proc);
+
+// Component state. get_state hands over a buffer the caller frees with
+// v3_buffer_free; an empty state yields a null buffer and a length of 0.
+int  v3_component_get_state(v3_component c, uint8_t** out_buf, int32_t* out_len);
+int  v3_component_set_state(v3_component c, const uint8_t* buf, int32_t len);
+void v3_buffer_free(uint8_t* buf);
+
+// Edit controller (release with v3_release)
+int  v3_factory_create_edit_controller(v3_factory f, const uint8_t cid[16], v3_edit_controller* out_ctrl);
+int  v3_controller_initialize(v3_edit_controller e);
+int  v3_controller_terminate(v3_edit_controller e);
+int  v3_controller_param_count(v3_edit_controller e);
+int  v3_controller_param_info(v3_edit_controller e, int32_t index, v3_param_info* out_info);
+int  v3_controller_get_param_normalized(v3_edit_controller e, uint32_t id, double* out_value);
+int  v3_controller_set_param_normalized(v3_edit_controller e, uint32_t id, double value);
+int  v3_controller_normalized_to_plain(v3_edit_controller e, uint32_t id, double normalized, double* out_plain);
 
 // Processor
 int  v3_audio_processor_setup(v3_audio_processor p, double sample_rate, int32_t max_block, int32_t in_channels, int32_t out_channels);
@@ -59,6 +168,8 @@ int  v3_audio_processor_set_active(v3_audio_processor p, int state);
 int  v3_audio_processor_set_processing(v3_audio_processor p, int state);
 int  v3_audio_processor_get_bus_arrangements(v3_audio_processor p, int32_t in_count, uint64_t* inputs, int32_t out_count, uint64_t* outputs);
 int  v3_audio_processor_set_bus_arrangements(v3_audio_processor p, int32_t in_count, const uint64_t* inputs, int32_t out_count, const uint64_t* outputs);
+int  v3_audio_processor_get_latency(v3_audio_processor p);
+int  v3_audio_processor_get_tail(v3_audio_processor p);
 
 // Process (float32, deinterleaved channel pointers)
 int  v3_audio_processor_process_f32(v3_audio_processor p,
@@ -66,6 +177,24 @@ int  v3_audio_processor_process_f32(v3_audio_processor p,
     float** outputs, int32_t out_channels,
     int32_t num_samples);
 
+// Process with input events and parameter changes. Points of one parameter are
+// queued in the order given, so pass them sorted by sample offset.
+int  v3_audio_processor_process_f32_ex(v3_audio_processor p,
+    const float** inputs, int32_t in_channels,
+    float** outputs, int32_t out_channels,
+    int32_t num_samples,
+    const v3_event* events, int32_t num_events,
+    const v3_param_point* params, int32_t num_params);
+
+// As _ex, with the block's transport; `context` may be null.
+int  v3_audio_processor_process_f32_ctx(v3_audio_processor p,
+    const float** inputs, int32_t in_channels,
+    float** outputs, int32_t out_channels,
+    int32_t num_samples,
+    const v3_event* events, int32_t num_events,
+    const v3_param_point* params, int32_t num_params,
+    const v3_process_context* context);
+
 #ifdef __cplusplus
 }
 #endif
@@ -74,17 +203,20 @@ int  v3_audio_processor_process_f32(v3_audio_processor p,
 
     let impl_cpp = format!(
         r#"
+#include <algorithm>
+#include <cstdlib>
 #include <cstring>
 #include <vector>
-#include <string>
 
 // VST3 SDK headers
+#include <pluginterfaces/base/ibstream.h>
 #include <pluginterfaces/base/ipluginbase.h>
 #include <pluginterfaces/base/funknown.h>
-#include <pluginterfaces/base/futils.h>
-#include <pluginterfaces/base/ustring.h>
 #include <pluginterfaces/vst/ivstcomponent.h>
 #include <pluginterfaces/vst/ivstaudioprocessor.h>
+#include <pluginterfaces/vst/ivsteditcontroller.h>
+#include <pluginterfaces/vst/ivstevents.h>
+#include <pluginterfaces/vst/ivstparameterchanges.h>
 #include <pluginterfaces/vst/ivstprocesscontext.h>
 #include <pluginterfaces/vst/vsttypes.h>
 
@@ -93,26 +225,105 @@ int  v3_audio_processor_process_f32(v3_audio_processor p,
 using namespace Steinberg;
 using namespace Steinberg::Vst;
 
-static inline FUID fromBytes(const uint8_t b[16]) {{ return FUID(b[0],b[1],b[2],b[3], b[4],b[5],b[6],b[7], b[8],b[9],b[10],b[11], b[12],b[13],b[14],b[15]); }}
+static thread_local tresult lastTresult = kResultOk;
+
+extern "C" int32_t v3_last_tresult(void) {{
+    return lastTresult;
+}}
+
+// V3_ERR_TRESULT, keeping `r` for v3_last_tresult.
+static int failed(tresult r) {{
+    lastTresult = r;
+    return V3_ERR_TRESULT;
+}}
+
+static int check(tresult r) {{
+    return r == kResultOk ? 0 : failed(r);
+}}
+
+// A queryInterface that produced nothing; a null object with kResultOk counts too.
+static int missing(tresult r) {{
+    lastTresult = r == kResultOk ? kNoInterface : r;
+    return V3_ERR_NO_INTERFACE;
+}}
+
+// createInstance fails with kNoInterface for a class or interface it does not
+// have; anything else is the plugin refusing.
+static int createFailed(tresult r) {{
+    return r == kResultOk || r == kNoInterface ? missing(r) : failed(r);
+}}
+
+// Interfaces are queried with the header-inline `*_iid` constants rather than the
+// `::iid` statics, which are only defined in SDK sources this shim does not compile.
+static IComponent* componentOf(FUnknown* obj) {{
+    IComponent* comp = nullptr;
+    if (obj->queryInterface(IComponent_iid, (void**)&comp) != kResultOk) return nullptr;
+    return comp;
+}}
+
+// The Rust side mirrors these layouts byte for byte.
+static_assert(sizeof(v3_class_info) == 208, "v3_class_info layout");
+static_assert(sizeof(v3_bus_info) == 148, "v3_bus_info layout");
+static_assert(sizeof(v3_param_info) == 408, "v3_param_info layout");
+static_assert(sizeof(v3_event) == 28, "v3_event layout");
+static_assert(sizeof(v3_param_point) == 16, "v3_param_point layout");
+static_assert(sizeof(v3_process_context) == 80, "v3_process_context layout");
+
+// UTF-16 (String128) to NUL-terminated UTF-8 in `out`; stops before a code point
+// that would not fit, so the result is never a partial sequence.
+static void utf16ToUtf8(const char16* in, int32 inLen, char* out, size_t outSize) {{
+    size_t n = 0;
+    for (int32 i = 0; i < inLen && in[i] != 0; ++i) {{
+        uint32_t c = static_cast<uint16_t>(in[i]);
+        if (c >= 0xD800 && c <= 0xDBFF && i + 1 < inLen) {{
+            uint32_t lo = static_cast<uint16_t>(in[i + 1]);
+            if (lo >= 0xDC00 && lo <= 0xDFFF) {{
+                c = 0x10000 + ((c - 0xD800) << 10) + (lo - 0xDC00);
+                ++i;
+            }}
+        }}
+        // Unpaired surrogates become U+FFFD.
+        if (c >= 0xD800 && c <= 0xDFFF) c = 0xFFFD;
+        char buf[4];
+        size_t len;
+        if (c < 0x80) {{
+            buf[0] = static_cast<char>(c);
+            len = 1;
+        }} else if (c < 0x800) {{
+            buf[0] = static_cast<char>(0xC0 | (c >> 6));
+            buf[1] = static_cast<char>(0x80 | (c & 0x3F));
+            len = 2;
+        }} else if (c < 0x10000) {{
+            buf[0] = static_cast<char>(0xE0 | (c >> 12));
+            buf[1] = static_cast<char>(0x80 | ((c >> 6) & 0x3F));
+            buf[2] = static_cast<char>(0x80 | (c & 0x3F));
+            len = 3;
+        }} else {{
+            buf[0] = static_cast<char>(0xF0 | (c >> 18));
+            buf[1] = static_cast<char>(0x80 | ((c >> 12) & 0x3F));
+            buf[2] = static_cast<char>(0x80 | ((c >> 6) & 0x3F));
+            buf[3] = static_cast<char>(0x80 | (c & 0x3F));
+            len = 4;
+        }}
+        if (n + len >= outSize) break;
+        std::memcpy(out + n, buf, len);
+        n += len;
+    }}
+    out[n] = 0;
+}}
 
 extern "C" int v3_factory_class_count(void* f) {{
     auto* fac = reinterpret_cast<IPluginFactory*>(f);
-    if (!fac) return -1;
-    int32 count = 0;
-    if (auto* f2 = FUnknownPtr<IPluginFactory2>(fac)) {{
-        count = f2->countClasses();
-    }} else {{
-        count = fac->countClasses();
-    }}
-    return (int)count;
+    if (!fac) return V3_ERR_NULL_HANDLE;
+    return (int)fac->countClasses();
 }}
 
 extern "C" int v3_factory_class_info(void* f, int idx, v3_class_info* out_info) {{
     auto* fac = reinterpret_cast<IPluginFactory*>(f);
-    if (!fac || !out_info) return -1;
+    if (!fac || !out_info) return V3_ERR_NULL_HANDLE;
     PClassInfo info{{}};
     tresult r = fac->getClassInfo((int32)idx, &info);
-    if (r != kResultOk) return -2;
+    if (r != kResultOk) return failed(r);
     std::memset(out_info, 0, sizeof(*out_info));
     std::strncpy(out_info->category, info.category, sizeof(out_info->category)-1);
     std::strncpy(out_info->name, info.name, sizeof(out_info->name)-1);
@@ -120,137 +331,483 @@ extern "C" int v3_factory_class_info(void* f, int idx, v3_class_info* out_info)
     return 0;
 }}
 
+// Creates the component, then queries its processor. Both outputs hold their own
+// reference; release each with v3_release.
 extern "C" int v3_factory_create_audio_processor(void* f, const uint8_t cid_b[16], void** out_proc, void** out_comp) {{
-    if (!f || !cid_b || !out_proc || !out_comp) return -1;
+    if (!f || !cid_b || !out_proc || !out_comp) return V3_ERR_NULL_HANDLE;
     auto* fac = reinterpret_cast<IPluginFactory*>(f);
-    FUID cid = fromBytes(cid_b);
-
-    FUnknown* unk = nullptr;
-    tresult r = fac->createInstance(cid, Vst::IAudioProcessor::iid, (void**)&unk);
-    if (r != kResultOk || !unk) return -2;
-    auto* proc = FUnknownPtr<Vst::IAudioProcessor>(unk);
-    auto* comp = FUnknownPtr<Vst::IComponent>(unk);
-    if (!proc || !comp) {{ if (unk) unk->release(); return -3; }}
-
-    *out_proc = proc.getInterface();
-    *out_comp = comp.getInterface();
+    IComponent* comp = nullptr;
+    tresult r = fac->createInstance(reinterpret_cast<FIDString>(cid_b), IComponent_iid, (void**)&comp);
+    if (r != kResultOk || !comp) return createFailed(r);
+    IAudioProcessor* proc = nullptr;
+    r = comp->queryInterface(IAudioProcessor_iid, (void**)&proc);
+    if (r != kResultOk || !proc) {{
+        comp->release();
+        return missing(r);
+    }}
+    *out_proc = proc;
+    *out_comp = comp;
     return 0;
 }}
 
 extern "C" int v3_release(void* o) {{
-    if (!o) return -1;
+    if (!o) return V3_ERR_NULL_HANDLE;
     auto* u = reinterpret_cast<FUnknown*>(o);
     return (int)u->release();
 }}
 
 extern "C" int v3_component_initialize(void* c) {{
-    if (!c) return -1;
+    if (!c) return V3_ERR_NULL_HANDLE;
     auto* comp = reinterpret_cast<IComponent*>(c);
-    return comp->initialize(nullptr) == kResultOk ? 0 : -2;
+    return check(comp->initialize(nullptr));
 }}
 
 extern "C" int v3_component_set_active(void* c, int state) {{
-    if (!c) return -1;
+    if (!c) return V3_ERR_NULL_HANDLE;
     auto* comp = reinterpret_cast<IComponent*>(c);
-    return comp->setActive(state ? true : false) == kResultOk ? 0 : -2;
+    return check(comp->setActive(state ? true : false));
 }}
 
 extern "C" int v3_component_terminate(void* c) {{
-    if (!c) return -1;
+    if (!c) return V3_ERR_NULL_HANDLE;
     auto* comp = reinterpret_cast<IComponent*>(c);
-    return comp->terminate() == kResultOk ? 0 : -2;
+    return check(comp->terminate());
 }}
 
-extern "C" int v3_component_get_bus_count(void* c, int32 media_type, int32 direction) {{
-    if (!c) return -1;
+extern "C" int v3_component_get_bus_count(void* c, int32_t media_type, int32_t direction) {{
+    if (!c) return V3_ERR_NULL_HANDLE;
     auto* comp = reinterpret_cast<IComponent*>(c);
-    return (int)comp->getBusCount((MediaTypes)media_type, (BusDirections)direction);
+    return (int)comp->getBusCount(media_type, direction);
 }}
 
-extern "C" int v3_component_get_bus_info(void* c, int32 media_type, int32 direction, int32 index, v3_bus_info* out_info) {{
-    if (!c || !out_info) return -1;
+extern "C" int v3_component_get_bus_info(void* c, int32_t media_type, int32_t direction, int32_t index, v3_bus_info* out_info) {{
+    if (!c || !out_info) return V3_ERR_NULL_HANDLE;
     auto* comp = reinterpret_cast<IComponent*>(c);
     BusInfo info{{}};
-    tresult r = comp->getBusInfo((MediaTypes)media_type, (BusDirections)direction, index, info);
-    if (r != kResultOk) return -2;
+    tresult r = comp->getBusInfo(media_type, direction, index, info);
+    if (r != kResultOk) return failed(r);
     std::memset(out_info, 0, sizeof(*out_info));
-    out_info->media_type = media_type;
-    out_info->direction = direction;
+    out_info->media_type = info.mediaType;
+    out_info->direction = info.direction;
     out_info->channel_count = info.channelCount;
     out_info->bus_type = info.busType;
     out_info->flags = info.flags;
-    Steinberg::String name(info.name);
-    name.toMultiByte(kCP_UTF8, out_info->name, sizeof(out_info->name));
+    utf16ToUtf8(info.name, 128, out_info->name, sizeof(out_info->name));
     return 0;
 }}
 
-extern "C" int v3_component_activate_bus(void* c, int32 media_type, int32 direction, int32 index, int state) {{
-    if (!c) return -1;
+extern "C" int v3_component_activate_bus(void* c, int32_t media_type, int32_t direction, int32_t index, int state) {{
+    if (!c) return V3_ERR_NULL_HANDLE;
     auto* comp = reinterpret_cast<IComponent*>(c);
-    return comp->activateBus((MediaTypes)media_type, (BusDirections)direction, index, state ? true : false) == kResultOk ? 0 : -2;
+    return check(comp->activateBus(media_type, direction, index, state ? true : false));
+}}
+
+extern "C" int v3_component_get_controller_cid(void* c, uint8_t out_cid[16]) {{
+    if (!c || !out_cid) return V3_ERR_NULL_HANDLE;
+    auto* comp = reinterpret_cast<IComponent*>(c);
+    TUID cid = {{0}};
+    tresult r = comp->getControllerClassId(cid);
+    if (r != kResultOk) return failed(r);
+    static const TUID none = {{0}};
+    if (std::memcmp(cid, none, sizeof(TUID)) == 0) return V3_ERR_NO_CONTROLLER;
+    std::memcpy(out_cid, cid, 16);
+    return 0;
+}}
+
+extern "C" int v3_component_get_audio_processor(void* c, void** out_proc) {{
+    if (!c || !out_proc) return V3_ERR_NULL_HANDLE;
+    auto* comp = reinterpret_cast<IComponent*>(c);
+    IAudioProcessor* proc = nullptr;
+    tresult r = comp->queryInterface(IAudioProcessor_iid, (void**)&proc);
+    if (r != kResultOk || !proc) return missing(r);
+    *out_proc = proc;
+    return 0;
+}}
+
+extern "C" int v3_factory_create_edit_controller(void* f, const uint8_t cid_b[16], void** out_ctrl) {{
+    if (!f || !cid_b || !out_ctrl) return V3_ERR_NULL_HANDLE;
+    auto* fac = reinterpret_cast<IPluginFactory*>(f);
+    IEditController* ctrl = nullptr;
+    tresult r = fac->createInstance(reinterpret_cast<FIDString>(cid_b), IEditController_iid, (void**)&ctrl);
+    if (r != kResultOk || !ctrl) return createFailed(r);
+    *out_ctrl = ctrl;
+    return 0;
+}}
+
+extern "C" int v3_controller_initialize(void* e) {{
+    if (!e) return V3_ERR_NULL_HANDLE;
+    return check(reinterpret_cast<IEditController*>(e)->initialize(nullptr));
+}}
+
+extern "C" int v3_controller_terminate(void* e) {{
+    if (!e) return V3_ERR_NULL_HANDLE;
+    return check(reinterpret_cast<IEditController*>(e)->terminate());
+}}
+
+extern "C" int v3_controller_param_count(void* e) {{
+    if (!e) return V3_ERR_NULL_HANDLE;
+    return (int)reinterpret_cast<IEditController*>(e)->getParameterCount();
+}}
+
+extern "C" int v3_controller_param_info(void* e, int32_t index, v3_param_info* out_info) {{
+    if (!e || !out_info) return V3_ERR_NULL_HANDLE;
+    auto* ctrl = reinterpret_cast<IEditController*>(e);
+    ParameterInfo info{{}};
+    tresult r = ctrl->getParameterInfo(index, info);
+    if (r != kResultOk) return failed(r);
+    std::memset(out_info, 0, sizeof(*out_info));
+    out_info->id = info.id;
+    utf16ToUtf8(info.title, 128, out_info->title, sizeof(out_info->title));
+    utf16ToUtf8(info.shortTitle, 128, out_info->short_title, sizeof(out_info->short_title));
+    utf16ToUtf8(info.units, 128, out_info->units, sizeof(out_info->units));
+    out_info->step_count = info.stepCount;
+    out_info->default_normalized = info.defaultNormalizedValue;
+    out_info->unit_id = info.unitId;
+    out_info->flags = info.flags;
+    return 0;
+}}
+
+extern "C" int v3_controller_get_param_normalized(void* e, uint32_t id, double* out_value) {{
+    if (!e || !out_value) return V3_ERR_NULL_HANDLE;
+    *out_value = reinterpret_cast<IEditController*>(e)->getParamNormalized(id);
+    return 0;
+}}
+
+extern "C" int v3_controller_set_param_normalized(void* e, uint32_t id, double value) {{
+    if (!e) return V3_ERR_NULL_HANDLE;
+    return check(reinterpret_cast<IEditController*>(e)->setParamNormalized(id, value));
+}}
+
+extern "C" int v3_controller_normalized_to_plain(void* e, uint32_t id, double normalized, double* out_plain) {{
+    if (!e || !out_plain) return V3_ERR_NULL_HANDLE;
+    *out_plain = reinterpret_cast<IEditController*>(e)->normalizedParamToPlain(id, normalized);
+    return 0;
 }}
 
-extern "C" int v3_audio_processor_setup(void* p, double sample_rate, int32 max_block, int32 in_channels, int32 out_channels) {{
-    if (!p) return -1;
+extern "C" int v3_audio_processor_setup(void* p, double sample_rate, int32_t max_block, int32_t in_channels, int32_t out_channels) {{
+    if (!p) return V3_ERR_NULL_HANDLE;
+    // Channel counts are negotiated with v3_audio_processor_set_bus_arrangements.
+    (void)in_channels;
+    (void)out_channels;
     auto* proc = reinterpret_cast<IAudioProcessor*>(p);
     ProcessSetup setup{{}};
     setup.processMode = kRealtime;
     setup.symbolicSampleSize = kSample32;
     setup.maxSamplesPerBlock = max_block;
     setup.sampleRate = sample_rate;
-    if (proc->setupProcessing(setup) != kResultOk) return -2;
+    tresult r = proc->setupProcessing(setup);
+    if (r != kResultOk) return failed(r);
 
-    auto* comp = FUnknownPtr<IComponent>(proc);
-    if (comp) {{
+    if (IComponent* comp = componentOf(proc)) {{
         // Try to activate main input/output buses
         comp->setActive(true);
+        comp->release();
     }}
     return 0;
 }}
 
 extern "C" int v3_audio_processor_set_active(void* p, int state) {{
-    if (!p) return -1;
-    auto* proc = reinterpret_cast<IAudioProcessor*>(p);
-    return proc->setActive(state?true:false) == kResultOk ? 0 : -2;
+    if (!p) return V3_ERR_NULL_HANDLE;
+    // setActive lives on the component, not the processor.
+    IComponent* comp = nullptr;
+    tresult r = reinterpret_cast<IAudioProcessor*>(p)->queryInterface(IComponent_iid, (void**)&comp);
+    if (r != kResultOk || !comp) return missing(r);
+    r = comp->setActive(state ? true : false);
+    comp->release();
+    return check(r);
 }}
 
 extern "C" int v3_audio_processor_set_processing(void* p, int state) {{
-    if (!p) return -1;
+    if (!p) return V3_ERR_NULL_HANDLE;
+    auto* proc = reinterpret_cast<IAudioProcessor*>(p);
+    return check(proc->setProcessing(state ? true : false));
+}}
+
+extern "C" int v3_audio_processor_get_latency(void* p) {{
+    if (!p) return V3_ERR_NULL_HANDLE;
     auto* proc = reinterpret_cast<IAudioProcessor*>(p);
-    return proc->setProcessing(state ? true : false) == kResultOk ? 0 : -2;
+    uint32 latency = proc->getLatencySamples();
+    return latency > (uint32)V3_INFINITE_TAIL ? V3_INFINITE_TAIL : (int)latency;
 }}
 
-extern "C" int v3_audio_processor_get_bus_arrangements(void* p, int32 in_count, uint64_t* inputs, int32 out_count, uint64_t* outputs) {{
-    if (!p) return -1;
-    if ((in_count > 0 && !inputs) || (out_count > 0 && !outputs)) return -2;
+extern "C" int v3_audio_processor_get_tail(void* p) {{
+    if (!p) return V3_ERR_NULL_HANDLE;
     auto* proc = reinterpret_cast<IAudioProcessor*>(p);
-    auto* ins = reinterpret_cast<SpeakerArrangement*>(inputs);
-    auto* outs = reinterpret_cast<SpeakerArrangement*>(outputs);
-    return proc->getBusArrangements(ins, in_count, outs, out_count) == kResultOk ? 0 : -3;
+    uint32 tail = proc->getTailSamples();
+    return tail > (uint32)V3_INFINITE_TAIL ? V3_INFINITE_TAIL : (int)tail;
 }}
 
-extern "C" int v3_audio_processor_set_bus_arrangements(void* p, int32 in_count, const uint64_t* inputs, int32 out_count, const uint64_t* outputs) {{
-    if (!p) return -1;
-    if ((in_count > 0 && !inputs) || (out_count > 0 && !outputs)) return -2;
+// IAudioProcessor reports one bus at a time; fills `in_count` inputs then
+// `out_count` outputs.
+extern "C" int v3_audio_processor_get_bus_arrangements(void* p, int32_t in_count, uint64_t* inputs, int32_t out_count, uint64_t* outputs) {{
+    if (!p) return V3_ERR_NULL_HANDLE;
+    if ((in_count > 0 && !inputs) || (out_count > 0 && !outputs)) return V3_ERR_INVALID_ARG;
     auto* proc = reinterpret_cast<IAudioProcessor*>(p);
-    std::vector<SpeakerArrangement> ins;
-    std::vector<SpeakerArrangement> outs;
-    ins.reserve(in_count);
-    outs.reserve(out_count);
     for (int32 i = 0; i < in_count; ++i) {{
-        ins.push_back(static_cast<SpeakerArrangement>(inputs[i]));
+        SpeakerArrangement arr = 0;
+        tresult r = proc->getBusArrangement(kInput, i, arr);
+        if (r != kResultOk) return failed(r);
+        inputs[i] = static_cast<uint64_t>(arr);
     }}
     for (int32 i = 0; i < out_count; ++i) {{
-        outs.push_back(static_cast<SpeakerArrangement>(outputs[i]));
+        SpeakerArrangement arr = 0;
+        tresult r = proc->getBusArrangement(kOutput, i, arr);
+        if (r != kResultOk) return failed(r);
+        outputs[i] = static_cast<uint64_t>(arr);
     }}
-    return proc->setBusArrangements(ins.data(), in_count, outs.data(), out_count) == kResultOk ? 0 : -3;
+    return 0;
 }}
 
-extern "C" int v3_audio_processor_process_f32(void* p,
-    const float** inputs, int32 in_channels,
-    float** outputs, int32 out_channels,
-    int32 num_samples) {{
-    if (!p) return -1;
+extern "C" int v3_audio_processor_set_bus_arrangements(void* p, int32_t in_count, const uint64_t* inputs, int32_t out_count, const uint64_t* outputs) {{
+    if (!p) return V3_ERR_NULL_HANDLE;
+    if (in_count < 0 || out_count < 0) return V3_ERR_INVALID_ARG;
+    if ((in_count > 0 && !inputs) || (out_count > 0 && !outputs)) return V3_ERR_INVALID_ARG;
+    auto* proc = reinterpret_cast<IAudioProcessor*>(p);
+    // setBusArrangements takes mutable arrays; pass copies.
+    std::vector<SpeakerArrangement> ins(inputs, inputs + in_count);
+    std::vector<SpeakerArrangement> outs(outputs, outputs + out_count);
+    return check(proc->setBusArrangements(ins.data(), in_count, outs.data(), out_count));
+}}
+
+// Host-side lists for one process call. They live on the caller's stack for the
+// duration of process(), so reference counting is a no-op.
+static bool iidIs(const TUID a, const TUID b) {{
+    return std::memcmp(a, b, sizeof(TUID)) == 0;
+}}
+
+class ShimEventList : public IEventList {{
+public:
+    std::vector<Event> events;
+
+    tresult PLUGIN_API queryInterface(const TUID iid, void** obj) override {{
+        if (iidIs(iid, IEventList_iid) || iidIs(iid, FUnknown_iid)) {{
+            *obj = this;
+            return kResultOk;
+        }}
+        *obj = nullptr;
+        return kNoInterface;
+    }}
+    uint32 PLUGIN_API addRef() override {{ return 1; }}
+    uint32 PLUGIN_API release() override {{ return 1; }}
+
+    int32 PLUGIN_API getEventCount() override {{ return (int32)events.size(); }}
+    tresult PLUGIN_API getEvent(int32 index, Event& e) override {{
+        if (index < 0 || index >= (int32)events.size()) return kInvalidArgument;
+        e = events[index];
+        return kResultOk;
+    }}
+    tresult PLUGIN_API addEvent(Event&) override {{ return kResultFalse; }}
+}};
+
+class ShimParamQueue : public IParamValueQueue {{
+public:
+    ParamID id = 0;
+    std::vector<std::pair<int32, ParamValue>> points;
+
+    tresult PLUGIN_API queryInterface(const TUID iid, void** obj) override {{
+        if (iidIs(iid, IParamValueQueue_iid) || iidIs(iid, FUnknown_iid)) {{
+            *obj = this;
+            return kResultOk;
+        }}
+        *obj = nullptr;
+        return kNoInterface;
+    }}
+    uint32 PLUGIN_API addRef() override {{ return 1; }}
+    uint32 PLUGIN_API release() override {{ return 1; }}
+
+    ParamID PLUGIN_API getParameterId() override {{ return id; }}
+    int32 PLUGIN_API getPointCount() override {{ return (int32)points.size(); }}
+    tresult PLUGIN_API getPoint(int32 index, int32& sampleOffset, ParamValue& value) override {{
+        if (index < 0 || index >= (int32)points.size()) return kInvalidArgument;
+        sampleOffset = points[index].first;
+        value = points[index].second;
+        return kResultOk;
+    }}
+    tresult PLUGIN_API addPoint(int32, ParamValue, int32&) override {{ return kResultFalse; }}
+}};
+
+class ShimParameterChanges : public IParameterChanges {{
+public:
+    std::vector<ShimParamQueue> queues;
+
+    tresult PLUGIN_API queryInterface(const TUID iid, void** obj) override {{
+        if (iidIs(iid, IParameterChanges_iid) || iidIs(iid, FUnknown_iid)) {{
+            *obj = this;
+            return kResultOk;
+        }}
+        *obj = nullptr;
+        return kNoInterface;
+    }}
+    uint32 PLUGIN_API addRef() override {{ return 1; }}
+    uint32 PLUGIN_API release() override {{ return 1; }}
+
+    int32 PLUGIN_API getParameterCount() override {{ return (int32)queues.size(); }}
+    IParamValueQueue* PLUGIN_API getParameterData(int32 index) override {{
+        if (index < 0 || index >= (int32)queues.size()) return nullptr;
+        return &queues[index];
+    }}
+    IParamValueQueue* PLUGIN_API addParameterData(const ParamID&, int32& index) override {{
+        index = -1;
+        return nullptr;
+    }}
+}};
+
+// A seekable in-memory IBStream for get/setState. Like the lists above it only
+// lives for the duration of the call.
+class ShimStream : public IBStream {{
+public:
+    std::vector<uint8_t> data;
+    int64 pos = 0;
+
+    tresult PLUGIN_API queryInterface(const TUID iid, void** obj) override {{
+        if (iidIs(iid, IBStream_iid) || iidIs(iid, FUnknown_iid)) {{
+            *obj = this;
+            return kResultOk;
+        }}
+        *obj = nullptr;
+        return kNoInterface;
+    }}
+    uint32 PLUGIN_API addRef() override {{ return 1; }}
+    uint32 PLUGIN_API release() override {{ return 1; }}
+
+    tresult PLUGIN_API read(void* buffer, int32 numBytes, int32* numBytesRead) override {{
+        if (numBytes < 0 || (numBytes > 0 && !buffer)) return kInvalidArgument;
+        int64 avail = (int64)data.size() - pos;
+        int32 n = (int32)std::max<int64>(0, std::min<int64>(numBytes, avail));
+        if (n > 0) std::memcpy(buffer, data.data() + pos, n);
+        pos += n;
+        if (numBytesRead) *numBytesRead = n;
+        return kResultOk;
+    }}
+    tresult PLUGIN_API write(void* buffer, int32 numBytes, int32* numBytesWritten) override {{
+        if (numBytes < 0 || (numBytes > 0 && !buffer)) return kInvalidArgument;
+        if (pos + numBytes > (int64)data.size()) data.resize(pos + numBytes);
+        if (numBytes > 0) std::memcpy(data.data() + pos, buffer, numBytes);
+        pos += numBytes;
+        if (numBytesWritten) *numBytesWritten = numBytes;
+        return kResultOk;
+    }}
+    tresult PLUGIN_API seek(int64 offset, int32 mode, int64* result) override {{
+        int64 base = mode == kIBSeekSet ? 0
+                   : mode == kIBSeekCur ? pos
+                   : mode == kIBSeekEnd ? (int64)data.size()
+                   : -1;
+        if (base < 0 || base + offset < 0) return kInvalidArgument;
+        pos = base + offset;
+        if (result) *result = pos;
+        return kResultOk;
+    }}
+    tresult PLUGIN_API tell(int64* result) override {{
+        if (!result) return kInvalidArgument;
+        *result = pos;
+        return kResultOk;
+    }}
+}};
+
+extern "C" int v3_component_get_state(void* c, uint8_t** out_buf, int32_t* out_len) {{
+    if (!c || !out_buf || !out_len) return V3_ERR_NULL_HANDLE;
+    *out_buf = nullptr;
+    *out_len = 0;
+    auto* comp = reinterpret_cast<IComponent*>(c);
+    ShimStream stream;
+    tresult r = comp->getState(&stream);
+    if (r != kResultOk) return failed(r);
+    if (stream.data.size() > (size_t)INT32_MAX) return failed(kOutOfMemory);
+    if (stream.data.empty()) return 0;
+    auto* buf = static_cast<uint8_t*>(std::malloc(stream.data.size()));
+    if (!buf) return failed(kOutOfMemory);
+    std::memcpy(buf, stream.data.data(), stream.data.size());
+    *out_buf = buf;
+    *out_len = (int32_t)stream.data.size();
+    return 0;
+}}
+
+extern "C" int v3_component_set_state(void* c, const uint8_t* buf, int32_t len) {{
+    if (!c) return V3_ERR_NULL_HANDLE;
+    if (len < 0 || (len > 0 && !buf)) return V3_ERR_INVALID_ARG;
+    auto* comp = reinterpret_cast<IComponent*>(c);
+    ShimStream stream;
+    if (len > 0) stream.data.assign(buf, buf + len);
+    return check(comp->setState(&stream));
+}}
+
+extern "C" void v3_buffer_free(uint8_t* buf) {{
+    std::free(buf);
+}}
+
+static Event toSdkEvent(const v3_event& in) {{
+    Event e{{}};
+    e.busIndex = in.bus_index;
+    e.sampleOffset = in.sample_offset;
+    if (in.type == V3_EVENT_NOTE_ON) {{
+        e.type = Event::kNoteOnEvent;
+        e.noteOn.channel = in.channel;
+        e.noteOn.pitch = in.pitch;
+        e.noteOn.velocity = in.velocity;
+        e.noteOn.noteId = in.note_id;
+        e.noteOn.tuning = in.tuning;
+    }} else {{
+        e.type = Event::kNoteOffEvent;
+        e.noteOff.channel = in.channel;
+        e.noteOff.pitch = in.pitch;
+        e.noteOff.velocity = in.velocity;
+        e.noteOff.noteId = in.note_id;
+        e.noteOff.tuning = in.tuning;
+    }}
+    return e;
+}}
+
+// Field for field; a valid flag is set only when the caller set its V3_CTX_ bit.
+static ProcessContext toSdkContext(const v3_process_context& in) {{
+    ProcessContext ctx{{}};
+    ctx.sampleRate = in.sample_rate;
+    ctx.projectTimeSamples = in.project_time_samples;
+    if (in.flags & V3_CTX_PLAYING) ctx.state |= ProcessContext::kPlaying;
+    if (in.flags & V3_CTX_RECORDING) ctx.state |= ProcessContext::kRecording;
+    if (in.flags & V3_CTX_CYCLE_ACTIVE) ctx.state |= ProcessContext::kCycleActive;
+    if (in.flags & V3_CTX_TEMPO_VALID) {{
+        ctx.tempo = in.tempo;
+        ctx.state |= ProcessContext::kTempoValid;
+    }}
+    if (in.flags & V3_CTX_TIME_SIG_VALID) {{
+        ctx.timeSigNumerator = in.time_sig_numerator;
+        ctx.timeSigDenominator = in.time_sig_denominator;
+        ctx.state |= ProcessContext::kTimeSigValid;
+    }}
+    if (in.flags & V3_CTX_PROJECT_MUSIC_VALID) {{
+        ctx.projectTimeMusic = in.project_time_music;
+        ctx.state |= ProcessContext::kProjectTimeMusicValid;
+    }}
+    if (in.flags & V3_CTX_BAR_POSITION_VALID) {{
+        ctx.barPositionMusic = in.bar_position_music;
+        ctx.state |= ProcessContext::kBarPositionValid;
+    }}
+    if (in.flags & V3_CTX_CYCLE_VALID) {{
+        ctx.cycleStartMusic = in.cycle_start_music;
+        ctx.cycleEndMusic = in.cycle_end_music;
+        ctx.state |= ProcessContext::kCycleValid;
+    }}
+    if (in.flags & V3_CTX_CONT_TIME_VALID) {{
+        // Sic: the SDK spells this field "continous".
+        ctx.continousTimeSamples = in.continuous_time_samples;
+        ctx.state |= ProcessContext::kContTimeValid;
+    }}
+    return ctx;
+}}
+
+extern "C" int v3_audio_processor_process_f32_ctx(void* p,
+    const float** inputs, int32_t in_channels,
+    float** outputs, int32_t out_channels,
+    int32_t num_samples,
+    const v3_event* events, int32_t num_events,
+    const v3_param_point* params, int32_t num_params,
+    const v3_process_context* context) {{
+    if (!p) return V3_ERR_NULL_HANDLE;
+    if (num_events < 0 || num_params < 0) return V3_ERR_INVALID_ARG;
+    if ((num_events > 0 && !events) || (num_params > 0 && !params)) return V3_ERR_INVALID_ARG;
+    for (int32 i = 0; i < num_events; ++i) {{
+        if (events[i].type != V3_EVENT_NOTE_ON && events[i].type != V3_EVENT_NOTE_OFF) return V3_ERR_INVALID_ARG;
+    }}
     auto* proc = reinterpret_cast<IAudioProcessor*>(p);
 
     AudioBusBuffers inBuf{{}};
@@ -264,6 +821,28 @@ extern "C" int v3_audio_processor_process_f32(void* p,
     AudioBusBuffers inputsArr[1] = {{ inBuf }};
     AudioBusBuffers outputsArr[1] = {{ outBuf }};
 
+    ShimEventList eventList;
+    eventList.events.reserve(num_events);
+    for (int32 i = 0; i < num_events; ++i) {{
+        eventList.events.push_back(toSdkEvent(events[i]));
+    }}
+    // Plugins read events in order; keep same-offset events as given.
+    std::stable_sort(eventList.events.begin(), eventList.events.end(),
+        [](const Event& a, const Event& b) {{ return a.sampleOffset < b.sampleOffset; }});
+
+    ShimParameterChanges changes;
+    for (int32 i = 0; i < num_params; ++i) {{
+        const v3_param_point& pt = params[i];
+        auto queue = std::find_if(changes.queues.begin(), changes.queues.end(),
+            [&](const ShimParamQueue& q) {{ return q.id == pt.param_id; }});
+        if (queue == changes.queues.end()) {{
+            changes.queues.emplace_back();
+            queue = changes.queues.end() - 1;
+            queue->id = pt.param_id;
+        }}
+        queue->points.emplace_back(pt.sample_offset, pt.value);
+    }}
+
     ProcessData data{{}};
     data.numSamples = num_samples;
     data.numInputs =  in_channels > 0 ? 1 : 0;
@@ -272,8 +851,33 @@ extern "C" int v3_audio_processor_process_f32(void* p,
     data.outputs = out_channels > 0 ? outputsArr : nullptr;
     data.processMode = kRealtime;
     data.symbolicSampleSize = kSample32;
+    data.inputEvents = num_events > 0 ? &eventList : nullptr;
+    data.inputParameterChanges = num_params > 0 ? &changes : nullptr;
+    ProcessContext ctx{{}};
+    if (context) {{
+        ctx = toSdkContext(*context);
+        data.processContext = &ctx;
+    }}
+
+    return check(proc->process(data));
+}}
 
-    return proc->process(data) == kResultOk ? 0 : -2;
+extern "C" int v3_audio_processor_process_f32_ex(void* p,
+    const float** inputs, int32_t in_channels,
+    float** outputs, int32_t out_channels,
+    int32_t num_samples,
+    const v3_event* events, int32_t num_events,
+    const v3_param_point* params, int32_t num_params) {{
+    return v3_audio_processor_process_f32_ctx(p, inputs, in_channels, outputs, out_channels,
+        num_samples, events, num_events, params, num_params, nullptr);
+}}
+
+extern "C" int v3_audio_processor_process_f32(void* p,
+    const float** inputs, int32_t in_channels,
+    float** outputs, int32_t out_channels,
+    int32_t num_samples) {{
+    return v3_audio_processor_process_f32_ctx(p, inputs, in_channels, outputs, out_channels,
+        num_samples, nullptr, 0, nullptr, 0, nullptr);
 }}
 
 "#,
@@ -0,0 +1,11 @@
+// Flags a `shim` build without the SDK, so lib.rs fails with one readable error
+// instead of a screen of undefined `v3_*` symbols at link time.
+fn main() {
+    println!("cargo:rerun-if-env-changed=VST3_SDK_DIR");
+    println!("cargo:rustc-check-cfg=cfg(shim_without_sdk)");
+    if std::env::var_os("CARGO_FEATURE_SHIM").is_some()
+        && std::env::var_os("VST3_SDK_DIR").is_none()
+    {
+        println!("cargo:rustc-cfg=shim_without_sdk");
+    }
+}
@@ -1,7 +1,20 @@
 // FFI to the C shim + dynamic loader for GetPluginFactory
 #![allow(non_camel_case_types, non_snake_case, non_upper_case_globals)]
 
-use libloading::{Library, Symbol};
+use libloading::Library;
+
+#[cfg(shim_without_sdk)]
+compile_error!(
+    "the `shim` feature compiles the C++ shim against the Steinberg VST3 SDK: set \
+     VST3_SDK_DIR to your vst3sdk checkout, or drop the feature to use only the loader"
+);
+
+#[cfg(feature = "shim")]
+pub mod safe;
+
+// Pulls the compiled shim into the link; its symbols back the `extern` block below.
+#[cfg(feature = "shim")]
+use openvst3_shim as _;
 
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -22,25 +35,183 @@ pub struct v3_bus_info {
     pub name: [u8; 128],
 }
 
+/// `ParameterInfo` with NUL-terminated UTF-8 strings.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct v3_param_info {
+    pub id: u32,
+    pub title: [u8; 128],
+    pub short_title: [u8; 128],
+    pub units: [u8; 128],
+    pub step_count: i32,
+    pub default_normalized: f64,
+    pub unit_id: i32,
+    pub flags: i32,
+}
+
+pub const V3_EVENT_NOTE_ON: i32 = 0;
+pub const V3_EVENT_NOTE_OFF: i32 = 1;
+
+/// A note on or off for [`process_f32_ex`]; `tuning` is in cents, `note_id` -1 when
+/// unused.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct v3_event {
+    pub event_type: i32,
+    pub bus_index: i32,
+    pub sample_offset: i32,
+    pub channel: i16,
+    pub pitch: i16,
+    pub velocity: f32,
+    pub note_id: i32,
+    pub tuning: f32,
+}
+
+impl v3_event {
+    pub fn note_on(sample_offset: i32, channel: i16, pitch: i16, velocity: f32) -> Self {
+        Self {
+            event_type: V3_EVENT_NOTE_ON,
+            bus_index: 0,
+            sample_offset,
+            channel,
+            pitch,
+            velocity,
+            note_id: -1,
+            tuning: 0.0,
+        }
+    }
+
+    pub fn note_off(sample_offset: i32, channel: i16, pitch: i16, velocity: f32) -> Self {
+        Self {
+            event_type: V3_EVENT_NOTE_OFF,
+            ..Self::note_on(sample_offset, channel, pitch, velocity)
+        }
+    }
+}
+
+/// One point of a parameter's queue for the block; `value` is normalized.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct v3_param_point {
+    pub param_id: u32,
+    pub sample_offset: i32,
+    pub value: f64,
+}
+
+pub const V3_CTX_PLAYING: u32 = 1 << 0;
+pub const V3_CTX_RECORDING: u32 = 1 << 1;
+pub const V3_CTX_CYCLE_ACTIVE: u32 = 1 << 2;
+pub const V3_CTX_TEMPO_VALID: u32 = 1 << 3;
+pub const V3_CTX_TIME_SIG_VALID: u32 = 1 << 4;
+pub const V3_CTX_PROJECT_MUSIC_VALID: u32 = 1 << 5;
+pub const V3_CTX_BAR_POSITION_VALID: u32 = 1 << 6;
+pub const V3_CTX_CYCLE_VALID: u32 = 1 << 7;
+pub const V3_CTX_CONT_TIME_VALID: u32 = 1 << 8;
+
+/// What [`v3_audio_processor_get_tail`] returns for an infinite tail.
+pub const V3_INFINITE_TAIL: i32 = i32::MAX;
+
+// Shim status codes; the tens digit is the category. `safe::ShimError::from_code`
+// turns them into errors.
+/// A null handle or output pointer.
+pub const V3_ERR_NULL_HANDLE: i32 = -10;
+/// A negative count, missing array or unknown event type.
+pub const V3_ERR_INVALID_ARG: i32 = -11;
+/// Returned by the slice wrappers, without calling the shim, when a channel slice is
+/// shorter than `num_samples`.
+pub const V3_ERR_SHORT_CHANNEL: i32 = -12;
+/// The plugin returned a failing `tresult`; [`v3_last_tresult`] has it.
+pub const V3_ERR_TRESULT: i32 = -20;
+/// `queryInterface` or `createInstance` produced no object.
+pub const V3_ERR_NO_INTERFACE: i32 = -30;
+/// The component names no separate controller class.
+pub const V3_ERR_NO_CONTROLLER: i32 = -31;
+
+/// Transport for [`process_f32_ctx`]. The shim copies a field into the plugin's
+/// `ProcessContext` only when its `V3_CTX_*_VALID` bit is set in `flags`;
+/// `sample_rate` and `project_time_samples` are always passed. Musical positions are
+/// in quarter notes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct v3_process_context {
+    pub sample_rate: f64,
+    pub project_time_samples: i64,
+    pub continuous_time_samples: i64,
+    pub project_time_music: f64,
+    pub bar_position_music: f64,
+    pub cycle_start_music: f64,
+    pub cycle_end_music: f64,
+    pub tempo: f64,
+    pub time_sig_numerator: i32,
+    pub time_sig_denominator: i32,
+    pub flags: u32,
+}
+
+impl v3_process_context {
+    /// Stopped transport at `project_time_samples`, nothing else valid.
+    pub fn at(sample_rate: f64, project_time_samples: i64) -> Self {
+        Self {
+            sample_rate,
+            project_time_samples,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_tempo(mut self, bpm: f64) -> Self {
+        self.tempo = bpm;
+        self.flags |= V3_CTX_TEMPO_VALID;
+        self
+    }
+
+    pub fn with_time_sig(mut self, numerator: i32, denominator: i32) -> Self {
+        self.time_sig_numerator = numerator;
+        self.time_sig_denominator = denominator;
+        self.flags |= V3_CTX_TIME_SIG_VALID;
+        self
+    }
+
+    /// Project position and start of the current bar, in quarter notes.
+    pub fn with_music_position(mut self, project: f64, bar_start: f64) -> Self {
+        self.project_time_music = project;
+        self.bar_position_music = bar_start;
+        self.flags |= V3_CTX_PROJECT_MUSIC_VALID | V3_CTX_BAR_POSITION_VALID;
+        self
+    }
+
+    pub fn playing(mut self, playing: bool) -> Self {
+        if playing {
+            self.flags |= V3_CTX_PLAYING;
+        } else {
+            self.flags &= !V3_CTX_PLAYING;
+        }
+        self
+    }
+}
+
 pub type v3_factory = *mut core::ffi::c_void;
 pub type v3_component = *mut core::ffi::c_void;
 pub type v3_audio_processor = *mut core::ffi::c_void;
 pub type v3_funknown = *mut core::ffi::c_void;
+pub type v3_edit_controller = *mut core::ffi::c_void;
 pub type v3_speaker_arrangement = u64;
 
-pub const MEDIA_TYPE_AUDIO: i32 = 0;
-pub const MEDIA_TYPE_EVENT: i32 = 1;
-
-pub const BUS_DIRECTION_INPUT: i32 = 0;
-pub const BUS_DIRECTION_OUTPUT: i32 = 1;
-
-pub const BUS_TYPE_MAIN: i32 = 0;
-pub const BUS_TYPE_AUX: i32 = 1;
+// The bus constants are openvst3-abi's, so the two crates spell them the same.
+pub use openvst3_abi::{
+    BUS_DIR_INPUT, BUS_DIR_OUTPUT, BUS_FLAG_DEFAULT_ACTIVE, BUS_FLAG_IS_CONTROL_VOLTAGE,
+    BUS_TYPE_AUX, BUS_TYPE_MAIN, MEDIA_TYPE_AUDIO, MEDIA_TYPE_EVENT,
+};
 
-pub const BUS_FLAG_DEFAULT_ACTIVE: u32 = 1 << 0;
-pub const BUS_FLAG_IS_CONTROL_VOLTAGE: u32 = 1 << 1;
+#[deprecated(note = "use BUS_DIR_INPUT")]
+pub const BUS_DIRECTION_INPUT: i32 = BUS_DIR_INPUT;
+#[deprecated(note = "use BUS_DIR_OUTPUT")]
+pub const BUS_DIRECTION_OUTPUT: i32 = BUS_DIR_OUTPUT;
 
+#[cfg(feature = "shim")]
 extern "C" {
+    /// The `tresult` behind the last -2x or -3x status returned on this thread;
+    /// `kOutOfMemory` for the shim's own allocation failures.
+    pub fn v3_last_tresult() -> i32;
+
     pub fn v3_factory_class_count(f: v3_factory) -> i32;
     pub fn v3_factory_class_info(f: v3_factory, idx: i32, out_info: *mut v3_class_info) -> i32;
     pub fn v3_factory_create_audio_processor(
@@ -70,6 +241,42 @@ extern "C" {
         index: i32,
         state: i32,
     ) -> i32;
+    /// [`V3_ERR_NO_CONTROLLER`] when the component names no separate controller class.
+    pub fn v3_component_get_controller_cid(c: v3_component, out_cid: *mut u8) -> i32;
+    pub fn v3_component_get_audio_processor(
+        c: v3_component,
+        out_proc: *mut v3_audio_processor,
+    ) -> i32;
+    pub fn v3_component_get_state(c: v3_component, out_buf: *mut *mut u8, out_len: *mut i32)
+        -> i32;
+    pub fn v3_component_set_state(c: v3_component, buf: *const u8, len: i32) -> i32;
+    pub fn v3_buffer_free(buf: *mut u8);
+
+    pub fn v3_factory_create_edit_controller(
+        f: v3_factory,
+        cid: *const u8,
+        out_ctrl: *mut v3_edit_controller,
+    ) -> i32;
+    pub fn v3_controller_initialize(e: v3_edit_controller) -> i32;
+    pub fn v3_controller_terminate(e: v3_edit_controller) -> i32;
+    pub fn v3_controller_param_count(e: v3_edit_controller) -> i32;
+    pub fn v3_controller_param_info(
+        e: v3_edit_controller,
+        index: i32,
+        out_info: *mut v3_param_info,
+    ) -> i32;
+    pub fn v3_controller_get_param_normalized(
+        e: v3_edit_controller,
+        id: u32,
+        out_value: *mut f64,
+    ) -> i32;
+    pub fn v3_controller_set_param_normalized(e: v3_edit_controller, id: u32, value: f64) -> i32;
+    pub fn v3_controller_normalized_to_plain(
+        e: v3_edit_controller,
+        id: u32,
+        normalized: f64,
+        out_plain: *mut f64,
+    ) -> i32;
 
     pub fn v3_audio_processor_setup(
         p: v3_audio_processor,
@@ -95,6 +302,11 @@ extern "C" {
         outputs: *const v3_speaker_arrangement,
     ) -> i32;
 
+    /// `getLatencySamples`, or [`V3_ERR_NULL_HANDLE`].
+    pub fn v3_audio_processor_get_latency(p: v3_audio_processor) -> i32;
+    /// `getTailSamples`, [`V3_INFINITE_TAIL`] for `kInfiniteTail`, or
+    /// [`V3_ERR_NULL_HANDLE`].
+    pub fn v3_audio_processor_get_tail(p: v3_audio_processor) -> i32;
     pub fn v3_audio_processor_process_f32(
         p: v3_audio_processor,
         inputs: *const *const f32,
@@ -103,6 +315,136 @@ extern "C" {
         out_ch: i32,
         num_samples: i32,
     ) -> i32;
+    /// Returns 0, [`V3_ERR_NULL_HANDLE`], [`V3_ERR_TRESULT`] when `process` failed, or
+    /// [`V3_ERR_INVALID_ARG`] for bad event or parameter arrays.
+    pub fn v3_audio_processor_process_f32_ex(
+        p: v3_audio_processor,
+        inputs: *const *const f32,
+        in_ch: i32,
+        outputs: *mut *mut f32,
+        out_ch: i32,
+        num_samples: i32,
+        events: *const v3_event,
+        num_events: i32,
+        params: *const v3_param_point,
+        num_params: i32,
+    ) -> i32;
+    /// As [`v3_audio_processor_process_f32_ex`]; `context` may be null.
+    pub fn v3_audio_processor_process_f32_ctx(
+        p: v3_audio_processor,
+        inputs: *const *const f32,
+        in_ch: i32,
+        outputs: *mut *mut f32,
+        out_ch: i32,
+        num_samples: i32,
+        events: *const v3_event,
+        num_events: i32,
+        params: *const v3_param_point,
+        num_params: i32,
+        context: *const v3_process_context,
+    ) -> i32;
+}
+
+/// [`v3_audio_processor_process_f32`] over one slice per channel.
+///
+/// # Safety
+/// `p` must be a live processor from [`v3_factory_create_audio_processor`] that has
+/// been set up and is processing.
+#[cfg(feature = "shim")]
+pub unsafe fn process_f32(
+    p: v3_audio_processor,
+    inputs: &[&[f32]],
+    outputs: &mut [&mut [f32]],
+    num_samples: usize,
+) -> i32 {
+    process_f32_ex(p, inputs, outputs, num_samples, &[], &[])
+}
+
+/// [`v3_audio_processor_process_f32_ex`] over one slice per channel, with the block's
+/// events and parameter points (each parameter's points sorted by offset).
+///
+/// # Safety
+/// As for [`process_f32`].
+#[cfg(feature = "shim")]
+pub unsafe fn process_f32_ex(
+    p: v3_audio_processor,
+    inputs: &[&[f32]],
+    outputs: &mut [&mut [f32]],
+    num_samples: usize,
+    events: &[v3_event],
+    params: &[v3_param_point],
+) -> i32 {
+    process_f32_ctx(p, inputs, outputs, num_samples, events, params, None)
+}
+
+/// [`process_f32_ex`] with the block's transport.
+///
+/// # Safety
+/// As for [`process_f32`].
+#[cfg(feature = "shim")]
+pub unsafe fn process_f32_ctx(
+    p: v3_audio_processor,
+    inputs: &[&[f32]],
+    outputs: &mut [&mut [f32]],
+    num_samples: usize,
+    events: &[v3_event],
+    params: &[v3_param_point],
+    context: Option<&v3_process_context>,
+) -> i32 {
+    if inputs.iter().any(|c| c.len() < num_samples) || outputs.iter().any(|c| c.len() < num_samples)
+    {
+        return V3_ERR_SHORT_CHANNEL;
+    }
+    let ins: Vec<*const f32> = inputs.iter().map(|c| c.as_ptr()).collect();
+    let mut outs: Vec<*mut f32> = outputs.iter_mut().map(|c| c.as_mut_ptr()).collect();
+    v3_audio_processor_process_f32_ctx(
+        p,
+        ins.as_ptr(),
+        ins.len() as i32,
+        outs.as_mut_ptr(),
+        outs.len() as i32,
+        num_samples as i32,
+        events.as_ptr(),
+        events.len() as i32,
+        params.as_ptr(),
+        params.len() as i32,
+        context.map_or(core::ptr::null(), |c| c as *const _),
+    )
+}
+
+/// The component's state (`IComponent::getState`), copied out of the shim's buffer,
+/// which is freed before returning. `Err` carries the shim status.
+///
+/// # Safety
+/// `c` must be a live component from [`v3_factory_create_audio_processor`].
+#[cfg(feature = "shim")]
+pub unsafe fn get_component_state(c: v3_component) -> Result<Vec<u8>, i32> {
+    let mut buf = core::ptr::null_mut();
+    let mut len = 0;
+    let status = v3_component_get_state(c, &mut buf, &mut len);
+    if status < 0 {
+        return Err(status);
+    }
+    if buf.is_null() {
+        return Ok(Vec::new());
+    }
+    let state = core::slice::from_raw_parts(buf, len as usize).to_vec();
+    v3_buffer_free(buf);
+    Ok(state)
+}
+
+/// Load `state` into the component (`IComponent::setState`); returns the shim status,
+/// or [`V3_ERR_INVALID_ARG`] without calling the shim when `state` does not fit an
+/// `int32` length.
+///
+/// # Safety
+/// As for [`get_component_state`].
+#[cfg(feature = "shim")]
+pub unsafe fn set_component_state(c: v3_component, state: &[u8]) -> i32 {
+    let Ok(len) = i32::try_from(state.len()) else {
+        return V3_ERR_INVALID_ARG;
+    };
+    v3_component_set_state(c, state.as_ptr(), len)
 }
 
 // Loader for GetPluginFactory
@@ -110,12 +452,15 @@ pub type GetPluginFactoryFn = unsafe extern "C" fn() -> v3_factory;
 
 pub struct Vst3Lib {
     pub lib: Library,
-    pub get_factory: Symbol<GetPluginFactoryFn>,
+    /// Copied out of the library, which `lib` keeps loaded.
+    pub get_factory: GetPluginFactoryFn,
 }
 impl Vst3Lib {
+    /// # Safety
+    /// Loading runs the library's initializers; `path` must be a plugin binary you trust.
     pub unsafe fn load<P: AsRef<std::ffi::OsStr>>(path: P) -> Result<Self, libloading::Error> {
         let lib = Library::new(path)?;
-        let get_factory: Symbol<GetPluginFactoryFn> = lib.get(b"GetPluginFactory\0")?;
+        let get_factory = *lib.get::<GetPluginFactoryFn>(b"GetPluginFactory\0")?;
         Ok(Self { lib, get_factory })
     }
 }
@@ -0,0 +1,594 @@
+//! A thin safe layer over the shim functions.
+//!
+//! Each handle owns one reference and releases it on drop: a [`ShimProcessor`]
+//! borrows the [`ShimComponent`] it came from, so it always goes first, and every
+//! handle borrows the [`Vst3Lib`], so the library cannot be unloaded under them.
+//! Status codes become [`ShimError`]s naming the shim function that failed, and
+//! fixed-size UTF-8 buffers become `String`s. Anything not covered here is still
+//! reachable through `as_raw` and the `extern` functions.
+
+use core::cell::Cell;
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::ptr::null_mut;
+
+use thiserror::Error;
+
+use crate::*;
+
+/// A failed shim call and the function it came from. Each variant is one of the
+/// header's `V3_ERR_*` codes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Error)]
+#[non_exhaustive]
+pub enum ShimError {
+    /// [`V3_ERR_NULL_HANDLE`].
+    #[error("{0}: null handle or output pointer")]
+    NullHandle(&'static str),
+    /// [`V3_ERR_INVALID_ARG`]: rejected by the shim before calling the plugin.
+    #[error("{0}: invalid arguments")]
+    InvalidArgument(&'static str),
+    /// [`V3_ERR_SHORT_CHANNEL`]: found before calling the shim.
+    #[error("{0}: channel slice shorter than the block")]
+    ShortChannel(&'static str),
+    /// [`V3_ERR_TRESULT`], with the plugin's `tresult`.
+    #[error("{func}: plugin returned tresult {tresult}")]
+    Failed { func: &'static str, tresult: i32 },
+    /// [`V3_ERR_NO_INTERFACE`].
+    #[error("{0}: plugin does not provide the interface")]
+    NoInterface(&'static str),
+    /// [`V3_ERR_NO_CONTROLLER`].
+    #[error("{0}: component names no separate controller class")]
+    NoController(&'static str),
+    /// A negative status outside the convention.
+    #[error("{0}: status {1}")]
+    Status(&'static str, i32),
+}
+
+impl ShimError {
+    /// The error for status `code` from `func`. Call it on the thread that made the
+    /// call, before the next shim call: [`V3_ERR_TRESULT`] picks up the plugin's
+    /// `tresult` from [`v3_last_tresult`].
+    pub fn from_code(func: &'static str, code: i32) -> Self {
+        match code {
+            V3_ERR_NULL_HANDLE => Self::NullHandle(func),
+            V3_ERR_INVALID_ARG => Self::InvalidArgument(func),
+            V3_ERR_SHORT_CHANNEL => Self::ShortChannel(func),
+            V3_ERR_TRESULT => Self::Failed {
+                func,
+                tresult: unsafe { v3_last_tresult() },
+            },
+            V3_ERR_NO_INTERFACE => Self::NoInterface(func),
+            V3_ERR_NO_CONTROLLER => Self::NoController(func),
+            _ => Self::Status(func, code),
+        }
+    }
+
+    /// The shim function that failed.
+    pub fn func(&self) -> &'static str {
+        match *self {
+            Self::NullHandle(f)
+            | Self::InvalidArgument(f)
+            | Self::ShortChannel(f)
+            | Self::Failed { func: f, .. }
+            | Self::NoInterface(f)
+            | Self::NoController(f)
+            | Self::Status(f, _) => f,
+        }
+    }
+
+    /// The raw status code.
+    pub fn code(&self) -> i32 {
+        match *self {
+            Self::NullHandle(_) => V3_ERR_NULL_HANDLE,
+            Self::InvalidArgument(_) => V3_ERR_INVALID_ARG,
+            Self::ShortChannel(_) => V3_ERR_SHORT_CHANNEL,
+            Self::Failed { .. } => V3_ERR_TRESULT,
+            Self::NoInterface(_) => V3_ERR_NO_INTERFACE,
+            Self::NoController(_) => V3_ERR_NO_CONTROLLER,
+            Self::Status(_, code) => code,
+        }
+    }
+}
+
+/// `Ok(status)` for non-negative statuses, which some functions use as a count.
+fn check(func: &'static str, status: i32) -> Result<i32, ShimError> {
+    if status < 0 {
+        Err(ShimError::from_code(func, status))
+    } else {
+        Ok(status)
+    }
+}
+
+/// Bytes up to the first NUL, lossily decoded.
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// Release one reference to a shim object.
+fn release(obj: *mut c_void) {
+    unsafe {
+        v3_release(obj);
+    }
+}
+
+impl Vst3Lib {
+    /// The plugin factory; `None` when `GetPluginFactory` returns null.
+    pub fn factory(&self) -> Option<ShimFactory<'_>> {
+        let ptr = unsafe { (self.get_factory)() };
+        (!ptr.is_null()).then_some(ShimFactory {
+            ptr,
+            _lib: PhantomData,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShimClassInfo {
+    pub category: String,
+    pub name: String,
+    pub cid: [u8; 16],
+}
+
+pub struct ShimFactory<'lib> {
+    ptr: v3_factory,
+    _lib: PhantomData<&'lib Vst3Lib>,
+}
+
+impl<'lib> ShimFactory<'lib> {
+    pub fn as_raw(&self) -> v3_factory {
+        self.ptr
+    }
+
+    pub fn class_count(&self) -> usize {
+        unsafe { v3_factory_class_count(self.ptr) }.max(0) as usize
+    }
+
+    pub fn class_info(&self, index: usize) -> Result<ShimClassInfo, ShimError> {
+        let mut raw = core::mem::MaybeUninit::<v3_class_info>::zeroed();
+        check("v3_factory_class_info", unsafe {
+            v3_factory_class_info(self.ptr, index as i32, raw.as_mut_ptr())
+        })?;
+        let raw = unsafe { raw.assume_init() };
+        Ok(ShimClassInfo {
+            category: c_string(&raw.category),
+            name: c_string(&raw.name),
+            cid: raw.cid,
+        })
+    }
+
+    /// Create and initialize the component `cid`, which must also be an audio
+    /// processor.
+    pub fn create_component(&self, cid: &[u8; 16]) -> Result<ShimComponent<'lib>, ShimError> {
+        let (mut proc, mut comp) = (null_mut(), null_mut());
+        check("v3_factory_create_audio_processor", unsafe {
+            v3_factory_create_audio_processor(self.ptr, cid.as_ptr(), &mut proc, &mut comp)
+        })?;
+        // ShimComponent::processor queries its own reference when asked.
+        release(proc);
+        let component = ShimComponent {
+            ptr: comp,
+            active: Cell::new(false),
+            _lib: PhantomData,
+        };
+        check("v3_component_initialize", unsafe {
+            v3_component_initialize(comp)
+        })?;
+        Ok(component)
+    }
+
+    /// Create and initialize the edit controller class `cid`.
+    pub fn create_edit_controller(
+        &self,
+        cid: &[u8; 16],
+    ) -> Result<ShimController<'lib>, ShimError> {
+        let mut ptr = null_mut();
+        check("v3_factory_create_edit_controller", unsafe {
+            v3_factory_create_edit_controller(self.ptr, cid.as_ptr(), &mut ptr)
+        })?;
+        let controller = ShimController {
+            ptr,
+            _lib: PhantomData,
+        };
+        check("v3_controller_initialize", unsafe {
+            v3_controller_initialize(ptr)
+        })?;
+        Ok(controller)
+    }
+
+    /// The controller of a split plugin: the class `component` names, created and
+    /// initialized. Fails with [`ShimError::NoController`] (or the plugin's `tresult`)
+    /// for single-component plugins.
+    pub fn create_controller_for(
+        &self,
+        component: &ShimComponent<'_>,
+    ) -> Result<ShimController<'lib>, ShimError> {
+        let cid = component.controller_cid()?;
+        self.create_edit_controller(&cid)
+    }
+}
+
+impl Drop for ShimFactory<'_> {
+    fn drop(&mut self) {
+        release(self.ptr);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShimBusInfo {
+    pub media_type: i32,
+    pub direction: i32,
+    pub channel_count: i32,
+    pub bus_type: i32,
+    pub flags: u32,
+    pub name: String,
+}
+
+/// An initialized component; deactivated (if active), terminated and released on drop.
+pub struct ShimComponent<'lib> {
+    ptr: v3_component,
+    active: Cell<bool>,
+    _lib: PhantomData<&'lib Vst3Lib>,
+}
+
+impl ShimComponent<'_> {
+    pub fn as_raw(&self) -> v3_component {
+        self.ptr
+    }
+
+    /// The component's audio processor, holding its own reference.
+    pub fn processor(&self) -> Result<ShimProcessor<'_>, ShimError> {
+        let mut ptr = null_mut();
+        check("v3_component_get_audio_processor", unsafe {
+            v3_component_get_audio_processor(self.ptr, &mut ptr)
+        })?;
+        Ok(ShimProcessor {
+            ptr,
+            processing: false,
+            component: self,
+        })
+    }
+
+    pub fn set_active(&self, active: bool) -> Result<(), ShimError> {
+        check("v3_component_set_active", unsafe {
+            v3_component_set_active(self.ptr, active as i32)
+        })?;
+        self.active.set(active);
+        Ok(())
+    }
+
+    pub fn bus_count(&self, media_type: i32, direction: i32) -> usize {
+        unsafe { v3_component_get_bus_count(self.ptr, media_type, direction) }.max(0) as usize
+    }
+
+    pub fn bus_info(
+        &self,
+        media_type: i32,
+        direction: i32,
+        index: usize,
+    ) -> Result<ShimBusInfo, ShimError> {
+        let mut raw = core::mem::MaybeUninit::<v3_bus_info>::zeroed();
+        check("v3_component_get_bus_info", unsafe {
+            v3_component_get_bus_info(
+                self.ptr,
+                media_type,
+                direction,
+                index as i32,
+                raw.as_mut_ptr(),
+            )
+        })?;
+        let raw = unsafe { raw.assume_init() };
+        Ok(ShimBusInfo {
+            media_type: raw.media_type,
+            direction: raw.direction,
+            channel_count: raw.channel_count,
+            bus_type: raw.bus_type,
+            flags: raw.flags,
+            name: c_string(&raw.name),
+        })
+    }
+
+    pub fn activate_bus(
+        &self,
+        media_type: i32,
+        direction: i32,
+        index: usize,
+        active: bool,
+    ) -> Result<(), ShimError> {
+        check("v3_component_activate_bus", unsafe {
+            v3_component_activate_bus(self.ptr, media_type, direction, index as i32, active as i32)
+        })
+        .map(drop)
+    }
+
+    /// `IComponent::getControllerClassId`.
+    pub fn controller_cid(&self) -> Result<[u8; 16], ShimError> {
+        let mut cid = [0u8; 16];
+        check("v3_component_get_controller_cid", unsafe {
+            v3_component_get_controller_cid(self.ptr, cid.as_mut_ptr())
+        })?;
+        Ok(cid)
+    }
+
+    /// `IComponent::getState`, e.g. for saving a preset.
+    pub fn state(&self) -> Result<Vec<u8>, ShimError> {
+        unsafe { get_component_state(self.ptr) }
+            .map_err(|status| ShimError::from_code("v3_component_get_state", status))
+    }
+
+    /// `IComponent::setState` with bytes from [`ShimComponent::state`] or a preset.
+    pub fn set_state(&self, state: &[u8]) -> Result<(), ShimError> {
+        check("v3_component_set_state", unsafe {
+            set_component_state(self.ptr, state)
+        })
+        .map(drop)
+    }
+}
+
+impl Drop for ShimComponent<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            if self.active.get() {
+                v3_component_set_active(self.ptr, 0);
+            }
+            v3_component_terminate(self.ptr);
+        }
+        release(self.ptr);
+    }
+}
+
+/// The `IAudioProcessor` of a [`ShimComponent`]; stops processing and is released on
+/// drop, before the component can be.
+pub struct ShimProcessor<'c> {
+    ptr: v3_audio_processor,
+    processing: bool,
+    component: &'c ShimComponent<'c>,
+}
+
+impl ShimProcessor<'_> {
+    pub fn as_raw(&self) -> v3_audio_processor {
+        self.ptr
+    }
+
+    /// `setupProcessing` for 32-bit realtime processing; the shim also activates the
+    /// component. Negotiate channel counts with [`ShimProcessor::set_bus_arrangements`].
+    pub fn setup(&mut self, sample_rate: f64, max_block: usize) -> Result<(), ShimError> {
+        check("v3_audio_processor_setup", unsafe {
+            v3_audio_processor_setup(self.ptr, sample_rate, max_block as i32, 0, 0)
+        })?;
+        self.component.active.set(true);
+        Ok(())
+    }
+
+    pub fn set_processing(&mut self, processing: bool) -> Result<(), ShimError> {
+        check("v3_audio_processor_set_processing", unsafe {
+            v3_audio_processor_set_processing(self.ptr, processing as i32)
+        })?;
+        self.processing = processing;
+        Ok(())
+    }
+
+    pub fn latency(&self) -> Result<u32, ShimError> {
+        check("v3_audio_processor_get_latency", unsafe {
+            v3_audio_processor_get_latency(self.ptr)
+        })
+        .map(|n| n as u32)
+    }
+
+    /// Tail in samples; `None` for an infinite tail.
+    pub fn tail(&self) -> Result<Option<u32>, ShimError> {
+        let tail = check("v3_audio_processor_get_tail", unsafe {
+            v3_audio_processor_get_tail(self.ptr)
+        })?;
+        Ok((tail != V3_INFINITE_TAIL).then_some(tail as u32))
+    }
+
+    /// The arrangements of the first `inputs` input and `outputs` output buses.
+    pub fn bus_arrangements(
+        &self,
+        inputs: usize,
+        outputs: usize,
+    ) -> Result<(Vec<u64>, Vec<u64>), ShimError> {
+        let (mut ins, mut outs) = (vec![0; inputs], vec![0; outputs]);
+        check("v3_audio_processor_get_bus_arrangements", unsafe {
+            v3_audio_processor_get_bus_arrangements(
+                self.ptr,
+                inputs as i32,
+                ins.as_mut_ptr(),
+                outputs as i32,
+                outs.as_mut_ptr(),
+            )
+        })?;
+        Ok((ins, outs))
+    }
+
+    pub fn set_bus_arrangements(
+        &mut self,
+        inputs: &[u64],
+        outputs: &[u64],
+    ) -> Result<(), ShimError> {
+        check("v3_audio_processor_set_bus_arrangements", unsafe {
+            v3_audio_processor_set_bus_arrangements(
+                self.ptr,
+                inputs.len() as i32,
+                inputs.as_ptr(),
+                outputs.len() as i32,
+                outputs.as_ptr(),
+            )
+        })
+        .map(drop)
+    }
+
+    /// Process `num_samples` frames, one slice per channel.
+    pub fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_samples: usize,
+    ) -> Result<(), ShimError> {
+        self.process_with(inputs, outputs, num_samples, &[], &[], None)
+    }
+
+    /// [`ShimProcessor::process`] with the block's events, parameter points (each
+    /// parameter's sorted by offset) and transport.
+    pub fn process_with(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        num_samples: usize,
+        events: &[v3_event],
+        params: &[v3_param_point],
+        context: Option<&v3_process_context>,
+    ) -> Result<(), ShimError> {
+        check("v3_audio_processor_process_f32_ctx", unsafe {
+            process_f32_ctx(
+                self.ptr,
+                inputs,
+                outputs,
+                num_samples,
+                events,
+                params,
+                context,
+            )
+        })
+        .map(drop)
+    }
+}
+
+impl Drop for ShimProcessor<'_> {
+    fn drop(&mut self) {
+        if self.processing {
+            unsafe {
+                v3_audio_processor_set_processing(self.ptr, 0);
+            }
+        }
+        release(self.ptr);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamInfo {
+    pub id: u32,
+    pub title: String,
+    pub short_title: String,
+    pub units: String,
+    pub step_count: i32,
+    pub default_normalized: f64,
+    pub unit_id: i32,
+    pub flags: i32,
+}
+
+/// An initialized edit controller; terminated and released on drop.
+pub struct ShimController<'lib> {
+    ptr: v3_edit_controller,
+    _lib: PhantomData<&'lib Vst3Lib>,
+}
+
+impl ShimController<'_> {
+    pub fn as_raw(&self) -> v3_edit_controller {
+        self.ptr
+    }
+
+    pub fn param_count(&self) -> usize {
+        unsafe { v3_controller_param_count(self.ptr) }.max(0) as usize
+    }
+
+    pub fn param_info(&self, index: usize) -> Result<ParamInfo, ShimError> {
+        let mut raw = core::mem::MaybeUninit::<v3_param_info>::zeroed();
+        check("v3_controller_param_info", unsafe {
+            v3_controller_param_info(self.ptr, index as i32, raw.as_mut_ptr())
+        })?;
+        let raw = unsafe { raw.assume_init() };
+        Ok(ParamInfo {
+            id: raw.id,
+            title: c_string(&raw.title),
+            short_title: c_string(&raw.short_title),
+            units: c_string(&raw.units),
+            step_count: raw.step_count,
+            default_normalized: raw.default_normalized,
+            unit_id: raw.unit_id,
+            flags: raw.flags,
+        })
+    }
+
+    /// Every parameter's info, in index order.
+    pub fn params(&self) -> Result<Vec<ParamInfo>, ShimError> {
+        (0..self.param_count())
+            .map(|i| self.param_info(i))
+            .collect()
+    }
+
+    pub fn param_normalized(&self, id: u32) -> Result<f64, ShimError> {
+        let mut value = 0.0;
+        check("v3_controller_get_param_normalized", unsafe {
+            v3_controller_get_param_normalized(self.ptr, id, &mut value)
+        })?;
+        Ok(value)
+    }
+
+    pub fn set_param_normalized(&self, id: u32, value: f64) -> Result<(), ShimError> {
+        check("v3_controller_set_param_normalized", unsafe {
+            v3_controller_set_param_normalized(self.ptr, id, value)
+        })
+        .map(drop)
+    }
+
+    pub fn normalized_to_plain(&self, id: u32, normalized: f64) -> Result<f64, ShimError> {
+        let mut plain = 0.0;
+        check("v3_controller_normalized_to_plain", unsafe {
+            v3_controller_normalized_to_plain(self.ptr, id, normalized, &mut plain)
+        })?;
+        Ok(plain)
+    }
+}
+
+impl Drop for ShimController<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            v3_controller_terminate(self.ptr);
+        }
+        release(self.ptr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn c_string_stops_at_nul_and_tolerates_bad_utf8() {
+        let mut buf = [0u8; 8];
+        buf[..3].copy_from_slice(b"Hz\xFF");
+        assert_eq!(c_string(&buf), "Hz\u{FFFD}");
+        assert_eq!(c_string(b"full"), "full");
+    }
+
+    #[test]
+    fn codes_map_to_errors_that_keep_the_function() {
+        assert_eq!(check("f", 3), Ok(3));
+        for code in [
+            V3_ERR_NULL_HANDLE,
+            V3_ERR_INVALID_ARG,
+            V3_ERR_SHORT_CHANNEL,
+            V3_ERR_TRESULT,
+            V3_ERR_NO_INTERFACE,
+            V3_ERR_NO_CONTROLLER,
+            -9,
+        ] {
+            let err = check("v3_release", code).unwrap_err();
+            assert_eq!((err.func(), err.code()), ("v3_release", code));
+        }
+        assert_eq!(
+            ShimError::from_code("v3_component_set_state", V3_ERR_INVALID_ARG).to_string(),
+            "v3_component_set_state: invalid arguments"
+        );
+        let failed = ShimError::Failed {
+            func: "v3_component_initialize",
+            tresult: 1,
+        };
+        assert_eq!(
+            failed.to_string(),
+            "v3_component_initialize: plugin returned tresult 1"
+        );
+    }
+}
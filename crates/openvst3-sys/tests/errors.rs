@@ -0,0 +1,42 @@
+//! One error from each status category, through the test plugin with its processor
+//! hidden. The fault is read when the plugin creates its factory, so this file holds
+//! a single test and sets it before the first load in its process.
+
+#![cfg(feature = "shim")]
+
+use core::ptr::null_mut;
+
+use openvst3_abi::K_INVALID_ARG;
+use openvst3_sys::safe::ShimError;
+use openvst3_sys::*;
+use openvst3_testplugin::{bundle::plugin_binary, NO_PROCESSOR_ENV};
+
+#[test]
+fn each_category_reaches_rust_as_its_own_error() {
+    std::env::set_var(NO_PROCESSOR_ENV, "1");
+    let lib = unsafe { Vst3Lib::load(plugin_binary()) }.unwrap();
+    let factory = lib.factory().unwrap();
+
+    // -1x: caught by the shim, the plugin is never called.
+    let status = unsafe { v3_factory_class_info(factory.as_raw(), 0, null_mut()) };
+    assert_eq!(
+        ShimError::from_code("v3_factory_class_info", status),
+        ShimError::NullHandle("v3_factory_class_info")
+    );
+
+    // -2x: the plugin's tresult comes along.
+    assert_eq!(
+        factory.class_info(7),
+        Err(ShimError::Failed {
+            func: "v3_factory_class_info",
+            tresult: K_INVALID_ARG
+        })
+    );
+
+    // -3x: the component exists but does not answer for IAudioProcessor.
+    let class = factory.class_info(0).unwrap();
+    assert_eq!(
+        factory.create_component(&class.cid).err(),
+        Some(ShimError::NoInterface("v3_factory_create_audio_processor"))
+    );
+}
@@ -0,0 +1,223 @@
+//! Every function declared in the `extern` block must exist in the compiled shim: a
+//! prototype without a C++ definition fails this test at link time. The calls only
+//! hit the null guards, so no plugin or SDK runtime is needed.
+
+#![cfg(feature = "shim")]
+
+use core::ptr::null_mut;
+
+use openvst3_sys::*;
+
+#[test]
+fn layouts_match_the_header() {
+    assert_eq!(core::mem::size_of::<v3_class_info>(), 208);
+    assert_eq!(core::mem::size_of::<v3_bus_info>(), 148);
+    assert_eq!(core::mem::size_of::<v3_param_info>(), 408);
+    assert_eq!(core::mem::size_of::<v3_event>(), 28);
+    assert_eq!(core::mem::size_of::<v3_param_point>(), 16);
+    assert_eq!(core::mem::size_of::<v3_process_context>(), 80);
+}
+
+#[test]
+fn every_declared_function_links_and_rejects_null() {
+    let mut class = core::mem::MaybeUninit::<v3_class_info>::uninit();
+    let mut bus = core::mem::MaybeUninit::<v3_bus_info>::uninit();
+    let mut arr = [0u64; 1];
+    let mut proc = null_mut();
+    let mut comp = null_mut();
+    unsafe {
+        assert_eq!(v3_factory_class_count(null_mut()), V3_ERR_NULL_HANDLE);
+        assert_eq!(
+            v3_factory_class_info(null_mut(), 0, class.as_mut_ptr()),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(
+            v3_factory_create_audio_processor(null_mut(), [0u8; 16].as_ptr(), &mut proc, &mut comp),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(v3_release(null_mut()), V3_ERR_NULL_HANDLE);
+        // Null guards answer before the plugin is reached, so no tresult is kept.
+        assert_eq!(v3_last_tresult(), 0);
+
+        assert_eq!(v3_component_initialize(null_mut()), V3_ERR_NULL_HANDLE);
+        assert_eq!(v3_component_set_active(null_mut(), 1), V3_ERR_NULL_HANDLE);
+        assert_eq!(v3_component_terminate(null_mut()), V3_ERR_NULL_HANDLE);
+        assert_eq!(
+            v3_component_get_bus_count(null_mut(), MEDIA_TYPE_AUDIO, BUS_DIR_INPUT),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(
+            v3_component_get_bus_info(
+                null_mut(),
+                MEDIA_TYPE_AUDIO,
+                BUS_DIR_OUTPUT,
+                0,
+                bus.as_mut_ptr()
+            ),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(
+            v3_component_activate_bus(null_mut(), MEDIA_TYPE_EVENT, BUS_DIR_INPUT, 0, 1),
+            V3_ERR_NULL_HANDLE
+        );
+
+        let mut cid = [0u8; 16];
+        assert_eq!(
+            v3_component_get_controller_cid(null_mut(), cid.as_mut_ptr()),
+            V3_ERR_NULL_HANDLE
+        );
+
+        assert_eq!(
+            v3_component_get_audio_processor(null_mut(), &mut proc),
+            V3_ERR_NULL_HANDLE
+        );
+        let mut buf = null_mut();
+        let mut len = 7;
+        assert_eq!(
+            v3_component_get_state(null_mut(), &mut buf, &mut len),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(
+            v3_component_set_state(null_mut(), [1u8].as_ptr(), 1),
+            V3_ERR_NULL_HANDLE
+        );
+        v3_buffer_free(null_mut());
+
+        let mut ctrl = null_mut();
+        let mut info = core::mem::MaybeUninit::<v3_param_info>::uninit();
+        let mut value = 0.0;
+        assert_eq!(
+            v3_factory_create_edit_controller(null_mut(), cid.as_ptr(), &mut ctrl),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(v3_controller_initialize(null_mut()), V3_ERR_NULL_HANDLE);
+        assert_eq!(v3_controller_terminate(null_mut()), V3_ERR_NULL_HANDLE);
+        assert_eq!(v3_controller_param_count(null_mut()), V3_ERR_NULL_HANDLE);
+        assert_eq!(
+            v3_controller_param_info(null_mut(), 0, info.as_mut_ptr()),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(
+            v3_controller_get_param_normalized(null_mut(), 0, &mut value),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(
+            v3_controller_set_param_normalized(null_mut(), 0, 0.5),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(
+            v3_controller_normalized_to_plain(null_mut(), 0, 0.5, &mut value),
+            V3_ERR_NULL_HANDLE
+        );
+
+        assert_eq!(
+            v3_audio_processor_setup(null_mut(), 48_000.0, 512, 2, 2),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(
+            v3_audio_processor_set_active(null_mut(), 1),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(
+            v3_audio_processor_get_latency(null_mut()),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(v3_audio_processor_get_tail(null_mut()), V3_ERR_NULL_HANDLE);
+        assert_eq!(
+            v3_audio_processor_set_processing(null_mut(), 1),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(
+            v3_audio_processor_get_bus_arrangements(null_mut(), 1, arr.as_mut_ptr(), 0, null_mut()),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(
+            v3_audio_processor_set_bus_arrangements(
+                null_mut(),
+                1,
+                arr.as_ptr(),
+                0,
+                core::ptr::null()
+            ),
+            V3_ERR_NULL_HANDLE
+        );
+        assert_eq!(
+            v3_audio_processor_process_f32(null_mut(), core::ptr::null(), 0, null_mut(), 0, 0),
+            V3_ERR_NULL_HANDLE
+        );
+        let events = [v3_event::note_on(0, 0, 60, 0.8)];
+        let points = [v3_param_point {
+            param_id: 0,
+            sample_offset: 0,
+            value: 0.5,
+        }];
+        assert_eq!(
+            v3_audio_processor_process_f32_ex(
+                null_mut(),
+                core::ptr::null(),
+                0,
+                null_mut(),
+                0,
+                0,
+                events.as_ptr(),
+                1,
+                points.as_ptr(),
+                1
+            ),
+            V3_ERR_NULL_HANDLE
+        );
+        let context = v3_process_context::at(48_000.0, 0).with_tempo(120.0);
+        assert_eq!(
+            v3_audio_processor_process_f32_ctx(
+                null_mut(),
+                core::ptr::null(),
+                0,
+                null_mut(),
+                0,
+                0,
+                events.as_ptr(),
+                1,
+                points.as_ptr(),
+                1,
+                &context
+            ),
+            V3_ERR_NULL_HANDLE
+        );
+    }
+}
+
+#[test]
+fn slice_wrappers_check_channel_lengths_before_calling() {
+    let input = [0.0f32; 8];
+    let mut output = [0.0f32; 4];
+    let events = [
+        v3_event::note_on(0, 0, 60, 1.0),
+        v3_event::note_off(3, 0, 60, 0.0),
+    ];
+    unsafe {
+        let mut outs = [&mut output[..]];
+        assert_eq!(
+            process_f32_ex(null_mut(), &[&input], &mut outs, 8, &events, &[]),
+            V3_ERR_SHORT_CHANNEL
+        );
+        // Lengths fit: the shim's own null check answers.
+        assert_eq!(
+            process_f32(null_mut(), &[&input], &mut outs, 4),
+            V3_ERR_NULL_HANDLE
+        );
+    }
+    unsafe {
+        assert_eq!(get_component_state(null_mut()), Err(V3_ERR_NULL_HANDLE));
+        assert_eq!(
+            set_component_state(null_mut(), &[1, 2, 3]),
+            V3_ERR_NULL_HANDLE
+        );
+    }
+    let context = v3_process_context::at(44_100.0, 1024)
+        .with_time_sig(6, 8)
+        .playing(true);
+    assert_eq!(context.flags, V3_CTX_TIME_SIG_VALID | V3_CTX_PLAYING);
+    assert_eq!(context.playing(false).flags, V3_CTX_TIME_SIG_VALID);
+    assert_eq!(events[1].event_type, V3_EVENT_NOTE_OFF);
+    assert_eq!(events[1].note_id, -1);
+}
@@ -0,0 +1,189 @@
+//! Test-side helpers that wrap the built test plugin in a `.vst3` bundle.
+//!
+//! Only meaningful from test binaries in the same target directory as the cdylib.
+
+use std::path::{Path, PathBuf};
+
+use openvst3_host::bundle::{package, platform_arch_dir, PackageOptions};
+
+/// The cdylib cargo built alongside this test binary.
+pub fn plugin_binary() -> PathBuf {
+    let name = format!(
+        "{}openvst3_testplugin{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    );
+    let exe = std::env::current_exe().unwrap();
+    let deps = exe.parent().unwrap();
+    [deps.join(&name), deps.parent().unwrap().join(&name)]
+        .into_iter()
+        .find(|p| p.is_file())
+        .unwrap_or_else(|| panic!("{name} not found next to {}", exe.display()))
+}
+
+/// Assemble `<tmp>/<tag>/OpenVST3TestPlugin.vst3` around a copy of the plugin binary.
+pub fn make_bundle(tag: &str) -> PathBuf {
+    let root =
+        std::env::temp_dir().join(format!("openvst3-testplugin-{}-{tag}", std::process::id()));
+    package(
+        plugin_binary(),
+        root,
+        &PackageOptions::new("OpenVST3TestPlugin"),
+    )
+    .unwrap()
+}
+
+pub fn remove_bundle(bundle: &Path) {
+    let _ = std::fs::remove_dir_all(bundle.parent().unwrap());
+}
+
+/// A copy of the plugin binary in `<tmp>/<tag>/` whose first `DT_NEEDED` entry is
+/// renamed to a library that does not exist; returns the copy and that name.
+#[cfg(target_os = "linux")]
+pub fn binary_with_missing_dependency(tag: &str) -> (PathBuf, String) {
+    let mut bytes = std::fs::read(plugin_binary()).unwrap();
+    // The dynamic string table holds the name once, NUL-terminated on both sides; a
+    // replacement of the same length leaves every offset intact.
+    let needed = "libgcc_s.so.1";
+    let missing = format!("lib{}.so", "z".repeat(needed.len() - 6));
+    let pattern = format!("\0{needed}\0");
+    let at = bytes
+        .windows(pattern.len())
+        .position(|w| w == pattern.as_bytes())
+        .unwrap_or_else(|| panic!("{needed} is not in the plugin's string table"));
+    bytes[at + 1..at + 1 + needed.len()].copy_from_slice(missing.as_bytes());
+
+    let dir =
+        std::env::temp_dir().join(format!("openvst3-testplugin-{}-{tag}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(plugin_binary().file_name().unwrap());
+    std::fs::write(&path, bytes).unwrap();
+    (path, missing)
+}
+
+/// A copy of the plugin binary in `<tmp>/<tag>/` with each exported name in
+/// `renames` replaced by one of the same length.
+#[cfg(target_os = "linux")]
+pub fn binary_with_renamed_exports(tag: &str, renames: &[(&str, &str)]) -> PathBuf {
+    let mut bytes = std::fs::read(plugin_binary()).unwrap();
+    for &(from, to) in renames {
+        assert_eq!(from.len(), to.len(), "{from} -> {to}");
+        let pattern = format!("\0{from}\0");
+        let mut found = false;
+        while let Some(at) = bytes
+            .windows(pattern.len())
+            .position(|w| w == pattern.as_bytes())
+        {
+            bytes[at + 1..at + 1 + to.len()].copy_from_slice(to.as_bytes());
+            found = true;
+        }
+        assert!(found, "{from} is not in the plugin's string table");
+    }
+
+    let dir =
+        std::env::temp_dir().join(format!("openvst3-testplugin-{}-{tag}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(plugin_binary().file_name().unwrap());
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+/// A 32-bit Windows DLL at `<tmp>/<tag>/fixture.dll` with no code, only an export
+/// table: `named` exports, then `by_ordinal` more that have no name.
+pub fn dll_with_exports(tag: &str, named: &[&str], by_ordinal: usize) -> PathBuf {
+    fn put(bytes: &mut [u8], at: usize, value: &[u8]) {
+        bytes[at..at + value.len()].copy_from_slice(value);
+    }
+    const SECTION: usize = 0x200;
+    const RVA: u32 = 0x1000;
+    let mut names: Vec<&str> = named.to_vec();
+    names.sort();
+    let functions = names.len() + by_ordinal;
+
+    let mut bytes = vec![0u8; SECTION * 2];
+    put(&mut bytes, 0, b"MZ");
+    // The PE header goes after the 64-byte DOS header, with no stub.
+    let pe = 0x80;
+    put(&mut bytes, 0x3c, &(pe as u32).to_le_bytes());
+    put(&mut bytes, pe, b"PE\0\0");
+    // COFF header: i386, one section, PE32 optional header, DLL.
+    put(&mut bytes, pe + 4, &0x14cu16.to_le_bytes());
+    put(&mut bytes, pe + 6, &1u16.to_le_bytes());
+    put(&mut bytes, pe + 20, &0xe0u16.to_le_bytes());
+    put(&mut bytes, pe + 22, &0x2102u16.to_le_bytes());
+    // Optional header.
+    let opt = pe + 24;
+    put(&mut bytes, opt, &0x10bu16.to_le_bytes());
+    put(&mut bytes, opt + 28, &0x1000_0000u32.to_le_bytes());
+    put(&mut bytes, opt + 32, &0x1000u32.to_le_bytes());
+    put(&mut bytes, opt + 36, &(SECTION as u32).to_le_bytes());
+    put(&mut bytes, opt + 40, &4u16.to_le_bytes());
+    put(&mut bytes, opt + 48, &4u16.to_le_bytes());
+    put(&mut bytes, opt + 56, &0x2000u32.to_le_bytes());
+    put(&mut bytes, opt + 60, &(SECTION as u32).to_le_bytes());
+    put(&mut bytes, opt + 68, &2u16.to_le_bytes());
+    put(&mut bytes, opt + 92, &16u32.to_le_bytes());
+    // Export directory: the first 0x180 bytes of the section.
+    put(&mut bytes, opt + 96, &RVA.to_le_bytes());
+    put(&mut bytes, opt + 100, &0x180u32.to_le_bytes());
+    // The one section.
+    let header = opt + 0xe0;
+    put(&mut bytes, header, b".edata");
+    put(&mut bytes, header + 8, &(SECTION as u32).to_le_bytes());
+    put(&mut bytes, header + 12, &RVA.to_le_bytes());
+    put(&mut bytes, header + 16, &(SECTION as u32).to_le_bytes());
+    put(&mut bytes, header + 20, &(SECTION as u32).to_le_bytes());
+    put(&mut bytes, header + 36, &0x4000_0040u32.to_le_bytes());
+
+    // Directory, address table, name pointers, name ordinals, then the strings.
+    let addresses = 40;
+    let pointers = addresses + 4 * functions;
+    let ordinals = pointers + 4 * names.len();
+    let mut strings = ordinals + 2 * names.len();
+    let rva = |offset: usize| (RVA + offset as u32).to_le_bytes();
+    let dir = SECTION;
+    put(&mut bytes, dir + 12, &rva(strings));
+    put(&mut bytes, dir + strings, b"fixture.dll\0");
+    strings += 12;
+    put(&mut bytes, dir + 16, &1u32.to_le_bytes());
+    put(&mut bytes, dir + 20, &(functions as u32).to_le_bytes());
+    put(&mut bytes, dir + 24, &(names.len() as u32).to_le_bytes());
+    put(&mut bytes, dir + 28, &rva(addresses));
+    put(&mut bytes, dir + 32, &rva(pointers));
+    put(&mut bytes, dir + 36, &rva(ordinals));
+    for i in 0..functions {
+        // Past the export directory, so not forwarders.
+        put(&mut bytes, dir + addresses + 4 * i, &rva(0x180 + i));
+    }
+    for (i, name) in names.iter().enumerate() {
+        put(&mut bytes, dir + pointers + 4 * i, &rva(strings));
+        put(
+            &mut bytes,
+            dir + ordinals + 2 * i,
+            &(i as u16).to_le_bytes(),
+        );
+        put(&mut bytes, dir + strings, name.as_bytes());
+        strings += name.len() + 1;
+    }
+    assert!(strings <= 0x180, "too many exports for the fixture");
+
+    let dir =
+        std::env::temp_dir().join(format!("openvst3-testplugin-{}-{tag}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("fixture.dll");
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+/// A `<name>.vst3` bundle next to `bundle` whose binary is not a loadable library.
+pub fn make_broken_bundle(bundle: &Path, name: &str) -> PathBuf {
+    let broken = bundle.with_file_name(format!("{name}.vst3"));
+    let bin_dir = broken.join("Contents").join(platform_arch_dir());
+    std::fs::create_dir_all(&bin_dir).unwrap();
+    std::fs::write(
+        bin_dir.join(format!("{name}{}", std::env::consts::DLL_SUFFIX)),
+        b"not a shared library",
+    )
+    .unwrap();
+    broken
+}
@@ -0,0 +1,152 @@
+//! Tiny VST3 module for end-to-end host tests.
+//!
+//! Wraps the host's mock gain plugin (one gain parameter, serialized state) behind the
+//! real module exports, so tests can dlopen it from a `.vst3` bundle like any plugin.
+
+use std::sync::Mutex;
+
+pub mod bundle;
+
+use openvst3_abi::{FUnknown, IPluginFactory, K_INTERNAL_ERR, K_RESULT_OK};
+use openvst3_host::testsupport::{Method, MockConfig, MockPlugin};
+
+/// Class name reported by the test plugin's single class.
+pub const CLASS_NAME: &str = "OpenVST3 Test Gain";
+
+/// Abort the process inside the named method (e.g. `process`, `get_class_info`), for
+/// isolation tests. `entry` aborts in the module entry point and `factory` in
+/// `GetPluginFactory` itself, before any class can be read.
+pub const CRASH_IN_ENV: &str = "OPENVST3_TESTPLUGIN_CRASH_IN";
+/// Block forever inside the named method (or `entry`, `factory`), for timeout tests.
+pub const HANG_IN_ENV: &str = "OPENVST3_TESTPLUGIN_HANG_IN";
+/// Return `kInternalError` from `get_class_info`, `create_instance` or `process`, for
+/// error reporting tests.
+pub const FAIL_IN_ENV: &str = "OPENVST3_TESTPLUGIN_FAIL_IN";
+/// When set, instances do not answer `queryInterface(IAudioProcessor)`.
+pub const NO_PROCESSOR_ENV: &str = "OPENVST3_TESTPLUGIN_NO_PROCESSOR";
+/// A number of aux output buses ("Aux 1", ...) to add, each with a gain parameter of
+/// its own (parameter `k` for bus `k`, default 0.5; 0 keeps the bus silent), for
+/// multi-out rendering tests.
+pub const AUX_OUTPUTS_ENV: &str = "OPENVST3_TESTPLUGIN_AUX_OUTPUTS";
+/// A CID (32 hex digits) for `getControllerClassId` to name, like a dual-component
+/// plugin whose controller class the factory does not list.
+pub const CONTROLLER_CID_ENV: &str = "OPENVST3_TESTPLUGIN_CONTROLLER_CID";
+
+static PLUGIN: Mutex<Option<MockPlugin>> = Mutex::new(None);
+
+fn method_from_env(var: &str) -> Option<Method> {
+    let name = std::env::var(var).ok()?;
+    let method = match name.to_ascii_lowercase().as_str() {
+        "get_class_info" => Method::GetClassInfo,
+        "initialize" => Method::Initialize,
+        "terminate" => Method::Terminate,
+        "setup_processing" => Method::SetupProcessing,
+        "set_active" => Method::SetActive,
+        "set_processing" => Method::SetProcessing,
+        "process" => Method::Process,
+        "get_state" => Method::GetState,
+        "set_state" => Method::SetState,
+        _ => return None,
+    };
+    Some(method)
+}
+
+fn config() -> MockConfig {
+    let fail_in = std::env::var(FAIL_IN_ENV).unwrap_or_default();
+    let fails = |method: &str| fail_in.eq_ignore_ascii_case(method);
+    let aux_outputs: i32 = std::env::var(AUX_OUTPUTS_ENV)
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(0);
+    MockConfig {
+        class_name: CLASS_NAME.into(),
+        num_params: 1 + aux_outputs.max(0) as usize,
+        aux_outputs,
+        follow_automation: true,
+        fail_get_class_info: fails("get_class_info").then_some(K_INTERNAL_ERR),
+        create_instance_result: if fails("create_instance") {
+            K_INTERNAL_ERR
+        } else {
+            K_RESULT_OK
+        },
+        process_returns_after_n_blocks: fails("process").then_some((0, K_INTERNAL_ERR)),
+        crash_in: method_from_env(CRASH_IN_ENV),
+        hang_in: method_from_env(HANG_IN_ENV),
+        hide_processor: std::env::var_os(NO_PROCESSOR_ENV).is_some(),
+        controller_cid: std::env::var(CONTROLLER_CID_ENV)
+            .ok()
+            .and_then(|hex| openvst3_host::parse_hex_16(&hex).ok()),
+        ..MockConfig::default()
+    }
+}
+
+/// Crash or hang in a module export, `stage` being `entry` or `factory`.
+fn fault_in(stage: &str) {
+    let named = |var| std::env::var(var).is_ok_and(|v| v.eq_ignore_ascii_case(stage));
+    if named(CRASH_IN_ENV) {
+        eprintln!("test plugin: crashing in {stage}");
+        std::process::abort();
+    }
+    if named(HANG_IN_ENV) {
+        loop {
+            std::thread::park();
+        }
+    }
+}
+
+/// Module factory export; each call hands out a new reference.
+#[no_mangle]
+pub extern "C" fn GetPluginFactory() -> *mut IPluginFactory {
+    fault_in("factory");
+    let mut plugin = PLUGIN.lock().unwrap();
+    let factory = plugin
+        .get_or_insert_with(|| MockPlugin::new(config()))
+        .factory_ptr();
+    unsafe { (*(factory as *mut FUnknown)).add_ref() };
+    factory
+}
+
+fn module_exit() -> bool {
+    // Hosts release the factory before calling the exit entry point.
+    PLUGIN.lock().unwrap().take();
+    true
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub extern "C" fn ModuleEntry(_handle: *mut core::ffi::c_void) -> bool {
+    fault_in("entry");
+    true
+}
+
+#[cfg(target_os = "linux")]
+#[no_mangle]
+pub extern "C" fn ModuleExit() -> bool {
+    module_exit()
+}
+
+#[cfg(target_os = "windows")]
+#[no_mangle]
+pub extern "C" fn InitDll() -> bool {
+    fault_in("entry");
+    true
+}
+
+#[cfg(target_os = "windows")]
+#[no_mangle]
+pub extern "C" fn ExitDll() -> bool {
+    module_exit()
+}
+
+#[cfg(target_os = "macos")]
+#[no_mangle]
+pub extern "C" fn bundleEntry(_bundle: *mut core::ffi::c_void) -> bool {
+    fault_in("entry");
+    true
+}
+
+#[cfg(target_os = "macos")]
+#[no_mangle]
+pub extern "C" fn bundleExit() -> bool {
+    module_exit()
+}
@@ -0,0 +1,247 @@
+//! Loads the test plugin through a real `.vst3` bundle and dlopen.
+
+// Loads real binaries, which Miri cannot.
+#![cfg(not(miri))]
+
+use openvst3_abi::{iids, FUnknown, IAudioProcessor, IComponent, IEditController, K_RESULT_OK};
+use openvst3_host::bundle::{package, ModuleInfo, PackageOptions};
+use openvst3_host::testsupport::{read_state_bytes, MOCK_CATEGORY, MOCK_CID};
+use openvst3_host::{
+    create_instance_raw, drive_process_capture_32f, list_classes, query_interface, BundlePath,
+    HostError, MemoryStream, Module,
+};
+#[cfg(target_os = "linux")]
+use openvst3_testplugin::bundle::{binary_with_missing_dependency, binary_with_renamed_exports};
+use openvst3_testplugin::bundle::{dll_with_exports, make_bundle, plugin_binary, remove_bundle};
+use openvst3_testplugin::CLASS_NAME;
+
+unsafe fn release<T>(obj: *mut T) -> u32 {
+    FUnknown::release(obj as *mut FUnknown)
+}
+
+#[test]
+fn bundle_resolves_and_lists_class() {
+    let bundle = make_bundle("list");
+    let bin = BundlePath::resolve(&bundle).unwrap();
+    let mut module = Module::load(&bin).unwrap();
+    let classes = list_classes(&mut module).unwrap();
+    assert_eq!(classes.len(), 1);
+    let (_, name, category, cid) = classes[0].as_ref().unwrap();
+    assert_eq!(name, CLASS_NAME);
+    assert_eq!(category, MOCK_CATEGORY);
+    assert_eq!(*cid, MOCK_CID);
+    drop(module);
+    remove_bundle(&bundle);
+}
+
+#[test]
+fn packaged_bundle_resolves_and_loads() {
+    let root = std::env::temp_dir().join(format!("openvst3-package-{}", std::process::id()));
+    let module = Module::load(plugin_binary()).unwrap();
+    let info = ModuleInfo::from_module(&module, "Packaged", "0.0.1").unwrap();
+    drop(module);
+
+    let options = PackageOptions {
+        module_info: Some(info),
+        symlink: cfg!(unix),
+        ..PackageOptions::new("Packaged")
+    };
+    let bundle = package(plugin_binary(), &root, &options).unwrap();
+    assert_eq!(bundle, root.join("Packaged.vst3"));
+    let bin = BundlePath::resolve(&bundle).unwrap();
+    assert_eq!(
+        bin.file_name().unwrap().to_str().unwrap(),
+        format!("Packaged{}", std::env::consts::DLL_SUFFIX)
+    );
+    let json = std::fs::read_to_string(bundle.join("Contents/Resources/moduleinfo.json")).unwrap();
+    assert!(
+        json.contains(&format!("\"Name\": \"{CLASS_NAME}\"")),
+        "{json}"
+    );
+
+    let mut module = Module::load(&bin).unwrap();
+    let classes = list_classes(&mut module).unwrap();
+    assert_eq!(classes[0].as_ref().unwrap().3, MOCK_CID);
+    drop(module);
+    let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn load_rejects_non_plugin_paths() {
+    assert!(matches!(
+        BundlePath::resolve(std::env::temp_dir()),
+        Err(HostError::InvalidBundle(_))
+    ));
+    assert!(matches!(
+        Module::load(std::env::temp_dir().join("no-such-plugin.so")),
+        Err(HostError::Dlopen(_))
+    ));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn missing_factory_lists_what_the_binary_exports() {
+    use openvst3_host::load_error::exports;
+
+    assert_eq!(
+        exports(&plugin_binary()),
+        Some(vec![
+            "GetPluginFactory".to_string(),
+            "ModuleEntry".into(),
+            "ModuleExit".into()
+        ])
+    );
+
+    let bin =
+        binary_with_renamed_exports("no-factory", &[("GetPluginFactory", "GetPluginFactorx")]);
+    let Err(HostError::NoFactorySymbol(missing)) = Module::load(&bin) else {
+        panic!("loaded a binary without GetPluginFactory");
+    };
+    assert_eq!(missing.path, bin);
+    assert_eq!(missing.tried, ["GetPluginFactory"]);
+    assert_eq!(missing.looks_like_vst3(), Some(true));
+    assert_eq!(
+        HostError::NoFactorySymbol(missing.clone()).to_string(),
+        "symbol `GetPluginFactory` not found: tried GetPluginFactory; \
+         the binary exports GetPluginFactorx, ModuleEntry, ModuleExit"
+    );
+    assert!(missing
+        .diagnosis()
+        .contains("  exports:\n    GetPluginFactorx\n"));
+    let _ = std::fs::remove_dir_all(bin.parent().unwrap());
+
+    // No entry point at all: some other library.
+    let bin = binary_with_renamed_exports(
+        "not-vst3",
+        &[
+            ("GetPluginFactory", "GetPluginFactorx"),
+            ("ModuleEntry", "ModuleEntrx"),
+            ("ModuleExit", "ModuleExix"),
+        ],
+    );
+    let Err(e @ HostError::NotVst3(_)) = Module::load(&bin) else {
+        panic!("a library without VST 3 exports was not told apart");
+    };
+    assert!(e.is_plugin_fault());
+    assert!(e.to_string().starts_with("not a VST 3 module: "), "{e}");
+    let _ = std::fs::remove_dir_all(bin.parent().unwrap());
+}
+
+#[test]
+fn decorated_and_ordinal_exports_are_enumerated() {
+    use openvst3_host::load_error::{exports, is_factory_alias};
+    use openvst3_host::MissingFactory;
+
+    let dll = dll_with_exports("decorated", &["_GetPluginFactory@0", "InitDll"], 1);
+    let names = exports(&dll).unwrap();
+    assert_eq!(names, ["#3", "InitDll", "_GetPluginFactory@0"]);
+    let aliases: Vec<_> = names.iter().filter(|e| is_factory_alias(e)).collect();
+    assert_eq!(aliases, ["_GetPluginFactory@0"]);
+    let missing = MissingFactory {
+        path: dll.clone(),
+        tried: vec!["GetPluginFactory".into()],
+        exports: Some(names),
+    };
+    assert_eq!(missing.looks_like_vst3(), Some(true));
+
+    let other = dll_with_exports("undecorated", &["DllMain", "Render"], 0);
+    assert_eq!(exports(&other).unwrap(), ["DllMain", "Render"]);
+    let _ = std::fs::remove_dir_all(dll.parent().unwrap());
+    let _ = std::fs::remove_dir_all(other.parent().unwrap());
+}
+
+#[test]
+fn load_failures_keep_the_loader_detail() {
+    let missing = std::env::temp_dir().join("no-such-plugin.so");
+    let Err(HostError::Dlopen(err)) = Module::load(&missing) else {
+        panic!("loaded a missing file");
+    };
+    assert_eq!(err.path, missing);
+    assert!(
+        err.os_message.contains("no-such-plugin.so"),
+        "{}",
+        err.os_message
+    );
+    // Nothing to read, so no dependency list.
+    assert_eq!(err.missing_deps, None);
+    // dlopen reports text only; a stale errno is no code.
+    #[cfg(unix)]
+    assert_eq!(err.code, None);
+    assert!(err.diagnosis().contains("no-such-plugin.so"));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn load_failure_names_the_missing_dependency() {
+    let (bin, dep) = binary_with_missing_dependency("missing-dep");
+    let Err(HostError::Dlopen(err)) = Module::load(&bin) else {
+        panic!("loaded a binary with a missing dependency");
+    };
+    assert_eq!(err.path, bin);
+    assert!(err.os_message.contains(&dep), "{}", err.os_message);
+    assert_eq!(err.missing_deps, Some(vec![dep.clone()]));
+    assert!(err.to_string().ends_with(&format!("(missing: {dep})")));
+    assert!(err
+        .diagnosis()
+        .contains(&format!("missing dependencies:\n    {dep}\n")));
+
+    // Dependencies that resolve are not listed.
+    assert_eq!(
+        openvst3_host::load_error::missing_dependencies(&plugin_binary()),
+        Some(vec![])
+    );
+    let _ = std::fs::remove_dir_all(bin.parent().unwrap());
+}
+
+#[test]
+fn process_block_and_state_round_trip() {
+    let bundle = make_bundle("process");
+    let mut module = Module::load(BundlePath::resolve(&bundle).unwrap()).unwrap();
+    unsafe {
+        let comp = create_instance_raw(module.factory_mut(), MOCK_CID, iids::ICOMPONENT.0).unwrap()
+            as *mut IComponent;
+        let proc =
+            query_interface(comp.cast(), iids::IAUDIO_PROCESSOR.0).unwrap() as *mut IAudioProcessor;
+        let ctrl =
+            query_interface(comp.cast(), iids::IEDIT_CONTROLLER.0).unwrap() as *mut IEditController;
+
+        assert_eq!((*ctrl).get_parameter_count(), 1);
+        assert_eq!((*ctrl).set_param_normalized(0, 0.5), K_RESULT_OK);
+
+        // The helper initializes and terminates the processor around the block; the
+        // gain set above survives that.
+        let input = [vec![1.0f32; 32], vec![-1.0f32; 32]];
+        let block = drive_process_capture_32f(proc, 48000.0, 32, 2, Some(&input)).unwrap();
+        assert_eq!(block.tresult, K_RESULT_OK);
+        assert_eq!(block.outputs, vec![vec![0.5; 32], vec![-0.5; 32]]);
+        assert_eq!(block.silence_flags, 0);
+
+        assert_eq!((*comp).initialize(core::ptr::null_mut()), K_RESULT_OK);
+        let mut stream = MemoryStream::new();
+        assert_eq!((*comp).get_state(stream.as_ibstream()), K_RESULT_OK);
+        assert_eq!(read_state_bytes(stream.bytes()).unwrap(), vec![0.5]);
+
+        (*ctrl).set_param_normalized(0, 1.0);
+        stream.rewind();
+        assert_eq!((*comp).set_state(stream.as_ibstream()), K_RESULT_OK);
+        assert_eq!((*ctrl).get_param_normalized(0), 0.5);
+
+        (*comp).terminate();
+        release(ctrl);
+        release(proc);
+        assert_eq!(release(comp), 0);
+    }
+    drop(module);
+    remove_bundle(&bundle);
+}
+
+#[test]
+fn reload_after_unload() {
+    let bundle = make_bundle("reload");
+    let bin = BundlePath::resolve(&bundle).unwrap();
+    for _ in 0..3 {
+        let mut module = Module::load(&bin).unwrap();
+        assert_eq!(list_classes(&mut module).unwrap().len(), 1);
+    }
+    remove_bundle(&bundle);
+}
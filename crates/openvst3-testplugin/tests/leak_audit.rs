@@ -0,0 +1,60 @@
+//! Reference-leak audit (`leak-audit` feature of openvst3-host).
+
+use openvst3_host::leak_audit;
+use openvst3_host::testsupport::{MockCounters, MockPlugin, MOCK_CID};
+
+#[test]
+fn clean_teardown_reports_nothing() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let inst = module.create_plugin(MOCK_CID).unwrap();
+    let report = module.leak_report();
+    // Component, processor and controller pointers of the live instance.
+    assert_eq!(report.leaked.len(), 3);
+    assert!(report
+        .leaked
+        .iter()
+        .any(|l| l.interface == "IAudioProcessor"));
+    drop(inst);
+    assert!(module.leak_report().is_clean());
+    assert!(module.try_unload().is_ok());
+    assert_eq!(MockCounters::get(&plugin.counters().live_instances), 0);
+}
+
+#[test]
+fn held_clone_blocks_unload() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let inst = module.create_plugin(MOCK_CID).unwrap();
+    let kept = inst.component().clone();
+    let extra = kept.clone();
+    drop(inst);
+
+    let (module, report) = module.try_unload().unwrap_err();
+    assert_eq!(report.leaked.len(), 1);
+    assert_eq!(report.leaked[0].interface, "IComponent");
+    assert_eq!(report.leaked[0].refs, 2);
+    assert_eq!(report.leaked[0].ptr, kept.as_ptr() as usize);
+    assert!(report.to_string().contains("IComponent"));
+    assert!(leak_audit::leak_report()
+        .leaked
+        .iter()
+        .any(|l| l.ptr == kept.as_ptr() as usize));
+
+    drop(kept);
+    drop(extra);
+    assert!(module.try_unload().is_ok());
+}
+
+#[test]
+fn modules_are_audited_separately() {
+    let a = MockPlugin::default();
+    let b = MockPlugin::default();
+    let mut ma = a.module().unwrap();
+    let mb = b.module().unwrap();
+    let inst = ma.create_plugin(MOCK_CID).unwrap();
+    assert!(mb.leak_report().is_clean());
+    assert!(!ma.leak_report().is_clean());
+    drop(inst);
+    assert!(ma.leak_report().is_clean());
+}
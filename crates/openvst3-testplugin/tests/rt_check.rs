@@ -0,0 +1,82 @@
+//! Allocation checks of the per-block paths (`rt-check` feature of openvst3-host).
+
+use openvst3_abi::{process_consts, ProcessSetup};
+use openvst3_host::rt_check::{allocations, assert_no_alloc, CountingAllocator};
+use openvst3_host::testsupport::{MockConfig, MockPlugin, MOCK_CID};
+use openvst3_host::{BlockIoPool, BoundProcessData, HostLimits, MidiConverter};
+
+#[global_allocator]
+static ALLOC: CountingAllocator = CountingAllocator;
+
+#[test]
+fn allocations_are_counted() {
+    let before = allocations();
+    let v = vec![0u8; 64];
+    assert!(allocations() > before);
+    drop(v);
+}
+
+#[test]
+#[should_panic(expected = "allocations in a realtime section")]
+fn assert_no_alloc_catches_an_allocation() {
+    assert_no_alloc(|| vec![0u8; 64]);
+}
+
+#[test]
+fn pool_bursts_are_dropped_without_allocating() {
+    const BLOCKS: usize = 200;
+    let limits = HostLimits {
+        max_events_per_block: 32,
+        ..HostLimits::default()
+    };
+    let mut pool = BlockIoPool::with_limits(1, 4, &limits);
+    let counters = pool.counters();
+    let mut midi = MidiConverter::new();
+    for cc in 0..8 {
+        midi.assign(0, cc, cc as u32);
+    }
+
+    let plugin = MockPlugin::new(MockConfig {
+        event_inputs: 1,
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let mut inst = module.create_plugin(MOCK_CID).unwrap();
+    inst.activate(&ProcessSetup {
+        process_mode: process_consts::PROCESS_MODE_REALTIME,
+        sample_rate: 48000.0,
+        max_samples_per_block: 64,
+        symbolic_sample_size: process_consts::SYMBOLIC_SAMPLE_32,
+        flags: 0,
+    })
+    .unwrap();
+    let mut bound = BoundProcessData::<f32>::new(2, 2, 64);
+
+    assert_no_alloc(|| {
+        for _ in 0..BLOCKS {
+            let mut io = pool.take().unwrap();
+            // The only set is out until it is recycled.
+            assert!(pool.take().is_none());
+            // 40 notes on 32 event slots, 8 controllers of 20 moves on 4 queues of 32
+            // points.
+            for i in 0..40u8 {
+                let (events, params) = io.lists();
+                midi.convert(&[0x90, i, 100], i as i32, events, params);
+            }
+            for i in 0..20u8 {
+                for cc in 0..8u8 {
+                    let (events, params) = io.lists();
+                    midi.convert(&[0xB0, cc, i], i as i32, events, params);
+                }
+            }
+            inst.process_bound(&mut bound, 64, Some(io.lists()))
+                .unwrap();
+            pool.recycle(io);
+        }
+    });
+
+    assert_eq!(counters.dropped_events(), (BLOCKS * 8) as u64);
+    assert_eq!(counters.dropped_points(), (BLOCKS * 4 * 20) as u64);
+    assert_eq!(counters.exhausted(), BLOCKS as u64);
+    assert_eq!(pool.available(), 1);
+}
@@ -0,0 +1,205 @@
+//! Scanner over real bundles: in-process results, cache reuse and the worker format.
+
+// Loads real binaries, which Miri cannot.
+#![cfg(not(miri))]
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use openvst3_host::scan::{run_worker, ScanDiff, ScanEvent, ScanFailure, Scanner};
+use openvst3_host::testsupport::MOCK_CID;
+use openvst3_testplugin::bundle::{make_broken_bundle, make_bundle, remove_bundle};
+use openvst3_testplugin::CLASS_NAME;
+
+#[test]
+fn scan_reports_plugins_and_failures() {
+    let bundle = make_bundle("scan");
+    let broken = make_broken_bundle(&bundle, "Broken");
+    let root = bundle.parent().unwrap().to_path_buf();
+    let mut scanner = Scanner::new()
+        .without_default_paths()
+        .add_path(&root)
+        .parallel(2);
+    assert_eq!(scanner.find_bundles(), vec![broken.clone(), bundle.clone()]);
+
+    let report = scanner.scan();
+    assert_eq!((report.scanned, report.cached), (2, 0));
+    assert_eq!(report.plugins.len(), 1);
+    let plugin = &report.plugins[0];
+    assert_eq!(plugin.name, CLASS_NAME);
+    assert_eq!(plugin.cid, MOCK_CID);
+    assert_eq!(plugin.path, bundle);
+    assert_eq!(report.failures.len(), 1);
+    assert_eq!(report.failures[0].path, broken);
+    assert!(matches!(
+        report.failures[0].failure,
+        ScanFailure::Load { .. }
+    ));
+
+    // Nothing changed: everything comes from the cache, unless failures are retried.
+    let mut again = Scanner::new()
+        .without_default_paths()
+        .add_path(&root)
+        .with_cache(scanner.into_cache());
+    let report = again.scan();
+    assert_eq!((report.scanned, report.cached), (0, 2));
+    assert_eq!(report.plugins.len(), 1);
+    let mut retry = Scanner::new()
+        .without_default_paths()
+        .add_path(&root)
+        .with_cache(again.into_cache())
+        .rescan_failed(true);
+    let report = retry.scan();
+    assert_eq!((report.scanned, report.cached), (1, 1));
+    assert_eq!(report.failures.len(), 1);
+
+    remove_bundle(&bundle);
+}
+
+#[test]
+fn scan_events_follow_each_bundle() {
+    let bundle = make_bundle("scan-events");
+    let broken = make_broken_bundle(&bundle, "Broken");
+    let root = bundle.parent().unwrap().to_path_buf();
+    let mut scanner = Scanner::new()
+        .without_default_paths()
+        .add_path(&root)
+        .parallel(2);
+
+    let events = scanner.scan_events();
+    assert_eq!(
+        (events.total(), events.scanned(), events.cached()),
+        (2, 2, 0)
+    );
+    let events: Vec<ScanEvent> = events.collect();
+    assert_eq!(events.len(), 4, "{events:?}");
+    // Each bundle starts before its result arrives.
+    let position = |wanted: &dyn Fn(&ScanEvent) -> bool| events.iter().position(wanted).unwrap();
+    let started = |path: &std::path::Path| {
+        let path = path.to_path_buf();
+        position(&move |e| *e == ScanEvent::Started(path.clone()))
+    };
+    let finished = position(&|e| matches!(e, ScanEvent::Finished(p) if p.cid == MOCK_CID));
+    let failed =
+        position(&|e| matches!(e, ScanEvent::Failed(p, ScanFailure::Load { .. }) if *p == broken));
+    assert!(started(&bundle) < finished);
+    assert!(started(&broken) < failed);
+    assert_eq!(scanner.cache().entries.len(), 2);
+
+    // Cached bundles come out of the cache as the same events, first.
+    let mut events = scanner.scan_events();
+    assert_eq!((events.scanned(), events.cached()), (0, 2));
+    assert_eq!(events.next(), Some(ScanEvent::Started(broken.clone())));
+    assert!(matches!(events.next(), Some(ScanEvent::Failed(..))));
+    // Dropped early, the scan keeps what it had.
+    drop(events);
+    assert_eq!(scanner.cache().entries.len(), 2);
+
+    remove_bundle(&bundle);
+}
+
+/// Wakes the thread parked in [`block_on`].
+struct Unpark(std::thread::Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+#[test]
+fn scan_stream_matches_the_blocking_scan() {
+    let bundle = make_bundle("scan-stream");
+    make_broken_bundle(&bundle, "Broken");
+    let root = bundle.parent().unwrap().to_path_buf();
+    let mut scanner = Scanner::new()
+        .without_default_paths()
+        .add_path(&root)
+        .parallel(2);
+
+    let mut stream = scanner.scan_stream();
+    let events = block_on(async {
+        let mut events = Vec::new();
+        while let Some(event) = stream.next().await {
+            events.push(event);
+        }
+        events
+    });
+    let starts = events
+        .iter()
+        .filter(|e| matches!(e, ScanEvent::Started(_)))
+        .count();
+    assert_eq!(starts, 2, "{events:?}");
+    let report = stream.into_report();
+    assert_eq!((report.scanned, report.cached), (2, 0));
+    assert_eq!(report.plugins.len(), 1);
+    assert_eq!(report.failures.len(), 1);
+
+    let report = scanner.scan();
+    assert_eq!((report.scanned, report.cached), (0, 2));
+
+    remove_bundle(&bundle);
+}
+
+#[test]
+fn worker_output_is_line_records() {
+    let bundle = make_bundle("scan-worker");
+    let mut out = Vec::new();
+    run_worker(&bundle, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.len(), 2, "{text}");
+    assert!(lines[0].starts_with("plugin\t4F70656E565354334D6F636B4761696E\t"));
+    assert!(lines[0].contains(CLASS_NAME));
+    assert_eq!(lines[1], "end");
+
+    let broken = make_broken_bundle(&bundle, "Broken");
+    let mut out = Vec::new();
+    run_worker(&broken, &mut out).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert!(text.starts_with("failure\tload\t"), "{text}");
+    remove_bundle(&bundle);
+}
+
+#[test]
+fn hashed_scans_see_a_rebuilt_binary() {
+    let bundle = make_bundle("scan-hash");
+    let root = bundle.parent().unwrap().to_path_buf();
+    let scan = || {
+        Scanner::new()
+            .without_default_paths()
+            .add_path(&root)
+            .hash_binaries(true)
+            .scan()
+            .plugins
+    };
+    let before = scan();
+    let hash = before[0].binary_hash.clone().unwrap();
+    assert_eq!(hash.len(), 64);
+    assert!(ScanDiff::compare(&before, &scan()).is_empty());
+
+    // Same version and classes, different bytes.
+    let binary = openvst3_host::BundlePath::resolve(&bundle).unwrap();
+    let mut bytes = std::fs::read(&binary).unwrap();
+    bytes.extend_from_slice(b"rebuilt");
+    std::fs::write(&binary, bytes).unwrap();
+    let after = scan();
+    assert_ne!(after[0].binary_hash.as_deref(), Some(hash.as_str()));
+    let diff = ScanDiff::compare(&before, &after);
+    assert_eq!(diff.updated, vec![(before[0].clone(), after[0].clone())]);
+    remove_bundle(&bundle);
+}
@@ -0,0 +1,198 @@
+//! Call tracing over the in-process mock (`trace` feature of openvst3-host).
+
+use openvst3_abi::K_RESULT_FALSE;
+use openvst3_abi::{process_consts, AudioBusBuffers32, ProcessData32, ProcessSetup};
+use openvst3_host::testsupport::{MockConfig, MockPlugin, MOCK_CID};
+use openvst3_host::trace::{set_process_sampling, TraceRecorder};
+use openvst3_host::{HostError, PluginInstance, ProcessMode};
+
+fn setup() -> ProcessSetup {
+    ProcessSetup {
+        process_mode: process_consts::PROCESS_MODE_REALTIME,
+        sample_rate: 44100.0,
+        max_samples_per_block: 32,
+        symbolic_sample_size: process_consts::SYMBOLIC_SAMPLE_32,
+        flags: 0,
+    }
+}
+
+fn process_silence(inst: &mut PluginInstance, frames: i32) {
+    let mut out = vec![0.0f32; frames as usize];
+    let mut out_ptrs = [out.as_mut_ptr()];
+    let mut out_bus = AudioBusBuffers32 {
+        num_channels: 1,
+        silence_flags: 0,
+        channel_buffers: out_ptrs.as_mut_ptr(),
+    };
+    let mut data = ProcessData32 {
+        num_inputs: 0,
+        num_outputs: 1,
+        inputs: core::ptr::null_mut(),
+        outputs: &mut out_bus,
+        num_samples: frames,
+        input_parameter_changes: core::ptr::null_mut(),
+        output_parameter_changes: core::ptr::null_mut(),
+        input_events: core::ptr::null_mut(),
+        output_events: core::ptr::null_mut(),
+        process_context: core::ptr::null_mut(),
+    };
+    unsafe { inst.process_32f(&mut data) }.unwrap();
+}
+
+#[test]
+fn records_lifecycle_sequence_with_arguments() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let recorder = TraceRecorder::new();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let mut inst = module.create_plugin(MOCK_CID).unwrap();
+        inst.activate(&setup()).unwrap();
+        inst.controller()
+            .unwrap()
+            .set_param_normalized(0, 0.25)
+            .unwrap();
+        process_silence(&mut inst, 32);
+    });
+
+    assert_eq!(
+        recorder.methods(),
+        [
+            "IComponent::initialize",
+            // Creation asks for a separate controller class,
+            "IComponent::getControllerClassId",
+            // and holds the bus layout and parameter count to the host limits.
+            "IComponent::getBusCount",
+            "IComponent::getBusInfo",
+            "IComponent::getBusCount",
+            "IComponent::getBusInfo",
+            "IComponent::getBusCount",
+            "IComponent::getBusCount",
+            "IEditController::getParameterCount",
+            // Bus layout (audio in/out, event in/out), then the default-active buses.
+            "IComponent::getBusCount",
+            "IComponent::getBusInfo",
+            "IComponent::getBusCount",
+            "IComponent::getBusInfo",
+            "IComponent::getBusCount",
+            "IComponent::getBusCount",
+            "IComponent::activateBus",
+            "IComponent::activateBus",
+            "IAudioProcessor::setupProcessing",
+            "IComponent::setActive",
+            "IAudioProcessor::setProcessing",
+            "IEditController::setParamNormalized",
+            "IAudioProcessor::process",
+            "IAudioProcessor::setProcessing",
+            "IComponent::setActive",
+            "IComponent::terminate",
+        ]
+    );
+    let calls = recorder.calls();
+    assert!(calls.windows(2).all(|w| w[0].seq < w[1].seq));
+    // getBusCount's and getParameterCount's results are counts, not tresults, and a
+    // single-component plugin answers getControllerClassId with kResultFalse.
+    assert!(calls
+        .iter()
+        .filter(|c| {
+            !matches!(
+                c.method.as_str(),
+                "getBusCount" | "getParameterCount" | "getControllerClassId"
+            )
+        })
+        .all(|c| c.result_code() == Some(0)));
+    assert_eq!(calls[1].result_code(), Some(1));
+    assert_eq!(calls[2].result_code(), Some(1));
+
+    assert_eq!(calls[16].field("direction"), Some("1"));
+    assert_eq!(calls[15].field("state"), Some("true"));
+    let setup_call = &calls[17];
+    assert_eq!(setup_call.field("sample_rate"), Some("44100.0"));
+    assert_eq!(setup_call.field("block_size"), Some("32"));
+    assert_eq!(calls[20].field("param_id"), Some("0"));
+    assert_eq!(calls[21].field("block_size"), Some("32"));
+    assert_eq!(calls[22].field("state"), Some("false"));
+}
+
+#[test]
+fn process_events_are_rate_limited() {
+    let plugin = MockPlugin::default();
+    let mut module = plugin.module().unwrap();
+    let recorder = TraceRecorder::new();
+    set_process_sampling(3, 10);
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let mut inst = module.create_plugin(MOCK_CID).unwrap();
+        inst.activate(&setup()).unwrap();
+        recorder.clear();
+        for _ in 0..25 {
+            process_silence(&mut inst, 8);
+        }
+        let traced = recorder
+            .methods()
+            .iter()
+            .filter(|m| *m == "IAudioProcessor::process")
+            .count();
+        // Blocks 0..3, then block 12 and 22.
+        assert_eq!(traced, 5);
+    });
+}
+
+#[test]
+fn process_mode_switch_restarts_processing_and_rolls_back() {
+    let plugin = MockPlugin::new(MockConfig {
+        reject_process_mode: Some(process_consts::PROCESS_MODE_PREFETCH),
+        ..MockConfig::default()
+    });
+    let mut module = plugin.module().unwrap();
+    let recorder = TraceRecorder::new();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let mut inst = module.create_plugin(MOCK_CID).unwrap();
+        inst.activate(&setup()).unwrap();
+        recorder.clear();
+        inst.set_process_mode(ProcessMode::Offline).unwrap();
+        assert_eq!(
+            recorder.methods(),
+            [
+                "IAudioProcessor::setProcessing",
+                "IComponent::setActive",
+                "IAudioProcessor::setupProcessing",
+                "IComponent::setActive",
+                "IAudioProcessor::setProcessing",
+            ]
+        );
+        let calls = recorder.calls();
+        assert_eq!(calls[0].field("state"), Some("false"));
+        assert_eq!(calls[1].field("state"), Some("false"));
+        assert_eq!(calls[2].field("process_mode"), Some("2"));
+        assert_eq!(calls[2].field("sample_rate"), Some("44100.0"));
+        assert_eq!(calls[2].field("block_size"), Some("32"));
+        assert_eq!(calls[3].field("state"), Some("true"));
+        assert_eq!(calls[4].field("state"), Some("true"));
+        assert_eq!(inst.process_mode(), Some(ProcessMode::Offline));
+
+        // Prefetch is refused, so the offline setup is made again before resuming.
+        recorder.clear();
+        assert!(matches!(
+            inst.set_process_mode(ProcessMode::Prefetch),
+            Err(HostError::TErr(K_RESULT_FALSE))
+        ));
+        assert_eq!(
+            recorder.methods(),
+            [
+                "IAudioProcessor::setProcessing",
+                "IComponent::setActive",
+                "IAudioProcessor::setupProcessing",
+                "IAudioProcessor::setupProcessing",
+                "IComponent::setActive",
+                "IAudioProcessor::setProcessing",
+            ]
+        );
+        let calls = recorder.calls();
+        assert_eq!(calls[2].field("process_mode"), Some("1"));
+        assert_eq!(calls[2].result_code(), Some(K_RESULT_FALSE.into()));
+        assert_eq!(calls[3].field("process_mode"), Some("2"));
+        assert_eq!(calls[3].result_code(), Some(0));
+        assert_eq!(inst.process_mode(), Some(ProcessMode::Offline));
+        assert!(inst.is_processing());
+        process_silence(&mut inst, 32);
+    });
+}
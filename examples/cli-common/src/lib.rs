@@ -0,0 +1,176 @@
+//! Exit codes and error output shared by the example command line tools.
+//!
+//! Every failure ends the process with one [`ExitCode`], picked by
+//! [`ExitCode::for_error`] from the [`HostError`] and the step that was running, so
+//! a script can tell a missing plugin (3) from one that failed while processing (6).
+//! With `--error-json` the final error is printed to stderr as one JSON object
+//! instead of a line of text:
+//!
+//! ```text
+//! {"category":"load","code":3,"message":"load error: dlopen failed: ..."}
+//! ```
+//!
+//! `tresult` is added when the plugin returned an error result.
+
+use std::fmt;
+use std::path::Path;
+
+use openvst3_host::HostError;
+
+/// The exit status of a failed run. Success is 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Bad arguments or input files, or a request the plugin cannot serve (unknown
+    /// class, interface or parameter).
+    UsageError = 2,
+    /// The module did not load: missing or broken binary or bundle, failing entry
+    /// point.
+    LoadError = 3,
+    /// The factory did not describe its classes.
+    ClassError = 4,
+    /// `createInstance`, `queryInterface` or the controller could not be had.
+    InstantiationError = 5,
+    /// A call on a created instance failed: setup, process, state, parameters.
+    ProcessingError = 6,
+    /// A validation check that ran failed.
+    ValidationFailed = 7,
+    /// A file could not be read or written.
+    Io = 8,
+}
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// The `category` of the `--error-json` object.
+    pub fn category(self) -> &'static str {
+        match self {
+            ExitCode::UsageError => "usage",
+            ExitCode::LoadError => "load",
+            ExitCode::ClassError => "class",
+            ExitCode::InstantiationError => "instantiation",
+            ExitCode::ProcessingError => "processing",
+            ExitCode::ValidationFailed => "validation",
+            ExitCode::Io => "io",
+        }
+    }
+
+    /// The code for `e` raised during `step`. Load and I/O errors have their own
+    /// codes whatever the step; other plugin faults are the step's; anything else
+    /// the host refused is a [`UsageError`](ExitCode::UsageError).
+    pub fn for_error(e: &HostError, step: ExitCode) -> ExitCode {
+        match e {
+            HostError::Io(_) => ExitCode::Io,
+            HostError::Dlopen(_)
+            | HostError::NoFactorySymbol(_)
+            | HostError::NotVst3(_)
+            | HostError::NullFactory
+            | HostError::ModuleEntryFailed
+            | HostError::InvalidBundle(_)
+            | HostError::BinaryNotFound => ExitCode::LoadError,
+            e if e.is_plugin_fault() => step,
+            _ => ExitCode::UsageError,
+        }
+    }
+}
+
+impl fmt::Display for ExitCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.category())
+    }
+}
+
+/// The error a run ends with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CliError {
+    pub exit: ExitCode,
+    pub message: String,
+    /// The plugin's error result, if that is what failed.
+    pub tresult: Option<i32>,
+    /// More lines for the text output, e.g. the loader's diagnosis of a failed load.
+    pub detail: Option<String>,
+}
+
+impl CliError {
+    pub fn new(exit: ExitCode, message: impl Into<String>) -> Self {
+        Self {
+            exit,
+            message: message.into(),
+            tresult: None,
+            detail: None,
+        }
+    }
+
+    pub fn usage(message: impl Into<String>) -> Self {
+        Self::new(ExitCode::UsageError, message)
+    }
+
+    /// `path` could not be read or written.
+    pub fn io(path: &Path, e: impl fmt::Display) -> Self {
+        Self::new(ExitCode::Io, format!("{}: {e}", path.display()))
+    }
+
+    /// `e` from `step`, reported as `<context>: <e>`.
+    pub fn host(context: &str, e: &HostError, step: ExitCode) -> Self {
+        Self {
+            exit: ExitCode::for_error(e, step),
+            message: format!("{context}: {e}"),
+            tresult: match e {
+                HostError::TErr(code) | HostError::CreateInstance { result: code, .. } => {
+                    Some(*code)
+                }
+                _ => None,
+            },
+            detail: match e {
+                HostError::Dlopen(d) => Some(d.diagnosis()),
+                HostError::NoFactorySymbol(m) | HostError::NotVst3(m) => Some(m.diagnosis()),
+                _ => None,
+            },
+        }
+    }
+
+    /// The `--error-json` object.
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut doc = serde_json::json!({
+            "category": self.exit.category(),
+            "code": self.exit.code(),
+            "message": self.message,
+        });
+        if let Some(tresult) = self.tresult {
+            doc["tresult"] = tresult.into();
+        }
+        doc
+    }
+
+    /// Print to stderr, as text or as the `--error-json` object.
+    pub fn report(&self, json: bool) {
+        if json {
+            eprintln!("{}", self.to_json());
+        } else {
+            eprintln!("{}", self.message);
+            if let Some(detail) = &self.detail {
+                eprint!("{detail}");
+            }
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Exit with 0, or report the error and exit with its code.
+pub fn exit(result: Result<(), CliError>, error_json: bool) -> ! {
+    match result {
+        Ok(()) => std::process::exit(0),
+        Err(e) => {
+            e.report(error_json);
+            std::process::exit(e.exit.code())
+        }
+    }
+}
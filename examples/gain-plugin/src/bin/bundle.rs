@@ -0,0 +1,73 @@
+//! Wrap the built gain plugin library in a `GainPlugin.vst3` bundle.
+//!
+//! `gain-plugin-bundle [OUT_DIR]` copies the library cargo built next to this binary
+//! into `OUT_DIR/GainPlugin.vst3/Contents/<arch>/` and writes its `moduleinfo.json`.
+//! `OUT_DIR` defaults to the binary's own folder, e.g. `target/debug`. Prints the
+//! bundle path.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use openvst3_host::bundle::{package, ModuleInfo, PackageOptions, PlistFields};
+use openvst3_host::{HostError, Module};
+
+const BUNDLE_NAME: &str = "GainPlugin";
+
+fn library(dir: &Path) -> Result<PathBuf, String> {
+    let name = format!(
+        "{}gain_plugin{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    );
+    // Test builds leave it in `deps/` only.
+    [dir.join(&name), dir.join("deps").join(&name)]
+        .into_iter()
+        .find(|p| p.is_file())
+        .ok_or_else(|| {
+            format!(
+                "{name} not found in {}; build the gain-plugin package first",
+                dir.display()
+            )
+        })
+}
+
+fn bundle(out_dir: &Path, lib: &Path) -> Result<PathBuf, HostError> {
+    let module = Module::load(lib)?;
+    let version = env!("CARGO_PKG_VERSION");
+    let options = PackageOptions {
+        module_info: Some(ModuleInfo::from_module(&module, BUNDLE_NAME, version)?),
+        macos_plist: cfg!(target_os = "macos").then(|| PlistFields {
+            identifier: "com.github.openvst3.gain".into(),
+            version: version.into(),
+            ..PlistFields::default()
+        }),
+        ..PackageOptions::new(BUNDLE_NAME)
+    };
+    drop(module);
+    package(lib, out_dir, &options)
+}
+
+fn main() -> ExitCode {
+    let exe = std::env::current_exe().expect("own executable path");
+    let target_dir = exe.parent().expect("executable in a folder");
+    let out_dir = std::env::args_os()
+        .nth(1)
+        .map_or_else(|| target_dir.to_path_buf(), PathBuf::from);
+    let lib = match library(target_dir) {
+        Ok(lib) => lib,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    match bundle(&out_dir, &lib) {
+        Ok(bundle) => {
+            println!("{}", bundle.display());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("cannot bundle {}: {e}", lib.display());
+            ExitCode::FAILURE
+        }
+    }
+}
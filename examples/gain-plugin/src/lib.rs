@@ -0,0 +1,53 @@
+//! A stereo gain plugin written with openvst3-plugin.
+//!
+//! One automatable parameter, [`GAIN`], in dB from -60 to +12; its value is saved
+//! with the component state. Build the `.vst3` bundle with
+//!
+//! ```text
+//! cargo run -p gain-plugin --bin gain-plugin-bundle
+//! cargo run -p host-cli -- --bundle target/debug/GainPlugin.vst3 --list
+//! ```
+
+use openvst3_plugin::{AudioProcessor, Factory, ParamDef, Params, PluginClass, ProcessCtx};
+
+pub const GAIN_PLUGIN_CID: [u8; 16] = *b"OpenVST3GainPlug";
+pub const CLASS_NAME: &str = "OpenVST3 Gain";
+
+/// Gain in dB.
+pub const GAIN: u32 = 0;
+pub const MIN_DB: f64 = -60.0;
+pub const MAX_DB: f64 = 12.0;
+
+pub fn db_to_gain(db: f64) -> f64 {
+    10f64.powf(db / 20.0)
+}
+
+#[derive(Default)]
+pub struct GainPlugin;
+
+impl AudioProcessor for GainPlugin {
+    fn params(&self) -> Params {
+        Params::new().param(
+            ParamDef::range(GAIN, "Gain", MIN_DB, MAX_DB, 0.0)
+                .unit("dB")
+                .precision(1),
+        )
+    }
+
+    fn process(&mut self, mut ctx: ProcessCtx<'_>) {
+        let gain = db_to_gain(ctx.params().plain(GAIN).unwrap_or(0.0));
+        ctx.audio().map(|x| x * gain);
+    }
+}
+
+pub fn factory() -> Factory {
+    Factory::new("OpenVST3")
+        .url("https://github.com/BobTheZombie/OpenVST3")
+        .class(
+            PluginClass::new::<GainPlugin>(GAIN_PLUGIN_CID, CLASS_NAME)
+                .sub_categories("Fx|Tools")
+                .version(env!("CARGO_PKG_VERSION")),
+        )
+}
+
+openvst3_plugin::export_plugin!(factory());
@@ -0,0 +1,85 @@
+//! Bundles the built plugin with `gain-plugin-bundle` and hosts it through dlopen.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use gain_plugin::{db_to_gain, CLASS_NAME, GAIN, GAIN_PLUGIN_CID, MAX_DB, MIN_DB};
+use openvst3_host::{list_classes, BundlePath, Module, RenderConfig};
+
+/// Run the bundle helper into a fresh folder; returns the `.vst3` path.
+fn make_bundle(tag: &str) -> PathBuf {
+    let out = std::env::temp_dir().join(format!("gain-plugin-{}-{tag}", std::process::id()));
+    std::fs::create_dir_all(&out).unwrap();
+    let run = Command::new(env!("CARGO_BIN_EXE_gain-plugin-bundle"))
+        .arg(&out)
+        .output()
+        .unwrap();
+    assert!(run.status.success(), "{run:?}");
+    let bundle = PathBuf::from(String::from_utf8(run.stdout).unwrap().trim());
+    assert_eq!(bundle, out.join("GainPlugin.vst3"));
+    bundle
+}
+
+fn load(bundle: &PathBuf) -> Module {
+    Module::load(BundlePath::resolve(bundle).unwrap()).unwrap()
+}
+
+#[test]
+fn bundle_lists_the_gain_class() {
+    let bundle = make_bundle("list");
+    let mut module = load(&bundle);
+    let classes = list_classes(&mut module).unwrap();
+    assert_eq!(classes.len(), 1);
+    let (_, name, _, cid) = classes[0].as_ref().unwrap();
+    assert_eq!((name.as_str(), *cid), (CLASS_NAME, GAIN_PLUGIN_CID));
+    drop(module);
+    let _ = std::fs::remove_dir_all(bundle.parent().unwrap());
+}
+
+#[test]
+fn sine_is_attenuated_by_six_db() {
+    let bundle = make_bundle("sine");
+    let mut module = load(&bundle);
+    let mut plugin = module.create_plugin(GAIN_PLUGIN_CID).unwrap();
+    let controller = plugin.controller().unwrap();
+    let info = controller.parameter_info(0).unwrap();
+    assert_eq!((info.id, info.units.as_str()), (GAIN, "dB"));
+
+    let minus_six = (-6.0 - MIN_DB) / (MAX_DB - MIN_DB);
+    controller.set_param_normalized(GAIN, minus_six).unwrap();
+    assert_eq!(
+        controller.param_string_by_value(GAIN, minus_six).unwrap(),
+        "-6.0"
+    );
+
+    let sine: Vec<f32> = (0..4800)
+        .map(|i| (2.0 * std::f64::consts::PI * 440.0 * i as f64 / 48_000.0).sin() as f32)
+        .collect();
+    let input = vec![sine.clone(), sine];
+    let config = RenderConfig {
+        block_size: 256,
+        ..RenderConfig::default()
+    };
+    let out = plugin
+        .render_offline(&config, &input, 4800, |_, _| {})
+        .unwrap();
+    let gain = db_to_gain(-6.0);
+    assert!((gain - 0.501_187).abs() < 1e-6);
+    for (o, i) in out.iter().zip(&input) {
+        assert_eq!(o.len(), i.len());
+        for (&o, &i) in o.iter().zip(i) {
+            assert!((o as f64 - i as f64 * gain).abs() < 1e-6, "{o} vs {i}");
+        }
+    }
+
+    // The gain survives a state round trip into a fresh instance.
+    let state = plugin.component_state().unwrap();
+    drop(plugin);
+    let fresh = module.create_plugin(GAIN_PLUGIN_CID).unwrap();
+    fresh.set_component_state(&state).unwrap();
+    let restored = fresh.controller().unwrap().get_param_normalized(GAIN);
+    assert!((restored - minus_six).abs() < 1e-12);
+    drop(fresh);
+    drop(module);
+    let _ = std::fs::remove_dir_all(bundle.parent().unwrap());
+}
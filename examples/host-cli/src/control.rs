@@ -0,0 +1,129 @@
+//! Controller commands: `--load-state`, `--set-param`, `--params` and `--dump-state`,
+//! run in that order on one instance of `--class`.
+
+use std::path::PathBuf;
+
+use openvst3_host as host;
+
+use cli_common::{CliError, ExitCode};
+
+use crate::Args;
+
+fn print_params(controller: &host::ControllerHandle) -> Result<(), CliError> {
+    let params = controller
+        .parameters()
+        .map_err(|e| CliError::host("parameter info error", &e, ExitCode::ClassError))?;
+    println!("params = {}", params.len());
+    for p in params {
+        let mut default = controller.format_param(p.id, p.default_normalized);
+        if !p.units.is_empty() {
+            default = format!("{default} {}", p.units);
+        }
+        let note = if p.is_read_only() {
+            "  [read-only]"
+        } else if !p.can_automate() {
+            "  [not automatable]"
+        } else {
+            ""
+        };
+        println!(
+            "#{:02}  id={:<8}  {:<24}  units={:<6}  default={} ({:.4}){}",
+            p.index, p.id, p.title, p.units, default, p.default_normalized, note
+        );
+        let flags = host::describe_param_flags(p.flags);
+        if !flags.is_empty() {
+            println!("      flags: {flags}");
+        }
+    }
+    Ok(())
+}
+
+fn set_param(controller: &host::ControllerHandle, id: u32, value: f64) -> Result<(), CliError> {
+    let info = controller
+        .find_parameter(id)
+        .map_err(|e| CliError::host("parameter info error", &e, ExitCode::ClassError))?
+        .ok_or_else(|| CliError::usage(format!("no parameter with id {id}")))?;
+    if info.is_read_only() {
+        return Err(CliError::usage(format!(
+            "parameter {id} ({}) is read-only; not changed",
+            info.title
+        )));
+    }
+    if !info.can_automate() {
+        return Err(CliError::usage(format!(
+            "parameter {id} ({}) is not automatable; not changed",
+            info.title
+        )));
+    }
+    controller
+        .set_param_normalized(id, value)
+        .map_err(|e| CliError::host("setParamNormalized error", &e, ExitCode::ProcessingError))?;
+    let now = controller.get_param_normalized(id);
+    let display = controller.param_string_by_value(id, now).map_err(|e| {
+        CliError::host("getParamStringByValue error", &e, ExitCode::ProcessingError)
+    })?;
+    println!(
+        "{} (id {id}) = {now:.4} -> {display}{}{}",
+        info.title,
+        if info.units.is_empty() { "" } else { " " },
+        info.units
+    );
+    Ok(())
+}
+
+/// Run the controller commands.
+pub fn run(args: &Args, bin: Result<PathBuf, host::HostError>) -> Result<(), CliError> {
+    let Some(idx) = args.class else {
+        return Err(CliError::usage(
+            "--params, --set-param and the state flags need --class",
+        ));
+    };
+    let mut module = bin
+        .and_then(host::Module::load)
+        .map_err(|e| CliError::host("load error", &e, ExitCode::LoadError))?;
+    let (_, _, cid) = host::read_class_info_v1(&mut module, idx)
+        .map_err(|e| CliError::host("class read error", &e, ExitCode::ClassError))?;
+    let plugin = module
+        .create_plugin(cid)
+        .map_err(|e| CliError::host("create error", &e, ExitCode::InstantiationError))?;
+
+    if let Some(path) = &args.load_state {
+        let state = std::fs::read(path).map_err(|e| CliError::io(path, e))?;
+        plugin
+            .set_component_state(&state)
+            .map_err(|e| CliError::host("setState error", &e, ExitCode::ProcessingError))?;
+        println!(
+            "loaded {} bytes of component state from {}",
+            state.len(),
+            path.display()
+        );
+    }
+
+    if args.params || !args.set_param.is_empty() {
+        let Some(controller) = plugin.controller() else {
+            return Err(CliError::new(
+                ExitCode::InstantiationError,
+                "class has no edit controller on its component",
+            ));
+        };
+        for &(id, value) in &args.set_param {
+            set_param(controller, id, value)?;
+        }
+        if args.params {
+            print_params(controller)?;
+        }
+    }
+
+    if let Some(path) = &args.dump_state {
+        let state = plugin
+            .component_state()
+            .map_err(|e| CliError::host("getState error", &e, ExitCode::ProcessingError))?;
+        std::fs::write(path, &state).map_err(|e| CliError::io(path, e))?;
+        println!(
+            "wrote {} bytes of component state to {}",
+            state.len(),
+            path.display()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,303 @@
+//! `--json` report: factory info, every class and, with `--probe`, what each audio
+//! class implements and survives. Per-class failures are entries in the document;
+//! only a module that cannot be loaded at all is reported as a top-level error.
+
+use openvst3_host as host;
+use openvst3_host::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::validate::Outcome;
+
+/// Bumped whenever a field is renamed or removed.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub schema_version: u32,
+    pub factory: Fallible<Factory>,
+    pub classes: Vec<ClassEntry>,
+}
+
+/// Document printed when the module itself failed to load.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadFailure {
+    pub schema_version: u32,
+    pub error: ErrorEntry,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ErrorEntry {
+    pub message: String,
+    pub plugin_fault: bool,
+}
+
+impl From<&host::HostError> for ErrorEntry {
+    fn from(e: &host::HostError) -> Self {
+        Self {
+            message: e.to_string(),
+            plugin_fault: e.is_plugin_fault(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Fallible<T> {
+    Ok(T),
+    Err { error: ErrorEntry },
+}
+
+impl<T> Fallible<T> {
+    fn from_result<U>(r: Result<U, host::HostError>, f: impl FnOnce(U) -> T) -> Self {
+        match r {
+            Ok(v) => Fallible::Ok(f(v)),
+            Err(e) => Fallible::Err { error: (&e).into() },
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Flags {
+    pub raw: u32,
+    pub names: Vec<String>,
+}
+
+/// Unknown bits stay in `raw` but get no name.
+impl Flags {
+    fn class(flags: ClassFlags) -> Self {
+        Self {
+            raw: flags.bits(),
+            names: lower(flags.iter_names().map(|(n, _)| n)),
+        }
+    }
+
+    fn factory(flags: FactoryFlags) -> Self {
+        Self {
+            raw: flags.bits() as u32,
+            names: lower(flags.iter_names().map(|(n, _)| n)),
+        }
+    }
+}
+
+fn lower<'a>(names: impl Iterator<Item = &'a str>) -> Vec<String> {
+    names.map(str::to_ascii_lowercase).collect()
+}
+
+#[derive(Serialize)]
+pub struct Factory {
+    pub vendor: String,
+    pub url: String,
+    pub email: String,
+    pub flags: Flags,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum ClassEntry {
+    Class(Box<Class>),
+    Unreadable { index: i32, error: ErrorEntry },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Class {
+    pub index: i32,
+    pub cid: String,
+    pub name: String,
+    pub name_encoding: &'static str,
+    pub category: String,
+    pub sub_categories: Vec<String>,
+    pub vendor: String,
+    pub version: String,
+    pub sdk_version: String,
+    pub class_flags: Flags,
+    #[serde(skip_serializing_if = "ProbeEntry::is_not_requested")]
+    pub probe: ProbeEntry,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum ProbeEntry {
+    NotRequested,
+    /// Serialized as `null`: the class is not an audio component (e.g. a controller).
+    NotApplicable,
+    Done(Probe),
+    Failed {
+        error: ErrorEntry,
+    },
+}
+
+impl ProbeEntry {
+    fn is_not_requested(&self) -> bool {
+        matches!(self, ProbeEntry::NotRequested)
+    }
+}
+
+/// Mirrors [`host::Capabilities`]; also the `--probe-worker` output.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Probe {
+    pub audio_processor: bool,
+    pub edit_controller: bool,
+    /// The separate controller class of a dual-component plugin.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub controller_cid: Option<String>,
+    pub unit_info: bool,
+    pub midi_mapping: bool,
+    pub note_expression_controller: bool,
+    pub buses: Buses,
+    pub latency_samples: Option<u32>,
+    /// As reported; 4294967295 is `kInfiniteTail`.
+    pub tail_samples: u32,
+    /// `None` without a controller.
+    pub parameter_count: Option<i32>,
+    pub float64: Trial,
+    pub state_round_trip: Trial,
+    pub null_process: Vec<NullProcess>,
+}
+
+/// One capability tried out on the plugin.
+#[derive(Serialize, Deserialize)]
+pub struct Trial {
+    pub outcome: Outcome,
+    /// Failure or skip reason.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub detail: Option<String>,
+}
+
+impl From<host::Outcome> for Trial {
+    fn from(outcome: host::Outcome) -> Self {
+        let (outcome, detail) = match outcome {
+            host::Outcome::Pass => (Outcome::Pass, None),
+            host::Outcome::Fail(d) => (Outcome::Fail, Some(d)),
+            host::Outcome::Skip(d) => (Outcome::Skip, Some(d)),
+            host::Outcome::Warn(d) => (Outcome::Warn, Some(d)),
+        };
+        Self { outcome, detail }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NullProcess {
+    pub sample_rate: f64,
+    #[serde(flatten)]
+    pub trial: Trial,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Buses {
+    pub audio_inputs: Vec<i32>,
+    pub audio_outputs: Vec<i32>,
+    pub event_inputs: i32,
+    pub event_outputs: i32,
+}
+
+impl From<host::Capabilities> for Probe {
+    fn from(c: host::Capabilities) -> Self {
+        let p = c.probe;
+        Self {
+            audio_processor: p.audio_processor,
+            edit_controller: p.edit_controller,
+            controller_cid: p
+                .architecture
+                .controller_cid()
+                .map(|cid| host::fmt_cid_hex(&cid.0)),
+            unit_info: c.unit_info,
+            midi_mapping: c.midi_mapping,
+            note_expression_controller: c.note_expression,
+            buses: Buses {
+                audio_inputs: p.buses.audio_inputs,
+                audio_outputs: p.buses.audio_outputs,
+                event_inputs: p.buses.event_inputs,
+                event_outputs: p.buses.event_outputs,
+            },
+            latency_samples: p.latency_samples,
+            tail_samples: c.tail_samples,
+            parameter_count: c.parameter_count,
+            float64: c.float64.into(),
+            state_round_trip: c.state_round_trip.into(),
+            null_process: c
+                .null_process
+                .into_iter()
+                .map(|(sample_rate, outcome)| NullProcess {
+                    sample_rate,
+                    trial: outcome.into(),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn encoding_name(e: host::Encoding) -> &'static str {
+    match e {
+        host::Encoding::Ascii => "ascii",
+        host::Encoding::Utf8 => "utf8",
+        host::Encoding::Lossy => "lossy",
+    }
+}
+
+fn class(c: &host::ClassInfo) -> Class {
+    Class {
+        index: c.index,
+        cid: host::fmt_cid_hex(&c.cid),
+        name: c.name.clone(),
+        name_encoding: encoding_name(c.name_encoding),
+        category: c.category.clone(),
+        sub_categories: c.sub_category_tags().map(str::to_string).collect(),
+        vendor: c.vendor.clone(),
+        version: c.version.clone(),
+        sdk_version: c.sdk_version.clone(),
+        class_flags: Flags::class(c.flags()),
+        probe: ProbeEntry::NotRequested,
+    }
+}
+
+/// Build the report for `module`, with `probe` run on every audio class if given.
+/// Unreadable classes are listed only when no filter narrows the selection.
+pub fn report(
+    module: &mut host::Module,
+    filter: Option<&host::ClassFilter>,
+    probe: Option<&dyn Fn([u8; 16]) -> ProbeEntry>,
+) -> Report {
+    let factory = Fallible::from_result(module.factory_info(), |f| Factory {
+        flags: Flags::factory(f.flags()),
+        vendor: f.vendor,
+        url: f.url,
+        email: f.email,
+    });
+    let entries: Vec<_> = module
+        .classes()
+        .filter(|e| e.map_or(filter.is_none(), |c| filter.is_none_or(|f| f.matches(c))))
+        .map(|e| {
+            e.cloned()
+                .map_err(|e| (e.index, ErrorEntry::from(&e.source)))
+        })
+        .collect();
+    let classes = entries
+        .into_iter()
+        .map(|entry| match entry {
+            Ok(info) => {
+                let mut c = class(&info);
+                if let Some(probe) = probe {
+                    c.probe = if info.category == classinfo_consts::K_VST_AUDIO_EFFECT_CLASS {
+                        probe(info.cid)
+                    } else {
+                        ProbeEntry::NotApplicable
+                    };
+                }
+                ClassEntry::Class(Box::new(c))
+            }
+            Err((index, error)) => ClassEntry::Unreadable { index, error },
+        })
+        .collect();
+    Report {
+        schema_version: SCHEMA_VERSION,
+        factory,
+        classes,
+    }
+}
@@ -1,8 +1,17 @@
 use clap::Parser;
-use openvst3_abi::IAudioProcessor;
+use cli_common::{CliError, ExitCode};
 use openvst3_host as host;
+use openvst3_host::prelude::*;
 use std::path::PathBuf;
 
+mod control;
+mod json;
+mod presets;
+mod probe;
+mod render;
+mod stress;
+mod validate;
+
 // Optional: load IIDs by name from iids.toml (same dir as binary or cwd)
 fn load_iids() -> std::collections::BTreeMap<String, [u8; 16]> {
     let mut map = std::collections::BTreeMap::new();
@@ -32,6 +41,60 @@ fn load_iids() -> std::collections::BTreeMap<String, [u8; 16]> {
     map
 }
 
+/// Resolve `--iid`-style hex or a name (built-in interfaces first, then iids.toml).
+/// `None` when neither is given.
+fn resolve_iid(
+    hex: Option<&str>,
+    name: Option<&str>,
+    iid_map: &std::collections::BTreeMap<String, [u8; 16]>,
+) -> Option<Result<[u8; 16], String>> {
+    if let Some(hex) = hex {
+        return Some(host::parse_hex_16(hex).map_err(|e| format!("iid parse error: {e}")));
+    }
+    let name = name?;
+    Some(
+        iids::by_name(name)
+            .map(|t| t.0)
+            .or_else(|| iid_map.get(name).copied())
+            .ok_or_else(|| {
+                format!("unknown interface name: {name} (not built in or in iids.toml)")
+            }),
+    )
+}
+
+/// Interface name for `iid` if known, else its hex.
+fn iid_label(iid: [u8; 16], iid_map: &std::collections::BTreeMap<String, [u8; 16]>) -> String {
+    iids::name_of(&Tuid(iid))
+        .or_else(|| {
+            iid_map
+                .iter()
+                .find(|(_, v)| **v == iid)
+                .map(|(k, _)| k.as_str())
+        })
+        .map_or_else(|| host::fmt_cid_hex(&iid), str::to_string)
+}
+
+fn category_filter(arg: &str) -> host::ClassFilter {
+    match arg.to_ascii_lowercase().as_str() {
+        "effect" | "effects" | "fx" => host::ClassFilter::audio_effects(),
+        "instrument" | "instruments" => host::ClassFilter::instruments(),
+        "controller" | "controllers" => host::ClassFilter::controllers(),
+        _ => host::ClassFilter::category(arg).or(host::ClassFilter::sub_category(arg)),
+    }
+}
+
+/// What a null process block reported beyond success, for the `processNN() OK` line.
+fn process_note(stats: &host::ProcessStats) -> String {
+    let silent = stats.plugin_marked_outputs_silent[0].count_ones();
+    if stats.nothing_to_do() {
+        ", plugin had nothing to do".into()
+    } else if silent > 0 {
+        format!(", {silent} flagged silent")
+    } else {
+        String::new()
+    }
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
@@ -47,28 +110,61 @@ struct Args {
     #[arg(long)]
     list: bool,
 
+    /// With --list: only show classes of this kind. `effect`, `instrument` and
+    /// `controller` are shorthands; anything else matches the category string or one
+    /// of the subcategory tags (e.g. `Delay` matches "Fx|Delay").
+    #[arg(long, value_name = "CATEGORY")]
+    category: Option<String>,
+
+    /// Print factory info and all classes as one JSON document (implies --list).
+    /// The exit code is non-zero only if the module cannot be loaded.
+    #[arg(long)]
+    json: bool,
+
+    /// Print the error a run ends with as a JSON object on stderr (category, code,
+    /// message and the plugin's tresult if any). Exit codes: 2 usage, 3 load, 4 class
+    /// info, 5 instantiation, 6 processing, 7 validation failed, 8 file I/O.
+    #[arg(long)]
+    error_json: bool,
+
+    /// Instantiate --class (default: every audio class), each in its own process, and
+    /// print what it implements and survives: controller interfaces, 64-bit
+    /// processing, latency, tail, buses, parameters, a state round trip and null
+    /// blocks at 44.1 and 192 kHz. With --json, the probes go into the class list.
+    #[arg(long)]
+    probe: bool,
+
     /// Index of class to instantiate (from --list)
     #[arg(long)]
     class: Option<i32>,
 
-    /// IID (16-byte hex) of interface to request at createInstance (e.g. IAudioProcessor)
+    /// IID (16-byte hex) of interface to request at createInstance (e.g. IAudioProcessor).
+    /// Without it (or --iid-name) the class is created as IComponent, falling back to
+    /// IAudioProcessor if the plugin refuses that.
     #[arg(long, value_name = "HEX32")]
     iid: Option<String>,
 
-    /// IID name (looked up in iids.toml) if --iid is not provided; e.g., IAudioProcessor
+    /// IID name if --iid is not provided, e.g. IComponent. Built-in interface names
+    /// always resolve; iids.toml can add more.
     #[arg(long, value_name = "NAME")]
     iid_name: Option<String>,
 
-    /// After instantiation, QueryInterface to this IID (hex or name) and drive that
-    #[arg(long)]
-    qi: bool,
+    /// After createInstance, QueryInterface to this IID (16-byte hex), release the
+    /// created pointer and drive the result
+    #[arg(long, value_name = "HEX32", conflicts_with = "qi_name")]
+    qi_iid: Option<String>,
+
+    /// Like --qi-iid, by interface name (e.g. create as IComponent, QI to IAudioProcessor)
+    #[arg(long, value_name = "NAME")]
+    qi_name: Option<String>,
 
-    /// Drive a single null process block with N frames on OUTS channels (requires --class and --iid/--iid-name)
+    /// Drive a single null process block with N frames on the --process-outs layout (requires --class)
     #[arg(long, default_value_t = 0)]
     process_frames: i32,
 
-    #[arg(long, default_value_t = 2)]
-    process_outs: i32,
+    /// Output layout for --process-frames: a name (mono, stereo, 5.1, …), `<n>ch` or 0x hex
+    #[arg(long, value_name = "LAYOUT", default_value = "stereo", value_parser = host::parse_arrangement)]
+    process_outs: u64,
 
     #[arg(long, default_value_t = 48000.0)]
     sample_rate: f64,
@@ -76,153 +172,399 @@ struct Args {
     /// Use 64-bit float processing (default: 32-bit)
     #[arg(long)]
     float64: bool,
+
+    /// Render --class offline to this WAV file
+    #[arg(long, value_name = "OUT.WAV")]
+    render: Option<PathBuf>,
+
+    /// With --render: length to render, e.g. `10s` or `500ms` (default: length of --input)
+    #[arg(long, value_parser = render::parse_duration, requires = "render")]
+    duration: Option<f64>,
+
+    /// With --render: audio fed to the main input bus; its sample rate overrides
+    /// --sample-rate
+    #[arg(long, value_name = "IN.WAV", requires = "render")]
+    input: Option<PathBuf>,
+
+    /// With --render: .vstpreset applied before rendering
+    #[arg(long, value_name = "FILE", requires = "render")]
+    preset: Option<PathBuf>,
+
+    /// With --render: set a parameter (normalized value) before the first block;
+    /// repeatable
+    #[arg(long, value_name = "ID=VALUE", value_parser = render::parse_param, requires = "render")]
+    param: Vec<(u32, f64)>,
+
+    /// With --render: frames rendered after the end; `auto` uses getTailSamples
+    /// (capped at 10 s)
+    #[arg(long, default_value = "auto", value_parser = render::parse_tail)]
+    tail: render::Tail,
+
+    /// With --render: sample format of the output file
+    #[arg(long, value_enum, default_value = "24")]
+    bit_depth: render::BitDepth,
+
+    /// With --render: frames per process call
+    #[arg(long, default_value_t = 512)]
+    block_size: usize,
+
+    /// With --render: also write every output bus the plugin does not keep silent
+    /// (a multi-out instrument's individual outs) to its own WAV file in DIR
+    #[arg(long, value_name = "DIR", requires = "render")]
+    stems: Option<PathBuf>,
+
+    /// With --render: render once per value of a parameter, from START to END
+    /// (normalized) in STEPS evenly spaced steps, each pass from the same starting
+    /// state. Writes OUT_000.wav, OUT_001.wav, … and OUT.json listing every file with
+    /// its value and the plugin's display string for it
+    #[arg(
+        long,
+        value_name = "ID:START:END:STEPS",
+        value_parser = render::parse_sweep,
+        requires = "render",
+        conflicts_with = "stems"
+    )]
+    sweep: Option<render::Sweep>,
+
+    /// With --stems: how the stem files are named
+    #[arg(long, value_enum, default_value = "numbered")]
+    stem_naming: render::StemNaming,
+
+    /// List the .vstpreset files in the standard preset folders of --class (default:
+    /// every audio class) for the factory's vendor, and in the --bundle
+    #[arg(long)]
+    list_presets: bool,
+
+    /// Print the parameter table of --class (id, title, units, default as the plugin
+    /// displays it, flags)
+    #[arg(long)]
+    params: bool,
+
+    /// setParamNormalized on --class and print the resulting display string;
+    /// repeatable. Read-only and non-automatable parameters are refused.
+    #[arg(long, value_name = "ID=VALUE", value_parser = render::parse_param)]
+    set_param: Vec<(u32, f64)>,
+
+    /// Write the component state of --class (after --load-state/--set-param) to FILE
+    #[arg(long, value_name = "FILE")]
+    dump_state: Option<PathBuf>,
+
+    /// Load component state from FILE into --class before anything else
+    #[arg(long, value_name = "FILE")]
+    load_state: Option<PathBuf>,
+
+    /// Run the validator on --class (default: every audio class), each check in its
+    /// own process. Prints a PASS/FAIL/SKIP table, or JSON with --json; exits with 7
+    /// if any check that ran failed.
+    #[arg(long)]
+    validate: bool,
+
+    /// With --validate: checks to run (state, buses, activation, arrangement,
+    /// controller, process, sidechain, params, ranges, automation, edits, teardown)
+    #[arg(long, value_delimiter = ',', value_parser = validate::parse_check, requires = "validate")]
+    checks: Vec<host::Check>,
+
+    /// With --validate: checks to leave out, e.g. automation for a quicker run
+    #[arg(long, value_delimiter = ',', value_parser = validate::parse_check, requires = "validate")]
+    skip_checks: Vec<host::Check>,
+
+    /// With --validate: sample rates for the process check (default 44100,48000)
+    #[arg(long, value_delimiter = ',', requires = "validate")]
+    sample_rates: Vec<f64>,
+
+    /// With --validate: block sizes for the process check (default 64,512)
+    #[arg(long, value_delimiter = ',', requires = "validate")]
+    block_sizes: Vec<usize>,
+
+    /// With --validate or --probe: seconds before a hung check or probe is killed and
+    /// failed
+    #[arg(long, value_name = "SECS", default_value_t = 30.0)]
+    check_timeout: f64,
+
+    /// Stress-test --class in this process: a seeded random sequence of lifecycle
+    /// operations, state round trips and parameter storms between processing blocks,
+    /// checked after every step. Prints the report; exits with 7 on a failure.
+    #[arg(long)]
+    stress: bool,
+
+    /// With --stress: the seed; the same seed replays the same sequence
+    #[arg(long, default_value_t = 0, requires = "stress")]
+    seed: u64,
+
+    /// With --stress: steps after the first activation
+    #[arg(long, default_value_t = 1000, requires = "stress")]
+    iterations: usize,
+
+    /// Internal: run one validation case (e.g. `process@48000/512`) on --cid
+    #[arg(long, value_name = "CASE", hide = true)]
+    validate_worker: Option<String>,
+
+    /// Internal: probe --cid
+    #[arg(long, hide = true)]
+    probe_worker: bool,
+
+    #[arg(long, value_name = "HEX32", hide = true)]
+    cid: Option<String>,
+}
+
+impl Args {
+    fn wants_controller_commands(&self) -> bool {
+        self.params
+            || !self.set_param.is_empty()
+            || self.dump_state.is_some()
+            || self.load_state.is_some()
+    }
+}
+
+fn print_json<T: serde::Serialize>(doc: &T) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(doc).expect("report serializes")
+    );
+}
+
+/// `--json` mode: everything goes to stdout as JSON, per-class failures included.
+fn run_json(args: &Args, bin: Result<PathBuf, host::HostError>) -> Result<(), CliError> {
+    let loaded = bin.and_then(|bin| Ok((host::Module::load(&bin)?, bin)));
+    let (mut module, bin) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            print_json(&json::LoadFailure {
+                schema_version: json::SCHEMA_VERSION,
+                error: (&e).into(),
+            });
+            return Err(CliError::host("load error", &e, ExitCode::LoadError));
+        }
+    };
+    let filter = args.category.as_deref().map(category_filter);
+    let timeout = std::time::Duration::from_secs_f64(args.check_timeout);
+    let probe = |cid| probe::probe_isolated(&bin, cid, timeout);
+    let probe: Option<&dyn Fn([u8; 16]) -> json::ProbeEntry> = args.probe.then_some(&probe);
+    print_json(&json::report(&mut module, filter.as_ref(), probe));
+    Ok(())
 }
 
 fn main() {
     let args = Args::parse();
+    let error_json = args.error_json;
+    cli_common::exit(run(args), error_json);
+}
+
+fn run(args: Args) -> Result<(), CliError> {
+    if args.json
+        || args.render.is_some()
+        || args.wants_controller_commands()
+        || args.validate
+        || args.validate_worker.is_some()
+        || args.probe
+        || args.probe_worker
+        || args.stress
+        || args.list_presets
+    {
+        let bin = match (&args.plugin, &args.bundle) {
+            (Some(p), _) => Ok(p.clone()),
+            (None, Some(b)) => host::BundlePath::resolve(b),
+            (None, None) => {
+                return Err(CliError::usage(
+                    "Provide either --plugin <file> or --bundle <dir>",
+                ))
+            }
+        };
+        if let Some(case) = &args.validate_worker {
+            return validate::run_worker(&args, bin, case);
+        }
+        if args.probe_worker {
+            return probe::run_worker(&args, bin);
+        }
+        if args.validate {
+            return validate::run(&args, bin);
+        }
+        if args.stress {
+            return stress::run(&args, bin);
+        }
+        if let Some(out) = &args.render {
+            return render::run(&args, bin, out);
+        }
+        if args.list_presets {
+            return presets::run(&args, bin);
+        }
+        if args.wants_controller_commands() {
+            return control::run(&args, bin);
+        }
+        if args.probe && !args.json {
+            return probe::run(&args, bin);
+        }
+        return run_json(&args, bin);
+    }
 
     let bin = if let Some(p) = args.plugin {
         p
     } else if let Some(b) = args.bundle {
-        match host::BundlePath::resolve(&b) {
-            Ok(p) => p,
-            Err(e) => {
-                eprintln!("bundle resolve error: {e}");
-                std::process::exit(2);
-            }
-        }
+        host::BundlePath::resolve(&b)
+            .map_err(|e| CliError::host("bundle resolve error", &e, ExitCode::LoadError))?
     } else {
-        eprintln!("Provide either --plugin <file> or --bundle <dir>");
-        std::process::exit(2);
+        return Err(CliError::usage(
+            "Provide either --plugin <file> or --bundle <dir>",
+        ));
     };
 
     let iid_map = load_iids();
 
-    match host::Module::load(&bin) {
-        Ok(mut module) => {
-            if args.list || args.class.is_none() {
-                match host::list_classes(&mut module) {
-                    Ok(list) => {
-                        println!("classes = {}", list.len());
-                        for (i, name, cat, cid) in list {
-                            println!(
-                                "#{i:02}  {:<22}  {:<24}  CID={}",
-                                cat,
-                                name,
-                                host::fmt_cid_hex(&cid)
-                            );
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("list error: {e}");
-                        std::process::exit(3);
-                    }
+    let mut module = host::Module::load(&bin)
+        .map_err(|e| CliError::host("load error", &e, ExitCode::LoadError))?;
+    if args.list || args.class.is_none() {
+        let filter = args
+            .category
+            .as_deref()
+            .map_or(host::ClassFilter::Any, category_filter);
+        // Unreadable classes can't be filtered; show them only in the full list.
+        let list: Vec<_> = module
+            .classes()
+            .filter(|e| e.map_or(args.category.is_none(), |c| filter.matches(c)))
+            .collect();
+        println!("classes = {}", list.len());
+        for entry in list {
+            let c = match entry {
+                Ok(c) => c,
+                Err(e) => {
+                    println!("#{:02}  <unreadable: {}>", e.index, e.source);
+                    continue;
                 }
+            };
+            println!(
+                "#{:02}  {:<22}  {:<24}  CID={}",
+                c.index,
+                c.category,
+                c.name,
+                host::fmt_cid_hex(&c.cid)
+            );
+            if c.name_encoding == host::Encoding::Lossy {
+                println!("      name: not valid UTF-8, shown with replacement characters");
             }
-            if let Some(idx) = args.class {
-                // grab class CID
-                let (_, _, cid_bytes) = match host::read_class_info_v1(&mut module, idx) {
-                    Ok(x) => x,
-                    Err(e) => {
-                        eprintln!("class read error: {e}");
-                        std::process::exit(4);
-                    }
-                };
+            let subs = c.parsed_sub_categories();
+            if !subs.is_empty() {
+                let tags: Vec<_> = subs.iter().map(|t| t.as_str()).collect();
+                println!("      subcategories: {}", tags.join(", "));
+            }
+            let flags = host::describe_class_flags(c.flags());
+            if !flags.is_empty() {
+                println!("      flags: {flags}");
+            }
+        }
+    }
+    if let Some(idx) = args.class {
+        // grab class CID
+        let (_, _, cid_bytes) = host::read_class_info_v1(&mut module, idx)
+            .map_err(|e| CliError::host("class read error", &e, ExitCode::ClassError))?;
 
-                // resolve IID
-                let iid_bytes = if let Some(hex) = args.iid.as_deref() {
-                    match host::parse_hex_16(hex) {
-                        Ok(x) => x,
+        // resolve IIDs
+        let iid_bytes = match resolve_iid(args.iid.as_deref(), args.iid_name.as_deref(), &iid_map) {
+            Some(Ok(x)) => Some(x),
+            Some(Err(msg)) => return Err(CliError::usage(msg)),
+            None => None,
+        };
+        let qi_iid = match resolve_iid(args.qi_iid.as_deref(), args.qi_name.as_deref(), &iid_map) {
+            Some(Ok(x)) => Some(x),
+            Some(Err(msg)) => return Err(CliError::usage(msg)),
+            None => None,
+        };
+        let final_iid = qi_iid.unwrap_or(iid_bytes.unwrap_or(iids::IAUDIO_PROCESSOR.0));
+        if args.process_frames > 0 && final_iid != iids::IAUDIO_PROCESSOR.0 {
+            return Err(CliError::usage(format!(
+                "--process-frames drives IAudioProcessor, not {}",
+                iid_label(final_iid, &iid_map)
+            )));
+        }
+
+        // create instance: as asked, or with the first IID the factory takes; either
+        // way only interfaces the instance answers are used
+        let probe = match iid_bytes {
+            Some(iid) => unsafe {
+                host::create_instance_raw(module.factory_mut(), cid_bytes, iid).map(|raw| {
+                    host::ProbeResult::of(
+                        host::InterfacePtr::from_raw_unchecked(raw as *mut FUnknown),
+                        Tuid(iid),
+                    )
+                })
+            },
+            None => host::instantiate_and_probe(&mut module, cid_bytes),
+        }
+        .map_err(|e| CliError::host("create error", &e, ExitCode::InstantiationError))?;
+
+        // the target interface must be answered
+        if let Some(qi_iid) = qi_iid {
+            unsafe { probe.instance().query::<FUnknown>(&Tuid(qi_iid)) }.map_err(|e| {
+                CliError::host("queryInterface error", &e, ExitCode::InstantiationError)
+            })?;
+        }
+        println!(
+            "instance satisfies {} (created as {})",
+            iid_label(final_iid, &iid_map),
+            iid_label(probe.created_as.0, &iid_map)
+        );
+
+        if args.process_frames > 0 {
+            let Some(processor) = &probe.processor else {
+                return Err(CliError::usage(format!(
+                    "--process-frames needs IAudioProcessor, but the instance only supports {}",
+                    probe.supported_names().join(", ")
+                )));
+            };
+            let proc_ptr = processor.as_ptr();
+            unsafe {
+                if args.float64 {
+                    match host::drive_process_capture_64f(
+                        proc_ptr,
+                        args.sample_rate,
+                        args.process_frames,
+                        speaker_arr::channel_count(args.process_outs) as i32,
+                        None,
+                    )
+                    .and_then(|block| block.stats())
+                    {
+                        Ok(stats) => println!(
+                            "process64() OK ({} frames, {} out{})",
+                            args.process_frames,
+                            host::format_arrangement(args.process_outs),
+                            process_note(&stats)
+                        ),
                         Err(e) => {
-                            eprintln!("iid parse error: {e}");
-                            std::process::exit(5);
-                        }
-                    }
-                } else if let Some(name) = args.iid_name.as_deref() {
-                    match iid_map.get(name) {
-                        Some(b) => *b,
-                        None => {
-                            eprintln!("iid name not found in iids.toml: {}", name);
-                            std::process::exit(5);
+                            return Err(CliError::host(
+                                "process64 error",
+                                &e,
+                                ExitCode::ProcessingError,
+                            ))
                         }
                     }
                 } else {
-                    eprintln!("provide --iid HEX32 or --iid-name from iids.toml");
-                    std::process::exit(5);
-                };
-
-                unsafe {
-                    // create instance
-                    let created =
-                        match host::create_instance_raw(module.factory_mut(), cid_bytes, iid_bytes)
-                        {
-                            Ok(p) => p,
-                            Err(e) => {
-                                eprintln!("createInstance error: {e}");
-                                std::process::exit(6);
-                            }
-                        };
-
-                    // if requested, QueryInterface to a different IID (by name or hex)
-                    let target_ptr = if args.qi {
-                        // if --iid-name was given, try the same; else use --iid again
-                        let qi_iid = iid_bytes; // simple: QI to same IID; adjust if you pass another value
-                        match host::query_interface(created, qi_iid) {
-                            Ok(p) => p,
-                            Err(e) => {
-                                eprintln!("QI error: {e}");
-                                std::process::exit(6);
-                            }
-                        }
-                    } else {
-                        created
-                    };
-
-                    if args.process_frames > 0 {
-                        if args.float64 {
-                            let proc_ptr = target_ptr as *mut IAudioProcessor;
-                            match host::drive_null_process_64f(
-                                proc_ptr,
-                                args.sample_rate,
-                                args.process_frames,
-                                args.process_outs,
-                            ) {
-                                Ok(_) => println!(
-                                    "process64() OK ({} frames, {} outs)",
-                                    args.process_frames, args.process_outs
-                                ),
-                                Err(e) => {
-                                    eprintln!("process64 error: {e}");
-                                    std::process::exit(7);
-                                }
-                            }
-                        } else {
-                            let proc_ptr = target_ptr as *mut IAudioProcessor;
-                            match host::drive_null_process_32f(
-                                proc_ptr,
-                                args.sample_rate,
-                                args.process_frames,
-                                args.process_outs,
-                            ) {
-                                Ok(_) => println!(
-                                    "process32() OK ({} frames, {} outs)",
-                                    args.process_frames, args.process_outs
-                                ),
-                                Err(e) => {
-                                    eprintln!("process32 error: {e}");
-                                    std::process::exit(7);
-                                }
-                            }
+                    match host::drive_process_capture_32f(
+                        proc_ptr,
+                        args.sample_rate,
+                        args.process_frames,
+                        speaker_arr::channel_count(args.process_outs) as i32,
+                        None,
+                    )
+                    .and_then(|block| block.stats())
+                    {
+                        Ok(stats) => println!(
+                            "process32() OK ({} frames, {} out{})",
+                            args.process_frames,
+                            host::format_arrangement(args.process_outs),
+                            process_note(&stats)
+                        ),
+                        Err(e) => {
+                            return Err(CliError::host(
+                                "process32 error",
+                                &e,
+                                ExitCode::ProcessingError,
+                            ))
                         }
-                    } else {
-                        println!("Instance created (no processing requested).");
                     }
                 }
             }
-        }
-        Err(e) => {
-            eprintln!("load error: {e}");
-            std::process::exit(1);
+        } else {
+            println!("Instance created (no processing requested).");
         }
     }
+    Ok(())
 }
@@ -0,0 +1,48 @@
+//! `--list-presets`: the `.vstpreset` files in the standard preset folders of `--class`
+//! (default: every audio class), filed under the factory's vendor.
+
+use std::path::PathBuf;
+
+use openvst3_host as host;
+use openvst3_host::prelude::*;
+
+use cli_common::{CliError, ExitCode};
+
+use crate::Args;
+
+/// List the presets.
+pub fn run(args: &Args, bin: Result<PathBuf, host::HostError>) -> Result<(), CliError> {
+    let module = bin
+        .and_then(host::Module::load)
+        .map_err(|e| CliError::host("load error", &e, ExitCode::LoadError))?;
+    let vendor = module
+        .factory_info()
+        .map_err(|e| CliError::host("factory info error", &e, ExitCode::ClassError))?
+        .vendor;
+    let classes: Vec<_> = module
+        .classes()
+        .filter_map(Result::ok)
+        .filter(|c| match args.class {
+            Some(index) => c.index == index,
+            None => c.category == classinfo_consts::K_VST_AUDIO_EFFECT_CLASS,
+        })
+        .collect();
+    if let (Some(index), true) = (args.class, classes.is_empty()) {
+        return Err(CliError::usage(format!("no readable class #{index}")));
+    }
+    for class in classes {
+        let mut locations = host::preset_locations(&vendor, &class.name);
+        if let Some(bundle) = &args.bundle {
+            locations = locations.with_bundle(bundle);
+        }
+        let presets = host::list_presets(&locations);
+        println!("{} by {vendor}: {} presets", class.name, presets.len());
+        for dir in locations.all() {
+            println!("  in {}", dir.display());
+        }
+        for preset in presets {
+            println!("  {:<24}  {}", preset.name, preset.path.display());
+        }
+    }
+    Ok(())
+}
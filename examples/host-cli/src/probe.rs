@@ -0,0 +1,198 @@
+//! `--probe`: a capability matrix of every audio class (or `--class`), from
+//! [`host::Module::probe_capabilities`].
+//!
+//! Each class is probed in a child `host-cli --probe-worker` process, so one that
+//! crashes, or hangs past `--check-timeout`, fails its own entry while the others are
+//! still probed. The worker prints the class's [`json::Probe`]; the parent prints a
+//! table or, with `--json`, puts the probes into the class list.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use openvst3_host as host;
+
+use cli_common::{CliError, ExitCode};
+
+use crate::json::{self, ErrorEntry, ProbeEntry, Trial};
+use crate::validate::Outcome;
+use crate::Args;
+
+/// `--probe-worker --cid HEX`: probe one class in this process, print it as JSON.
+pub fn run_worker(args: &Args, bin: Result<PathBuf, host::HostError>) -> Result<(), CliError> {
+    let cid = match args.cid.as_deref().map(host::parse_hex_16) {
+        Some(Ok(cid)) => cid,
+        Some(Err(e)) => return Err(CliError::usage(e.to_string())),
+        None => return Err(CliError::usage("--probe-worker needs --cid")),
+    };
+    let mut module = bin.and_then(host::Module::load).map_err(|e| CliError {
+        // The parent reports the last line of stderr, which must be the message.
+        detail: None,
+        ..CliError::host("load error", &e, ExitCode::LoadError)
+    })?;
+    let caps = module.probe_capabilities(cid).map_err(|e| CliError {
+        detail: None,
+        ..CliError::host("probe error", &e, ExitCode::InstantiationError)
+    })?;
+    println!(
+        "{}",
+        serde_json::to_string(&json::Probe::from(caps)).expect("probe serializes")
+    );
+    Ok(())
+}
+
+/// Probe `cid` in a worker process; crashes, hangs and errors become
+/// [`ProbeEntry::Failed`].
+pub fn probe_isolated(bin: &Path, cid: [u8; 16], timeout: Duration) -> ProbeEntry {
+    let exe = std::env::current_exe().expect("current executable");
+    let mut cmd = Command::new(exe);
+    cmd.arg("--plugin")
+        .arg(bin)
+        .arg("--probe-worker")
+        .arg("--cid")
+        .arg(host::fmt_cid_hex(&cid));
+    let failed = |message: String| ProbeEntry::Failed {
+        error: ErrorEntry {
+            message,
+            plugin_fault: true,
+        },
+    };
+    match host::isolate::run_isolated(&mut cmd, "probe worker", timeout) {
+        Ok(out) => match serde_json::from_slice::<json::Probe>(&out.stdout) {
+            Ok(probe) if out.status.success() => ProbeEntry::Done(probe),
+            _ => {
+                let stderr = String::from_utf8_lossy(&out.stderr);
+                let last = stderr.lines().last().unwrap_or("no output").trim();
+                failed(format!("worker exited with {}: {last}", out.status))
+            }
+        },
+        Err(host::HostError::TimedOut { after, .. }) => {
+            failed(format!("did not finish within {after:?}"))
+        }
+        Err(e) => failed(e.to_string()),
+    }
+}
+
+fn yes_no(b: bool) -> &'static str {
+    if b {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+fn trial(t: &Trial) -> String {
+    let word = match t.outcome {
+        Outcome::Pass => "yes",
+        Outcome::Fail => "FAIL",
+        Outcome::Skip => "SKIP",
+        Outcome::Warn => "WARN",
+    };
+    match &t.detail {
+        Some(detail) => format!("{word}: {detail}"),
+        None => word.into(),
+    }
+}
+
+fn print_table(name: &str, cid: [u8; 16], entry: &ProbeEntry) {
+    println!("{name}  CID={}", host::fmt_cid_hex(&cid));
+    let p = match entry {
+        ProbeEntry::Done(p) => p,
+        ProbeEntry::Failed { error } => {
+            println!("  probe failed: {}", error.message);
+            return;
+        }
+        ProbeEntry::NotRequested | ProbeEntry::NotApplicable => return,
+    };
+    let buses = format!(
+        "audio in {:?} out {:?}, event in {} out {}",
+        p.buses.audio_inputs, p.buses.audio_outputs, p.buses.event_inputs, p.buses.event_outputs
+    );
+    let mut rows = vec![
+        ("IAudioProcessor", yes_no(p.audio_processor).to_string()),
+        (
+            "IEditController",
+            match &p.controller_cid {
+                Some(cid) => format!("separate class {cid}"),
+                None => yes_no(p.edit_controller).into(),
+            },
+        ),
+        ("IUnitInfo", yes_no(p.unit_info).into()),
+        ("IMidiMapping", yes_no(p.midi_mapping).into()),
+        (
+            "INoteExpressionController",
+            yes_no(p.note_expression_controller).into(),
+        ),
+        ("64-bit processing", trial(&p.float64)),
+        (
+            "latency",
+            p.latency_samples
+                .map_or("-".into(), |n| format!("{n} samples")),
+        ),
+        (
+            "tail",
+            match host::Tail::from_samples(p.tail_samples) {
+                host::Tail::None => "none".into(),
+                host::Tail::Samples(n) => format!("{n} samples"),
+                host::Tail::Infinite => "infinite".into(),
+            },
+        ),
+        ("buses", buses),
+        (
+            "parameters",
+            p.parameter_count
+                .map_or("no controller".into(), |n| n.to_string()),
+        ),
+        ("state round trip", trial(&p.state_round_trip)),
+    ];
+    let null_process: Vec<(String, String)> = p
+        .null_process
+        .iter()
+        .map(|n| {
+            (
+                format!("null process {} Hz", n.sample_rate),
+                trial(&n.trial),
+            )
+        })
+        .collect();
+    rows.extend(null_process.iter().map(|(k, v)| (k.as_str(), v.clone())));
+    for (what, value) in rows {
+        println!("  {what:<26} {value}");
+    }
+}
+
+/// `--probe` without `--json`: one table per class. Classes that fail to probe are
+/// listed with the reason; only a module that cannot be loaded fails the run.
+pub fn run(args: &Args, bin: Result<PathBuf, host::HostError>) -> Result<(), CliError> {
+    let load_error = |e| CliError::host("load error", &e, ExitCode::LoadError);
+    let bin = bin.map_err(load_error)?;
+    let mut module = host::Module::load(&bin).map_err(load_error)?;
+    let classes: Vec<(String, [u8; 16])> = match args.class {
+        Some(idx) => {
+            let (name, _, cid) = host::read_class_info_v1(&mut module, idx)
+                .map_err(|e| CliError::host("class read error", &e, ExitCode::ClassError))?;
+            vec![(name, cid)]
+        }
+        None => module
+            .classes_matching(
+                &host::ClassFilter::audio_effects().or(host::ClassFilter::instruments()),
+            )
+            .map(|c| (c.name.clone(), c.cid))
+            .collect(),
+    };
+    // The workers load their own copies.
+    drop(module);
+    if classes.is_empty() {
+        return Err(CliError::usage(
+            "no audio classes to probe; pick one with --class",
+        ));
+    }
+    let timeout = Duration::from_secs_f64(args.check_timeout);
+    for (i, (name, cid)) in classes.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        print_table(name, *cid, &probe_isolated(&bin, *cid, timeout));
+    }
+    Ok(())
+}
@@ -0,0 +1,527 @@
+//! `--render` mode: offline bounce of one class to a WAV file.
+//!
+//! The class picked with `--class` is created, optionally loaded from a `.vstpreset`,
+//! given its `--param` values and rendered in `kOffline` mode over `--input` (effects)
+//! or silence of `--duration` (generators). Progress goes to stderr. With `--stems`
+//! every aux output bus is rendered too, and each audible bus also gets a file of its
+//! own.
+//!
+//! With `--sweep` the same render runs once per value of one parameter. Each pass
+//! starts from the state captured after `--preset` and `--param`, so passes do not
+//! depend on each other, and gets its value as a parameter change at frame 0. The
+//! files are numbered after `OUT.WAV` (`out_000.wav`, `out_001.wav`, …) and listed
+//! with their values in `out.json`.
+
+use std::path::{Path, PathBuf};
+
+use openvst3_host as host;
+use serde::Serialize;
+
+use cli_common::{CliError, ExitCode};
+
+use crate::Args;
+
+/// Upper bound for `--tail auto` when the plugin reports an infinite tail.
+const AUTO_TAIL_CAP_SECS: f64 = 10.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tail {
+    Auto,
+    Frames(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum BitDepth {
+    #[value(name = "16")]
+    Int16,
+    #[value(name = "24")]
+    Int24,
+    #[value(name = "32f")]
+    Float32,
+}
+
+/// How [`write_stems`] names its files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum StemNaming {
+    /// `02 Aux 2.wav`: the bus index, then its name.
+    Numbered,
+    /// `Aux 2.wav`; a name an earlier bus took gets the bus index appended.
+    Name,
+}
+
+/// `10s`, `500ms` or plain seconds (`2.5`).
+pub fn parse_duration(s: &str) -> Result<f64, String> {
+    let (num, scale) = if let Some(ms) = s.strip_suffix("ms") {
+        (ms, 1e-3)
+    } else if let Some(secs) = s.strip_suffix('s') {
+        (secs, 1.0)
+    } else {
+        (s, 1.0)
+    };
+    match num.trim().parse::<f64>() {
+        Ok(v) if v.is_finite() && v >= 0.0 => Ok(v * scale),
+        _ => Err(format!("expected a duration like 10s or 500ms, got {s:?}")),
+    }
+}
+
+pub fn parse_tail(s: &str) -> Result<Tail, String> {
+    if s.eq_ignore_ascii_case("auto") {
+        return Ok(Tail::Auto);
+    }
+    s.parse()
+        .map(Tail::Frames)
+        .map_err(|_| format!("expected `auto` or a frame count, got {s:?}"))
+}
+
+/// `ID=VALUE` with a normalized value in 0..=1.
+pub fn parse_param(s: &str) -> Result<(u32, f64), String> {
+    let (id, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected ID=VALUE, got {s:?}"))?;
+    let id = id
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid parameter id {id:?}"))?;
+    match value.trim().parse::<f64>() {
+        Ok(v) if (0.0..=1.0).contains(&v) => Ok((id, v)),
+        _ => Err(format!(
+            "parameter value must be normalized (0..1), got {value:?}"
+        )),
+    }
+}
+
+/// `--sweep ID:START:END:STEPS`: `steps` renders with the parameter at evenly spaced
+/// normalized values from `start` to `end`, both included.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sweep {
+    pub id: u32,
+    pub start: f64,
+    pub end: f64,
+    pub steps: usize,
+}
+
+impl Sweep {
+    /// The value of pass `i`.
+    pub fn value(&self, i: usize) -> f64 {
+        if self.steps <= 1 {
+            return self.start;
+        }
+        self.start + (self.end - self.start) * i as f64 / (self.steps - 1) as f64
+    }
+}
+
+pub fn parse_sweep(s: &str) -> Result<Sweep, String> {
+    let [id, start, end, steps] = s.split(':').collect::<Vec<_>>()[..] else {
+        return Err(format!("expected ID:START:END:STEPS, got {s:?}"));
+    };
+    let id = id
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid parameter id {id:?}"))?;
+    let normalized = |v: &str| match v.trim().parse::<f64>() {
+        Ok(v) if (0.0..=1.0).contains(&v) => Ok(v),
+        _ => Err(format!("sweep values must be normalized (0..1), got {v:?}")),
+    };
+    let (start, end) = (normalized(start)?, normalized(end)?);
+    match steps.trim().parse::<usize>() {
+        Ok(steps) if steps > 0 => Ok(Sweep {
+            id,
+            start,
+            end,
+            steps,
+        }),
+        _ => Err(format!("sweep steps must be at least 1, got {steps:?}")),
+    }
+}
+
+/// `--sweep` manifest written next to the renders.
+#[derive(Serialize)]
+struct SweepManifest {
+    param: u32,
+    /// `None` without an edit controller, or for an id it does not list.
+    title: Option<String>,
+    units: Option<String>,
+    renders: Vec<SweepRender>,
+}
+
+#[derive(Serialize)]
+struct SweepRender {
+    /// Relative to the manifest.
+    file: String,
+    value: f64,
+    /// `getParamStringByValue`; `None` where the plugin has no string for it.
+    display: Option<String>,
+}
+
+/// Planar samples of `path` and its sample rate.
+fn read_wav(path: &Path) -> Result<(Vec<Vec<f32>>, f64), hound::Error> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let mut planar = vec![Vec::new(); spec.channels as usize];
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 * scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    for frame in interleaved.chunks(planar.len().max(1)) {
+        for (ch, &s) in planar.iter_mut().zip(frame) {
+            ch.push(s);
+        }
+    }
+    Ok((planar, spec.sample_rate as f64))
+}
+
+type WavOut = hound::WavWriter<std::io::BufWriter<std::fs::File>>;
+
+/// Create the output file up front so an unwritable path fails before rendering.
+fn create_wav(
+    path: &Path,
+    channels: usize,
+    sample_rate: f64,
+    depth: BitDepth,
+) -> Result<WavOut, hound::Error> {
+    let (bits_per_sample, sample_format) = match depth {
+        BitDepth::Int16 => (16, hound::SampleFormat::Int),
+        BitDepth::Int24 => (24, hound::SampleFormat::Int),
+        BitDepth::Float32 => (32, hound::SampleFormat::Float),
+    };
+    let spec = hound::WavSpec {
+        channels: channels as u16,
+        sample_rate: sample_rate.round() as u32,
+        bits_per_sample,
+        sample_format,
+    };
+    hound::WavWriter::create(path, spec)
+}
+
+fn write_wav(
+    mut writer: WavOut,
+    channels: &[Vec<f32>],
+    depth: BitDepth,
+) -> Result<(), hound::Error> {
+    let frames = channels.first().map_or(0, Vec::len);
+    for i in 0..frames {
+        for ch in channels {
+            let s = ch[i];
+            match depth {
+                BitDepth::Int16 => writer.write_sample((s.clamp(-1.0, 1.0) * 32767.0) as i16)?,
+                BitDepth::Int24 => {
+                    writer.write_sample((s.clamp(-1.0, 1.0) * 8_388_607.0) as i32)?
+                }
+                BitDepth::Float32 => writer.write_sample(s)?,
+            }
+        }
+    }
+    writer.finalize()
+}
+
+/// `name` made safe for a file name; `bus N` for an unnamed bus.
+fn file_stem(index: i32, name: &str) -> String {
+    let clean: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    match clean.trim() {
+        "" => format!("bus {index}"),
+        clean => clean.to_string(),
+    }
+}
+
+/// Write each bus of `rendered` the plugin did not keep silent to a WAV file in `dir`,
+/// creating it. Returns the files written, in bus order.
+pub fn write_stems(
+    rendered: &host::RenderedAudio,
+    dir: &Path,
+    naming: StemNaming,
+    depth: BitDepth,
+) -> Result<Vec<PathBuf>, CliError> {
+    std::fs::create_dir_all(dir).map_err(|e| CliError::io(dir, e))?;
+    let mut written: Vec<PathBuf> = Vec::new();
+    for bus in rendered.audible() {
+        let stem = file_stem(bus.index, &bus.name);
+        let mut path = match naming {
+            StemNaming::Numbered => dir.join(format!("{:02} {stem}.wav", bus.index)),
+            StemNaming::Name => dir.join(format!("{stem}.wav")),
+        };
+        if written.contains(&path) {
+            path = dir.join(format!("{stem} {}.wav", bus.index));
+        }
+        create_wav(&path, bus.channels.len(), rendered.sample_rate, depth)
+            .and_then(|writer| write_wav(writer, &bus.channels, depth))
+            .map_err(|e| CliError::io(&path, e))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Load `path` as a preset, warning when it was saved for another class.
+fn load_preset(plugin: &host::PluginInstance, path: &Path, cid: [u8; 16]) -> Result<(), CliError> {
+    let bytes = std::fs::read(path).map_err(|e| CliError::io(path, e))?;
+    let preset = host::VstPreset::parse(&bytes).map_err(|e| CliError::io(path, e))?;
+    if preset.class_id != cid {
+        eprintln!(
+            "warning: preset is for class {}, not {}",
+            host::fmt_cid_hex(&preset.class_id),
+            host::fmt_cid_hex(&cid)
+        );
+    }
+    plugin
+        .load_preset(&preset)
+        .map_err(|e| CliError::host("preset load error", &e, ExitCode::ProcessingError))
+}
+
+/// Run `--render out.wav`.
+pub fn run(args: &Args, bin: Result<PathBuf, host::HostError>, out: &Path) -> Result<(), CliError> {
+    let Some(idx) = args.class else {
+        return Err(CliError::usage("--render needs --class"));
+    };
+    let input = match &args.input {
+        Some(path) => Some(read_wav(path).map_err(|e| CliError::io(path, e))?),
+        None => None,
+    };
+    let sample_rate = input.as_ref().map_or(args.sample_rate, |(_, sr)| *sr);
+    let frames = match (args.duration, &input) {
+        (Some(secs), _) => (secs * sample_rate).round() as usize,
+        (None, Some((planar, _))) => planar.first().map_or(0, Vec::len),
+        (None, None) => return Err(CliError::usage("--render needs --duration or --input")),
+    };
+
+    let mut module = bin
+        .and_then(host::Module::load)
+        .map_err(|e| CliError::host("load error", &e, ExitCode::LoadError))?;
+    let (_, _, cid) = host::read_class_info_v1(&mut module, idx)
+        .map_err(|e| CliError::host("class read error", &e, ExitCode::ClassError))?;
+    let mut plugin = module
+        .plugin(cid)
+        .io_mode(host::IoMode::OfflineProcessing)
+        .create()
+        .map_err(|e| CliError::host("create error", &e, ExitCode::InstantiationError))?;
+
+    if let Some(path) = &args.preset {
+        load_preset(&plugin, path, cid)?;
+    }
+    if !args.param.is_empty() {
+        let Some(controller) = plugin.controller() else {
+            return Err(CliError::usage(
+                "--param needs a plugin with an edit controller",
+            ));
+        };
+        for &(id, value) in &args.param {
+            controller.set_param_normalized(id, value).map_err(|e| {
+                CliError::host(&format!("parameter {id}"), &e, ExitCode::ProcessingError)
+            })?;
+        }
+    }
+
+    let wanted_inputs = input.as_ref().map_or(0, |(planar, _)| planar.len());
+    let wanted_outputs = if wanted_inputs > 0 { wanted_inputs } else { 2 };
+    let (input_channels, output_channels) =
+        plugin.negotiate_channels(wanted_inputs, wanted_outputs);
+    if (input_channels, output_channels) != (wanted_inputs, wanted_outputs) {
+        let (ins, outs) = plugin.channel_arrangements(wanted_inputs, wanted_outputs);
+        let report = host::explain_arrangement_mismatch(plugin.processor(), &ins, &outs);
+        eprintln!(
+            "note: plugin refused {wanted_inputs} in / {wanted_outputs} out, \
+             rendering {input_channels} in / {output_channels} out\n{report}"
+        );
+    }
+    let (tail, plugin_tail) = match args.tail {
+        Tail::Auto => (0, Some((AUTO_TAIL_CAP_SECS * sample_rate) as usize)),
+        Tail::Frames(n) => (n, None),
+    };
+    let config = host::RenderConfig {
+        sample_rate,
+        block_size: args.block_size,
+        input_channels,
+        output_channels,
+        tail,
+        plugin_tail,
+        sidechain: None,
+        aux_outputs: args.stems.is_some(),
+        priority: Some(host::Profile::High),
+    };
+    let planar = input.map(|(planar, _)| planar).unwrap_or_default();
+    if let Some(sweep) = args.sweep {
+        return run_sweep(
+            &mut plugin,
+            &config,
+            &planar,
+            frames,
+            sweep,
+            out,
+            args.bit_depth,
+        );
+    }
+    let rendered = render_to(
+        &mut plugin,
+        &config,
+        &planar,
+        frames,
+        &host::RenderEvents::default(),
+        out,
+        args.bit_depth,
+    )?;
+    report_notes(&rendered);
+    let main = rendered.main();
+    eprintln!(
+        "wrote {} frames x {} channels to {}",
+        main.first().map_or(0, Vec::len),
+        main.len(),
+        out.display()
+    );
+    if let Some(dir) = &args.stems {
+        let stems = write_stems(&rendered, dir, args.stem_naming, args.bit_depth)?;
+        let silent = rendered.buses.len() - stems.len();
+        eprintln!(
+            "wrote {} stems to {} ({silent} silent buses skipped)",
+            stems.len(),
+            dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// Render once with `events` and write the main bus to `out`, which is created before
+/// rendering and removed again if it fails.
+fn render_to(
+    plugin: &mut host::PluginInstance,
+    config: &host::RenderConfig,
+    planar: &[Vec<f32>],
+    frames: usize,
+    events: &host::RenderEvents,
+    out: &Path,
+    depth: BitDepth,
+) -> Result<host::RenderedAudio, CliError> {
+    let writer = create_wav(out, config.output_channels, config.sample_rate, depth)
+        .map_err(|e| CliError::io(out, e))?;
+    let mut last_pct = None;
+    let converter = host::MidiConverter::new();
+    let rendered =
+        plugin.render_offline_buses(config, planar, frames, events, &converter, |done, total| {
+            let pct = done * 100 / total.max(1);
+            if last_pct != Some(pct) {
+                eprint!("\rrendering {pct:3}%");
+                last_pct = Some(pct);
+            }
+        });
+    eprintln!();
+    let rendered = match rendered {
+        Ok(r) => r,
+        Err(e) => {
+            // Don't leave a header-only file behind.
+            drop(writer);
+            let _ = std::fs::remove_file(out);
+            return Err(CliError::host(
+                "process error",
+                &e,
+                ExitCode::ProcessingError,
+            ));
+        }
+    };
+    write_wav(writer, rendered.main(), depth).map_err(|e| CliError::io(out, e))?;
+    Ok(rendered)
+}
+
+fn report_notes(rendered: &host::RenderedAudio) {
+    if let Some(achieved @ host::Achieved::Unchanged(_)) = &rendered.priority {
+        eprintln!("note: rendered at normal priority; {achieved}");
+    }
+    if rendered.tail_capped {
+        eprintln!(
+            "note: tail cut at {AUTO_TAIL_CAP_SECS} s; the plugin reports a longer or infinite one"
+        );
+    }
+}
+
+/// `out_000.wav` for `out.wav` and pass 0.
+fn sweep_path(out: &Path, i: usize) -> PathBuf {
+    let stem = out.file_stem().unwrap_or_default().to_string_lossy();
+    out.with_file_name(format!("{stem}_{i:03}.wav"))
+}
+
+/// Render once per [`Sweep`] value from the plugin's current state and write the
+/// manifest to `out` with a `.json` extension.
+fn run_sweep(
+    plugin: &mut host::PluginInstance,
+    config: &host::RenderConfig,
+    planar: &[Vec<f32>],
+    frames: usize,
+    sweep: Sweep,
+    out: &Path,
+    depth: BitDepth,
+) -> Result<(), CliError> {
+    let snapshot = host::StateSnapshot::capture(plugin)
+        .map_err(|e| CliError::host("state capture error", &e, ExitCode::ProcessingError))?;
+    let info = plugin.controller().and_then(|controller| {
+        controller
+            .parameters()
+            .ok()?
+            .into_iter()
+            .find(|p| p.id == sweep.id)
+    });
+    if plugin.controller().is_some() && info.is_none() {
+        eprintln!(
+            "warning: the controller lists no parameter {}; sweeping it anyway",
+            sweep.id
+        );
+    }
+    let mut renders = Vec::with_capacity(sweep.steps);
+    for i in 0..sweep.steps {
+        let value = sweep.value(i);
+        let path = sweep_path(out, i);
+        plugin
+            .recall(&snapshot)
+            .map_err(|e| CliError::host("state recall error", &e, ExitCode::ProcessingError))?;
+        let events = host::RenderEvents {
+            automation: vec![(sweep.id, vec![(0, value)])],
+            ..Default::default()
+        };
+        eprintln!(
+            "sweep {}/{}: parameter {} = {value}",
+            i + 1,
+            sweep.steps,
+            sweep.id
+        );
+        let rendered = render_to(plugin, config, planar, frames, &events, &path, depth)?;
+        if i + 1 == sweep.steps {
+            report_notes(&rendered);
+        }
+        let display = plugin
+            .controller()
+            .and_then(|controller| controller.param_string_by_value(sweep.id, value).ok());
+        renders.push(SweepRender {
+            file: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+            value,
+            display,
+        });
+    }
+    let manifest = SweepManifest {
+        param: sweep.id,
+        title: info.as_ref().map(|p| p.title.clone()),
+        units: info.map(|p| p.units),
+        renders,
+    };
+    let path = out.with_extension("json");
+    let json = serde_json::to_string_pretty(&manifest).expect("manifest serializes");
+    std::fs::write(&path, json + "\n").map_err(|e| CliError::io(&path, e))?;
+    eprintln!(
+        "wrote {} renders of parameter {} and {}",
+        sweep.steps,
+        sweep.id,
+        path.display()
+    );
+    Ok(())
+}
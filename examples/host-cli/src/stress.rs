@@ -0,0 +1,44 @@
+//! `--stress`: a seeded random walk through host operations on `--class`, in this
+//! process (see [`host::stress`]).
+//!
+//! The report goes to stdout: one line when the walk passes, and after a failure the
+//! numbered operations that led to it. `--seed` replays a walk exactly.
+
+use std::path::PathBuf;
+
+use openvst3_host as host;
+
+use cli_common::{CliError, ExitCode};
+
+use crate::Args;
+
+/// `--stress`; fails with [`ExitCode::ValidationFailed`] if the walk did.
+pub fn run(args: &Args, bin: Result<PathBuf, host::HostError>) -> Result<(), CliError> {
+    let Some(idx) = args.class else {
+        return Err(CliError::usage("--stress needs --class"));
+    };
+    let mut module = bin
+        .and_then(host::Module::load)
+        .map_err(|e| CliError::host("load error", &e, ExitCode::LoadError))?;
+    let (_, _, cid) = host::read_class_info_v1(&mut module, idx)
+        .map_err(|e| CliError::host("class read error", &e, ExitCode::ClassError))?;
+    let mut plugin = module
+        .create_plugin(cid)
+        .map_err(|e| CliError::host("create error", &e, ExitCode::InstantiationError))?;
+    let report = plugin.stress_test(&host::StressOptions {
+        seed: args.seed,
+        iterations: args.iterations,
+        ..host::StressOptions::default()
+    });
+    println!("{report}");
+    match &report.failure {
+        None => Ok(()),
+        Some(failure) => Err(CliError::new(
+            ExitCode::ValidationFailed,
+            format!(
+                "stress test failed at step {} with seed {}",
+                failure.step, report.seed
+            ),
+        )),
+    }
+}
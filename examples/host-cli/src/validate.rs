@@ -0,0 +1,293 @@
+//! `--validate`: run the host's validator against every audio class (or `--class`).
+//!
+//! Each check case runs in a child `host-cli --validate-worker CASE` process, so a
+//! plugin that crashes or hangs fails that case instead of killing the run. The worker
+//! prints one [`CheckResult`] as JSON; the parent collects them into a
+//! `ValidationReport` per class and prints a table or, with `--json`, the reports.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use openvst3_host as host;
+use serde::{Deserialize, Serialize};
+
+use cli_common::{CliError, ExitCode};
+
+use crate::Args;
+
+/// clap value parser for `--checks` and `--skip-checks`.
+pub fn parse_check(s: &str) -> Result<host::Check, String> {
+    s.parse()
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Document {
+    pub schema_version: u32,
+    pub success: bool,
+    pub reports: Vec<Report>,
+}
+
+/// Mirrors [`host::ValidationReport`], plus the class name.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub cid: String,
+    pub name: String,
+    pub passed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub warned: usize,
+    pub results: Vec<CheckResult>,
+}
+
+/// Mirrors [`host::CheckResult`]; also the worker's output.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckResult {
+    pub check: String,
+    /// Only for `process`.
+    pub setup: Option<Setup>,
+    pub outcome: Outcome,
+    /// Failure, skip or warning reason.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub elapsed_ms: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Setup {
+    pub sample_rate: f64,
+    pub block_size: usize,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Pass,
+    Fail,
+    Skip,
+    Warn,
+}
+
+impl From<&host::CheckResult> for CheckResult {
+    fn from(r: &host::CheckResult) -> Self {
+        let (outcome, detail) = match &r.outcome {
+            host::Outcome::Pass => (Outcome::Pass, None),
+            host::Outcome::Fail(d) => (Outcome::Fail, Some(d.clone())),
+            host::Outcome::Skip(d) => (Outcome::Skip, Some(d.clone())),
+            host::Outcome::Warn(d) => (Outcome::Warn, Some(d.clone())),
+        };
+        Self {
+            check: r.case.check.name().into(),
+            setup: r.case.process.map(|p| Setup {
+                sample_rate: p.sample_rate,
+                block_size: p.block_size,
+            }),
+            outcome,
+            detail,
+            elapsed_ms: r.elapsed.as_secs_f64() * 1e3,
+        }
+    }
+}
+
+impl CheckResult {
+    fn into_host(self, case: host::CheckCase) -> host::CheckResult {
+        let detail = self.detail.unwrap_or_default();
+        host::CheckResult {
+            case,
+            outcome: match self.outcome {
+                Outcome::Pass => host::Outcome::Pass,
+                Outcome::Fail => host::Outcome::Fail(detail),
+                Outcome::Skip => host::Outcome::Skip(detail),
+                Outcome::Warn => host::Outcome::Warn(detail),
+            },
+            elapsed: Duration::from_secs_f64(self.elapsed_ms.max(0.0) / 1e3),
+        }
+    }
+}
+
+fn report(name: &str, r: &host::ValidationReport) -> Report {
+    Report {
+        cid: host::fmt_cid_hex(&r.cid),
+        name: name.into(),
+        passed: r.passed(),
+        failed: r.failed(),
+        skipped: r.skipped(),
+        warned: r.warned(),
+        results: r.results.iter().map(CheckResult::from).collect(),
+    }
+}
+
+/// `--validate-worker CASE --cid HEX`: run one case in this process, print it as JSON.
+pub fn run_worker(
+    args: &Args,
+    bin: Result<PathBuf, host::HostError>,
+    case: &str,
+) -> Result<(), CliError> {
+    let case: host::CheckCase = case.parse().map_err(CliError::usage)?;
+    let cid = match args.cid.as_deref().map(host::parse_hex_16) {
+        Some(Ok(cid)) => cid,
+        Some(Err(e)) => return Err(CliError::usage(e.to_string())),
+        None => return Err(CliError::usage("--validate-worker needs --cid")),
+    };
+    let mut module = bin.and_then(host::Module::load).map_err(|e| CliError {
+        // The parent reports the last line of stderr, which must be the message.
+        detail: None,
+        ..CliError::host("load error", &e, ExitCode::LoadError)
+    })?;
+    let result = module.run_check(cid, case);
+    println!(
+        "{}",
+        serde_json::to_string(&CheckResult::from(&result)).expect("result serializes")
+    );
+    Ok(())
+}
+
+/// Run `case` in a worker process; crashes, hangs and garbage become failures.
+fn run_case(
+    bin: &Path,
+    cid: [u8; 16],
+    case: host::CheckCase,
+    timeout: Duration,
+) -> host::CheckResult {
+    let exe = std::env::current_exe().expect("current executable");
+    let mut cmd = Command::new(exe);
+    cmd.arg("--plugin")
+        .arg(bin)
+        .arg("--validate-worker")
+        .arg(case.to_string())
+        .arg("--cid")
+        .arg(host::fmt_cid_hex(&cid));
+    let failed = |detail: String| host::CheckResult {
+        case,
+        outcome: host::Outcome::Fail(detail),
+        elapsed: Duration::ZERO,
+    };
+    match host::isolate::run_isolated(&mut cmd, "validation worker", timeout) {
+        Ok(out) => match serde_json::from_slice::<CheckResult>(&out.stdout) {
+            Ok(r) if out.status.success() => r.into_host(case),
+            _ => {
+                let stderr = String::from_utf8_lossy(&out.stderr);
+                let last = stderr.lines().last().unwrap_or("no output").trim();
+                failed(format!("worker exited with {}: {last}", out.status))
+            }
+        },
+        Err(host::HostError::TimedOut { after, .. }) => {
+            failed(format!("did not finish within {after:?}"))
+        }
+        Err(e) => failed(e.to_string()),
+    }
+}
+
+fn print_table(name: &str, r: &host::ValidationReport) {
+    println!("{name}  CID={}", host::fmt_cid_hex(&r.cid));
+    println!("  {:<10} {:<16} {:<6} DETAIL", "CHECK", "SETUP", "RESULT");
+    for res in &r.results {
+        let setup = res
+            .case
+            .process
+            .map(|p| format!("{} Hz / {}", p.sample_rate, p.block_size))
+            .unwrap_or_default();
+        let (word, detail) = match &res.outcome {
+            host::Outcome::Pass => ("PASS", ""),
+            host::Outcome::Fail(d) => ("FAIL", d.as_str()),
+            host::Outcome::Skip(d) => ("SKIP", d.as_str()),
+            host::Outcome::Warn(d) => ("WARN", d.as_str()),
+        };
+        let row = format!(
+            "  {:<10} {:<16} {:<6} {detail}",
+            res.case.check.name(),
+            setup,
+            word
+        );
+        println!("{}", row.trim_end());
+    }
+}
+
+/// `--validate`; fails with [`ExitCode::ValidationFailed`] if a check that ran failed.
+pub fn run(args: &Args, bin: Result<PathBuf, host::HostError>) -> Result<(), CliError> {
+    let load_error = |e| CliError::host("load error", &e, ExitCode::LoadError);
+    let bin = bin.map_err(load_error)?;
+    let mut module = host::Module::load(&bin).map_err(load_error)?;
+    let classes: Vec<(String, [u8; 16])> = match args.class {
+        Some(idx) => {
+            let (name, _, cid) = host::read_class_info_v1(&mut module, idx)
+                .map_err(|e| CliError::host("class read error", &e, ExitCode::ClassError))?;
+            vec![(name, cid)]
+        }
+        None => module
+            .classes_matching(
+                &host::ClassFilter::audio_effects().or(host::ClassFilter::instruments()),
+            )
+            .map(|c| (c.name.clone(), c.cid))
+            .collect(),
+    };
+    // The workers load their own copies.
+    drop(module);
+    if classes.is_empty() {
+        return Err(CliError::usage(
+            "no audio classes to validate; pick one with --class",
+        ));
+    }
+
+    let mut options = host::ValidateOptions::default();
+    if !args.checks.is_empty() {
+        options.checks = args.checks.clone();
+    }
+    options.checks.retain(|c| !args.skip_checks.contains(c));
+    if !args.sample_rates.is_empty() {
+        options.sample_rates = args.sample_rates.clone();
+    }
+    if !args.block_sizes.is_empty() {
+        options.block_sizes = args.block_sizes.clone();
+    }
+    let timeout = Duration::from_secs_f64(args.check_timeout);
+
+    let reports: Vec<(String, host::ValidationReport)> = classes
+        .into_iter()
+        .map(|(name, cid)| {
+            let results = options
+                .cases()
+                .into_iter()
+                .map(|case| run_case(&bin, cid, case, timeout))
+                .collect();
+            (name, host::ValidationReport { cid, results })
+        })
+        .collect();
+    let success = reports.iter().all(|(_, r)| r.is_success());
+    let failed_checks: usize = reports.iter().map(|(_, r)| r.failed()).sum();
+
+    if args.json {
+        crate::print_json(&Document {
+            schema_version: crate::json::SCHEMA_VERSION,
+            success,
+            reports: reports.iter().map(|(n, r)| report(n, r)).collect(),
+        });
+    } else {
+        let (mut passed, mut failed, mut skipped, mut warned) = (0, 0, 0, 0);
+        for (name, r) in &reports {
+            print_table(name, r);
+            passed += r.passed();
+            failed += r.failed();
+            skipped += r.skipped();
+            warned += r.warned();
+        }
+        let warnings = match warned {
+            0 => String::new(),
+            1 => ", 1 warning".into(),
+            n => format!(", {n} warnings"),
+        };
+        println!("summary: {passed} passed, {failed} failed, {skipped} skipped{warnings}");
+    }
+    if success {
+        Ok(())
+    } else {
+        Err(CliError::new(
+            ExitCode::ValidationFailed,
+            format!("{failed_checks} checks failed"),
+        ))
+    }
+}
@@ -0,0 +1,88 @@
+//! `--params`, `--set-param` and the state flags against the workspace test plugin.
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+use openvst3_testplugin::bundle::{make_bundle, remove_bundle};
+
+fn run(tag: &str, extra: &[&str]) -> Output {
+    let bundle = make_bundle(tag);
+    let out = Command::new(env!("CARGO_BIN_EXE_host-cli"))
+        .arg("--bundle")
+        .arg(&bundle)
+        .args(["--class", "0"])
+        .args(extra)
+        .output()
+        .unwrap();
+    remove_bundle(&bundle);
+    out
+}
+
+fn stdout(out: &Output) -> String {
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8(out.stdout.clone()).unwrap()
+}
+
+fn scratch(tag: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("host-cli-{tag}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn params_lists_the_gain_parameter() {
+    let text = stdout(&run("cli-params", &["--params"]));
+    assert!(text.starts_with("params = 1\n"), "{text}");
+    assert!(text.contains("id=0 ") && text.contains("Gain"), "{text}");
+    assert!(text.contains("default=1.00 (1.0000)"), "{text}");
+    assert!(text.contains("flags: automatable"), "{text}");
+}
+
+#[test]
+fn set_param_prints_display_string() {
+    let text = stdout(&run("cli-set-param", &["--set-param", "0=0.5"]));
+    assert_eq!(text.trim(), "Gain (id 0) = 0.5000 -> 0.50");
+
+    let out = run("cli-set-param-missing", &["--set-param", "9=0.5"]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("no parameter with id 9"));
+}
+
+#[test]
+fn dumped_state_loads_back() {
+    let dir = scratch("state");
+    let a = dir.join("a.bin");
+    let b = dir.join("b.bin");
+    let default = dir.join("default.bin");
+    stdout(&run(
+        "cli-state-a",
+        &["--set-param", "0=0.25", "--dump-state", a.to_str().unwrap()],
+    ));
+    stdout(&run(
+        "cli-state-default",
+        &["--dump-state", default.to_str().unwrap()],
+    ));
+    let text = stdout(&run(
+        "cli-state-b",
+        &[
+            "--load-state",
+            a.to_str().unwrap(),
+            "--dump-state",
+            b.to_str().unwrap(),
+        ],
+    ));
+    assert!(text.contains("loaded 12 bytes"), "{text}");
+    assert_eq!(std::fs::read(&a).unwrap(), std::fs::read(&b).unwrap());
+    assert_ne!(std::fs::read(&a).unwrap(), std::fs::read(&default).unwrap());
+
+    let out = run(
+        "cli-state-missing",
+        &["--load-state", dir.join("nope.bin").to_str().unwrap()],
+    );
+    assert_eq!(out.status.code(), Some(8));
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,137 @@
+//! The exit code contract and `--error-json`, against the workspace test plugin made
+//! to fail at each step (see `openvst3_testplugin::FAIL_IN_ENV`).
+
+use std::process::{Command, Output};
+
+use openvst3_testplugin::bundle::{make_bundle, remove_bundle};
+use openvst3_testplugin::FAIL_IN_ENV;
+
+fn run(tag: &str, fail_in: Option<&str>, extra: &[&str]) -> Output {
+    let bundle = make_bundle(tag);
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_host-cli"));
+    cmd.arg("--bundle")
+        .arg(&bundle)
+        .arg("--error-json")
+        .args(extra);
+    if let Some(method) = fail_in {
+        cmd.env(FAIL_IN_ENV, method);
+    }
+    let out = cmd.output().unwrap();
+    remove_bundle(&bundle);
+    out
+}
+
+/// The last stderr line, which `--error-json` makes the error object.
+fn error(out: &Output) -> serde_json::Value {
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    let last = stderr.lines().last().unwrap_or_default();
+    serde_json::from_str(last).unwrap_or_else(|e| panic!("{e}: {stderr}"))
+}
+
+/// Tag, failing method, arguments, exit code and category.
+type Case = (
+    &'static str,
+    Option<&'static str>,
+    &'static [&'static str],
+    i32,
+    &'static str,
+);
+
+#[test]
+fn each_step_has_its_own_code() {
+    let cases: [Case; 5] = [
+        ("usage", None, &["--class", "7"], 2, "usage"),
+        (
+            "class",
+            Some("get_class_info"),
+            &["--class", "0"],
+            4,
+            "class",
+        ),
+        (
+            "create",
+            Some("create_instance"),
+            &["--class", "0"],
+            5,
+            "instantiation",
+        ),
+        (
+            "process",
+            Some("process"),
+            &["--class", "0", "--process-frames", "64"],
+            6,
+            "processing",
+        ),
+        (
+            "validate",
+            Some("process"),
+            &["--validate", "--checks", "process", "--block-sizes", "64"],
+            7,
+            "validation",
+        ),
+    ];
+    for (tag, fail_in, args, code, category) in cases {
+        let out = run(&format!("cli-exit-{tag}"), fail_in, args);
+        assert_eq!(out.status.code(), Some(code), "{tag}: {out:?}");
+        let doc = error(&out);
+        assert_eq!(doc["category"], category, "{tag}: {doc}");
+        assert_eq!(doc["code"], code, "{tag}: {doc}");
+        assert!(doc["message"].is_string(), "{tag}: {doc}");
+    }
+}
+
+#[test]
+fn plugin_error_results_are_reported() {
+    let out = run(
+        "cli-exit-tresult",
+        Some("process"),
+        &["--class", "0", "--process-frames", "64"],
+    );
+    let doc = error(&out);
+    assert_eq!(doc["tresult"], -4, "{doc}");
+    assert!(
+        doc["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("process32 error"),
+        "{doc}"
+    );
+    let out = run(
+        "cli-exit-create",
+        Some("create_instance"),
+        &["--class", "0"],
+    );
+    let doc = error(&out);
+    assert_eq!(doc["tresult"], -4, "{doc}");
+    let message = doc["message"].as_str().unwrap();
+    assert!(
+        message.contains("createInstance of OpenVST3 Test Gain (")
+            && message.contains("as IComponent ("),
+        "{doc}"
+    );
+}
+
+#[test]
+fn missing_plugin_is_a_load_error() {
+    let out = Command::new(env!("CARGO_BIN_EXE_host-cli"))
+        .args(["--plugin", "/nonexistent/plugin.so", "--error-json"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(3));
+    let doc = error(&out);
+    assert_eq!(doc["category"], "load");
+    assert!(doc.get("tresult").is_none(), "{doc}");
+
+    // Without --error-json the message comes first, then the loader's diagnosis.
+    let out = Command::new(env!("CARGO_BIN_EXE_host-cli"))
+        .args(["--plugin", "/nonexistent/plugin.so"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(3));
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.starts_with("load error: dlopen failed"), "{stderr}");
+    assert!(
+        stderr.contains("  path: /nonexistent/plugin.so"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,88 @@
+//! Locks the `--json` schema against the workspace test plugin.
+//!
+//! Run with `UPDATE_SNAPSHOTS=1` to rewrite the files under `tests/snapshots/`.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use openvst3_testplugin::bundle::{make_bundle, remove_bundle};
+
+fn snapshot_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("snapshots")
+        .join(format!("{name}.json"))
+}
+
+/// Run host-cli on a fresh bundle and return (exit code, parsed stdout).
+fn run(tag: &str, extra: &[&str]) -> (i32, serde_json::Value) {
+    let bundle = make_bundle(tag);
+    let out = Command::new(env!("CARGO_BIN_EXE_host-cli"))
+        .arg("--bundle")
+        .arg(&bundle)
+        .args(extra)
+        .output()
+        .unwrap();
+    remove_bundle(&bundle);
+    let doc = serde_json::from_slice(&out.stdout).unwrap_or_else(|e| {
+        panic!(
+            "stdout is not JSON ({e}):\n{}",
+            String::from_utf8_lossy(&out.stdout)
+        )
+    });
+    (out.status.code().unwrap(), doc)
+}
+
+fn assert_snapshot(name: &str, doc: &serde_json::Value) {
+    let path = snapshot_path(name);
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        let text = serde_json::to_string_pretty(doc).unwrap() + "\n";
+        std::fs::write(&path, text).unwrap();
+        return;
+    }
+    let expected: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(
+        doc,
+        &expected,
+        "{} is out of date; rerun with UPDATE_SNAPSHOTS=1",
+        path.display()
+    );
+}
+
+#[test]
+fn json_list_matches_snapshot() {
+    let (code, doc) = run("cli-json", &["--json"]);
+    assert_eq!(code, 0);
+    assert_snapshot("list", &doc);
+}
+
+#[test]
+fn json_probe_matches_snapshot() {
+    let (code, doc) = run("cli-json-probe", &["--json", "--probe"]);
+    assert_eq!(code, 0);
+    assert_snapshot("probe", &doc);
+}
+
+#[test]
+fn json_category_filter_can_select_nothing() {
+    let (code, doc) = run("cli-json-filter", &["--json", "--category", "instrument"]);
+    assert_eq!(code, 0);
+    assert_eq!(doc["classes"], serde_json::json!([]));
+}
+
+#[test]
+fn json_load_failure_is_structured() {
+    let out = Command::new(env!("CARGO_BIN_EXE_host-cli"))
+        .args(["--plugin", "/nonexistent/plugin.so", "--json"])
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(3));
+    let doc: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(doc["schemaVersion"], 1);
+    assert_eq!(doc["error"]["pluginFault"], true);
+    assert!(doc["error"]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("dlopen failed"));
+}
@@ -0,0 +1,47 @@
+//! `--list-presets` with the home folder pointed at a scratch directory.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use openvst3_testplugin::bundle::{make_bundle, remove_bundle};
+use openvst3_testplugin::CLASS_NAME;
+
+fn list(bundle: &Path, home: &Path) -> String {
+    let out = Command::new(env!("CARGO_BIN_EXE_host-cli"))
+        .arg("--bundle")
+        .arg(bundle)
+        .arg("--list-presets")
+        .env("HOME", home)
+        .env("USERPROFILE", home)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8(out.stdout).unwrap()
+}
+
+#[test]
+fn presets_in_the_user_folder_are_listed() {
+    let bundle = make_bundle("cli-presets");
+    let home = bundle.parent().unwrap().join("home");
+    let text = list(&bundle, &home);
+    assert!(text.starts_with(&format!("{CLASS_NAME} by ")), "{text}");
+    assert!(text.contains(": 0 presets"), "{text}");
+    // The first location is the user's, below the home folder.
+    let user = PathBuf::from(
+        text.lines()
+            .find_map(|l| l.strip_prefix("  in "))
+            .expect(&text),
+    );
+    assert!(user.starts_with(&home), "{text}");
+
+    std::fs::create_dir_all(user.join("Leads")).unwrap();
+    std::fs::write(user.join("Leads").join("Bright.vstpreset"), b"").unwrap();
+    let text = list(&bundle, &home);
+    remove_bundle(&bundle);
+    assert!(text.contains(": 1 presets"), "{text}");
+    assert!(text.contains("  Bright  "), "{text}");
+}
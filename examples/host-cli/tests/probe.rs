@@ -0,0 +1,65 @@
+//! `--probe` against the workspace test plugin, including crashing and hanging
+//! builds of it.
+
+use std::process::Command;
+use std::process::Output;
+
+use openvst3_testplugin::bundle::{make_bundle, remove_bundle};
+use openvst3_testplugin::{CRASH_IN_ENV, HANG_IN_ENV};
+
+fn run(tag: &str, env: Option<(&str, &str)>, extra: &[&str]) -> (Output, String) {
+    let bundle = make_bundle(tag);
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_host-cli"));
+    cmd.arg("--bundle").arg(&bundle).arg("--probe").args(extra);
+    if let Some((k, v)) = env {
+        cmd.env(k, v);
+    }
+    let out = cmd.output().unwrap();
+    remove_bundle(&bundle);
+    let text = String::from_utf8(out.stdout.clone()).unwrap();
+    (out, text)
+}
+
+fn row<'a>(text: &'a str, what: &str) -> &'a str {
+    text.lines()
+        .find(|l| l.trim_start().starts_with(what))
+        .unwrap_or_else(|| panic!("no {what} row in:\n{text}"))
+}
+
+#[test]
+fn probe_prints_the_capability_matrix() {
+    let (out, text) = run("cli-probe", None, &[]);
+    assert_eq!(
+        out.status.code(),
+        Some(0),
+        "{text}{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(text.starts_with("OpenVST3 Test Gain  CID="), "{text}");
+    assert!(row(&text, "IAudioProcessor").ends_with(" yes"));
+    assert!(row(&text, "IUnitInfo").ends_with(" no"));
+    assert!(row(&text, "64-bit processing").ends_with(" yes"));
+    assert!(row(&text, "state round trip").ends_with(" yes"));
+    assert!(row(&text, "null process 44100 Hz").ends_with(" yes"));
+    assert!(row(&text, "null process 192000 Hz").ends_with(" yes"));
+    assert!(row(&text, "buses").ends_with("audio in [2] out [2], event in 0 out 0"));
+}
+
+#[test]
+fn crashing_class_is_an_entry_not_an_abort() {
+    let (out, text) = run("cli-probe-crash", Some((CRASH_IN_ENV, "process")), &[]);
+    assert_eq!(out.status.code(), Some(0), "{text}");
+    let failed = row(&text, "probe failed:");
+    assert!(failed.contains("crashed"), "{failed}");
+
+    let (out, text) = run(
+        "cli-probe-hang",
+        Some((HANG_IN_ENV, "process")),
+        &["--check-timeout", "1"],
+    );
+    assert_eq!(out.status.code(), Some(0), "{text}");
+    assert!(
+        row(&text, "probe failed:").ends_with("did not finish within 1s"),
+        "{text}"
+    );
+}
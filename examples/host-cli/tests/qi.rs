@@ -0,0 +1,113 @@
+//! `--iid-name`/`--qi-name` (and neither) against the workspace test plugin.
+
+use std::process::{Command, Output};
+
+use openvst3_testplugin::bundle::{make_bundle, remove_bundle};
+use openvst3_testplugin::NO_PROCESSOR_ENV;
+
+fn run(tag: &str, extra: &[&str]) -> Output {
+    run_with(tag, extra, &[])
+}
+
+fn run_with(tag: &str, extra: &[&str], env: &[(&str, &str)]) -> Output {
+    let bundle = make_bundle(tag);
+    let out = Command::new(env!("CARGO_BIN_EXE_host-cli"))
+        .envs(env.iter().copied())
+        .arg("--bundle")
+        .arg(&bundle)
+        .args(["--class", "0"])
+        .args(extra)
+        .output()
+        .unwrap();
+    remove_bundle(&bundle);
+    out
+}
+
+#[test]
+fn component_queries_to_processor_and_processes() {
+    let out = run(
+        "cli-qi",
+        &[
+            "--iid-name",
+            "IComponent",
+            "--qi-name",
+            "IAudioProcessor",
+            "--process-frames",
+            "64",
+        ],
+    );
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(
+        text.contains("instance satisfies IAudioProcessor (created as IComponent)"),
+        "{text}"
+    );
+    assert!(
+        text.contains("process32() OK (64 frames, stereo out, 2 flagged silent)"),
+        "{text}"
+    );
+}
+
+#[test]
+fn unsupported_target_interface_is_a_qi_failure() {
+    let out = run(
+        "cli-qi-missing",
+        &["--iid-name", "IComponent", "--qi-name", "IPluginFactory"],
+    );
+    assert_eq!(out.status.code(), Some(5));
+    assert!(String::from_utf8_lossy(&out.stderr).contains(
+        "QueryInterface for IPluginFactory (7A4D811C-5211-4A1F-AED9-D2EE0B43BF9F) failed"
+    ));
+}
+
+#[test]
+fn unknown_interface_names_and_mismatched_processing_are_usage_errors() {
+    let out = run(
+        "cli-qi-unknown",
+        &["--iid-name", "IComponent", "--qi-name", "INope"],
+    );
+    assert_eq!(out.status.code(), Some(2));
+
+    let out = run(
+        "cli-qi-process",
+        &["--iid-name", "IComponent", "--process-frames", "64"],
+    );
+    assert_eq!(out.status.code(), Some(2));
+}
+
+#[test]
+fn without_an_iid_the_class_is_created_as_a_component() {
+    let out = run("cli-qi-default", &["--process-frames", "64"]);
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(
+        text.contains("instance satisfies IAudioProcessor (created as IComponent)"),
+        "{text}"
+    );
+}
+
+#[test]
+fn processing_an_instance_without_a_processor_is_refused() {
+    let out = run_with(
+        "cli-qi-noproc",
+        &["--process-frames", "64"],
+        &[(NO_PROCESSOR_ENV, "1")],
+    );
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert_eq!(out.status.code(), Some(2), "{stderr}");
+    assert!(
+        stderr.contains(
+            "--process-frames needs IAudioProcessor, but the instance only supports \
+             FUnknown, IPluginBase, IComponent, IEditController"
+        ),
+        "{stderr}"
+    );
+}
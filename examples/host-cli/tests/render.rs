@@ -0,0 +1,250 @@
+//! `--render` against the workspace test plugin.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use openvst3_testplugin::bundle::{make_bundle, remove_bundle};
+use openvst3_testplugin::AUX_OUTPUTS_ENV;
+
+fn scratch(tag: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("host-cli-{tag}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn render(tag: &str, extra: &[&str]) -> Output {
+    render_with_aux(tag, 0, extra)
+}
+
+/// [`render`] with the test plugin given `aux` aux output buses.
+fn render_with_aux(tag: &str, aux: usize, extra: &[&str]) -> Output {
+    let bundle = make_bundle(tag);
+    let out = Command::new(env!("CARGO_BIN_EXE_host-cli"))
+        .arg("--bundle")
+        .arg(&bundle)
+        .args(["--class", "0"])
+        .args(extra)
+        .env(AUX_OUTPUTS_ENV, aux.to_string())
+        .output()
+        .unwrap();
+    remove_bundle(&bundle);
+    out
+}
+
+fn write_input(path: &Path, frames: usize) {
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: 44_100,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut w = hound::WavWriter::create(path, spec).unwrap();
+    for _ in 0..frames {
+        w.write_sample(0.5f32).unwrap();
+        w.write_sample(-0.25f32).unwrap();
+    }
+    w.finalize().unwrap();
+}
+
+#[test]
+fn renders_input_through_gain_parameter() {
+    let dir = scratch("render");
+    let input = dir.join("in.wav");
+    let output = dir.join("out.wav");
+    write_input(&input, 3000);
+
+    let out = render(
+        "cli-render",
+        &[
+            "--render",
+            output.to_str().unwrap(),
+            "--input",
+            input.to_str().unwrap(),
+            "--param",
+            "0=0.5",
+            "--bit-depth",
+            "32f",
+        ],
+    );
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(String::from_utf8_lossy(&out.stderr).contains("100%"));
+
+    let mut reader = hound::WavReader::open(&output).unwrap();
+    let spec = reader.spec();
+    assert_eq!((spec.channels, spec.sample_rate), (2, 44_100));
+    let samples: Vec<f32> = reader.samples().map(Result::unwrap).collect();
+    assert_eq!(samples.len(), 2 * 3000);
+    assert!(samples.chunks(2).all(|f| f == [0.25, -0.125]));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+/// The file names in `dir`, sorted.
+fn listing(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn stems_are_written_per_audible_bus() {
+    let dir = scratch("render-stems");
+    let input = dir.join("in.wav");
+    let output = dir.join("mix.wav");
+    write_input(&input, 1000);
+
+    for (naming, expected) in [
+        (
+            "numbered",
+            ["00 Main Out.wav", "01 Aux 1.wav", "03 Aux 3.wav"],
+        ),
+        ("name", ["Aux 1.wav", "Aux 3.wav", "Main Out.wav"]),
+    ] {
+        let stems = dir.join(naming);
+        let out = render_with_aux(
+            &format!("cli-render-stems-{naming}"),
+            3,
+            &[
+                "--render",
+                output.to_str().unwrap(),
+                "--input",
+                input.to_str().unwrap(),
+                // Aux 2's gain: the plugin keeps that bus silent.
+                "--param",
+                "2=0",
+                "--bit-depth",
+                "32f",
+                "--stems",
+                stems.to_str().unwrap(),
+                "--stem-naming",
+                naming,
+            ],
+        );
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(out.status.success(), "{stderr}");
+        assert!(stderr.contains("(1 silent buses skipped)"), "{stderr}");
+        assert_eq!(listing(&stems), expected);
+    }
+
+    let mut reader = hound::WavReader::open(dir.join("numbered/01 Aux 1.wav")).unwrap();
+    assert_eq!(reader.spec().channels, 2);
+    let samples: Vec<f32> = reader.samples().map(Result::unwrap).collect();
+    assert_eq!(samples.len(), 2 * 1000);
+    assert!(samples.chunks(2).all(|f| f == [0.25, -0.125]));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn unwritable_output_is_an_io_failure() {
+    let dir = scratch("render-io");
+    let output = dir.join("missing").join("out.wav");
+    let out = render(
+        "cli-render-io",
+        &["--render", output.to_str().unwrap(), "--duration", "10ms"],
+    );
+    assert_eq!(out.status.code(), Some(8));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn render_without_length_is_a_usage_error() {
+    let out = render("cli-render-usage", &["--render", "unused.wav"]);
+    assert_eq!(out.status.code(), Some(2));
+}
+
+#[test]
+fn sweep_renders_once_per_value_with_a_manifest() {
+    let dir = scratch("render-sweep");
+    let input = dir.join("in.wav");
+    let output = dir.join("out.wav");
+    write_input(&input, 1000);
+
+    let out = render(
+        "cli-render-sweep",
+        &[
+            "--render",
+            output.to_str().unwrap(),
+            "--input",
+            input.to_str().unwrap(),
+            // The state every pass starts from; the sweep value replaces it.
+            "--param",
+            "0=0.9",
+            "--bit-depth",
+            "32f",
+            "--sweep",
+            "0:1:0:3",
+        ],
+    );
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "{stderr}");
+    assert_eq!(
+        listing(&dir),
+        [
+            "in.wav",
+            "out.json",
+            "out_000.wav",
+            "out_001.wav",
+            "out_002.wav"
+        ]
+    );
+
+    for (file, expected) in [
+        ("out_000.wav", [0.5, -0.25]),
+        ("out_001.wav", [0.25, -0.125]),
+        ("out_002.wav", [0.0, 0.0]),
+    ] {
+        let mut reader = hound::WavReader::open(dir.join(file)).unwrap();
+        let samples: Vec<f32> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples.len(), 2 * 1000, "{file}");
+        assert!(samples.chunks(2).all(|f| f == expected), "{file}");
+    }
+
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(dir.join("out.json")).unwrap()).unwrap();
+    assert_eq!(manifest["param"], 0);
+    let renders: Vec<_> = manifest["renders"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| {
+            (
+                r["file"].as_str().unwrap().to_string(),
+                r["value"].as_f64().unwrap(),
+                r["display"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        renders,
+        [
+            ("out_000.wav".to_string(), 1.0, "1.00".to_string()),
+            ("out_001.wav".to_string(), 0.5, "0.50".to_string()),
+            ("out_002.wav".to_string(), 0.0, "0.00".to_string()),
+        ]
+    );
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn malformed_sweep_is_a_usage_error() {
+    for sweep in ["0:0:1", "0:0:2:3", "0:0:1:0"] {
+        let out = render(
+            "cli-render-sweep-usage",
+            &[
+                "--render",
+                "unused.wav",
+                "--duration",
+                "10ms",
+                "--sweep",
+                sweep,
+            ],
+        );
+        assert_eq!(out.status.code(), Some(2), "{sweep}");
+    }
+}
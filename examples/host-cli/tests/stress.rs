@@ -0,0 +1,59 @@
+//! `--stress` against the workspace test plugin.
+
+use std::process::{Command, Output};
+
+use openvst3_testplugin::bundle::{make_bundle, remove_bundle};
+use openvst3_testplugin::FAIL_IN_ENV;
+
+fn stress(tag: &str, fail_in: Option<&str>, seed: &str) -> Output {
+    let bundle = make_bundle(tag);
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_host-cli"));
+    cmd.arg("--bundle").arg(&bundle).args([
+        "--class",
+        "0",
+        "--stress",
+        "--iterations",
+        "300",
+        "--seed",
+        seed,
+    ]);
+    if let Some(method) = fail_in {
+        cmd.env(FAIL_IN_ENV, method);
+    }
+    let out = cmd.output().unwrap();
+    remove_bundle(&bundle);
+    out
+}
+
+#[test]
+fn stress_passes_on_the_test_plugin() {
+    let first = stress("cli-stress", None, "11");
+    let stdout = String::from_utf8_lossy(&first.stdout);
+    assert!(
+        first.status.success(),
+        "{stdout}{}",
+        String::from_utf8_lossy(&first.stderr)
+    );
+    assert_eq!(stdout.trim_end(), "seed 11: 301 steps, passed");
+}
+
+#[test]
+fn stress_failure_lists_the_steps_and_exits_7() {
+    let out = stress("cli-stress-fail", Some("process"), "5");
+    assert_eq!(out.status.code(), Some(7));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let mut lines = stdout.lines();
+    let summary = lines.next().unwrap();
+    assert!(
+        summary.starts_with("seed 5: failed at step ") && summary.ends_with("tresult failure: -4"),
+        "{stdout}"
+    );
+    assert_eq!(lines.next(), Some("     0  activate"));
+    let again = stress("cli-stress-fail-again", Some("process"), "5");
+    assert_eq!(again.stdout, out.stdout);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(
+        stderr.contains("stress test failed at step") && stderr.contains("with seed 5"),
+        "{stderr}"
+    );
+}
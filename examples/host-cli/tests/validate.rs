@@ -0,0 +1,188 @@
+//! `--validate` against the workspace test plugin, including crashing and hanging
+//! builds of it (see `openvst3_testplugin::CRASH_IN_ENV`).
+
+use std::process::{Command, Output};
+
+use openvst3_testplugin::bundle::{make_bundle, remove_bundle};
+use openvst3_testplugin::{CONTROLLER_CID_ENV, CRASH_IN_ENV, HANG_IN_ENV};
+
+fn run(tag: &str, env: Option<(&str, &str)>, extra: &[&str]) -> Output {
+    let bundle = make_bundle(tag);
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_host-cli"));
+    cmd.arg("--bundle")
+        .arg(&bundle)
+        .arg("--validate")
+        .args(extra);
+    if let Some((k, v)) = env {
+        cmd.env(k, v);
+    }
+    let out = cmd.output().unwrap();
+    remove_bundle(&bundle);
+    out
+}
+
+fn text(out: &Output) -> String {
+    String::from_utf8(out.stdout.clone()).unwrap()
+}
+
+#[test]
+fn validate_passes_test_plugin() {
+    let out = run("cli-validate", None, &[]);
+    let text = text(&out);
+    assert_eq!(
+        out.status.code(),
+        Some(0),
+        "{text}{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(text.contains("OpenVST3 Test Gain"), "{text}");
+    assert!(text.contains("process    48000 Hz / 512   PASS"), "{text}");
+    assert!(text.contains("teardown"), "{text}");
+    assert!(
+        text.trim_end()
+            .ends_with("summary: 14 passed, 0 failed, 1 skipped"),
+        "{text}"
+    );
+}
+
+#[test]
+fn validate_json_mirrors_report() {
+    let out = run(
+        "cli-validate-json",
+        None,
+        &[
+            "--json",
+            "--checks",
+            "state,process",
+            "--sample-rates",
+            "96000",
+            "--block-sizes",
+            "32",
+        ],
+    );
+    assert!(out.status.success());
+    let doc: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!(doc["success"], true);
+    let report = &doc["reports"][0];
+    assert_eq!(report["cid"], "4F70656E565354334D6F636B4761696E");
+    assert_eq!(report["passed"], 2);
+    let results = report["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["check"], "state");
+    assert!(results[0]["setup"].is_null());
+    assert_eq!(results[1]["setup"]["sampleRate"], 96000.0);
+    assert_eq!(results[1]["setup"]["blockSize"], 32);
+    assert!(results.iter().all(|r| r["outcome"] == "pass"));
+}
+
+#[test]
+fn skipped_checks_are_left_out() {
+    let out = run(
+        "cli-validate-skip",
+        None,
+        &[
+            "--json",
+            "--checks",
+            "state,ranges,automation",
+            "--skip-checks",
+            "automation,teardown",
+        ],
+    );
+    assert!(out.status.success());
+    let doc: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+    let results = doc["reports"][0]["results"].as_array().unwrap();
+    let checks: Vec<_> = results
+        .iter()
+        .map(|r| r["check"].as_str().unwrap())
+        .collect();
+    assert_eq!(checks, ["state", "ranges"]);
+    assert!(results.iter().all(|r| r["outcome"] == "pass"));
+}
+
+#[test]
+fn unlisted_controller_class_only_warns() {
+    let cid = "0123456789ABCDEF0123456789ABCDEF";
+    let out = run(
+        "cli-validate-controller",
+        Some((CONTROLLER_CID_ENV, cid)),
+        &["--checks", "state,controller"],
+    );
+    let text = text(&out);
+    assert_eq!(out.status.code(), Some(0), "{text}");
+    let row = text
+        .lines()
+        .find(|l| l.trim_start().starts_with("controller "))
+        .unwrap_or_default();
+    assert!(row.contains("WARN"), "{text}");
+    assert!(
+        row.ends_with(&format!(
+            "getControllerClassId names {cid}, which the factory does not list"
+        )),
+        "{row}"
+    );
+    assert!(
+        text.trim_end()
+            .ends_with("summary: 1 passed, 0 failed, 0 skipped, 1 warning"),
+        "{text}"
+    );
+}
+
+#[test]
+fn crash_fails_only_that_check() {
+    let out = run(
+        "cli-validate-crash",
+        Some((CRASH_IN_ENV, "process")),
+        &["--checks", "state,process,params", "--block-sizes", "64"],
+    );
+    let text = text(&out);
+    assert_eq!(out.status.code(), Some(7), "{text}");
+    for line in text
+        .lines()
+        .filter(|l| l.trim_start().starts_with("process "))
+    {
+        assert!(line.contains("FAIL") && line.contains("crashed"), "{line}");
+    }
+    assert!(
+        text.contains("summary: 2 passed, 2 failed, 0 skipped"),
+        "{text}"
+    );
+}
+
+#[test]
+fn activation_crash_fails_in_its_worker() {
+    let out = run(
+        "cli-validate-activation-crash",
+        Some((CRASH_IN_ENV, "process")),
+        &["--checks", "activation"],
+    );
+    let text = text(&out);
+    assert_eq!(out.status.code(), Some(7), "{text}");
+    let row = text
+        .lines()
+        .find(|l| l.trim_start().starts_with("activation "))
+        .unwrap_or_default();
+    // A plugin that writes nothing itself is reported after the check's last
+    // "activation: <permutation>" line; the mock announces its own crash.
+    assert!(row.contains("FAIL") && row.contains("crashed"), "{text}");
+}
+
+#[test]
+fn hang_fails_after_check_timeout() {
+    let out = run(
+        "cli-validate-hang",
+        Some((HANG_IN_ENV, "process")),
+        &[
+            "--checks",
+            "process",
+            "--sample-rates",
+            "48000",
+            "--block-sizes",
+            "64",
+            "--check-timeout",
+            "1",
+        ],
+    );
+    let text = text(&out);
+    assert_eq!(out.status.code(), Some(7), "{text}");
+    assert!(text.contains("FAIL   did not finish within 1s"), "{text}");
+}
@@ -0,0 +1,112 @@
+//! WAV input and output, and the test signal generators a session can ask for.
+
+use std::path::Path;
+
+use openvst3_host::{SessionBitDepth, SessionInput};
+
+/// Planar samples of `path` and its sample rate.
+pub fn read_wav(path: &Path) -> Result<(Vec<Vec<f32>>, f64), hound::Error> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let mut planar = vec![Vec::new(); spec.channels as usize];
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 * scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    for frame in interleaved.chunks(planar.len().max(1)) {
+        for (ch, &s) in planar.iter_mut().zip(frame) {
+            ch.push(s);
+        }
+    }
+    Ok((planar, spec.sample_rate as f64))
+}
+
+pub type WavOut = hound::WavWriter<std::io::BufWriter<std::fs::File>>;
+
+/// Create the output file up front so an unwritable path fails before rendering.
+pub fn create_wav(
+    path: &Path,
+    channels: usize,
+    sample_rate: f64,
+    depth: SessionBitDepth,
+) -> Result<WavOut, hound::Error> {
+    let (bits_per_sample, sample_format) = match depth {
+        SessionBitDepth::Int16 => (16, hound::SampleFormat::Int),
+        SessionBitDepth::Int24 => (24, hound::SampleFormat::Int),
+        SessionBitDepth::Float32 => (32, hound::SampleFormat::Float),
+    };
+    let spec = hound::WavSpec {
+        channels: channels as u16,
+        sample_rate: sample_rate.round() as u32,
+        bits_per_sample,
+        sample_format,
+    };
+    hound::WavWriter::create(path, spec)
+}
+
+pub fn write_wav(
+    mut writer: WavOut,
+    channels: &[Vec<f32>],
+    depth: SessionBitDepth,
+) -> Result<(), hound::Error> {
+    let frames = channels.first().map_or(0, Vec::len);
+    for i in 0..frames {
+        for ch in channels {
+            let s = ch[i];
+            match depth {
+                SessionBitDepth::Int16 => {
+                    writer.write_sample((s.clamp(-1.0, 1.0) * 32767.0) as i16)?
+                }
+                SessionBitDepth::Int24 => {
+                    writer.write_sample((s.clamp(-1.0, 1.0) * 8_388_607.0) as i32)?
+                }
+                SessionBitDepth::Float32 => writer.write_sample(s)?,
+            }
+        }
+    }
+    writer.finalize()
+}
+
+/// `frames` of a generator input on `channels` identical channels; `None` for
+/// silence and file inputs.
+pub fn generate(
+    input: &SessionInput,
+    channels: usize,
+    frames: usize,
+    sample_rate: f64,
+) -> Option<Vec<Vec<f32>>> {
+    let gain = |level_db: f64| 10f64.powf(level_db / 20.0);
+    let signal: Vec<f32> = match *input {
+        SessionInput::Silence | SessionInput::File { .. } => return None,
+        SessionInput::Sine {
+            frequency,
+            level_db,
+        } => {
+            let step = std::f64::consts::TAU * frequency / sample_rate;
+            (0..frames)
+                .map(|i| ((i as f64 * step).sin() * gain(level_db)) as f32)
+                .collect()
+        }
+        SessionInput::Noise { level_db } => {
+            // xorshift32: reproducible renders without a rand dependency.
+            let mut state = 0x9E37_79B9u32;
+            (0..frames)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    let uniform = (state >> 8) as f64 / (1u32 << 23) as f64 - 1.0;
+                    (uniform * gain(level_db)) as f32
+                })
+                .collect()
+        }
+        SessionInput::Impulse => (0..frames).map(|i| (i == 0) as u8 as f32).collect(),
+    };
+    Some(vec![signal; channels])
+}
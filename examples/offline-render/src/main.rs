@@ -0,0 +1,292 @@
+//! Offline render driven by a session file.
+//!
+//! `offline-render session.json` renders what a [`RenderSession`] describes: plugin and
+//! class, preset, input file or generator, MIDI file, parameter automation, sample
+//! rate, block size, tail and output file. Session files are JSON; relative paths in
+//! them are relative to the file. Progress goes to stderr.
+//!
+//! Exit codes are host-cli's ([`cli_common::ExitCode`]): 2 for session mistakes and
+//! other usage errors, 3 load (including a session whose plugin does not exist), 4
+//! class info, 5 createInstance, 6 process or preset errors from the plugin, and 8
+//! for files that cannot be read or written. `--error-json` prints the error as JSON.
+
+mod audio;
+
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use cli_common::{CliError, ExitCode};
+use openvst3_host::{self as host, ClassRef, RenderSession, SessionInput, SessionTail};
+
+/// Upper bound for `"tail": "auto"` when the plugin reports an infinite tail.
+const AUTO_TAIL_CAP_SECS: f64 = 10.0;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Args {
+    /// Session file (JSON)
+    session: PathBuf,
+
+    /// Check the session and open the plugin, but do not render
+    #[arg(long)]
+    check: bool,
+
+    /// No progress output
+    #[arg(long, short)]
+    quiet: bool,
+
+    /// Print the final error as a JSON object on stderr
+    #[arg(long)]
+    error_json: bool,
+}
+
+fn main() {
+    let args = Args::parse();
+    cli_common::exit(run(&args), args.error_json);
+}
+
+/// Read, resolve and validate the session at `path`.
+fn load_session(path: &Path) -> Result<RenderSession, CliError> {
+    let text = std::fs::read_to_string(path).map_err(|e| CliError::io(path, e))?;
+    // serde_json names the offending field and its line and column.
+    let usage = |e: &dyn std::fmt::Display| CliError::usage(format!("{}: {e}", path.display()));
+    let mut session: RenderSession = serde_json::from_str(&text).map_err(|e| usage(&e))?;
+    session.resolve_paths(path.parent().unwrap_or(Path::new("")));
+    session.validate().map_err(|e| usage(&e))?;
+    if !session.plugin.exists() {
+        return Err(CliError::new(
+            ExitCode::LoadError,
+            format!(
+                "{}: plugin: {} does not exist",
+                path.display(),
+                session.plugin.display()
+            ),
+        ));
+    }
+    Ok(session)
+}
+
+fn run(args: &Args) -> Result<(), CliError> {
+    let session = load_session(&args.session)?;
+
+    let input = match &session.input {
+        SessionInput::File { path } => {
+            Some(audio::read_wav(path).map_err(|e| CliError::io(path, e))?)
+        }
+        _ => None,
+    };
+    let sample_rate = match (session.sample_rate, &input) {
+        (Some(sr), Some((_, file_sr))) if sr != *file_sr => {
+            let msg = format!("sampleRate {sr} differs from the input file's {file_sr} Hz");
+            return Err(CliError::usage(format!("{msg}; resample the file first")));
+        }
+        (Some(sr), _) => sr,
+        (None, Some((_, file_sr))) => *file_sr,
+        (None, None) => 48_000.0,
+    };
+    let midi = match &session.midi {
+        Some(path) => {
+            let bytes = std::fs::read(path).map_err(|e| CliError::io(path, e))?;
+            host::read_smf(&bytes)
+                .map_err(|e| CliError::usage(format!("{}: {e}", path.display())))?
+        }
+        None => Vec::new(),
+    };
+    let frames = match (session.duration, &input) {
+        (Some(secs), _) => (secs * sample_rate).round() as usize,
+        (None, Some((planar, _))) => planar.first().map_or(0, Vec::len),
+        // Validation guarantees a MIDI file here; render through its last message.
+        (None, None) => midi
+            .last()
+            .map_or(0, |(t, _)| (t * sample_rate).round() as usize + 1),
+    };
+
+    let bin = if session.plugin.is_dir() {
+        host::BundlePath::resolve(&session.plugin)
+    } else {
+        Ok(session.plugin.clone())
+    };
+    let mut module = bin
+        .and_then(host::Module::load)
+        .map_err(|e| CliError::host("load error", &e, ExitCode::LoadError))?;
+    let cid = match session.class {
+        ClassRef::Index(i) => {
+            host::read_class_info_v1(&mut module, i)
+                .map_err(|e| CliError::host("class read error", &e, ExitCode::ClassError))?
+                .2
+        }
+        ClassRef::Cid(cid) => cid,
+    };
+    let mut plugin = module
+        .plugin(cid)
+        .io_mode(host::IoMode::OfflineProcessing)
+        .create()
+        .map_err(|e| CliError::host("create error", &e, ExitCode::InstantiationError))?;
+    if let Some(path) = &session.preset {
+        load_preset(&plugin, path, cid)?;
+    }
+
+    let mut converter = host::MidiConverter::new();
+    if !midi.is_empty() {
+        if let Some(controller) = plugin.controller() {
+            unsafe { converter.learn(controller.as_ptr() as *mut openvst3_abi::FUnknown) };
+        }
+    }
+    let events = host::RenderEvents {
+        midi: midi
+            .iter()
+            .map(|&(t, msg)| ((t * sample_rate).round() as usize, msg))
+            .collect(),
+        automation: session.automation_frames(sample_rate),
+    };
+
+    let wanted_inputs = match (&input, &session.input) {
+        (Some((planar, _)), _) => planar.len(),
+        (None, SessionInput::Silence) => 0,
+        (None, _) => session.channels.unwrap_or(2),
+    };
+    let wanted_outputs =
+        session
+            .channels
+            .unwrap_or(if wanted_inputs > 0 { wanted_inputs } else { 2 });
+    let (input_channels, output_channels) =
+        plugin.negotiate_channels(wanted_inputs, wanted_outputs);
+    let tail = match session.tail {
+        SessionTail::Auto => plugin.tail_frames((AUTO_TAIL_CAP_SECS * sample_rate) as usize),
+        SessionTail::Seconds(s) => (s * sample_rate).round() as usize,
+    };
+    let config = host::RenderConfig {
+        sample_rate,
+        block_size: session.block_size,
+        input_channels,
+        output_channels,
+        tail,
+        plugin_tail: None,
+        sidechain: None,
+        aux_outputs: false,
+        priority: Some(host::Profile::High),
+    };
+    if args.check {
+        eprintln!(
+            "ok: {} frames + {tail} tail at {sample_rate} Hz, {input_channels} in / \
+             {output_channels} out, {} MIDI messages, {} automation lanes",
+            frames,
+            events.midi.len(),
+            events.automation.len()
+        );
+        return Ok(());
+    }
+
+    let planar = match input {
+        Some((planar, _)) => planar,
+        None => {
+            audio::generate(&session.input, input_channels, frames, sample_rate).unwrap_or_default()
+        }
+    };
+    let out = &session.output;
+    let writer = audio::create_wav(out, output_channels, sample_rate, session.bit_depth)
+        .map_err(|e| CliError::io(out, e))?;
+    let mut last_pct = None;
+    let rendered = plugin.render_offline_buses(
+        &config,
+        &planar,
+        frames,
+        &events,
+        &converter,
+        |done, total| {
+            let pct = done * 100 / total.max(1);
+            if !args.quiet && last_pct != Some(pct) {
+                eprint!("\rrendering {pct:3}%");
+                last_pct = Some(pct);
+            }
+        },
+    );
+    if !args.quiet {
+        eprintln!();
+    }
+    let rendered = match rendered {
+        Ok(r) => r,
+        Err(e) => {
+            // Don't leave a header-only file behind.
+            drop(writer);
+            let _ = std::fs::remove_file(out);
+            return Err(CliError::host(
+                "process error",
+                &e,
+                ExitCode::ProcessingError,
+            ));
+        }
+    };
+    if let Some(achieved @ host::Achieved::Unchanged(_)) = &rendered.priority {
+        if !args.quiet {
+            eprintln!("note: rendered at normal priority; {achieved}");
+        }
+    }
+    let rendered = rendered.into_main();
+    audio::write_wav(writer, &rendered, session.bit_depth).map_err(|e| CliError::io(out, e))?;
+    if !args.quiet {
+        eprintln!(
+            "wrote {} frames x {} channels to {}",
+            rendered.first().map_or(0, Vec::len),
+            rendered.len(),
+            out.display()
+        );
+        eprintln!("{}", level_report(&rendered, sample_rate));
+    }
+    Ok(())
+}
+
+/// Peak and loudest 300 ms RMS per channel, and the loudest momentary loudness.
+fn level_report(rendered: &[Vec<f32>], sample_rate: f64) -> String {
+    let mut meter = host::Meter::new(rendered.len(), sample_rate, &host::MeterConfig::default());
+    let frames = rendered.first().map_or(0, Vec::len);
+    // Step in 100 ms blocks so the loudness maximum is sampled often enough.
+    let step = ((sample_rate / 10.0) as usize).max(1);
+    let mut max_rms = vec![0.0f64; rendered.len()];
+    let mut block: Vec<&[f32]> = Vec::with_capacity(rendered.len());
+    for start in (0..frames).step_by(step) {
+        let end = (start + step).min(frames);
+        block.clear();
+        block.extend(rendered.iter().map(|chan| &chan[start..end]));
+        meter.process_planar(&block, end - start);
+        for (ch, max) in max_rms.iter_mut().enumerate() {
+            *max = max.max(meter.rms(ch));
+        }
+    }
+    let db = |linear: f64| {
+        if linear > 0.0 {
+            format!("{:.1}", host::meter::to_db(linear))
+        } else {
+            "-inf".into()
+        }
+    };
+    let peaks: Vec<String> = (0..rendered.len())
+        .map(|ch| db(meter.max_peak(ch)))
+        .collect();
+    let rms: Vec<String> = max_rms.iter().map(|&r| db(r)).collect();
+    let lufs = meter
+        .max_momentary_lufs()
+        .filter(|l| l.is_finite())
+        .map_or("-inf".into(), |l| format!("{l:.1}"));
+    format!(
+        "levels: peak dBFS {} | max rms dBFS {} | max {lufs} LUFS-M",
+        peaks.join(" "),
+        rms.join(" ")
+    )
+}
+
+/// Load `path` as a preset, warning when it was saved for another class.
+fn load_preset(plugin: &host::PluginInstance, path: &Path, cid: [u8; 16]) -> Result<(), CliError> {
+    let bytes = std::fs::read(path).map_err(|e| CliError::io(path, e))?;
+    let preset = host::VstPreset::parse(&bytes).map_err(|e| CliError::io(path, e))?;
+    if preset.class_id != cid {
+        eprintln!(
+            "warning: preset is for class {}, not {}",
+            host::fmt_cid_hex(&preset.class_id),
+            host::fmt_cid_hex(&cid)
+        );
+    }
+    plugin
+        .load_preset(&preset)
+        .map_err(|e| CliError::host("preset load error", &e, ExitCode::ProcessingError))
+}
@@ -0,0 +1,182 @@
+//! offline-render sessions against the workspace test plugin.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use openvst3_testplugin::bundle::{make_bundle, remove_bundle};
+
+fn scratch(tag: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("offline-render-{tag}-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Write `json` as `session.json` in `dir` and run it.
+fn run(dir: &Path, json: &str) -> Output {
+    let session = dir.join("session.json");
+    std::fs::write(&session, json).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_offline-render"))
+        .arg("--quiet")
+        .arg(&session)
+        .output()
+        .unwrap()
+}
+
+fn stderr(out: &Output) -> String {
+    String::from_utf8(out.stderr.clone()).unwrap()
+}
+
+#[test]
+fn renders_generator_with_automation() {
+    let dir = scratch("render");
+    let bundle = make_bundle("render-session");
+    let json = format!(
+        r#"{{
+            "plugin": {bundle:?},
+            "class": 0,
+            "input": {{ "kind": "sine", "frequency": 440, "levelDb": -6 }},
+            "automation": [
+                {{ "param": 0, "points": [{{ "time": 0, "value": 0.5 }}, {{ "time": 0.05, "value": 1 }}] }}
+            ],
+            "sampleRate": 48000,
+            "blockSize": 256,
+            "duration": 0.1,
+            "tail": 0,
+            "output": "out.wav",
+            "bitDepth": "32f"
+        }}"#
+    );
+    let out = run(&dir, &json);
+    remove_bundle(&bundle);
+    assert_eq!(out.status.code(), Some(0), "{}", stderr(&out));
+
+    let reader = hound::WavReader::open(dir.join("out.wav")).unwrap();
+    let spec = reader.spec();
+    assert_eq!((spec.channels, spec.sample_rate), (2, 48_000));
+    assert_eq!(reader.duration(), 4800);
+    let peak = reader
+        .into_samples::<f32>()
+        .map(Result::unwrap)
+        .fold(0.0f32, |m, s| m.max(s.abs()));
+    assert!(peak > 0.1 && peak <= 1.0, "peak {peak}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn schema_mistakes_name_the_field() {
+    let dir = scratch("schema");
+
+    let out = run(
+        &dir,
+        r#"{ "plugin": "x.vst3", "output": "o.wav",
+             "smapleRate": 44100 }"#,
+    );
+    let text = stderr(&out);
+    assert_eq!(out.status.code(), Some(2), "{text}");
+    assert!(text.contains("unknown field `smapleRate`"), "{text}");
+    assert!(text.contains("line 2"), "{text}");
+
+    let out = run(&dir, r#"{ "output": "o.wav" }"#);
+    assert!(stderr(&out).contains("missing field `plugin`"));
+
+    let out = run(
+        &dir,
+        r#"{ "plugin": "x.vst3", "output": "o.wav", "input": { "kind": "saw" } }"#,
+    );
+    assert!(
+        stderr(&out).contains("unknown variant `saw`"),
+        "{}",
+        stderr(&out)
+    );
+
+    let out = run(
+        &dir,
+        r#"{ "plugin": "x.vst3", "output": "o.wav", "class": "not-a-cid", "duration": 1 }"#,
+    );
+    assert!(
+        stderr(&out).contains("a class index or a 32-digit hex CID"),
+        "{}",
+        stderr(&out)
+    );
+
+    let out = run(
+        &dir,
+        r#"{ "plugin": "x.vst3", "output": "o.wav", "duration": 1,
+             "automation": [{ "param": 0, "points": [{ "time": 0, "value": 1.5 }] }] }"#,
+    );
+    assert!(stderr(&out).contains("automation[0].points[0].value"));
+
+    let out = run(
+        &dir,
+        r#"{ "plugin": "missing.vst3", "output": "o.wav", "duration": 1 }"#,
+    );
+    let text = stderr(&out);
+    assert_eq!(out.status.code(), Some(3), "{text}");
+    assert!(text.contains("missing.vst3 does not exist"), "{text}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn error_json_reports_the_category() {
+    let dir = scratch("error-json");
+    let session = dir.join("session.json");
+    std::fs::write(
+        &session,
+        r#"{ "plugin": "missing.vst3", "output": "o.wav", "duration": 1 }"#,
+    )
+    .unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_offline-render"))
+        .arg("--error-json")
+        .arg(&session)
+        .output()
+        .unwrap();
+    assert_eq!(out.status.code(), Some(3));
+    let doc: serde_json::Value = serde_json::from_str(stderr(&out).trim()).unwrap();
+    assert_eq!(doc["category"], "load");
+    assert_eq!(doc["code"], 3);
+    assert!(doc["message"]
+        .as_str()
+        .unwrap()
+        .contains("missing.vst3 does not exist"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn report_ends_with_output_levels() {
+    let dir = scratch("levels");
+    let bundle = make_bundle("render-levels");
+    let json = format!(
+        r#"{{
+            "plugin": {bundle:?},
+            "input": {{ "kind": "sine", "frequency": 1000, "levelDb": -6 }},
+            "sampleRate": 48000,
+            "duration": 0.5,
+            "tail": 0,
+            "output": "out.wav",
+            "bitDepth": "32f"
+        }}"#
+    );
+    let session = dir.join("session.json");
+    std::fs::write(&session, json).unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_offline-render"))
+        .arg(&session)
+        .output()
+        .unwrap();
+    remove_bundle(&bundle);
+    let text = stderr(&out);
+    assert_eq!(out.status.code(), Some(0), "{text}");
+
+    let peak = hound::WavReader::open(dir.join("out.wav"))
+        .unwrap()
+        .into_samples::<f32>()
+        .map(Result::unwrap)
+        .fold(0.0f32, |m, s| m.max(s.abs()));
+    let line = text.lines().last().unwrap();
+    let want = format!("{:.1}", 20.0 * (peak as f64).log10());
+    assert!(
+        line.starts_with(&format!("levels: peak dBFS {want} {want} | max rms dBFS ")),
+        "{line}"
+    );
+    assert!(line.ends_with(" LUFS-M"), "{line}");
+    std::fs::remove_dir_all(dir).unwrap();
+}
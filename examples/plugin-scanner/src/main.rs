@@ -0,0 +1,341 @@
+//! Find installed VST3 plugins and list their audio classes. A plugin found in more
+//! than one place is listed again under "shadowed installs", with the install `--prefer`
+//! picked. `--diff` compares the scan with an earlier `--json` output. `--progress`
+//! draws a progress bar on stderr while scanning, when stderr is a terminal.
+//!
+//! Exit status: 0 when every bundle scanned, 1 when some failed (their reasons are
+//! listed after the results), 2 for usage errors and cache files that cannot be
+//! written.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use clap::Parser;
+use openvst3_host as host;
+use openvst3_host::scan::FailedBundle;
+use serde::{Deserialize, Serialize};
+
+mod progress;
+
+const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct Args {
+    /// Also search this folder (or bundle); repeatable
+    #[arg(long, value_name = "DIR")]
+    path: Vec<PathBuf>,
+
+    /// Only search --path folders, not the platform's standard VST3 locations
+    #[arg(long)]
+    no_default_paths: bool,
+
+    /// Scan up to N bundles at the same time
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    parallel: usize,
+
+    /// Scan each bundle in a child process so crashes and hangs only fail that bundle
+    #[arg(long)]
+    isolated: bool,
+
+    /// With --isolated: seconds before a hung bundle is killed
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = 30.0,
+        requires = "isolated"
+    )]
+    timeout: f64,
+
+    /// Reuse results for unchanged bundles from FILE and write this scan back to it
+    #[arg(long, value_name = "FILE")]
+    cache: Option<PathBuf>,
+
+    /// With --cache: rescan bundles that failed last time even if unchanged
+    #[arg(long, requires = "cache")]
+    rescan_failed: bool,
+
+    /// Which install of a plugin found in several places to use
+    #[arg(long, value_name = "POLICY", value_enum, default_value_t = Prefer::User)]
+    prefer: Prefer,
+
+    /// Print the results as JSON instead of a table
+    #[arg(long)]
+    json: bool,
+
+    /// Also report what changed since FILE, an earlier --json output
+    #[arg(long, value_name = "FILE")]
+    diff: Option<PathBuf>,
+
+    /// Record a SHA-256 of each module binary, so --diff notices rebuilt binaries
+    #[arg(long)]
+    hash: bool,
+
+    /// Show a progress bar on stderr while scanning (only on a terminal)
+    #[arg(long)]
+    progress: bool,
+
+    /// Internal: scan one bundle and report to the parent on stdout
+    #[arg(long, value_name = "BUNDLE", hide = true)]
+    scan_worker: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum Prefer {
+    /// The per-user VST3 folder over system-wide ones
+    User,
+    /// The highest class version
+    Newest,
+    /// The earliest search path (--path folders come after the standard ones)
+    PathOrder,
+}
+
+impl From<Prefer> for host::DuplicatePolicy {
+    fn from(prefer: Prefer) -> Self {
+        match prefer {
+            Prefer::User => Self::UserDirWins,
+            Prefer::Newest => Self::NewestVersion,
+            Prefer::PathOrder => Self::PathOrder,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Document<'a> {
+    schema_version: u32,
+    plugins: &'a [host::ScannedPlugin],
+    duplicates: &'a [host::DuplicateGroup],
+    failures: Vec<Failure<'a>>,
+    scanned: usize,
+    cached: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diff: Option<&'a host::ScanDiff>,
+}
+
+/// The part of an earlier [`Document`] `--diff` reads.
+#[derive(Deserialize)]
+struct Previous {
+    plugins: Vec<host::ScannedPlugin>,
+}
+
+fn load_previous(path: &Path) -> Result<Vec<host::ScannedPlugin>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    serde_json::from_str::<Previous>(&text)
+        .map(|p| p.plugins)
+        .map_err(|e| format!("{}: {e}", path.display()))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Failure<'a> {
+    path: &'a Path,
+    reason: String,
+    failure: &'a host::ScanFailure,
+}
+
+impl<'a> From<&'a FailedBundle> for Failure<'a> {
+    fn from(f: &'a FailedBundle) -> Self {
+        Self {
+            path: &f.path,
+            reason: f.failure.to_string(),
+            failure: &f.failure,
+        }
+    }
+}
+
+fn load_cache(path: &Path) -> host::ScanCache {
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return host::ScanCache::default();
+    };
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        eprintln!("warning: ignoring cache {}: {e}", path.display());
+        host::ScanCache::default()
+    })
+}
+
+fn save_cache(path: &Path, cache: &host::ScanCache) -> Result<(), String> {
+    let text = serde_json::to_string_pretty(cache).expect("cache serializes");
+    std::fs::write(path, text).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn print_diff(previous: &Path, diff: &host::ScanDiff) {
+    if diff.is_empty() {
+        println!("\nno changes since {}", previous.display());
+        return;
+    }
+    println!("\nchanges since {}:", previous.display());
+    let line = |what: &str, p: &host::ScannedPlugin| {
+        println!(
+            "  {what:<7}  {} {} ({}) {}",
+            p.name,
+            host::fmt_cid_hex(&p.cid),
+            p.version,
+            p.path.display()
+        );
+    };
+    diff.added.iter().for_each(|p| line("added", p));
+    diff.removed.iter().for_each(|p| line("removed", p));
+    for (old, new) in &diff.updated {
+        line("updated", new);
+        if old.version != new.version {
+            println!("           version {} -> {}", old.version, new.version);
+        }
+        if old.name != new.name {
+            println!("           name {} -> {}", old.name, new.name);
+        }
+        if old.path != new.path {
+            println!("           moved from {}", old.path.display());
+        }
+    }
+}
+
+fn print_table(report: &host::ScanReport) {
+    let rows: Vec<[String; 6]> = report
+        .plugins
+        .iter()
+        .map(|p| {
+            [
+                p.name.clone(),
+                p.vendor.clone(),
+                p.version.clone(),
+                p.sub_categories.clone(),
+                host::fmt_cid_hex(&p.cid),
+                p.path.display().to_string(),
+            ]
+        })
+        .collect();
+    let header = ["NAME", "VENDOR", "VERSION", "CATEGORY", "CID", "PATH"].map(String::from);
+    let mut widths = header.clone().map(|h| h.chars().count());
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    for row in std::iter::once(&header).chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, w)| format!("{cell:<w$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+    println!(
+        "\n{} plugins in {} bundles ({} scanned, {} cached)",
+        report.plugins.len(),
+        report.scanned + report.cached,
+        report.scanned,
+        report.cached
+    );
+    if !report.duplicates.is_empty() {
+        println!("\nshadowed installs:");
+        for group in &report.duplicates {
+            let chosen = &group.chosen;
+            println!(
+                "  {} {}: using {} ({})",
+                chosen.name,
+                host::fmt_cid_hex(&group.cid),
+                chosen.path.display(),
+                chosen.version
+            );
+            for shadowed in &group.shadowed {
+                println!(
+                    "    shadows {} ({})",
+                    shadowed.path.display(),
+                    shadowed.version
+                );
+            }
+        }
+    }
+    if !report.failures.is_empty() {
+        println!("\nfailed bundles:");
+        for f in &report.failures {
+            println!("  {}: {}", f.path.display(), f.failure);
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    if let Some(bundle) = &args.scan_worker {
+        if let Err(e) = host::scan::run_worker_process(bundle) {
+            eprintln!("{e}");
+            std::process::exit(2);
+        }
+        return;
+    }
+
+    let previous = match args.diff.as_deref().map(load_previous).transpose() {
+        Ok(previous) => previous,
+        Err(e) => {
+            eprintln!("cannot read --diff file: {e}");
+            std::process::exit(2);
+        }
+    };
+
+    let mut scanner = host::Scanner::new()
+        .parallel(args.parallel)
+        .duplicate_policy(args.prefer.into())
+        .hash_binaries(args.hash);
+    if args.no_default_paths {
+        scanner = scanner.without_default_paths();
+    }
+    for path in &args.path {
+        scanner = scanner.add_path(path);
+    }
+    if args.isolated {
+        let exe = std::env::current_exe().expect("current executable");
+        scanner = scanner
+            .isolated(move |bundle| {
+                let mut cmd = Command::new(&exe);
+                cmd.arg("--scan-worker").arg(bundle);
+                cmd
+            })
+            .timeout(Duration::from_secs_f64(args.timeout));
+    }
+    if let Some(path) = &args.cache {
+        scanner = scanner
+            .with_cache(load_cache(path))
+            .rescan_failed(args.rescan_failed);
+    }
+
+    let report = if args.progress {
+        progress::scan(&mut scanner)
+    } else {
+        scanner.scan()
+    };
+    let diff = previous.map(|old| host::ScanDiff::compare(&old, &report.plugins));
+
+    if args.json {
+        let doc = Document {
+            schema_version: SCHEMA_VERSION,
+            plugins: &report.plugins,
+            duplicates: &report.duplicates,
+            failures: report.failures.iter().map(Failure::from).collect(),
+            scanned: report.scanned,
+            cached: report.cached,
+            diff: diff.as_ref(),
+        };
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&doc).expect("report serializes")
+        );
+    } else {
+        print_table(&report);
+        if let (Some(path), Some(diff)) = (&args.diff, &diff) {
+            print_diff(path, diff);
+        }
+    }
+
+    if let Some(path) = &args.cache {
+        if let Err(e) = save_cache(path, scanner.cache()) {
+            eprintln!("cannot write cache: {e}");
+            std::process::exit(2);
+        }
+    }
+    if !report.failures.is_empty() {
+        std::process::exit(1);
+    }
+}
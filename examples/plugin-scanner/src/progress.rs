@@ -0,0 +1,39 @@
+//! `--progress`: scan through [`host::ScanStream`] and show a bar on stderr as
+//! bundles start.
+
+use futures_executor::block_on_stream;
+use indicatif::{ProgressBar, ProgressStyle};
+
+use openvst3_host as host;
+
+/// Scan with a progress bar and report the results.
+pub fn scan(scanner: &mut host::Scanner) -> host::ScanReport {
+    let mut stream = scanner.scan_stream();
+    let bar = ProgressBar::new(stream.total() as u64).with_style(
+        ProgressStyle::with_template("[{bar:30}] {pos}/{len} bundles, {prefix} failed  {msg}")
+            .expect("valid template")
+            .progress_chars("#>-"),
+    );
+    bar.set_prefix("0");
+    let mut failed = 0;
+    for event in block_on_stream(&mut stream) {
+        match event {
+            host::ScanEvent::Started(path) => {
+                bar.inc(1);
+                bar.set_message(
+                    path.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .into_owned(),
+                );
+            }
+            host::ScanEvent::Failed(..) => {
+                failed += 1;
+                bar.set_prefix(failed.to_string());
+            }
+            host::ScanEvent::Finished(_) => {}
+        }
+    }
+    bar.finish_with_message("");
+    stream.into_report()
+}
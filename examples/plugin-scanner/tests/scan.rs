@@ -0,0 +1,279 @@
+//! plugin-scanner over a folder holding the test plugin and a broken bundle, and over
+//! two folders holding the same plugin.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use openvst3_testplugin::bundle::{make_broken_bundle, make_bundle, remove_bundle};
+use openvst3_testplugin::{CLASS_NAME, CRASH_IN_ENV, HANG_IN_ENV};
+
+/// A scan root with `OpenVST3TestPlugin.vst3` and `Broken.vst3`.
+fn scan_root(tag: &str) -> (PathBuf, PathBuf) {
+    let bundle = make_bundle(tag);
+    make_broken_bundle(&bundle, "Broken");
+    let root = bundle.parent().unwrap().to_path_buf();
+    (bundle, root)
+}
+
+fn scan(root: &Path, env: Option<(&str, &str)>, extra: &[&str]) -> Output {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_plugin-scanner"));
+    cmd.arg("--no-default-paths")
+        .arg("--path")
+        .arg(root)
+        .args(extra);
+    if let Some((k, v)) = env {
+        cmd.env(k, v);
+    }
+    cmd.output().unwrap()
+}
+
+fn stdout(out: &Output) -> String {
+    String::from_utf8(out.stdout.clone()).unwrap()
+}
+
+#[test]
+fn table_lists_plugins_then_failures() {
+    let (bundle, root) = scan_root("scanner-table");
+    let out = scan(&root, None, &["--parallel", "2"]);
+    let text = stdout(&out);
+    remove_bundle(&bundle);
+    assert_eq!(out.status.code(), Some(1), "{text}");
+    let mut lines = text.lines();
+    let header = lines.next().unwrap();
+    assert!(header.starts_with("NAME  "), "{text}");
+    let row = lines.next().unwrap();
+    assert!(row.starts_with(CLASS_NAME), "{text}");
+    assert!(row.contains("4F70656E565354334D6F636B4761696E"), "{text}");
+    // Columns line up with the header.
+    assert_eq!(header.find("CID"), row.find("4F70656E"));
+    assert!(
+        text.contains("1 plugins in 2 bundles (2 scanned, 0 cached)"),
+        "{text}"
+    );
+    assert!(text.contains("failed bundles:"), "{text}");
+    assert!(text.contains("Broken.vst3: load failed"), "{text}");
+}
+
+#[test]
+fn progress_bar_stays_off_piped_output() {
+    let (bundle, root) = scan_root("scanner-progress");
+    let out = scan(&root, None, &["--progress", "--parallel", "2"]);
+    let text = stdout(&out);
+    remove_bundle(&bundle);
+    // The bar is only drawn on a terminal, so a log gets no cursor movement.
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!stderr.contains("bundles,"), "{stderr}");
+    assert!(!text.contains('\r') && !text.contains('\x1b'), "{text}");
+    assert!(
+        text.contains("1 plugins in 2 bundles (2 scanned, 0 cached)"),
+        "{text}"
+    );
+}
+
+#[test]
+fn cache_skips_unchanged_bundles() {
+    let (bundle, root) = scan_root("scanner-cache");
+    let cache = root.join("cache.json");
+    let cache = cache.to_str().unwrap();
+    let first: serde_json::Value =
+        serde_json::from_slice(&scan(&root, None, &["--json", "--cache", cache]).stdout).unwrap();
+    let second: serde_json::Value =
+        serde_json::from_slice(&scan(&root, None, &["--json", "--cache", cache]).stdout).unwrap();
+    let retried: serde_json::Value = serde_json::from_slice(
+        &scan(
+            &root,
+            None,
+            &["--json", "--cache", cache, "--rescan-failed"],
+        )
+        .stdout,
+    )
+    .unwrap();
+    remove_bundle(&bundle);
+
+    assert_eq!(
+        (&first["scanned"], &first["cached"]),
+        (&2.into(), &0.into())
+    );
+    assert_eq!(
+        (&second["scanned"], &second["cached"]),
+        (&0.into(), &2.into())
+    );
+    assert_eq!(
+        (&retried["scanned"], &retried["cached"]),
+        (&1.into(), &1.into())
+    );
+    assert_eq!(second["plugins"], first["plugins"]);
+    assert_eq!(second["plugins"][0]["name"], CLASS_NAME);
+    assert_eq!(second["failures"][0]["failure"]["kind"], "load");
+}
+
+#[test]
+fn isolated_scan_survives_crashes_and_hangs() {
+    let (bundle, root) = scan_root("scanner-isolated");
+    let crashed = scan(
+        &root,
+        Some((CRASH_IN_ENV, "factory")),
+        &["--isolated", "--json"],
+    );
+    let hung = scan(
+        &root,
+        Some((HANG_IN_ENV, "factory")),
+        &[
+            "--isolated",
+            "--json",
+            "--timeout",
+            "0.5",
+            "--parallel",
+            "2",
+        ],
+    );
+    remove_bundle(&bundle);
+
+    for (out, kind) in [(crashed, "crashed"), (hung, "timedOut")] {
+        assert_eq!(out.status.code(), Some(1));
+        let doc: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+        assert_eq!(doc["plugins"].as_array().unwrap().len(), 0);
+        let failures = doc["failures"].as_array().unwrap();
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[1]["failure"]["kind"], kind, "{doc}");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn crash_reports_the_phase_it_happened_in() {
+    let (bundle, root) = scan_root("scanner-phase");
+    let cases = [
+        ("entry", "load"),
+        ("factory", "factory"),
+        ("get_class_info", "class 0"),
+    ];
+    for (crash_in, phase) in cases {
+        let out = scan(
+            &root,
+            Some((CRASH_IN_ENV, crash_in)),
+            &["--isolated", "--json"],
+        );
+        let doc: serde_json::Value = serde_json::from_slice(&out.stdout).unwrap();
+        let failure = &doc["failures"][1]["failure"];
+        assert_eq!(failure["kind"], "crashed", "{doc}");
+        let expected: serde_json::Value = match phase.strip_prefix("class ") {
+            Some(index) => serde_json::json!({ "class": index.parse::<u32>().unwrap() }),
+            None => phase.into(),
+        };
+        assert_eq!(failure["phase"], expected, "{crash_in}: {doc}");
+        assert!(
+            doc["failures"][1]["reason"]
+                .as_str()
+                .unwrap()
+                .starts_with(&format!("crashed in {phase}: signal 6")),
+            "{doc}"
+        );
+        if cfg!(all(target_os = "linux", target_env = "gnu")) {
+            assert!(
+                !failure["backtrace"].as_array().unwrap().is_empty(),
+                "{doc}"
+            );
+        }
+    }
+    remove_bundle(&bundle);
+}
+
+#[test]
+fn shadowed_installs_are_listed_after_the_results() {
+    let first = make_bundle("scanner-shadow-a");
+    let second = make_bundle("scanner-shadow-b");
+    let (first_root, second_root) = (first.parent().unwrap(), second.parent().unwrap());
+    // Search the second root first: path order prefers it.
+    let out = scan(
+        second_root,
+        None,
+        &[
+            "--path",
+            first_root.to_str().unwrap(),
+            "--prefer",
+            "path-order",
+        ],
+    );
+    let text = stdout(&out);
+    let json = scan(
+        second_root,
+        None,
+        &["--path", first_root.to_str().unwrap(), "--json"],
+    );
+    remove_bundle(&first);
+    remove_bundle(&second);
+
+    assert_eq!(out.status.code(), Some(0), "{text}");
+    assert!(
+        text.contains("2 plugins in 2 bundles (2 scanned, 0 cached)"),
+        "{text}"
+    );
+    let section = text.split("shadowed installs:\n").nth(1).expect(&text);
+    let mut lines = section.lines();
+    let using = lines.next().unwrap();
+    assert!(
+        using.starts_with(&format!("  {CLASS_NAME} 4F70656E")),
+        "{text}"
+    );
+    assert!(
+        using.contains(&format!("using {}", second.display())),
+        "{text}"
+    );
+    assert!(
+        lines
+            .next()
+            .unwrap()
+            .starts_with(&format!("    shadows {}", first.display())),
+        "{text}"
+    );
+
+    let doc: serde_json::Value = serde_json::from_slice(&json.stdout).unwrap();
+    let group = &doc["duplicates"][0];
+    assert_eq!(group["cid"], "4F70656E565354334D6F636B4761696E", "{doc}");
+    assert_eq!(group["chosen"]["path"], second.to_str().unwrap(), "{doc}");
+    assert_eq!(
+        group["shadowed"][0]["path"],
+        first.to_str().unwrap(),
+        "{doc}"
+    );
+}
+
+#[test]
+fn diff_reports_a_rebuilt_binary() {
+    let (bundle, root) = scan_root("scanner-diff");
+    let previous = root.join("previous.json");
+    let out = scan(&root, None, &["--json", "--hash"]);
+    std::fs::write(&previous, &out.stdout).unwrap();
+    let previous = previous.to_str().unwrap();
+
+    let unchanged = stdout(&scan(&root, None, &["--hash", "--diff", previous]));
+    assert!(unchanged.contains("\nno changes since "), "{unchanged}");
+
+    let binary = openvst3_host::BundlePath::resolve(&bundle).unwrap();
+    let mut bytes = std::fs::read(&binary).unwrap();
+    bytes.extend_from_slice(b"rebuilt");
+    std::fs::write(&binary, bytes).unwrap();
+    let text = stdout(&scan(&root, None, &["--hash", "--diff", previous]));
+    let json = |out: Output| serde_json::from_slice::<serde_json::Value>(&out.stdout).unwrap();
+    let first = json(scan(&root, None, &["--json", "--hash", "--diff", previous]));
+    let second = json(scan(&root, None, &["--json", "--hash", "--diff", previous]));
+    let missing = scan(&root, None, &["--diff", "/nonexistent/previous.json"]);
+    remove_bundle(&bundle);
+
+    assert!(text.contains("\nchanges since "), "{text}");
+    assert!(
+        text.contains(&format!(
+            "  updated  {CLASS_NAME} 4F70656E565354334D6F636B4761696E"
+        )),
+        "{text}"
+    );
+    // Stable for scripts: same scans, same diff.
+    assert_eq!(first["diff"], second["diff"]);
+    assert_eq!(first["diff"]["added"], serde_json::json!([]));
+    assert_eq!(first["diff"]["removed"], serde_json::json!([]));
+    let updated = &first["diff"]["updated"][0];
+    assert_ne!(updated[0]["binaryHash"], updated[1]["binaryHash"]);
+    assert_eq!(updated[1]["name"], CLASS_NAME);
+    assert_eq!(missing.status.code(), Some(2));
+}
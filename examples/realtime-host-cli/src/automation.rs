@@ -0,0 +1,65 @@
+//! `--record-automation`: the edits the plugin's controller reports, usually from its
+//! editor, written out as automation lanes when the run ends.
+//!
+//! A [`host::ComponentHandler`] on the controller passes `beginEdit`, `performEdit`
+//! and `endEdit` to a [`host::AutomationRecorder`], which the audio callback syncs
+//! with each block's context; the two share one clock. The file is a JSON array of
+//! lanes, ready for the `automation` key of an offline-render session.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use openvst3_host as host;
+
+/// See the module docs.
+pub struct Automation {
+    path: PathBuf,
+    clock: host::MonotonicClock,
+    recorder: Arc<host::AutomationRecorder>,
+    handler: Box<host::ComponentHandler>,
+}
+
+impl Automation {
+    /// Record for a stream at `sample_rate`, to be written to `path`.
+    pub fn new(path: PathBuf, sample_rate: f64) -> Self {
+        let clock = host::MonotonicClock::new();
+        let recorder = Arc::new(host::AutomationRecorder::new(sample_rate).with_clock(clock));
+        let handler = host::ComponentHandler::new(|_| {});
+        handler.track_automation(&recorder);
+        Self {
+            path,
+            clock,
+            recorder,
+            handler,
+        }
+    }
+
+    /// The clock the stream's contexts must read `systemTime` from.
+    pub fn clock(&self) -> host::MonotonicClock {
+        self.clock
+    }
+
+    /// The audio callback's end: sync it with every block.
+    pub fn recorder(&self) -> Arc<host::AutomationRecorder> {
+        self.recorder.clone()
+    }
+
+    /// Record the edits of `controller`; again after a reload for the new one.
+    pub fn attach(&self, controller: &host::ControllerHandle) -> Result<(), host::HostError> {
+        controller.set_component_handler(&self.handler)
+    }
+
+    /// Write the lanes. Call once the plugin is released, as the handler goes with it.
+    pub fn finish(self) -> Result<(), String> {
+        let gestures = self.recorder.gestures().len();
+        let lanes = self.recorder.lanes();
+        let text = serde_json::to_string_pretty(&lanes).expect("lanes serialize");
+        std::fs::write(&self.path, text).map_err(|e| format!("{}: {e}", self.path.display()))?;
+        println!(
+            "automation: {gestures} gestures on {} parameters written to {}",
+            lanes.len(),
+            self.path.display()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+//! Console control while the stream runs.
+//!
+//! Lines from stdin are handled on the main thread by [`Console`], which talks to the
+//! edit controller directly but reaches the audio callback only through two lock-free
+//! queues: a [`ParamRingBuffer`] for parameter edits and a ring of note events. The
+//! callback drains both into the block's lists via [`ControlFeed`]: notes at sample
+//! offset 0, and parameter edits as short ramps (or at offset 0 with `--smoothing-ms 0`).
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use openvst3_host as host;
+use openvst3_host::prelude::*;
+use openvst3_host::ring::{Consumer, Producer};
+use openvst3_host::{
+    ControllerHandle, EventList, ParamRingBuffer, ParamSender, ParamSmoother, ParameterChanges,
+};
+
+/// Edits and notes that can wait for the audio thread.
+const QUEUE: usize = 256;
+
+const USAGE: &str = "commands:
+  param ID VALUE     set parameter ID to normalized VALUE (0..1)
+  bypass on|off      set the plugin's bypass parameter
+  params             list parameters with their last values
+  note PITCH [VEL]   note on (velocity 1..127, default 100)
+  noteoff PITCH      note off
+  reload             reload the plugin binary without stopping audio
+  help               show this list
+  quit, empty line   stop";
+
+/// Audio-callback end of the console.
+pub struct ControlFeed {
+    params: ParamRingBuffer,
+    notes: Consumer<Event>,
+    smoother: Option<ParamSmoother>,
+}
+
+impl ControlFeed {
+    /// Move queued edits and notes into the lists of this block of `frames`; whatever
+    /// does not fit stays queued for the next block.
+    pub fn drain(&mut self, frames: usize, events: &mut EventList, params: &mut ParameterChanges) {
+        match &mut self.smoother {
+            Some(smoother) => self.params.drain_smoothed(smoother, frames, params),
+            None => self.params.drain_into(params),
+        };
+        while let Some(event) = self.notes.peek() {
+            if !events.push(event) {
+                break;
+            }
+            self.notes.skip(1);
+        }
+    }
+}
+
+/// What the caller should do after a line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Stop,
+}
+
+/// Main-thread end of the console.
+pub struct Console {
+    params: ParamSender,
+    notes: Producer<Event>,
+    controller: Option<ControllerHandle>,
+    /// Values sent from the console, shown by `params` instead of the controller's.
+    sent: BTreeMap<ParamID, ParamValue>,
+}
+
+/// Connect a console to the plugin instance `component`, using its edit controller
+/// (if it has one on the same object) for `params` and `bypass`. Edits to the
+/// controller's parameters ramp over `smoothing` at `sample_rate`, unless it is zero.
+pub unsafe fn open(
+    component: *mut core::ffi::c_void,
+    sample_rate: f64,
+    smoothing: Duration,
+) -> (Console, ControlFeed) {
+    let controller = controller_of(component);
+    let smoother = match &controller {
+        Some(controller) if !smoothing.is_zero() => {
+            let params = controller.parameters().unwrap_or_default();
+            let smoother =
+                params
+                    .iter()
+                    .fold(ParamSmoother::new(sample_rate, smoothing), |smoother, p| {
+                        smoother.track(p.id, p.step_count, controller.get_param_normalized(p.id))
+                    });
+            Some(smoother)
+        }
+        _ => None,
+    };
+    let (param_tx, param_rx) = ParamRingBuffer::with_capacity(QUEUE);
+    let (note_tx, note_rx) = host::ring::ring(QUEUE);
+    let console = Console {
+        params: param_tx,
+        notes: note_tx,
+        controller,
+        sent: BTreeMap::new(),
+    };
+    let feed = ControlFeed {
+        params: param_rx,
+        notes: note_rx,
+        smoother,
+    };
+    (console, feed)
+}
+
+/// The edit controller on the same object as `component`, if there is one.
+pub unsafe fn controller_of(component: *mut core::ffi::c_void) -> Option<ControllerHandle> {
+    host::query_interface(component, iids::IEDIT_CONTROLLER.0)
+        .ok()
+        .and_then(|ptr| host::InterfacePtr::from_raw(ptr as *mut IEditController))
+        .map(ControllerHandle::new)
+}
+
+fn parse<T: std::str::FromStr>(word: Option<&str>, what: &str) -> Result<T, String> {
+    let word = word.ok_or_else(|| format!("missing {what}"))?;
+    word.parse()
+        .map_err(|_| format!("invalid {what}: {word:?}"))
+}
+
+impl Console {
+    /// Handle one line of input; errors print the usage.
+    pub fn handle(&mut self, line: &str) -> Flow {
+        match self.command(line) {
+            Ok(flow) => flow,
+            Err(e) => {
+                eprintln!("{e}\n{USAGE}");
+                Flow::Continue
+            }
+        }
+    }
+
+    fn command(&mut self, line: &str) -> Result<Flow, String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(Flow::Stop);
+        };
+        match command {
+            "quit" | "exit" => return Ok(Flow::Stop),
+            "help" => println!("{USAGE}"),
+            "param" => {
+                let id = parse(words.next(), "parameter id")?;
+                let value: ParamValue = parse(words.next(), "value")?;
+                if !(0.0..=1.0).contains(&value) {
+                    return Err(format!("value {value} is outside 0..1"));
+                }
+                self.set(id, value)?;
+            }
+            "bypass" => {
+                let value = match words.next() {
+                    Some("on") => 1.0,
+                    Some("off") => 0.0,
+                    _ => return Err("bypass takes on or off".into()),
+                };
+                let id = self.bypass_id()?;
+                self.set(id, value)?;
+            }
+            "params" => self.print_params()?,
+            "note" => {
+                let pitch: u8 = parse(words.next(), "pitch")?;
+                let velocity: u8 = match words.next() {
+                    Some(v) => parse(Some(v), "velocity")?,
+                    None => 100,
+                };
+                if pitch > 127 || !(1..=127).contains(&velocity) {
+                    return Err("pitch must be 0..127 and velocity 1..127".into());
+                }
+                self.note(Event::note_on(0, 0, pitch as i16, velocity as f32 / 127.0))?;
+            }
+            "noteoff" => {
+                let pitch: u8 = parse(words.next(), "pitch")?;
+                if pitch > 127 {
+                    return Err("pitch must be 0..127".into());
+                }
+                self.note(Event::note_off(0, 0, pitch as i16, 0.0))?;
+            }
+            other => return Err(format!("unknown command {other:?}")),
+        }
+        if words.next().is_some() {
+            eprintln!("note: extra words after {command:?} were ignored");
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn set(&mut self, id: ParamID, value: ParamValue) -> Result<(), String> {
+        if !self.params.send(id, value) {
+            return Err("parameter queue is full; try again".into());
+        }
+        // Keep the controller in step, as a host does after automation.
+        if let Some(controller) = &self.controller {
+            let _ = controller.set_param_normalized(id, value);
+        }
+        self.sent.insert(id, value);
+        Ok(())
+    }
+
+    fn note(&mut self, event: Event) -> Result<(), String> {
+        if self.notes.push(event) {
+            Ok(())
+        } else {
+            Err("note queue is full; try again".into())
+        }
+    }
+
+    /// Talk to the controller of `component` after a reload replaced the instance;
+    /// values sent so far are kept, as the new instance got the old one's state.
+    pub unsafe fn reconnect(&mut self, component: *mut core::ffi::c_void) {
+        self.controller = controller_of(component);
+    }
+
+    /// The plugin's edit controller, if it has one on the component.
+    pub fn controller(&self) -> Result<&ControllerHandle, String> {
+        self.controller
+            .as_ref()
+            .ok_or_else(|| "the plugin has no edit controller on its component".into())
+    }
+
+    fn bypass_id(&self) -> Result<ParamID, String> {
+        let params = self.controller()?.parameters().map_err(|e| e.to_string())?;
+        params
+            .iter()
+            .find(|p| p.flags.contains(ParameterFlags::IS_BYPASS))
+            .map(|p| p.id)
+            .ok_or_else(|| "the plugin has no bypass parameter".into())
+    }
+
+    fn print_params(&self) -> Result<(), String> {
+        let controller = self.controller()?;
+        let params = controller.parameters().map_err(|e| e.to_string())?;
+        for p in &params {
+            let value = self
+                .sent
+                .get(&p.id)
+                .copied()
+                .unwrap_or_else(|| controller.get_param_normalized(p.id));
+            let display = controller
+                .param_string_by_value(p.id, value)
+                .map(|s| {
+                    format!(
+                        " ({s}{}{})",
+                        if p.units.is_empty() { "" } else { " " },
+                        p.units
+                    )
+                })
+                .unwrap_or_default();
+            println!("{:>10}  {:<24}  {value:.4}{display}", p.id, p.title);
+        }
+        Ok(())
+    }
+}
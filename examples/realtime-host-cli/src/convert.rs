@@ -0,0 +1,65 @@
+//! Integer device formats: the plugin runs in 32-bit float and the callback converts
+//! each sample with cpal's [`Sample`] conversions while interleaving, optionally with
+//! TPDF dither for 16-bit devices.
+//!
+//! cpal's float-to-integer conversions scale by the integer range and truncate with
+//! `as`, so anything at or beyond full scale saturates and NaN becomes zero.
+
+use cpal::{FromSample, Sample};
+
+/// One 16-bit least significant bit.
+const LSB16: f32 = 1.0 / 32_768.0;
+
+/// Float-to-device sample conversion for one stream.
+pub struct Converter {
+    dither: Option<Tpdf>,
+}
+
+impl Converter {
+    /// With `dither`, adds triangular noise of ±1 LSB of 16-bit audio before each
+    /// conversion; only meaningful for 16-bit formats.
+    pub fn new(dither: bool) -> Self {
+        Self {
+            dither: dither.then(Tpdf::new),
+        }
+    }
+
+    #[inline]
+    pub fn sample<S: Sample + FromSample<f32>>(&mut self, x: f32) -> S {
+        match &mut self.dither {
+            Some(tpdf) => S::from_sample(x + tpdf.next()),
+            None => S::from_sample(x),
+        }
+    }
+
+    /// Convert an interleaved float block into the device buffer.
+    pub fn block<S: Sample + FromSample<f32>>(&mut self, src: &[f32], dst: &mut [S]) {
+        for (d, &s) in dst.iter_mut().zip(src) {
+            *d = self.sample(s);
+        }
+    }
+}
+
+/// Triangular dither: the difference of two uniform values from a xorshift generator,
+/// which is plenty for decorrelating quantization error and never allocates or locks.
+struct Tpdf {
+    state: u32,
+}
+
+impl Tpdf {
+    fn new() -> Self {
+        Self { state: 0x9E37_79B9 }
+    }
+
+    /// A uniform value in `[0, 1)`.
+    fn uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    fn next(&mut self) -> f32 {
+        (self.uniform() - self.uniform()) * LSB16
+    }
+}
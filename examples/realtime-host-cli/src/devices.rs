@@ -0,0 +1,260 @@
+//! Audio host, device and stream config selection on top of cpal.
+//!
+//! Only depends on cpal so other examples can reuse it as is. Every lookup that can
+//! miss returns an error naming what is available instead.
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::{Device, Host, HostId, SampleFormat, SupportedStreamConfig};
+
+/// Which way a device is used.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Input,
+    Output,
+}
+
+impl Direction {
+    fn label(self) -> &'static str {
+        match self {
+            Direction::Input => "input",
+            Direction::Output => "output",
+        }
+    }
+}
+
+fn host_names() -> String {
+    cpal::available_hosts()
+        .iter()
+        .map(|id| id.name().to_lowercase())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The default host, or the available host called `name` (`jack`, `alsa`, `wasapi`,
+/// `coreaudio`, ...; case-insensitive).
+pub fn select_host(name: Option<&str>) -> Result<Host, String> {
+    let Some(name) = name else {
+        return Ok(cpal::default_host());
+    };
+    let id: HostId = cpal::available_hosts()
+        .into_iter()
+        .find(|id| id.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            format!(
+                "audio host {name:?} is not available; available: {}",
+                host_names()
+            )
+        })?;
+    cpal::host_from_id(id).map_err(|e| format!("audio host {name:?}: {e}"))
+}
+
+fn devices(host: &Host, direction: Direction) -> Result<Vec<Device>, String> {
+    let devices = match direction {
+        Direction::Input => host.input_devices().map(|d| d.collect()),
+        Direction::Output => host.output_devices().map(|d| d.collect()),
+    };
+    devices.map_err(|e| e.to_string())
+}
+
+/// The default device, or the one whose name is `spec`, or the `spec`-th device in
+/// `--list-devices` order.
+pub fn select_device(
+    host: &Host,
+    direction: Direction,
+    spec: Option<&str>,
+) -> Result<Device, String> {
+    let label = direction.label();
+    let Some(spec) = spec else {
+        let device = match direction {
+            Direction::Input => host.default_input_device(),
+            Direction::Output => host.default_output_device(),
+        };
+        return device.ok_or_else(|| format!("no default {label} device"));
+    };
+    let mut devices = devices(host, direction)?;
+    let names: Vec<String> = devices
+        .iter()
+        .map(|d| d.name().unwrap_or_default())
+        .collect();
+    let index = names
+        .iter()
+        .position(|n| n == spec)
+        .or_else(|| spec.parse::<usize>().ok().filter(|&i| i < devices.len()));
+    match index {
+        Some(i) => Ok(devices.swap_remove(i)),
+        None => {
+            let available: Vec<String> = names
+                .iter()
+                .enumerate()
+                .map(|(i, n)| format!("{i}: {n}"))
+                .collect();
+            Err(format!(
+                "no {label} device {spec:?}; available: {}",
+                available.join(", ")
+            ))
+        }
+    }
+}
+
+/// Constraints for [`select_config`]; `None` fields accept anything.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ConfigRequest {
+    pub format: Option<SampleFormat>,
+    pub channels: Option<u16>,
+    pub sample_rate: Option<u32>,
+}
+
+/// The device's default config if it meets `request`, otherwise the first supported
+/// config that does, at the requested rate or else the default rate when supported
+/// and the config's highest rate otherwise.
+pub fn select_config(
+    device: &Device,
+    direction: Direction,
+    request: &ConfigRequest,
+) -> Result<SupportedStreamConfig, String> {
+    let default = match direction {
+        Direction::Input => device.default_input_config(),
+        Direction::Output => device.default_output_config(),
+    }
+    .ok();
+    let matches = |format: SampleFormat, channels: u16| {
+        request.format.is_none_or(|f| f == format) && request.channels.is_none_or(|c| c == channels)
+    };
+    if let Some(cfg) = &default {
+        if matches(cfg.sample_format(), cfg.channels())
+            && request.sample_rate.is_none_or(|r| r == cfg.sample_rate().0)
+        {
+            return Ok(cfg.clone());
+        }
+    }
+
+    let ranges: Vec<_> = match direction {
+        Direction::Input => device.supported_input_configs().map(|r| r.collect()),
+        Direction::Output => device.supported_output_configs().map(|r| r.collect()),
+    }
+    .map_err(|e| e.to_string())?;
+    let wanted = request
+        .sample_rate
+        .or(default.as_ref().map(|c| c.sample_rate().0));
+    let found = ranges
+        .iter()
+        .filter(|r| matches(r.sample_format(), r.channels()))
+        .find_map(|r| {
+            let rate = cpal::SampleRate(wanted?);
+            (r.min_sample_rate() <= rate && rate <= r.max_sample_rate())
+                .then(|| r.with_sample_rate(rate))
+        })
+        .or_else(|| {
+            if request.sample_rate.is_some() {
+                return None;
+            }
+            ranges
+                .iter()
+                .find(|r| matches(r.sample_format(), r.channels()))
+                .map(|r| r.with_max_sample_rate())
+        });
+    found.ok_or_else(|| {
+        let name = device.name().unwrap_or_default();
+        let supported: Vec<String> = ranges.iter().map(describe_range).collect();
+        format!(
+            "{name}: no {} config with {}; supported: {}",
+            direction.label(),
+            describe_request(request),
+            supported.join(", ")
+        )
+    })
+}
+
+fn describe_request(request: &ConfigRequest) -> String {
+    let mut parts = Vec::new();
+    if let Some(f) = request.format {
+        parts.push(format!("format {f}"));
+    }
+    if let Some(c) = request.channels {
+        parts.push(format!("{c} channels"));
+    }
+    if let Some(r) = request.sample_rate {
+        parts.push(format!("{r} Hz"));
+    }
+    if parts.is_empty() {
+        "any settings".into()
+    } else {
+        parts.join(", ")
+    }
+}
+
+fn describe_range(range: &cpal::SupportedStreamConfigRange) -> String {
+    let (min, max) = (range.min_sample_rate().0, range.max_sample_rate().0);
+    let rates = if min == max {
+        format!("{min} Hz")
+    } else {
+        format!("{min}-{max} Hz")
+    };
+    format!("{} {}ch {rates}", range.sample_format(), range.channels())
+}
+
+/// [`select_config`] for each of `formats` in turn (overriding `request.format`),
+/// returning the first that the device supports.
+pub fn select_config_preferring(
+    device: &Device,
+    direction: Direction,
+    request: &ConfigRequest,
+    formats: &[SampleFormat],
+) -> Result<SupportedStreamConfig, String> {
+    let mut last = None;
+    for &format in formats {
+        let request = ConfigRequest {
+            format: Some(format),
+            ..*request
+        };
+        match select_config(device, direction, &request) {
+            Ok(config) => return Ok(config),
+            Err(e) => last = Some(e),
+        }
+    }
+    match last {
+        Some(e) if formats.len() > 1 => {
+            let tried: Vec<String> = formats.iter().map(|f| f.to_string()).collect();
+            Err(format!("{e} (tried formats {})", tried.join(", ")))
+        }
+        Some(e) => Err(e),
+        None => Err("no sample formats to try".into()),
+    }
+}
+
+/// Print every available host and its devices with their supported configs.
+pub fn print_devices() {
+    let default_id = cpal::default_host().id();
+    for id in cpal::available_hosts() {
+        let marker = if id == default_id { " (default)" } else { "" };
+        println!("host: {}{marker}", id.name().to_lowercase());
+        let host = match cpal::host_from_id(id) {
+            Ok(host) => host,
+            Err(e) => {
+                println!("  unavailable: {e}");
+                continue;
+            }
+        };
+        for direction in [Direction::Output, Direction::Input] {
+            let label = direction.label();
+            let devices = match devices(&host, direction) {
+                Ok(devices) => devices,
+                Err(e) => {
+                    println!("  {label} devices unavailable: {e}");
+                    continue;
+                }
+            };
+            for (index, device) in devices.iter().enumerate() {
+                println!("  {label} {index}: {}", device.name().unwrap_or_default());
+                let ranges: Vec<_> = match direction {
+                    Direction::Input => device.supported_input_configs().map(|r| r.collect()),
+                    Direction::Output => device.supported_output_configs().map(|r| r.collect()),
+                }
+                .unwrap_or_default();
+                for range in &ranges {
+                    println!("      {}", describe_range(range));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! `--silence-stop`: letting the plugin's tail ring out before the stream stops.
+//!
+//! The audio callback feeds the peak of every output block to a
+//! [`SilenceDetector`](host::SilenceDetector). When the run ends (Enter, the end of
+//! stdin or `--duration`, not Ctrl-C) the input is silenced and the main thread waits
+//! until the detector hears silence, for no longer than the tail the plugin reports:
+//! not at all without one, and [`MAX_TAIL`] for an infinite one.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use openvst3_host as host;
+use openvst3_host::analyze_block;
+use openvst3_host::limiter::Sample;
+
+/// The longest wait for a tail, however long the plugin says it is.
+pub const MAX_TAIL: Duration = Duration::from_secs(30);
+
+/// How often the main thread looks at the detector.
+const POLL: Duration = Duration::from_millis(10);
+
+/// Audio-callback end: listens to the output.
+pub struct Listen {
+    detector: host::SilenceDetector,
+    silent: Arc<AtomicBool>,
+}
+
+impl Listen {
+    /// Take in one interleaved output block.
+    pub fn record<T: Sample>(&mut self, data: &[T]) {
+        let found = analyze_block(data);
+        let peak = if found.is_finite() {
+            found.peak
+        } else {
+            f64::NAN
+        };
+        let silent = self.detector.next_block(peak);
+        self.silent.store(silent, Ordering::Relaxed);
+    }
+}
+
+/// Main-thread end: waits for the silence.
+pub struct Drain {
+    silent: Arc<AtomicBool>,
+    sample_rate: f64,
+}
+
+/// A listener that calls output at or below `threshold_db` silence.
+pub fn start(threshold_db: f64, sample_rate: f64) -> (Listen, Drain) {
+    let silent = Arc::new(AtomicBool::new(false));
+    let listen = Listen {
+        detector: host::SilenceDetector::new().threshold_db(threshold_db),
+        silent: silent.clone(),
+    };
+    (
+        listen,
+        Drain {
+            silent,
+            sample_rate,
+        },
+    )
+}
+
+/// How long to wait for a `tail` at `sample_rate`.
+pub fn wait_limit(tail: host::Tail, sample_rate: f64) -> Duration {
+    let cap = (MAX_TAIL.as_secs_f64() * sample_rate) as usize;
+    let (frames, _) = tail.frames(cap);
+    if sample_rate > 0.0 {
+        Duration::from_secs_f64(frames as f64 / sample_rate)
+    } else {
+        Duration::ZERO
+    }
+}
+
+impl Drain {
+    /// Whether the last blocks the callback played were quiet enough.
+    pub fn is_silent(&self) -> bool {
+        self.silent.load(Ordering::Relaxed)
+    }
+
+    /// Wait for the output to fall silent, or for the plugin's `tail` to run out, and
+    /// say which happened.
+    pub fn wait(&self, tail: host::Tail) {
+        let limit = wait_limit(tail, self.sample_rate);
+        if limit.is_zero() {
+            return;
+        }
+        let started = Instant::now();
+        while !self.is_silent() {
+            if started.elapsed() >= limit {
+                println!(
+                    "tail: still sounding after {:.1} s, stopping",
+                    limit.as_secs_f64()
+                );
+                return;
+            }
+            std::thread::sleep(POLL);
+        }
+        println!(
+            "tail: silent after {:.1} s",
+            started.elapsed().as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,198 @@
+//! `--editor`: the plugin's editor view in a winit window on the main thread.
+//!
+//! Audio keeps running on its own thread; the window loop wakes every 20 ms to handle
+//! console lines (a `quit`, an empty line or Ctrl-C closes the window) and to apply
+//! resize requests the plugin made through the host `IPlugFrame`. Closing the window
+//! detaches and releases the view before the window is destroyed.
+//!
+//! This is a first cut: it embeds into X11 windows (Xlib or XCB) on Linux, and passes
+//! the `HWND` / `NSView` on Windows and macOS, but the host frame does not yet offer
+//! the Linux `IRunLoop`, so editors that rely on it for timers may not redraw.
+
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use openvst3_host::prelude::*;
+use openvst3_host::PlugView;
+use winit::application::ApplicationHandler;
+use winit::dpi::PhysicalSize;
+use winit::event::WindowEvent;
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::raw_window_handle::{HasWindowHandle, RawWindowHandle};
+use winit::window::{Window, WindowId};
+
+use crate::control::{Console, Flow};
+
+/// How often the loop checks the console and the frame while the window is idle.
+const POLL: Duration = Duration::from_millis(20);
+
+/// A created view and the event loop that will show it.
+pub struct Editor {
+    event_loop: EventLoop<()>,
+    view: PlugView,
+}
+
+/// Create the event loop for `view`. Call on the main thread, before audio starts,
+/// so a missing display fails early.
+pub fn open(view: PlugView) -> Result<Editor, Box<dyn std::error::Error>> {
+    let event_loop = EventLoop::new()?;
+    Ok(Editor { event_loop, view })
+}
+
+impl Editor {
+    /// Show the editor until the window is closed or the console says stop.
+    pub fn run(
+        self,
+        title: &str,
+        console: &mut Console,
+        lines: &Receiver<Option<String>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut app = App {
+            title,
+            view: Some(self.view),
+            window: None,
+            console,
+            lines,
+            error: None,
+        };
+        self.event_loop.run_app(&mut app)?;
+        match app.error {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+struct App<'a> {
+    title: &'a str,
+    /// Taken when the window closes, so the view goes before the window.
+    view: Option<PlugView>,
+    window: Option<Window>,
+    console: &'a mut Console,
+    lines: &'a Receiver<Option<String>>,
+    error: Option<String>,
+}
+
+fn rect(size: PhysicalSize<u32>) -> ViewRect {
+    ViewRect {
+        left: 0,
+        top: 0,
+        right: size.width as i32,
+        bottom: size.height as i32,
+    }
+}
+
+fn physical(rect: ViewRect) -> PhysicalSize<u32> {
+    PhysicalSize::new(rect.width().max(1) as u32, rect.height().max(1) as u32)
+}
+
+/// The parent pointer and platform type string for embedding into `window`.
+fn parent_of(window: &Window) -> Result<(*mut core::ffi::c_void, &'static [u8]), String> {
+    let handle = window.window_handle().map_err(|e| e.to_string())?;
+    match handle.as_raw() {
+        // X11 parents are passed as the window id itself.
+        RawWindowHandle::Xlib(h) => Ok((
+            h.window as usize as *mut _,
+            platform_types::X11_EMBED_WINDOW_ID,
+        )),
+        RawWindowHandle::Xcb(h) => Ok((
+            h.window.get() as usize as *mut _,
+            platform_types::X11_EMBED_WINDOW_ID,
+        )),
+        RawWindowHandle::Win32(h) => Ok((h.hwnd.get() as *mut _, platform_types::HWND)),
+        RawWindowHandle::AppKit(h) => Ok((h.ns_view.as_ptr(), platform_types::NSVIEW)),
+        other => Err(format!("cannot embed an editor in a {other:?} window")),
+    }
+}
+
+impl App<'_> {
+    fn open_window(&mut self, event_loop: &ActiveEventLoop) -> Result<(), String> {
+        let view = self.view.as_mut().expect("view lives until close");
+        let size = view.size().map_err(|e| format!("editor size: {e}"))?;
+        let attributes = Window::default_attributes()
+            .with_title(self.title)
+            .with_inner_size(physical(size))
+            .with_resizable(view.can_resize());
+        let window = event_loop
+            .create_window(attributes)
+            .map_err(|e| e.to_string())?;
+        let (parent, ty) = parent_of(&window)?;
+        if !view.platform_type_supported(ty) {
+            let name = String::from_utf8_lossy(&ty[..ty.len() - 1]).into_owned();
+            return Err(format!("the editor does not support {name} parents"));
+        }
+        unsafe { view.attach(parent, ty) }.map_err(|e| format!("attach editor: {e}"))?;
+        self.window = Some(window);
+        Ok(())
+    }
+
+    /// Detach and release the view, then destroy the window.
+    fn close(&mut self, event_loop: &ActiveEventLoop) {
+        if let Some(mut view) = self.view.take() {
+            if let Err(e) = view.remove() {
+                eprintln!("editor: removed: {e}");
+            }
+        }
+        self.window = None;
+        event_loop.exit();
+    }
+}
+
+impl ApplicationHandler for App<'_> {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() || self.view.is_none() {
+            return;
+        }
+        if let Err(e) = self.open_window(event_loop) {
+            self.error = Some(e);
+            self.close(event_loop);
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => self.close(event_loop),
+            WindowEvent::Resized(size) => {
+                let (Some(view), Some(window)) = (&self.view, &self.window) else {
+                    return;
+                };
+                let mut wanted = rect(size);
+                if view.can_resize() {
+                    wanted = view.constrain(wanted);
+                    if wanted != rect(size) {
+                        let _ = window.request_inner_size(physical(wanted));
+                    }
+                }
+                if let Err(e) = view.set_size(wanted) {
+                    eprintln!("editor: onSize: {e}");
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        loop {
+            match self.lines.try_recv() {
+                Ok(Some(line)) => {
+                    if self.console.handle(&line) == Flow::Stop {
+                        return self.close(event_loop);
+                    }
+                }
+                Ok(None) | Err(TryRecvError::Disconnected) => return self.close(event_loop),
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+        if let (Some(view), Some(window)) = (&self.view, &self.window) {
+            if let Some(wanted) = view.take_resize_request() {
+                // Applied now on some platforms; otherwise a Resized event follows.
+                if let Some(size) = window.request_inner_size(physical(wanted)) {
+                    if let Err(e) = view.set_size(rect(size)) {
+                        eprintln!("editor: onSize: {e}");
+                    }
+                }
+            }
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + POLL));
+    }
+}
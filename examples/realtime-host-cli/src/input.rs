@@ -0,0 +1,212 @@
+//! Live input: a cpal input stream feeding the plugin's main input bus.
+//!
+//! The input callback pushes interleaved f32 frames into a [`host::ring`]; the output
+//! callback pulls one block per process call through [`InputFeed`]. The two streams
+//! may run on different clocks, so the feed keeps the buffered amount near the
+//! requested latency by dropping frames when it runs ahead and repeating the last
+//! frame when it runs dry, counting both instead of buffering without bound. The
+//! plugin's input goes through an [`InputGate`](host::InputGate), so a muted or
+//! disconnected input stops being copied into every plugin channel.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use cpal::traits::DeviceTrait;
+use openvst3_host as host;
+use openvst3_host::ring::{Consumer, Producer};
+
+use crate::devices::{self, ConfigRequest, Direction};
+
+/// Drift counters shared by both callbacks, in frames.
+#[derive(Default)]
+pub struct InputStats {
+    pub dropped: AtomicU64,
+    pub duplicated: AtomicU64,
+}
+
+impl InputStats {
+    pub fn line(&self) -> String {
+        format!(
+            "input drift: {} frames dropped, {} duplicated",
+            self.dropped.load(Ordering::Relaxed),
+            self.duplicated.load(Ordering::Relaxed)
+        )
+    }
+}
+
+/// Output-callback end of the input bridge.
+pub struct InputFeed {
+    ring: Consumer<f32>,
+    channels: usize,
+    /// Frames kept buffered on top of one block.
+    latency: usize,
+    primed: bool,
+    last_frame: Vec<f32>,
+    scratch: Vec<f32>,
+    stats: Arc<InputStats>,
+    /// Set by [`LiveInput::silence`].
+    silenced: Arc<AtomicBool>,
+}
+
+impl InputFeed {
+    /// Fill `bound`'s input channels with `frames` frames. Plugin channel `c` reads
+    /// device channel `c % channels`, so a mono input feeds every plugin channel and
+    /// surplus device channels are ignored.
+    pub fn read<T: host::BusSample + From<f32>>(
+        &mut self,
+        bound: &mut host::BoundProcessData<T>,
+        frames: usize,
+    ) {
+        let ch = self.channels;
+        let available = self.ring.available() / ch;
+        if self.silenced.load(Ordering::Relaxed) {
+            self.ring.skip(available * ch);
+            bound.write_input(0.0, |planar| {
+                for out in planar {
+                    out[..frames].fill(T::from(0.0));
+                }
+            });
+            return;
+        }
+        if !self.primed {
+            if available < self.latency + frames {
+                bound.write_input(0.0, |planar| {
+                    for out in planar {
+                        out[..frames].fill(T::from(0.0));
+                    }
+                });
+                return;
+            }
+            self.primed = true;
+        }
+        // Running ahead of the output clock: drop back to the target fill level.
+        let high = self.latency + 3 * frames;
+        if available > high {
+            let excess = available - (self.latency + frames);
+            self.ring.skip(excess * ch);
+            self.stats
+                .dropped
+                .fetch_add(excess as u64, Ordering::Relaxed);
+        }
+
+        let want = frames * ch;
+        let got = self.ring.pop_slice(&mut self.scratch[..want]) / ch;
+        if got > 0 {
+            self.last_frame
+                .copy_from_slice(&self.scratch[(got - 1) * ch..got * ch]);
+        }
+        // Running behind: hold the last frame rather than inserting a gap.
+        for frame in got..frames {
+            self.scratch[frame * ch..(frame + 1) * ch].copy_from_slice(&self.last_frame);
+        }
+        if got < frames {
+            self.stats
+                .duplicated
+                .fetch_add((frames - got) as u64, Ordering::Relaxed);
+        }
+
+        let block = &self.scratch[..want];
+        let peak = block.iter().fold(0.0f32, |peak, x| peak.max(x.abs()));
+        bound.write_input(peak as f64, |planar| {
+            for (c, out) in planar.iter_mut().enumerate() {
+                let src = c % ch;
+                for (frame, sample) in out[..frames].iter_mut().enumerate() {
+                    *sample = T::from(block[frame * ch + src]);
+                }
+            }
+        });
+    }
+}
+
+/// Input-callback end of the input bridge.
+struct InputSink {
+    ring: Producer<f32>,
+    channels: usize,
+    stats: Arc<InputStats>,
+}
+
+impl InputSink {
+    fn write(&mut self, data: &[f32]) {
+        // Whole frames only, so the channels stay aligned in the ring.
+        let ch = self.channels;
+        let fits = (self.ring.free() / ch).min(data.len() / ch);
+        self.ring.push_slice(&data[..fits * ch]);
+        let lost = data.len() / ch - fits;
+        if lost > 0 {
+            self.stats.dropped.fetch_add(lost as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The input stream; keep it alive while the output callback reads the feed.
+pub struct LiveInput {
+    pub stream: cpal::Stream,
+    pub stats: Arc<InputStats>,
+    pub device_name: String,
+    pub channels: usize,
+    silenced: Arc<AtomicBool>,
+}
+
+impl LiveInput {
+    /// Feed the plugin silence from now on, whatever the device delivers.
+    pub fn silence(&self) {
+        self.silenced.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Open `name` (a device name or index, or the default input) at `sample_rate` as f32
+/// and start capturing. `latency_frames` of audio are buffered before the feed starts
+/// delivering.
+pub fn open(
+    host: &cpal::Host,
+    name: Option<&str>,
+    sample_rate: u32,
+    block_frames: usize,
+    latency_frames: usize,
+) -> Result<(LiveInput, InputFeed), Box<dyn std::error::Error>> {
+    let device = devices::select_device(host, Direction::Input, name)?;
+    let device_name = device.name()?;
+    let request = ConfigRequest {
+        format: Some(cpal::SampleFormat::F32),
+        channels: None,
+        sample_rate: Some(sample_rate),
+    };
+    let config = devices::select_config(&device, Direction::Input, &request)?;
+    let config: cpal::StreamConfig = config.config();
+    let channels = config.channels as usize;
+
+    // Room for the latency plus several blocks of jitter from either callback.
+    let capacity = (latency_frames + 8 * block_frames) * channels;
+    let (producer, consumer) = host::ring::ring(capacity);
+    let stats = Arc::new(InputStats::default());
+    let mut sink = InputSink {
+        ring: producer,
+        channels,
+        stats: stats.clone(),
+    };
+    let stream = device.build_input_stream(
+        &config,
+        move |data: &[f32], _| sink.write(data),
+        |err| eprintln!("input stream error: {err}"),
+        None,
+    )?;
+    let silenced = Arc::new(AtomicBool::new(false));
+    let feed = InputFeed {
+        ring: consumer,
+        channels,
+        latency: latency_frames,
+        primed: false,
+        last_frame: vec![0.0; channels],
+        scratch: vec![0.0; block_frames * channels],
+        stats: stats.clone(),
+        silenced: silenced.clone(),
+    };
+    let live = LiveInput {
+        stream,
+        stats,
+        device_name,
+        channels,
+        silenced,
+    };
+    Ok((live, feed))
+}
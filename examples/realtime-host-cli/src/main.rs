@@ -1,38 +1,32 @@
 use clap::Parser;
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use openvst3_abi::{process_consts, IAudioProcessor, ProcessSetup};
+use cpal::traits::{DeviceTrait, StreamTrait};
 use openvst3_host as host;
+use openvst3_host::prelude::*;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+
+mod automation;
+mod control;
+mod convert;
+mod devices;
+mod drain;
+#[cfg(feature = "editor")]
+mod editor;
+mod input;
+#[cfg(feature = "midi")]
+mod midi;
+mod record;
+mod soak;
+mod stats;
+#[cfg(test)]
+mod tests;
+mod watch;
 
 fn load_hex_iid(hex: &str) -> Result<[u8; 16], host::HostError> {
     host::parse_hex_16(hex)
 }
 
-fn parse_hex64_list(values: Option<&Vec<String>>) -> Result<Option<Vec<u64>>, host::HostError> {
-    match values {
-        Some(list) => {
-            let mut out = Vec::new();
-            for raw in list {
-                let trimmed = raw.trim();
-                if trimmed.is_empty() {
-                    continue;
-                }
-                let cleaned = trimmed.trim_start_matches("0x");
-                let val = u64::from_str_radix(cleaned, 16).map_err(|_| {
-                    host::HostError::InvalidBundle(format!("invalid hex64 value: {trimmed}"))
-                })?;
-                out.push(val);
-            }
-            if out.is_empty() {
-                Ok(None)
-            } else {
-                Ok(Some(out))
-            }
-        }
-        None => Ok(None),
-    }
-}
-
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
@@ -46,11 +40,21 @@ struct Args {
 
     /// Index of class to instantiate (from host-cli --list output).
     #[arg(long)]
-    class: i32,
+    #[cfg_attr(not(feature = "midi"), arg(required_unless_present = "list_devices"))]
+    #[cfg_attr(
+        feature = "midi",
+        arg(required_unless_present_any = ["list_devices", "list_midi_ports"])
+    )]
+    class: Option<i32>,
 
     /// IID (16-byte hex) of interface to request at createInstance (e.g. IAudioProcessor).
     #[arg(long, value_name = "HEX32")]
-    iid: String,
+    #[cfg_attr(not(feature = "midi"), arg(required_unless_present = "list_devices"))]
+    #[cfg_attr(
+        feature = "midi",
+        arg(required_unless_present_any = ["list_devices", "list_midi_ports"])
+    )]
+    iid: Option<String>,
 
     /// Optional IID (hex) to QueryInterface for IComponent (for bus info / diagnostics).
     #[arg(long, value_name = "HEX32")]
@@ -64,19 +68,140 @@ struct Args {
     #[arg(long)]
     float64: bool,
 
-    /// Optional comma-separated input arrangement u64 IDs for setBusArrangements.
-    #[arg(long, value_delimiter = ',')]
-    in_arrs: Option<Vec<String>>,
+    /// Add TPDF dither when the device takes 16-bit integer samples.
+    #[arg(long)]
+    dither: bool,
+
+    /// List audio hosts and their devices with supported configs, then exit.
+    #[arg(long)]
+    list_devices: bool,
+
+    /// Audio host API to use (jack, alsa, wasapi, coreaudio, ...); default: cpal's.
+    #[arg(long, value_name = "HOST")]
+    audio_host: Option<String>,
+
+    /// Output device by name or --list-devices index; default: the host's default.
+    #[arg(long, value_name = "NAME-OR-INDEX")]
+    output_device: Option<String>,
+
+    /// Open the output with exactly N channels.
+    #[arg(long, value_name = "N")]
+    channels: Option<u16>,
+
+    /// Comma-separated input bus layouts for setBusArrangements: names (mono,
+    /// stereo, quad, 5.0, 5.1, 7.1, 7.1.4, ambi1-3), `<n>ch` or 0x hex.
+    #[arg(long, value_name = "LAYOUTS", value_delimiter = ',', value_parser = host::parse_arrangement)]
+    in_arrs: Option<Vec<u64>>,
+
+    /// Comma-separated output bus layouts for setBusArrangements, as for --in-arrs.
+    #[arg(long, value_name = "LAYOUTS", value_delimiter = ',', value_parser = host::parse_arrangement)]
+    out_arrs: Option<Vec<u64>>,
+
+    /// Feed the default input device into the plugin's main input bus.
+    #[arg(long)]
+    with_input: bool,
+
+    /// Feed this input device (name or --list-devices index) into the plugin; implies
+    /// --with-input.
+    #[arg(long, value_name = "NAME")]
+    input_device: Option<String>,
+
+    /// Extra input buffering in milliseconds on top of one block; more absorbs
+    /// jitter between separate input and output devices.
+    #[arg(long, value_name = "MS", default_value_t = 10.0)]
+    input_latency_ms: f64,
+
+    /// Keep the output at or below this level (e.g. -3): gain reduction plus a hard
+    /// clamp after the plugin, with a warning on sustained DC offset.
+    #[arg(long, value_name = "DBFS", allow_hyphen_values = true)]
+    limit: Option<f64>,
+
+    /// Also write the plugin output to this WAV file (32-bit float).
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Record the parameter edits the plugin's controller reports (from its editor,
+    /// say) and write them to this JSON file as offline-render automation lanes.
+    #[arg(long, value_name = "FILE")]
+    record_automation: Option<PathBuf>,
+
+    /// Run a transport from the project start: the plugin sees kPlaying, tempo and
+    /// project time. Without it only continuous and system time are reported.
+    #[arg(long)]
+    play: bool,
+
+    /// Tempo in BPM for --play.
+    #[arg(long, value_name = "BPM", default_value_t = 120.0, requires = "play")]
+    tempo: f64,
+
+    /// Ramp console parameter edits over this many milliseconds so plugins that do
+    /// not smooth them do not click; 0 applies them at once. Stepped parameters
+    /// always snap.
+    #[arg(long, value_name = "MS", default_value_t = 20.0)]
+    smoothing_ms: f64,
+
+    /// Print callback timing, DSP load, overruns and output peaks once per second.
+    #[arg(long)]
+    stats: bool,
+
+    /// Like --stats, but one JSON object per line.
+    #[arg(long)]
+    stats_json: bool,
+
+    /// Stop after this long (e.g. 600s) instead of waiting for Enter, and print a
+    /// summary; the end of stdin no longer stops the run.
+    #[arg(long, value_name = "DURATION", value_parser = soak::parse_duration)]
+    duration: Option<std::time::Duration>,
+
+    /// Stop with exit code 3 as soon as a process call fails or non-finite output is
+    /// found in N sampled blocks (default 1), and print a summary.
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "1")]
+    exit_on_error: Option<u64>,
+
+    /// On Enter, the end of stdin or --duration, silence the input and keep playing
+    /// until the plugin's tail dies away, for no longer than the tail it reports (at
+    /// most 30 s). Ctrl-C still stops at once.
+    #[arg(long)]
+    silence_stop: bool,
+
+    /// Level at or below which --silence-stop counts the output as silent.
+    #[arg(long, value_name = "DBFS", default_value_t = -90.0, allow_hyphen_values = true, requires = "silence_stop")]
+    silence_threshold: f64,
+
+    /// Play the plugin from this MIDI input port (its full name or a unique part of it).
+    #[cfg(feature = "midi")]
+    #[arg(long, value_name = "NAME")]
+    midi_port: Option<String>,
 
-    /// Optional comma-separated output arrangement u64 IDs for setBusArrangements.
-    #[arg(long, value_delimiter = ',')]
-    out_arrs: Option<Vec<String>>,
+    /// Send the plugin's output events (an arpeggiator's notes, a MIDI effect's
+    /// controllers) to this MIDI output port (its full name or a unique part of it).
+    #[cfg(feature = "midi")]
+    #[arg(long, value_name = "NAME")]
+    midi_out: Option<String>,
+
+    /// List MIDI input ports and exit.
+    #[cfg(feature = "midi")]
+    #[arg(long)]
+    list_midi_ports: bool,
+
+    /// Open the plugin's editor in a window; closing it stops, like an empty line.
+    #[cfg(feature = "editor")]
+    #[arg(long)]
+    editor: bool,
+
+    /// Reload the plugin without stopping audio whenever its binary changes on disk;
+    /// the `reload` command does the same by hand.
+    #[arg(long)]
+    #[cfg_attr(feature = "editor", arg(conflicts_with = "editor"))]
+    watch: bool,
 }
 
 struct ProcessorRuntime {
     ptr: *mut IAudioProcessor,
     initialized: bool,
     processing: bool,
+    /// What the audio callback calls; [`reload`](Self::reload) repoints it.
+    live: Arc<host::LiveProcessor>,
 }
 
 impl ProcessorRuntime {
@@ -85,6 +210,7 @@ impl ProcessorRuntime {
             ptr,
             initialized: false,
             processing: false,
+            live: Arc::new(host::LiveProcessor::new(ptr)),
         }
     }
 
@@ -92,12 +218,16 @@ impl ProcessorRuntime {
         self.ptr
     }
 
+    fn live(&self) -> Arc<host::LiveProcessor> {
+        self.live.clone()
+    }
+
     unsafe fn initialize(&mut self) -> Result<(), host::HostError> {
         if self.initialized {
             return Ok(());
         }
         let tr = (*self.ptr).initialize(core::ptr::null_mut());
-        if tr != openvst3_abi::K_RESULT_OK {
+        if tr != K_RESULT_OK {
             return Err(host::HostError::TErr(tr));
         }
         self.initialized = true;
@@ -106,7 +236,7 @@ impl ProcessorRuntime {
 
     unsafe fn setup_processing(&mut self, setup: &ProcessSetup) -> Result<(), host::HostError> {
         let tr = (*self.ptr).setup_processing(setup);
-        if tr != openvst3_abi::K_RESULT_OK {
+        if tr != K_RESULT_OK {
             return Err(host::HostError::TErr(tr));
         }
         Ok(())
@@ -114,7 +244,8 @@ impl ProcessorRuntime {
 
     unsafe fn set_processing(&mut self, active: bool) -> Result<(), host::HostError> {
         let tr = (*self.ptr).set_processing(if active { 1 } else { 0 });
-        if tr != openvst3_abi::K_RESULT_OK {
+        // Optional call: plugins may answer kNotImplemented or kResultFalse.
+        if !matches!(tr, K_RESULT_OK | K_NOT_IMPLEMENTED | K_RESULT_FALSE) {
             return Err(host::HostError::TErr(tr));
         }
         self.processing = active;
@@ -124,13 +255,112 @@ impl ProcessorRuntime {
     unsafe fn terminate(&mut self) -> Result<(), host::HostError> {
         if self.initialized {
             let tr = (*self.ptr).terminate();
-            if tr != openvst3_abi::K_RESULT_OK {
+            if tr != K_RESULT_OK {
                 return Err(host::HostError::TErr(tr));
             }
             self.initialized = false;
         }
         Ok(())
     }
+
+    /// Replace the instance with a new one of `spec.cid` from `module` while a stream
+    /// may be calling it, as [`host::PluginInstance::reload`] does: the new instance
+    /// gets the [`host::ReloadState`], arrangements and setup first, the callback is
+    /// muted only for the pointer swap, and the old instance is torn down afterwards.
+    /// On error the old instance is left running.
+    unsafe fn reload(
+        &mut self,
+        module: &mut host::Module,
+        spec: &ReloadSpec,
+    ) -> Result<(), host::HostError> {
+        let state = host::ReloadState::capture(
+            component_of(self.ptr).as_ref(),
+            control::controller_of(self.ptr.cast()).as_ref(),
+            host::HostLimits::default().max_state_bytes,
+        )?;
+        let probe = create_probed(module, spec.cid, spec.iid)?;
+        let created = probe.require_processor()?.interface().clone().into_raw();
+        drop(probe);
+        let mut fresh = ProcessorRuntime::new(created);
+        fresh.initialize()?;
+        state.restore(
+            component_of(fresh.ptr).as_ref(),
+            control::controller_of(created.cast()).as_ref(),
+        )?;
+        if spec.in_arrs.is_some() || spec.out_arrs.is_some() {
+            let ins = spec.in_arrs.as_deref().unwrap_or(&[]);
+            let outs = spec.out_arrs.as_deref().unwrap_or(&[]);
+            set_arrangements(fresh.ptr, ins, outs)?;
+        }
+        fresh.setup_processing(&spec.setup)?;
+        if self.processing {
+            fresh.set_processing(true)?;
+        }
+
+        self.live.replace(fresh.ptr);
+        core::mem::swap(&mut self.ptr, &mut fresh.ptr);
+        core::mem::swap(&mut self.initialized, &mut fresh.initialized);
+        core::mem::swap(&mut self.processing, &mut fresh.processing);
+        // `fresh` now holds the old instance.
+        drop(fresh);
+        Ok(())
+    }
+}
+
+/// What [`ProcessorRuntime::reload`] repeats on the new instance.
+struct ReloadSpec {
+    cid: [u8; 16],
+    iid: [u8; 16],
+    in_arrs: Option<Vec<u64>>,
+    out_arrs: Option<Vec<u64>>,
+    setup: ProcessSetup,
+}
+
+/// Create class `cid` with `iid` and see which interfaces the instance really answers,
+/// rather than trusting it to be what `iid` names.
+unsafe fn create_probed(
+    module: &mut host::Module,
+    cid: [u8; 16],
+    iid: [u8; 16],
+) -> Result<host::ProbeResult, host::HostError> {
+    let raw = host::create_instance_raw(module.factory_mut(), cid, iid)?;
+    Ok(host::ProbeResult::of(
+        host::InterfacePtr::from_raw_unchecked(raw as *mut FUnknown),
+        Tuid(iid),
+    ))
+}
+
+/// The instance's `IComponent`, if the processor object implements it.
+unsafe fn component_of(ptr: *mut IAudioProcessor) -> Option<host::ComponentHandle> {
+    host::query_interface(ptr.cast(), iids::ICOMPONENT.0)
+        .ok()
+        .and_then(|raw| host::InterfacePtr::from_raw(raw as *mut IComponent))
+        .map(host::ComponentHandle::new)
+}
+
+unsafe fn processor_of(ptr: *mut IAudioProcessor) -> Option<host::ProcessorHandle> {
+    host::query_interface(ptr.cast(), iids::IAUDIO_PROCESSOR.0)
+        .ok()
+        .and_then(|raw| host::InterfacePtr::from_raw(raw as *mut IAudioProcessor))
+        .map(host::ProcessorHandle::new)
+}
+
+/// `setBusArrangements`, printing what the plugin has instead when it refuses.
+unsafe fn set_arrangements(
+    ptr: *mut IAudioProcessor,
+    ins: &[u64],
+    outs: &[u64],
+) -> Result<(), host::HostError> {
+    let result = host::set_bus_arrangements(ptr, ins, outs);
+    if let (Err(host::HostError::ArrangementRejected { .. }), Some(processor)) =
+        (&result, processor_of(ptr))
+    {
+        eprintln!(
+            "arrangements refused:\n{}",
+            host::explain_arrangement_mismatch(&processor, ins, outs)
+        );
+    }
+    result
 }
 
 impl Drop for ProcessorRuntime {
@@ -147,176 +377,622 @@ impl Drop for ProcessorRuntime {
                 let _ = (*self.ptr).terminate();
                 self.initialized = false;
             }
-            let base = self.ptr as *mut openvst3_abi::FUnknown;
+            let base = self.ptr as *mut FUnknown;
             if !base.is_null() {
-                let _ = (*base).release();
+                let _ = FUnknown::release(base);
             }
         }
     }
 }
 
+/// Audio flowing into the processor. It borrows the runtime, so `terminate` cannot be
+/// reached while a stream may still call into the plugin; dropping it stops the
+/// streams first and processing second.
+struct Playing<'a> {
+    output: Option<cpal::Stream>,
+    input: Option<input::LiveInput>,
+    runtime: &'a mut ProcessorRuntime,
+}
+
+impl ProcessorRuntime {
+    /// Turn processing on and start `input` (if any), then `output`.
+    unsafe fn start(
+        &mut self,
+        output: cpal::Stream,
+        input: Option<input::LiveInput>,
+    ) -> Result<Playing<'_>, Box<dyn std::error::Error>> {
+        self.set_processing(true)?;
+        let playing = Playing {
+            output: Some(output),
+            input,
+            runtime: self,
+        };
+        if let Some(live) = &playing.input {
+            live.stream.play()?;
+        }
+        if let Some(stream) = &playing.output {
+            stream.play()?;
+        }
+        Ok(playing)
+    }
+}
+
+impl Playing<'_> {
+    /// [`ProcessorRuntime::reload`] under the running streams.
+    unsafe fn reload(
+        &mut self,
+        module: &mut host::Module,
+        spec: &ReloadSpec,
+    ) -> Result<(), host::HostError> {
+        self.runtime.reload(module, spec)
+    }
+
+    fn ptr(&self) -> *mut IAudioProcessor {
+        self.runtime.ptr()
+    }
+
+    /// Feed the plugin silence instead of the input device, if there is one.
+    fn silence_input(&self) {
+        if let Some(live) = &self.input {
+            live.silence();
+        }
+    }
+}
+
+impl Drop for Playing<'_> {
+    fn drop(&mut self) {
+        // Dropping a cpal stream waits for a running callback, so after these two
+        // lines nothing calls process any more.
+        drop(self.output.take());
+        drop(self.input.take());
+        if let Err(e) = unsafe { self.runtime.set_processing(false) } {
+            eprintln!("set_processing(false) error: {e}");
+        }
+    }
+}
+
+/// Device formats in order of preference when --float64 is not given: float first,
+/// then integer formats converted from 32-bit float processing, widest first.
+const OUTPUT_FORMATS: [cpal::SampleFormat; 5] = [
+    cpal::SampleFormat::F32,
+    cpal::SampleFormat::I32,
+    cpal::SampleFormat::I16,
+    cpal::SampleFormat::U16,
+    cpal::SampleFormat::F64,
+];
+
+/// Write planar channel buffers into the interleaved device buffer, converting each
+/// sample on the way.
+fn interleave<T: Copy, S>(
+    planar: &[impl AsRef<[T]>],
+    buffer: &mut [S],
+    mut convert: impl FnMut(T) -> S,
+) {
+    let channels = planar.len();
+    for (frame, out) in buffer.chunks_exact_mut(channels).enumerate() {
+        for (sample, chan) in out.iter_mut().zip(planar) {
+            *sample = convert(chan.as_ref()[frame]);
+        }
+    }
+}
+
+fn format_arrangements(arrs: &[u64]) -> String {
+    let names: Vec<String> = arrs.iter().map(|&a| host::format_arrangement(a)).collect();
+    names.join(", ")
+}
+
+/// The plugin's main bus widths and, when its output layout is not the device's,
+/// the map from one to the other.
+struct Layout {
+    inputs: usize,
+    outputs: usize,
+    out_map: Option<host::ChannelMap>,
+}
+
+impl Layout {
+    /// `out_arr` is the plugin's main output arrangement; the input bus takes
+    /// `in_arr` if given, else as many channels as the output.
+    fn new(in_arr: Option<u64>, out_arr: u64, device_channels: usize) -> Self {
+        let outputs = speaker_arr::channel_count(out_arr);
+        let device = host::render::arrangement_for_channels(device_channels);
+        Self {
+            inputs: in_arr.map_or(outputs, speaker_arr::channel_count),
+            outputs,
+            out_map: (out_arr != device)
+                .then(|| host::ChannelMap::from_arrangements(out_arr, device)),
+        }
+    }
+}
+
+/// The plugin's main output arrangement: the one asked for with `--out-arrs`, else a
+/// plain layout of the width its component reports (if within the host limits), else
+/// the device's.
+unsafe fn output_arrangement(
+    ptr: *mut IAudioProcessor,
+    out_arrs: Option<&[u64]>,
+    device_channels: usize,
+) -> u64 {
+    if let Some(&arr) = out_arrs.and_then(|arrs| arrs.first()) {
+        return arr;
+    }
+    let width = component_of(ptr)
+        .and_then(|c| c.bus_info(MEDIA_TYPE_AUDIO, BUS_DIR_OUTPUT, 0).ok())
+        .filter(|info| {
+            let bus = host::BusId {
+                media_type: MEDIA_TYPE_AUDIO,
+                direction: BUS_DIR_OUTPUT,
+                index: 0,
+            };
+            host::HostLimits::default()
+                .check_channels(bus, info.channel_count)
+                .is_ok()
+        })
+        .map_or(0, |info| info.channel_count as usize);
+    host::render::arrangement_for_channels(if width > 0 { width } else { device_channels })
+}
+
+/// Output channels mapped to the device's layout, with their buffers.
+struct OutputMap<T> {
+    map: host::ChannelMap,
+    planar: Box<[Box<[T]>]>,
+}
+
+impl<T: host::BusSample> OutputMap<T> {
+    fn new(map: host::ChannelMap, max_frames: usize) -> Self {
+        let planar = (0..map.dst_channels())
+            .map(|_| vec![T::default(); max_frames].into_boxed_slice())
+            .collect();
+        Self { map, planar }
+    }
+}
+
+/// The block's output on the device's channels.
+fn device_output<'a, T: host::BusSample + host::limiter::Sample>(
+    bound: &'a host::BoundProcessData<T>,
+    out_map: &'a mut Option<OutputMap<T>>,
+    frames: usize,
+) -> &'a [Box<[T]>] {
+    match out_map {
+        Some(out) => {
+            out.map.apply(bound.outputs(), &mut out.planar, frames);
+            &out.planar
+        }
+        None => bound.outputs(),
+    }
+}
+
+/// Read this block's input into the plugin's input channels, if there is an input.
+fn read_input<T: host::BusSample + From<f32>>(
+    input: &mut Option<input::InputFeed>,
+    bound: &mut host::BoundProcessData<T>,
+    frames: usize,
+) {
+    if let Some(feed) = input {
+        feed.read(bound, frames);
+    }
+}
+
+/// A block while a reload swaps the processor: the output is silent, the input is
+/// read so it stays in step, and queued edits wait for the next block.
+fn muted_block<T: host::BusSample + From<f32>>(
+    input: &mut Option<input::InputFeed>,
+    bound: &mut host::BoundProcessData<T>,
+    frames: usize,
+) {
+    read_input(input, bound, frames);
+    for chan in bound.outputs_mut() {
+        chan[..frames].fill(T::from(0.0));
+    }
+}
+
+/// Events and parameter points handed to the plugin per block; the rest of a burst
+/// is dropped and counted in the pool.
+const EVENTS_PER_BLOCK: usize = 256;
+const PARAMS_PER_BLOCK: usize = 32;
+
+/// Where each block's `ProcessContext` comes from: the sample clock alone, or a
+/// transport running on it with --play.
+enum Timeline {
+    Stopped(host::SampleClock),
+    Playing(host::Transport),
+}
+
+impl Timeline {
+    /// Start a transport at `tempo` on the clock counted so far.
+    fn play(&mut self, tempo: f64) {
+        if let Timeline::Stopped(clock) = self {
+            let mut transport = host::Transport::with_clock(clock.clone()).tempo(tempo);
+            transport.play();
+            *self = Timeline::Playing(transport);
+        }
+    }
+
+    fn next_block(&mut self, frames: usize) -> ProcessContext {
+        match self {
+            Timeline::Stopped(clock) => clock.next_block(frames),
+            Timeline::Playing(transport) => transport.next_block(frames),
+        }
+    }
+}
+
+/// The one set of block lists a callback state needs: it is recycled after every
+/// block, before the next callback takes it again.
+fn block_io_pool() -> host::BlockIoPool {
+    host::BlockIoPool::new(1, EVENTS_PER_BLOCK, PARAMS_PER_BLOCK, EVENTS_PER_BLOCK)
+}
+
+// The callback state is moved into the audio thread once and only touched there.
+unsafe impl Send for CallbackState32 {}
+
 struct CallbackState32 {
-    proc_ptr: *mut IAudioProcessor,
+    live: Arc<host::LiveProcessor>,
+    /// Device channels.
     channels: usize,
-    max_frames: usize,
-    channel_data: Vec<Vec<f32>>,
-    channel_ptrs: Vec<*mut f32>,
-    outs_bus: openvst3_abi::AudioBusBuffers32,
+    /// The plugin's main output bus, plus its main input bus with an input.
+    bound: host::BoundProcessData<f32>,
+    /// Counts every block, muted ones included, so continuous time never stalls.
+    timeline: Timeline,
+    out_map: Option<OutputMap<f32>>,
+    input: Option<input::InputFeed>,
+    control: control::ControlFeed,
+    io: host::BlockIoPool,
+    /// Synced with every block's context for --record-automation.
+    automation: Option<Arc<host::AutomationRecorder>>,
+    #[cfg(feature = "midi")]
+    midi: Option<midi::MidiFeed>,
+    #[cfg(feature = "midi")]
+    midi_out: Option<midi::MidiOutFeed>,
 }
 
 impl CallbackState32 {
-    unsafe fn new(proc_ptr: *mut IAudioProcessor, channels: usize, max_frames: usize) -> Self {
-        let mut channel_data = Vec::with_capacity(channels);
-        for _ in 0..channels {
-            channel_data.push(vec![0.0f32; max_frames]);
-        }
-        let mut channel_ptrs = channel_data
-            .iter_mut()
-            .map(|c| c.as_mut_ptr())
-            .collect::<Vec<_>>();
-        let outs_bus = openvst3_abi::AudioBusBuffers32 {
-            num_channels: channels as i32,
-            silence_flags: 0,
-            channel_buffers: channel_ptrs.as_mut_ptr(),
-        };
+    fn new(
+        live: Arc<host::LiveProcessor>,
+        layout: &Layout,
+        channels: usize,
+        setup: &ProcessSetup,
+        input: Option<input::InputFeed>,
+        control: control::ControlFeed,
+        io: host::BlockIoPool,
+    ) -> Self {
+        let in_channels = if input.is_some() { layout.inputs } else { 0 };
+        let max_frames = setup.max_samples_per_block as usize;
+        let mut bound = host::BoundProcessData::new(in_channels, layout.outputs, max_frames);
+        bound.set_input_gate(input.is_some().then(host::InputGate::new));
         Self {
-            proc_ptr,
+            live,
             channels,
-            max_frames,
-            channel_data,
-            channel_ptrs,
-            outs_bus,
+            bound,
+            timeline: Timeline::Stopped(host::SampleClock::new(setup.sample_rate)),
+            out_map: layout
+                .out_map
+                .clone()
+                .map(|map| OutputMap::new(map, max_frames)),
+            input,
+            control,
+            io,
+            automation: None,
+            #[cfg(feature = "midi")]
+            midi: None,
+            #[cfg(feature = "midi")]
+            midi_out: None,
         }
     }
 
-    unsafe fn process(&mut self, buffer: &mut [f32]) -> Result<(), host::HostError> {
+    /// Note when this callback runs, for placing MIDI input in its block.
+    #[cfg_attr(not(feature = "midi"), allow(unused_variables))]
+    fn sync(&mut self, info: &cpal::OutputCallbackInfo, samples: usize) {
+        #[cfg(feature = "midi")]
+        if let Some(feed) = &mut self.midi {
+            feed.sync(info, samples / self.channels);
+        }
+    }
+
+    /// Process one block into the interleaved `buffer`, converting each output sample
+    /// with `convert`.
+    unsafe fn process<S>(
+        &mut self,
+        buffer: &mut [S],
+        convert: impl FnMut(f32) -> S,
+    ) -> Result<(), host::HostError> {
         let frames = buffer.len() / self.channels;
-        if frames > self.max_frames {
-            return Err(host::HostError::InvalidBundle(format!(
-                "callback frames ({frames}) exceed max block ({})",
-                self.max_frames
-            )));
-        }
-        for (idx, chan) in self.channel_data.iter_mut().enumerate() {
-            self.channel_ptrs[idx] = chan.as_mut_ptr();
-        }
-        self.outs_bus.channel_buffers = self.channel_ptrs.as_mut_ptr();
-        self.outs_bus.num_channels = self.channels as i32;
-        self.outs_bus.silence_flags = 0;
-
-        let mut data = openvst3_abi::ProcessData32 {
-            num_inputs: 0,
-            num_outputs: 1,
-            inputs: core::ptr::null_mut(),
-            outputs: &mut self.outs_bus,
-            num_samples: frames as i32,
-            input_parameter_changes: core::ptr::null_mut(),
-            output_parameter_changes: core::ptr::null_mut(),
-            input_events: core::ptr::null_mut(),
-            output_events: core::ptr::null_mut(),
+        if frames > self.bound.max_frames() {
+            return Err(host::HostError::BlockSizeExceeded {
+                got: frames,
+                max: self.bound.max_frames(),
+            });
+        }
+        let context = self.timeline.next_block(frames);
+        if let Some(recorder) = &self.automation {
+            recorder.sync(&context);
+        }
+        self.bound.set_context(Some(context));
+        let Some(guard) = self.live.enter() else {
+            muted_block(&mut self.input, &mut self.bound, frames);
+            let planar = device_output(&self.bound, &mut self.out_map, frames);
+            interleave(planar, buffer, convert);
+            return Ok(());
         };
-
-        let proc = &mut *self.proc_ptr;
-        let tr = proc.process_32f(&mut data);
-        if tr != openvst3_abi::K_RESULT_OK {
+        read_input(&mut self.input, &mut self.bound, frames);
+        let tr = self.io.with_block(|io| {
+            let (events, params) = io.lists();
+            self.control.drain(frames, events, params);
+            #[cfg(feature = "midi")]
+            if let Some(feed) = &mut self.midi {
+                feed.fill(frames, events, params);
+            }
+            let data = self.bound.prepare(frames, Some((events, params)))?;
+            Ok::<_, host::HostError>((*guard.as_ptr()).process_32f(data))
+        });
+        drop(guard);
+        // The one set is always back in the pool by now, so `None` does not happen.
+        let tr = tr.unwrap_or(Ok(K_RESULT_FALSE))?;
+        // kResultFalse: nothing to do this block, not an error.
+        if !matches!(tr, K_RESULT_OK | K_RESULT_FALSE) {
             return Err(host::HostError::TErr(tr));
         }
 
-        for frame in 0..frames {
-            for ch in 0..self.channels {
-                buffer[frame * self.channels + ch] = self.channel_data[ch][frame];
-            }
+        #[cfg(feature = "midi")]
+        if let (Some(out), Some(events)) = (&mut self.midi_out, self.bound.output_events()) {
+            out.forward(events);
         }
+
+        let planar = device_output(&self.bound, &mut self.out_map, frames);
+        interleave(planar, buffer, convert);
         Ok(())
     }
 }
 
+unsafe impl Send for CallbackState64 {}
+
 struct CallbackState64 {
-    proc_ptr: *mut IAudioProcessor,
+    live: Arc<host::LiveProcessor>,
+    /// Device channels.
     channels: usize,
-    max_frames: usize,
-    channel_data: Vec<Vec<f64>>,
-    channel_ptrs: Vec<*mut f64>,
-    outs_bus: openvst3_abi::AudioBusBuffers64,
+    /// The plugin's main output bus, plus its main input bus with an input.
+    bound: host::BoundProcessData<f64>,
+    /// Counts every block, muted ones included, so continuous time never stalls.
+    timeline: Timeline,
+    out_map: Option<OutputMap<f64>>,
+    input: Option<input::InputFeed>,
+    control: control::ControlFeed,
+    io: host::BlockIoPool,
+    /// Synced with every block's context for --record-automation.
+    automation: Option<Arc<host::AutomationRecorder>>,
+    #[cfg(feature = "midi")]
+    midi: Option<midi::MidiFeed>,
+    #[cfg(feature = "midi")]
+    midi_out: Option<midi::MidiOutFeed>,
 }
 
 impl CallbackState64 {
-    unsafe fn new(proc_ptr: *mut IAudioProcessor, channels: usize, max_frames: usize) -> Self {
-        let mut channel_data = Vec::with_capacity(channels);
-        for _ in 0..channels {
-            channel_data.push(vec![0.0f64; max_frames]);
-        }
-        let mut channel_ptrs = channel_data
-            .iter_mut()
-            .map(|c| c.as_mut_ptr())
-            .collect::<Vec<_>>();
-        let outs_bus = openvst3_abi::AudioBusBuffers64 {
-            num_channels: channels as i32,
-            silence_flags: 0,
-            channel_buffers: channel_ptrs.as_mut_ptr(),
-        };
+    fn new(
+        live: Arc<host::LiveProcessor>,
+        layout: &Layout,
+        channels: usize,
+        setup: &ProcessSetup,
+        input: Option<input::InputFeed>,
+        control: control::ControlFeed,
+        io: host::BlockIoPool,
+    ) -> Self {
+        let in_channels = if input.is_some() { layout.inputs } else { 0 };
+        let max_frames = setup.max_samples_per_block as usize;
+        let mut bound = host::BoundProcessData::new(in_channels, layout.outputs, max_frames);
+        bound.set_input_gate(input.is_some().then(host::InputGate::new));
         Self {
-            proc_ptr,
+            live,
             channels,
-            max_frames,
-            channel_data,
-            channel_ptrs,
-            outs_bus,
+            bound,
+            timeline: Timeline::Stopped(host::SampleClock::new(setup.sample_rate)),
+            out_map: layout
+                .out_map
+                .clone()
+                .map(|map| OutputMap::new(map, max_frames)),
+            input,
+            control,
+            io,
+            automation: None,
+            #[cfg(feature = "midi")]
+            midi: None,
+            #[cfg(feature = "midi")]
+            midi_out: None,
+        }
+    }
+
+    /// Note when this callback runs, for placing MIDI input in its block.
+    #[cfg_attr(not(feature = "midi"), allow(unused_variables))]
+    fn sync(&mut self, info: &cpal::OutputCallbackInfo, samples: usize) {
+        #[cfg(feature = "midi")]
+        if let Some(feed) = &mut self.midi {
+            feed.sync(info, samples / self.channels);
         }
     }
 
     unsafe fn process(&mut self, buffer: &mut [f64]) -> Result<(), host::HostError> {
         let frames = buffer.len() / self.channels;
-        if frames > self.max_frames {
-            return Err(host::HostError::InvalidBundle(format!(
-                "callback frames ({frames}) exceed max block ({})",
-                self.max_frames
-            )));
-        }
-        for (idx, chan) in self.channel_data.iter_mut().enumerate() {
-            self.channel_ptrs[idx] = chan.as_mut_ptr();
-        }
-        self.outs_bus.channel_buffers = self.channel_ptrs.as_mut_ptr();
-        self.outs_bus.num_channels = self.channels as i32;
-        self.outs_bus.silence_flags = 0;
-
-        let mut data = openvst3_abi::ProcessData64 {
-            num_inputs: 0,
-            num_outputs: 1,
-            inputs: core::ptr::null_mut(),
-            outputs: &mut self.outs_bus,
-            num_samples: frames as i32,
-            input_parameter_changes: core::ptr::null_mut(),
-            output_parameter_changes: core::ptr::null_mut(),
-            input_events: core::ptr::null_mut(),
-            output_events: core::ptr::null_mut(),
+        if frames > self.bound.max_frames() {
+            return Err(host::HostError::BlockSizeExceeded {
+                got: frames,
+                max: self.bound.max_frames(),
+            });
+        }
+        let context = self.timeline.next_block(frames);
+        if let Some(recorder) = &self.automation {
+            recorder.sync(&context);
+        }
+        self.bound.set_context(Some(context));
+        let Some(guard) = self.live.enter() else {
+            muted_block(&mut self.input, &mut self.bound, frames);
+            let planar = device_output(&self.bound, &mut self.out_map, frames);
+            interleave(planar, buffer, |x| x);
+            return Ok(());
         };
-
-        let proc = &mut *self.proc_ptr;
-        let tr = proc.process_64f(&mut data);
-        if tr != openvst3_abi::K_RESULT_OK {
+        read_input(&mut self.input, &mut self.bound, frames);
+        let tr = self.io.with_block(|io| {
+            let (events, params) = io.lists();
+            self.control.drain(frames, events, params);
+            #[cfg(feature = "midi")]
+            if let Some(feed) = &mut self.midi {
+                feed.fill(frames, events, params);
+            }
+            let data = self.bound.prepare(frames, Some((events, params)))?;
+            Ok::<_, host::HostError>((*guard.as_ptr()).process_64f(data))
+        });
+        drop(guard);
+        // The one set is always back in the pool by now, so `None` does not happen.
+        let tr = tr.unwrap_or(Ok(K_RESULT_FALSE))?;
+        // kResultFalse: nothing to do this block, not an error.
+        if !matches!(tr, K_RESULT_OK | K_RESULT_FALSE) {
             return Err(host::HostError::TErr(tr));
         }
 
-        for frame in 0..frames {
-            for ch in 0..self.channels {
-                buffer[frame * self.channels + ch] = self.channel_data[ch][frame];
-            }
+        #[cfg(feature = "midi")]
+        if let (Some(out), Some(events)) = (&mut self.midi_out, self.bound.output_events()) {
+            out.forward(events);
         }
+
+        let planar = device_output(&self.bound, &mut self.out_map, frames);
+        interleave(planar, buffer, |x| x);
         Ok(())
     }
 }
 
+/// Stages that see the plugin's interleaved output, in order.
+struct Post {
+    limiter: Option<host::SafetyLimiter>,
+    /// Channel + 1 of a sustained DC offset the limiter saw, 0 for none.
+    dc_channel: Arc<AtomicUsize>,
+    tap: Option<record::RecordTap>,
+    probe: Option<stats::Probe>,
+    watch: Option<soak::Watch>,
+    listen: Option<drain::Listen>,
+}
+
+impl Post {
+    fn is_empty(&self) -> bool {
+        self.limiter.is_none()
+            && self.tap.is_none()
+            && self.probe.is_none()
+            && self.watch.is_none()
+            && self.listen.is_none()
+    }
+
+    /// Read the clock before the plugin runs, when `--stats` times the callback.
+    fn start(&self) -> Option<u64> {
+        self.probe.as_ref().map(stats::Probe::start)
+    }
+
+    /// Run the stages on a block the plugin wrote; `processed` says whether its
+    /// process call succeeded and `started` is what [`start`](Self::start) returned.
+    fn run<T: host::limiter::Sample + Into<f64>>(
+        &mut self,
+        started: Option<u64>,
+        processed: bool,
+        data: &mut [T],
+    ) {
+        // Before the limiter, which silences non-finite samples.
+        if let Some(watch) = &mut self.watch {
+            watch.record(processed, data);
+        }
+        if let Some(listen) = &mut self.listen {
+            listen.record(data);
+        }
+        if let Some(limiter) = &mut self.limiter {
+            limiter.process(data);
+            if let Some(channel) = limiter.dc_offset() {
+                self.dc_channel.store(channel + 1, Ordering::Relaxed);
+            }
+        }
+        if let Some(tap) = &mut self.tap {
+            tap.write(data);
+        }
+        if let (Some(probe), Some(started)) = (&mut self.probe, started) {
+            probe.record(started, data);
+        }
+    }
+}
+
+/// Output stream for an integer device format. The plugin still processes 32-bit
+/// float; without post stages the samples are converted while interleaving, otherwise
+/// the stages run on a float block that is converted afterwards.
+fn converted_stream<S, E>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut state: CallbackState32,
+    mut post: Post,
+    mut converter: convert::Converter,
+    stop: Arc<AtomicBool>,
+    err_fn: E,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    S: cpal::SizedSample + cpal::FromSample<f32>,
+    E: FnMut(cpal::StreamError) + Send + 'static,
+{
+    let mut scratch = vec![0.0f32; state.bound.max_frames() * state.channels];
+    device.build_output_stream(
+        config,
+        move |data: &mut [S], info| {
+            if stop.load(Ordering::Acquire) {
+                data.fill(S::EQUILIBRIUM);
+                return;
+            }
+            state.sync(info, data.len());
+            let started = post.start();
+            // Blocks too long for the scratch buffer fail in `process` either way.
+            let result = match scratch.get_mut(..data.len()) {
+                Some(block) if !post.is_empty() => {
+                    let result = unsafe { state.process(block, |x| x) };
+                    post.run(started, result.is_ok(), block);
+                    converter.block(block, data);
+                    result
+                }
+                _ => unsafe { state.process(data, |x| converter.sample(x)) },
+            };
+            if let Err(e) = result {
+                eprintln!("process32 error: {e}");
+            }
+        },
+        err_fn,
+        None,
+    )
+}
+
+/// 2 for host-side errors (bad arguments, unknown class), 3 when the plugin failed,
+/// 1 for anything else (audio device, I/O).
+fn exit_code(err: &(dyn std::error::Error + 'static)) -> i32 {
+    if err.is::<soak::Failed>() {
+        return 3;
+    }
+    match err.downcast_ref::<host::HostError>() {
+        Some(e) if e.is_plugin_fault() => 3,
+        Some(_) => 2,
+        None => 1,
+    }
+}
+
 fn main() {
     if let Err(err) = run() {
         eprintln!("error: {err}");
-        std::process::exit(1);
+        std::process::exit(exit_code(err.as_ref()));
     }
 }
 
 fn run() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
 
+    if args.list_devices {
+        devices::print_devices();
+        return Ok(());
+    }
+    #[cfg(feature = "midi")]
+    if args.list_midi_ports {
+        for (index, name) in midi::port_names()?.iter().enumerate() {
+            println!("{index}: {name}");
+        }
+        return Ok(());
+    }
+    let class = args.class.expect("clap requires --class");
+    let iid = args.iid.as_deref().expect("clap requires --iid");
+
     let bin = if let Some(p) = args.plugin {
         p
     } else if let Some(b) = args.bundle {
@@ -327,60 +1003,71 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut module =
         host::Module::load(&bin).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
-    let (_, _, cid) = host::read_class_info_v1(&mut module, args.class)
+    #[cfg_attr(not(feature = "editor"), allow(unused_variables))]
+    let (class_name, _, cid) = host::read_class_info_v1(&mut module, class)
         .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
-    let iid_bytes =
-        load_hex_iid(&args.iid).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+    let iid_bytes = load_hex_iid(iid).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
 
-    let created = unsafe {
-        host::create_instance_raw(module.factory_mut(), cid, iid_bytes)
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?
+    let probe = unsafe { create_probed(&mut module, cid, iid_bytes) }
+        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+    let Some(processor) = &probe.processor else {
+        return Err(format!(
+            "the instance does not implement IAudioProcessor; it supports {}",
+            probe.supported_names().join(", ")
+        )
+        .into());
     };
-    if created.is_null() {
-        return Err("createInstance returned null".into());
-    }
-    let proc_ptr = created as *mut IAudioProcessor;
-    if proc_ptr.is_null() {
-        return Err("instance did not implement IAudioProcessor".into());
-    }
+    // The runtime owns its own reference; the rest of the probe goes now.
+    let proc_ptr = processor.interface().clone().into_raw();
+    drop(probe);
+    let created = proc_ptr as *mut core::ffi::c_void;
 
     if let Some(hex) = args.component_iid.as_deref() {
         let comp_iid = load_hex_iid(hex).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
         unsafe {
             if let Ok(ptr) = host::query_interface(created, comp_iid) {
-                let outs = host::detect_output_channels(ptr as *mut openvst3_abi::IComponent);
-                println!("component reports {outs} output channels (bus 0)");
+                let outs = host::detect_output_channels(ptr as *mut IComponent);
+                match outs.reported {
+                    Some(n) if !outs.is_fallback() => {
+                        println!("component reports {n} output channels (bus 0)")
+                    }
+                    Some(n) => println!(
+                        "component reports {n} output channels (bus 0); treating it as {}",
+                        outs.suggested
+                    ),
+                    None => println!(
+                        "component reports no output bus info ({} buses); assuming {}",
+                        outs.bus_count, outs.suggested
+                    ),
+                }
             }
         }
     }
 
-    let in_arrs = parse_hex64_list(args.in_arrs.as_ref())
-        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
-    let out_arrs = parse_hex64_list(args.out_arrs.as_ref())
-        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+    let (in_arrs, out_arrs) = (args.in_arrs, args.out_arrs);
 
-    let host = cpal::default_host();
-    let device = host
-        .default_output_device()
-        .ok_or_else(|| "no default output device".to_string())?;
-
-    let default_config = device.default_output_config()?;
-    let config_to_use = if args.float64 {
-        if default_config.sample_format() == cpal::SampleFormat::F64 {
-            default_config
-        } else {
-            let mut found = None;
-            for cfg in device.supported_output_configs()? {
-                if cfg.sample_format() == cpal::SampleFormat::F64 {
-                    found = Some(cfg.with_max_sample_rate());
-                    break;
-                }
-            }
-            found.ok_or_else(|| "no f64 output config available".to_string())?
-        }
+    let host = devices::select_host(args.audio_host.as_deref())?;
+    let device = devices::select_device(
+        &host,
+        devices::Direction::Output,
+        args.output_device.as_deref(),
+    )?;
+    let request = devices::ConfigRequest {
+        format: None,
+        channels: args.channels,
+        sample_rate: None,
+    };
+    let formats: &[cpal::SampleFormat] = if args.float64 {
+        &[cpal::SampleFormat::F64]
     } else {
-        default_config
+        &OUTPUT_FORMATS
     };
+    let config_to_use =
+        devices::select_config_preferring(&device, devices::Direction::Output, &request, formats)?;
+    let format = config_to_use.sample_format();
+    if args.dither && !matches!(format, cpal::SampleFormat::I16 | cpal::SampleFormat::U16) {
+        eprintln!("note: --dither only applies to 16-bit devices; this one takes {format}");
+    }
 
     let sample_rate = config_to_use.sample_rate().0 as f64;
     let mut stream_config: cpal::StreamConfig = config_to_use.config();
@@ -390,7 +1077,7 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
     stream_config.buffer_size = cpal::BufferSize::Fixed(args.frames);
     let channels = stream_config.channels as usize;
     println!(
-        "device: {} | sr: {} Hz | channels: {} | frames: {}",
+        "device: {} | sr: {} Hz | channels: {} | format: {format} | frames: {}",
         device.name()?,
         sample_rate,
         channels,
@@ -408,83 +1095,479 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
         let ins = in_arrs.as_deref().unwrap_or(&[]);
         let outs = out_arrs.as_deref().unwrap_or(&[]);
         unsafe {
-            host::set_bus_arrangements(runtime.ptr(), ins, outs)
+            set_arrangements(runtime.ptr(), ins, outs)
                 .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
         }
+        println!(
+            "arrangements: in [{}] | out [{}]",
+            format_arrangements(ins),
+            format_arrangements(outs)
+        );
     }
 
-    let setup = ProcessSetup {
-        process_mode: process_consts::PROCESS_MODE_REALTIME,
-        sample_rate,
-        max_samples_per_block: args.frames as i32,
-        symbolic_sample_size: if matches!(config_to_use.sample_format(), cpal::SampleFormat::F64) {
-            process_consts::SYMBOLIC_SAMPLE_64
-        } else {
-            process_consts::SYMBOLIC_SAMPLE_32
-        },
-        flags: 0,
+    let precision = if format == cpal::SampleFormat::F64 {
+        host::SymbolicSampleSize::Sample64
+    } else {
+        host::SymbolicSampleSize::Sample32
     };
+    let mut setup = host::ProcessSetupBuilder::new()
+        .sample_rate(sample_rate)
+        .max_block(args.frames as i32)
+        .precision(precision);
+    if let Some(processor) = unsafe { processor_of(runtime.ptr()) } {
+        setup = setup.for_processor(&processor);
+    }
+    // The device format fixes the sample type, so a downgrade is no way out.
+    if let Some(warning) = setup.warnings().first() {
+        return Err(format!("{warning}, but the output device takes {format} samples").into());
+    }
+    let setup = setup.build()?;
     unsafe {
         runtime
             .setup_processing(&setup)
             .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
     }
+    let out_arr = unsafe { output_arrangement(runtime.ptr(), out_arrs.as_deref(), channels) };
+    let in_arr = in_arrs.as_ref().and_then(|arrs| arrs.first().copied());
+    let layout = Layout::new(in_arr, out_arr, channels);
+    if layout.out_map.is_some() {
+        println!(
+            "layout: plugin output {} ({} channels) mapped to {channels} device channels",
+            host::format_arrangement(out_arr),
+            layout.outputs
+        );
+    }
+    let reload_spec = ReloadSpec {
+        cid,
+        iid: iid_bytes,
+        in_arrs,
+        out_arrs,
+        setup,
+    };
 
-    let err_fn = |err| eprintln!("stream error: {err}");
+    let (live_input, mut feed) = if args.with_input || args.input_device.is_some() {
+        let latency = (args.input_latency_ms.max(0.0) * sample_rate / 1000.0) as usize;
+        let (live, feed) = input::open(
+            &host,
+            args.input_device.as_deref(),
+            sample_rate as u32,
+            args.frames as usize,
+            latency,
+        )?;
+        println!(
+            "input: {} | channels: {} -> {} plugin inputs | latency: {latency} frames",
+            live.device_name, live.channels, layout.inputs
+        );
+        (Some(live), Some(feed))
+    } else {
+        (None, None)
+    };
 
-    let stream = match config_to_use.sample_format() {
-        cpal::SampleFormat::F32 => {
-            let mut state =
-                unsafe { CallbackState32::new(runtime.ptr(), channels, args.frames as usize) };
-            device.build_output_stream(
-                &stream_config,
-                move |data: &mut [f32], _| {
-                    if let Err(e) = unsafe { state.process(data) } {
-                        eprintln!("process32 error: {e}");
-                    }
-                },
-                err_fn,
-            )?
+    #[cfg(feature = "midi")]
+    let (live_midi, mut midi_feed) = match args.midi_port.as_deref() {
+        Some(name) => {
+            let (live, feed) = unsafe { midi::open(name, created as *mut FUnknown, sample_rate)? };
+            println!(
+                "midi: {} | {} controller assignments",
+                live.port_name, live.mapped
+            );
+            (Some(live), Some(feed))
         }
+        None => (None, None),
+    };
+    #[cfg(feature = "midi")]
+    let (live_midi_out, mut midi_out_feed) = match args.midi_out.as_deref() {
+        Some(name) => {
+            let (live, feed) = midi::open_output(name, sample_rate)?;
+            println!("midi out: {}", live.port_name);
+            (Some(live), Some(feed))
+        }
+        None => (None, None),
+    };
+
+    let limiter = args
+        .limit
+        .map(|db| host::SafetyLimiter::new(db, channels, sample_rate));
+    // Channel + 1 of a sustained DC offset the limiter saw, 0 for none.
+    let dc_channel = Arc::new(AtomicUsize::new(0));
+    if let Some(db) = args.limit {
+        println!("limiter: ceiling {db} dBFS");
+        let dc_channel = dc_channel.clone();
+        std::thread::spawn(move || {
+            let mut warned = 0;
+            loop {
+                std::thread::sleep(std::time::Duration::from_secs(1));
+                let channel = dc_channel.load(Ordering::Relaxed);
+                if channel != 0 && channel != warned {
+                    eprintln!(
+                        "warning: sustained DC offset on output channel {}",
+                        channel - 1
+                    );
+                    warned = channel;
+                }
+            }
+        });
+    }
+    let (recording, tap) = match &args.record {
+        Some(path) => {
+            let (recording, tap) = record::start(path, channels, sample_rate as u32)
+                .map_err(|e| format!("{}: {e}", path.display()))?;
+            println!("recording to {}", path.display());
+            (Some(recording), Some(tap))
+        }
+        None => (None, None),
+    };
+    let (probe, stats_shared) = if args.stats || args.stats_json {
+        let record_dropped = recording.as_ref().map(|r| r.dropped_blocks.clone());
+        let (probe, shared) = stats::start(channels, sample_rate, args.stats_json, record_dropped);
+        (Some(probe), Some(shared))
+    } else {
+        (None, None)
+    };
+    let smoothing = std::time::Duration::from_secs_f64(args.smoothing_ms.max(0.0) / 1000.0);
+    let (mut console, control_feed) = unsafe { control::open(created, sample_rate, smoothing) };
+    let automation = match &args.record_automation {
+        Some(path) => {
+            let automation = automation::Automation::new(path.clone(), sample_rate);
+            automation
+                .attach(console.controller()?)
+                .map_err(|e| format!("--record-automation: {e}"))?;
+            println!("recording automation to {}", path.display());
+            Some(automation)
+        }
+        None => None,
+    };
+    // Created before audio starts so a missing editor or display fails early; dropped
+    // (and so released) before the console's controller.
+    #[cfg(feature = "editor")]
+    let editor = if args.editor {
+        Some(editor::open(console.controller()?.create_editor()?)?)
+    } else {
+        None
+    };
+    // Console lines arrive as Some; Ctrl-C, the end of stdin and the soak watchdog
+    // send None.
+    let (line_tx, line_rx) = mpsc::channel();
+    let (watch, soak) = if args.duration.is_some() || args.exit_on_error.is_some() {
+        let limits = soak::Limits {
+            duration: args.duration,
+            max_non_finite: args.exit_on_error.map(|n| n.max(1)),
+        };
+        let (watch, soak) = soak::start(limits, line_tx.clone());
+        (Some(watch), Some(soak))
+    } else {
+        (None, None)
+    };
+    let health = soak.as_ref().map(|s| s.health().clone());
+    let err_fn = move |err| {
+        if let Some(shared) = &stats_shared {
+            shared.stream_error();
+        }
+        if let Some(health) = &health {
+            health.stream_error();
+        }
+        eprintln!("stream error: {err}");
+    };
+    // Set on Enter or Ctrl-C; the callback plays silence from then on.
+    let stop = Arc::new(AtomicBool::new(false));
+    let (listen, drain) = if args.silence_stop {
+        let (listen, drain) = drain::start(args.silence_threshold, sample_rate);
+        (Some(listen), Some(drain))
+    } else {
+        (None, None)
+    };
+
+    let post = Post {
+        limiter,
+        dc_channel: dc_channel.clone(),
+        tap,
+        probe,
+        watch,
+        listen,
+    };
+    let block_io = block_io_pool();
+    let block_io_counters = block_io.counters();
+    let stream = match format {
         cpal::SampleFormat::F64 => {
-            let mut state =
-                unsafe { CallbackState64::new(runtime.ptr(), channels, args.frames as usize) };
+            let mut state = CallbackState64::new(
+                runtime.live(),
+                &layout,
+                channels,
+                &setup,
+                feed.take(),
+                control_feed,
+                block_io,
+            );
+            if let Some(automation) = &automation {
+                state.timeline = Timeline::Stopped(host::SampleClock::with_clock(
+                    sample_rate,
+                    automation.clock(),
+                ));
+                state.automation = Some(automation.recorder());
+            }
+            if args.play {
+                state.timeline.play(args.tempo);
+            }
+            #[cfg(feature = "midi")]
+            {
+                state.midi = midi_feed.take();
+                state.midi_out = midi_out_feed.take();
+                if state.midi_out.is_some() {
+                    state.bound.set_output_events(EVENTS_PER_BLOCK);
+                }
+            }
+            let stop = stop.clone();
+            let mut post = post;
             device.build_output_stream(
                 &stream_config,
-                move |data: &mut [f64], _| {
-                    if let Err(e) = unsafe { state.process(data) } {
+                move |data: &mut [f64], info| {
+                    if stop.load(Ordering::Acquire) {
+                        data.fill(0.0);
+                        return;
+                    }
+                    state.sync(info, data.len());
+                    let started = post.start();
+                    let result = unsafe { state.process(data) };
+                    if let Err(e) = &result {
                         eprintln!("process64 error: {e}");
                     }
+                    post.run(started, result.is_ok(), data);
                 },
                 err_fn,
+                None,
             )?
         }
+        cpal::SampleFormat::F32
+        | cpal::SampleFormat::I32
+        | cpal::SampleFormat::I16
+        | cpal::SampleFormat::U16 => {
+            let mut state = CallbackState32::new(
+                runtime.live(),
+                &layout,
+                channels,
+                &setup,
+                feed.take(),
+                control_feed,
+                block_io,
+            );
+            if let Some(automation) = &automation {
+                state.timeline = Timeline::Stopped(host::SampleClock::with_clock(
+                    sample_rate,
+                    automation.clock(),
+                ));
+                state.automation = Some(automation.recorder());
+            }
+            if args.play {
+                state.timeline.play(args.tempo);
+            }
+            #[cfg(feature = "midi")]
+            {
+                state.midi = midi_feed.take();
+                state.midi_out = midi_out_feed.take();
+                if state.midi_out.is_some() {
+                    state.bound.set_output_events(EVENTS_PER_BLOCK);
+                }
+            }
+            let stop = stop.clone();
+            let converter = convert::Converter::new(args.dither);
+            match format {
+                cpal::SampleFormat::I32 => converted_stream::<i32, _>(
+                    &device,
+                    &stream_config,
+                    state,
+                    post,
+                    convert::Converter::new(false),
+                    stop,
+                    err_fn,
+                )?,
+                cpal::SampleFormat::I16 => converted_stream::<i16, _>(
+                    &device,
+                    &stream_config,
+                    state,
+                    post,
+                    converter,
+                    stop,
+                    err_fn,
+                )?,
+                cpal::SampleFormat::U16 => converted_stream::<u16, _>(
+                    &device,
+                    &stream_config,
+                    state,
+                    post,
+                    converter,
+                    stop,
+                    err_fn,
+                )?,
+                _ => {
+                    let mut post = post;
+                    device.build_output_stream(
+                        &stream_config,
+                        move |data: &mut [f32], info| {
+                            if stop.load(Ordering::Acquire) {
+                                data.fill(0.0);
+                                return;
+                            }
+                            state.sync(info, data.len());
+                            let started = post.start();
+                            let result = unsafe { state.process(data, |x| x) };
+                            if let Err(e) = &result {
+                                eprintln!("process32 error: {e}");
+                            }
+                            post.run(started, result.is_ok(), data);
+                        },
+                        err_fn,
+                        None,
+                    )?
+                }
+            }
+        }
         other => {
             return Err(format!("unsupported sample format: {other:?}").into());
         }
     };
 
-    unsafe {
-        runtime
-            .set_processing(true)
-            .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
+    if let Some(live) = &live_input {
+        let stats = live.stats.clone();
+        std::thread::spawn(move || {
+            let mut last = String::new();
+            loop {
+                std::thread::sleep(std::time::Duration::from_secs(1));
+                let line = stats.line();
+                if line != last {
+                    eprintln!("{line}");
+                    last = line;
+                }
+            }
+        });
     }
 
-    stream.play()?;
-    println!("stream started. Press Enter to stop...");
-    let mut line = String::new();
-    let _ = std::io::stdin().read_line(&mut line);
+    if args.watch {
+        watch::spawn(bin.clone(), line_tx.clone());
+        println!("watching {}", bin.display());
+    }
+    // Console lines arrive as Some; Ctrl-C and the end of stdin as None.
+    {
+        let stop = stop.clone();
+        let line_tx = line_tx.clone();
+        ctrlc::set_handler(move || {
+            stop.store(true, Ordering::Release);
+            let _ = line_tx.send(None);
+        })?;
+    }
+    let eof_stops = args.duration.is_none();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+            if line_tx.send(Some(line)).is_err() {
+                return;
+            }
+        }
+        if eof_stops {
+            let _ = line_tx.send(None);
+        }
+    });
 
-    unsafe {
-        if let Err(e) = runtime.set_processing(false) {
-            eprintln!("set_processing(false) error: {e}");
+    let mut playing = unsafe { runtime.start(stream, live_input)? };
+    // This thread turns console lines into parameter edits for the audio callback.
+    let console_priority = host::promote_current_thread(host::Profile::High);
+    if !console_priority.is_promoted() {
+        eprintln!("note: console at normal priority; {console_priority}");
+    }
+    println!("stream started. Type help for commands; Enter or Ctrl-C stops.");
+    if let Some(duration) = args.duration {
+        println!("running for {:.1} s", duration.as_secs_f64());
+    }
+    #[cfg(feature = "editor")]
+    let windowed = editor
+        .map(|editor| {
+            if let Err(e) = editor.run(&class_name, &mut console, &line_rx) {
+                eprintln!("editor: {e}");
+            }
+        })
+        .is_some();
+    #[cfg(not(feature = "editor"))]
+    let windowed = false;
+    if !windowed {
+        while let Ok(Some(line)) = line_rx.recv() {
+            if line.trim() == watch::RELOAD {
+                let reloaded = watch::load_copy(&bin).and_then(|mut fresh| {
+                    unsafe {
+                        playing.reload(&mut fresh, &reload_spec)?;
+                        console.reconnect(playing.ptr().cast());
+                    }
+                    if let (Some(automation), Ok(controller)) = (&automation, console.controller())
+                    {
+                        automation.attach(controller)?;
+                    }
+                    Ok(fresh)
+                });
+                match reloaded {
+                    Ok(fresh) => {
+                        // Unloaded only now that its instance and controller are gone.
+                        drop(std::mem::replace(&mut module, fresh));
+                        println!("reloaded {}", bin.display());
+                    }
+                    Err(e) => eprintln!("reload failed, keeping the running instance: {e}"),
+                }
+                continue;
+            }
+            if console.handle(&line) == control::Flow::Stop {
+                break;
+            }
         }
-        if let Err(e) = runtime.terminate() {
-            eprintln!("terminate error: {e}");
+    }
+    // Ctrl-C has set `stop` already and does not wait.
+    if let (Some(drain), false) = (&drain, stop.load(Ordering::Acquire)) {
+        playing.silence_input();
+        let tail = host::Tail::from_samples(unsafe { (*playing.ptr()).get_tail_samples() });
+        drain.wait(tail);
+    }
+    stop.store(true, Ordering::Release);
+    drop(playing);
+    drop(console);
+
+    if let Some(recording) = recording {
+        let dropped = recording.dropped_blocks.load(Ordering::Relaxed);
+        match recording.finish(channels) {
+            Ok(frames) => println!("recorded {frames} frames ({dropped} blocks dropped)"),
+            Err(e) => eprintln!("record error: {e}"),
         }
     }
 
-    drop(stream);
+    if let Err(e) = unsafe { runtime.terminate() } {
+        eprintln!("terminate error: {e}");
+    }
+    if let Some(automation) = automation {
+        // The plugin may hold the handler until it is released.
+        drop(runtime);
+        if let Err(e) = automation.finish() {
+            eprintln!("record-automation error: {e}");
+        }
+    }
+    #[cfg(feature = "midi")]
+    if let Some(live) = live_midi {
+        let dropped = live.dropped.load(Ordering::Relaxed);
+        if dropped > 0 {
+            eprintln!("midi: {dropped} messages dropped (queue full)");
+        }
+    }
+    #[cfg(feature = "midi")]
+    if let Some(live) = live_midi_out {
+        let dropped = live.dropped.load(Ordering::Relaxed);
+        if dropped > 0 {
+            eprintln!("midi out: {dropped} events dropped (queue full or port refused)");
+        }
+    }
+    let (events, points) = (
+        block_io_counters.dropped_events(),
+        block_io_counters.dropped_points(),
+    );
+    if events + points > 0 {
+        eprintln!("{events} events and {points} parameter points dropped (block lists full)");
+    }
+    if let Some(soak) = soak {
+        soak.finish(args.stats_json)?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,303 @@
+//! Live MIDI: a midir input port feeding the plugin's event bus 0, and an output port
+//! the plugin's output events go to.
+//!
+//! The midir callback stamps each message with its arrival time and pushes it into a
+//! [`host::ring`]; the audio callback drains the ring once per block through a
+//! [`MidiConverter`] into the block's [`EventList`] and [`ParameterChanges`].
+//! Messages that arrived during the previous block's span are placed at the matching
+//! sample offset. That span comes from the stream's callback timestamps through a
+//! [`ClockSync`], not from when the audio thread happened to wake up, so the placement
+//! jitters by a sample or two rather than by the wake-up lateness. When a list is full
+//! the remaining messages stay queued for the next block (at offset 0) instead of
+//! being dropped, so note-offs always reach the plugin.
+//!
+//! midir's input thread runs at normal priority; the callback promotes it to
+//! [`host::Profile::Realtime`] when the first message arrives, so arrival stamps don't
+//! wait behind whatever else the machine is doing.
+//!
+//! Output goes the other way: the audio callback stamps each output event with when
+//! its sample offset will play and pushes it into a second ring; a forwarding thread
+//! converts it with [`host::events_to_midi`] and sends it on time. Sysex data events
+//! are not forwarded, as their bytes are only valid during the `process` call.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput};
+use openvst3_host as host;
+use openvst3_host::midi::Converted;
+use openvst3_host::prelude::*;
+use openvst3_host::ring::{Consumer, Producer};
+use openvst3_host::{ChannelPolicy, ClockSync, EventList, MidiConverter, ParameterChanges};
+
+/// Messages queued between the MIDI thread and the audio callback.
+const QUEUE: usize = 1024;
+
+#[derive(Clone, Copy, Default)]
+struct Stamped {
+    /// Arrival time in nanoseconds since the feed's epoch.
+    at: u64,
+    len: u8,
+    bytes: [u8; 3],
+}
+
+/// Audio-callback end of the MIDI bridge.
+pub struct MidiFeed {
+    ring: Consumer<Stamped>,
+    converter: MidiConverter,
+    epoch: Instant,
+    sync: ClockSync,
+    /// The stream time `sync` counts from; reset when the stream's clock goes back.
+    stream_origin: Option<cpal::StreamInstant>,
+}
+
+impl MidiFeed {
+    /// Relate the callback about to process `frames` frames to the arrival clock.
+    pub fn sync(&mut self, info: &cpal::OutputCallbackInfo, frames: usize) {
+        let host = self.epoch.elapsed().as_nanos() as u64;
+        let callback = info.timestamp().callback;
+        let origin = *self.stream_origin.get_or_insert(callback);
+        let stream = match callback.duration_since(&origin) {
+            Some(elapsed) => elapsed,
+            // A restarted stream: `sync` sees its clock start over and resyncs.
+            None => {
+                self.stream_origin = Some(callback);
+                std::time::Duration::ZERO
+            }
+        };
+        self.sync.update(host, stream.as_nanos() as u64, frames);
+    }
+
+    /// Convert everything queued so far into the lists for a block of `frames` frames.
+    pub fn fill(&mut self, frames: usize, events: &mut EventList, params: &mut ParameterChanges) {
+        while let Some(msg) = self.ring.peek() {
+            let offset = self.sync.offset(msg.at, frames);
+            let bytes = &msg.bytes[..msg.len as usize];
+            if self.converter.convert(bytes, offset, events, params) == Converted::Full {
+                break;
+            }
+            self.ring.skip(1);
+        }
+    }
+}
+
+/// The open port; keep it alive while the audio callback reads the feed.
+pub struct LiveMidi {
+    _connection: MidiInputConnection<()>,
+    pub port_name: String,
+    /// Controller assignments found through the plugin's `IMidiMapping`.
+    pub mapped: usize,
+    /// Messages lost because the queue was full.
+    pub dropped: Arc<AtomicU64>,
+}
+
+fn midi_input() -> Result<MidiInput, String> {
+    let mut input = MidiInput::new("openvst3 realtime-host-cli").map_err(|e| e.to_string())?;
+    // Only channel voice messages reach the plugin.
+    input.ignore(Ignore::All);
+    Ok(input)
+}
+
+/// Names of the MIDI input ports, in midir's order.
+pub fn port_names() -> Result<Vec<String>, String> {
+    let input = midi_input()?;
+    Ok(input
+        .ports()
+        .iter()
+        .map(|p| input.port_name(p).unwrap_or_default())
+        .collect())
+}
+
+/// The port in `names` named `name`, or the only one containing it.
+fn find_port(names: &[String], name: &str, direction: &str) -> Result<usize, String> {
+    let index = names.iter().position(|n| n == name).or_else(|| {
+        let mut partial = names.iter().enumerate().filter(|(_, n)| n.contains(name));
+        match (partial.next(), partial.next()) {
+            (Some((i, _)), None) => Some(i),
+            _ => None,
+        }
+    });
+    index.ok_or_else(|| {
+        format!(
+            "no MIDI {direction} port named {name:?}; available: {}",
+            names.join(", ")
+        )
+    })
+}
+
+/// Connect to the port named `name` (or the only port containing it) and learn the
+/// controller mapping of `controller`, the object that may implement `IMidiMapping`.
+pub unsafe fn open(
+    name: &str,
+    controller: *mut FUnknown,
+    sample_rate: f64,
+) -> Result<(LiveMidi, MidiFeed), Box<dyn std::error::Error>> {
+    let input = midi_input()?;
+    let ports = input.ports();
+    let names: Vec<String> = ports
+        .iter()
+        .map(|p| input.port_name(p).unwrap_or_default())
+        .collect();
+    let index = find_port(&names, name, "input")?;
+
+    let mut converter = MidiConverter::new();
+    let mapped = converter.learn(controller);
+
+    let epoch = Instant::now();
+    let (mut producer, consumer): (Producer<Stamped>, _) = host::ring::ring(QUEUE);
+    let dropped = Arc::new(AtomicU64::new(0));
+    let lost = dropped.clone();
+    let mut promoted = false;
+    let connection = input
+        .connect(
+            &ports[index],
+            "plugin input",
+            move |_, message, _| {
+                // Sysex and other long messages are not channel voice messages.
+                if message.is_empty() || message.len() > 3 {
+                    return;
+                }
+                let mut msg = Stamped {
+                    at: epoch.elapsed().as_nanos() as u64,
+                    len: message.len() as u8,
+                    ..Stamped::default()
+                };
+                msg.bytes[..message.len()].copy_from_slice(message);
+                if !producer.push(msg) {
+                    lost.fetch_add(1, Ordering::Relaxed);
+                }
+                if !promoted {
+                    promoted = true;
+                    let achieved = host::promote_current_thread(host::Profile::Realtime);
+                    if !achieved.is_promoted() {
+                        eprintln!("note: MIDI input at normal priority; {achieved}");
+                    }
+                }
+            },
+            (),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let feed = MidiFeed {
+        ring: consumer,
+        converter,
+        epoch,
+        sync: ClockSync::new(sample_rate),
+        stream_origin: None,
+    };
+    let live = LiveMidi {
+        _connection: connection,
+        port_name: names[index].clone(),
+        mapped,
+        dropped,
+    };
+    Ok((live, feed))
+}
+
+/// Output events queued between the audio callback and the forwarding thread.
+const OUT_QUEUE: usize = 1024;
+
+/// How often the forwarding thread looks for events when there are none.
+const OUT_POLL: Duration = Duration::from_millis(1);
+
+#[derive(Clone, Copy, Default)]
+struct Timed {
+    /// When the event plays, in nanoseconds since the feed's epoch.
+    at: u64,
+    event: Event,
+}
+
+/// Audio-callback end of the MIDI output bridge.
+pub struct MidiOutFeed {
+    ring: Producer<Timed>,
+    epoch: Instant,
+    sample_rate: f64,
+    lost: Arc<AtomicU64>,
+}
+
+impl MidiOutFeed {
+    /// Queue the plugin's output events of the block just processed, which plays from
+    /// about now on.
+    pub fn forward(&mut self, events: &EventList) {
+        let now = self.epoch.elapsed().as_nanos() as u64;
+        for event in events.events() {
+            if event.type_ == event_consts::DATA {
+                continue;
+            }
+            let offset = event.sample_offset.max(0) as f64 / self.sample_rate;
+            let timed = Timed {
+                at: now + (offset * 1e9) as u64,
+                event: *event,
+            };
+            if !self.ring.push(timed) {
+                self.lost.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// The open output port; its forwarding thread runs until the process exits.
+pub struct LiveMidiOut {
+    pub port_name: String,
+    /// Events lost because the queue was full, or messages the port refused.
+    pub dropped: Arc<AtomicU64>,
+}
+
+/// Connect to the output port named `name` (or the only port containing it) and start
+/// forwarding what the returned feed queues.
+pub fn open_output(
+    name: &str,
+    sample_rate: f64,
+) -> Result<(LiveMidiOut, MidiOutFeed), Box<dyn std::error::Error>> {
+    let output = MidiOutput::new("openvst3 realtime-host-cli").map_err(|e| e.to_string())?;
+    let ports = output.ports();
+    let names: Vec<String> = ports
+        .iter()
+        .map(|p| output.port_name(p).unwrap_or_default())
+        .collect();
+    let index = find_port(&names, name, "output")?;
+    let mut connection = output
+        .connect(&ports[index], "plugin output")
+        .map_err(|e| e.to_string())?;
+
+    let epoch = Instant::now();
+    let (producer, mut consumer): (_, Consumer<Timed>) = host::ring::ring(OUT_QUEUE);
+    let dropped = Arc::new(AtomicU64::new(0));
+    let refused = dropped.clone();
+    std::thread::spawn(move || {
+        let achieved = host::promote_current_thread(host::Profile::Realtime);
+        if !achieved.is_promoted() {
+            eprintln!("note: MIDI output at normal priority; {achieved}");
+        }
+        loop {
+            let Some(timed) = consumer.pop() else {
+                std::thread::sleep(OUT_POLL);
+                continue;
+            };
+            let due = epoch + Duration::from_nanos(timed.at);
+            if let Some(wait) = due.checked_duration_since(Instant::now()) {
+                std::thread::sleep(wait);
+            }
+            // No data events are queued, so nothing here points into the plugin.
+            let messages = unsafe { host::events_to_midi(&[timed.event], ChannelPolicy::Keep) };
+            for (_, message) in messages {
+                if connection.send(message.as_bytes()).is_err() {
+                    refused.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    });
+
+    let feed = MidiOutFeed {
+        ring: producer,
+        epoch,
+        sample_rate,
+        lost: dropped.clone(),
+    };
+    let live = LiveMidiOut {
+        port_name: names[index].clone(),
+        dropped,
+    };
+    Ok((live, feed))
+}
@@ -0,0 +1,110 @@
+//! `--record`: tee the processed output into a 32-bit float WAV file.
+//!
+//! The audio callback copies each block into a [`host::ring`] if the whole block fits
+//! and otherwise drops it and counts the overrun; it never waits for the disk. A
+//! writer thread drains the ring into hound and finalizes the file when the
+//! recording is finished.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use openvst3_host as host;
+use openvst3_host::ring::Producer;
+
+/// Seconds of audio the ring can hold while the disk catches up.
+const BUFFER_SECONDS: usize = 2;
+
+/// Audio-callback end of the recording.
+pub struct RecordTap {
+    ring: Producer<f32>,
+    dropped_blocks: Arc<AtomicU64>,
+}
+
+impl RecordTap {
+    /// Queue one interleaved block, or drop all of it if the writer is behind.
+    pub fn write<T: Copy + Into<f64>>(&mut self, data: &[T]) {
+        if self.ring.free() < data.len() {
+            self.dropped_blocks.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        for &sample in data {
+            self.ring.push(sample.into() as f32);
+        }
+    }
+}
+
+/// The writer thread; [`Recording::finish`] flushes and finalizes the file.
+pub struct Recording {
+    done: Arc<AtomicBool>,
+    writer: JoinHandle<Result<u64, hound::Error>>,
+    /// Blocks lost because the writer fell behind.
+    pub dropped_blocks: Arc<AtomicU64>,
+}
+
+impl Recording {
+    /// Stop the writer once everything queued is on disk. Call after the stream
+    /// is gone. Returns the number of frames written.
+    pub fn finish(self, channels: usize) -> Result<u64, String> {
+        self.done.store(true, Ordering::Release);
+        let samples = self
+            .writer
+            .join()
+            .map_err(|_| "record writer panicked".to_string())?
+            .map_err(|e| e.to_string())?;
+        Ok(samples / channels.max(1) as u64)
+    }
+}
+
+/// Create `path` and start the writer thread.
+pub fn start(
+    path: &Path,
+    channels: usize,
+    sample_rate: u32,
+) -> Result<(Recording, RecordTap), hound::Error> {
+    let spec = hound::WavSpec {
+        channels: channels as u16,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut wav = hound::WavWriter::create(path, spec)?;
+    let capacity = BUFFER_SECONDS * sample_rate as usize * channels;
+    let (producer, mut consumer) = host::ring::ring(capacity);
+    let done = Arc::new(AtomicBool::new(false));
+    let finished = done.clone();
+    let writer = std::thread::spawn(move || {
+        let mut buf = vec![0.0f32; 4096 * channels.max(1)];
+        let mut written = 0u64;
+        loop {
+            // Read the flag first so nothing queued before it was set is missed.
+            let last = finished.load(Ordering::Acquire);
+            let n = consumer.pop_slice(&mut buf);
+            for &sample in &buf[..n] {
+                wav.write_sample(sample)?;
+            }
+            written += n as u64;
+            if n == 0 {
+                if last {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+        wav.finalize()?;
+        Ok(written)
+    });
+    let dropped_blocks = Arc::new(AtomicU64::new(0));
+    let tap = RecordTap {
+        ring: producer,
+        dropped_blocks: dropped_blocks.clone(),
+    };
+    let recording = Recording {
+        done,
+        writer,
+        dropped_blocks,
+    };
+    Ok((recording, tap))
+}
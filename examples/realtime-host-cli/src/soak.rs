@@ -0,0 +1,209 @@
+//! `--duration` and `--exit-on-error`: unattended soak runs.
+//!
+//! The audio callback counts callbacks and failed process calls and scans every
+//! [`CHECK_EVERY`]th output block with [`analyze_block`], before the limiter could hide
+//! a NaN. A watchdog thread ends the run the same way Ctrl-C does, when the duration is
+//! up or, with `--exit-on-error`, once a process call failed or non-finite output was
+//! seen often enough. The totals are printed when the run ends.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use openvst3_host::analyze_block;
+use openvst3_host::limiter::Sample;
+use serde::Serialize;
+
+/// Output blocks per NaN scan; bounds the cost of checking to a fraction of a pass.
+pub const CHECK_EVERY: u64 = 8;
+
+/// How often the watchdog looks at the counters.
+const POLL: Duration = Duration::from_millis(50);
+
+/// `10s`, `500ms` or plain seconds (`2.5`).
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (num, scale) = if let Some(ms) = s.strip_suffix("ms") {
+        (ms, 1e-3)
+    } else if let Some(secs) = s.strip_suffix('s') {
+        (secs, 1.0)
+    } else {
+        (s, 1.0)
+    };
+    match num.trim().parse::<f64>() {
+        Ok(v) if v.is_finite() && v >= 0.0 => Ok(Duration::from_secs_f64(v * scale)),
+        _ => Err(format!("expected a duration like 600s or 500ms, got {s:?}")),
+    }
+}
+
+/// The run failed under `--exit-on-error`; exits with the plugin-fault code.
+#[derive(Debug)]
+pub struct Failed(pub String);
+
+impl fmt::Display for Failed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "soak test failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for Failed {}
+
+/// Totals shared by the callback, the stream error callback and the watchdog.
+#[derive(Default)]
+pub struct Health {
+    callbacks: AtomicU64,
+    process_errors: AtomicU64,
+    checked_blocks: AtomicU64,
+    non_finite_blocks: AtomicU64,
+    stream_errors: AtomicU64,
+}
+
+impl Health {
+    /// Count an error reported by the audio backend.
+    pub fn stream_error(&self) {
+        self.stream_errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Audio-callback end of the soak checks.
+pub struct Watch {
+    health: Arc<Health>,
+    block: u64,
+}
+
+impl Watch {
+    /// Record one callback: whether the plugin's process call succeeded, and the
+    /// interleaved output it wrote.
+    pub fn record<T: Sample>(&mut self, processed: bool, data: &[T]) {
+        let health = &self.health;
+        health.callbacks.fetch_add(1, Ordering::Relaxed);
+        if !processed {
+            health.process_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        if self.block.is_multiple_of(CHECK_EVERY) {
+            health.checked_blocks.fetch_add(1, Ordering::Relaxed);
+            if !analyze_block(data).is_finite() {
+                health.non_finite_blocks.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.block += 1;
+    }
+}
+
+/// When a run should end.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub duration: Option<Duration>,
+    /// `--exit-on-error N`: fail on any process error or after N non-finite blocks.
+    pub max_non_finite: Option<u64>,
+}
+
+/// Main-thread end: the counters and the watchdog's verdict.
+pub struct Soak {
+    health: Arc<Health>,
+    failure: Arc<Mutex<Option<String>>>,
+    started: Instant,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Summary {
+    seconds: f64,
+    callbacks: u64,
+    process_errors: u64,
+    checked_blocks: u64,
+    non_finite_blocks: u64,
+    stream_errors: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    failure: Option<String>,
+}
+
+/// Start the watchdog, which sends `None` on `stop` (like Ctrl-C) when the run should
+/// end, and return the callback's watch.
+pub fn start(limits: Limits, stop: mpsc::Sender<Option<String>>) -> (Watch, Soak) {
+    let health = Arc::new(Health::default());
+    let failure = Arc::new(Mutex::new(None));
+    let started = Instant::now();
+    {
+        let health = health.clone();
+        let failure = failure.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(POLL);
+            if let Some(limit) = limits.max_non_finite {
+                let errors = health.process_errors.load(Ordering::Relaxed);
+                let non_finite = health.non_finite_blocks.load(Ordering::Relaxed);
+                let reason = if errors > 0 {
+                    Some(format!("{errors} process call(s) failed"))
+                } else if non_finite >= limit {
+                    Some(format!(
+                        "non-finite output in {non_finite} checked block(s)"
+                    ))
+                } else {
+                    None
+                };
+                if reason.is_some() {
+                    *failure.lock().unwrap() = reason;
+                    let _ = stop.send(None);
+                    return;
+                }
+            }
+            if limits.duration.is_some_and(|d| started.elapsed() >= d) {
+                let _ = stop.send(None);
+                return;
+            }
+        });
+    }
+    let watch = Watch {
+        health: health.clone(),
+        block: 0,
+    };
+    (
+        watch,
+        Soak {
+            health,
+            failure,
+            started,
+        },
+    )
+}
+
+impl Soak {
+    pub fn health(&self) -> &Arc<Health> {
+        &self.health
+    }
+
+    /// Print the totals (as one JSON object with `json`) and report a failure.
+    pub fn finish(self, json: bool) -> Result<(), Failed> {
+        let load = |c: &AtomicU64| c.load(Ordering::Relaxed);
+        let summary = Summary {
+            seconds: self.started.elapsed().as_secs_f64(),
+            callbacks: load(&self.health.callbacks),
+            process_errors: load(&self.health.process_errors),
+            checked_blocks: load(&self.health.checked_blocks),
+            non_finite_blocks: load(&self.health.non_finite_blocks),
+            stream_errors: load(&self.health.stream_errors),
+            failure: self.failure.lock().unwrap().take(),
+        };
+        if json {
+            println!(
+                "{}",
+                serde_json::to_string(&summary).expect("summary serialize")
+            );
+        } else {
+            println!(
+                "summary: {:.1} s | {} callbacks | {} process errors | non-finite output in {} \
+                 of {} checked blocks | {} stream errors",
+                summary.seconds,
+                summary.callbacks,
+                summary.process_errors,
+                summary.non_finite_blocks,
+                summary.checked_blocks,
+                summary.stream_errors
+            );
+        }
+        match summary.failure {
+            Some(reason) => Err(Failed(reason)),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! `--stats`: callback timing, DSP load, overruns and output levels.
+//!
+//! The audio callback only touches a [`host::Meter`], atomics, a
+//! [`host::TimingCollector`] and a [`host::ring`] of per-callback timings. A reporter thread drains both once per second and prints a summary of
+//! that interval, as text or as one JSON object per line.
+
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use openvst3_host as host;
+use openvst3_host::ring::{Consumer, Producer};
+use serde::Serialize;
+
+/// Timings buffered between reports; more than a second of 16-frame blocks at 48 kHz.
+const TIMINGS: usize = 4096;
+
+#[derive(Clone, Copy, Default)]
+struct Timing {
+    elapsed_ns: u32,
+    budget_ns: u32,
+}
+
+/// Counters shared by the audio callback, the stream error callback and the reporter.
+pub struct Shared {
+    /// Callback count, overruns and the longest callback, against the audio's duration.
+    timing: host::TimingCollector,
+    stream_errors: AtomicU64,
+    /// Per-channel absolute peak, as `f32` bits (non-negative floats order like ints).
+    peaks: Box<[AtomicU32]>,
+    /// Per-channel RMS over the meter's window at the last callback, as `f32` bits.
+    rms: Box<[AtomicU32]>,
+    /// Momentary loudness in LUFS at the last callback, as `f32` bits.
+    momentary: AtomicU32,
+    /// The `--record` overrun total, when recording.
+    record_dropped: Option<Arc<AtomicU64>>,
+}
+
+impl Shared {
+    /// Count an error reported by the audio backend (usually an underrun).
+    pub fn stream_error(&self) {
+        self.stream_errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Audio-callback end of the statistics.
+pub struct Probe {
+    ring: Producer<Timing>,
+    shared: Arc<Shared>,
+    channels: usize,
+    meter: host::Meter,
+}
+
+impl Probe {
+    /// Read the clock at the start of a callback, for [`record`](Self::record).
+    pub fn start(&self) -> u64 {
+        self.shared.timing.start()
+    }
+
+    /// Record a callback that started at `started` and wrote the interleaved `data`.
+    pub fn record<T: host::limiter::Sample>(&mut self, started: u64, data: &[T]) {
+        let frames = data.len() / self.channels.max(1);
+        let elapsed = self.shared.timing.finish(started, frames);
+        let budget = self.shared.timing.budget().nanos(frames);
+        // A full ring only loses percentile samples; the counters stay exact.
+        self.ring.push(Timing {
+            elapsed_ns: elapsed.min(u32::MAX as u64) as u32,
+            budget_ns: budget.min(u32::MAX as u64) as u32,
+        });
+        self.meter.process_interleaved(data);
+        for (ch, (peak, rms)) in self.shared.peaks.iter().zip(&*self.shared.rms).enumerate() {
+            peak.fetch_max(
+                (self.meter.max_peak(ch) as f32).to_bits(),
+                Ordering::Relaxed,
+            );
+            rms.store((self.meter.rms(ch) as f32).to_bits(), Ordering::Relaxed);
+        }
+        if let Some(lufs) = self.meter.momentary_lufs() {
+            self.shared
+                .momentary
+                .store((lufs as f32).to_bits(), Ordering::Relaxed);
+        }
+        self.meter.reset_max();
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Interval {
+    callbacks: u64,
+    p50_ms: f64,
+    p95_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+    /// Processing time over the time the processed audio lasts.
+    load: f64,
+    over_budget: u64,
+    stream_errors: u64,
+    /// `None` for a silent channel.
+    peak_dbfs: Vec<Option<f64>>,
+    /// RMS over the last 300 ms at the end of the interval; `None` for silence.
+    rms_dbfs: Vec<Option<f64>>,
+    /// Momentary (400 ms, K-weighted) loudness at the end of the interval; `None` for
+    /// silence.
+    momentary_lufs: Option<f64>,
+    /// Blocks `--record` had to drop; absent when not recording.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    record_dropped_blocks: Option<u64>,
+}
+
+impl Interval {
+    fn take(
+        ring: &mut Consumer<Timing>,
+        shared: &Shared,
+        timings: &mut Vec<Timing>,
+        seen: &mut host::TimingSnapshot,
+        record_seen: &mut u64,
+    ) -> Self {
+        timings.clear();
+        while let Some(t) = ring.pop() {
+            timings.push(t);
+        }
+        let (elapsed, budget) = timings.iter().fold((0u64, 0u64), |(e, b), t| {
+            (e + t.elapsed_ns as u64, b + t.budget_ns as u64)
+        });
+        let mut ns: Vec<u32> = timings.iter().map(|t| t.elapsed_ns).collect();
+        ns.sort_unstable();
+        let pct = |p: f64| match ns.len() {
+            0 => 0.0,
+            n => ns[((n - 1) as f64 * p).round() as usize] as f64 / 1e6,
+        };
+        let now = shared.timing.snapshot();
+        let (callbacks, over_budget) = (now.calls - seen.calls, now.overruns - seen.overruns);
+        *seen = now;
+        Interval {
+            callbacks,
+            p50_ms: pct(0.50),
+            p95_ms: pct(0.95),
+            p99_ms: pct(0.99),
+            // Exact even when the ring overflowed.
+            max_ms: shared.timing.take_max().as_secs_f64() * 1e3,
+            load: if budget > 0 {
+                elapsed as f64 / budget as f64
+            } else {
+                0.0
+            },
+            over_budget,
+            stream_errors: shared.stream_errors.swap(0, Ordering::Relaxed),
+            peak_dbfs: shared
+                .peaks
+                .iter()
+                .map(|p| db(f32::from_bits(p.swap(0, Ordering::Relaxed))))
+                .collect(),
+            rms_dbfs: shared
+                .rms
+                .iter()
+                .map(|r| db(f32::from_bits(r.load(Ordering::Relaxed))))
+                .collect(),
+            momentary_lufs: Some(f32::from_bits(shared.momentary.load(Ordering::Relaxed)) as f64)
+                .filter(|lufs| lufs.is_finite()),
+            record_dropped_blocks: shared.record_dropped.as_ref().map(|total| {
+                let total = total.load(Ordering::Relaxed);
+                let new = total - *record_seen;
+                *record_seen = total;
+                new
+            }),
+        }
+    }
+
+    fn line(&self) -> String {
+        let levels = |dbs: &[Option<f64>]| {
+            dbs.iter()
+                .map(|p| p.map_or("-inf".into(), |db| format!("{db:.1}")))
+                .collect::<Vec<String>>()
+                .join(" ")
+        };
+        let lufs = self
+            .momentary_lufs
+            .map_or("-inf".into(), |lufs| format!("{lufs:.1}"));
+        let record = self
+            .record_dropped_blocks
+            .map_or(String::new(), |n| format!(" | record dropped {n} blocks"));
+        format!(
+            "stats: {} callbacks | p50 {:.3} ms p95 {:.3} ms p99 {:.3} ms max {:.3} ms | \
+             load {:.1}% | over budget {} | stream errors {} | peak dBFS {} | \
+             rms dBFS {} | {lufs} LUFS-M{record}",
+            self.callbacks,
+            self.p50_ms,
+            self.p95_ms,
+            self.p99_ms,
+            self.max_ms,
+            self.load * 100.0,
+            self.over_budget,
+            self.stream_errors,
+            levels(&self.peak_dbfs),
+            levels(&self.rms_dbfs),
+        )
+    }
+}
+
+/// A level in dBFS, `None` for silence.
+fn db(linear: f32) -> Option<f64> {
+    (linear > 0.0).then(|| host::meter::to_db(linear as f64))
+}
+
+/// Start the reporter thread and return the callback's probe plus the shared
+/// counters for the stream error callback. `record_dropped` is the `--record`
+/// overrun counter, if recording.
+pub fn start(
+    channels: usize,
+    sample_rate: f64,
+    json: bool,
+    record_dropped: Option<Arc<AtomicU64>>,
+) -> (Probe, Arc<Shared>) {
+    let (producer, mut consumer) = host::ring::ring(TIMINGS);
+    let shared = Arc::new(Shared {
+        timing: host::TimingCollector::new(host::TimingBudget::Realtime { sample_rate }),
+        stream_errors: AtomicU64::new(0),
+        peaks: (0..channels).map(|_| AtomicU32::new(0)).collect(),
+        rms: (0..channels).map(|_| AtomicU32::new(0)).collect(),
+        momentary: AtomicU32::new(f32::NEG_INFINITY.to_bits()),
+        record_dropped,
+    });
+    let reporter = shared.clone();
+    std::thread::spawn(move || {
+        let mut timings = Vec::with_capacity(TIMINGS);
+        let mut seen = host::TimingSnapshot::default();
+        let mut record_seen = 0;
+        loop {
+            std::thread::sleep(Duration::from_secs(1));
+            let interval = Interval::take(
+                &mut consumer,
+                &reporter,
+                &mut timings,
+                &mut seen,
+                &mut record_seen,
+            );
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string(&interval).expect("stats serialize")
+                );
+            } else {
+                println!("{}", interval.line());
+            }
+        }
+    });
+    let probe = Probe {
+        ring: producer,
+        shared: shared.clone(),
+        channels,
+        meter: host::Meter::new(channels, sample_rate, &host::MeterConfig::default()),
+    };
+    (probe, shared)
+}
@@ -0,0 +1,144 @@
+//! Tests for the sample conversion helpers, the output layout, the reload watcher,
+//! `--silence-stop` and `--record-automation`.
+
+use std::time::{Duration, SystemTime};
+
+use openvst3_host as host;
+
+use crate::automation::Automation;
+use crate::convert::Converter;
+use crate::drain::{self, MAX_TAIL};
+use crate::watch::MtimeWatch;
+use crate::{device_output, interleave, Layout, OutputMap};
+
+#[test]
+fn float_to_i16_scales_and_saturates() {
+    let mut convert = Converter::new(false);
+    let cases: [(f32, i16); 10] = [
+        (0.0, 0),
+        (0.5, 16_384),
+        (-0.5, -16_384),
+        (1.0 / 32_768.0, 1),
+        (0.999_97, 32_767),
+        (-1.0, -32_768),
+        // Full scale and beyond clip instead of wrapping.
+        (1.0, 32_767),
+        (1.5, 32_767),
+        (-3.0, -32_768),
+        (f32::NAN, 0),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(convert.sample::<i16>(input), expected, "input {input}");
+    }
+    assert_eq!(convert.sample::<u16>(0.0), 32_768);
+    assert_eq!(convert.sample::<u16>(-1.0), 0);
+    assert_eq!(convert.sample::<u16>(2.0), u16::MAX);
+    assert_eq!(convert.sample::<i32>(-1.0), i32::MIN);
+    assert_eq!(convert.sample::<i32>(1.0), i32::MAX);
+}
+
+#[test]
+fn interleave_converts_each_sample_once() {
+    let planar = vec![vec![0.5f32, -0.5, 9.0], vec![1.0, 2.0, 9.0]];
+    let mut convert = Converter::new(false);
+    let mut out = [0i16; 4];
+    interleave(&planar, &mut out, |x| convert.sample(x));
+    assert_eq!(out, [16_384, 32_767, -16_384, 32_767]);
+}
+
+#[test]
+fn stereo_plugin_output_is_mapped_to_a_mono_device() {
+    let stereo = openvst3_host::prelude::speaker_arr::STEREO;
+    assert!(Layout::new(None, stereo, 2).out_map.is_none());
+    let layout = Layout::new(None, stereo, 1);
+    assert_eq!((layout.inputs, layout.outputs), (2, 2));
+
+    let mut bound = openvst3_host::BoundProcessData::<f32>::new(0, 2, 4);
+    for (chan, level) in bound.outputs_mut().zip([0.5, 0.25]) {
+        chan.fill(level);
+    }
+    let mut out_map = layout.out_map.map(|map| OutputMap::new(map, 4));
+    let planar = device_output(&bound, &mut out_map, 4);
+    assert_eq!(planar.len(), 1);
+    let mut device = [0.0f32; 4];
+    interleave(planar, &mut device, |x| x);
+    assert!(device
+        .iter()
+        .all(|&x| (x - 0.75 * core::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6));
+}
+
+#[test]
+fn tpdf_dither_stays_within_one_lsb() {
+    let mut convert = Converter::new(true);
+    let mut out = vec![0i16; 10_000];
+    convert.block(&vec![0.25f32; out.len()], &mut out);
+    assert!(out.iter().all(|&s| (8_191..=8_193).contains(&s)));
+    assert!(out.iter().any(|&s| s != 8_192));
+}
+
+#[test]
+fn watch_waits_for_a_stable_new_mtime() {
+    let t = |secs| Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+    let mut watch = MtimeWatch::new(t(1));
+    assert!(!watch.poll(t(1)));
+    // Still being written: the time changes between polls.
+    assert!(!watch.poll(t(2)));
+    assert!(!watch.poll(t(3)));
+    assert!(watch.poll(t(3)));
+    assert!(!watch.poll(t(3)));
+    // A binary that is briefly missing mid-build does not trigger on its own.
+    assert!(!watch.poll(None));
+    assert!(!watch.poll(None));
+    assert!(!watch.poll(t(4)));
+    assert!(watch.poll(t(4)));
+}
+
+#[test]
+fn silence_stop_waits_no_longer_than_the_reported_tail() {
+    use openvst3_host::Tail;
+    assert_eq!(drain::wait_limit(Tail::None, 48_000.0), Duration::ZERO);
+    assert_eq!(
+        drain::wait_limit(Tail::Samples(24_000), 48_000.0),
+        Duration::from_millis(500)
+    );
+    assert_eq!(drain::wait_limit(Tail::Infinite, 48_000.0), MAX_TAIL);
+    assert_eq!(
+        drain::wait_limit(Tail::Samples(u32::MAX - 1), 48_000.0),
+        MAX_TAIL
+    );
+
+    // Silence is 8 quiet blocks in a row; NaN output is never quiet.
+    let (mut listen, drain) = drain::start(-60.0, 48_000.0);
+    listen.record(&[0.5f32; 64]);
+    for _ in 0..7 {
+        listen.record(&[0.0001f32; 64]);
+    }
+    assert!(!drain.is_silent());
+    listen.record(&[0.0001f32; 64]);
+    assert!(drain.is_silent());
+    listen.record(&[0.0, f32::NAN]);
+    assert!(!drain.is_silent());
+}
+
+#[test]
+fn recorded_automation_is_written_as_session_lanes() {
+    let path =
+        std::env::temp_dir().join(format!("openvst3-automation-{}.json", std::process::id()));
+    let automation = Automation::new(path.clone(), 48_000.0);
+    let recorder = automation.recorder();
+    let mut clock = host::SampleClock::with_clock(48_000.0, automation.clock());
+    recorder.sync(&clock.next_block(64));
+    recorder.begin_edit(4);
+    recorder.perform_edit(4, 0.5);
+    recorder.end_edit(4);
+    automation.finish().unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let lanes: Vec<host::AutomationLane> = serde_json::from_str(&text).unwrap();
+    assert_eq!(lanes.len(), 1);
+    assert_eq!(lanes[0].param, 4);
+    assert_eq!(lanes[0].points.len(), 1);
+    assert_eq!(lanes[0].points[0].value, 0.5);
+    assert!(lanes[0].points[0].time >= 0.0);
+}
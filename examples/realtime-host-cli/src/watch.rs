@@ -0,0 +1,79 @@
+//! `--watch`: reload the plugin when its binary changes on disk.
+//!
+//! A thread polls the binary's modification time and, once a changed file has kept
+//! the same time for one poll (so a half-written build is not picked up), sends a
+//! `reload` console line. The main thread handles it like a typed one: it loads a
+//! private copy of the binary, since the loader would hand back the already mapped
+//! module for the same path, and swaps the processor in without stopping the stream.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, SystemTime};
+
+use openvst3_host as host;
+
+const POLL: Duration = Duration::from_millis(250);
+
+/// The console line that triggers a reload.
+pub const RELOAD: &str = "reload";
+
+/// Decides when a changed binary is ready to load.
+pub struct MtimeWatch {
+    loaded: Option<SystemTime>,
+    seen: Option<SystemTime>,
+}
+
+impl MtimeWatch {
+    /// `loaded` is the modification time of the binary already running.
+    pub fn new(loaded: Option<SystemTime>) -> Self {
+        Self {
+            loaded,
+            seen: loaded,
+        }
+    }
+
+    /// Feed one poll's modification time; true when a reload is due.
+    pub fn poll(&mut self, now: Option<SystemTime>) -> bool {
+        let due = now.is_some() && now == self.seen && now != self.loaded;
+        if due {
+            self.loaded = now;
+        }
+        self.seen = now;
+        due
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Poll `bin` for the rest of the run, sending [`RELOAD`] lines to `lines`.
+pub fn spawn(bin: PathBuf, lines: mpsc::Sender<Option<String>>) {
+    std::thread::spawn(move || {
+        let mut watch = MtimeWatch::new(modified(&bin));
+        loop {
+            std::thread::sleep(POLL);
+            if watch.poll(modified(&bin)) && lines.send(Some(RELOAD.into())).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// Load a fresh copy of `bin`. The copy is deleted once loaded where the platform
+/// allows it; on Windows it stays in the temp folder.
+pub fn load_copy(bin: &Path) -> Result<host::Module, Box<dyn std::error::Error>> {
+    static COPIES: AtomicUsize = AtomicUsize::new(0);
+    let name = bin.file_name().ok_or("plugin path has no file name")?;
+    let copy = std::env::temp_dir().join(format!(
+        "openvst3-reload-{}-{}-{}",
+        std::process::id(),
+        COPIES.fetch_add(1, Ordering::Relaxed),
+        name.to_string_lossy()
+    ));
+    std::fs::copy(bin, &copy).map_err(|e| format!("{}: {e}", copy.display()))?;
+    let module = host::Module::load(&copy);
+    let _ = std::fs::remove_file(&copy);
+    Ok(module?)
+}
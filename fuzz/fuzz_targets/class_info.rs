@@ -0,0 +1,56 @@
+//! Class info decoding from arbitrary `PClassInfo`/`PClassInfo2` buffers, as a
+//! factory could hand them over: no NUL terminators, invalid UTF-8, any flags.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use openvst3_abi::{PClassInfo, PClassInfo2};
+use openvst3_host::{decode_fixed_cstr, describe_class_flags, ClassInfo};
+
+/// Hands out the input bytes field by field, zero once they run out.
+struct Bytes<'a>(std::slice::Iter<'a, u8>);
+
+impl Bytes<'_> {
+    fn fixed<const N: usize>(&mut self) -> [i8; N] {
+        std::array::from_fn(|_| self.0.next().copied().unwrap_or(0) as i8)
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.fixed::<4>().map(|b| b as u8))
+    }
+}
+
+fn exercise(class: &ClassInfo) {
+    let _ = class.sub_category_tags().count();
+    let _ = class.parsed_sub_categories();
+    let _ = (class.is_instrument(), class.is_effect());
+    let _ = describe_class_flags(class.flags());
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (name, _) = decode_fixed_cstr(&data.iter().map(|&b| b as i8).collect::<Vec<_>>());
+    assert!(!name.contains('\0'));
+
+    let mut bytes = Bytes(data.iter());
+    let v2 = PClassInfo2 {
+        cid: bytes.fixed(),
+        cardinality: bytes.u32() as i32,
+        category: bytes.fixed(),
+        name: bytes.fixed(),
+        class_flags: bytes.u32(),
+        sub_categories: bytes.fixed(),
+        vendor: bytes.fixed(),
+        version: bytes.fixed(),
+        sdk_version: bytes.fixed(),
+    };
+    let v1 = PClassInfo {
+        cid: v2.cid,
+        cardinality: v2.cardinality,
+        category: v2.category,
+        name: v2.name,
+    };
+    let (class1, class2) = (ClassInfo::from_v1(0, &v1), ClassInfo::from_v2(0, &v2));
+    assert_eq!((&class1.name, class1.cid), (&class2.name, class2.cid));
+    exercise(&class1);
+    exercise(&class2);
+});
@@ -0,0 +1,12 @@
+//! `parse_hex_16` over arbitrary strings: parsed IDs must format back to themselves.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use openvst3_host::{fmt_cid_hex, parse_hex_16};
+
+fuzz_target!(|s: &str| {
+    if let Ok(id) = parse_hex_16(s) {
+        assert_eq!(parse_hex_16(&fmt_cid_hex(&id)).unwrap(), id);
+    }
+});
@@ -0,0 +1,16 @@
+//! `VstPreset::parse` over arbitrary files: no panics, no chunk data beyond the
+//! file's size, and whatever parses survives a write/read round trip.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use openvst3_host::VstPreset;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(preset) = VstPreset::parse(data) else {
+        return;
+    };
+    let total: usize = preset.chunks.iter().map(|(_, chunk)| chunk.len()).sum();
+    assert!(total <= data.len());
+    assert_eq!(VstPreset::parse(&preset.to_bytes()).unwrap(), preset);
+});